@@ -0,0 +1,115 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Criterion benchmarks for the execution backends.
+//!
+//! Run with `cargo bench`; results land in `target/criterion/`. Each workload
+//! is benchmarked once per backend so regressions and backend speedups show
+//! up side by side under the same benchmark group.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use grease::prelude::{Grease, InterpretResult};
+
+/// Backends that can execute a script. Only the bytecode interpreter exists
+/// today; JIT and WASM backends get a variant here once they land.
+#[derive(Clone, Copy)]
+enum Backend {
+    Interpreter,
+}
+
+impl Backend {
+    const ALL: &'static [Backend] = &[Backend::Interpreter];
+
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Interpreter => "interpreter",
+        }
+    }
+
+    /// Set up a fresh runtime. Kept out of the measured time so native
+    /// registration doesn't dominate the small workloads.
+    fn prepare(self) -> Grease {
+        match self {
+            Backend::Interpreter => Grease::new(),
+        }
+    }
+
+    fn run(self, runtime: &mut Grease, source: &str) -> InterpretResult {
+        match self {
+            Backend::Interpreter => runtime.run(source).expect("benchmark script failed to compile"),
+        }
+    }
+}
+
+const WORKLOADS: &[(&str, &str)] = &[
+    ("fib", "\
+def fib(n):
+    if n < 2:
+        return n
+    return fib(n - 1) + fib(n - 2)
+result = fib(18)
+"),
+    ("loops", "\
+total = 0
+i = 0
+while i < 20000:
+    total = total + i % 7
+    i = i + 1
+for x in [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]:
+    total = total + x
+"),
+    ("string_building", "\
+s = \"\"
+i = 0
+while i < 1000:
+    s = s + i + \",\"
+    i = i + 1
+"),
+    ("dictionary_churn", "\
+d = {}
+i = 0
+while i < 1000:
+    key = \"k\" + (i % 50)
+    d = dict_merge(d, {key: i})
+    d = dict_remove(d, \"k\" + ((i + 25) % 50))
+    i = i + 1
+"),
+    ("method_dispatch", "\
+class Counter:
+    def step(self, n):
+        return n + 1
+c = new Counter()
+n = 0
+while n < 5000:
+    n = c.step(n)
+"),
+];
+
+fn backends(c: &mut Criterion) {
+    for (name, source) in WORKLOADS {
+        let mut group = c.benchmark_group(*name);
+        for &backend in Backend::ALL {
+            // A failing script would otherwise benchmark its error path
+            let result = backend.run(&mut backend.prepare(), source);
+            assert_eq!(result, InterpretResult::Ok, "{} failed on {}", name, backend.name());
+
+            group.bench_with_input(BenchmarkId::from_parameter(backend.name()), source, |b, source| {
+                b.iter_batched(|| backend.prepare(), |mut runtime| backend.run(&mut runtime, source), BatchSize::SmallInput)
+            });
+        }
+        group.finish();
+    }
+}
+
+/// Constructing a runtime, which embedders pay before every short script.
+fn startup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("startup");
+    group.bench_function("new", |b| b.iter(Grease::new));
+    group.bench_function("new_and_eval", |b| {
+        b.iter(|| Grease::new().evaluate("1 + 2").expect("benchmark script failed to compile"))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, backends, startup);
+criterion_main!(benches);
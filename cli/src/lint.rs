@@ -0,0 +1,167 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! `grease lint`: lint, and optionally fix, many files at once.
+
+use crate::report::{self, Format, Report};
+use grease::prelude::{Grease, ProjectConfig};
+use rayon::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Options {
+    pub fix: bool,
+    pub diff: bool,
+    pub format: Format,
+    pub verbose: bool,
+}
+
+/// Lint every file `paths` name, in parallel, and print the findings
+/// grouped by file. Exits with status 1 if anything was found or a file
+/// couldn't be linted.
+pub fn run(paths: &[String], options: &Options) {
+    let files = match collect_files(paths) {
+        Ok(files) => files,
+        Err(msg) => {
+            eprintln!("Lint Error: {}", msg);
+            std::process::exit(1);
+        }
+    };
+    let reports: Vec<Report> = files.par_iter().map(|file| lint_file(file, options)).collect();
+
+    let clean = reports.iter().all(Report::is_clean);
+    match options.format {
+        Format::Text => print_text(&reports, options),
+        format => report::print(&reports, format),
+    }
+    if !clean {
+        std::process::exit(1);
+    }
+}
+
+/// Files named directly, `.grease` files under named directories, and
+/// files matching glob patterns, each once, in the order given.
+fn collect_files(paths: &[String]) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        let found = if Path::new(path).is_dir() {
+            let mut found = Vec::new();
+            grease_files_under(Path::new(path), &mut found)
+                .map_err(|e| format!("Failed to read directory '{}': {}", path, e))?;
+            found
+        } else if Path::new(path).exists() {
+            vec![PathBuf::from(path)]
+        } else {
+            let matches = glob::glob(path).map_err(|e| format!("Invalid pattern '{}': {}", path, e))?;
+            let mut found = Vec::new();
+            for entry in matches.flatten() {
+                if entry.is_dir() {
+                    grease_files_under(&entry, &mut found)
+                        .map_err(|e| format!("Failed to read directory '{}': {}", entry.display(), e))?;
+                } else {
+                    found.push(entry);
+                }
+            }
+            if found.is_empty() {
+                return Err(format!("No files match '{}'", path));
+            }
+            found
+        };
+        for file in found {
+            let file = file.display().to_string();
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn grease_files_under(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<Result<_, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            grease_files_under(&entry, files)?;
+        } else if entry.extension().is_some_and(|extension| extension == "grease") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn lint_file(file: &str, options: &Options) -> Report {
+    let mut report = Report::new(file);
+    if let Err(msg) = lint_into(&mut report, options) {
+        report.failure = Some(msg);
+    }
+    report
+}
+
+fn lint_into(report: &mut Report, options: &Options) -> Result<(), String> {
+    let file = report.file.as_str();
+    let mut source = fs::read_to_string(file).map_err(|e| format!("Error reading file '{}': {}", file, e))?;
+    let script_dir = Path::new(file).parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut grease = Grease::new().with_verbose(options.verbose).with_script_dir(script_dir);
+    if let Some(path) = ProjectConfig::discover(Path::new(file)) {
+        grease = grease.with_config(ProjectConfig::load(&path).map_err(|msg| format!("Config Error: {}", msg))?);
+    }
+
+    if options.fix || options.diff {
+        let fixed = grease.fix(&source)?;
+        if options.diff {
+            report.diff = Some(grease::fix::unified_diff(file, &source, &fixed));
+        }
+        if !options.fix {
+            return Ok(());
+        }
+        if fixed != source {
+            fs::write(file, &fixed).map_err(|e| format!("Error writing file '{}': {}", file, e))?;
+        }
+        source = fixed;
+    }
+    report.errors = grease.lint(&source)?;
+    Ok(())
+}
+
+fn print_text(reports: &[Report], options: &Options) {
+    for report in reports {
+        if let Some(diff) = &report.diff {
+            print!("{}", diff);
+        }
+        for error in &report.errors {
+            println!("{}:{}:{}: {}", report.file, error.line, error.column, error.message);
+        }
+        if let Some(failure) = &report.failure {
+            eprintln!("{}: Lint Error: {}", report.file, failure);
+        }
+    }
+    // A diff preview doesn't lint, so there's nothing to call clean
+    let linted = options.fix || !options.diff;
+    if linted && reports.iter().all(Report::is_clean) {
+        println!("No lint errors found.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_files() {
+        let dir = std::env::temp_dir().join(format!("grease_lint_{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        for file in ["b.grease", "a.grease", "notes.txt", "nested/c.grease"] {
+            fs::write(dir.join(file), "x = 1\n").unwrap();
+        }
+        let path = |file: &str| dir.join(file).display().to_string();
+
+        let files = collect_files(&[path(""), path("a.grease")]).unwrap();
+        assert_eq!(files, [path("a.grease"), path("b.grease"), path("nested/c.grease")]);
+        let files = collect_files(&[path("*.grease"), path("notes.txt")]).unwrap();
+        assert_eq!(files, [path("a.grease"), path("b.grease"), path("notes.txt")]);
+        assert!(collect_files(&[path("*.gr")]).unwrap_err().starts_with("No files match"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
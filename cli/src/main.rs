@@ -0,0 +1,536 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{generate, Shell};
+use clap_mangen::Man;
+use grease::prelude::{Grease, InterpretResult, ModuleSource, ProjectConfig, RunOutcome};
+use grease::dump;
+use grease::replay::NativeLog;
+#[cfg(not(target_os = "wasi"))]
+use grease::eval_server;
+use grease::highlight;
+#[cfg(feature = "jupyter")]
+use grease::jupyter_kernel;
+use grease::repl::REPL;
+#[cfg(feature = "lsp")]
+use grease::lsp_server::serve_stdio;
+use std::fs;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+
+mod lint;
+mod report;
+use report::Report;
+
+#[derive(Parser)]
+#[command(name = "grease")]
+#[command(version = grease::VERSION)]
+#[command(about = "A modern scripting language written in Rust")]
+#[command(long_about = "Grease is a scripting language written in pure Rust. It compiles to bytecode and runs on a custom VM.\n\nThe high-performance oil for your Rust engine.")]
+struct Args {
+    /// Execute inline code
+    #[arg(short, long)]
+    eval: Option<String>,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Run the script reproducibly: deny natives that observe other
+    /// processes and report timings as 0
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Record the native calls the script makes to a file
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<String>,
+
+    /// Serve native calls from a recording instead of making them
+    #[arg(long, value_name = "FILE")]
+    replay: Option<String>,
+
+    /// If the script fails at runtime or the interpreter panics, write a
+    /// crash report to attach to a bug report into DIR (default: the
+    /// current directory). Nothing is sent anywhere
+    #[arg(long, value_name = "DIR", num_args = 0..=1, require_equals = true, default_missing_value = ".")]
+    crash_report: Option<String>,
+
+    /// Don't run ~/.greaserc when starting the REPL
+    #[arg(long)]
+    no_rc: bool,
+
+    /// File to execute
+    file: Option<String>,
+
+    /// Arguments passed to the script (available via script_args)
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, requires = "file")]
+    script_args: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate shell completions
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Generate manpage
+    Manpage,
+    /// Print the tokens of a source file
+    Tokens {
+        /// File to tokenize
+        file: String,
+        /// Print JSON instead of one token per line
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the syntax tree of a source file
+    Parse {
+        /// File to parse
+        file: String,
+        /// Print JSON with spans instead of the debug tree
+        #[arg(long)]
+        json: bool,
+    },
+    /// Generate an editor syntax definition from the lexer's tables
+    Highlight {
+        /// Editor format to generate
+        #[arg(long, value_enum)]
+        format: HighlightFormat,
+    },
+    /// Inspect the modules scripts can `use`
+    Modules {
+        #[command(subcommand)]
+        command: ModulesCommand,
+    },
+    /// Translate a script to Rust that calls Grease's runtime shim
+    Transpile {
+        /// File to transpile
+        file: String,
+        /// Where to write the Rust; stdout if not given
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Write a Markdown reference of the native functions scripts can call
+    Docs {
+        /// Where to write the Markdown; stdout if not given
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Compile a source file to bytecode that runs without recompiling
+    Compile {
+        /// File to compile
+        file: String,
+        /// Where to write the bytecode; the file with a .gbc extension if not given
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Compile a source file and verify its bytecode without running it
+    Check {
+        /// File to check
+        file: String,
+        /// How to print a failure
+        #[arg(long, value_enum, default_value = "text")]
+        format: report::Format,
+    },
+    /// Lint Grease source code
+    Lint {
+        /// Files, directories (searched for .grease files) or glob patterns
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Apply safe fixes to the file, then report what's left
+        #[arg(long)]
+        fix: bool,
+        /// Print the safe fixes as a unified diff
+        #[arg(long)]
+        diff: bool,
+        /// How to print the findings
+        #[arg(long, value_enum, default_value = "text")]
+        format: report::Format,
+    },
+    /// Start Language Server Protocol server
+    #[cfg(feature = "lsp")]
+    Lsp,
+    /// Evaluate snippets sent as newline-delimited JSON over a socket
+    #[cfg(not(target_os = "wasi"))]
+    #[command(group(clap::ArgGroup::new("listen").required(true).args(["socket", "tcp"])))]
+    Serve {
+        /// Unix socket path to listen on
+        #[cfg(unix)]
+        #[arg(long)]
+        socket: Option<String>,
+        /// TCP address to listen on, e.g. 127.0.0.1:7878
+        #[arg(long)]
+        tcp: Option<String>,
+    },
+    /// Run as a Jupyter kernel
+    #[cfg(feature = "jupyter")]
+    Jupyter {
+        /// Connection file passed by Jupyter
+        #[arg(required_unless_present = "install")]
+        connection_file: Option<String>,
+        /// Register the Grease kernel with Jupyter
+        #[arg(long)]
+        install: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModulesCommand {
+    /// List each module by name with the file it loads from
+    List {
+        /// Print JSON instead of one module per line
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HighlightFormat {
+    /// TextMate grammar (VS Code, Sublime Text)
+    #[value(name = "tmLanguage")]
+    TmLanguage,
+    /// Vim syntax file
+    Vim,
+    /// Emacs major mode
+    Emacs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match args.command {
+        Some(Commands::Completions { shell }) => {
+            generate(shell, &mut Args::command(), "grease", &mut io::stdout());
+        }
+        Some(Commands::Manpage) => {
+            let man = Man::new(Args::command());
+            man.render(&mut io::stdout()).unwrap();
+        }
+        Some(Commands::Tokens { file, json }) => {
+            let source = read_source(&file);
+            if json {
+                print_json(dump::tokens_json(&source));
+            } else {
+                match dump::tokens(&source) {
+                    Ok(tokens) => {
+                        for token in tokens {
+                            println!("{}:{}\t{}\t{:?}", token.line, token.column, token.kind(), token.lexeme);
+                        }
+                    }
+                    Err(msg) => {
+                        eprintln!("Lex Error: {}", msg);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Some(Commands::Parse { file, json }) => {
+            let source = read_source(&file);
+            if json {
+                print_json(dump::ast_json(&source));
+            } else {
+                match dump::tokens(&source).and_then(|tokens| grease::parser::Parser::new(tokens).parse()) {
+                    Ok(program) => println!("{:#?}", program),
+                    Err(msg) => {
+                        eprintln!("Parse Error: {}", msg);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Some(Commands::Highlight { format }) => {
+            let format = match format {
+                HighlightFormat::TmLanguage => highlight::Format::TmLanguage,
+                HighlightFormat::Vim => highlight::Format::Vim,
+                HighlightFormat::Emacs => highlight::Format::Emacs,
+            };
+            print!("{}", highlight::generate(format));
+        }
+        Some(Commands::Modules { command: ModulesCommand::List { json } }) => {
+            list_modules(json);
+        }
+        Some(Commands::Transpile { file, output }) => {
+            let source = read_source(&file);
+            let name = Path::new(&file).file_name().map_or(file.clone(), |name| name.to_string_lossy().into_owned());
+            let rust = with_project_config(Grease::new(), &file).parse(&source)
+                .and_then(|program| grease::transpile::transpile(&program, &name));
+            let written = rust.and_then(|rust| match &output {
+                Some(output) => fs::write(output, rust).map_err(|e| format!("Failed to write '{}': {}", output, e)),
+                None => {
+                    print!("{}", rust);
+                    Ok(())
+                }
+            });
+            if let Err(msg) = written {
+                eprintln!("{}: {}", file, msg);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Docs { output }) => {
+            let markdown = grease::docs::markdown(&mut Grease::new().vm);
+            match &output {
+                Some(output) => {
+                    if let Err(e) = fs::write(output, markdown) {
+                        eprintln!("Failed to write '{}': {}", output, e);
+                        std::process::exit(1);
+                    }
+                }
+                None => print!("{}", markdown),
+            }
+        }
+        Some(Commands::Compile { file, output }) => {
+            let output = output.unwrap_or_else(|| Path::new(&file).with_extension("gbc").display().to_string());
+            let written = with_project_config(Grease::new(), &file).compile_file(&file)
+                .and_then(|bytes| fs::write(&output, bytes).map_err(|e| format!("Failed to write '{}': {}", output, e)));
+            if let Err(msg) = written {
+                eprintln!("{}: {}", file, msg);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Check { file, format: report::Format::Text }) => {
+            let source = read_source(&file);
+            if let Err(msg) = with_project_config(Grease::new(), &file).check(&source) {
+                eprintln!("{}: {}", file, msg);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Check { file, format }) => {
+            // Every failure, even reading the file, goes in the report
+            let mut result = Report::new(&file);
+            result.failure = check_file(&file).err();
+            report::print(std::slice::from_ref(&result), format);
+            if !result.is_clean() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Lint { paths, fix, diff, format }) => {
+            lint::run(&paths, &lint::Options { fix, diff, format, verbose: args.verbose });
+        }
+        #[cfg(feature = "lsp")]
+        Some(Commands::Lsp) => {
+            // Start LSP server
+            if let Err(e) = serve_stdio() {
+                eprintln!("LSP server error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        #[cfg(unix)]
+        Some(Commands::Serve { socket: Some(path), .. }) => {
+            let grease = Grease::new().with_verbose(args.verbose);
+            if let Err(e) = eval_server::serve_unix(grease, Path::new(&path)) {
+                eprintln!("Server error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        #[cfg(not(target_os = "wasi"))]
+        Some(Commands::Serve { tcp, .. }) => {
+            let grease = Grease::new().with_verbose(args.verbose);
+            let address = tcp.expect("clap requires --socket or --tcp");
+            if let Err(e) = eval_server::serve_tcp(grease, &address) {
+                eprintln!("Server error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        #[cfg(feature = "jupyter")]
+        Some(Commands::Jupyter { install: true, .. }) => {
+            let installed = std::env::current_exe().and_then(|exe| jupyter_kernel::install_kernelspec(&exe));
+            match installed {
+                Ok(dir) => println!("Installed Grease kernel in {}", dir.display()),
+                Err(e) => {
+                    eprintln!("Failed to install kernel: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        #[cfg(feature = "jupyter")]
+        Some(Commands::Jupyter { connection_file, .. }) => {
+            let grease = Grease::new().with_verbose(args.verbose);
+            let connection_file = connection_file.expect("clap requires a connection file");
+            if let Err(e) = jupyter_kernel::run_kernel(grease, Path::new(&connection_file)) {
+                eprintln!("Kernel error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        None => {
+            if let Some(code) = &args.eval {
+                // Execute inline code
+                let grease = with_native_log(Grease::new().with_verbose(args.verbose).with_deterministic(args.deterministic), &args);
+                report_outcome(evaluate_with_crash_report(grease, &args, |grease| grease.evaluate(code)), Some(code));
+            } else if let Some(filename) = &args.file {
+                // Run script file
+                let grease = Grease::new()
+                    .with_verbose(args.verbose)
+                    .with_deterministic(args.deterministic)
+                    .with_args(args.script_args.clone());
+                let grease = with_project_config(with_native_log(grease, &args), filename);
+                report_outcome(evaluate_with_crash_report(grease, &args, |grease| grease.evaluate_file(filename)), None);
+            } else {
+                // Run REPL
+                let mut repl = REPL::new();
+                if !args.no_rc {
+                    if let Some(path) = REPL::default_startup_file() {
+                        repl = repl.with_startup_file(path);
+                    }
+                }
+                repl.run();
+            }
+        }
+    }
+}
+
+/// Record or replay native calls as `--record` or `--replay` asks, exiting
+/// with an error if the file can't be opened.
+fn with_native_log(grease: Grease, args: &Args) -> Grease {
+    let log = match (&args.record, &args.replay) {
+        (Some(path), _) => NativeLog::record(path),
+        (None, Some(path)) => NativeLog::replay(path),
+        (None, None) => return grease,
+    };
+    match log {
+        Ok(log) => grease.with_native_log(log),
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Run a script with `evaluate`, writing a crash report into the
+/// `--crash-report` directory if it fails at runtime or the interpreter
+/// panics. A panic carries on unwinding once the report is written.
+fn evaluate_with_crash_report(mut grease: Grease, args: &Args, evaluate: impl FnOnce(&mut Grease) -> Result<RunOutcome, String>) -> Result<RunOutcome, String> {
+    let Some(dir) = &args.crash_report else {
+        return evaluate(&mut grease);
+    };
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| evaluate(&mut grease)));
+    let crash = match &result {
+        Ok(Ok(RunOutcome { result: InterpretResult::RuntimeError(message), traceback, .. })) => Some((message.clone(), traceback.clone())),
+        Ok(_) => None,
+        Err(panic) => {
+            let message = panic.downcast_ref::<&str>().copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            Some((format!("interpreter panicked: {}", message), Vec::new()))
+        }
+    };
+    if let Some((error, traceback)) = crash {
+        let report = grease::crash::report(&grease.vm, args.file.as_deref(), &error, &traceback);
+        match grease::crash::write_report(Path::new(dir), &report) {
+            Ok(path) => eprintln!("Crash report written to {}", path.display()),
+            Err(msg) => eprintln!("Error: {}", msg),
+        }
+    }
+    result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Apply the `grease.toml` governing `file`, if there is one, exiting with
+/// an error if it can't be loaded.
+fn with_project_config(grease: Grease, file: &str) -> Grease {
+    match ProjectConfig::discover(Path::new(file)).map(|path| ProjectConfig::load(&path)) {
+        Some(Ok(config)) => grease.with_config(config),
+        Some(Err(msg)) => {
+            eprintln!("Config Error: {}", msg);
+            std::process::exit(1);
+        }
+        None => grease,
+    }
+}
+
+fn list_modules(json: bool) {
+    let modules = Grease::new().available_modules();
+    let source = |source: &ModuleSource| match source {
+        ModuleSource::Native => None,
+        ModuleSource::File(path) | ModuleSource::Plugin(path) => Some(path.display().to_string()),
+    };
+    if json {
+        let modules: Vec<_> = modules.iter().map(|(name, module)| serde_json::json!({
+            "name": name,
+            "native": *module == ModuleSource::Native,
+            "plugin": matches!(module, ModuleSource::Plugin(_)),
+            "path": source(module),
+        })).collect();
+        println!("{}", serde_json::Value::Array(modules));
+        return;
+    }
+    for (name, module) in &modules {
+        match module {
+            ModuleSource::Plugin(path) => println!("{}\t{} (plugin)", name, path.display()),
+            _ => println!("{}\t{}", name, source(module).unwrap_or_else(|| "(native)".to_string())),
+        }
+    }
+}
+
+fn check_file(file: &str) -> Result<(), String> {
+    let source = fs::read_to_string(file).map_err(|e| format!("Error reading file '{}': {}", file, e))?;
+    let mut grease = Grease::new();
+    if let Some(path) = ProjectConfig::discover(Path::new(file)) {
+        grease = grease.with_config(ProjectConfig::load(&path).map_err(|msg| format!("Config Error: {}", msg))?);
+    }
+    grease.check(&source)
+}
+
+/// Read a source file, exiting with an error if it can't be read.
+fn read_source(file: &str) -> String {
+    fs::read_to_string(file).unwrap_or_else(|err| {
+        eprintln!("Error reading file '{}': {}", file, err);
+        std::process::exit(1);
+    })
+}
+
+/// Print a JSON dump, or `{"error": message}` and exit non-zero, so tools
+/// can read either from stdout.
+fn print_json(dump: Result<serde_json::Value, String>) {
+    match dump {
+        Ok(value) => println!("{}", value),
+        Err(msg) => {
+            println!("{}", serde_json::json!({ "error": msg }));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Print a script's warnings and, if it failed, its error, the line it
+/// failed on and its traceback, exiting with status 1 on failure. `inline`
+/// is the source of code run with `--eval`, which has no file to read.
+fn report_outcome(result: Result<RunOutcome, String>, inline: Option<&str>) {
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            std::process::exit(1);
+        }
+    };
+    for warning in &outcome.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    match &outcome.result {
+        InterpretResult::Ok => {}
+        InterpretResult::CompileError(msg) => {
+            eprintln!("Compile Error: {}", msg);
+            std::process::exit(1);
+        }
+        InterpretResult::RuntimeError(msg) => {
+            eprintln!("Runtime Error: {}", msg);
+            if let (Some(frame), Some(location)) = (outcome.traceback.first(), outcome.error_location()) {
+                let source = match &frame.file {
+                    Some(file) => fs::read_to_string(file).ok(),
+                    None => inline.map(str::to_string),
+                };
+                if let Some(snippet) = source.and_then(|source| location.snippet(&source)) {
+                    eprintln!("{}", snippet);
+                }
+            }
+            for frame in &outcome.traceback {
+                eprintln!("  {}", frame);
+            }
+            std::process::exit(1);
+        }
+    }
+}
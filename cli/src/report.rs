@@ -0,0 +1,209 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Machine-readable findings for `grease lint` and `grease check`: JSON,
+//! SARIF for code-scanning dashboards, and GitHub Actions workflow
+//! commands that annotate pull requests.
+
+use clap::ValueEnum;
+use grease::prelude::LintError;
+use serde_json::json;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// `file:line:column: message` lines
+    Text,
+    /// One JSON object with every file's findings
+    Json,
+    /// SARIF 2.1.0 log for code-scanning uploads
+    Sarif,
+    /// GitHub Actions `::warning`/`::error` annotations
+    Github,
+}
+
+/// What checking one file found.
+pub struct Report {
+    pub file: String,
+    pub errors: Vec<LintError>,
+    /// The fixes as a unified diff, with `lint --diff`.
+    pub diff: Option<String>,
+    /// Why the file couldn't be linted or checked at all.
+    pub failure: Option<String>,
+}
+
+impl Report {
+    pub fn new(file: &str) -> Self {
+        Report { file: file.to_string(), errors: Vec::new(), diff: None, failure: None }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.failure.is_none()
+    }
+}
+
+/// Print `reports` in one of the machine-readable formats.
+pub fn print(reports: &[Report], format: Format) {
+    match format {
+        Format::Text => unreachable!("commands print their own text"),
+        Format::Json => print_json(reports),
+        Format::Sarif => println!("{}", sarif(reports)),
+        Format::Github => print!("{}", github(reports)),
+    }
+}
+
+fn print_json(reports: &[Report]) {
+    let files: Vec<_> = reports.iter().map(|report| {
+        let errors: Vec<_> = report.errors.iter().map(|error| json!({
+            "rule": error.rule,
+            "line": error.line,
+            "column": error.column,
+            "message": error.message,
+            "fixable": error.fix.is_some(),
+        })).collect();
+        json!({
+            "file": report.file,
+            "errors": errors,
+            "diff": report.diff,
+            "error": report.failure,
+        })
+    }).collect();
+    let error_count: usize = reports.iter().map(|report| report.errors.len()).sum();
+    let failed = reports.iter().filter(|report| report.failure.is_some()).count();
+    println!("{}", json!({ "files": files, "error_count": error_count, "failed_files": failed }));
+}
+
+/// Rule for failures: errors that stop a file compiling.
+const ERROR_RULE: &str = "error";
+
+/// A lint error or failure, flattened for the formats that list them.
+struct Finding<'a> {
+    rule: &'static str,
+    /// `warning` or `error`, as both SARIF and GitHub spell them.
+    level: &'static str,
+    file: &'a str,
+    line: Option<usize>,
+    column: Option<usize>,
+    message: &'a str,
+}
+
+/// Every finding, file by file. Failures point where their message says,
+/// when it says.
+fn findings(reports: &[Report]) -> Vec<Finding<'_>> {
+    let mut findings = Vec::new();
+    for report in reports {
+        for error in &report.errors {
+            findings.push(Finding {
+                rule: error.rule,
+                level: "warning",
+                file: &report.file,
+                line: Some(error.line),
+                column: Some(error.column),
+                message: &error.message,
+            });
+        }
+        if let Some(failure) = &report.failure {
+            let (line, column) = location(failure);
+            findings.push(Finding { rule: ERROR_RULE, level: "error", file: &report.file, line, column, message: failure });
+        }
+    }
+    findings
+}
+
+/// The line and column an error message names, as in "... at line 3,
+/// column 7" or "(line 3)".
+fn location(message: &str) -> (Option<usize>, Option<usize>) {
+    let number_after = |label: &str| {
+        let start = message.rfind(label)? + label.len();
+        let digits: String = message[start..].chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().ok()
+    };
+    (number_after("line "), number_after("column "))
+}
+
+fn sarif(reports: &[Report]) -> serde_json::Value {
+    let findings = findings(reports);
+    let mut rules: Vec<&str> = findings.iter().map(|finding| finding.rule).collect();
+    rules.sort();
+    rules.dedup();
+
+    let results: Vec<_> = findings.iter().map(|finding| {
+        let mut location = json!({ "physicalLocation": { "artifactLocation": { "uri": finding.file } } });
+        if let Some(line) = finding.line {
+            location["physicalLocation"]["region"] = json!({ "startLine": line, "startColumn": finding.column.unwrap_or(1) });
+        }
+        json!({
+            "ruleId": finding.rule,
+            "ruleIndex": rules.iter().position(|rule| *rule == finding.rule),
+            "level": finding.level,
+            "message": { "text": finding.message },
+            "locations": [location],
+        })
+    }).collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "grease",
+                    "version": grease::VERSION,
+                    "rules": rules.iter().map(|rule| json!({ "id": rule })).collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn github(reports: &[Report]) -> String {
+    // Workflow commands end at a newline and split properties on ',' and ':'
+    let escape = |text: &str| text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+    let escape_property = |text: &str| escape(text).replace(':', "%3A").replace(',', "%2C");
+
+    findings(reports).iter().map(|finding| {
+        let mut properties = format!("file={}", escape_property(finding.file));
+        if let Some(line) = finding.line {
+            properties.push_str(&format!(",line={}", line));
+        }
+        if let Some(column) = finding.column {
+            properties.push_str(&format!(",col={}", column));
+        }
+        properties.push_str(&format!(",title={}", escape_property(finding.rule)));
+        format!("::{} {}::{}\n", finding.level, properties, escape(finding.message))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reports() -> Vec<Report> {
+        let mut linted = Report::new("a.grease");
+        linted.errors.push(LintError { rule: "unused-variable", message: "Unused variable 'x'".to_string(), line: 2, column: 5, fix: None });
+        let mut failed = Report::new("b,c.grease");
+        failed.failure = Some("Expected ':' after if condition at line 3, column 9".to_string());
+        vec![linted, failed]
+    }
+
+    #[test]
+    fn test_sarif_results() {
+        let log = sarif(&reports());
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"], json!([{ "id": "error" }, { "id": "unused-variable" }]));
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results[0]["ruleIndex"], 1);
+        assert_eq!(results[0]["locations"][0]["physicalLocation"]["region"], json!({ "startLine": 2, "startColumn": 5 }));
+        assert_eq!(results[1]["level"], "error");
+        assert_eq!(results[1]["locations"][0]["physicalLocation"]["region"]["startLine"], 3);
+    }
+
+    #[test]
+    fn test_github_annotations() {
+        assert_eq!(github(&reports()), concat!(
+            "::warning file=a.grease,line=2,col=5,title=unused-variable::Unused variable 'x'\n",
+            "::error file=b%2Cc.grease,line=3,col=9,title=error::Expected ':' after if condition at line 3, column 9\n",
+        ));
+        assert_eq!(location("Invalid bytecode in f: Stack underflow at offset 4 (line 7)"), (Some(7), None));
+    }
+}
@@ -0,0 +1,41 @@
+;;; grease-mode.el --- Major mode for Grease  -*- lexical-binding: t; -*-
+
+;; Generated by `grease highlight --format emacs'; do not edit.
+
+;;; Code:
+
+(defvar grease-mode-syntax-table
+  (let ((table (make-syntax-table)))
+    (modify-syntax-entry ?# "<" table)
+    (modify-syntax-entry ?\n ">" table)
+    (modify-syntax-entry ?' "\"" table)
+    (modify-syntax-entry ?_ "_" table)
+    table)
+  "Syntax table for `grease-mode'.")
+
+(defconst grease-font-lock-keywords
+  `((,(regexp-opt '("def" "if" "elif" "else" "while" "for" "in" "break" "continue" "return" "use" "from" "lazy" "as" "throw" "try" "catch" "finally" "with" "class" "new") 'symbols) . font-lock-keyword-face)
+    (,(regexp-opt '("and" "or" "not") 'symbols) . font-lock-keyword-face)
+    (,(regexp-opt '("true" "false" "null") 'symbols) . font-lock-constant-face)
+    (,(regexp-opt '("self" "super") 'symbols) . font-lock-builtin-face)
+    ("\\_<def\\s-+\\(\\(?:\\sw\\|\\s_\\)+\\)" 1 font-lock-function-name-face)
+    ("\\_<class\\s-+\\(\\(?:\\sw\\|\\s_\\)+\\)" 1 font-lock-type-face)
+    ("@\\(?:\\sw\\|\\s_\\)+" . font-lock-preprocessor-face)
+    (,(regexp-opt '("==" "!=" "<=" ">=" "=" "<" ">" "+" "-" "*" "/" "%" ".")) . 'font-lock-operator-face))
+  "Highlighting for `grease-mode'.")
+
+;;;###autoload
+(define-derived-mode grease-mode prog-mode "Grease"
+  "Major mode for editing Grease scripts."
+  (setq-local comment-start "# ")
+  (setq-local comment-start-skip "#+\\s-*")
+  (setq-local indent-tabs-mode nil)
+  (setq-local tab-width 4)
+  (setq-local font-lock-defaults '(grease-font-lock-keywords)))
+
+;;;###autoload
+(add-to-list 'auto-mode-alist '("\\.grease\\'" . grease-mode))
+
+(provide 'grease-mode)
+
+;;; grease-mode.el ends here
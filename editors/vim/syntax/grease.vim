@@ -0,0 +1,36 @@
+" Vim syntax file
+" Language: Grease
+" Generated by `grease highlight --format vim`; do not edit.
+" Install as ~/.vim/syntax/grease.vim and add:
+"   au BufRead,BufNewFile *.grease set filetype=grease
+
+if exists("b:current_syntax")
+  finish
+endif
+
+syn keyword greaseKeyword def if elif else while for in break continue return use from lazy as throw try catch finally with class new
+syn keyword greaseOperatorWord and or not
+syn keyword greaseConstant true false null
+syn keyword greaseSelf self super
+syn match greaseFunction "\%(\<def\s\+\)\@<=\h\w*"
+syn match greaseType "\%(\<class\s\+\)\@<=\h\w*"
+syn match greaseDecorator "@\h\w*"
+syn match greaseNumber "\<\d\+\%(\.\d\+\)\=\>"
+syn match greaseOperator "\V==\|!=\|<=\|>=\|=\|<\|>\|+\|-\|*\|/\|%\|."
+syn region greaseString start=+"+ end=+"+ oneline
+syn region greaseString start=+'+ end=+'+ oneline
+syn match greaseComment "#.*$"
+
+hi def link greaseKeyword Keyword
+hi def link greaseOperatorWord Operator
+hi def link greaseConstant Constant
+hi def link greaseSelf Identifier
+hi def link greaseFunction Function
+hi def link greaseType Type
+hi def link greaseDecorator PreProc
+hi def link greaseNumber Number
+hi def link greaseOperator Operator
+hi def link greaseString String
+hi def link greaseComment Comment
+
+let b:current_syntax = "grease"
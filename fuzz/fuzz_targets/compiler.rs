@@ -0,0 +1,18 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use grease::compiler::Compiler;
+use grease::lexer::Lexer;
+use grease::parser::Parser;
+use libfuzzer_sys::fuzz_target;
+
+// Whatever the parser accepts must compile or fail with an error. Source
+// seeds from examples/ keep most inputs past the lexer.
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data).into_owned();
+    let Ok(tokens) = Lexer::new(source).tokenize() else { return };
+    let Ok(program) = Parser::new(tokens).parse() else { return };
+    let _ = Compiler::new().compile(&program);
+});
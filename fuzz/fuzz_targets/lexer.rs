@@ -0,0 +1,13 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use grease::lexer::Lexer;
+use libfuzzer_sys::fuzz_target;
+
+// Arbitrary text must tokenize or return an error, never panic
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data).into_owned();
+    let _ = Lexer::new(source).tokenize();
+});
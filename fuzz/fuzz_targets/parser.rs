@@ -0,0 +1,75 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use grease::parser::Parser;
+use grease::token::{Token, TokenType};
+use libfuzzer_sys::fuzz_target;
+
+/// Map a byte to a token, so the parser sees token sequences the lexer
+/// would never produce (stray dedents, unbalanced brackets, ...).
+fn token_for(byte: u8, index: usize) -> Token {
+    let token_type = match byte % 52 {
+        0 => TokenType::Number(byte as f64),
+        1 => TokenType::String("s".to_string()),
+        2 => TokenType::Boolean(byte % 2 == 0),
+        3 => TokenType::Identifier(["x", "print", "self", "Error"][byte as usize % 4].to_string()),
+        4 => TokenType::Fn,
+        5 => TokenType::If,
+        6 => TokenType::Elif,
+        7 => TokenType::Else,
+        8 => TokenType::While,
+        9 => TokenType::For,
+        10 => TokenType::In,
+        11 => TokenType::Return,
+        12 => TokenType::Use,
+        13 => TokenType::Try,
+        14 => TokenType::Catch,
+        15 => TokenType::Finally,
+        16 => TokenType::Throw,
+        17 => TokenType::With,
+        18 => TokenType::As,
+        19 => TokenType::True,
+        20 => TokenType::False,
+        21 => TokenType::Null,
+        22 => TokenType::Class,
+        23 => TokenType::New,
+        24 => TokenType::SelfKw,
+        25 => TokenType::Super,
+        26 => TokenType::Assign,
+        27 => TokenType::Plus,
+        28 => TokenType::Minus,
+        29 => TokenType::Multiply,
+        30 => TokenType::Divide,
+        31 => TokenType::Modulo,
+        32 => TokenType::Equal,
+        33 => TokenType::NotEqual,
+        34 => TokenType::Less,
+        35 => TokenType::GreaterEqual,
+        36 => TokenType::And,
+        37 => TokenType::Or,
+        38 => TokenType::Not,
+        39 => TokenType::LeftParen,
+        40 => TokenType::RightParen,
+        41 => TokenType::LeftBrace,
+        42 => TokenType::RightBrace,
+        43 => TokenType::LeftBracket,
+        44 => TokenType::RightBracket,
+        45 => TokenType::Comma,
+        46 => TokenType::Dot,
+        47 => TokenType::Colon,
+        48 => TokenType::At,
+        49 => TokenType::Newline,
+        50 => TokenType::Indent,
+        _ => TokenType::Dedent,
+    };
+    Token::new(token_type, String::new(), 1, index + 1)
+}
+
+// Any token stream ending in EOF must parse or return an error
+fuzz_target!(|data: &[u8]| {
+    let mut tokens: Vec<Token> = data.iter().enumerate().map(|(i, b)| token_for(*b, i)).collect();
+    tokens.push(Token::new(TokenType::EOF, String::new(), 1, data.len() + 1));
+    let _ = Parser::new(tokens).parse();
+});
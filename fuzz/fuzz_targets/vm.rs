@@ -0,0 +1,42 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use grease::bytecode::{Chunk, Value};
+use grease::source_map::SourceLocation;
+use grease::vm::VM;
+use libfuzzer_sys::fuzz_target;
+
+/// Constants the fuzzer can place in the chunk's pool. Strings name real
+/// globals so GetGlobal/Call reach the natives.
+fn constant_for(byte: u8) -> Value {
+    match byte % 8 {
+        0 => Value::Null,
+        1 => Value::Boolean(byte % 2 == 0),
+        2 => Value::Number(byte as f64),
+        3 => Value::Number(0.0),
+        4 => Value::String("print".to_string()),
+        5 => Value::String("Error".to_string()),
+        6 => Value::String("x".to_string()),
+        _ => Value::array(vec![Value::Number(1.0), Value::String("a".to_string())]),
+    }
+}
+
+// Arbitrary chunks must run to completion or fail with a runtime error.
+// Input layout: constant count, one byte per constant, then the code.
+fuzz_target!(|data: &[u8]| {
+    let Some((&count, rest)) = data.split_first() else { return };
+    let count = (count as usize % 16).min(rest.len());
+    let (constants, code) = rest.split_at(count);
+
+    let mut chunk = Chunk::new();
+    chunk.constants = constants.iter().map(|b| constant_for(*b)).collect();
+    chunk.code = code.to_vec();
+    chunk.source_map.record(0, SourceLocation { line: 1, column: 1 });
+    let mut vm = VM::new();
+    // Backward jumps make trivial infinite loops; stop them rather than
+    // letting them time out
+    vm.limits.max_instructions = Some(100_000);
+    let _ = vm.interpret(chunk);
+});
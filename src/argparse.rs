@@ -0,0 +1,407 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::bytecode::Value;
+use crate::vm::VM;
+use std::collections::HashMap;
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("script_args", 0, |vm, _args| {
+        Ok(Value::array(vm.script_args.iter().cloned().map(Value::String).collect()))
+    });
+
+    vm.register_native("argparse_parse", 2, |vm, args| {
+        let parser = ArgParser::from_value(&args[0])?;
+        let argv = match &args[1] {
+            Value::Null => vm.script_args.clone(),
+            Value::Array(items) => items.lock().unwrap().iter().map(VM::format_value).collect(),
+            _ => return Err("TypeError: argparse_parse expects an array of arguments or null".to_string()),
+        };
+        match parser.parse(&argv)? {
+            Some(parsed) => Ok(Value::dictionary(parsed)),
+            None => {
+                // --help was requested: show it and let the script decide what to do
+                vm.write_output(parser.help().trim_end())?;
+                Ok(Value::Null)
+            }
+        }
+    });
+
+    vm.register_native("argparse_help", 1, |_vm, args| {
+        ArgParser::from_value(&args[0]).map(|parser| Value::String(parser.help()))
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArgKind {
+    Flag,
+    Option,
+    Positional,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArgType {
+    String,
+    Number,
+    Boolean,
+}
+
+#[derive(Debug, Clone)]
+struct ArgSpec {
+    name: String,
+    kind: ArgKind,
+    ty: ArgType,
+    short: Option<char>,
+    help: String,
+    default: Value,
+    required: bool,
+}
+
+impl ArgSpec {
+    fn long(&self) -> String {
+        format!("--{}", self.name.replace('_', "-"))
+    }
+
+    fn usage(&self) -> String {
+        let metavar = self.name.to_uppercase();
+        match self.kind {
+            ArgKind::Flag => format!("[{}]", self.long()),
+            ArgKind::Option if self.required => format!("{} {}", self.long(), metavar),
+            ArgKind::Option => format!("[{} {}]", self.long(), metavar),
+            ArgKind::Positional if self.required => self.name.clone(),
+            ArgKind::Positional => format!("[{}]", self.name),
+        }
+    }
+}
+
+/// Command-line parser built from a Grease dictionary spec:
+///
+/// ```text
+/// {"prog": "tool", "description": "...", "arguments": [
+///     {"name": "verbose", "kind": "flag", "short": "v", "help": "..."},
+///     {"name": "count", "kind": "option", "type": "number", "default": 1},
+///     {"name": "input", "kind": "positional"}
+/// ]}
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArgParser {
+    prog: String,
+    description: String,
+    specs: Vec<ArgSpec>,
+}
+
+impl ArgParser {
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let spec = match value {
+            Value::Dictionary(spec) => spec.lock().unwrap().clone(),
+            _ => return Err("TypeError: argparse spec must be a dictionary".to_string()),
+        };
+        let prog = optional_string(&spec, "prog")?.unwrap_or_else(|| "script".to_string());
+        let description = optional_string(&spec, "description")?.unwrap_or_default();
+
+        let arguments = match spec.get("arguments") {
+            Some(Value::Array(arguments)) => arguments.lock().unwrap().clone(),
+            None => Vec::new(),
+            Some(_) => return Err("TypeError: argparse spec 'arguments' must be an array".to_string()),
+        };
+        let mut specs = Vec::with_capacity(arguments.len());
+        for argument in &arguments {
+            let spec = parse_arg_spec(argument)?;
+            if specs.iter().any(|existing: &ArgSpec| existing.name == spec.name) {
+                return Err(format!("Duplicate argument '{}'", spec.name));
+            }
+            if spec.short.is_some() && specs.iter().any(|existing: &ArgSpec| existing.short == spec.short) {
+                return Err(format!("Duplicate short option for argument '{}'", spec.name));
+            }
+            specs.push(spec);
+        }
+
+        Ok(ArgParser { prog, description, specs })
+    }
+
+    /// Parse `argv`, returning `None` when `--help`/`-h` was requested.
+    pub fn parse(&self, argv: &[String]) -> Result<Option<HashMap<String, Value>>, String> {
+        let mut parsed = HashMap::with_capacity(self.specs.len());
+        let mut positionals = Vec::new();
+        let mut options_done = false;
+        let mut i = 0;
+
+        while i < argv.len() {
+            let arg = &argv[i];
+            i += 1;
+
+            if options_done || arg == "-" || !arg.starts_with('-') {
+                positionals.push(arg.clone());
+                continue;
+            }
+            if arg == "--" {
+                options_done = true;
+                continue;
+            }
+            if arg == "--help" || arg == "-h" {
+                return Ok(None);
+            }
+
+            let (spec, inline_value) = if let Some(long) = arg.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let spec = self.specs.iter()
+                    .find(|s| s.kind != ArgKind::Positional && s.name.replace('_', "-") == name)
+                    .ok_or_else(|| self.error(&format!("unrecognized option '--{}'", name)))?;
+                (spec, value)
+            } else {
+                let mut chars = arg[1..].chars();
+                let short = chars.next().unwrap_or_default();
+                let rest: String = chars.collect();
+                let spec = self.specs.iter()
+                    .find(|s| s.kind != ArgKind::Positional && s.short == Some(short))
+                    .ok_or_else(|| self.error(&format!("unrecognized option '-{}'", short)))?;
+                (spec, if rest.is_empty() { None } else { Some(rest) })
+            };
+
+            match spec.kind {
+                ArgKind::Flag => {
+                    if inline_value.is_some() {
+                        return Err(self.error(&format!("flag '{}' does not take a value", spec.long())));
+                    }
+                    parsed.insert(spec.name.clone(), Value::Boolean(true));
+                }
+                _ => {
+                    let raw = match inline_value {
+                        Some(value) => value,
+                        None if i < argv.len() => {
+                            i += 1;
+                            argv[i - 1].clone()
+                        }
+                        None => return Err(self.error(&format!("option '{}' expects a value", spec.long()))),
+                    };
+                    parsed.insert(spec.name.clone(), self.convert(spec, &raw)?);
+                }
+            }
+        }
+
+        let mut positionals = positionals.into_iter();
+        for spec in self.specs.iter().filter(|s| s.kind == ArgKind::Positional) {
+            if let Some(raw) = positionals.next() {
+                parsed.insert(spec.name.clone(), self.convert(spec, &raw)?);
+            }
+        }
+        if let Some(extra) = positionals.next() {
+            return Err(self.error(&format!("unexpected argument '{}'", extra)));
+        }
+
+        for spec in &self.specs {
+            if parsed.contains_key(&spec.name) {
+                continue;
+            }
+            if spec.required {
+                let shown = if spec.kind == ArgKind::Positional { spec.name.clone() } else { spec.long() };
+                return Err(self.error(&format!("missing required argument '{}'", shown)));
+            }
+            parsed.insert(spec.name.clone(), spec.default.clone());
+        }
+
+        Ok(Some(parsed))
+    }
+
+    pub fn usage(&self) -> String {
+        let mut usage = format!("usage: {}", self.prog);
+        for spec in &self.specs {
+            usage.push(' ');
+            usage.push_str(&spec.usage());
+        }
+        usage
+    }
+
+    pub fn help(&self) -> String {
+        let mut help = self.usage();
+        help.push('\n');
+        if !self.description.is_empty() {
+            help.push('\n');
+            help.push_str(&self.description);
+            help.push('\n');
+        }
+
+        let positionals: Vec<&ArgSpec> = self.specs.iter().filter(|s| s.kind == ArgKind::Positional).collect();
+        if !positionals.is_empty() {
+            help.push_str("\narguments:\n");
+            for spec in positionals {
+                help.push_str(&help_line(spec.name.clone(), spec));
+            }
+        }
+
+        help.push_str("\noptions:\n");
+        help.push_str(&format!("  {:<24} {}\n", "-h, --help", "show this help message"));
+        for spec in self.specs.iter().filter(|s| s.kind != ArgKind::Positional) {
+            let mut label = match spec.short {
+                Some(short) => format!("-{}, {}", short, spec.long()),
+                None => format!("    {}", spec.long()),
+            };
+            if spec.kind == ArgKind::Option {
+                label.push(' ');
+                label.push_str(&spec.name.to_uppercase());
+            }
+            help.push_str(&help_line(label, spec));
+        }
+        help
+    }
+
+    fn convert(&self, spec: &ArgSpec, raw: &str) -> Result<Value, String> {
+        match spec.ty {
+            ArgType::String => Ok(Value::String(raw.to_string())),
+            ArgType::Number => raw.parse::<f64>().map(Value::Number).map_err(|_| {
+                self.error(&format!("argument '{}' expects a number, got '{}'", spec.name, raw))
+            }),
+            ArgType::Boolean => match raw {
+                "true" | "yes" | "1" => Ok(Value::Boolean(true)),
+                "false" | "no" | "0" => Ok(Value::Boolean(false)),
+                _ => Err(self.error(&format!("argument '{}' expects a boolean, got '{}'", spec.name, raw))),
+            },
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{}\n{}: error: {}", self.usage(), self.prog, message)
+    }
+}
+
+fn help_line(label: String, spec: &ArgSpec) -> String {
+    let mut line = format!("  {:<24} {}", label, spec.help);
+    if spec.kind != ArgKind::Flag && !matches!(spec.default, Value::Null) {
+        line.push_str(&format!(" (default: {})", VM::format_value(&spec.default)));
+    }
+    line.trim_end().to_string() + "\n"
+}
+
+fn optional_string(dict: &HashMap<String, Value>, key: &str) -> Result<Option<String>, String> {
+    match dict.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(format!("TypeError: argparse field '{}' must be a string", key)),
+    }
+}
+
+fn parse_arg_spec(value: &Value) -> Result<ArgSpec, String> {
+    let dict = match value {
+        Value::Dictionary(dict) => dict.lock().unwrap().clone(),
+        _ => return Err("TypeError: Each argparse argument must be a dictionary".to_string()),
+    };
+    let name = optional_string(&dict, "name")?
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| "argparse argument is missing a 'name'".to_string())?;
+
+    let kind = match optional_string(&dict, "kind")?.as_deref() {
+        Some("flag") => ArgKind::Flag,
+        Some("option") | None => ArgKind::Option,
+        Some("positional") => ArgKind::Positional,
+        Some(other) => return Err(format!("Unknown kind '{}' for argument '{}'", other, name)),
+    };
+    let ty = match optional_string(&dict, "type")?.as_deref() {
+        Some("string") | None => ArgType::String,
+        Some("number") => ArgType::Number,
+        Some("boolean") => ArgType::Boolean,
+        Some(other) => return Err(format!("Unknown type '{}' for argument '{}'", other, name)),
+    };
+
+    let short = match optional_string(&dict, "short")? {
+        None => None,
+        Some(short) => {
+            let mut chars = short.trim_start_matches('-').chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if kind != ArgKind::Positional => Some(c),
+                _ => return Err(format!("Invalid short option '{}' for argument '{}'", short, name)),
+            }
+        }
+    };
+
+    let default = match (kind, dict.get("default")) {
+        (ArgKind::Flag, _) => Value::Boolean(false),
+        (_, Some(default)) => default.clone(),
+        (_, None) => Value::Null,
+    };
+    let required = match dict.get("required") {
+        Some(Value::Boolean(required)) => *required,
+        Some(_) => return Err(format!("TypeError: 'required' for argument '{}' must be a boolean", name)),
+        // Positionals without a default must be supplied
+        None => kind == ArgKind::Positional && !dict.contains_key("default"),
+    };
+
+    Ok(ArgSpec {
+        name,
+        kind,
+        ty,
+        short,
+        help: optional_string(&dict, "help")?.unwrap_or_default(),
+        default,
+        required,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser() -> ArgParser {
+        let mut grease = crate::grease::Grease::new();
+        let source = "spec = {\"prog\": \"tool\", \"description\": \"Does things\", \"arguments\": [{\"name\": \"verbose\", \"kind\": \"flag\", \"short\": \"v\", \"help\": \"talk more\"}, {\"name\": \"count\", \"type\": \"number\", \"default\": 1, \"short\": \"n\"}, {\"name\": \"dry_run\", \"kind\": \"flag\"}, {\"name\": \"input\", \"kind\": \"positional\", \"help\": \"file to read\"}]}";
+        grease.run(source).unwrap();
+        ArgParser::from_value(grease.vm.globals.get("spec").unwrap()).unwrap()
+    }
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_flags_options_and_positionals() {
+        let parsed = parser().parse(&argv(&["-v", "--count=3", "--dry-run", "in.txt"])).unwrap().unwrap();
+        assert!(matches!(parsed.get("verbose"), Some(Value::Boolean(true))));
+        assert!(matches!(parsed.get("dry_run"), Some(Value::Boolean(true))));
+        assert!(matches!(parsed.get("count"), Some(Value::Number(n)) if *n == 3.0));
+        assert!(matches!(parsed.get("input"), Some(Value::String(s)) if s == "in.txt"));
+    }
+
+    #[test]
+    fn test_parse_defaults_and_short_values() {
+        let parsed = parser().parse(&argv(&["-n5", "--", "-file"])).unwrap().unwrap();
+        assert!(matches!(parsed.get("verbose"), Some(Value::Boolean(false))));
+        assert!(matches!(parsed.get("count"), Some(Value::Number(n)) if *n == 5.0));
+        assert!(matches!(parsed.get("input"), Some(Value::String(s)) if s == "-file"));
+
+        let parsed = parser().parse(&argv(&["in.txt"])).unwrap().unwrap();
+        assert!(matches!(parsed.get("count"), Some(Value::Number(n)) if *n == 1.0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parser = parser();
+        assert!(parser.parse(&argv(&[])).unwrap_err().contains("missing required argument 'input'"));
+        assert!(parser.parse(&argv(&["--nope", "x"])).unwrap_err().contains("unrecognized option"));
+        assert!(parser.parse(&argv(&["--count", "abc", "x"])).unwrap_err().contains("expects a number"));
+        assert!(parser.parse(&argv(&["a", "b"])).unwrap_err().contains("unexpected argument 'b'"));
+    }
+
+    #[test]
+    fn test_help_generation() {
+        let parser = parser();
+        assert!(parser.parse(&argv(&["--help"])).unwrap().is_none());
+        let help = parser.help();
+        assert!(help.starts_with("usage: tool [--verbose] [--count COUNT] [--dry-run] input\n"));
+        assert!(help.contains("Does things"));
+        assert!(help.contains("-v, --verbose"));
+        assert!(help.contains("(default: 1)"));
+        assert!(help.contains("file to read"));
+    }
+
+    #[test]
+    fn test_argparse_native_uses_script_args() {
+        let mut grease = crate::grease::Grease::new().with_args(argv(&["--name", "grease"]));
+        let source = "args = argparse_parse({\"arguments\": [{\"name\": \"name\"}]}, null)\nname = args[\"name\"]\nraw = script_args()";
+        let result = grease.run(source).unwrap();
+        assert_eq!(result, crate::vm::InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("name"), Some(Value::String(s)) if s == "grease"));
+        assert!(matches!(grease.vm.globals.get("raw"), Some(Value::Array(items)) if items.lock().unwrap().len() == 2));
+    }
+}
@@ -1,12 +1,16 @@
 // Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::token::Token;
+use crate::token::{Span, Token};
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+/// Nodes serialize as `{"kind": variant, "data": fields}` for `grease parse --json`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "data")]
 pub enum Expression {
     Number(f64),
     String(String),
+    Bytes(Vec<u8>),
     Boolean(bool),
     Null,
     Identifier(Token),
@@ -38,11 +42,18 @@ pub enum Expression {
     },
     Grouping(Box<Expression>),
     Array(Vec<Expression>),
+    Tuple(Vec<Expression>),
     Dictionary(Vec<(Expression, Expression)>),
     Index {
         array: Box<Expression>,
         index: Box<Expression>,
     },
+    /// `array[start:end]`, either bound optional.
+    Slice {
+        array: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+    },
     NewInstance {
         class: Box<Expression>,
         arguments: Vec<Expression>,
@@ -68,7 +79,8 @@ pub enum Expression {
     },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "data")]
 pub enum Statement {
     Expression(Expression),
     VariableDeclaration {
@@ -76,11 +88,17 @@ pub enum Statement {
         type_annotation: Option<String>,
         initializer: Option<Expression>,
     },
+    Unpack {
+        targets: Vec<Token>,
+        value: Expression,
+    },
     FunctionDeclaration {
         name: Token,
         parameters: Vec<(Token, Option<String>)>,
         return_type: Option<String>,
         body: Vec<Statement>,
+        /// `@decorator` expressions, outermost first
+        decorators: Vec<Expression>,
     },
     Return {
         value: Option<Expression>,
@@ -99,10 +117,24 @@ pub enum Statement {
         iterable: Expression,
         body: Vec<Statement>,
     },
+    /// Leave the innermost loop.
+    Break,
+    /// Go on to the innermost loop's next iteration.
+    Continue,
     Block(Vec<Statement>),
     Use {
         module: String,
         alias: Option<String>,
+        /// Members bound directly by `from module use a, b`; empty for `use`.
+        names: Vec<Token>,
+        /// `lazy use m` binds the module without loading it until a member
+        /// is first used.
+        lazy: bool,
+    },
+    /// `use plugin "path/to/libfoo.so"` loads a native plugin; `path` is the
+    /// string token.
+    UsePlugin {
+        path: Token,
     },
     ClassDeclaration {
         name: Token,
@@ -111,11 +143,17 @@ pub enum Statement {
     },
     Try {
         try_block: Vec<Statement>,
-        catch_block: Vec<Statement>,
+        catch_clauses: Vec<CatchClause>,
+        finally_block: Option<Vec<Statement>>,
     },
     Throw {
         value: Option<Expression>,
     },
+    With {
+        manager: Expression,
+        name: Option<Token>,
+        body: Vec<Statement>,
+    },
     RustInline {
         code: String,
     },
@@ -124,7 +162,190 @@ pub enum Statement {
     },
 }
 
-#[derive(Debug, Clone)]
+/// One `catch` clause of a `try` statement. A clause without exception types
+/// catches everything.
+#[derive(Debug, Clone, Serialize)]
+pub struct CatchClause {
+    pub exception_types: Vec<Expression>,
+    pub name: Option<Token>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Program {
     pub statements: Vec<Statement>,
-}
\ No newline at end of file
+}
+
+impl Program {
+    pub fn new(statements: Vec<Statement>) -> Self {
+        Program { statements }
+    }
+}
+/// The span covering all of `spans` that are known.
+fn cover(spans: impl IntoIterator<Item = Option<Span>>) -> Option<Span> {
+    spans.into_iter().flatten().reduce(Span::to)
+}
+
+fn token_spans<'a>(tokens: impl IntoIterator<Item = &'a Token>) -> std::vec::IntoIter<Option<Span>> {
+    tokens.into_iter().map(|token| Some(token.span())).collect::<Vec<_>>().into_iter()
+}
+
+fn block_span(statements: &[Statement]) -> Option<Span> {
+    cover(statements.iter().map(Statement::span))
+}
+
+/// Constructors for front ends building an AST by hand.
+impl Expression {
+    pub fn number(value: f64) -> Self {
+        Expression::Number(value)
+    }
+
+    pub fn string(value: impl Into<String>) -> Self {
+        Expression::String(value.into())
+    }
+
+    pub fn identifier(name: Token) -> Self {
+        Expression::Identifier(name)
+    }
+
+    pub fn binary(left: Expression, operator: Token, right: Expression) -> Self {
+        Expression::Binary { left: Box::new(left), operator, right: Box::new(right) }
+    }
+
+    pub fn unary(operator: Token, right: Expression) -> Self {
+        Expression::Unary { operator, right: Box::new(right) }
+    }
+
+    pub fn assignment(name: Token, value: Expression) -> Self {
+        Expression::Assignment { name, value: Box::new(value) }
+    }
+
+    pub fn call(callee: Expression, arguments: Vec<Expression>) -> Self {
+        Expression::Call { callee: Box::new(callee), arguments }
+    }
+
+    pub fn index(array: Expression, index: Expression) -> Self {
+        Expression::Index { array: Box::new(array), index: Box::new(index) }
+    }
+
+    pub fn property(object: Expression, property: Token) -> Self {
+        Expression::PropertyAccess { object: Box::new(object), property }
+    }
+}
+
+impl Expression {
+    /// The span of the tokens in the expression. Literals and brackets keep
+    /// no token, so they only count where a token around them does.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expression::Number(_) | Expression::String(_) | Expression::Bytes(_) | Expression::Boolean(_) | Expression::Null
+            | Expression::RustInline { .. } | Expression::AsmInline { .. } => None,
+            Expression::Identifier(token) => Some(token.span()),
+            Expression::Binary { left, operator, right } => cover([left.span(), Some(operator.span()), right.span()]),
+            Expression::Unary { operator, right } => cover([Some(operator.span()), right.span()]),
+            Expression::Assignment { name, value } => cover([Some(name.span()), value.span()]),
+            Expression::PropertyAssignment { object, property, value } => {
+                cover([object.span(), Some(property.span()), value.span()])
+            }
+            Expression::Call { callee: target, arguments } | Expression::NewInstance { class: target, arguments } => {
+                cover(std::iter::once(target.span()).chain(arguments.iter().map(Expression::span)))
+            }
+            Expression::ModuleAccess { module, member } => Some(module.span().to(member.span())),
+            Expression::Grouping(inner) => inner.span(),
+            Expression::Array(elements) | Expression::Tuple(elements) => cover(elements.iter().map(Expression::span)),
+            Expression::Dictionary(pairs) => cover(pairs.iter().flat_map(|(key, value)| [key.span(), value.span()])),
+            Expression::Index { array, index } => cover([array.span(), index.span()]),
+            Expression::Slice { array, start, end } => {
+                cover([array.span(), start.as_ref().and_then(|start| start.span()), end.as_ref().and_then(|end| end.span())])
+            }
+            Expression::PropertyAccess { object, property } => cover([object.span(), Some(property.span())]),
+            Expression::MethodCall { object, method, arguments } => {
+                cover([object.span(), Some(method.span())].into_iter().chain(arguments.iter().map(Expression::span)))
+            }
+            Expression::SuperCall { method, arguments } => {
+                cover(token_spans(method).chain(arguments.iter().map(Expression::span)))
+            }
+        }
+    }
+}
+
+impl Statement {
+    pub fn expression(expression: Expression) -> Self {
+        Statement::Expression(expression)
+    }
+
+    /// `name = initializer`
+    pub fn variable(name: Token, initializer: Expression) -> Self {
+        Statement::VariableDeclaration { name, type_annotation: None, initializer: Some(initializer) }
+    }
+
+    pub fn function(name: Token, parameters: Vec<Token>, body: Vec<Statement>) -> Self {
+        Statement::FunctionDeclaration {
+            name,
+            parameters: parameters.into_iter().map(|parameter| (parameter, None)).collect(),
+            return_type: None,
+            body,
+            decorators: Vec::new(),
+        }
+    }
+
+    pub fn returning(value: Option<Expression>) -> Self {
+        Statement::Return { value }
+    }
+
+    pub fn if_else(condition: Expression, then_branch: Vec<Statement>, else_branch: Option<Vec<Statement>>) -> Self {
+        Statement::If { condition, then_branch, else_branch }
+    }
+
+    pub fn while_loop(condition: Expression, body: Vec<Statement>) -> Self {
+        Statement::While { condition, body }
+    }
+}
+
+impl Statement {
+    /// The span of the tokens in the statement, nested blocks included.
+    /// Keywords keep no token, so e.g. an `if` starts at its condition.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Statement::Expression(expression) => expression.span(),
+            Statement::VariableDeclaration { name, initializer, .. } => {
+                cover([Some(name.span()), initializer.as_ref().and_then(Expression::span)])
+            }
+            Statement::Unpack { targets, value } => cover(token_spans(targets).chain([value.span()])),
+            Statement::FunctionDeclaration { name, parameters, body, decorators, .. } => cover(
+                decorators.iter().map(Expression::span)
+                    .chain([Some(name.span())])
+                    .chain(token_spans(parameters.iter().map(|(parameter, _)| parameter)))
+                    .chain([block_span(body)]),
+            ),
+            Statement::Return { value } | Statement::Throw { value } => value.as_ref().and_then(Expression::span),
+            Statement::If { condition, then_branch, else_branch } => cover([
+                condition.span(),
+                block_span(then_branch),
+                else_branch.as_deref().and_then(block_span),
+            ]),
+            Statement::While { condition, body } => cover([condition.span(), block_span(body)]),
+            Statement::For { variable, iterable, body } => cover([Some(variable.span()), iterable.span(), block_span(body)]),
+            Statement::Block(statements) => block_span(statements),
+            Statement::Use { names, .. } => cover(token_spans(names)),
+            Statement::UsePlugin { path } => Some(path.span()),
+            Statement::ClassDeclaration { name, superclass, methods } => {
+                cover(token_spans(std::iter::once(name).chain(superclass)).chain([block_span(methods)]))
+            }
+            Statement::Try { try_block, catch_clauses, finally_block } => cover(
+                std::iter::once(block_span(try_block))
+                    .chain(catch_clauses.iter().flat_map(|clause| {
+                        clause.exception_types.iter().map(Expression::span)
+                            .chain(token_spans(&clause.name))
+                            .chain([block_span(&clause.body)])
+                            .collect::<Vec<_>>()
+                    }))
+                    .chain([finally_block.as_deref().and_then(block_span)]),
+            ),
+            Statement::With { manager, name, body } => {
+                cover([manager.span()].into_iter().chain(token_spans(name)).chain([block_span(body)]))
+            }
+            Statement::Break | Statement::Continue | Statement::RustInline { .. } | Statement::AsmInline { .. } => None,
+        }
+    }
+}
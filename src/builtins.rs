@@ -0,0 +1,508 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::bytecode::{Shared, Value};
+use crate::vm::VM;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub fn register_natives(vm: &mut VM) {
+    // Arrays, dictionaries and objects are shared between the variables
+    // holding them; `copy` makes a new one of the outermost, `deepcopy` of
+    // everything inside it as well
+    vm.register_native("copy", 1, |_vm, args| Ok(args[0].shallow_copy()));
+    vm.register_native("deepcopy", 1, |_vm, args| Ok(args[0].deep_copy()));
+
+    // `input(prompt)` reads a line from the host's input; null at end of input
+    vm.register_native("input", 1, |vm, args| {
+        if !matches!(args[0], Value::Null) {
+            vm.write_output_partial(&VM::format_value(&args[0]))?;
+        }
+        Ok(vm.read_input_line()?.map_or(Value::Null, Value::String))
+    });
+
+    vm.register_native("warn", 1, |vm, args| {
+        vm.warn(VM::format_value(&args[0]));
+        Ok(Value::Null)
+    });
+
+    vm.register_native("equals", 2, |_vm, args| {
+        Ok(Value::Boolean(VM::values_equal(&args[0], &args[1])))
+    });
+
+    vm.register_native("hash", 1, |_vm, args| {
+        // Keep the result within f64's exact integer range
+        hash_value(&args[0]).map(|hash| Value::Number((hash & ((1 << 53) - 1)) as f64))
+    });
+
+    // Non-throwing dictionary access. `dict_remove` and `dict_merge` return
+    // a changed copy and leave the dictionary as it was; `d.delete(key)`
+    // changes it in place.
+    vm.register_native("dict_get", 3, |_vm, args| {
+        let (dict, key) = dict_and_key("dict_get", &args)?;
+        Ok(dict.lock().unwrap().get(key).cloned().unwrap_or_else(|| args[2].clone()))
+    });
+
+    vm.register_native("dict_has", 2, |_vm, args| {
+        let (dict, key) = dict_and_key("dict_has", &args)?;
+        Ok(Value::Boolean(dict.lock().unwrap().contains_key(key)))
+    });
+
+    vm.register_native("dict_remove", 2, |_vm, args| {
+        let (dict, key) = dict_and_key("dict_remove", &args)?;
+        let mut dict = dict.lock().unwrap().clone();
+        dict.remove(key);
+        Ok(Value::dictionary(dict))
+    });
+
+    vm.register_native("dict_merge", 2, |_vm, args| {
+        match (&args[0], &args[1]) {
+            (Value::Dictionary(base), Value::Dictionary(overrides)) => {
+                let mut merged = base.lock().unwrap().clone();
+                let overrides = overrides.lock().unwrap().clone();
+                merged.extend(overrides);
+                Ok(Value::dictionary(merged))
+            }
+            _ => Err("TypeError: dict_merge expects two dictionaries".to_string()),
+        }
+    });
+
+    // `enumerate(iterable[, start])` and `zip(a, b, ...)` pair up what
+    // `for` loops over them would visit; zip stops at the shortest
+    vm.register_native("enumerate", 1..=2, enumerate);
+    vm.register_native("zip", 1.., zip);
+
+    // `range(stop)`, `range(start, stop)` or `range(start, stop, step)`
+    // counts lazily, so `for i in range(1000000)` doesn't build an array
+    vm.register_native("range", 1..=3, range);
+
+    // Aggregates over an iterable, each taking an optional key function
+    // whose result is used in place of the element: `sorted(people, age)`
+    vm.register_native("sorted", 1..=2, sorted);
+    vm.register_native("min", 1..=2, min);
+    vm.register_native("max", 1..=2, max);
+    vm.register_native("sum", 1..=2, sum);
+}
+
+pub fn enumerate(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let start = match args.as_slice() {
+        [_] => 0.0,
+        [_, Value::Number(start)] => *start,
+        _ => return Err("TypeError: enumerate expects (iterable) or (iterable, start number)".to_string()),
+    };
+    let elements = vm.iterate(args[0].clone())?;
+    Ok(Value::array(elements.into_iter().enumerate()
+        .map(|(i, element)| Value::Tuple(vec![Value::Number(start + i as f64), element]))
+        .collect()))
+}
+
+pub fn zip(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let columns = args.into_iter().map(|iterable| vm.iterate(iterable)).collect::<Result<Vec<_>, _>>()?;
+    let len = columns.iter().map(Vec::len).min().unwrap_or(0);
+    Ok(Value::array((0..len).map(|i| Value::Tuple(columns.iter().map(|column| column[i].clone()).collect())).collect()))
+}
+
+pub fn range(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let (start, stop, step) = match args.as_slice() {
+        [Value::Number(stop)] => (0.0, *stop, 1.0),
+        [Value::Number(start), Value::Number(stop)] => (*start, *stop, 1.0),
+        [Value::Number(start), Value::Number(stop), Value::Number(step)] => (*start, *stop, *step),
+        _ => return Err("TypeError: range expects (stop), (start, stop) or (start, stop, step) numbers".to_string()),
+    };
+    if step == 0.0 || !step.is_finite() {
+        return Err("TypeError: range step must be a non-zero number".to_string());
+    }
+    Ok(Value::Range { start, stop, step })
+}
+
+/// The elements of `args[0]` with their keys: the element itself, or what
+/// the key function `args[1]` returns for it.
+fn keyed(vm: &mut VM, name: &str, args: Vec<Value>) -> Result<Vec<(Value, Value)>, String> {
+    let mut args = args.into_iter();
+    let (Some(iterable), key, None) = (args.next(), args.next(), args.next()) else {
+        return Err(format!("TypeError: {} expects (iterable) or (iterable, key function)", name));
+    };
+    let elements = vm.iterate(iterable)?;
+    match key {
+        None | Some(Value::Null) => Ok(elements.into_iter().map(|element| (element.clone(), element)).collect()),
+        Some(key @ (Value::Function(_) | Value::Closure(_) | Value::NativeFunction(_))) => elements.into_iter()
+            .map(|element| Ok((vm.call_function(key.clone(), vec![element.clone()])?, element)))
+            .collect(),
+        Some(other) => Err(format!("TypeError: {} expects a key function, got {}", name, VM::format_value(&other))),
+    }
+}
+
+/// Order numbers, strings, bytes, and arrays or tuples of them element by
+/// element.
+fn compare(a: &Value, b: &Value) -> Result<Ordering, String> {
+    compare_within(a, b, &mut Vec::new())
+}
+
+/// [`compare`], with `comparing` holding the pairs of arrays being compared
+/// further up, so arrays that contain themselves are caught rather than
+/// compared forever.
+fn compare_within(a: &Value, b: &Value, comparing: &mut Vec<(usize, usize)>) -> Result<Ordering, String> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => Ok(a.total_cmp(b)),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (Value::Bytes(a), Value::Bytes(b)) => Ok(a.cmp(b)),
+        (Value::Array(a), Value::Array(b)) if Arc::ptr_eq(a, b) => Ok(Ordering::Equal),
+        (Value::Array(a_items), Value::Array(b_items)) => {
+            let pair = (Arc::as_ptr(a_items) as usize, Arc::as_ptr(b_items) as usize);
+            if comparing.contains(&pair) {
+                return Err("ValueError: Cannot compare arrays that contain themselves".to_string());
+            }
+            // Copy the elements out first, so comparing them locks nothing
+            let a_items = a_items.lock().unwrap().clone();
+            let b_items = b_items.lock().unwrap().clone();
+            comparing.push(pair);
+            let ordering = compare_within(&Value::Tuple(a_items), &Value::Tuple(b_items), comparing);
+            comparing.pop();
+            ordering
+        }
+        (Value::Tuple(a), Value::Tuple(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                match compare_within(a, b, comparing)? {
+                    Ordering::Equal => continue,
+                    unequal => return Ok(unequal),
+                }
+            }
+            Ok(a.len().cmp(&b.len()))
+        }
+        _ => Err(format!("TypeError: Cannot compare {} and {}", VM::format_value(a), VM::format_value(b))),
+    }
+}
+
+pub fn sorted(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let mut keyed = keyed(vm, "sorted", args)?;
+    let mut error = None;
+    keyed.sort_by(|(a, _), (b, _)| compare(a, b).unwrap_or_else(|e| {
+        error.get_or_insert(e);
+        Ordering::Equal
+    }));
+    match error {
+        Some(e) => Err(e),
+        None => Ok(Value::array(keyed.into_iter().map(|(_, element)| element).collect())),
+    }
+}
+
+/// The first element whose key orders as `wanted` against every other's.
+fn extreme(vm: &mut VM, name: &str, args: Vec<Value>, wanted: Ordering) -> Result<Value, String> {
+    let mut keyed = keyed(vm, name, args)?.into_iter();
+    let mut best = keyed.next().ok_or_else(|| format!("{} of an empty iterable", name))?;
+    for candidate in keyed {
+        if compare(&candidate.0, &best.0)? == wanted {
+            best = candidate;
+        }
+    }
+    Ok(best.1)
+}
+
+pub fn min(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    extreme(vm, "min", args, Ordering::Less)
+}
+
+pub fn max(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    extreme(vm, "max", args, Ordering::Greater)
+}
+
+pub fn sum(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    keyed(vm, "sum", args)?.into_iter().try_fold(0.0, |total, (key, _)| match key {
+        Value::Number(n) => Ok(total + n),
+        other => Err(format!("TypeError: sum expects numbers, got {}", VM::format_value(&other))),
+    }).map(Value::Number)
+}
+
+type Dict = Shared<HashMap<String, Value>>;
+
+fn dict_and_key<'a>(name: &str, args: &'a [Value]) -> Result<(&'a Dict, &'a str), String> {
+    match (&args[0], &args[1]) {
+        (Value::Dictionary(dict), Value::String(key)) => Ok((dict, key)),
+        (Value::Dictionary(_), _) => Err(format!("TypeError: {} expects a string key", name)),
+        _ => Err(format!("TypeError: {} expects a dictionary", name)),
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Stable structural hash of a value, consistent with [`VM::values_equal`].
+///
+/// The hash does not depend on process state, so it is identical across runs
+/// and can be persisted (e.g. as a cache key).
+pub fn hash_value(value: &Value) -> Result<u64, String> {
+    let mut hasher = Fnv(FNV_OFFSET);
+    hasher.value(value)?;
+    Ok(hasher.0)
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn tag(&mut self, tag: u8, len: usize) {
+        self.bytes(&[tag]);
+        self.bytes(&(len as u64).to_le_bytes());
+    }
+
+    fn value(&mut self, value: &Value) -> Result<(), String> {
+        match value {
+            Value::Null => self.tag(b'Z', 0),
+            Value::Boolean(b) => self.tag(if *b { b'T' } else { b'F' }, 0),
+            Value::Number(n) => {
+                // 0.0 == -0.0, so they must hash the same
+                let n = if *n == 0.0 { 0.0 } else { *n };
+                self.tag(b'N', 8);
+                self.bytes(&n.to_bits().to_le_bytes());
+            }
+            Value::String(s) => {
+                self.tag(b'S', s.len());
+                self.bytes(s.as_bytes());
+            }
+            Value::Bytes(bytes) => {
+                self.tag(b'B', bytes.len());
+                self.bytes(bytes);
+            }
+            // Already locked: being hashed further up, so it contains itself
+            Value::Array(items) => {
+                let items = items.try_lock().map_err(|_| "TypeError: unhashable type: an array that contains itself".to_string())?;
+                self.tag(b'A', items.len());
+                for item in items.iter() {
+                    self.value(item)?;
+                }
+            }
+            Value::Tuple(items) => {
+                self.tag(b'U', items.len());
+                for item in items {
+                    self.value(item)?;
+                }
+            }
+            Value::Dictionary(entries) => {
+                let entries = entries.try_lock().map_err(|_| "TypeError: unhashable type: a dictionary that contains itself".to_string())?;
+                self.tag(b'D', entries.len());
+                self.entries(&entries)?;
+            }
+            Value::Object { class_name, module, fields } => {
+                let fields = fields.try_lock().map_err(|_| format!("TypeError: unhashable type: a '{}' object that contains itself", class_name))?;
+                self.tag(b'O', fields.len());
+                self.value(&Value::String(class_name.clone()))?;
+                self.value(&module.clone().map_or(Value::Null, Value::String))?;
+                self.entries(&fields)?;
+            }
+            Value::Function(function) => return Err(format!("TypeError: unhashable type: function '{}'", function.name)),
+            Value::Closure(closure) => return Err(format!("TypeError: unhashable type: function '{}'", closure.function.name)),
+            Value::NativeFunction(function) => return Err(format!("TypeError: unhashable type: native function '{}'", function.name)),
+            Value::Class { name, .. } => return Err(format!("TypeError: unhashable type: class '{}'", name)),
+            Value::Module(name) => return Err(format!("TypeError: unhashable type: module '{}'", name)),
+            Value::Range { start, stop, step } => {
+                self.tag(b'R', 24);
+                for n in [start, stop, step] {
+                    self.bytes(&n.to_bits().to_le_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn entries(&mut self, entries: &HashMap<String, Value>) -> Result<(), String> {
+        // Key order is unspecified, so hash entries in sorted order
+        let mut keys: Vec<&String> = entries.keys().collect();
+        keys.sort();
+        for key in keys {
+            self.value(&Value::String(key.clone()))?;
+            self.value(&entries[key])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    fn run(source: &str) -> Grease {
+        let mut grease = Grease::new();
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        grease
+    }
+
+    #[test]
+    fn test_equals_compares_dictionaries_structurally() {
+        let grease = run("a = equals({\"x\": [1, 2], \"y\": 3}, {\"y\": 3, \"x\": [1, 2]})\nb = equals({\"x\": 1}, {\"x\": 2})\nc = {\"k\": null} == {\"k\": null}");
+        assert!(matches!(grease.vm.globals.get("a"), Some(Value::Boolean(true))));
+        assert!(matches!(grease.vm.globals.get("b"), Some(Value::Boolean(false))));
+        assert!(matches!(grease.vm.globals.get("c"), Some(Value::Boolean(true))));
+    }
+
+    #[test]
+    fn test_objects_compare_class_and_fields() {
+        let mut fields = HashMap::new();
+        fields.insert("x".to_string(), Value::Number(1.0));
+        let a = Value::object("Point", fields.clone());
+        let b = Value::object("Vector", fields.clone());
+        assert!(VM::values_equal(&a, &a.clone()));
+        assert!(VM::values_equal(&a, &Value::object("Point", fields)));
+        assert!(!VM::values_equal(&a, &b));
+    }
+
+    #[test]
+    fn test_hash_matches_equality() {
+        let grease = run("a = hash({\"x\": [1, \"two\"], \"y\": true})\nb = hash({\"y\": true, \"x\": [1, \"two\"]})\nc = hash(\"abc\")\nd = hash(0)\ne = hash(-0)");
+        let get = |name: &str| match grease.vm.globals.get(name) {
+            Some(Value::Number(n)) => *n,
+            other => panic!("Expected number for {}, got {:?}", name, other),
+        };
+        assert_eq!(get("a"), get("b"));
+        assert_ne!(get("a"), get("c"));
+        assert_eq!(get("d"), get("e"));
+        assert_eq!(hash_value(&Value::String("abc".to_string())).unwrap() & ((1 << 53) - 1), get("c") as u64);
+    }
+
+    #[test]
+    fn test_hash_rejects_functions() {
+        let mut grease = Grease::new();
+        let result = grease.run("hash(native_add)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(msg) if msg.contains("unhashable")));
+    }
+
+    #[test]
+    fn test_dict_helpers() {
+        let grease = run("d = {\"a\": 1, \"b\": 2}\nhit = dict_get(d, \"a\", 0)\nmiss = dict_get(d, \"z\", 0)\nhas = dict_has(d, \"b\")\nremoved = dict_remove(d, \"a\")\nmerged = dict_merge(d, {\"b\": 3, \"c\": 4})");
+        assert!(matches!(grease.vm.globals.get("hit"), Some(Value::Number(n)) if *n == 1.0));
+        assert!(matches!(grease.vm.globals.get("miss"), Some(Value::Number(n)) if *n == 0.0));
+        assert!(matches!(grease.vm.globals.get("has"), Some(Value::Boolean(true))));
+        assert!(matches!(grease.vm.globals.get("removed"), Some(Value::Dictionary(d)) if d.lock().unwrap().len() == 1 && !d.lock().unwrap().contains_key("a")));
+        match grease.vm.globals.get("merged") {
+            Some(Value::Dictionary(d)) => {
+                let d = d.lock().unwrap();
+                assert_eq!(d.len(), 3);
+                assert!(matches!(d.get("b"), Some(Value::Number(n)) if *n == 3.0));
+            }
+            other => panic!("Expected merged dictionary, got {:?}", other),
+        }
+        // The original dictionary is left untouched
+        assert!(matches!(grease.vm.globals.get("d"), Some(Value::Dictionary(d)) if d.lock().unwrap().len() == 2));
+    }
+
+    #[test]
+    fn test_copy_and_deepcopy() {
+        let grease = run("a = [1, [2, 3]]\nb = deepcopy(a)\nc = copy(a)\nsame = equals(a, b) and equals(a, c)");
+        assert!(matches!(grease.vm.globals.get("same"), Some(Value::Boolean(true))));
+    }
+
+    #[test]
+    fn test_deepcopy_keeps_cycles() {
+        let grease = run("a = [1]\na.push(a)\nb = deepcopy(a)\nb.push(2)\ninner = b[1]\ninner.push(3)\n\
+            class Node:\n    \"A node\"\nnode = new Node()\nnode.next = node\ncopied = deepcopy(node)\n\
+            linked = copied.next == copied and copied.next != node");
+        let formatted = |name| VM::format_value(grease.vm.globals.get(name).unwrap());
+        assert_eq!(formatted("a"), "[1, [...]]");
+        // The copy's inner array is the copy itself, not the original
+        assert_eq!(formatted("b"), "[1, [...], 2, 3]");
+        assert_eq!(formatted("linked"), "true");
+    }
+
+    #[test]
+    fn test_compare_rejects_arrays_that_contain_themselves() {
+        let grease = run("a = [1]\na.push(a)\nsame = sorted([a, a])");
+        assert!(matches!(grease.vm.globals.get("same"), Some(Value::Array(items)) if items.lock().unwrap().len() == 2));
+
+        let mut grease = Grease::new();
+        let result = grease.run("a = []\nb = [a]\na.push(b)\nsorted([a, b])").unwrap();
+        assert_eq!(result, InterpretResult::RuntimeError("ValueError: Cannot compare arrays that contain themselves".to_string()));
+    }
+
+    #[test]
+    fn test_enumerate_and_zip() {
+        let grease = run("total = 0\ntext = \"\"\n\
+            for pair in enumerate([\"a\", \"b\"], 1):\n    n, line = pair\n    total = total + n\n    text = text + line\n\
+            chars = enumerate(\"hi\")\n\
+            pairs = zip({\"y\": 2, \"x\": 1}, [10, 20, 30], \"ab\")");
+        let formatted = |name| VM::format_value(grease.vm.globals.get(name).unwrap());
+        assert_eq!((formatted("total"), formatted("text")), ("3".to_string(), "ab".to_string()));
+        assert_eq!(formatted("chars"), "[(0, h), (1, i)]");
+        assert_eq!(formatted("pairs"), "[(x, 10, a), (y, 20, b)]");
+
+        let mut grease = Grease::new();
+        let result = grease.run("zip()").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("zip expects")));
+        let result = grease.run("enumerate(5)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("Cannot iterate over 5")));
+    }
+
+    #[test]
+    fn test_range() {
+        let grease = run("total = 0\nfor i in range(1, 10, 2):\n    total = total + i\n\
+            down = []\nfor i in range(3, 0, -1):\n    down.push(i)\n\
+            for i in range(1000000000000):\n    if i == 3:\n        break\n\
+            r = range(5)\n\
+            facts = [r[1], r[-1], r.len(), r.contains(4), r.contains(5), r.contains(1.5), range(2, 2).len()]\n\
+            numbers = range(0, 1, 0.25).to_array()\n\
+            pairs = enumerate(range(2))\n\
+            same = range(3) == range(0, 3, 1)");
+        let formatted = |name| VM::format_value(grease.vm.globals.get(name).unwrap());
+        assert_eq!(formatted("total"), "25");
+        assert_eq!(formatted("down"), "[3, 2, 1]");
+        assert_eq!(formatted("i"), "3");
+        assert_eq!(formatted("r"), "range(0, 5)");
+        assert_eq!(formatted("facts"), "[1, 4, 5, true, false, false, 0]");
+        assert_eq!(formatted("numbers"), "[0, 0.25, 0.5, 0.75]");
+        assert_eq!(formatted("pairs"), "[(0, 0), (1, 1)]");
+        assert_eq!(formatted("same"), "true");
+
+        let mut grease = Grease::new();
+        let result = grease.run("range(1, 5, 0)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("range step must be a non-zero number")));
+        let result = grease.run("range(3)[3]").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("Index 3 out of bounds for range of length 3")));
+
+        // Longer than any array: indexing still works, making the numbers doesn't
+        let grease = run("big = range(100000000000000000000)\nends = [big[-100000000000000000000], big[-1]]");
+        assert_eq!(VM::format_value(grease.vm.globals.get("ends").unwrap()), "[0, 100000000000000000000]");
+        let mut grease = Grease::new();
+        for call in ["sum", "sorted", "min", "max"] {
+            let result = grease.run(&format!("{}(range(100000000000000000000))", call)).unwrap();
+            assert!(matches!(result, InterpretResult::RuntimeError(ref e) if e.contains("too large to make into an array")), "{}: {:?}", call, result);
+        }
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let grease = run("def age(person):\n    return person[\"age\"]\n\
+            def by_name_length(name):\n    return (len(name), name)\n\
+            people = [{\"name\": \"ann\", \"age\": 41}, {\"name\": \"bo\", \"age\": 29}]\n\
+            from py use len\n\
+            youngest = min(people, age)[\"name\"]\n\
+            oldest = max(people, age)[\"name\"]\n\
+            total = sum(people, age) + sum((1, 2))\n\
+            names = sorted([\"carla\", \"bo\", \"al\", \"di\"], by_name_length)\n\
+            numbers = sorted({\"b\": 1, \"a\": 2})\n\
+            first = max([3, 7, 7, 1])");
+        let formatted = |name| VM::format_value(grease.vm.globals.get(name).unwrap());
+        assert_eq!((formatted("youngest"), formatted("oldest")), ("bo".to_string(), "ann".to_string()));
+        assert_eq!(formatted("total"), "73");
+        assert_eq!(formatted("names"), "[al, bo, di, carla]");
+        assert_eq!(formatted("numbers"), "[a, b]");
+        assert_eq!(formatted("first"), "7");
+
+        let mut grease = Grease::new();
+        for (source, error) in [
+            ("sorted([1, \"a\"])", "Cannot compare"),
+            ("min([])", "min of an empty iterable"),
+            ("sum([\"a\"])", "sum expects numbers"),
+            ("max([1], 2)", "max expects a key function"),
+        ] {
+            let result = grease.run(source).unwrap();
+            assert!(matches!(&result, InterpretResult::RuntimeError(e) if e.contains(error)), "{}: {:?}", source, result);
+        }
+    }
+}
@@ -1,6 +1,9 @@
 // Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::source_map::{SourceLocation, SourceMap};
+use sha2::{Digest, Sha256};
+
 #[derive(Debug, Clone)]
 pub enum OpCode {
     // Constants
@@ -21,10 +24,16 @@ pub enum OpCode {
     JumpIfTrue,
     Loop,
     Dup,
+    ForIter,
+    GetIter,
 
     // Functions
     Call,
     Return,
+    Closure,
+    GetUpvalue,
+    SetUpvalue,
+    CloseUpvalue,
     
     // Operations
     Add,
@@ -35,8 +44,11 @@ pub enum OpCode {
     Negate,
     Array,
     Index,
+    Slice,
     Length,
     Dictionary,
+    Tuple,
+    Unpack,
 
     // Classes
     CreateClass,
@@ -65,6 +77,7 @@ pub enum OpCode {
     // Modules
     Import,
     GetModule,
+    LoadPlugin,
     
     // Inline Code
     RustInline,
@@ -75,12 +88,26 @@ pub enum OpCode {
     Catch,
     Throw,
     PopException,
+    Rethrow,
+    ExceptionMatches,
+
+    // Context managers
+    WithEnter,
+    WithExit,
+
+    // Jumps with 32-bit offsets, for code too large for the 16-bit forms
+    JumpLong,
+    JumpIfFalseLong,
+    JumpIfTrueLong,
+    LoopLong,
+    ForIterLong,
+    TryLong,
 }
 
 #[derive(Debug, Clone)]
 pub struct Class {
     pub name: String,
-    pub methods: std::collections::HashMap<String, usize>, // constant indices
+    pub methods: std::collections::HashMap<String, Value>, // Value::Function
     pub superclass: Option<String>,
 }
 
@@ -88,28 +115,224 @@ pub struct Class {
 pub enum Value {
     Number(f64),
     String(String),
+    /// Raw bytes, e.g. a `b"..."` literal or a file read with
+    /// `read_file_bytes`.
+    Bytes(Vec<u8>),
     Boolean(bool),
     Null,
     Function(Function),
+    /// A function with the variables it captured from enclosing functions.
+    /// Boxed, like the source map, to keep every stack slot small.
+    Closure(Box<Closure>),
     NativeFunction(NativeFunction),
-    Array(Vec<Value>),
+    /// Arrays, dictionaries and objects live on the heap: copying the value
+    /// copies a reference, so a change made through one variable is seen
+    /// through every other that holds it. `copy` and `deepcopy` make a
+    /// separate one.
+    Array(Shared<Vec<Value>>),
     Object {
         class_name: String,
-        fields: std::collections::HashMap<String, Value>,
+        /// Module whose namespace the class was defined in, or `None` for
+        /// the script's globals. Methods are looked up there, so classes
+        /// of the same name in different modules stay apart.
+        module: Option<String>,
+        fields: Shared<std::collections::HashMap<String, Value>>,
     },
-    Dictionary(std::collections::HashMap<String, Value>),
+    Dictionary(Shared<std::collections::HashMap<String, Value>>),
+    Tuple(Vec<Value>),
     Class {
         name: String,
-        methods: std::collections::HashMap<String, usize>, // constant indices
+        methods: std::collections::HashMap<String, Value>, // Value::Function
         superclass: Option<String>,
+        /// Module the class was defined in; its superclass name resolves
+        /// there, and its instances carry it.
+        module: Option<String>,
+    },
+    Module(String), // key into VM::modules
+    /// `range(start, stop, step)`: the numbers from `start` up to (or, with
+    /// a negative step, down to) `stop`, made one at a time as a `for` loop
+    /// asks for them.
+    Range {
+        start: f64,
+        stop: f64,
+        step: f64,
     },
 }
 
+/// Module of the classes natives make objects of, such as file handles.
+/// No script can `use` it or define a class in it, so scripts can't make
+/// objects that pass for a native's.
+pub const NATIVE_MODULE: &str = "<native>";
+
+/// Contents of an array, dictionary or object, shared by every value that
+/// refers to it. Don't hold the lock while running anything that could read
+/// the same value again, such as a script function or a nested value's
+/// formatting; copy out what's needed first.
+pub type Shared<T> = std::sync::Arc<std::sync::Mutex<T>>;
+
+impl Value {
+    pub fn array(items: Vec<Value>) -> Value {
+        Value::Array(Shared::new(std::sync::Mutex::new(items)))
+    }
+
+    pub fn dictionary(entries: std::collections::HashMap<String, Value>) -> Value {
+        Value::Dictionary(Shared::new(std::sync::Mutex::new(entries)))
+    }
+
+    pub fn object(class_name: &str, fields: std::collections::HashMap<String, Value>) -> Value {
+        Value::object_in(None, class_name, fields)
+    }
+
+    /// An object of a native class, which scripts can't forge; see
+    /// [`NATIVE_MODULE`].
+    pub fn native_object(class_name: &str, fields: std::collections::HashMap<String, Value>) -> Value {
+        Value::object_in(Some(NATIVE_MODULE.to_string()), class_name, fields)
+    }
+
+    /// Whether this is an object of native class `class_name`.
+    pub fn is_native_object(&self, class_name: &str) -> bool {
+        matches!(self, Value::Object { class_name: name, module: Some(module), .. } if name == class_name && module == NATIVE_MODULE)
+    }
+
+    /// An object of class `class_name` defined in `module`; see
+    /// [`Value::Object`].
+    pub fn object_in(module: Option<String>, class_name: &str, fields: std::collections::HashMap<String, Value>) -> Value {
+        Value::Object { class_name: class_name.to_string(), module, fields: Shared::new(std::sync::Mutex::new(fields)) }
+    }
+
+    /// A new array, dictionary or object with the same elements; the
+    /// elements themselves are still shared. Other values are returned as
+    /// they are.
+    pub fn shallow_copy(&self) -> Value {
+        match self {
+            Value::Array(items) => Value::array(items.lock().unwrap().clone()),
+            Value::Dictionary(entries) => Value::dictionary(entries.lock().unwrap().clone()),
+            Value::Object { class_name, module, fields } => Value::object_in(module.clone(), class_name, fields.lock().unwrap().clone()),
+            other => other.clone(),
+        }
+    }
+
+    /// A copy that shares nothing with the original, down to the innermost
+    /// element. A value that contains itself is copied into one that
+    /// contains the copy.
+    pub fn deep_copy(&self) -> Value {
+        self.deep_copy_into(&mut std::collections::HashMap::new())
+    }
+
+    /// [`Value::deep_copy`], with `copies` holding the copy of each
+    /// container already reached, by its address.
+    fn deep_copy_into(&self, copies: &mut std::collections::HashMap<usize, Value>) -> Value {
+        fn copy_entries(
+            entries: &Shared<std::collections::HashMap<String, Value>>,
+            copies: &mut std::collections::HashMap<usize, Value>,
+        ) -> std::collections::HashMap<String, Value> {
+            let entries = entries.lock().unwrap().clone();
+            entries.iter().map(|(key, value)| (key.clone(), value.deep_copy_into(copies))).collect()
+        }
+
+        // The empty copy is recorded before the contents are, so elements
+        // that lead back to the container get the copy
+        let address = match self {
+            Value::Array(items) => std::sync::Arc::as_ptr(items) as usize,
+            Value::Dictionary(entries) => std::sync::Arc::as_ptr(entries) as usize,
+            Value::Object { fields, .. } => std::sync::Arc::as_ptr(fields) as usize,
+            Value::Tuple(items) => return Value::Tuple(items.iter().map(|item| item.deep_copy_into(copies)).collect()),
+            other => return other.clone(),
+        };
+        if let Some(copy) = copies.get(&address) {
+            return copy.clone();
+        }
+        let copy = match self {
+            Value::Array(_) => Value::array(Vec::new()),
+            Value::Object { class_name, module, .. } => Value::object_in(module.clone(), class_name, std::collections::HashMap::new()),
+            _ => Value::dictionary(std::collections::HashMap::new()),
+        };
+        copies.insert(address, copy.clone());
+        match (self, &copy) {
+            (Value::Array(items), Value::Array(copied)) => {
+                let items = items.lock().unwrap().clone();
+                let items = items.iter().map(|item| item.deep_copy_into(copies)).collect();
+                *copied.lock().unwrap() = items;
+            }
+            (Value::Dictionary(entries), Value::Dictionary(copied))
+            | (Value::Object { fields: entries, .. }, Value::Object { fields: copied, .. }) => {
+                let entries = copy_entries(entries, copies);
+                *copied.lock().unwrap() = entries;
+            }
+            _ => unreachable!("copies are made with the original's kind"),
+        }
+        copy
+    }
+}
+
+/// Signature of a Rust function callable from scripts.
+pub type NativeFn = fn(&mut crate::vm::VM, Vec<Value>) -> Result<Value, String>;
+
+/// Arity of a native that takes any number of arguments and checks them
+/// itself.
+pub const VARIADIC: usize = usize::MAX;
+
+/// How many arguments a native takes: a count such as `2`, a range such as
+/// `1..=3` for optional arguments, `1..` for at least one, or [`VARIADIC`].
+/// The VM checks it before the native runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    /// `usize::MAX` when there's no limit.
+    pub max: usize,
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        (self.min..=self.max).contains(&count)
+    }
+
+    /// The count, if the native takes exactly one number of arguments.
+    pub fn exact(&self) -> Option<usize> {
+        (self.min == self.max).then_some(self.min)
+    }
+}
+
+impl From<usize> for Arity {
+    fn from(count: usize) -> Self {
+        match count {
+            VARIADIC => Arity { min: 0, max: usize::MAX },
+            count => Arity { min: count, max: count },
+        }
+    }
+}
+
+impl From<std::ops::RangeInclusive<usize>> for Arity {
+    fn from(range: std::ops::RangeInclusive<usize>) -> Self {
+        Arity { min: *range.start(), max: *range.end() }
+    }
+}
+
+impl From<std::ops::RangeFrom<usize>> for Arity {
+    fn from(range: std::ops::RangeFrom<usize>) -> Self {
+        Arity { min: range.start, max: usize::MAX }
+    }
+}
+
+/// As error messages put it: "1 argument", "1 to 3 arguments", "at least 1
+/// argument".
+impl std::fmt::Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let noun = |count: usize| if count == 1 { "argument" } else { "arguments" };
+        match (self.min, self.max) {
+            (min, max) if min == max => write!(f, "{} {}", min, noun(min)),
+            (0, usize::MAX) => write!(f, "any number of arguments"),
+            (min, usize::MAX) => write!(f, "at least {} {}", min, noun(min)),
+            (min, max) => write!(f, "{} to {} arguments", min, max),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NativeFunction {
     pub name: String,
-    pub arity: usize,
-    pub function: fn(&mut crate::vm::VM, Vec<Value>) -> Result<Value, String>,
+    pub arity: Arity,
+    pub function: NativeFn,
 }
 
 #[derive(Debug, Clone)]
@@ -117,13 +340,47 @@ pub struct Function {
     pub name: String,
     pub arity: usize,
     pub chunk: Chunk,
+    /// Module the function was compiled in; its global reads and writes
+    /// resolve in that module's namespace first.
+    pub module: Option<String>,
+}
+
+/// Made by `CLOSURE`, which is followed by the function's constant index, the
+/// number of variables it captures, and for each one a byte that is 1 for a
+/// local slot of the enclosing function (0 for one of its upvalues) and the
+/// slot or upvalue index.
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub function: Function,
+    pub upvalues: Vec<Upvalue>,
+}
+
+/// A captured variable, shared by the function that declared it and every
+/// closure that captured it.
+pub type Upvalue = std::sync::Arc<std::sync::Mutex<UpvalueCell>>;
+
+#[derive(Debug, Clone)]
+pub enum UpvalueCell {
+    /// The variable's scope is still running; it lives in this absolute
+    /// stack slot.
+    Open(usize),
+    /// The scope has ended and the value moved here.
+    Closed(Value),
 }
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
     pub code: Vec<u8>,
     pub constants: Vec<Value>,
-    pub lines: Vec<usize>,
+    /// Where each byte of `code` came from in the source. Boxed to keep
+    /// `Value::Function`, and so every stack slot, small.
+    pub source_map: Box<SourceMap>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chunk {
@@ -131,13 +388,13 @@ impl Chunk {
         Chunk {
             code: Vec::with_capacity(256),
             constants: Vec::with_capacity(64),
-            lines: Vec::with_capacity(256),
+            source_map: Box::default(),
         }
     }
 
-    pub fn write(&mut self, byte: u8, line: usize) {
+    pub fn write(&mut self, byte: u8, location: SourceLocation) {
+        self.source_map.record(self.code.len(), location);
         self.code.push(byte);
-        self.lines.push(line);
     }
 
     pub fn add_constant(&mut self, value: Value) -> usize {
@@ -146,106 +403,169 @@ impl Chunk {
     }
 
     pub fn disassemble(&self, name: &str) {
-        println!("== {} ==", name);
-        
+        print!("{}", self.disassembly(name));
+    }
+
+    /// The listing `disassemble` prints: one instruction per line with its
+    /// offset and source line, followed by the functions and methods the
+    /// chunk defines.
+    pub fn disassembly(&self, name: &str) -> String {
+        let mut out = format!("== {} ==\n", name);
         let mut offset = 0;
         while offset < self.code.len() {
-            offset = self.disassemble_instruction(offset);
+            offset = self.disassemble_instruction(offset, &mut out);
         }
+        for constant in &self.constants {
+            match constant {
+                Value::Function(function) => out.push_str(&function.chunk.disassembly(&function.name)),
+                Value::Class { name: class, methods, .. } => {
+                    let mut methods: Vec<_> = methods.iter().collect();
+                    methods.sort_by(|a, b| a.0.cmp(b.0));
+                    for (method, value) in methods {
+                        if let Value::Function(function) = value {
+                            out.push_str(&function.chunk.disassembly(&format!("{}.{}", class, method)));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        out
     }
 
-    fn disassemble_instruction(&self, offset: usize) -> usize {
-        print!("{:04} ", offset);
+    fn disassemble_instruction(&self, offset: usize, out: &mut String) -> usize {
+        out.push_str(&format!("{:04} ", offset));
         
-        if offset > 0 && self.lines[offset] == self.lines[offset - 1] {
-            print!("   | ");
+        let line = |offset| self.source_map.lookup(offset).map_or(0, |location| location.line);
+        if offset > 0 && line(offset) == line(offset - 1) {
+            out.push_str("   | ");
         } else {
-            print!("{:4} ", self.lines[offset]);
+            out.push_str(&format!("{:4} ", line(offset)));
         }
         
         let instruction = self.code[offset];
         match OpCode::from_byte(instruction) {
             Some(op) => match op {
-                OpCode::Constant => self.constant_instruction("CONSTANT", offset),
-                OpCode::Null => self.simple_instruction("NULL", offset),
-                OpCode::True => self.simple_instruction("TRUE", offset),
-                OpCode::False => self.simple_instruction("FALSE", offset),
-                OpCode::GetGlobal => self.constant_instruction("GET_GLOBAL", offset),
-                OpCode::SetGlobal => self.constant_instruction("SET_GLOBAL", offset),
-                OpCode::GetLocal => self.byte_instruction("GET_LOCAL", offset),
-                OpCode::SetLocal => self.byte_instruction("SET_LOCAL", offset),
-                OpCode::Jump => self.jump_instruction("JUMP", 1, offset),
-                OpCode::JumpIfFalse => self.jump_instruction("JUMP_IF_FALSE", 1, offset),
-                OpCode::JumpIfTrue => self.jump_instruction("JUMP_IF_TRUE", 1, offset),
-                OpCode::Loop => self.jump_instruction("LOOP", -1, offset),
-                OpCode::Dup => self.simple_instruction("DUP", offset),
-                OpCode::Call => self.byte_instruction("CALL", offset),
-                OpCode::Return => self.simple_instruction("RETURN", offset),
-                OpCode::Add => self.simple_instruction("ADD", offset),
-                OpCode::Subtract => self.simple_instruction("SUBTRACT", offset),
-                OpCode::Multiply => self.simple_instruction("MULTIPLY", offset),
-                OpCode::Divide => self.simple_instruction("DIVIDE", offset),
-                OpCode::Modulo => self.simple_instruction("MODULO", offset),
-                OpCode::Negate => self.simple_instruction("NEGATE", offset),
-                OpCode::Array => self.byte_instruction("ARRAY", offset),
-                OpCode::Index => self.simple_instruction("INDEX", offset),
-                OpCode::Length => self.simple_instruction("LENGTH", offset),
-                OpCode::Dictionary => self.byte_instruction("DICTIONARY", offset),
-                OpCode::CreateClass => self.simple_instruction("CREATE_CLASS", offset),
-                OpCode::CreateInstance => self.simple_instruction("CREATE_INSTANCE", offset),
-                OpCode::GetProperty => self.simple_instruction("GET_PROPERTY", offset),
-                OpCode::SetProperty => self.simple_instruction("SET_PROPERTY", offset),
-                OpCode::CallMethod => self.simple_instruction("CALL_METHOD", offset),
-                OpCode::GetSuper => self.simple_instruction("GET_SUPER", offset),
-                OpCode::Equal => self.simple_instruction("EQUAL", offset),
-                OpCode::NotEqual => self.simple_instruction("NOT_EQUAL", offset),
-                OpCode::Less => self.simple_instruction("LESS", offset),
-                OpCode::LessEqual => self.simple_instruction("LESS_EQUAL", offset),
-                OpCode::Greater => self.simple_instruction("GREATER", offset),
-                OpCode::GreaterEqual => self.simple_instruction("GREATER_EQUAL", offset),
-                OpCode::Not => self.simple_instruction("NOT", offset),
-                OpCode::And => self.simple_instruction("AND", offset),
-                OpCode::Or => self.simple_instruction("OR", offset),
-                OpCode::Pop => self.simple_instruction("POP", offset),
-                OpCode::Import => self.constant_instruction("IMPORT", offset),
-                OpCode::GetModule => self.constant_instruction("GET_MODULE", offset),
-                OpCode::RustInline => self.constant_instruction("RUST_INLINE", offset),
-                OpCode::AsmInline => self.constant_instruction("ASM_INLINE", offset),
-                OpCode::Try => self.jump_instruction("TRY", 1, offset),
-                OpCode::Catch => self.simple_instruction("CATCH", offset),
-                OpCode::Throw => self.simple_instruction("THROW", offset),
-                OpCode::PopException => self.simple_instruction("POP_EXCEPTION", offset),
+                OpCode::Constant => self.constant_instruction("CONSTANT", offset, out),
+                OpCode::Null => self.simple_instruction("NULL", offset, out),
+                OpCode::True => self.simple_instruction("TRUE", offset, out),
+                OpCode::False => self.simple_instruction("FALSE", offset, out),
+                OpCode::GetGlobal => self.constant_instruction("GET_GLOBAL", offset, out),
+                OpCode::SetGlobal => self.constant_instruction("SET_GLOBAL", offset, out),
+                OpCode::GetLocal => self.byte_instruction("GET_LOCAL", offset, out),
+                OpCode::SetLocal => self.byte_instruction("SET_LOCAL", offset, out),
+                OpCode::Jump => self.jump_instruction("JUMP", 1, offset, out),
+                OpCode::JumpIfFalse => self.jump_instruction("JUMP_IF_FALSE", 1, offset, out),
+                OpCode::JumpIfTrue => self.jump_instruction("JUMP_IF_TRUE", 1, offset, out),
+                OpCode::Loop => self.jump_instruction("LOOP", -1, offset, out),
+                OpCode::JumpLong => self.long_jump_instruction("JUMP_LONG", 1, offset, out),
+                OpCode::JumpIfFalseLong => self.long_jump_instruction("JUMP_IF_FALSE_LONG", 1, offset, out),
+                OpCode::JumpIfTrueLong => self.long_jump_instruction("JUMP_IF_TRUE_LONG", 1, offset, out),
+                OpCode::LoopLong => self.long_jump_instruction("LOOP_LONG", -1, offset, out),
+                OpCode::Dup => self.simple_instruction("DUP", offset, out),
+                OpCode::ForIter => self.jump_instruction("FOR_ITER", 1, offset, out),
+                OpCode::ForIterLong => self.long_jump_instruction("FOR_ITER_LONG", 1, offset, out),
+                OpCode::Call => self.byte_instruction("CALL", offset, out),
+                OpCode::Return => self.simple_instruction("RETURN", offset, out),
+                OpCode::Closure => self.closure_instruction(offset, out),
+                OpCode::GetUpvalue => self.byte_instruction("GET_UPVALUE", offset, out),
+                OpCode::SetUpvalue => self.byte_instruction("SET_UPVALUE", offset, out),
+                OpCode::CloseUpvalue => self.simple_instruction("CLOSE_UPVALUE", offset, out),
+                OpCode::Add => self.simple_instruction("ADD", offset, out),
+                OpCode::Subtract => self.simple_instruction("SUBTRACT", offset, out),
+                OpCode::Multiply => self.simple_instruction("MULTIPLY", offset, out),
+                OpCode::Divide => self.simple_instruction("DIVIDE", offset, out),
+                OpCode::Modulo => self.simple_instruction("MODULO", offset, out),
+                OpCode::Negate => self.simple_instruction("NEGATE", offset, out),
+                OpCode::Array => self.byte_instruction("ARRAY", offset, out),
+                OpCode::Index => self.simple_instruction("INDEX", offset, out),
+                OpCode::Slice => self.simple_instruction("SLICE", offset, out),
+                OpCode::GetIter => self.simple_instruction("GET_ITER", offset, out),
+                OpCode::Length => self.simple_instruction("LENGTH", offset, out),
+                OpCode::Dictionary => self.byte_instruction("DICTIONARY", offset, out),
+                OpCode::Tuple => self.byte_instruction("TUPLE", offset, out),
+                OpCode::Unpack => self.byte_instruction("UNPACK", offset, out),
+                OpCode::CreateClass => self.simple_instruction("CREATE_CLASS", offset, out),
+                OpCode::CreateInstance => self.byte_instruction("CREATE_INSTANCE", offset, out),
+                OpCode::GetProperty => self.simple_instruction("GET_PROPERTY", offset, out),
+                OpCode::SetProperty => self.simple_instruction("SET_PROPERTY", offset, out),
+                OpCode::CallMethod => self.byte_instruction("CALL_METHOD", offset, out),
+                OpCode::GetSuper => self.simple_instruction("GET_SUPER", offset, out),
+                OpCode::Equal => self.simple_instruction("EQUAL", offset, out),
+                OpCode::NotEqual => self.simple_instruction("NOT_EQUAL", offset, out),
+                OpCode::Less => self.simple_instruction("LESS", offset, out),
+                OpCode::LessEqual => self.simple_instruction("LESS_EQUAL", offset, out),
+                OpCode::Greater => self.simple_instruction("GREATER", offset, out),
+                OpCode::GreaterEqual => self.simple_instruction("GREATER_EQUAL", offset, out),
+                OpCode::Not => self.simple_instruction("NOT", offset, out),
+                OpCode::And => self.simple_instruction("AND", offset, out),
+                OpCode::Or => self.simple_instruction("OR", offset, out),
+                OpCode::Pop => self.simple_instruction("POP", offset, out),
+                OpCode::Import => self.constant_instruction("IMPORT", offset, out),
+                OpCode::LoadPlugin => self.constant_instruction("LOAD_PLUGIN", offset, out),
+                OpCode::GetModule => self.constant_instruction("GET_MODULE", offset, out),
+                OpCode::RustInline => self.constant_instruction("RUST_INLINE", offset, out),
+                OpCode::AsmInline => self.constant_instruction("ASM_INLINE", offset, out),
+                OpCode::Try => self.jump_instruction("TRY", 1, offset, out),
+                OpCode::TryLong => self.long_jump_instruction("TRY_LONG", 1, offset, out),
+                OpCode::Catch => self.simple_instruction("CATCH", offset, out),
+                OpCode::Throw => self.simple_instruction("THROW", offset, out),
+                OpCode::PopException => self.simple_instruction("POP_EXCEPTION", offset, out),
+                OpCode::Rethrow => self.simple_instruction("RETHROW", offset, out),
+                OpCode::ExceptionMatches => self.byte_instruction("EXCEPTION_MATCHES", offset, out),
+                OpCode::WithEnter => self.simple_instruction("WITH_ENTER", offset, out),
+                OpCode::WithExit => self.byte_instruction("WITH_EXIT", offset, out),
             },
             None => {
-                println!("Unknown opcode {}", instruction);
+                out.push_str(&format!("Unknown opcode {}\n", instruction));
                 offset + 1
             }
         }
     }
 
-    fn simple_instruction(&self, name: &str, offset: usize) -> usize {
-        println!("{}", name);
+    fn simple_instruction(&self, name: &str, offset: usize, out: &mut String) -> usize {
+        out.push_str(&format!("{}\n", name));
         offset + 1
     }
 
-    fn constant_instruction(&self, name: &str, offset: usize) -> usize {
+    fn constant_instruction(&self, name: &str, offset: usize, out: &mut String) -> usize {
         let constant = self.code[offset + 1];
-        print!("{:16} {:4} '", name, constant);
-        println!("{:?}'", self.constants[constant as usize]);
+        match &self.constants[constant as usize] {
+            // Listed on their own after the chunk
+            Value::Function(function) => out.push_str(&format!("{:16} {:4} <fn {}>\n", name, constant, function.name)),
+            Value::Class { name: class, .. } => out.push_str(&format!("{:16} {:4} <class {}>\n", name, constant, class)),
+            value => out.push_str(&format!("{:16} {:4} '{:?}'\n", name, constant, value)),
+        }
         offset + 2
     }
 
-    fn byte_instruction(&self, name: &str, offset: usize) -> usize {
+    fn closure_instruction(&self, offset: usize, out: &mut String) -> usize {
+        let count = self.code[offset + 2] as usize;
+        let captures: Vec<String> = self.code[offset + 3..offset + 3 + 2 * count].chunks(2)
+            .map(|capture| format!("{} {}", if capture[0] == 1 { "local" } else { "upvalue" }, capture[1]))
+            .collect();
+        let constant = self.code[offset + 1];
+        out.push_str(&format!("{:16} {:4} [{}]\n", "CLOSURE", constant, captures.join(", ")));
+        offset + 3 + 2 * count
+    }
+
+    fn byte_instruction(&self, name: &str, offset: usize, out: &mut String) -> usize {
         let slot = self.code[offset + 1];
-        println!("{:16} {:4}", name, slot);
+        out.push_str(&format!("{:16} {:4}\n", name, slot));
         offset + 2
     }
 
-    fn jump_instruction(&self, name: &str, sign: i16, offset: usize) -> usize {
-        let jump = ((self.code[offset + 1] as u16) << 8) | (self.code[offset + 2] as u16);
-        println!("{:16} {:4} -> {}", name, offset, (offset as i16) + sign * (jump as i16));
+    fn jump_instruction(&self, name: &str, sign: isize, offset: usize, out: &mut String) -> usize {
+        let jump = u16::from_be_bytes([self.code[offset + 1], self.code[offset + 2]]);
+        out.push_str(&format!("{:16} {:4} -> {}\n", name, offset, (offset + 3) as isize + sign * jump as isize));
         offset + 3
     }
+
+    fn long_jump_instruction(&self, name: &str, sign: isize, offset: usize, out: &mut String) -> usize {
+        let jump = u32::from_be_bytes([self.code[offset + 1], self.code[offset + 2], self.code[offset + 3], self.code[offset + 4]]);
+        out.push_str(&format!("{:16} {:4} -> {}\n", name, offset, (offset + 5) as isize + sign * jump as isize));
+        offset + 5
+    }
 }
 
 impl OpCode {
@@ -300,6 +620,26 @@ impl OpCode {
             OpCode::Catch => 55,
             OpCode::Throw => 56,
             OpCode::PopException => 57,
+            OpCode::Tuple => 58,
+            OpCode::Unpack => 59,
+            OpCode::ForIter => 60,
+            OpCode::Rethrow => 61,
+            OpCode::WithEnter => 62,
+            OpCode::WithExit => 63,
+            OpCode::ExceptionMatches => 64,
+            OpCode::JumpLong => 65,
+            OpCode::JumpIfFalseLong => 66,
+            OpCode::JumpIfTrueLong => 67,
+            OpCode::LoopLong => 68,
+            OpCode::ForIterLong => 69,
+            OpCode::TryLong => 70,
+            OpCode::LoadPlugin => 71,
+            OpCode::Closure => 72,
+            OpCode::GetUpvalue => 73,
+            OpCode::SetUpvalue => 74,
+            OpCode::CloseUpvalue => 75,
+            OpCode::Slice => 76,
+            OpCode::GetIter => 77,
         }
     }
 
@@ -354,7 +694,359 @@ impl OpCode {
             55 => Some(OpCode::Catch),
             56 => Some(OpCode::Throw),
             57 => Some(OpCode::PopException),
+            58 => Some(OpCode::Tuple),
+            59 => Some(OpCode::Unpack),
+            60 => Some(OpCode::ForIter),
+            61 => Some(OpCode::Rethrow),
+            62 => Some(OpCode::WithEnter),
+            63 => Some(OpCode::WithExit),
+            64 => Some(OpCode::ExceptionMatches),
+            65 => Some(OpCode::JumpLong),
+            66 => Some(OpCode::JumpIfFalseLong),
+            67 => Some(OpCode::JumpIfTrueLong),
+            68 => Some(OpCode::LoopLong),
+            69 => Some(OpCode::ForIterLong),
+            70 => Some(OpCode::TryLong),
+            71 => Some(OpCode::LoadPlugin),
+            72 => Some(OpCode::Closure),
+            73 => Some(OpCode::GetUpvalue),
+            74 => Some(OpCode::SetUpvalue),
+            75 => Some(OpCode::CloseUpvalue),
+            76 => Some(OpCode::Slice),
+            77 => Some(OpCode::GetIter),
             _ => None,
         }
     }
-}
\ No newline at end of file
+
+    /// The 32-bit-offset form of a jump instruction; other instructions are
+    /// returned unchanged.
+    pub fn long_form(self) -> Self {
+        match self {
+            OpCode::Jump => OpCode::JumpLong,
+            OpCode::JumpIfFalse => OpCode::JumpIfFalseLong,
+            OpCode::JumpIfTrue => OpCode::JumpIfTrueLong,
+            OpCode::Loop => OpCode::LoopLong,
+            OpCode::ForIter => OpCode::ForIterLong,
+            OpCode::Try => OpCode::TryLong,
+            other => other,
+        }
+    }
+
+    /// Bytes of operand that follow the instruction. `CLOSURE` has two more
+    /// for each variable it captures after these.
+    pub fn operand_width(&self) -> usize {
+        match self {
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfTrue | OpCode::Loop | OpCode::ForIter | OpCode::Try => 2,
+            OpCode::JumpLong | OpCode::JumpIfFalseLong | OpCode::JumpIfTrueLong | OpCode::LoopLong | OpCode::ForIterLong | OpCode::TryLong => 4,
+            OpCode::Constant | OpCode::GetGlobal | OpCode::SetGlobal | OpCode::GetLocal | OpCode::SetLocal
+            | OpCode::Call | OpCode::CreateInstance | OpCode::CallMethod | OpCode::Array | OpCode::Tuple
+            | OpCode::Dictionary | OpCode::Unpack | OpCode::RustInline | OpCode::AsmInline
+            | OpCode::ExceptionMatches | OpCode::WithExit | OpCode::Import | OpCode::LoadPlugin
+            | OpCode::GetUpvalue | OpCode::SetUpvalue => 1,
+            OpCode::Closure => 2,
+            _ => 0,
+        }
+    }
+
+    pub fn is_long_jump(&self) -> bool {
+        matches!(self, OpCode::JumpLong | OpCode::JumpIfFalseLong | OpCode::JumpIfTrueLong | OpCode::LoopLong | OpCode::ForIterLong | OpCode::TryLong)
+    }
+}
+/// First bytes of a compiled `.gbc` file.
+pub const BYTECODE_MAGIC: &[u8; 4] = b"GBC\0";
+/// Layout of compiled files; bump it whenever the layout changes.
+const BYTECODE_FORMAT: u8 = 3;
+
+/// Compiled files are laid out as the magic bytes, the format byte, the
+/// Grease version that wrote them, SHA-256 hashes of the opcode set and of
+/// the source, then the top-level chunk. A chunk is its code, its constants
+/// and its source map; lengths and numbers are little-endian `u64`s and
+/// strings are UTF-8 after their length. Opcodes aren't stable between
+/// releases, so only a version with the same opcodes loads a file.
+impl Chunk {
+    /// Whether `bytes` hold a compiled file rather than source.
+    pub fn is_compiled(bytes: &[u8]) -> bool {
+        bytes.starts_with(BYTECODE_MAGIC)
+    }
+
+    /// The chunk, compiled from `source`, as a compiled file. Fails if a
+    /// constant only exists at runtime, such as a native function.
+    pub fn to_bytes(&self, source: &str) -> Result<Vec<u8>, String> {
+        let mut out = BYTECODE_MAGIC.to_vec();
+        out.push(BYTECODE_FORMAT);
+        write_str(&mut out, crate::VERSION);
+        out.extend_from_slice(&opcode_set_hash());
+        out.extend_from_slice(&Sha256::digest(source));
+        write_chunk(&mut out, self)?;
+        Ok(out)
+    }
+
+    /// Load a compiled file. The bytecode isn't verified; run it through
+    /// [`crate::verifier::verify`] before trusting it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Chunk, String> {
+        let mut reader = Reader { bytes, position: 0, depth: 0 };
+        reader.header()?;
+        let chunk = reader.chunk()?;
+        if reader.position != bytes.len() {
+            return Err("Compiled file has trailing bytes".to_string());
+        }
+        Ok(chunk)
+    }
+
+    /// Whether the compiled file `bytes` loads in this version and was
+    /// compiled from `source` as it is now.
+    pub fn is_current(bytes: &[u8], source: &str) -> bool {
+        let mut reader = Reader { bytes, position: 0, depth: 0 };
+        reader.header().is_ok_and(|source_hash| source_hash == Sha256::digest(source).as_slice())
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u64).to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, text: &str) {
+    write_len(out, text.len());
+    out.extend_from_slice(text.as_bytes());
+}
+
+fn write_option(out: &mut Vec<u8>, text: Option<&str>) {
+    match text {
+        Some(text) => {
+            out.push(1);
+            write_str(out, text);
+        }
+        None => out.push(0),
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, chunk: &Chunk) -> Result<(), String> {
+    write_len(out, chunk.code.len());
+    out.extend_from_slice(&chunk.code);
+    write_len(out, chunk.constants.len());
+    for constant in &chunk.constants {
+        write_value(out, constant)?;
+    }
+    write_option(out, chunk.source_map.file.as_deref());
+    write_option(out, chunk.source_map.function.as_deref());
+    write_len(out, chunk.source_map.entries().len());
+    for (offset, location) in chunk.source_map.entries() {
+        write_len(out, *offset);
+        write_len(out, location.line);
+        write_len(out, location.column);
+    }
+    Ok(())
+}
+
+/// Fields in name order, so the same chunk always writes the same bytes.
+fn write_fields(out: &mut Vec<u8>, fields: &std::collections::HashMap<String, Value>) -> Result<(), String> {
+    let mut names: Vec<&String> = fields.keys().collect();
+    names.sort();
+    write_len(out, names.len());
+    for name in names {
+        write_str(out, name);
+        write_value(out, &fields[name])?;
+    }
+    Ok(())
+}
+
+fn write_values(out: &mut Vec<u8>, items: &[Value]) -> Result<(), String> {
+    write_len(out, items.len());
+    items.iter().try_for_each(|item| write_value(out, item))
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) -> Result<(), String> {
+    match value {
+        Value::Null => out.push(0),
+        Value::Boolean(b) => out.extend_from_slice(&[1, *b as u8]),
+        Value::Number(n) => {
+            out.push(2);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        Value::String(s) => {
+            out.push(3);
+            write_str(out, s);
+        }
+        Value::Bytes(bytes) => {
+            out.push(4);
+            write_len(out, bytes.len());
+            out.extend_from_slice(bytes);
+        }
+        Value::Function(function) => {
+            out.push(5);
+            write_str(out, &function.name);
+            write_len(out, function.arity);
+            write_option(out, function.module.as_deref());
+            write_chunk(out, &function.chunk)?;
+        }
+        Value::Module(name) => {
+            out.push(6);
+            write_str(out, name);
+        }
+        Value::Class { name, methods, superclass, module } => {
+            out.push(7);
+            write_str(out, name);
+            write_option(out, superclass.as_deref());
+            write_option(out, module.as_deref());
+            write_fields(out, methods)?;
+        }
+        Value::Array(items) => {
+            out.push(8);
+            write_values(out, &items.lock().unwrap())?;
+        }
+        Value::Tuple(items) => {
+            out.push(9);
+            write_values(out, items)?;
+        }
+        Value::Dictionary(entries) => {
+            out.push(10);
+            write_fields(out, &entries.lock().unwrap())?;
+        }
+        Value::Object { class_name, module, fields } => {
+            out.push(11);
+            write_str(out, class_name);
+            write_option(out, module.as_deref());
+            write_fields(out, &fields.lock().unwrap())?;
+        }
+        Value::NativeFunction(native) => return Err(format!("Can't compile native function '{}' into a file", native.name)),
+        Value::Closure(closure) => return Err(format!("Can't compile closure '{}' into a file", closure.function.name)),
+        Value::Range { .. } => return Err("Can't compile a range into a file".to_string()),
+    }
+    Ok(())
+}
+
+/// A hash of every opcode's number and name, which changes whenever one is
+/// added, removed or renumbered.
+fn opcode_set_hash() -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for byte in 0..=u8::MAX {
+        if let Some(op) = OpCode::from_byte(byte) {
+            hasher.update(format!("{} {:?}\n", byte, op));
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// How deeply constants may nest (functions within functions, arrays
+/// within arrays) in a compiled file; reading each level takes stack.
+const MAX_CONSTANT_DEPTH: usize = 256;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    /// Values being read around the current one.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Check the header is one this version loads, returning the hash of
+    /// the source the file was compiled from.
+    fn header(&mut self) -> Result<&'a [u8], String> {
+        if self.take(BYTECODE_MAGIC.len())? != BYTECODE_MAGIC {
+            return Err("Not a compiled Grease file".to_string());
+        }
+        let format = self.byte()?;
+        let version = self.string()?;
+        if format != BYTECODE_FORMAT || version != crate::VERSION || self.take(32)? != opcode_set_hash() {
+            return Err(format!("Compiled by Grease {}, which this version ({}) can't load; compile the source again", version, crate::VERSION));
+        }
+        self.take(32)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(n).filter(|&end| end <= self.bytes.len())
+            .ok_or("Compiled file is truncated")?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().expect("took 8 bytes")))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        usize::try_from(self.u64()?).map_err(|_| "Compiled file has an impossible length".to_string())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "Compiled file has a string that isn't UTF-8".to_string())
+    }
+
+    fn option(&mut self) -> Result<Option<String>, String> {
+        match self.byte()? {
+            0 => Ok(None),
+            _ => self.string().map(Some),
+        }
+    }
+
+    fn chunk(&mut self) -> Result<Chunk, String> {
+        let len = self.len()?;
+        let code = self.take(len)?.to_vec();
+        let constants = self.values()?;
+        let mut source_map = SourceMap::default();
+        source_map.file = self.option()?;
+        source_map.function = self.option()?;
+        for _ in 0..self.len()? {
+            let offset = self.len()?;
+            source_map.record(offset, SourceLocation { line: self.len()?, column: self.len()? });
+        }
+        Ok(Chunk { code, constants, source_map: Box::new(source_map) })
+    }
+
+    fn fields(&mut self) -> Result<std::collections::HashMap<String, Value>, String> {
+        (0..self.len()?).map(|_| Ok((self.string()?, self.value()?))).collect()
+    }
+
+    fn values(&mut self) -> Result<Vec<Value>, String> {
+        (0..self.len()?).map(|_| self.value()).collect()
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        if self.depth == MAX_CONSTANT_DEPTH {
+            return Err(format!("Compiled file nests constants more than {} deep", MAX_CONSTANT_DEPTH));
+        }
+        self.depth += 1;
+        let value = self.value_contents();
+        self.depth -= 1;
+        value
+    }
+
+    fn value_contents(&mut self) -> Result<Value, String> {
+        Ok(match self.byte()? {
+            0 => Value::Null,
+            1 => Value::Boolean(self.byte()? != 0),
+            2 => Value::Number(f64::from_le_bytes(self.take(8)?.try_into().expect("took 8 bytes"))),
+            3 => Value::String(self.string()?),
+            4 => {
+                let len = self.len()?;
+                Value::Bytes(self.take(len)?.to_vec())
+            }
+            5 => Value::Function(Function {
+                name: self.string()?,
+                arity: self.len()?,
+                module: self.option()?,
+                chunk: self.chunk()?,
+            }),
+            6 => Value::Module(self.string()?),
+            7 => Value::Class { name: self.string()?, superclass: self.option()?, module: self.option()?, methods: self.fields()? },
+            8 => Value::array(self.values()?),
+            9 => Value::Tuple(self.values()?),
+            10 => Value::dictionary(self.fields()?),
+            11 => {
+                let class_name = self.string()?;
+                let module = self.option()?;
+                if module.as_deref() == Some(NATIVE_MODULE) {
+                    return Err(format!("Compiled file has a native '{}' object", class_name));
+                }
+                Value::object_in(module, &class_name, self.fields()?)
+            }
+            tag => return Err(format!("Compiled file has an unknown value tag {}", tag)),
+        })
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Raw bytes, kept apart from strings so data that isn't UTF-8 (file
+//! contents, hashes, packets) survives intact. A `b"..."` literal holds
+//! ASCII characters and `\xNN` escapes (`\\` and `\"` for a backslash and a
+//! quote); indexing and `for` loops give each byte as a number, and `+`
+//! concatenates. The `bytes_*` natives convert to and from strings, hex and
+//! base64:
+//!
+//! ```text
+//! data = read_file_bytes("logo.png")
+//! print(bytes_len(data), data[0])  # the first byte, as a number
+//! write_file_bytes("copy.png", data)
+//! ```
+
+use crate::bytecode::Value;
+use crate::sandbox::{require, Capability};
+use crate::vm::VM;
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("bytes_len", 1, |_vm, args| {
+        Ok(Value::Number(bytes_of("bytes_len", &args[0])?.len() as f64))
+    });
+
+    // UTF-8 between strings and bytes
+    vm.register_native("bytes_encode", 1, |_vm, args| match &args[0] {
+        Value::String(s) => Ok(Value::Bytes(s.as_bytes().to_vec())),
+        _ => Err("TypeError: bytes_encode expects a string".to_string()),
+    });
+    vm.register_native("bytes_decode", 1, |_vm, args| {
+        String::from_utf8(bytes_of("bytes_decode", &args[0])?.to_vec())
+            .map(Value::String)
+            .map_err(|e| format!("bytes_decode: invalid UTF-8 at byte {}", e.utf8_error().valid_up_to()))
+    });
+
+    // An array of numbers 0-255 and back
+    vm.register_native("bytes_from_array", 1, |_vm, args| {
+        let Value::Array(items) = &args[0] else {
+            return Err("TypeError: bytes_from_array expects an array of numbers".to_string());
+        };
+        items.lock().unwrap().iter().map(|item| match item {
+            Value::Number(n) if n.fract() == 0.0 && (0.0..=255.0).contains(n) => Ok(*n as u8),
+            other => Err(format!("bytes_from_array: {} is not a byte", VM::format_value(other))),
+        }).collect::<Result<Vec<u8>, String>>().map(Value::Bytes)
+    });
+    vm.register_native("bytes_to_array", 1, |_vm, args| {
+        Ok(Value::array(bytes_of("bytes_to_array", &args[0])?.iter().map(|&b| Value::Number(b as f64)).collect()))
+    });
+
+    vm.register_native("bytes_hex", 1, |_vm, args| {
+        Ok(Value::String(to_hex(bytes_of("bytes_hex", &args[0])?)))
+    });
+    vm.register_native("bytes_from_hex", 1, |_vm, args| match &args[0] {
+        Value::String(s) => from_hex(s).map(Value::Bytes),
+        _ => Err("TypeError: bytes_from_hex expects a string".to_string()),
+    });
+    vm.register_native("bytes_base64", 1, |_vm, args| {
+        Ok(Value::String(to_base64(bytes_of("bytes_base64", &args[0])?)))
+    });
+    vm.register_native("bytes_from_base64", 1, |_vm, args| match &args[0] {
+        Value::String(s) => from_base64(s).map(Value::Bytes),
+        _ => Err("TypeError: bytes_from_base64 expects a string".to_string()),
+    });
+
+    vm.register_native("read_file_bytes", 1, |vm, args| {
+        require(vm, Capability::Filesystem, "read_file_bytes")?;
+        let Value::String(path) = &args[0] else {
+            return Err("TypeError: read_file_bytes expects a path string".to_string());
+        };
+        std::fs::read(vm.resolve_path(path))
+            .map(Value::Bytes)
+            .map_err(|e| format!("IOError: Failed to read '{}': {}", path, e))
+    });
+    vm.register_native("write_file_bytes", 2, |vm, args| {
+        require(vm, Capability::Filesystem, "write_file_bytes")?;
+        let (Value::String(path), Value::Bytes(data)) = (&args[0], &args[1]) else {
+            return Err("TypeError: write_file_bytes expects (path string, bytes)".to_string());
+        };
+        std::fs::write(vm.resolve_path(path), data)
+            .map(|_| Value::Null)
+            .map_err(|e| format!("IOError: Failed to write '{}': {}", path, e))
+    });
+}
+
+fn bytes_of<'a>(name: &str, value: &'a Value) -> Result<&'a [u8], String> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        _ => Err(format!("TypeError: {} expects bytes", name)),
+    }
+}
+
+/// `bytes` written as a literal that reads back as the same bytes, e.g.
+/// `b"GIF\x89"`.
+pub fn literal(bytes: &[u8]) -> String {
+    let mut text = String::from("b\"");
+    for &byte in bytes {
+        match byte {
+            b'"' => text.push_str("\\\""),
+            b'\\' => text.push_str("\\\\"),
+            b' '..=b'~' => text.push(byte as char),
+            _ => text.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    text.push('"');
+    text
+}
+
+/// The bytes a literal's text between the quotes stands for.
+pub fn parse_literal(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\\') => bytes.push(b'\\'),
+                Some('"') => bytes.push(b'"'),
+                Some('x') => {
+                    let digits: String = chars.by_ref().take(2).collect();
+                    let byte = (digits.len() == 2).then(|| u8::from_str_radix(&digits, 16).ok()).flatten()
+                        .ok_or_else(|| format!("Invalid escape '\\x{}' in bytes literal", digits))?;
+                    bytes.push(byte);
+                }
+                Some(other) => return Err(format!("Invalid escape '\\{}' in bytes literal", other)),
+                None => return Err("Bytes literal ends with a lone '\\'".to_string()),
+            },
+            c if c.is_ascii() => bytes.push(c as u8),
+            c => return Err(format!("Bytes literal can only contain ASCII characters, found '{}'; use \\x escapes", c)),
+        }
+    }
+    Ok(bytes)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = text.chars().collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err("TypeError: bytes_from_hex expects an even number of hex digits".to_string());
+    }
+    digits.chunks(2).map(|pair| {
+        let pair: String = pair.iter().collect();
+        u8::from_str_radix(&pair, 16).map_err(|_| format!("bytes_from_hex: '{}' is not a hex byte", pair))
+    }).collect()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with `=` padding.
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = group.iter().enumerate().fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= group.len() {
+                text.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Decode standard base64; padding is optional and whitespace is ignored.
+pub fn from_base64(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text.chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>();
+    let digits = digits.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in digits.chars() {
+        let value = BASE64.iter().position(|&digit| digit as char == c)
+            .ok_or_else(|| format!("bytes_from_base64: '{}' is not a base64 digit", c))?;
+        n = n << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+        }
+    }
+    if bits >= 6 {
+        return Err("bytes_from_base64: truncated input".to_string());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    #[test]
+    fn test_codecs_round_trip() {
+        for sample in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar", b"\x00\xff\"\\"] {
+            assert_eq!(from_base64(&to_base64(sample)).unwrap(), sample);
+            assert_eq!(from_hex(&to_hex(sample)).unwrap(), sample);
+            let literal = literal(sample);
+            assert_eq!(parse_literal(&literal[2..literal.len() - 1]).unwrap(), sample);
+        }
+        assert_eq!(to_base64(b"foobar!"), "Zm9vYmFyIQ==");
+        assert!(from_base64("Zm9v!").is_err());
+        assert!(parse_literal("caf\u{e9}").is_err());
+        assert!(parse_literal("\\x4").is_err());
+    }
+
+    #[test]
+    fn test_bytes_values() {
+        let dir = std::env::temp_dir().join(format!("grease-bytes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.bin");
+        let source = format!("data = b\"GIF\\x89\" + bytes_from_hex(\"00ff\")\n\
+            write_file_bytes(\"{0}\", data)\n\
+            back = read_file_bytes(\"{0}\")\n\
+            total = 0\n\
+            for byte in back:\n    total = total + byte\n\
+            facts = [bytes_len(back), back[3], back == data, bytes_base64(b\"hi\"), bytes_decode(bytes_encode(\"h\u{e9}\"))]",
+            path.display());
+        let mut grease = Grease::new();
+        assert_eq!(grease.run(&source).unwrap(), InterpretResult::Ok);
+        let formatted = |name| VM::format_value(grease.vm.globals.get(name).unwrap());
+        assert_eq!(formatted("back"), "b\"GIF\\x89\\x00\\xff\"");
+        assert_eq!(formatted("total"), (71 + 73 + 70 + 0x89 + 0xff).to_string());
+        assert_eq!(formatted("facts"), "[6, 137, true, aGk=, h\u{e9}]");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let result = grease.run("bytes_decode(b\"\\xff\")").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("invalid UTF-8")));
+    }
+}
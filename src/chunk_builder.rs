@@ -0,0 +1,296 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Building chunks instruction by instruction, for tools, tests and front
+//! ends other than the compiler.
+//!
+//! A [`ChunkBuilder`] takes typed instructions and jumps to [`Label`]s, and
+//! [`build`](ChunkBuilder::build) lays them out: it resolves each jump,
+//! picks the 16- or 32-bit form by distance, turns backward jumps into
+//! `LOOP`, and runs the [verifier](crate::verifier) on the result.
+//!
+//! ```text
+//! let mut builder = ChunkBuilder::new();
+//! let done = builder.label();
+//! builder.emit(OpCode::True)?;
+//! builder.emit_jump(OpCode::JumpIfFalse, done)?;
+//! builder.emit(OpCode::Pop)?;
+//! builder.bind(done)?;
+//! builder.emit(OpCode::Return)?;
+//! let chunk = builder.build()?;
+//! ```
+
+use crate::bytecode::{Chunk, OpCode, Value};
+use crate::source_map::SourceLocation;
+use crate::verifier;
+
+/// A position in the code that jumps can target, bound with
+/// [`ChunkBuilder::bind`] before or after the jumps to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+#[derive(Debug, Clone)]
+enum Item {
+    Op { op: OpCode, operand: Option<u8>, location: SourceLocation },
+    Jump { op: OpCode, label: Label, location: SourceLocation },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChunkBuilder {
+    items: Vec<Item>,
+    constants: Vec<Value>,
+    /// The item each label is bound before, once bound.
+    labels: Vec<Option<usize>>,
+    location: SourceLocation,
+    function: Option<String>,
+}
+
+impl ChunkBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the function the chunk is the body of, for tracebacks.
+    pub fn with_function(mut self, name: &str) -> Self {
+        self.function = Some(name.to_string());
+        self
+    }
+
+    /// Attribute the instructions emitted from now on to `line` and
+    /// `column` of the source.
+    pub fn at(&mut self, line: usize, column: usize) -> &mut Self {
+        self.location = SourceLocation { line, column };
+        self
+    }
+
+    /// Emit an instruction without operands, e.g. `ADD` or `RETURN`.
+    pub fn emit(&mut self, op: OpCode) -> Result<&mut Self, String> {
+        if op.operand_width() != 0 {
+            return Err(format!("{:?} takes an operand", op));
+        }
+        self.items.push(Item::Op { op, operand: None, location: self.location });
+        Ok(self)
+    }
+
+    /// Emit an instruction with a one-byte operand, e.g. `CALL 2` or
+    /// `GET_LOCAL 0`.
+    pub fn emit_with(&mut self, op: OpCode, operand: u8) -> Result<&mut Self, String> {
+        if op.operand_width() != 1 {
+            return Err(format!("{:?} doesn't take a one-byte operand", op));
+        }
+        self.items.push(Item::Op { op, operand: Some(operand), location: self.location });
+        Ok(self)
+    }
+
+    /// Add `value` to the constant table, returning its index.
+    pub fn add_constant(&mut self, value: Value) -> Result<u8, String> {
+        let index = u8::try_from(self.constants.len()).map_err(|_| "Too many constants in one chunk".to_string())?;
+        self.constants.push(value);
+        Ok(index)
+    }
+
+    /// Push `value`.
+    pub fn emit_constant(&mut self, value: Value) -> Result<&mut Self, String> {
+        let index = self.add_constant(value)?;
+        self.emit_with(OpCode::Constant, index)
+    }
+
+    /// Emit an instruction that takes a name, e.g. `GET_GLOBAL "x"` or
+    /// `IMPORT "math"`.
+    pub fn emit_name(&mut self, op: OpCode, name: &str) -> Result<&mut Self, String> {
+        if !matches!(op, OpCode::GetGlobal | OpCode::SetGlobal | OpCode::Import | OpCode::LoadPlugin) {
+            return Err(format!("{:?} doesn't take a name", op));
+        }
+        let index = self.add_constant(Value::String(name.to_string()))?;
+        self.emit_with(op, index)
+    }
+
+    /// A new label, not yet bound.
+    pub fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Bind `label` to the next instruction emitted.
+    pub fn bind(&mut self, label: Label) -> Result<&mut Self, String> {
+        let slot = self.labels.get_mut(label.0).ok_or("Label from another builder")?;
+        if slot.is_some() {
+            return Err(format!("Label {} is already bound", label.0));
+        }
+        *slot = Some(self.items.len());
+        Ok(self)
+    }
+
+    /// Jump to `label` with `JUMP`, `JUMP_IF_FALSE`, `JUMP_IF_TRUE`,
+    /// `FOR_ITER`, `TRY` or `LOOP`. `JUMP` to an earlier label becomes
+    /// `LOOP`; the other forms only jump forward, and `LOOP` only back.
+    pub fn emit_jump(&mut self, op: OpCode, label: Label) -> Result<&mut Self, String> {
+        let op = match op {
+            OpCode::JumpLong => OpCode::Jump,
+            OpCode::JumpIfFalseLong => OpCode::JumpIfFalse,
+            OpCode::JumpIfTrueLong => OpCode::JumpIfTrue,
+            OpCode::LoopLong => OpCode::Loop,
+            OpCode::ForIterLong => OpCode::ForIter,
+            OpCode::TryLong => OpCode::Try,
+            op @ (OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfTrue | OpCode::Loop | OpCode::ForIter | OpCode::Try) => op,
+            op => return Err(format!("{:?} is not a jump", op)),
+        };
+        if label.0 >= self.labels.len() {
+            return Err("Label from another builder".to_string());
+        }
+        self.items.push(Item::Jump { op, label, location: self.location });
+        Ok(self)
+    }
+
+    /// Lay the instructions out into a chunk and verify its stack discipline.
+    pub fn build(self) -> Result<Chunk, String> {
+        let chunk = self.assemble()?;
+        verifier::verify(&chunk)?;
+        Ok(chunk)
+    }
+
+    fn assemble(self) -> Result<Chunk, String> {
+        let labels = self.labels.iter().enumerate()
+            .map(|(index, bound)| bound.ok_or_else(|| format!("Label {} is never bound", index)))
+            .collect::<Result<Vec<usize>, String>>()?;
+
+        // Start with every jump short and widen those that don't reach
+        // until nothing changes; widening only moves targets further away
+        let mut long = vec![false; self.items.len()];
+        let offsets = loop {
+            let mut offsets = Vec::with_capacity(self.items.len() + 1);
+            let mut offset = 0;
+            for (item, &long) in self.items.iter().zip(&long) {
+                offsets.push(offset);
+                offset += match item {
+                    Item::Op { op, .. } => 1 + op.operand_width(),
+                    Item::Jump { .. } => if long { 5 } else { 3 },
+                };
+            }
+            offsets.push(offset);
+
+            let mut widened = false;
+            for (index, item) in self.items.iter().enumerate() {
+                if let Item::Jump { label, .. } = item {
+                    let (_, distance) = jump_distance(offsets[index], offsets[labels[label.0]], long[index]);
+                    if !long[index] && distance > usize::from(u16::MAX) {
+                        long[index] = true;
+                        widened = true;
+                    }
+                }
+            }
+            if !widened {
+                break offsets;
+            }
+        };
+
+        let mut chunk = Chunk::new();
+        chunk.constants = self.constants;
+        chunk.source_map.function = self.function;
+        for (index, item) in self.items.into_iter().enumerate() {
+            match item {
+                Item::Op { op, operand, location } => {
+                    chunk.write(op.to_byte(), location);
+                    chunk.code.extend(operand);
+                }
+                Item::Jump { op, label, location } => {
+                    let (backward, distance) = jump_distance(offsets[index], offsets[labels[label.0]], long[index]);
+                    let op = match (op, backward) {
+                        (OpCode::Jump | OpCode::Loop, true) => OpCode::Loop,
+                        (OpCode::Loop, false) => return Err(format!("LOOP at offset {} jumps forward", offsets[index])),
+                        (op, true) => return Err(format!("{:?} at offset {} can't jump backward", op, offsets[index])),
+                        (op, false) => op,
+                    };
+                    if long[index] {
+                        chunk.write(op.long_form().to_byte(), location);
+                        let distance = u32::try_from(distance).map_err(|_| "Too much code to jump over".to_string())?;
+                        chunk.code.extend(distance.to_be_bytes());
+                    } else {
+                        chunk.write(op.to_byte(), location);
+                        chunk.code.extend((distance as u16).to_be_bytes());
+                    }
+                }
+            }
+        }
+        Ok(chunk)
+    }
+}
+
+/// Whether a jump at `from` to `to` goes backward, and how far it goes from
+/// the end of its operand, which is where the VM counts from.
+fn jump_distance(from: usize, to: usize, long: bool) -> (bool, usize) {
+    let next = from + if long { 5 } else { 3 };
+    if to <= from {
+        (true, next - to)
+    } else {
+        (false, to - next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::{InterpretResult, VM};
+
+    #[test]
+    fn test_build_and_run_a_loop() {
+        // total = 0; n = 3; while n: total = total + n; n = n - 1
+        let mut builder = ChunkBuilder::new();
+        let (top, done) = (builder.label(), builder.label());
+        builder.at(1, 1).emit_constant(Value::Number(0.0)).unwrap();
+        builder.emit_name(OpCode::SetGlobal, "total").unwrap();
+        builder.emit_constant(Value::Number(3.0)).unwrap();
+        builder.emit_name(OpCode::SetGlobal, "n").unwrap();
+        builder.bind(top).unwrap().emit_name(OpCode::GetGlobal, "n").unwrap();
+        builder.emit_jump(OpCode::JumpIfFalse, done).unwrap().emit(OpCode::Pop).unwrap();
+        builder.emit_name(OpCode::GetGlobal, "total").unwrap().emit_name(OpCode::GetGlobal, "n").unwrap();
+        builder.emit(OpCode::Add).unwrap().emit_name(OpCode::SetGlobal, "total").unwrap();
+        builder.emit_name(OpCode::GetGlobal, "n").unwrap().emit_constant(Value::Number(1.0)).unwrap();
+        builder.emit(OpCode::Subtract).unwrap().emit_name(OpCode::SetGlobal, "n").unwrap();
+        builder.emit_jump(OpCode::Jump, top).unwrap();
+        builder.bind(done).unwrap().emit(OpCode::Pop).unwrap();
+        builder.emit(OpCode::Null).unwrap().emit(OpCode::Return).unwrap();
+        let chunk = builder.build().unwrap();
+        assert!(chunk.code.contains(&OpCode::Loop.to_byte()));
+
+        let mut vm = VM::new();
+        assert_eq!(vm.interpret(chunk), InterpretResult::Ok);
+        assert!(matches!(vm.globals.get("total"), Some(Value::Number(n)) if *n == 6.0));
+    }
+
+    #[test]
+    fn test_far_jumps_use_the_long_form() {
+        let mut builder = ChunkBuilder::new();
+        let end = builder.label();
+        builder.emit_jump(OpCode::Jump, end).unwrap();
+        for _ in 0..40_000 {
+            builder.emit(OpCode::Null).unwrap().emit(OpCode::Pop).unwrap();
+        }
+        builder.bind(end).unwrap().emit(OpCode::Null).unwrap().emit(OpCode::Return).unwrap();
+        let chunk = builder.build().unwrap();
+        assert_eq!(chunk.code[0], OpCode::JumpLong.to_byte());
+        assert_eq!(u32::from_be_bytes(chunk.code[1..5].try_into().unwrap()), 80_000);
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_code() {
+        let mut builder = ChunkBuilder::new();
+        assert!(builder.emit(OpCode::Call).is_err());
+        assert!(builder.emit_with(OpCode::Add, 1).is_err());
+        assert!(builder.emit_jump(OpCode::Pop, Label(0)).is_err());
+
+        let unbound = builder.label();
+        builder.emit_jump(OpCode::Jump, unbound).unwrap();
+        assert_eq!(builder.clone().build().unwrap_err(), "Label 0 is never bound");
+
+        let mut builder = ChunkBuilder::new();
+        let top = builder.label();
+        builder.bind(top).unwrap().emit(OpCode::True).unwrap().emit_jump(OpCode::JumpIfFalse, top).unwrap();
+        assert!(builder.build().unwrap_err().contains("can't jump backward"));
+
+        // The verifier still checks the stack
+        let mut builder = ChunkBuilder::new();
+        builder.emit(OpCode::Add).unwrap().emit(OpCode::Return).unwrap();
+        assert!(builder.build().unwrap_err().contains("Stack underflow"));
+    }
+}
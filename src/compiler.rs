@@ -3,18 +3,113 @@
 
 use crate::ast::*;
 use crate::bytecode::*;
+use crate::source_map::SourceLocation;
+use crate::verifier;
+use crate::vm::VM;
 use crate::token::{Token, TokenType};
+use std::collections::HashMap;
 
+#[derive(Clone)]
 pub struct Compiler {
     chunk: Chunk,
     locals: Vec<Local>,
     scope_depth: usize,
+    /// Whether this compiler is compiling a function body. Blocks at the top
+    /// level of a script don't have a call frame, so their variables are globals.
+    in_function: bool,
+    /// Enclosing `try`/`with` blocks, innermost last, that a `return` has to
+    /// unwind before leaving the function.
+    cleanups: Vec<Cleanup>,
+    /// Enclosing loops, innermost last, for `break` and `continue`.
+    loops: Vec<Loop>,
+    /// Compiler-managed temporaries on the stack in top-level code, where
+    /// they aren't locals.
+    temporaries: usize,
+    /// Module being compiled, recorded on every function so its globals
+    /// resolve in the module's namespace.
+    namespace: Option<String>,
+    /// Source position attributed to the code being emitted.
+    location: SourceLocation,
+    /// Emit forward jumps with 32-bit offsets. Set when compiling again
+    /// after a 16-bit jump overflowed.
+    long_jumps: bool,
+    /// A forward jump was too far for its 16-bit offset.
+    jump_overflowed: bool,
+    /// Variables of enclosing functions this function captured, in upvalue
+    /// order.
+    captures: Vec<Capture>,
+    /// Functions enclosing this one, innermost last, whose locals it can
+    /// capture.
+    enclosing: Vec<EnclosingFunction>,
+    /// Names with a value fixed at compile time, like `platform`, which
+    /// `if` conditions made of them and literals are folded on.
+    defines: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone)]
+enum Cleanup {
+    Try,
+    With { manager_slot: usize },
+    Finally(Vec<Statement>),
+}
+
+/// A loop being compiled and the state its body starts in.
+#[derive(Debug, Clone)]
+struct Loop {
+    /// Where `continue` jumps back to.
+    start: usize,
+    /// `break` jumps to patch once the loop's exit is known.
+    breaks: Vec<usize>,
+    /// Values on the stack when the body starts, which leaving it keeps.
+    stack_height: usize,
+    /// Cleanups enclosing the loop, which leaving the body doesn't unwind.
+    cleanups: usize,
 }
 
 #[derive(Debug, Clone)]
 struct Local {
     name: String,
     depth: usize,
+    /// A nested function captured the local, so leaving its scope has to
+    /// close the upvalue rather than just pop it.
+    captured: bool,
+}
+
+/// Where `CLOSURE` finds a variable the new closure captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capture {
+    /// A local slot of the function creating the closure.
+    Local(usize),
+    /// An upvalue of the function creating the closure, which captured the
+    /// variable from further out.
+    Upvalue(usize),
+}
+
+/// The variables of a function while a function nested in it is compiled.
+#[derive(Debug, Clone)]
+struct EnclosingFunction {
+    locals: Vec<Local>,
+    captures: Vec<Capture>,
+}
+
+/// The defines scripts compile with: `platform` and `arch` as Rust names
+/// them (`"linux"`, `"windows"`, `"macos"`, `"wasi"`; `"x86_64"`,
+/// `"aarch64"`, `"wasm32"`), and whether the optional `system`, `term` and
+/// `plugins` features were built, as `feature_system` and so on.
+pub fn default_defines() -> HashMap<String, Value> {
+    HashMap::from([
+        ("platform".to_string(), Value::String(std::env::consts::OS.to_string())),
+        ("arch".to_string(), Value::String(std::env::consts::ARCH.to_string())),
+        ("feature_system".to_string(), Value::Boolean(cfg!(feature = "system"))),
+        ("feature_term".to_string(), Value::Boolean(cfg!(feature = "term"))),
+        ("feature_plugins".to_string(), Value::Boolean(cfg!(feature = "plugins"))),
+    ])
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Compiler {
@@ -23,24 +118,154 @@ impl Compiler {
             chunk: Chunk::new(),
             locals: Vec::with_capacity(16),
             scope_depth: 0,
+            in_function: false,
+            cleanups: Vec::new(),
+            loops: Vec::new(),
+            temporaries: 0,
+            namespace: None,
+            location: SourceLocation::default(),
+            long_jumps: false,
+            jump_overflowed: false,
+            captures: Vec::new(),
+            enclosing: Vec::new(),
+            defines: HashMap::new(),
+        }
+    }
+
+    /// Record `file` in the source maps of everything compiled, for
+    /// tracebacks.
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.chunk.source_map.file = Some(file.into());
+        self
+    }
+
+    /// Fix the values of names like `platform` (see [`default_defines`]).
+    /// Reading one compiles to its value, assigning one is an error, and an
+    /// `if` whose condition compares them to literals compiles only the
+    /// branch it takes.
+    pub fn with_defines(mut self, defines: HashMap<String, Value>) -> Self {
+        self.defines = defines;
+        self
+    }
+
+    /// Compile the source of module `name`.
+    pub fn for_module(name: &str) -> Self {
+        Compiler {
+            namespace: Some(name.to_string()),
+            ..Self::new()
         }
     }
 
     pub fn compile(&mut self, program: &Program) -> Result<&Chunk, String> {
-        for statement in &program.statements {
-            self.compile_statement(statement)?;
+        self.with_jump_widths(|compiler| {
+            if let Some((last, rest)) = program.statements.split_last() {
+                for statement in rest {
+                    compiler.compile_statement(statement)?;
+                }
+                // A trailing expression is the script's result, so it stays on
+                // the stack for the final Return
+                match last {
+                    Statement::Expression(expr) => compiler.compile_expression(expr)?,
+                    statement => compiler.compile_statement(statement)?,
+                }
+            }
+            compiler.emit_return();
+            Ok(())
+        })?;
+        if cfg!(debug_assertions) {
+            verifier::verify(&self.chunk)?;
         }
-        
-        self.emit_return();
         Ok(&self.chunk)
     }
 
+    /// Compile a module's top-level code into a function that runs it, so a
+    /// `use` can load the module while the importing script is running.
+    pub fn compile_module(&mut self, program: &Program) -> Result<Function, String> {
+        self.with_jump_widths(|compiler| {
+            for statement in &program.statements {
+                compiler.compile_statement(statement)?;
+            }
+            compiler.emit_byte(OpCode::Null);
+            compiler.emit_return();
+            Ok(())
+        })?;
+        if cfg!(debug_assertions) {
+            verifier::verify(&self.chunk)?;
+        }
+
+        Ok(Function {
+            name: self.namespace.clone().unwrap_or_else(|| "module".to_string()),
+            arity: 0,
+            chunk: self.chunk.clone(),
+            module: self.namespace.clone(),
+        })
+    }
+
     fn compile_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        let parent = self.location;
+        if let Some(token) = Self::statement_token(statement) {
+            self.location = Self::token_location(token);
+        }
+        let result = self.emit_statement(statement);
+        self.location = parent;
+        result
+    }
+
+    /// Compile `expression`, attributing its code to its own position in
+    /// the source map; its operands record theirs.
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), String> {
+        let parent = self.location;
+        if let Some(token) = Self::expression_token(expression) {
+            self.location = Self::token_location(token);
+        }
+        let result = self.emit_expression(expression);
+        self.location = parent;
+        result
+    }
+
+    fn token_location(token: &Token) -> SourceLocation {
+        SourceLocation { line: token.line, column: token.column }
+    }
+
+    /// The token that locates a statement, if it has one.
+    fn statement_token(statement: &Statement) -> Option<&Token> {
+        match statement {
+            Statement::Expression(expression) => Self::expression_token(expression),
+            Statement::VariableDeclaration { name, .. }
+            | Statement::FunctionDeclaration { name, .. }
+            | Statement::ClassDeclaration { name, .. } => Some(name),
+            Statement::Unpack { targets, .. } => targets.first(),
+            Statement::For { variable, .. } => Some(variable),
+            Statement::If { condition, .. } | Statement::While { condition, .. } => Self::expression_token(condition),
+            Statement::Return { value: Some(value) } | Statement::Throw { value: Some(value) } => Self::expression_token(value),
+            Statement::With { manager, .. } => Self::expression_token(manager),
+            _ => None,
+        }
+    }
+
+    /// The token that locates an expression: its operator, name or member.
+    fn expression_token(expression: &Expression) -> Option<&Token> {
+        match expression {
+            Expression::Identifier(token) => Some(token),
+            Expression::Binary { operator, .. } | Expression::Unary { operator, .. } => Some(operator),
+            Expression::Assignment { name, .. } => Some(name),
+            Expression::PropertyAssignment { property, .. } | Expression::PropertyAccess { property, .. } => Some(property),
+            Expression::ModuleAccess { member, .. } => Some(member),
+            Expression::MethodCall { method, .. } => Some(method),
+            Expression::SuperCall { method, .. } => method.as_ref(),
+            Expression::Call { callee, .. } => Self::expression_token(callee),
+            Expression::NewInstance { class, .. } => Self::expression_token(class),
+            Expression::Grouping(inner) => Self::expression_token(inner),
+            Expression::Index { array, .. } | Expression::Slice { array, .. } => Self::expression_token(array),
+            _ => None,
+        }
+    }
+
+    fn emit_statement(&mut self, statement: &Statement) -> Result<(), String> {
         match statement {
             Statement::Expression(expr) => {
                 self.compile_expression(expr)?;
-                // Don't pop for now - this needs more thought about REPL vs script mode
-                // self.emit_byte(OpCode::Pop);
+                self.emit_byte(OpCode::Pop);
             }
             Statement::VariableDeclaration { name, type_annotation: _, initializer } => {
                 if let Some(initializer) = initializer {
@@ -48,19 +273,77 @@ impl Compiler {
                 } else {
                     self.emit_byte(OpCode::Null);
                 }
-                
-                self.declare_variable(&name)?;
-                self.define_variable(&name)?;
+                self.assign_variable(name)?;
+            }
+            Statement::Unpack { targets, value } => {
+                if !self.is_local_scope() {
+                    self.compile_expression(value)?;
+                    self.emit_bytes(OpCode::Unpack, targets.len() as u8);
+                    // SetGlobal pops, so assign from the top of the stack down
+                    for target in targets.iter().rev() {
+                        self.define_variable(target)?;
+                    }
+                    return Ok(());
+                }
+
+                // Reserve slots for targets that aren't locals yet, then store
+                // every unpacked value into its slot
+                for target in targets {
+                    let is_new = match &target.token_type {
+                        TokenType::Identifier(name) => self.resolve_local(name).is_none() && self.resolve_upvalue(name).is_none(),
+                        _ => return Err("Expected identifier".to_string()),
+                    };
+                    if is_new {
+                        self.emit_byte(OpCode::Null);
+                        self.declare_variable(target)?;
+                        self.define_variable(target)?;
+                    }
+                }
+                self.compile_expression(value)?;
+                self.emit_bytes(OpCode::Unpack, targets.len() as u8);
+                for target in targets.iter().rev() {
+                    if let TokenType::Identifier(name) = &target.token_type {
+                        self.emit_store(name)?;
+                    }
+                }
             }
-            Statement::FunctionDeclaration { name, parameters, return_type: _, body } => {
-                self.declare_variable(&name)?;
+            Statement::FunctionDeclaration { name, parameters, return_type: _, body, decorators } => {
+                self.declare_variable(name)?;
                 self.mark_initialized();
                 
-                let function = self.compile_function(name, parameters, body)?;
+                // A function that captures variables has to be created as a
+                // closure each time the declaration runs
+                let (function, captures) = self.compile_closure(name, parameters, body)?;
                 let constant = self.chunk.add_constant(Value::Function(function));
-                self.emit_bytes(OpCode::Constant, constant as u8);
+                if captures.is_empty() {
+                    self.emit_bytes(OpCode::Constant, constant as u8);
+                } else {
+                    self.emit_bytes(OpCode::Closure, constant as u8);
+                    self.chunk.write(captures.len() as u8, self.location);
+                    for capture in captures {
+                        let (is_local, index) = match capture {
+                            Capture::Local(slot) => (1, slot),
+                            Capture::Upvalue(index) => (0, index),
+                        };
+                        self.chunk.write(is_local, self.location);
+                        self.chunk.write(index as u8, self.location);
+                    }
+                }
                 
-                self.define_variable(&name)?;
+                self.define_variable(name)?;
+
+                // `@decorator` rebinds the name to `decorator(function)`,
+                // applying the decorator nearest the `def` first
+                for decorator in decorators.iter().rev() {
+                    self.compile_statement(&Statement::VariableDeclaration {
+                        name: name.clone(),
+                        type_annotation: None,
+                        initializer: Some(Expression::Call {
+                            callee: Box::new(decorator.clone()),
+                            arguments: vec![Expression::Identifier(name.clone())],
+                        }),
+                    })?;
+                }
             }
             Statement::Return { value } => {
                 if let Some(value) = value {
@@ -68,9 +351,19 @@ impl Compiler {
                 } else {
                     self.emit_byte(OpCode::Null);
                 }
+                self.emit_cleanups()?;
                 self.emit_byte(OpCode::Return);
             }
             Statement::If { condition, then_branch, else_branch } => {
+                // Platform checks leave only the code for this platform
+                if let Some(taken) = self.constant_condition(condition) {
+                    if VM::is_truthy(&taken) {
+                        self.compile_block(then_branch)?;
+                    } else if let Some(else_branch) = else_branch {
+                        self.compile_block(else_branch)?;
+                    }
+                    return Ok(());
+                }
                 self.compile_expression(condition)?;
                 
                 let else_jump = self.emit_jump(OpCode::JumpIfFalse);
@@ -79,14 +372,14 @@ impl Compiler {
                 self.compile_block(then_branch)?;
                 
                 let else_jump_2 = self.emit_jump(OpCode::Jump);
-                self.patch_jump(else_jump);
+                self.patch_jump(else_jump)?;
                 self.emit_byte(OpCode::Pop);
                 
                 if let Some(else_branch) = else_branch {
                     self.compile_block(else_branch)?;
                 }
                 
-                self.patch_jump(else_jump_2);
+                self.patch_jump(else_jump_2)?;
             }
             Statement::While { condition, body } => {
                 let loop_start = self.chunk.code.len();
@@ -95,52 +388,69 @@ impl Compiler {
                 let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
                 self.emit_byte(OpCode::Pop); // Pop condition result
                 
-                self.compile_block(body)?;
-                
-                self.emit_loop(loop_start);
-                self.patch_jump(exit_jump);
-                // No pop here - condition was already popped before loop body
+                self.compile_loop_body(loop_start, body, |compiler| {
+                    compiler.emit_loop(loop_start)?;
+                    compiler.patch_jump(exit_jump)?;
+                    compiler.emit_byte(OpCode::Pop); // Pop condition result on exit
+                    Ok(())
+                })?;
             }
             Statement::For { variable, iterable, body } => {
+                self.begin_scope();
+
+                // The iterable and the current index live on the stack for the
+                // whole loop; inside functions they occupy hidden local slots
+                // after the loop variable's own slot
+                if self.is_local_scope() {
+                    self.emit_byte(OpCode::Null);
+                    self.declare_variable(variable)?;
+                    self.define_variable(variable)?;
+                }
                 self.compile_expression(iterable)?;
-                
-                self.declare_variable(variable)?;
-                self.define_variable(variable)?; // array in variable
-                
+                self.emit_byte(OpCode::GetIter);
+                self.add_hidden_local("(for iterable)");
+                let zero_constant = self.chunk.add_constant(Value::Number(0.0));
+                self.emit_bytes(OpCode::Constant, zero_constant as u8);
+                self.add_hidden_local("(for index)");
+
+                let loop_start = self.chunk.code.len();
+                let exit_jump = self.emit_jump(OpCode::ForIter);
                 let variable_name = match &variable.token_type {
-                    TokenType::Identifier(name) => name,
+                    TokenType::Identifier(name) => name.clone(),
                     _ => return Err("Expected identifier".to_string()),
                 };
-                
-                let zero_constant = self.chunk.add_constant(Value::Number(0.0));
-                self.emit_bytes(OpCode::Constant, zero_constant as u8); // index
-                let name_constant = self.chunk.add_constant(Value::String(variable_name.clone()));
-                self.emit_bytes(OpCode::GetGlobal, name_constant as u8); // get array
-                self.emit_byte(OpCode::Length); // length
-                
-                let loop_start = self.chunk.code.len();
-                
-                self.emit_byte(OpCode::Dup); // dup index
-                self.emit_bytes(OpCode::GetGlobal, name_constant as u8); // get array
-                self.emit_byte(OpCode::Index); // element
-                self.emit_bytes(OpCode::SetGlobal, name_constant as u8); // set variable to element
-                
-                self.compile_block(body)?;
-                
-                let one_constant = self.chunk.add_constant(Value::Number(1.0));
-                self.emit_bytes(OpCode::Constant, one_constant as u8);
-                self.emit_byte(OpCode::Add); // index + 1
-                self.emit_byte(OpCode::Dup); // dup new index
-                self.emit_byte(OpCode::Dup); // dup length
-                self.emit_byte(OpCode::Less); // index < length
-                let jump = self.emit_jump(OpCode::JumpIfTrue);
-                let offset = loop_start as i16 - (jump as i16 + 2);
-                self.chunk.code[jump] = (offset >> 8) as u8;
-                self.chunk.code[jump + 1] = (offset & 0xff) as u8;
-                
-                self.emit_byte(OpCode::Pop); // pop index+1
-                self.emit_byte(OpCode::Pop); // pop length
-                self.emit_byte(OpCode::Pop); // pop index
+                if let Some(slot) = self.resolve_local(&variable_name) {
+                    self.emit_bytes(OpCode::SetLocal, slot as u8);
+                } else {
+                    let name_constant = self.global_constant(&variable_name)?;
+                    self.emit_bytes(OpCode::SetGlobal, name_constant as u8);
+                }
+
+                self.compile_loop_body(loop_start, body, |compiler| {
+                    compiler.emit_loop(loop_start)?;
+                    compiler.patch_jump(exit_jump)
+                })?;
+
+                self.pop_hidden_local(); // pop index
+                self.pop_hidden_local(); // pop iterable
+                self.end_scope();
+            }
+            Statement::Break => {
+                let Some(innermost) = self.loops.last() else {
+                    return Err("'break' outside of a loop".to_string());
+                };
+                let (stack_height, cleanups) = (innermost.stack_height, innermost.cleanups);
+                self.emit_leave_loop_body(stack_height, cleanups)?;
+                let jump = self.emit_jump(OpCode::Jump);
+                self.loops.last_mut().expect("checked above").breaks.push(jump);
+            }
+            Statement::Continue => {
+                let Some(innermost) = self.loops.last() else {
+                    return Err("'continue' outside of a loop".to_string());
+                };
+                let (start, stack_height, cleanups) = (innermost.start, innermost.stack_height, innermost.cleanups);
+                self.emit_leave_loop_body(stack_height, cleanups)?;
+                self.emit_loop(start)?;
             }
             Statement::Block(statements) => {
                 self.begin_scope();
@@ -149,18 +459,59 @@ impl Compiler {
                 }
                 self.end_scope();
             }
-            Statement::Use { module: _, alias: _ } => {
-                // Imports are handled at the Grease level, not compiled to bytecode
+            Statement::Use { module, alias, names, lazy } => {
+                // The module loads when the statement runs, so a missing module
+                // raises an ImportError that an enclosing try can catch
+                if *lazy {
+                    let constant = self.chunk.add_constant(Value::Module(module.clone()));
+                    self.emit_bytes(OpCode::Constant, constant as u8);
+                } else {
+                    let constant = self.chunk.add_constant(Value::String(module.clone()));
+                    self.emit_bytes(OpCode::Import, constant as u8);
+                }
+
+                if names.is_empty() {
+                    let binding = alias.as_deref().unwrap_or(module);
+                    let binding = Token::new(TokenType::Identifier(binding.to_string()), binding.to_string(), 0, 0);
+                    return self.assign_variable(&binding);
+                }
+
+                // Keep the module around while its members are bound
+                let module_slot = self.is_local_scope().then_some(self.locals.len());
+                self.add_hidden_local("(use module)");
+                for name in names {
+                    match module_slot {
+                        Some(slot) => self.emit_bytes(OpCode::GetLocal, slot as u8),
+                        None => self.emit_byte(OpCode::Dup),
+                    }
+                    let constant = self.chunk.add_constant(Value::String(name.lexeme.clone()));
+                    self.emit_bytes(OpCode::Constant, constant as u8);
+                    self.emit_byte(OpCode::GetProperty);
+                    self.assign_variable(name)?;
+                }
+                self.pop_hidden_local();
+            }
+            Statement::UsePlugin { path } => {
+                let constant = self.chunk.add_constant(Value::String(path.lexeme.clone()));
+                self.emit_bytes(OpCode::LoadPlugin, constant as u8);
             }
             Statement::ClassDeclaration { name, superclass, methods } => {
                 // Compile class definition
                 // Store methods in a class object
                 let mut method_map = std::collections::HashMap::new();
                 for method in methods {
-                    if let Statement::FunctionDeclaration { name: method_name, parameters, return_type: _, body } = method {
-                        let function = self.compile_function(&method_name, &parameters, &body)?;
-                        let method_constant = self.chunk.add_constant(Value::Function(function));
-                        method_map.insert(method_name.lexeme.clone(), method_constant);
+                    if let Statement::FunctionDeclaration { name: method_name, parameters, return_type: _, body, decorators } = method {
+                        if !decorators.is_empty() {
+                            return Err(format!("Decorators are not supported on method '{}'", method_name.lexeme));
+                        }
+                        if !matches!(parameters.first(), Some((param, _)) if param.token_type == TokenType::SelfKw) {
+                            return Err(format!(
+                                "Method '{}' in class '{}' must take 'self' as its first parameter (line {})",
+                                method_name.lexeme, name.lexeme, method_name.line
+                            ));
+                        }
+                        let function = self.compile_function(method_name, parameters, body)?;
+                        method_map.insert(method_name.lexeme.clone(), Value::Function(function));
                     }
                 }
                 
@@ -169,29 +520,19 @@ impl Compiler {
                     name: name.lexeme.clone(),
                     methods: method_map,
                     superclass: superclass.as_ref().map(|s| s.lexeme.clone()),
+                    module: self.namespace.clone(),
                 };
                 let class_constant = self.chunk.add_constant(class_value);
                 
-                // Define class as global
+                // Define class as global, even inside a function: objects find
+                // their class (and its methods) by name in the globals of
+                // the module that defined it
                 self.emit_bytes(OpCode::Constant, class_constant as u8);
-                 self.declare_variable(&name)?;
-                 self.define_variable(&name)?;
+                let name_constant = self.global_constant(&name.lexeme)?;
+                self.emit_bytes(OpCode::SetGlobal, name_constant as u8);
              }
-             Statement::Try { try_block, catch_block } => {
-                 // For now, just compile try block and ignore catch
-                 // TODO: Implement proper exception handling with jumps
-                 self.begin_scope();
-                 for stmt in try_block {
-                     self.compile_statement(stmt)?;
-                 }
-                 self.end_scope();
-                 
-                 // Compile catch block
-                 self.begin_scope();
-                 for stmt in catch_block {
-                     self.compile_statement(stmt)?;
-                 }
-                 self.end_scope();
+             Statement::Try { try_block, catch_clauses, finally_block } => {
+                 self.compile_try(try_block, catch_clauses, finally_block.as_ref())?;
              }
              Statement::Throw { value } => {
                  if let Some(val) = value {
@@ -201,6 +542,45 @@ impl Compiler {
                  }
                  self.emit_byte(OpCode::Throw);
              }
+             Statement::With { manager, name, body } => {
+                 self.begin_scope();
+
+                 // The manager stays on the stack (a hidden local in functions)
+                 // so it can be released however the body is left
+                 self.compile_expression(manager)?;
+                 self.add_hidden_local("(with manager)");
+                 self.emit_byte(OpCode::WithEnter);
+                 let mut above_manager = 0;
+                 match name {
+                     Some(name) if self.is_local_scope() => {
+                         self.declare_variable(name)?;
+                         self.define_variable(name)?;
+                         above_manager = 1;
+                     }
+                     Some(name) => self.define_variable(name)?,
+                     None => self.emit_byte(OpCode::Pop),
+                 }
+
+                 let manager_slot = self.stack_height() - 1 - above_manager;
+                 let handler_jump = self.emit_jump(OpCode::Try);
+                 self.cleanups.push(Cleanup::With { manager_slot });
+                 self.compile_block(body)?;
+                 self.cleanups.pop();
+                 self.emit_byte(OpCode::PopException);
+                 self.emit_byte(OpCode::Null);
+                 self.emit_bytes(OpCode::WithExit, above_manager as u8);
+                 let end_jump = self.emit_jump(OpCode::Jump);
+
+                 // The body threw: release the manager, then re-raise
+                 self.patch_jump(handler_jump)?;
+                 self.emit_byte(OpCode::Dup);
+                 self.emit_bytes(OpCode::WithExit, (above_manager + 1) as u8);
+                 self.emit_byte(OpCode::Rethrow);
+                 self.patch_jump(end_jump)?;
+
+                 self.pop_hidden_local(); // pop manager
+                 self.end_scope();
+             }
              Statement::RustInline { code } => {
                  let constant = self.chunk.add_constant(Value::String(code.clone()));
                  self.emit_bytes(OpCode::RustInline, constant as u8);
@@ -214,7 +594,7 @@ impl Compiler {
         Ok(())
     }
 
-    fn compile_expression(&mut self, expression: &Expression) -> Result<(), String> {
+    fn emit_expression(&mut self, expression: &Expression) -> Result<(), String> {
         match expression {
             Expression::Number(value) => {
                 let constant = self.chunk.add_constant(Value::Number(*value));
@@ -224,6 +604,10 @@ impl Compiler {
                 let constant = self.chunk.add_constant(Value::String(value.clone()));
                 self.emit_bytes(OpCode::Constant, constant as u8);
             }
+            Expression::Bytes(value) => {
+                let constant = self.chunk.add_constant(Value::Bytes(value.clone()));
+                self.emit_bytes(OpCode::Constant, constant as u8);
+            }
             Expression::Boolean(value) => {
                 self.emit_byte(if *value { OpCode::True } else { OpCode::False });
             }
@@ -231,9 +615,22 @@ impl Compiler {
                 self.emit_byte(OpCode::Null);
             }
             Expression::Identifier(ref token) => {
-                if let TokenType::Identifier(ref name) = token.token_type {
+                if let TokenType::SelfKw = token.token_type {
+                    if let Some(local) = self.resolve_local("self") {
+                        self.emit_bytes(OpCode::GetLocal, local as u8);
+                    } else if let Some(upvalue) = self.resolve_upvalue("self") {
+                        self.emit_bytes(OpCode::GetUpvalue, upvalue as u8);
+                    } else {
+                        return Err("'self' used outside of a method".to_string());
+                    }
+                } else if let TokenType::Identifier(ref name) = token.token_type {
                     if let Some(local) = self.resolve_local(name) {
                         self.emit_bytes(OpCode::GetLocal, local as u8);
+                    } else if let Some(upvalue) = self.resolve_upvalue(name) {
+                        self.emit_bytes(OpCode::GetUpvalue, upvalue as u8);
+                    } else if let Some(value) = self.defines.get(name) {
+                        let constant = self.chunk.add_constant(value.clone());
+                        self.emit_bytes(OpCode::Constant, constant as u8);
                     } else {
                         let constant = self.chunk.add_constant(Value::String(name.clone()));
                         self.emit_bytes(OpCode::GetGlobal, constant as u8);
@@ -275,18 +672,14 @@ impl Compiler {
             }
             Expression::Assignment { name, value } => {
                 self.compile_expression(value)?;
+                // Assignment is an expression, so leave the value on the stack
+                self.emit_byte(OpCode::Dup);
                 
                 let variable_name = match &name.token_type {
                     TokenType::Identifier(name) => name.clone(),
                     _ => return Err("Expected identifier in assignment".to_string()),
                 };
-                
-                if let Some(local) = self.resolve_local(&variable_name) {
-                    self.emit_bytes(OpCode::SetLocal, local as u8);
-                } else {
-                    let constant = self.chunk.add_constant(Value::String(variable_name));
-                    self.emit_bytes(OpCode::SetGlobal, constant as u8);
-                }
+                self.emit_store(&variable_name)?;
             }
             Expression::PropertyAssignment { object, property, value } => {
                 self.compile_expression(object)?;
@@ -338,6 +731,12 @@ impl Compiler {
                 }
                 self.emit_bytes(OpCode::Array, elements.len() as u8);
             }
+            Expression::Tuple(elements) => {
+                for element in elements {
+                    self.compile_expression(element)?;
+                }
+                self.emit_bytes(OpCode::Tuple, elements.len() as u8);
+            }
             Expression::Dictionary(pairs) => {
                 for (key, value) in pairs {
                     self.compile_expression(key)?;
@@ -350,6 +749,16 @@ impl Compiler {
                 self.compile_expression(index)?;
                 self.emit_byte(OpCode::Index);
             }
+            Expression::Slice { array, start, end } => {
+                self.compile_expression(array)?;
+                for bound in [start, end] {
+                    match bound {
+                        Some(bound) => self.compile_expression(bound)?,
+                        None => self.emit_byte(OpCode::Null),
+                    }
+                }
+                self.emit_byte(OpCode::Slice);
+            }
             Expression::NewInstance { class, arguments } => {
                 self.compile_expression(class)?;
                 for arg in arguments {
@@ -364,12 +773,13 @@ impl Compiler {
                 self.emit_byte(OpCode::GetProperty);
             }
             Expression::MethodCall { object, method, arguments } => {
+                // Stack layout expected by CallMethod: object, name, arguments
                 self.compile_expression(object)?;
+                let method_constant = self.chunk.add_constant(Value::String(method.lexeme.clone()));
+                self.emit_bytes(OpCode::Constant, method_constant as u8);
                 for arg in arguments {
                     self.compile_expression(arg)?;
                 }
-                    let method_constant = self.chunk.add_constant(Value::String(method.lexeme.clone()));
-                self.emit_bytes(OpCode::Constant, method_constant as u8);
                 self.emit_bytes(OpCode::CallMethod, arguments.len() as u8);
             }
             Expression::SuperCall { method, arguments } => {
@@ -398,39 +808,238 @@ impl Compiler {
         Ok(())
     }
 
+    /// Compile a function that can't capture variables, such as a method.
     fn compile_function(&mut self, name: &Token, parameters: &Vec<(Token, Option<String>)>, body: &Vec<Statement>) -> Result<Function, String> {
-        let mut compiler = Compiler::new();
-        compiler.begin_scope();
-        
-        // Add parameters as locals
-        for (param, _) in parameters {
-            let _param_name = match &param.token_type {
-                TokenType::Identifier(name) => name.clone(),
-                _ => return Err("Expected parameter name".to_string()),
-            };
-            compiler.declare_variable(param)?;
-            compiler.define_variable(param)?;
+        self.compile_function_in(name, parameters, body, &mut Vec::new()).map(|(function, _)| function)
+    }
+
+    /// Compile a `def`, which can capture the variables of the functions
+    /// enclosing it, returning the function and what it captures.
+    fn compile_closure(&mut self, name: &Token, parameters: &Vec<(Token, Option<String>)>, body: &Vec<Statement>) -> Result<(Function, Vec<Capture>), String> {
+        let mut enclosing = std::mem::take(&mut self.enclosing);
+        if self.in_function {
+            enclosing.push(EnclosingFunction {
+                locals: std::mem::take(&mut self.locals),
+                captures: std::mem::take(&mut self.captures),
+            });
         }
-        
-        for statement in body {
-            compiler.compile_statement(statement)?;
+        let function = self.compile_function_in(name, parameters, body, &mut enclosing);
+        if self.in_function {
+            let this = enclosing.pop().expect("pushed above");
+            self.locals = this.locals;
+            self.captures = this.captures;
         }
-        
-        compiler.end_scope();
-        compiler.emit_return();
+        self.enclosing = enclosing;
+        function
+    }
+
+    /// Compile a function nested in `enclosing`, which gets back the
+    /// enclosing functions with the locals the new one captured marked.
+    fn compile_function_in(&mut self, name: &Token, parameters: &Vec<(Token, Option<String>)>, body: &Vec<Statement>, enclosing: &mut Vec<EnclosingFunction>) -> Result<(Function, Vec<Capture>), String> {
+        let mut compiler = Compiler::new();
+        compiler.in_function = true;
+        compiler.namespace = self.namespace.clone();
+        compiler.defines = self.defines.clone();
+        compiler.chunk.source_map.file = self.chunk.source_map.file.clone();
+        compiler.enclosing = std::mem::take(enclosing);
+        let compiled = compiler.compile_body(parameters, body);
+        *enclosing = std::mem::take(&mut compiler.enclosing);
+        compiled?;
         
         let function_name = match &name.token_type {
             TokenType::Identifier(name) => name.clone(),
             _ => "anonymous".to_string(),
         };
+        compiler.chunk.source_map.function = Some(function_name.clone());
         
-        Ok(Function {
+        let function = Function {
             name: function_name,
             arity: parameters.len(),
             chunk: compiler.chunk.clone(),
+            module: self.namespace.clone(),
+        };
+        Ok((function, compiler.captures))
+    }
+
+    fn compile_body(&mut self, parameters: &Vec<(Token, Option<String>)>, body: &Vec<Statement>) -> Result<(), String> {
+        self.begin_scope();
+        
+        // Add parameters as locals
+        for (param, _) in parameters {
+            let param = match &param.token_type {
+                TokenType::Identifier(_) => param.clone(),
+                // Methods take the receiver as an ordinary `self` local
+                TokenType::SelfKw => Token::new(TokenType::Identifier("self".to_string()), param.lexeme.clone(), param.line, param.column),
+                _ => return Err("Expected parameter name".to_string()),
+            };
+            self.declare_variable(&param)?;
+            self.define_variable(&param)?;
+        }
+        
+        self.with_jump_widths(|compiler| {
+            for statement in body {
+                compiler.compile_statement(statement)?;
+            }
+
+            // The scope is not closed: Return discards the frame's locals, and
+            // methods still need `self` in its slot at that point. Falling off
+            // the end returns null rather than whatever the caller left on the
+            // stack.
+            compiler.emit_byte(OpCode::Null);
+            compiler.emit_return();
+            Ok(())
         })
     }
 
+    fn compile_try(&mut self, try_block: &Vec<Statement>, catch_clauses: &[CatchClause], finally_block: Option<&Vec<Statement>>) -> Result<(), String> {
+        // An outer handler runs the finally block when the try block or a
+        // catch clause throws
+        let finally_jump = finally_block.map(|block| {
+            let jump = self.emit_jump(OpCode::Try);
+            self.cleanups.push(Cleanup::Finally(block.clone()));
+            jump
+        });
+
+        // Register a handler pointing at the catch clauses for the duration
+        // of the try block
+        let handler_jump = self.emit_jump(OpCode::Try);
+        self.cleanups.push(Cleanup::Try);
+        self.compile_block(try_block)?;
+        self.cleanups.pop();
+        self.emit_byte(OpCode::PopException);
+        let end_jump = self.emit_jump(OpCode::Jump);
+
+        // The VM unwinds to here with the exception value pushed
+        self.patch_jump(handler_jump)?;
+        self.begin_scope();
+        self.add_hidden_local("(exception)");
+        let mut done_jumps = Vec::new();
+        for clause in catch_clauses {
+            let mut next_clause = None;
+            if !clause.exception_types.is_empty() {
+                self.emit_byte(OpCode::Dup);
+                for exception_type in &clause.exception_types {
+                    self.compile_expression(exception_type)?;
+                }
+                self.emit_bytes(OpCode::ExceptionMatches, clause.exception_types.len() as u8);
+                next_clause = Some(self.emit_jump(OpCode::JumpIfFalse));
+                self.emit_byte(OpCode::Pop);
+            }
+
+            self.begin_scope();
+            if let Some(name) = &clause.name {
+                self.emit_byte(OpCode::Dup);
+                self.declare_variable(name)?;
+                self.define_variable(name)?;
+            }
+            self.compile_block(&clause.body)?;
+            self.end_scope();
+            done_jumps.push(self.emit_jump(OpCode::Jump));
+
+            if let Some(next_clause) = next_clause {
+                self.patch_jump(next_clause)?;
+                self.emit_byte(OpCode::Pop);
+            }
+        }
+        // No clause matched, so the exception keeps propagating
+        self.emit_byte(OpCode::Rethrow);
+        for jump in done_jumps {
+            self.patch_jump(jump)?;
+        }
+        self.pop_hidden_local(); // pop exception
+        self.end_scope();
+        self.patch_jump(end_jump)?;
+
+        if let (Some(block), Some(finally_jump)) = (finally_block, finally_jump) {
+            self.cleanups.pop();
+            self.emit_byte(OpCode::PopException);
+            self.compile_block(block)?;
+            let skip_jump = self.emit_jump(OpCode::Jump);
+
+            // Something threw: run the finally block, then re-raise
+            self.patch_jump(finally_jump)?;
+            self.begin_scope();
+            self.add_hidden_local("(exception)");
+            self.compile_block(block)?;
+            self.emit_byte(OpCode::Rethrow);
+            self.forget_hidden_local();
+            self.end_scope();
+            self.patch_jump(skip_jump)?;
+        }
+        Ok(())
+    }
+
+    /// Leave every enclosing `try`/`with` block ahead of a `return`, whose
+    /// value is on top of the stack, releasing `with` managers and running
+    /// `finally` blocks on the way.
+    fn emit_cleanups(&mut self) -> Result<(), String> {
+        // A top-level `return` ends the script, so there is nothing to unwind
+        if !self.in_function {
+            return Ok(());
+        }
+        self.emit_cleanups_to(0, true)
+    }
+
+    /// Leave the enclosing `try`/`with` blocks after the first `outer`,
+    /// innermost first. `returning` says a return value is on top of the
+    /// stack, to be kept there.
+    fn emit_cleanups_to(&mut self, outer: usize, returning: bool) -> Result<(), String> {
+        let cleanups = self.cleanups.clone();
+        for (depth, cleanup) in cleanups.iter().enumerate().skip(outer).rev() {
+            self.emit_byte(OpCode::PopException);
+            match cleanup {
+                Cleanup::Try => {}
+                Cleanup::With { manager_slot } => {
+                    let above_manager = self.stack_height() - 1 - manager_slot + usize::from(returning);
+                    self.emit_byte(OpCode::Null);
+                    self.emit_bytes(OpCode::WithExit, above_manager as u8);
+                }
+                // Only the blocks outside this one apply to a jump out of it
+                Cleanup::Finally(block) if returning => {
+                    self.cleanups.truncate(depth);
+                    self.begin_scope();
+                    self.add_hidden_local("(return value)");
+                    self.compile_block(block)?;
+                    // Leave the return value on the stack for `Return`
+                    self.locals.pop();
+                    self.scope_depth -= 1;
+                }
+                Cleanup::Finally(block) => {
+                    self.cleanups.truncate(depth);
+                    self.compile_block(block)?;
+                }
+            }
+        }
+        self.cleanups = cleanups;
+        Ok(())
+    }
+
+    /// Compile a loop's body, which starts at `start`, then `finish` the
+    /// loop; `break` jumps to the code after what `finish` emits.
+    fn compile_loop_body(&mut self, start: usize, body: &Vec<Statement>, finish: impl FnOnce(&mut Compiler) -> Result<(), String>) -> Result<(), String> {
+        self.loops.push(Loop { start, breaks: Vec::new(), stack_height: self.stack_height(), cleanups: self.cleanups.len() });
+        let compiled = self.compile_block(body);
+        let innermost = self.loops.pop().expect("pushed above");
+        compiled?;
+        finish(self)?;
+        for jump in innermost.breaks {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    /// Unwind what the loop body declared and entered, ahead of a `break`
+    /// or `continue`, leaving `stack_height` values on the stack. The
+    /// compiler's own view of the scope is kept for the code that follows.
+    fn emit_leave_loop_body(&mut self, stack_height: usize, cleanups: usize) -> Result<(), String> {
+        self.emit_cleanups_to(cleanups, false)?;
+        for index in (stack_height..self.stack_height()).rev() {
+            let captured = self.locals.get(index).is_some_and(|local| local.captured);
+            self.emit_byte(if captured { OpCode::CloseUpvalue } else { OpCode::Pop });
+        }
+        Ok(())
+    }
+
     fn compile_block(&mut self, statements: &Vec<Statement>) -> Result<(), String> {
         self.begin_scope();
         for statement in statements {
@@ -440,8 +1049,27 @@ impl Compiler {
         Ok(())
     }
 
+    /// Store the value on top of the stack into `name`. Assigning to a
+    /// variable that is already a local, or one captured from an enclosing
+    /// function, updates it instead of shadowing it in the current block.
+    fn assign_variable(&mut self, name: &Token) -> Result<(), String> {
+        let existing = match &name.token_type {
+            TokenType::Identifier(variable_name) => {
+                self.resolve_local(variable_name).is_some() || self.resolve_upvalue(variable_name).is_some()
+            }
+            _ => false,
+        };
+        if let (true, TokenType::Identifier(variable_name)) = (existing, &name.token_type) {
+            self.emit_store(variable_name)?;
+            Ok(())
+        } else {
+            self.declare_variable(name)?;
+            self.define_variable(name)
+        }
+    }
+
     fn declare_variable(&mut self, name: &Token) -> Result<(), String> {
-        if self.scope_depth > 0 {
+        if self.is_local_scope() {
             let variable_name = match &name.token_type {
                 TokenType::Identifier(name) => name.clone(),
                 _ => return Err("Expected identifier".to_string()),
@@ -460,7 +1088,7 @@ impl Compiler {
     }
 
     fn define_variable(&mut self, name: &Token) -> Result<(), String> {
-        if self.scope_depth > 0 {
+        if self.is_local_scope() {
             self.mark_initialized();
             Ok(())
         } else {
@@ -468,16 +1096,54 @@ impl Compiler {
                 TokenType::Identifier(name) => name.clone(),
                 _ => return Err("Expected identifier".to_string()),
             };
-            let constant = self.chunk.add_constant(Value::String(variable_name));
+            let constant = self.global_constant(&variable_name)?;
             self.emit_bytes(OpCode::SetGlobal, constant as u8);
             Ok(())
         }
     }
 
+    /// Reserve a local slot for a compiler-managed temporary. Top-level code
+    /// has no frame, so temporaries there are plain stack values instead.
+    fn add_hidden_local(&mut self, name: &str) {
+        if self.is_local_scope() {
+            self.add_local(name.to_string());
+        } else {
+            self.temporaries += 1;
+        }
+    }
+
+    /// Pop the newest temporary at the top level; a local slot is popped
+    /// when its scope ends instead.
+    fn pop_hidden_local(&mut self) {
+        if !self.is_local_scope() {
+            self.emit_byte(OpCode::Pop);
+            self.forget_hidden_local();
+        }
+    }
+
+    /// Stop counting the newest top-level temporary, which the code emitted
+    /// already took off the stack.
+    fn forget_hidden_local(&mut self) {
+        if !self.is_local_scope() {
+            self.temporaries -= 1;
+        }
+    }
+
+    /// Values the code being compiled has on the stack: locals in a
+    /// function, temporaries at the top level.
+    fn stack_height(&self) -> usize {
+        self.locals.len() + self.temporaries
+    }
+
+    fn is_local_scope(&self) -> bool {
+        self.in_function && self.scope_depth > 0
+    }
+
     fn add_local(&mut self, name: String) {
         self.locals.push(Local {
             name,
             depth: self.scope_depth,
+            captured: false,
         });
     }
 
@@ -487,8 +1153,90 @@ impl Compiler {
         }
     }
 
+    /// Pop the top of the stack into the named local, captured variable or
+    /// global.
+    fn emit_store(&mut self, name: &str) -> Result<(), String> {
+        if let Some(local) = self.resolve_local(name) {
+            self.emit_bytes(OpCode::SetLocal, local as u8);
+        } else if let Some(upvalue) = self.resolve_upvalue(name) {
+            self.emit_bytes(OpCode::SetUpvalue, upvalue as u8);
+        } else {
+            let constant = self.global_constant(name)?;
+            self.emit_bytes(OpCode::SetGlobal, constant as u8);
+        }
+        Ok(())
+    }
+
+    /// The constant naming global `name` for `SET_GLOBAL`. Defines read as
+    /// their value, so they can't be assigned.
+    fn global_constant(&mut self, name: &str) -> Result<usize, String> {
+        if self.defines.contains_key(name) {
+            return Err(format!("'{}' is a compile-time define and can't be assigned", name));
+        }
+        Ok(self.chunk.add_constant(Value::String(name.to_string())))
+    }
+
+    /// The value of an `if` condition made only of defines, literals, `==`,
+    /// `!=`, `and`, `or` and `not`, or `None` if it depends on the run.
+    /// Variables that shadow a define make the condition a runtime one.
+    fn constant_condition(&self, expression: &Expression) -> Option<Value> {
+        match expression {
+            Expression::Number(n) => Some(Value::Number(*n)),
+            Expression::String(s) => Some(Value::String(s.clone())),
+            Expression::Boolean(b) => Some(Value::Boolean(*b)),
+            Expression::Null => Some(Value::Null),
+            Expression::Grouping(inner) => self.constant_condition(inner),
+            Expression::Identifier(Token { token_type: TokenType::Identifier(name), .. }) => {
+                let shadowed = self.resolve_local(name).is_some()
+                    || self.enclosing.iter().any(|function| function.locals.iter().any(|local| &local.name == name));
+                if shadowed { None } else { self.defines.get(name).cloned() }
+            }
+            Expression::Unary { operator, right } if operator.token_type == TokenType::Not => {
+                Some(Value::Boolean(!VM::is_truthy(&self.constant_condition(right)?)))
+            }
+            Expression::Binary { left, operator, right } => {
+                let (left, right) = (self.constant_condition(left)?, self.constant_condition(right)?);
+                match operator.token_type {
+                    TokenType::Equal => Some(Value::Boolean(VM::values_equal(&left, &right))),
+                    TokenType::NotEqual => Some(Value::Boolean(!VM::values_equal(&left, &right))),
+                    TokenType::And => Some(Value::Boolean(VM::is_truthy(&left) && VM::is_truthy(&right))),
+                    TokenType::Or => Some(Value::Boolean(VM::is_truthy(&left) || VM::is_truthy(&right))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
     fn resolve_local(&self, name: &str) -> Option<usize> {
-        self.locals.iter().rev().position(|local| local.name == name)
+        self.locals.iter().rposition(|local| local.name == name)
+    }
+
+    /// Upvalue index of `name` if it is a local of an enclosing function,
+    /// capturing it the first time it is used.
+    fn resolve_upvalue(&mut self, name: &str) -> Option<usize> {
+        Self::capture(&mut self.enclosing, &mut self.captures, name)
+    }
+
+    /// Index in `captures` of the variable `name` from the innermost of
+    /// `enclosing` that has it as a local. The functions in between capture
+    /// it too, so each closure can hand it on to the next.
+    fn capture(enclosing: &mut [EnclosingFunction], captures: &mut Vec<Capture>, name: &str) -> Option<usize> {
+        let (parent, outer) = enclosing.split_last_mut()?;
+        let capture = match parent.locals.iter().rposition(|local| local.name == name) {
+            Some(slot) => {
+                parent.locals[slot].captured = true;
+                Capture::Local(slot)
+            }
+            None => Capture::Upvalue(Self::capture(outer, &mut parent.captures, name)?),
+        };
+        Some(match captures.iter().position(|existing| *existing == capture) {
+            Some(index) => index,
+            None => {
+                captures.push(capture);
+                captures.len() - 1
+            }
+        })
     }
 
     fn begin_scope(&mut self) {
@@ -498,53 +1246,87 @@ impl Compiler {
     fn end_scope(&mut self) {
         self.scope_depth -= 1;
         
-        while self.locals.last().map_or(false, |local| local.depth > self.scope_depth) {
-            self.emit_byte(OpCode::Pop);
-            self.locals.pop();
+        while let Some(local) = self.locals.pop_if(|local| local.depth > self.scope_depth) {
+            self.emit_byte(if local.captured { OpCode::CloseUpvalue } else { OpCode::Pop });
         }
     }
 
     fn emit_byte(&mut self, byte: OpCode) {
-        self.chunk.write(byte.to_byte(), 0);
+        self.chunk.write(byte.to_byte(), self.location);
     }
 
     fn emit_bytes(&mut self, byte1: OpCode, byte2: u8) {
         self.emit_byte(byte1);
-        self.chunk.write(byte2, 0);
+        self.chunk.write(byte2, self.location);
     }
 
     fn emit_return(&mut self) {
         self.emit_byte(OpCode::Return);
     }
 
+    /// Run `compile`, which emits this compiler's code. If a forward jump
+    /// turns out too far for a 16-bit offset, start over from the state
+    /// before it ran with every forward jump in its long form.
+    fn with_jump_widths(&mut self, compile: impl Fn(&mut Compiler) -> Result<(), String>) -> Result<(), String> {
+        let start = self.clone();
+        match compile(self) {
+            Err(_) if self.jump_overflowed && !self.long_jumps => {
+                *self = Compiler { long_jumps: true, ..start };
+                compile(self)
+            }
+            result => result,
+        }
+    }
+
+    /// Emit a forward jump with a placeholder offset for `patch_jump` to
+    /// fill in, returning the jump's position.
     fn emit_jump(&mut self, instruction: OpCode) -> usize {
+        let (instruction, width) = if self.long_jumps { (instruction.long_form(), 4) } else { (instruction, 2) };
         self.emit_byte(instruction);
-        self.chunk.write(0, 0);
-        self.chunk.write(0, 0);
-        self.chunk.code.len() - 3
+        for _ in 0..width {
+            self.chunk.write(0, self.location);
+        }
+        self.chunk.code.len() - 1 - width
     }
 
-    fn patch_jump(&mut self, offset: usize) {
-        let jump = self.chunk.code.len() - offset - 3;
-        
-        if jump > u16::MAX as usize {
-            panic!("Too much code to jump over");
+    /// Point the jump at `offset` to the end of the code emitted so far.
+    fn patch_jump(&mut self, offset: usize) -> Result<(), String> {
+        let long = OpCode::from_byte(self.chunk.code[offset]).is_some_and(|op| op.is_long_jump());
+        let width = if long { 4 } else { 2 };
+        let jump = self.chunk.code.len() - offset - 1 - width;
+        if long {
+            let jump = u32::try_from(jump).map_err(|_| "Too much code to jump over".to_string())?;
+            self.chunk.code[offset + 1..offset + 5].copy_from_slice(&jump.to_be_bytes());
+        } else {
+            let jump = u16::try_from(jump).map_err(|_| {
+                self.jump_overflowed = true;
+                "Too much code to jump over".to_string()
+            })?;
+            self.chunk.code[offset + 1..offset + 3].copy_from_slice(&jump.to_be_bytes());
         }
-        
-        self.chunk.code[offset + 1] = ((jump >> 8) & 0xff) as u8;
-        self.chunk.code[offset + 2] = (jump & 0xff) as u8;
+        Ok(())
     }
 
-    fn emit_loop(&mut self, loop_start: usize) {
-        let offset = self.chunk.code.len() - loop_start + 2;
-        
-        if offset > u16::MAX as usize {
-            panic!("Loop body too large");
+    /// Jump back to `loop_start`; the distance is known, so the offset is
+    /// only as wide as it needs to be.
+    fn emit_loop(&mut self, loop_start: usize) -> Result<(), String> {
+        match u16::try_from(self.chunk.code.len() - loop_start + 3) {
+            Ok(offset) => {
+                self.emit_byte(OpCode::Loop);
+                for byte in offset.to_be_bytes() {
+                    self.chunk.write(byte, self.location);
+                }
+            }
+            Err(_) => {
+                let offset = u32::try_from(self.chunk.code.len() - loop_start + 5)
+                    .map_err(|_| "Loop body too large".to_string())?;
+                self.emit_byte(OpCode::LoopLong);
+                for byte in offset.to_be_bytes() {
+                    self.chunk.write(byte, self.location);
+                }
+            }
         }
-        
-        self.emit_byte(OpCode::Loop);
-        self.chunk.write(((offset >> 8) & 0xff) as u8, 0);
-        self.chunk.write((offset & 0xff) as u8, 0);
+        Ok(())
     }
 }
 
@@ -560,7 +1342,7 @@ mod tests {
         let mut parser = Parser::new(tokens);
         let program = parser.parse()?;
         let mut compiler = Compiler::new();
-        compiler.compile(&program).map(|chunk| chunk.clone())
+        compiler.compile(&program).cloned()
     }
 
     #[test]
@@ -600,6 +1382,32 @@ mod tests {
         assert!(!chunk.code.is_empty());
     }
 
+    #[test]
+    fn test_compile_break_outside_loop() {
+        assert_eq!(compile_code("break").unwrap_err(), "'break' outside of a loop");
+        // A function body doesn't continue the loop around its definition
+        let error = compile_code("while true:\n    def f():\n        continue").unwrap_err();
+        assert_eq!(error, "'continue' outside of a loop");
+    }
+
+    #[test]
+    fn test_compile_picks_jump_widths() {
+        let chunk = compile_code("while true:\n    if false:\n        1").unwrap();
+        assert!(chunk.code.contains(&OpCode::Loop.to_byte()));
+        assert!(!chunk.code.iter().any(|&byte| OpCode::from_byte(byte).is_some_and(|op| op.is_long_jump())));
+
+        // Over 64 KiB of locals shuffling needs 32-bit offsets both ways
+        let padding = "        a = b\n".repeat(20_000);
+        let chunk = compile_code(&format!("def f(a, b):\n    while a:\n{}        a = false\nf(true, 1)", padding)).unwrap();
+        let function = chunk.constants.iter().find_map(|constant| match constant {
+            Value::Function(function) => Some(function),
+            _ => None,
+        }).unwrap();
+        assert!(function.chunk.code.len() > u16::MAX as usize);
+        assert!(function.chunk.code.contains(&OpCode::JumpIfFalseLong.to_byte()));
+        assert!(function.chunk.code.contains(&OpCode::LoopLong.to_byte()));
+    }
+
     #[test]
     fn test_compile_function() {
         let chunk = compile_code("def test():\n    return 1").unwrap();
@@ -615,15 +1423,39 @@ mod tests {
     #[test]
     fn test_compile_use() {
         let chunk = compile_code("use math").unwrap();
-        // Use statements don't emit opcodes since they're processed before compilation
-        assert_eq!(chunk.code, vec![13]);
+        // Import the module at runtime, then bind it to its name
+        assert_eq!(chunk.code[0], OpCode::Import.to_byte());
+        assert_eq!(chunk.code[2], OpCode::SetGlobal.to_byte());
+        assert!(matches!(&chunk.constants[chunk.code[3] as usize], Value::String(name) if name == "math"));
     }
 
     #[test]
     fn test_compile_use_with_alias() {
         let chunk = compile_code("use math as m").unwrap();
-        // Use statements don't emit opcodes since they're processed before compilation
-        assert_eq!(chunk.code, vec![13]);
+        assert_eq!(chunk.code[0], OpCode::Import.to_byte());
+        assert!(matches!(&chunk.constants[chunk.code[3] as usize], Value::String(name) if name == "m"));
+    }
+
+    #[test]
+    fn test_compile_lazy_use_defers_import() {
+        let chunk = compile_code("lazy use math").unwrap();
+        assert!(!chunk.code.contains(&OpCode::Import.to_byte()));
+        assert!(chunk.constants.iter().any(|constant| matches!(constant, Value::Module(name) if name == "math")));
+    }
+
+    #[test]
+    fn test_compile_if_on_defines_keeps_taken_branch() {
+        let source = "if platform == \"windows\" and not debug:\n    windows_only()\nelse:\n    elsewhere()";
+        let program = Parser::new(Lexer::new(source.to_string()).tokenize().unwrap()).parse().unwrap();
+        let defines = HashMap::from([
+            ("platform".to_string(), Value::String("linux".to_string())),
+            ("debug".to_string(), Value::Boolean(false)),
+        ]);
+        let chunk = Compiler::new().with_defines(defines).compile(&program).unwrap().clone();
+        let named = |wanted: &str| chunk.constants.iter().any(|constant| matches!(constant, Value::String(name) if name == wanted));
+        assert!(named("elsewhere"));
+        assert!(!named("windows_only"));
+        assert!(!chunk.code.contains(&OpCode::JumpIfFalse.to_byte()));
     }
 
 
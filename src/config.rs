@@ -0,0 +1,308 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::bytecode::Value;
+use crate::edition::Edition;
+use crate::environment::Environment;
+use crate::sandbox::{require, Capability};
+use crate::vm::VM;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the project configuration file looked up by `config_load` and the CLI tools.
+pub const CONFIG_FILE_NAME: &str = "grease.toml";
+
+/// Project-level tool configuration read from `grease.toml`.
+///
+/// The same file is consumed by the linter (`[lint]` section) and by user
+/// scripts through the `config_load` native, so every tool sees one source of truth.
+#[derive(Debug, Clone)]
+pub struct ProjectConfig {
+    pub path: PathBuf,
+    table: toml::Table,
+}
+
+impl ProjectConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("IOError: Failed to read config '{}': {}", path.display(), e))?;
+        let mut config = Self::parse(&source)
+            .map_err(|e| format!("Invalid config '{}': {}", path.display(), e))?;
+        config.path = path.to_path_buf();
+        Ok(config)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let table = source.parse::<toml::Table>().map_err(|e| e.message().to_string())?;
+        let config = ProjectConfig {
+            path: PathBuf::from(CONFIG_FILE_NAME),
+            table,
+        };
+        if let Some(version) = config.get_string("syntax", "version") {
+            Edition::parse(version).map_err(|e| format!("[syntax] version: {}", e))?;
+        }
+        Ok(config)
+    }
+
+    /// Walk up from `start` looking for `grease.toml`.
+    pub fn discover(start: &Path) -> Option<PathBuf> {
+        let start = crate::environment::canonical(start).ok()?;
+        let dir = if start.is_file() { start.parent()? } else { &start };
+        find_upwards(dir, Path::new(CONFIG_FILE_NAME))
+    }
+
+    pub fn section(&self, name: &str) -> Option<&toml::Table> {
+        self.table.get(name).and_then(|v| v.as_table())
+    }
+
+    pub fn get_bool(&self, section: &str, key: &str) -> Option<bool> {
+        self.section(section)?.get(key)?.as_bool()
+    }
+
+    pub fn get_string(&self, section: &str, key: &str) -> Option<&str> {
+        self.section(section)?.get(key)?.as_str()
+    }
+
+    pub fn get_integer(&self, section: &str, key: &str) -> Option<i64> {
+        self.section(section)?.get(key)?.as_integer()
+    }
+
+    /// Columns a tab indents to in source files (`[syntax] tab_width`).
+    pub fn tab_width(&self) -> Option<usize> {
+        self.get_integer("syntax", "tab_width").and_then(|width| usize::try_from(width).ok()).filter(|&width| width > 0)
+    }
+
+    /// Language edition for sources without a version pragma
+    /// (`[syntax] version`).
+    pub fn edition(&self) -> Option<Edition> {
+        self.get_string("syntax", "version").and_then(|version| Edition::parse(version).ok())
+    }
+
+    pub fn to_value(&self) -> Value {
+        table_to_value(&self.table)
+    }
+}
+
+fn table_to_value(table: &toml::Table) -> Value {
+    let mut dict = HashMap::with_capacity(table.len());
+    for (key, value) in table {
+        dict.insert(key.clone(), toml_to_value(value));
+    }
+    Value::dictionary(dict)
+}
+
+fn toml_to_value(value: &toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s.clone()),
+        toml::Value::Integer(i) => Value::Number(*i as f64),
+        toml::Value::Float(f) => Value::Number(*f),
+        toml::Value::Boolean(b) => Value::Boolean(*b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(items) => Value::array(items.iter().map(toml_to_value).collect()),
+        toml::Value::Table(table) => table_to_value(table),
+    }
+}
+
+/// Parse the contents of a `.env` file into key/value pairs, in file order.
+///
+/// Supports `#` comments, an optional `export ` prefix, and single or double
+/// quoted values (double quotes understand `\n`, `\t`, `\"` and `\\`).
+pub fn parse_dotenv(source: &str) -> Result<Vec<(String, String)>, String> {
+    let mut pairs = Vec::new();
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Expected KEY=VALUE at line {}", line_number))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("Invalid variable name '{}' at line {}", key, line_number));
+        }
+
+        let value = value.trim();
+        let value = if let Some(rest) = value.strip_prefix('"') {
+            let end = rest
+                .rfind('"')
+                .ok_or_else(|| format!("Unterminated string at line {}", line_number))?;
+            unescape_double_quoted(&rest[..end])
+        } else if let Some(rest) = value.strip_prefix('\'') {
+            let end = rest
+                .rfind('\'')
+                .ok_or_else(|| format!("Unterminated string at line {}", line_number))?;
+            rest[..end].to_string()
+        } else {
+            // Unquoted values may carry a trailing comment
+            match value.find(" #") {
+                Some(pos) => value[..pos].trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+
+        pairs.push((key.to_string(), value));
+    }
+
+    Ok(pairs)
+}
+
+fn unescape_double_quoted(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Load a `.env` file into `environment`, returning how many variables were set.
+///
+/// Variables that are already present in the environment are left untouched.
+pub fn load_dotenv(path: &Path, environment: &mut Environment) -> Result<usize, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("IOError: Failed to read '{}': {}", path.display(), e))?;
+    let pairs = parse_dotenv(&source).map_err(|e| format!("ValueError: {}: {}", path.display(), e))?;
+
+    let mut loaded = 0;
+    for (key, value) in pairs {
+        if environment.get(&key).is_none() {
+            environment.set(&key, Some(&value));
+            loaded += 1;
+        }
+    }
+    Ok(loaded)
+}
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("dotenv_load", 1, |vm, args| {
+        require(vm, Capability::Filesystem, "dotenv_load")?;
+        require(vm, Capability::Environment, "dotenv_load")?;
+        match &args[0] {
+            Value::String(path) => {
+                let path = vm.resolve_path(path);
+                load_dotenv(&path, &mut vm.environment).map(|count| Value::Number(count as f64))
+            }
+            _ => Err("TypeError: dotenv_load expects a path string".to_string()),
+        }
+    });
+
+    vm.register_native("config_load", 1, |vm, args| {
+        require(vm, Capability::Filesystem, "config_load")?;
+        let (path, resolved) = match &args[0] {
+            Value::String(path) => (PathBuf::from(path), vm.resolve_path(path)),
+            _ => return Err("TypeError: config_load expects a path string".to_string()),
+        };
+        // Relative names are resolved against the project, searching parent
+        // directories of the script (or the working directory)
+        let resolved = if path.is_relative() && !resolved.exists() {
+            let start = match &vm.script_dir {
+                Some(dir) => dir.clone(),
+                None => std::env::current_dir().map_err(|e| format!("IOError: {}", e))?,
+            };
+            find_upwards(&start, &path)
+                .ok_or_else(|| format!("IOError: Config file '{}' not found", path.display()))?
+        } else {
+            resolved
+        };
+        ProjectConfig::load(&resolved).map(|config| config.to_value())
+    });
+}
+
+fn find_upwards(start: &Path, relative: &Path) -> Option<PathBuf> {
+    let mut dir = start;
+    loop {
+        let candidate = dir.join(relative);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        dir = dir.parent()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dotenv_basic() {
+        let pairs = parse_dotenv("# comment\nFOO=bar\n\nexport BAZ = qux # trailing\n").unwrap();
+        assert_eq!(pairs, vec![
+            ("FOO".to_string(), "bar".to_string()),
+            ("BAZ".to_string(), "qux".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_dotenv_quotes() {
+        let pairs = parse_dotenv("A=\"line\\nnext\"\nB='raw \\n # kept'").unwrap();
+        assert_eq!(pairs[0].1, "line\nnext");
+        assert_eq!(pairs[1].1, "raw \\n # kept");
+    }
+
+    #[test]
+    fn test_parse_dotenv_errors() {
+        assert!(parse_dotenv("NOT A PAIR").is_err());
+        assert!(parse_dotenv("BAD-NAME=1").is_err());
+        assert!(parse_dotenv("A=\"open").is_err());
+    }
+
+    #[test]
+    fn test_project_config_to_value() {
+        let config = ProjectConfig::parse("name = \"demo\"\n[lint]\nunused_variables = false\nlevel = 2\n").unwrap();
+        assert_eq!(config.get_bool("lint", "unused_variables"), Some(false));
+        match config.to_value() {
+            Value::Dictionary(dict) => {
+                let dict = dict.lock().unwrap();
+                assert!(matches!(dict.get("name"), Some(Value::String(s)) if s == "demo"));
+                match dict.get("lint") {
+                    Some(Value::Dictionary(lint)) => {
+                        assert!(matches!(lint.lock().unwrap().get("level"), Some(Value::Number(n)) if *n == 2.0));
+                    }
+                    other => panic!("Expected lint table, got {:?}", other),
+                }
+            }
+            other => panic!("Expected dictionary, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dotenv_load_native() {
+        let dir = std::env::temp_dir().join(format!("grease_dotenv_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let env_path = dir.join(".env");
+        fs::write(&env_path, "GREASE_DOTENV_TEST_VALUE=loaded\n").unwrap();
+
+        let mut grease = crate::grease::Grease::new();
+        let source = format!("count = dotenv_load(\"{}\")", env_path.display());
+        let result = grease.run(&source).unwrap();
+        assert_eq!(result, crate::vm::InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("count"), Some(Value::Number(n)) if *n == 1.0));
+        assert_eq!(grease.vm.environment.get("GREASE_DOTENV_TEST_VALUE").as_deref(), Some("loaded"));
+        assert!(std::env::var_os("GREASE_DOTENV_TEST_VALUE").is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_load_native_missing_file() {
+        let mut grease = crate::grease::Grease::new();
+        let result = grease.run("config_load(\"definitely_missing_grease_config.toml\")").unwrap();
+        assert!(matches!(result, crate::vm::InterpretResult::RuntimeError(msg) if msg.contains("not found")));
+    }
+}
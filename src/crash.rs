@@ -0,0 +1,134 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Crash reports: what a run that failed or panicked left in the VM, as a
+//! text file to attach to a bug report. `grease --crash-report[=DIR]` writes
+//! one; nothing is ever sent anywhere.
+//!
+//! A report holds the Grease version and platform, the script's path, the
+//! error and traceback, the values on the VM's stack (shortened), the names
+//! and kinds of the script's globals but not their values, and the
+//! disassembled bytecode of the script. Stack values can still be private,
+//! so the report says to look it over before sharing it.
+
+use crate::bytecode::Value;
+use crate::source_map::TraceFrame;
+use crate::vm::VM;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Longest a stack value is shown, in characters.
+const PREVIEW_LEN: usize = 80;
+
+/// The report for a run of `script` (`None` for inline code) that stopped
+/// with `error`. An empty `traceback` is taken from where the VM stopped,
+/// as after a panic.
+pub fn report(vm: &VM, script: Option<&str>, error: &str, traceback: &[TraceFrame]) -> String {
+    let mut out = String::new();
+    out.push_str("Grease crash report\n");
+    out.push_str("Review this file before sharing it: the stack below can hold values from the script.\n");
+    out.push('\n');
+    out.push_str(&format!("version: {}\n", crate::VERSION));
+    out.push_str(&format!("platform: {} {}\n", std::env::consts::OS, std::env::consts::ARCH));
+    out.push_str(&format!("script: {}\n", script.unwrap_or("<eval>")));
+    out.push_str(&format!("error: {}\n", error));
+
+    let captured;
+    let traceback = if traceback.is_empty() {
+        captured = vm.capture_traceback();
+        &captured
+    } else {
+        traceback
+    };
+    out.push_str("\ntraceback:\n");
+    for frame in traceback {
+        out.push_str(&format!("  {}\n", frame));
+    }
+
+    out.push_str(&format!("\nstack ({} values, bottom first):\n", vm.stack.len()));
+    for (slot, value) in vm.stack.iter().enumerate() {
+        out.push_str(&format!("  {:4}  {}: {}\n", slot, kind(value), preview(value)));
+    }
+
+    let mut globals: Vec<(&String, &Value)> = vm.globals.iter()
+        .filter(|(name, value)| !vm.is_builtin(name) && !matches!(value, Value::NativeFunction(_)))
+        .collect();
+    globals.sort_by(|a, b| a.0.cmp(b.0));
+    out.push_str(&format!("\nglobals ({}):\n", globals.len()));
+    for (name, value) in globals {
+        out.push_str(&format!("  {}: {}\n", name, kind(value)));
+    }
+
+    out.push_str("\nbytecode:\n");
+    match vm.script_chunk() {
+        Some(chunk) => out.push_str(&chunk.disassembly(script.unwrap_or("<eval>"))),
+        None => out.push_str("(none)\n"),
+    }
+    out
+}
+
+/// Write `report` to a new `grease-crash-<time>-<pid>.txt` in `dir`,
+/// returning its path.
+pub fn write_report(dir: &Path, report: &str) -> Result<PathBuf, String> {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let path = dir.join(format!("grease-crash-{}-{}.txt", seconds, std::process::id()));
+    std::fs::write(&path, report).map_err(|e| format!("Failed to write crash report '{}': {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// What a value is, with its size for collections: `array of 3`.
+fn kind(value: &Value) -> String {
+    match value {
+        Value::Number(_) => "number".to_string(),
+        Value::String(s) => format!("string of {}", s.chars().count()),
+        Value::Bytes(bytes) => format!("bytes of {}", bytes.len()),
+        Value::Boolean(_) => "boolean".to_string(),
+        Value::Null => "null".to_string(),
+        Value::Function(_) | Value::Closure(_) => "function".to_string(),
+        Value::NativeFunction(_) => "native".to_string(),
+        Value::Array(items) => format!("array of {}", items.lock().unwrap().len()),
+        Value::Tuple(items) => format!("tuple of {}", items.len()),
+        Value::Dictionary(entries) => format!("dictionary of {}", entries.lock().unwrap().len()),
+        Value::Object { class_name, .. } => format!("{} object", class_name),
+        Value::Class { .. } => "class".to_string(),
+        Value::Module(_) => "module".to_string(),
+        &Value::Range { start, stop, step } => format!("range of {}", crate::vm::range_len(start, stop, step)),
+    }
+}
+
+fn preview(value: &Value) -> String {
+    let text = VM::format_value(value);
+    match text.char_indices().nth(PREVIEW_LEN) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Grease, InterpretResult};
+
+    #[test]
+    fn test_report_describes_the_failed_run() {
+        let mut grease = Grease::new();
+        let outcome = grease.evaluate("secret = \"hunter2\"\nitems = [1, 2]\ndef fail(x):\n    return x[5]\nfail(items)").unwrap();
+        let InterpretResult::RuntimeError(error) = &outcome.result else { panic!("{:?}", outcome.result) };
+        let report = super::report(&grease.vm, Some("fail.grease"), error, &outcome.traceback);
+
+        assert!(report.contains(&format!("version: {}", crate::VERSION)));
+        assert!(report.contains("script: fail.grease"));
+        assert!(report.contains("error: IndexError: Index 5 out of bounds for array of length 2"));
+        assert!(report.contains("  at fail (line 4, column"));
+        assert!(report.contains("array of 2: [1, 2]"));
+        assert!(report.contains("  secret: string of 7\n"));
+        assert!(!report.contains("  print:"));
+        assert!(report.contains("== fail.grease ==") && report.contains("CALL"));
+        assert!(report.contains("== fail ==\n") && report.contains("<fn fail>"));
+
+        let dir = std::env::temp_dir().join(format!("grease-crash-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = super::write_report(&dir, &report).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), report);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Documentation for natives: what each one does and what its parameters
+//! are. The built-in natives' docs are in [`BUILTINS`]; hosts document
+//! their own with `VM::document_native`. Scripts read them with `help`,
+//! the language server shows them on hover and `grease docs` writes them
+//! all out as Markdown:
+//!
+//! ```text
+//! >>> help(getenv)
+//! getenv(name)
+//!   The environment variable as the script sees it, or null if it isn't set.
+//!   name: the variable's name
+//! ```
+
+use crate::bytecode::{Arity, NativeFunction, Value};
+use crate::vm::VM;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NativeDoc {
+    pub summary: String,
+    /// Each parameter's name and description, in order.
+    pub params: Vec<(String, String)>,
+}
+
+impl NativeDoc {
+    pub fn new(summary: &str) -> Self {
+        NativeDoc { summary: summary.to_string(), params: Vec::new() }
+    }
+
+    pub fn param(mut self, name: &str, description: &str) -> Self {
+        self.params.push((name.to_string(), description.to_string()));
+        self
+    }
+}
+
+/// A parameter's name and description.
+pub type ParamDoc = (&'static str, &'static str);
+
+/// Name, parameters and summary of each native `VM::new` registers.
+pub const BUILTINS: &[(&str, &[ParamDoc], &str)] = &[
+    ("argparse_help", &[("spec", "the parser specification")], "The help text for a parser specification."),
+    ("argparse_parse", &[("spec", "the parser specification"), ("args", "arguments to parse, or null for the script's")],
+        "Parse command-line arguments into a dictionary, or print the help and return null for --help."),
+    ("bytes_base64", &[("data", "the bytes")], "Bytes as base64 text."),
+    ("bytes_decode", &[("data", "the bytes")], "Decode UTF-8 bytes to a string."),
+    ("bytes_encode", &[("text", "the string")], "Encode a string as UTF-8 bytes."),
+    ("bytes_from_array", &[("numbers", "an array of numbers 0-255")], "Bytes from an array of numbers."),
+    ("bytes_from_base64", &[("text", "base64 text")], "Bytes from base64 text."),
+    ("bytes_from_hex", &[("text", "hexadecimal text")], "Bytes from hexadecimal text."),
+    ("bytes_hex", &[("data", "the bytes")], "Bytes as hexadecimal text."),
+    ("bytes_len", &[("data", "the bytes")], "How many bytes there are."),
+    ("bytes_to_array", &[("data", "the bytes")], "Bytes as an array of numbers 0-255."),
+    ("cache", &[("function", "the function to wrap"), ("max_size", "results to keep, or null for no limit")],
+        "Wrap a function so repeated calls with the same arguments return a cached result."),
+    ("cache_clear", &[("function", "a function made by memoize or cache")], "Empty a memoized function's cache."),
+    ("cache_info", &[("function", "a function made by memoize or cache")], "A memoized function's hits, misses, size and max_size."),
+    ("chdir", &[("path", "the directory, relative to the current one")], "Change the script's working directory."),
+    ("close", &[("handle", "a resource handle")], "Close a file or other resource; closing it again does nothing."),
+    ("config_load", &[("path", "the file, searched for up the project's directories if relative")],
+        "Load a grease.toml-style configuration file as a dictionary."),
+    ("copy", &[("value", "any value")], "A new array, dictionary or object holding the same items as the value."),
+    ("deepcopy", &[("value", "any value")], "A copy of the value and everything in it."),
+    ("deserialize", &[("text", "text made by serialize")], "The value serialized text stands for."),
+    ("dict_get", &[("dict", "the dictionary"), ("key", "the key"), ("default", "what to return if the key is missing")],
+        "The value at a key, or the default."),
+    ("dict_has", &[("dict", "the dictionary"), ("key", "the key")], "Whether the dictionary has the key."),
+    ("dict_merge", &[("base", "the dictionary"), ("overrides", "entries to add or replace")],
+        "A copy of the dictionary with the other's entries added."),
+    ("dict_remove", &[("dict", "the dictionary"), ("key", "the key")], "A copy of the dictionary without the key."),
+    ("dir_hash", &[("path", "the directory")], "The SHA-256 of every file in a directory tree and their paths, as hex."),
+    ("dotenv_load", &[("path", "the .env file")], "Set the variables a .env file lists, returning how many."),
+    ("enumerate", &[("iterable", "what to loop over"), ("start", "the first index, 0 if not given")],
+        "(index, element) tuples for what a for loop over the iterable visits."),
+    ("environ", &[], "Every environment variable as the script sees them."),
+    ("equals", &[("a", "a value"), ("b", "another value")], "Whether two values are equal, element by element."),
+    ("file_hash", &[("path", "the file"), ("algo", "sha256 if not given, or sha224, sha384 or sha512")],
+        "The hex digest of a file's contents."),
+    ("file_open", &[("path", "the file"), ("mode", "\"r\" to read, \"w\" to write or \"a\" to append")],
+        "Open a file, returning a handle that also works with `with`."),
+    ("file_read_line", &[("file", "a handle open for reading")], "The next line without its ending, or null at the end."),
+    ("file_write", &[("file", "a handle open for writing"), ("text", "the string")], "Write a string to a file."),
+    ("file_write_line", &[("file", "a handle open for writing"), ("text", "the string")], "Write a string and a newline to a file."),
+    ("getcwd", &[], "The script's working directory."),
+    ("getenv", &[("name", "the variable's name")], "The environment variable as the script sees it, or null if it isn't set."),
+    ("help", &[("native", "a native function or its name")], "What a native does and what its parameters are."),
+    ("hash", &[("value", "any value but a function")], "A hash of the value that's the same in every run."),
+    ("input", &[("prompt", "text to show first")], "Read a line of input, or null at the end of the input."),
+    ("is_finite", &[("number", "the number")], "Whether the number is neither infinite nor NaN."),
+    ("is_nan", &[("number", "the number")], "Whether the number is NaN."),
+    ("max", &[("iterable", "the elements"), ("key", "a function whose result is compared instead, or null")],
+        "The largest element."),
+    ("memoize", &[("function", "the function to wrap")],
+        "Wrap a function so repeated calls with the same arguments return a cached result."),
+    ("min", &[("iterable", "the elements"), ("key", "a function whose result is compared instead, or null")],
+        "The smallest element."),
+    ("module_exists", &[("name", "the module's name")], "Whether `use` can load the module."),
+    ("native_add", &[("a", "a number"), ("b", "another number")], "The sum of two numbers."),
+    ("parse_float", &[("text", "the string")], "The number a string holds; throws a ValueError if it isn't one."),
+    ("parse_int", &[("text", "the string"), ("base", "the base, 2 to 36")],
+        "The integer a string holds in a base; throws a ValueError if it isn't one."),
+    ("perf_counters", &[], "Instructions run, allocations and timings of the current run."),
+    ("perf_reset", &[], "Start the performance counters again from zero."),
+    ("range", &[("start", "the first number; with one argument it's the stop and counting starts at 0"),
+        ("stop", "where counting stops, not included"), ("step", "how much to count by, 1 if not given")],
+        "The numbers from start up to stop, made one at a time as a for loop asks for them."),
+    ("read_file_bytes", &[("path", "the file")], "A file's contents as bytes."),
+    ("retry", &[("function", "called with no arguments"), ("attempts", "most calls to make, 3 if not given"),
+        ("backoff_ms", "milliseconds to wait after the first failure, doubling after each; 100 if not given")],
+        "What the function returns once a call doesn't throw; rethrows the last error if none succeed."),
+    ("round", &[("number", "the number"), ("digits", "decimal places; negative rounds to tens, hundreds...")],
+        "The number rounded, halves away from zero."),
+    ("script_args", &[], "The arguments given after the script's path."),
+    ("serialize", &[("value", "the value")], "The value as text that deserialize turns back into it."),
+    ("shell_join", &[("args", "an array of strings")], "A command line that runs the arguments, each quoted as needed."),
+    ("shell_quote", &[("text", "the string")], "The string quoted for the shell."),
+    ("shell_split", &[("command", "a command line")], "Split a command line into arguments as the shell would."),
+    ("sorted", &[("iterable", "the elements"), ("key", "a function whose result is compared instead, or null")],
+        "The elements in ascending order."),
+    ("sum", &[("iterable", "the numbers"), ("key", "a function whose result is added instead, or null")],
+        "The total of the numbers."),
+    ("system.monitor_process", &[("pid", "the process, or null for the interpreter"), ("samples", "how many samples to take"),
+        ("interval_ms", "milliseconds between samples"), ("callback", "called with each sample; returning false stops, or null")],
+        "Sample a process's CPU, memory and open files, returning the samples."),
+    ("system.setenv", &[("name", "the variable's name"), ("value", "the value, or null to unset it")],
+        "Set an environment variable as the script sees it."),
+    ("term.clear", &[], "Clear the terminal."),
+    ("term.clear_line", &[], "Clear the terminal's current line."),
+    ("term.move_cursor", &[("column", "the column, from 0"), ("row", "the row, from 0")], "Move the terminal's cursor."),
+    ("term.progress", &[("current", "progress so far"), ("total", "progress when done"), ("width", "the bar's width, from 1 to 10000")],
+        "A progress bar as a string."),
+    ("term.read_key", &[], "Wait for a key press and return its name."),
+    ("term.show_cursor", &[("visible", "whether to show it")], "Show or hide the terminal's cursor."),
+    ("term.size", &[], "The terminal's columns and rows."),
+    ("term.spinner", &[("frame", "the frame number")], "A frame of a spinner animation."),
+    ("term.styled", &[("text", "the string"), ("style", "words such as \"red bold\" or \"white on_blue\"")],
+        "The text with terminal colors and styles, unless output isn't a terminal."),
+    ("term.write", &[("text", "the string")], "Write text to the script's output without a newline."),
+    ("to_bin", &[("number", "an integer")], "The integer in binary, without a prefix."),
+    ("to_hex", &[("number", "an integer")], "The integer in hexadecimal, without a prefix."),
+    ("to_oct", &[("number", "an integer")], "The integer in octal, without a prefix."),
+    ("until", &[("function", "called with no arguments"), ("timeout_ms", "milliseconds to keep checking for")],
+        "The first truthy value the function returns, checking again less and less often; throws once the time is up."),
+    ("warn", &[("message", "the warning")], "Report a warning to the host without stopping the script."),
+    ("watch", &[("paths", "a file or directory, or an array of them"), ("callback", "called with the changed paths; returning false stops"),
+        ("interval_ms", "milliseconds between looks, 500 if not given")],
+        "Call a function whenever files are added, removed or modified."),
+    ("write_file_bytes", &[("path", "the file"), ("data", "the bytes")], "Replace a file's contents with bytes."),
+    ("zip", &[("iterables", "one or more things to loop over")], "Tuples of the iterables' elements in step, up to the shortest."),
+];
+
+/// The built-in docs for native `name`.
+pub fn builtin(name: &str) -> Option<NativeDoc> {
+    let (_, params, summary) = BUILTINS.iter().find(|(builtin, _, _)| *builtin == name)?;
+    Some(params.iter().fold(NativeDoc::new(summary), |doc, (param, description)| doc.param(param, description)))
+}
+
+/// `name(a, b)`, or the number of arguments if the parameters aren't
+/// documented.
+fn signature(name: &str, doc: Option<&NativeDoc>, arity: Option<Arity>) -> String {
+    match (doc, arity) {
+        (Some(doc), _) if !doc.params.is_empty() || arity.is_none_or(|arity| arity.max == 0) => {
+            let params: Vec<&str> = doc.params.iter().map(|(param, _)| param.as_str()).collect();
+            format!("{}({})", name, params.join(", "))
+        }
+        (_, Some(arity)) => format!("{}(...) takes {}", name, arity),
+        (_, None) => format!("{}(...)", name),
+    }
+}
+
+/// What `help` shows for native `name`.
+pub fn help_text(name: &str, doc: Option<&NativeDoc>, arity: Option<Arity>) -> String {
+    let mut text = signature(name, doc, arity);
+    match doc {
+        Some(doc) => {
+            text += &format!("\n  {}", doc.summary);
+            for (param, description) in &doc.params {
+                text += &format!("\n  {}: {}", param, description);
+            }
+        }
+        None => text += "\n  No documentation.",
+    }
+    text
+}
+
+/// Markdown for the language server's hover.
+pub fn hover_markdown(name: &str, doc: &NativeDoc) -> String {
+    let mut text = format!("```grease\n{}\n```\n{}", signature(name, Some(doc), None), doc.summary);
+    if !doc.params.is_empty() {
+        text += "\n";
+    }
+    for (param, description) in &doc.params {
+        text += &format!("\n- `{}`: {}", param, description);
+    }
+    text
+}
+
+/// A Markdown reference of every native `vm` has as a global, sorted by
+/// name.
+/// The global natives, module natives under their qualified names, such as
+/// `term.styled`.
+fn natives(vm: &VM) -> impl Iterator<Item = (&str, Arity)> {
+    vm.globals.iter().filter_map(|(_, value)| match value {
+        Value::NativeFunction(native) => Some((vm.deprecated_native(&native.name).unwrap_or(&native.name), native.arity)),
+        _ => None,
+    })
+}
+
+/// The native a script calls as `name`, e.g. `getenv` or `term.styled`.
+fn native_named<'a>(vm: &'a VM, name: &str) -> Option<&'a NativeFunction> {
+    let value = match name.split_once('.') {
+        Some((module, member)) if vm.is_native_module(module) => vm.modules.get(module)?.get(member),
+        _ => vm.globals.get(name),
+    };
+    match value {
+        Some(Value::NativeFunction(native)) => Some(native),
+        _ => None,
+    }
+}
+
+pub fn markdown(vm: &mut VM) -> String {
+    vm.load_lazy_modules();
+    let mut natives: Vec<(&str, Arity)> = natives(vm).collect();
+    natives.sort_by_key(|(name, _)| *name);
+    natives.dedup_by_key(|(name, _)| *name);
+    let mut text = String::from("# Native functions\n");
+    for (name, arity) in natives {
+        let doc = vm.native_doc(name);
+        text += &format!("\n## {}\n\n`{}`\n\n", name, signature(name, doc.as_ref(), Some(arity)));
+        match doc {
+            Some(doc) => {
+                text += &doc.summary;
+                text += "\n";
+                if !doc.params.is_empty() {
+                    text += "\n";
+                }
+                for (param, description) in &doc.params {
+                    text += &format!("- `{}`: {}\n", param, description);
+                }
+            }
+            None => text += "No documentation.\n",
+        }
+    }
+    text
+}
+
+pub fn register_natives(vm: &mut VM) {
+    // help(getenv) or help("getenv")
+    vm.register_native("help", 1, |vm, args| {
+        if let Value::String(name) = &args[0] {
+            vm.load_lazy_module_of(name);
+        }
+        let (name, arity) = match &args[0] {
+            Value::NativeFunction(native) => (native.name.clone(), Some(native.arity)),
+            Value::String(name) => match native_named(vm, name) {
+                Some(native) => (name.clone(), Some(native.arity)),
+                _ if vm.native_doc(name).is_some() => (name.clone(), None),
+                _ => return Err(format!("help: there is no native named '{}'", name)),
+            },
+            other => return Err(format!("help expects a native function or its name, got {}", VM::format_value(other))),
+        };
+        Ok(Value::String(help_text(&name, vm.native_doc(&name).as_ref(), arity)))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    #[test]
+    fn test_every_builtin_native_is_documented() {
+        let mut vm = VM::new();
+        vm.load_lazy_modules();
+        for (name, arity) in natives(&vm) {
+            let doc = builtin(name).unwrap_or_else(|| panic!("{} has no documentation", name));
+            // Optional parameters are documented too
+            assert!(doc.params.len() >= arity.min, "{} documents too few parameters", name);
+        }
+    }
+
+    #[test]
+    fn test_help() {
+        let mut grease = Grease::new();
+        grease.register_native("pad", 1..=2, |_vm, args| Ok(args[0].clone()));
+        grease.vm.document_native("pad", NativeDoc::new("Pad text.").param("text", "the string").param("width", "how wide"));
+        let source = "a = help(getenv)\nb = help(\"pad\")\nc = help(environ)";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        let text = |grease: &Grease, name| VM::format_value(grease.vm.globals.get(name).unwrap());
+        assert_eq!(text(&grease, "a"), "getenv(name)\n  The environment variable as the script sees it, or null if it isn't set.\n  name: the variable's name");
+        assert_eq!(text(&grease, "b"), "pad(text, width)\n  Pad text.\n  text: the string\n  width: how wide");
+        assert_eq!(text(&grease, "c"), "environ()\n  Every environment variable as the script sees them.");
+
+        grease.register_native("bare", 1.., |_vm, _args| Ok(Value::Null));
+        assert_eq!(grease.run("d = help(bare)").unwrap(), InterpretResult::Ok);
+        assert_eq!(text(&grease, "d"), "bare(...) takes at least 1 argument\n  No documentation.");
+        let result = grease.run("help(\"nothing\")").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("no native named 'nothing'")));
+
+        let markdown = markdown(&mut grease.vm);
+        assert!(markdown.contains("## pad\n\n`pad(text, width)`\n\nPad text.\n\n- `text`: the string\n"));
+    }
+}
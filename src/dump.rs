@@ -0,0 +1,90 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! `grease tokens` and `grease parse --json`: the lexer's tokens and the
+//! parser's AST as JSON, for formatters, doc generators and other tools that
+//! don't link the crate.
+//!
+//! Tokens carry the `line` and `column` of their first character and the
+//! `end_line` and `end_column` just past their last. AST nodes are `{"kind", "data"}` objects; nodes that
+//! contain tokens also get a `span` covering them, e.g.
+//! `{"start": {"line": 1, "column": 1}, "end": {"line": 1, "column": 6}}`.
+
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::Token;
+use serde_json::{json, Value as Json};
+
+pub fn tokens(source: &str) -> Result<Vec<Token>, String> {
+    Lexer::new(source.to_string()).tokenize()
+}
+
+pub fn tokens_json(source: &str) -> Result<Json, String> {
+    serde_json::to_value(tokens(source)?).map_err(|e| e.to_string())
+}
+
+pub fn ast_json(source: &str) -> Result<Json, String> {
+    let program = Parser::new(tokens(source)?).parse()?;
+    let mut ast = serde_json::to_value(&program).map_err(|e| e.to_string())?;
+    add_spans(&mut ast);
+    Ok(ast)
+}
+
+type Position = (u64, u64);
+
+/// Give every AST node under `node` the span of the tokens inside it, and
+/// return that span.
+fn add_spans(node: &mut Json) -> Option<(Position, Position)> {
+    match node {
+        Json::Object(fields) if fields.contains_key("lexeme") => {
+            let number = |key: &str| fields[key].as_u64().unwrap_or(0);
+            Some(((number("line"), number("column")), (number("end_line"), number("end_column"))))
+        }
+        Json::Object(fields) => {
+            let span = fields.values_mut().filter_map(add_spans).reduce(merge);
+            if let (Some((start, end)), true) = (span, fields.contains_key("data")) {
+                let position = |(line, column): Position| json!({ "line": line, "column": column });
+                fields.insert("span".to_string(), json!({ "start": position(start), "end": position(end) }));
+            }
+            span
+        }
+        Json::Array(items) => items.iter_mut().filter_map(add_spans).reduce(merge),
+        _ => None,
+    }
+}
+
+fn merge(a: (Position, Position), b: (Position, Position)) -> (Position, Position) {
+    (a.0.min(b.0), a.1.max(b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_json_has_positions() {
+        let tokens = tokens_json("name = \"hi\"\nprint(name)").unwrap();
+        assert_eq!(tokens[0], json!({ "kind": "Identifier", "lexeme": "name", "line": 1, "column": 1, "end_line": 1, "end_column": 5 }));
+        assert_eq!(tokens[1]["kind"], "Assign");
+        assert_eq!(tokens[2]["kind"], "String");
+        assert_eq!(tokens[2]["column"], 8);
+        assert_eq!(tokens[4]["line"], 2);
+        assert_eq!(tokens.as_array().unwrap().last().unwrap()["kind"], "EOF");
+    }
+
+    #[test]
+    fn test_ast_json_has_kinds_and_spans() {
+        let ast = ast_json("total = price * 2\nprint(total)").unwrap();
+        let declaration = &ast["statements"][0];
+        assert_eq!(declaration["kind"], "VariableDeclaration");
+        assert_eq!(declaration["data"]["name"]["lexeme"], "total");
+        let binary = &declaration["data"]["initializer"];
+        assert_eq!(binary["kind"], "Binary");
+        assert_eq!(binary["data"]["operator"]["lexeme"], "*");
+        // Literals carry no token, so the span ends at the operator
+        assert_eq!(binary["span"], json!({ "start": { "line": 1, "column": 9 }, "end": { "line": 1, "column": 16 } }));
+        assert_eq!(ast["statements"][1]["span"]["start"]["line"], 2);
+
+        assert!(ast_json("x = (").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Language editions.
+//!
+//! An [`Edition`] names a version of the language's rules, so syntax that
+//! would break existing scripts can be introduced behind one: a script opts
+//! in with a `# grease: version 0.2` pragma among its leading comments, or a
+//! whole project does with `[syntax] version = "0.2"` in `grease.toml`. The
+//! pragma wins. Scripts that ask for neither keep [`Edition::V0_1`] rules.
+//!
+//! Edition 0.2 adds the `fn` keyword as an alias of `def` and `&&`, `||`
+//! and `!` as aliases of `and`, `or` and `not`; `fn` is an ordinary name in
+//! 0.1.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edition {
+    #[default]
+    V0_1,
+    V0_2,
+}
+
+impl Edition {
+    pub const ALL: &'static [Edition] = &[Edition::V0_1, Edition::V0_2];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Edition::V0_1 => "0.1",
+            Edition::V0_2 => "0.2",
+        }
+    }
+
+    pub fn parse(version: &str) -> Result<Edition, String> {
+        Self::ALL.iter().copied().find(|edition| edition.as_str() == version).ok_or_else(|| {
+            let supported: Vec<&str> = Self::ALL.iter().map(|edition| edition.as_str()).collect();
+            format!("Unknown language version '{}' (supported: {})", version, supported.join(", "))
+        })
+    }
+
+    /// The edition a `# grease: version X` pragma in the comments and blank
+    /// lines at the top of `source` asks for.
+    pub fn pragma(source: &str) -> Result<Option<Edition>, String> {
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(comment) = line.strip_prefix('#') else { break };
+            let version = comment.trim().strip_prefix("grease:").and_then(|rest| rest.trim().strip_prefix("version"));
+            if let Some(version) = version {
+                return Self::parse(version.trim())
+                    .map(Some)
+                    .map_err(|e| format!("{} at line {}", e, index + 1));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl fmt::Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pragma_in_leading_comments() {
+        assert_eq!(Edition::pragma("#!/usr/bin/env grease\n\n# grease: version 0.2\nx = 1"), Ok(Some(Edition::V0_2)));
+        assert_eq!(Edition::pragma("#grease:version 0.1"), Ok(Some(Edition::V0_1)));
+        // Only the header counts
+        assert_eq!(Edition::pragma("x = 1\n# grease: version 0.2"), Ok(None));
+        assert_eq!(
+            Edition::pragma("# grease: version 9").unwrap_err(),
+            "Unknown language version '9' (supported: 0.1, 0.2) at line 1"
+        );
+        assert!(Edition::V0_2 > Edition::default());
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Environment variables as a script sees them: the host process's, with
+//! the changes the script made on top. `system.setenv` and `dotenv_load`
+//! only change the VM's overlay, so scripts can't affect the embedding
+//! program or other VMs (or tests running in parallel); `getenv` and
+//! `environ` read through it, and processes started for the script, such as
+//! the REPL's `:edit` editor, get it with [`Environment::apply`]. A host that wants scripts to change the real
+//! environment opts in with `Grease::with_env_write_through(true)`.
+//!
+//! The working directory is kept the same way: `chdir` only moves the VM's
+//! own, which file natives resolve relative paths against and processes
+//! started for the script run in. Until a script calls it, relative paths are taken from
+//! the script's directory, or the process's working directory.
+
+use crate::bytecode::Value;
+use crate::sandbox::{require, Capability};
+use crate::vm::VM;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    /// Variables scripts set, or unset with `None`.
+    overrides: HashMap<String, Option<String>>,
+    /// Also make changes to the process environment.
+    pub write_through: bool,
+    /// Working directory set with `chdir`.
+    current_dir: Option<PathBuf>,
+}
+
+impl Environment {
+    pub fn get(&self, name: &str) -> Option<String> {
+        match self.overrides.get(name) {
+            Some(value) => value.clone(),
+            None => std::env::var(name).ok(),
+        }
+    }
+
+    /// Set `name`, or unset it with `None`.
+    pub fn set(&mut self, name: &str, value: Option<&str>) {
+        if self.write_through {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+        self.overrides.insert(name.to_string(), value.map(str::to_string));
+    }
+
+    /// Every variable, sorted by name.
+    pub fn vars(&self) -> BTreeMap<String, String> {
+        let mut vars: BTreeMap<String, String> = std::env::vars().collect();
+        for (name, value) in &self.overrides {
+            match value {
+                Some(value) => vars.insert(name.clone(), value.clone()),
+                None => vars.remove(name),
+            };
+        }
+        vars
+    }
+
+    /// The working directory a script changed to, if it did.
+    pub fn current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    pub fn set_current_dir(&mut self, dir: PathBuf) {
+        self.current_dir = Some(dir);
+    }
+
+    /// Drop the changes scripts made, keeping `write_through`.
+    pub fn clear(&mut self) {
+        self.overrides.clear();
+        self.current_dir = None;
+    }
+
+    /// Give `command` the variables and working directory as scripts see
+    /// them.
+    pub fn apply(&self, command: &mut Command) {
+        for (name, value) in &self.overrides {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name),
+            };
+        }
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+    }
+}
+
+/// `path` made absolute with symlinks and `..` resolved. WASI has no way to
+/// resolve them, so there it's only checked to exist.
+pub(crate) fn canonical(path: &Path) -> std::io::Result<PathBuf> {
+    #[cfg(target_os = "wasi")]
+    return path.metadata().map(|_| path.to_path_buf());
+    #[cfg(not(target_os = "wasi"))]
+    path.canonicalize()
+}
+
+pub fn register_natives(vm: &mut VM) {
+    // null when the variable isn't set
+    vm.register_native("getenv", 1, |vm, args| {
+        require(vm, Capability::Environment, "getenv")?;
+        match &args[0] {
+            Value::String(name) => Ok(vm.environment.get(name).map_or(Value::Null, Value::String)),
+            _ => Err("TypeError: getenv expects a variable name string".to_string()),
+        }
+    });
+
+    vm.register_native("environ", 0, |vm, _args| {
+        require(vm, Capability::Environment, "environ")?;
+        let vars = vm.environment.vars().into_iter().map(|(name, value)| (name, Value::String(value)));
+        Ok(Value::dictionary(vars.collect()))
+    });
+
+    vm.register_native("getcwd", 0, |vm, _args| {
+        require(vm, Capability::Filesystem, "getcwd")?;
+        let dir = match vm.environment.current_dir().or(vm.script_dir.as_deref()) {
+            Some(dir) => dir.to_path_buf(),
+            None => std::env::current_dir().map_err(|e| format!("IOError: Failed to get the working directory: {}", e))?,
+        };
+        Ok(Value::String(dir.display().to_string()))
+    });
+
+    // Relative to the current directory, like `cd`
+    vm.register_native("chdir", 1, |vm, args| {
+        require(vm, Capability::Filesystem, "chdir")?;
+        let Value::String(path) = &args[0] else {
+            return Err("TypeError: chdir expects a path string".to_string());
+        };
+        let dir = canonical(&vm.resolve_path(path))
+            .map_err(|e| format!("IOError: Failed to change directory to '{}': {}", path, e))?;
+        if !dir.is_dir() {
+            return Err(format!("IOError: Failed to change directory to '{}': not a directory", path));
+        }
+        vm.environment.set_current_dir(dir);
+        Ok(Value::Null)
+    });
+}
+
+/// The `system` module's environment natives.
+pub fn register_system_natives(vm: &mut VM) {
+    // A null value unsets the variable
+    vm.register_module_native("system", "setenv", 2, |vm, args| {
+        require(vm, Capability::Environment, "system.setenv")?;
+        match (&args[0], &args[1]) {
+            (Value::String(name), _) if name.is_empty() || name.contains(['=', '\0']) => {
+                Err(format!("system.setenv: invalid variable name '{}'", name))
+            }
+            (Value::String(name), Value::String(value)) => {
+                vm.environment.set(name, Some(value));
+                Ok(Value::Null)
+            }
+            (Value::String(name), Value::Null) => {
+                vm.environment.set(name, None);
+                Ok(Value::Null)
+            }
+            _ => Err("TypeError: system.setenv expects (name string, value string or null)".to_string()),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    #[test]
+    fn test_changes_stay_in_the_vm() {
+        std::env::set_var("GREASE_ENV_TEST_HOST", "host");
+        let mut first = Grease::new();
+        let mut second = Grease::new();
+        let source = "use system\nsystem.setenv(\"GREASE_ENV_TEST_SET\", \"first\")\n\
+            system.setenv(\"GREASE_ENV_TEST_HOST\", null)\n\
+            seen = [getenv(\"GREASE_ENV_TEST_SET\"), getenv(\"GREASE_ENV_TEST_HOST\")]\n\
+            listed = dict_has(environ(), \"GREASE_ENV_TEST_SET\") and not dict_has(environ(), \"GREASE_ENV_TEST_HOST\")";
+        assert_eq!(first.run(source).unwrap(), InterpretResult::Ok);
+        assert_eq!(second.run("seen = [getenv(\"GREASE_ENV_TEST_SET\"), getenv(\"GREASE_ENV_TEST_HOST\")]").unwrap(), InterpretResult::Ok);
+
+        assert_eq!(VM::format_value(first.vm.globals.get("seen").unwrap()), "[first, null]");
+        assert!(matches!(first.vm.globals.get("listed"), Some(Value::Boolean(true))));
+        assert_eq!(VM::format_value(second.vm.globals.get("seen").unwrap()), "[null, host]");
+        assert!(std::env::var_os("GREASE_ENV_TEST_SET").is_none());
+        assert_eq!(std::env::var("GREASE_ENV_TEST_HOST").unwrap(), "host");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commands_see_the_overlay() {
+        let mut environment = Environment::default();
+        environment.set("GREASE_ENV_TEST_CHILD", Some("child"));
+        environment.set_current_dir(PathBuf::from("/"));
+        let mut command = Command::new("sh");
+        command.args(["-c", "printf %s \"$GREASE_ENV_TEST_CHILD $PWD\""]);
+        environment.apply(&mut command);
+        assert_eq!(command.output().unwrap().stdout, b"child /");
+    }
+
+    #[test]
+    fn test_chdir_stays_in_the_vm() {
+        let dir = std::env::temp_dir().join(format!("grease-chdir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub").join("data.bin"), b"hi").unwrap();
+        let process_dir = std::env::current_dir().unwrap();
+
+        let mut grease = Grease::new();
+        let source = format!("chdir(\"{}\")\nchdir(\"sub\")\ndata = read_file_bytes(\"data.bin\")\nhere = getcwd()", dir.display());
+        assert_eq!(grease.run(&source).unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("data").unwrap()), "b\"hi\"");
+        let here = VM::format_value(grease.vm.globals.get("here").unwrap());
+        assert_eq!(PathBuf::from(here), std::fs::canonicalize(dir.join("sub")).unwrap());
+        assert_eq!(std::env::current_dir().unwrap(), process_dir);
+
+        let mut other = Grease::new();
+        assert_eq!(other.run("here = getcwd()").unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(other.vm.globals.get("here").unwrap()), process_dir.display().to_string());
+        let result = other.run("chdir(\"no-such-directory-here\")").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("Failed to change directory")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_through_and_capability() {
+        let mut grease = Grease::new().with_env_write_through(true);
+        assert_eq!(grease.run("use system\nsystem.setenv(\"GREASE_ENV_TEST_THROUGH\", \"yes\")").unwrap(), InterpretResult::Ok);
+        assert_eq!(std::env::var("GREASE_ENV_TEST_THROUGH").unwrap(), "yes");
+
+        let mut grease = Grease::new().with_capabilities(crate::sandbox::Capabilities::none());
+        let result = grease.run("getenv(\"HOME\")").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("needs the environment capability")));
+    }
+}
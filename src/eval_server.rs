@@ -0,0 +1,228 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! `grease serve`: a warm interpreter that evaluates snippets sent over a
+//! Unix socket or TCP, for editors, notebooks and other tools.
+//!
+//! The protocol is newline-delimited JSON. Each request is one line:
+//!
+//! ```text
+//! {"id": 1, "code": "x = 20\nx + 1"}
+//! ```
+//!
+//! and gets one response line with the captured output, the value of a
+//! trailing expression, warnings and the error if the snippet failed:
+//!
+//! ```text
+//! {"error":null,"id":1,"ok":true,"output":"","value":"21","warnings":[]}
+//! ```
+//!
+//! `error` is `{"kind": "compile" | "runtime" | "protocol", "message": ...}`.
+//! Connections are served one at a time and share one interpreter, so
+//! globals and loaded modules persist between snippets and clients.
+
+use crate::bytecode::Value;
+use crate::grease::Grease;
+use crate::vm::{InterpretResult, VM};
+use serde_json::{json, Value as Json};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+/// The interpreter behind a server, with its script output captured so it
+/// can be returned with each response.
+pub struct Session {
+    grease: Grease,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+/// The result of one snippet run in a [`Session`].
+pub struct Evaluation {
+    /// Everything the snippet printed.
+    pub output: String,
+    /// The trailing expression's value, unless it was `null`.
+    pub value: Option<String>,
+    pub warnings: Vec<String>,
+    /// `("compile" | "runtime", message)` when the snippet failed.
+    pub error: Option<(&'static str, String)>,
+}
+
+struct CapturedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Session {
+    pub fn new(grease: Grease) -> Self {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let grease = grease.with_stdout(Box::new(CapturedOutput(output.clone())));
+        Session { grease, output }
+    }
+
+    /// Run `code` in the session's interpreter and collect what it printed.
+    pub fn evaluate(&mut self, code: &str) -> Evaluation {
+        let outcome = self.grease.evaluate(code);
+        let output = String::from_utf8_lossy(&std::mem::take(&mut *self.output.lock().unwrap())).into_owned();
+        match outcome {
+            Ok(outcome) => Evaluation {
+                output,
+                value: match &outcome.value {
+                    Value::Null => None,
+                    value => Some(VM::format_value(value)),
+                },
+                warnings: outcome.warnings,
+                error: match outcome.result {
+                    InterpretResult::Ok => None,
+                    InterpretResult::CompileError(msg) => Some(("compile", msg)),
+                    InterpretResult::RuntimeError(msg) => Some(("runtime", msg)),
+                },
+            },
+            // Lexer and parser errors
+            Err(msg) => Evaluation {
+                output,
+                value: None,
+                warnings: Vec::new(),
+                error: Some(("compile", msg)),
+            },
+        }
+    }
+
+    /// Answer one request line with one response line (without the newline).
+    pub fn handle(&mut self, line: &str) -> String {
+        let request: Json = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return error_response(Json::Null, "protocol", &format!("Invalid JSON request: {}", e)),
+        };
+        let id = request.get("id").cloned().unwrap_or(Json::Null);
+        let Some(code) = request.get("code").and_then(Json::as_str) else {
+            return error_response(id, "protocol", "Request needs a \"code\" string");
+        };
+
+        let evaluation = self.evaluate(code);
+        let error = match &evaluation.error {
+            Some((kind, message)) => json!({ "kind": kind, "message": message }),
+            None => Json::Null,
+        };
+        let response = json!({
+            "id": id,
+            "ok": error.is_null(),
+            "output": evaluation.output,
+            "value": evaluation.value,
+            "warnings": evaluation.warnings,
+            "error": error,
+        });
+        response.to_string()
+    }
+
+    /// Answer requests from `reader` on `writer` until the client hangs up.
+    pub fn serve_connection(&mut self, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            writeln!(writer, "{}", self.handle(&line))?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn error_response(id: Json, kind: &str, message: &str) -> String {
+    json!({
+        "id": id,
+        "ok": false,
+        "output": "",
+        "value": null,
+        "warnings": [],
+        "error": { "kind": kind, "message": message },
+    })
+    .to_string()
+}
+
+/// Serve `grease` on a TCP address such as `127.0.0.1:7878`.
+pub fn serve_tcp(grease: Grease, address: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let mut session = Session::new(grease);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let reader = BufReader::new(stream.try_clone()?);
+        // A client that disconnects mid-request shouldn't stop the server
+        if let Err(e) = session.serve_connection(reader, stream) {
+            eprintln!("Connection error: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Serve `grease` on a Unix socket at `path`, replacing a stale socket left
+/// by an earlier server.
+#[cfg(unix)]
+pub fn serve_unix(grease: Grease, path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let mut session = Session::new(grease);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let reader = BufReader::new(stream.try_clone()?);
+        if let Err(e) = session.serve_connection(reader, stream) {
+            eprintln!("Connection error: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_keeps_state_and_captures_output() {
+        let mut session = Session::new(Grease::new());
+        let requests = "{\"id\": 1, \"code\": \"x = 20\\nprint(\\\"hi\\\")\"}\n\n{\"id\": \"b\", \"code\": \"x + 1\"}\n";
+        let mut responses = Vec::new();
+        session.serve_connection(requests.as_bytes(), &mut responses).unwrap();
+
+        let responses: Vec<Json> = String::from_utf8(responses).unwrap().lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["ok"], true);
+        assert_eq!(responses[0]["output"], "hi\n");
+        assert_eq!(responses[0]["value"], Json::Null);
+        assert_eq!(responses[1]["id"], "b");
+        assert_eq!(responses[1]["value"], "21");
+        assert_eq!(responses[1]["output"], "");
+    }
+
+    #[test]
+    fn test_session_reports_errors() {
+        let mut session = Session::new(Grease::new());
+        let response: Json = serde_json::from_str(&session.handle("{\"code\": \"print(missing)\"}")).unwrap();
+        assert_eq!(response["ok"], false);
+        assert_eq!(response["error"]["kind"], "runtime");
+
+        let response: Json = serde_json::from_str(&session.handle("{\"code\": \"x = (\"}")).unwrap();
+        assert_eq!(response["error"]["kind"], "compile");
+
+        let response: Json = serde_json::from_str(&session.handle("not json")).unwrap();
+        assert_eq!(response["error"]["kind"], "protocol");
+        let response: Json = serde_json::from_str(&session.handle("{\"id\": 3}")).unwrap();
+        assert_eq!(response["id"], 3);
+        assert_eq!(response["error"]["kind"], "protocol");
+    }
+}
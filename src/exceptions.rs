@@ -0,0 +1,95 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::bytecode::Value;
+use crate::vm::VM;
+use std::collections::HashMap;
+
+/// Built-in exception classes and their superclasses. Every exception
+/// derives from `Error`, so `catch Error as e:` catches all of them.
+pub const BUILTIN_EXCEPTIONS: &[(&str, Option<&str>)] = &[
+    ("Error", None),
+    ("TypeError", Some("Error")),
+    ("ValueError", Some("Error")),
+    ("IndexError", Some("Error")),
+    ("KeyError", Some("Error")),
+    ("IOError", Some("Error")),
+    ("ImportError", Some("Error")),
+];
+
+pub fn register_natives(vm: &mut VM) {
+    for (name, superclass) in BUILTIN_EXCEPTIONS {
+        vm.globals.insert(name.to_string(), Value::Class {
+            name: name.to_string(),
+            methods: HashMap::new(),
+            superclass: superclass.map(str::to_string),
+            module: None,
+        });
+    }
+}
+
+/// Create an exception object of `class_name` carrying `message`.
+pub fn new_exception(class_name: &str, message: &str) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert("message".to_string(), Value::String(message.to_string()));
+    Value::object(class_name, fields)
+}
+
+/// Turn a runtime error message into the exception object a `catch` clause
+/// receives. Faults name their built-in class where they're raised, as a
+/// prefix such as `"TypeError: "`; anything else is a plain `Error`.
+pub fn from_runtime_error(message: &str) -> Value {
+    let tagged = BUILTIN_EXCEPTIONS.iter().find_map(|&(class_name, _)| {
+        let rest = message.strip_prefix(class_name)?.strip_prefix(": ")?;
+        Some((class_name, rest))
+    });
+    match tagged {
+        Some((class_name, message)) => new_exception(class_name, message),
+        None => new_exception("Error", message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class_of(value: &Value) -> &str {
+        match value {
+            Value::Object { class_name, .. } => class_name,
+            other => panic!("Expected exception object, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors_map_to_classes() {
+        assert_eq!(class_of(&from_runtime_error("KeyError: 'k' not found in dictionary")), "KeyError");
+        assert_eq!(class_of(&from_runtime_error("IndexError: Index 5 out of bounds for array of length 2")), "IndexError");
+        assert_eq!(class_of(&from_runtime_error("TypeError: Operands must be numbers")), "TypeError");
+        assert_eq!(class_of(&from_runtime_error("ImportError: Module 'ui' not found")), "ImportError");
+        assert_eq!(class_of(&from_runtime_error("Undefined variable 'x'")), "Error");
+        // The class comes from the tag alone, never from the wording
+        assert_eq!(class_of(&from_runtime_error("Failed: No such file or directory (os error 2)")), "Error");
+        assert_eq!(class_of(&from_runtime_error("print expects nothing in particular")), "Error");
+        assert_eq!(class_of(&from_runtime_error("TypeErrors: not a tag")), "Error");
+    }
+
+    #[test]
+    fn test_vm_faults_carry_their_class() {
+        let mut grease = crate::grease::Grease::new();
+        let source = "def kind(f):\n    try:\n        f()\n    catch TypeError as e:\n        return \"type\"\n    catch ValueError as e:\n        return \"value\"\n    catch IndexError as e:\n        return \"index\"\n    catch IOError as e:\n        return \"io\"\n    catch Error as e:\n        return \"error\"\n\
+            def add():\n    return 1 - \"a\"\ndef divide():\n    return 1 / 0\ndef index():\n    return [1][3]\ndef read():\n    return read_file_bytes(\"/nonexistent/grease\")\ndef undefined():\n    return missing\n\
+            kinds = [kind(add), kind(divide), kind(index), kind(read), kind(undefined)]";
+        assert_eq!(grease.run(source).unwrap(), crate::vm::InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("kinds").unwrap()), "[type, value, index, io, error]");
+    }
+
+    #[test]
+    fn test_message_drops_class_prefix() {
+        match from_runtime_error("KeyError: 'k' not found in dictionary") {
+            Value::Object { fields, .. } => {
+                assert!(matches!(fields.lock().unwrap().get("message"), Some(Value::String(m)) if m == "'k' not found in dictionary"));
+            }
+            other => panic!("Expected exception object, got {:?}", other),
+        }
+    }
+}
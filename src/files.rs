@@ -0,0 +1,261 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Natives for build scripts that redo work only when their inputs change:
+//! checksums of files and directory trees, and a loop that waits for files
+//! to change.
+//!
+//! ```text
+//! if file_hash("src/main.c") != previous:
+//!     build()
+//! def rebuild(changed):
+//!     print("changed: " + ", ".join(changed))
+//!     build()
+//! watch(["src", "Makefile"], rebuild)
+//! ```
+
+use crate::bytecode::Value;
+use crate::bytes::to_hex;
+use crate::sandbox::{require, Capability};
+use crate::vm::VM;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Milliseconds `watch` waits between looks when no interval is given.
+const DEFAULT_INTERVAL_MS: f64 = 500.0;
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("file_hash", 1..=2, file_hash);
+    vm.register_native("dir_hash", 1, dir_hash);
+    vm.register_native("watch", 2..=3, watch);
+}
+
+/// `file_hash(path[, algo])`: the hex digest of a file's contents, with
+/// `"sha256"` (the default), `"sha224"`, `"sha384"` or `"sha512"`.
+pub fn file_hash(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    require(vm, Capability::Filesystem, "file_hash")?;
+    let Value::String(path) = &args[0] else {
+        return Err("TypeError: file_hash expects a path string".to_string());
+    };
+    let algo = match args.get(1) {
+        None => "sha256",
+        Some(Value::String(algo)) => algo.as_str(),
+        Some(_) => return Err("TypeError: file_hash expects the algorithm as a string".to_string()),
+    };
+    let resolved = vm.resolve_path(path);
+    let digest = match algo {
+        "sha224" => digest_file::<Sha224>(&resolved),
+        "sha256" => digest_file::<Sha256>(&resolved),
+        "sha384" => digest_file::<Sha384>(&resolved),
+        "sha512" => digest_file::<Sha512>(&resolved),
+        _ => return Err(format!("file_hash: unknown algorithm '{}'; use sha224, sha256, sha384 or sha512", algo)),
+    };
+    digest.map(|digest| Value::String(to_hex(&digest))).map_err(|e| format!("IOError: Failed to read '{}': {}", path, e))
+}
+
+/// `dir_hash(path)`: the SHA-256 of every file under a directory, their
+/// paths relative to it and, for symlinks, their targets. Timestamps are
+/// left out, so the hash only changes when what's in the tree does.
+pub fn dir_hash(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    require(vm, Capability::Filesystem, "dir_hash")?;
+    let Value::String(path) = &args[0] else {
+        return Err("TypeError: dir_hash expects a path string".to_string());
+    };
+    let root = vm.resolve_path(path);
+    if !root.is_dir() {
+        return Err(format!("dir_hash: '{}' is not a directory", path));
+    }
+    let mut entries = Vec::new();
+    walk(&root, "", &mut entries).map_err(|e| format!("IOError: Failed to read '{}': {}", path, e))?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut hasher = Sha256::new();
+    for (relative, full) in entries {
+        let content = match fs::symlink_metadata(&full) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                fs::read_link(&full).map(|target| target.to_string_lossy().into_owned().into_bytes())
+            }
+            _ => digest_file::<Sha256>(&full),
+        }.map_err(|e| format!("IOError: Failed to read '{}': {}", full.display(), e))?;
+        // Lengths keep "ab" + "c" apart from "a" + "bc"
+        hasher.update((relative.len() as u64).to_le_bytes());
+        hasher.update(relative.as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    Ok(Value::String(to_hex(&hasher.finalize())))
+}
+
+/// `watch(paths, callback[, interval_ms])` looks at a path or array of
+/// paths every `interval_ms` (500 by default) and calls `callback` with
+/// the sorted paths of the files that were added, removed or modified
+/// since the last look. Directories are watched with everything in them.
+/// It returns once `callback` returns `false`.
+pub fn watch(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    require(vm, Capability::Filesystem, "watch")?;
+    let paths = match &args[0] {
+        Value::String(path) => vec![path.clone()],
+        Value::Array(items) => items.lock().unwrap().iter().map(|item| match item {
+            Value::String(path) => Ok(path.clone()),
+            _ => Err("TypeError: watch expects a path string or an array of them".to_string()),
+        }).collect::<Result<Vec<_>, _>>()?,
+        _ => return Err("TypeError: watch expects a path string or an array of them".to_string()),
+    };
+    let callback = match &args[1] {
+        callable @ (Value::Function(_) | Value::Closure(_) | Value::NativeFunction(_)) => callable.clone(),
+        _ => return Err("TypeError: watch expects a function to call with the changed paths".to_string()),
+    };
+    let interval = match args.get(2) {
+        None => DEFAULT_INTERVAL_MS,
+        Some(Value::Number(n)) if *n >= 0.0 && n.is_finite() => *n,
+        Some(_) => return Err("TypeError: watch expects a non-negative interval in milliseconds".to_string()),
+    };
+    let interval = Duration::try_from_secs_f64(interval / 1000.0)
+        .map_err(|_| "TypeError: watch expects an interval short enough to wait for".to_string())?;
+    let roots: Vec<(String, PathBuf)> = paths.into_iter().map(|path| {
+        let resolved = vm.resolve_path(&path);
+        (path, resolved)
+    }).collect();
+
+    let mut last = snapshot(&roots);
+    loop {
+        thread::sleep(interval);
+        let current = snapshot(&roots);
+        let changed: BTreeSet<&String> = last.keys().chain(current.keys())
+            .filter(|path| last.get(*path) != current.get(*path))
+            .collect();
+        if !changed.is_empty() {
+            let changed = changed.into_iter().map(|path| Value::String(path.clone())).collect();
+            if let Value::Boolean(false) = vm.call_function(callback.clone(), vec![Value::array(changed)])? {
+                return Ok(Value::Null);
+            }
+        }
+        last = current;
+    }
+}
+
+fn digest_file<D: Digest>(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = D::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(hasher.finalize().to_vec()),
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+}
+
+/// Every file and symlink under `dir`, with its path joined onto `prefix`.
+/// Symlinks aren't followed, so links back up the tree can't loop.
+fn walk(dir: &Path, prefix: &str, out: &mut Vec<(String, PathBuf)>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let relative = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+        if entry.file_type()?.is_dir() {
+            walk(&entry.path(), &relative, out)?;
+        } else {
+            out.push((relative, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+/// The size and modification time of every file under `roots`, keyed by
+/// its path as the script would write it. Paths that can't be read are
+/// left out, so they show up as changed once they can.
+fn snapshot(roots: &[(String, PathBuf)]) -> BTreeMap<String, (u64, Option<SystemTime>)> {
+    let mut files = BTreeMap::new();
+    for (path, resolved) in roots {
+        let mut entries = Vec::new();
+        if resolved.is_dir() {
+            // A directory that can't be read partway still reports what was
+            let _ = walk(resolved, path.trim_end_matches('/'), &mut entries);
+        } else {
+            entries.push((path.clone(), resolved.clone()));
+        }
+        for (display, full) in entries {
+            if let Ok(metadata) = fs::symlink_metadata(&full) {
+                files.insert(display, (metadata.len(), metadata.modified().ok()));
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bytecode::Value;
+    use crate::grease::Grease;
+    use crate::vm::{InterpretResult, VM};
+    use std::fs;
+
+    fn formatted(grease: &Grease, name: &str) -> String {
+        VM::format_value(grease.vm.globals.get(name).unwrap())
+    }
+
+    #[test]
+    fn test_file_and_dir_hashes() {
+        let dir = std::env::temp_dir().join(format!("grease-files-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "abc").unwrap();
+        fs::write(dir.join("sub/b.txt"), "").unwrap();
+
+        let mut grease = Grease::new();
+        grease.vm.globals.insert("dir".to_string(), Value::String(dir.display().to_string()));
+        let result = grease.run("file = dir + \"/a.txt\"\n\
+            sha256 = file_hash(file)\n\
+            sha512 = file_hash(file, \"sha512\")\n\
+            before = dir_hash(dir)\n\
+            write_file_bytes(dir + \"/sub/b.txt\", b\"x\")\n\
+            after = dir_hash(dir)\n\
+            changed = before != after\n\
+            same = after == dir_hash(dir)");
+        assert_eq!(result, Ok(InterpretResult::Ok));
+        assert_eq!(formatted(&grease, "sha256"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert!(formatted(&grease, "sha512").starts_with("ddaf35a193617aba"));
+        assert_eq!(formatted(&grease, "changed"), "true");
+        assert_eq!(formatted(&grease, "same"), "true");
+        assert_eq!(
+            grease.run("file_hash(file, \"md5\")"),
+            Ok(InterpretResult::RuntimeError("file_hash: unknown algorithm 'md5'; use sha224, sha256, sha384 or sha512".to_string()))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_reports_changed_files() {
+        let dir = std::env::temp_dir().join(format!("grease-watch-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("input.txt"), "one").unwrap();
+        let writer = {
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                fs::write(dir.join("input.txt"), "changed").unwrap();
+                fs::write(dir.join("new.txt"), "").unwrap();
+            })
+        };
+
+        let mut grease = Grease::new();
+        grease.vm.globals.insert("dir".to_string(), Value::String(dir.display().to_string()));
+        let result = grease.run("seen = []\n\
+            def on_change(paths):\n    for path in paths:\n        seen.push(path)\n    return seen.len() < 2\n\
+            watch(dir, on_change, 20)");
+        writer.join().unwrap();
+        assert_eq!(result, Ok(InterpretResult::Ok));
+        let dir = dir.display().to_string();
+        assert_eq!(formatted(&grease, "seen"), format!("[{}/input.txt, {}/new.txt]", dir, dir));
+        assert_eq!(
+            grease.run("watch(dir, on_change, 100000000000000000000000000)"),
+            Ok(InterpretResult::RuntimeError("TypeError: watch expects an interval short enough to wait for".to_string()))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
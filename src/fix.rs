@@ -0,0 +1,199 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Source edits that repair lint findings and simple syntax errors.
+//!
+//! Lint rules attach a [`Fix`] to findings they can repair without changing
+//! what the script does, and [`missing_colon`] repairs a block header whose
+//! `:` was left out. `grease lint --fix` applies them to files with
+//! [`apply`]; the language server offers the same fixes as quick fixes.
+
+use crate::token::{Span, Token, TokenType};
+
+/// Replace the source in `span` with `text`. An empty span inserts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub span: Span,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    /// What the fix does, e.g. for an editor's quick-fix menu.
+    pub title: String,
+    pub edits: Vec<Edit>,
+}
+
+impl Fix {
+    /// Where the first of the fix's edits starts.
+    pub fn start(&self) -> (usize, usize) {
+        self.edits.iter().map(|edit| (edit.span.line, edit.span.column)).min().unwrap_or((0, 0))
+    }
+}
+
+/// `source` with `edits` made. Edits may come in any order but must not
+/// overlap; a position past the end of a line or the source means its end.
+pub fn apply(source: &str, edits: &[Edit]) -> Result<String, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut line_starts = vec![0];
+    line_starts.extend(chars.iter().enumerate().filter(|(_, c)| **c == '\n').map(|(i, _)| i + 1));
+    let offset = |line: usize, column: usize| -> usize {
+        let Some(&start) = line_starts.get(line.saturating_sub(1)) else { return chars.len() };
+        let end = line_starts.get(line).map_or(chars.len(), |next| next - 1);
+        (start + column.saturating_sub(1)).min(end.max(start))
+    };
+
+    let mut ranges: Vec<(usize, usize, &str)> = edits.iter().map(|edit| {
+        let span = edit.span;
+        let start = offset(span.line, span.column);
+        // The start of a line past the last one is the end of the source
+        let end = if span.end_line > line_starts.len() { chars.len() } else { offset(span.end_line, span.end_column) };
+        (start, end.max(start), edit.text.as_str())
+    }).collect();
+    ranges.sort_by_key(|&(start, end, _)| (start, end));
+    if ranges.windows(2).any(|pair| pair[0].1 > pair[1].0) {
+        return Err("Fix edits overlap".to_string());
+    }
+
+    let mut result = String::with_capacity(source.len());
+    let mut position = 0;
+    for (start, end, text) in ranges {
+        result.extend(&chars[position..start]);
+        result.push_str(text);
+        position = end;
+    }
+    result.extend(&chars[position..]);
+    Ok(result)
+}
+
+/// A fix for a parse `error` at `at` when a block header's body starts on
+/// the next, indented line but the header lacks its `:`, as in
+/// `if x > 1` followed by an indented block. Nothing else is unambiguous
+/// enough to guess at.
+pub fn missing_colon(tokens: &[Token], error: &str, at: Span) -> Option<Fix> {
+    if !error.starts_with("Expected ':'") {
+        return None;
+    }
+    let index = tokens.iter().position(|token| token.span() == at && token.token_type == TokenType::Indent)?;
+    let header_end = tokens[..index].last()?;
+    let span = Span { line: header_end.end_line, column: header_end.end_column, end_line: header_end.end_line, end_column: header_end.end_column };
+    Some(Fix { title: "Add missing ':'".to_string(), edits: vec![Edit { span, text: ":".to_string() }] })
+}
+
+/// A unified diff from `old` to `new`, both called `path`, with three lines
+/// of context; empty when they're the same.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence table, filled from the end
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] { common[i + 1][j + 1] + 1 } else { common[i + 1][j].max(common[i][j + 1]) };
+        }
+    }
+
+    // (old line index, new line index, ' ' | '-' | '+')
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((i, j, ' '));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push((i, j, '-'));
+            i += 1;
+        } else {
+            lines.push((i, j, '+'));
+            j += 1;
+        }
+    }
+
+    const CONTEXT: usize = 3;
+    let mut output = String::new();
+    let mut index = 0;
+    while let Some(first_change) = lines[index..].iter().position(|line| line.2 != ' ').map(|offset| index + offset) {
+        // Extend the hunk while changes are close enough to share context
+        let start = first_change.saturating_sub(CONTEXT);
+        let mut end = first_change;
+        while let Some(next) = lines[end + 1..].iter().position(|line| line.2 != ' ').map(|offset| end + 1 + offset) {
+            if next - end > 2 * CONTEXT {
+                break;
+            }
+            end = next;
+        }
+        let end = (end + CONTEXT + 1).min(lines.len());
+
+        if output.is_empty() {
+            output.push_str(&format!("--- {}\n+++ {}\n", path, path));
+        }
+        let hunk = &lines[start..end];
+        let old_count = hunk.iter().filter(|line| line.2 != '+').count();
+        let new_count = hunk.iter().filter(|line| line.2 != '-').count();
+        // Empty ranges name the line before them, as diff does
+        let (old_start, new_start) = (hunk[0].0 + usize::from(old_count > 0), hunk[0].1 + usize::from(new_count > 0));
+        output.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_count, new_start, new_count));
+        for &(i, j, kind) in hunk {
+            let text = if kind == '+' { new[j] } else { old[i] };
+            output.push_str(&format!("{}{}\n", kind, text));
+        }
+        index = end;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn span(line: usize, column: usize, end_line: usize, end_column: usize) -> Span {
+        Span { line, column, end_line, end_column }
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let source = "a = 1\nb = 'two'\nc = 3";
+        let edits = [
+            Edit { span: span(2, 5, 2, 10), text: "\"two\"".to_string() },
+            Edit { span: span(1, 1, 2, 1), text: String::new() },
+            Edit { span: span(3, 6, 3, 6), text: "  # three".to_string() },
+        ];
+        assert_eq!(apply(source, &edits).unwrap(), "b = \"two\"\nc = 3  # three");
+        // Deleting the last line runs to the end of the source
+        assert_eq!(apply(source, &[Edit { span: span(3, 1, 4, 1), text: String::new() }]).unwrap(), "a = 1\nb = 'two'\n");
+        let overlapping = [Edit { span: span(1, 1, 1, 4), text: String::new() }, Edit { span: span(1, 3, 1, 5), text: String::new() }];
+        assert!(apply(source, &overlapping).is_err());
+    }
+
+    #[test]
+    fn test_missing_colon_fix() {
+        let source = "if x > 1  # big\n    y = 2";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens.clone());
+        let error = parser.parse().unwrap_err();
+        let fix = missing_colon(&tokens, &error, parser.span().unwrap()).unwrap();
+        assert_eq!(apply(source, &fix.edits).unwrap(), "if x > 1:  # big\n    y = 2");
+
+        // Without an indented body on the next line it could be anything
+        let source = "x = f(1 2)";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens.clone());
+        let error = parser.parse().unwrap_err();
+        assert!(missing_colon(&tokens, &error, parser.span().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        assert_eq!(unified_diff("a.grease", "x = 1\n", "x = 1\n"), "");
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        assert_eq!(
+            unified_diff("a.grease", old, new),
+            "--- a.grease\n+++ a.grease\n@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n@@ -8,4 +8,3 @@\n h\n i\n j\n-k\n"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pluggable front ends: an embedder can supply their own parser, e.g. for
+//! a JSON-defined DSL or a stricter syntax, that produces the crate's AST,
+//! and reuse the compiler and VM unchanged.
+//!
+//! ```text
+//! struct Calculator;
+//!
+//! impl Frontend for Calculator {
+//!     fn parse(&self, source: &str) -> Result<Program, String> {
+//!         let n: f64 = source.trim().parse().map_err(|e| format!("{}", e))?;
+//!         Ok(Program::new(vec![Statement::expression(Expression::number(n))]))
+//!     }
+//! }
+//!
+//! let mut grease = Grease::new().with_frontend(Box::new(Calculator));
+//! ```
+//!
+//! Nodes are built with the constructors on [`Expression`](crate::ast::Expression)
+//! and [`Statement`](crate::ast::Statement), and names and operators with
+//! [`Token::identifier`](crate::token::Token::identifier) and
+//! [`Token::operator`](crate::token::Token::operator). The positions given to
+//! tokens are the ones runtime errors and tracebacks report. Modules the
+//! program `use`s are still Grease source, and linting always parses Grease.
+
+use crate::ast::Program;
+use crate::edition::Edition;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// Turns source text into a [`Program`].
+pub trait Frontend: Send {
+    fn parse(&self, source: &str) -> Result<Program, String>;
+}
+
+impl<F: Fn(&str) -> Result<Program, String> + Send> Frontend for F {
+    fn parse(&self, source: &str) -> Result<Program, String> {
+        self(source)
+    }
+}
+
+/// The Grease syntax: the lexer and parser.
+pub struct GreaseSyntax {
+    pub tab_width: usize,
+    pub edition: Edition,
+}
+
+impl Frontend for GreaseSyntax {
+    fn parse(&self, source: &str) -> Result<Program, String> {
+        let tokens = Lexer::new(source.to_string()).with_tab_width(self.tab_width).with_edition(self.edition).tokenize()?;
+        Parser::new(tokens).parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Expression, Statement};
+    use crate::bytecode::Value;
+    use crate::token::{Token, TokenType};
+    use crate::{Grease, InterpretResult};
+
+    /// `name = a op b` lines, e.g. `total = 2 * 21`.
+    fn assignments(source: &str) -> Result<Program, String> {
+        let mut statements = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [name, "=", left, operator, right] = words[..] else {
+                return Err(format!("Expected 'name = a op b' at line {}", index + 1));
+            };
+            let operand = |word: &str| match word.parse() {
+                Ok(n) => Expression::number(n),
+                Err(_) => Expression::identifier(Token::identifier(word, index + 1, 1)),
+            };
+            let operator = match operator {
+                "+" => TokenType::Plus,
+                "*" => TokenType::Multiply,
+                "/" => TokenType::Divide,
+                _ => return Err(format!("Unknown operator '{}' at line {}", operator, index + 1)),
+            };
+            let value = Expression::binary(operand(left), Token::operator(operator, index + 1, 1), operand(right));
+            statements.push(Statement::variable(Token::identifier(name, index + 1, 1), value));
+        }
+        Ok(Program::new(statements))
+    }
+
+    #[test]
+    fn test_custom_frontend() {
+        let mut grease = Grease::new().with_frontend(Box::new(assignments));
+        assert_eq!(grease.run("x = 2 * 21\ny = x + 1"), Ok(InterpretResult::Ok));
+        assert!(matches!(grease.vm.globals.get("y"), Some(Value::Number(n)) if *n == 43.0));
+        assert!(grease.run("print(x)").unwrap_err().contains("Expected 'name = a op b'"));
+        assert!(grease.check("z = x / 2").is_ok());
+
+        // Errors point where the front end's tokens say
+        let outcome = grease.evaluate("z = 1 + 0\nw = z + missing").unwrap();
+        assert!(matches!(outcome.result, InterpretResult::RuntimeError(e) if e.contains("missing")));
+        assert_eq!(outcome.traceback[0].location.as_ref().map(|location| location.line), Some(2));
+    }
+}
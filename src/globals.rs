@@ -0,0 +1,171 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage for script globals.
+//!
+//! [`Globals`] reads like a `HashMap<String, Value>`, but every name it has
+//! seen keeps a fixed slot, even after removal. The VM caches the slot each
+//! `GET_GLOBAL`/`SET_GLOBAL` resolves to, so a global used in a loop is hashed
+//! once rather than on every access.
+
+use crate::bytecode::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct Globals {
+    slots: HashMap<String, usize>,
+    /// Name and value of each slot; removed globals leave `None` behind.
+    entries: Vec<(String, Option<Value>)>,
+    len: usize,
+}
+
+impl Globals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Globals {
+            slots: HashMap::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.slots.get(name).and_then(|&slot| self.entries[slot].1.as_ref())
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Value> {
+        let slot = *self.slots.get(name)?;
+        self.entries[slot].1.as_mut()
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Bind `name`, returning its previous value.
+    pub fn insert(&mut self, name: String, value: Value) -> Option<Value> {
+        let slot = match self.slots.get(&name) {
+            Some(&slot) => slot,
+            None => self.add_slot(name),
+        };
+        self.set_slot(slot, value)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.remove_entry(name).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry(&mut self, name: &str) -> Option<(String, Value)> {
+        let slot = *self.slots.get(name)?;
+        let value = self.entries[slot].1.take()?;
+        self.len -= 1;
+        Some((name.to_string(), value))
+    }
+
+    /// Bound globals in the order their names were first seen.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.entries.iter().filter_map(|(name, value)| value.as_ref().map(|value| (name, value)))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.iter().map(|(name, _)| name)
+    }
+
+    /// The slot of `name`, if it was ever bound.
+    pub(crate) fn slot_of(&self, name: &str) -> Option<usize> {
+        self.slots.get(name).copied()
+    }
+
+    /// The slot of `name`, allocating one if the name is new.
+    pub(crate) fn slot(&mut self, name: &str) -> usize {
+        match self.slots.get(name) {
+            Some(&slot) => slot,
+            None => self.add_slot(name.to_string()),
+        }
+    }
+
+    /// Whether `slot` holds the global called `name`, so a slot cached for
+    /// another `Globals` is never trusted.
+    pub(crate) fn slot_is(&self, slot: usize, name: &str) -> bool {
+        self.entries.get(slot).is_some_and(|(slot_name, _)| slot_name == name)
+    }
+
+    pub(crate) fn get_slot(&self, slot: usize) -> Option<&Value> {
+        self.entries[slot].1.as_ref()
+    }
+
+    pub(crate) fn set_slot(&mut self, slot: usize, value: Value) -> Option<Value> {
+        let previous = self.entries[slot].1.replace(value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    fn add_slot(&mut self, name: String) -> usize {
+        let slot = self.entries.len();
+        self.slots.insert(name.clone(), slot);
+        self.entries.push((name, None));
+        slot
+    }
+}
+
+impl Extend<(String, Value)> for Globals {
+    fn extend<I: IntoIterator<Item = (String, Value)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl FromIterator<(String, Value)> for Globals {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Self {
+        let mut globals = Globals::new();
+        globals.extend(iter);
+        globals
+    }
+}
+
+impl IntoIterator for Globals {
+    type Item = (String, Value);
+    type IntoIter = std::iter::FilterMap<std::vec::IntoIter<(String, Option<Value>)>, fn((String, Option<Value>)) -> Option<(String, Value)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter().filter_map(|(name, value)| value.map(|value| (name, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_survive_removal() {
+        let mut globals = Globals::new();
+        globals.insert("x".to_string(), Value::Number(1.0));
+        let slot = globals.slot("x");
+        assert!(matches!(globals.remove("x"), Some(Value::Number(n)) if n == 1.0));
+        assert!(!globals.contains_key("x"));
+        assert!(globals.is_empty());
+        assert!(globals.get_slot(slot).is_none());
+
+        globals.insert("y".to_string(), Value::Null);
+        globals.insert("x".to_string(), Value::Number(2.0));
+        assert_eq!(globals.slot_of("x"), Some(slot));
+        assert!(globals.slot_is(slot, "x") && !globals.slot_is(slot, "y"));
+        assert!(matches!(globals.get_slot(slot), Some(Value::Number(n)) if *n == 2.0));
+        assert_eq!(globals.keys().collect::<Vec<_>>(), ["x", "y"]);
+        assert_eq!(globals.len(), 2);
+    }
+}
@@ -4,14 +4,35 @@
 use crate::lexer::Lexer;
 use crate::parser::Parser;
 use crate::compiler::Compiler;
-use crate::vm::{VM, InterpretResult};
+use crate::bytecode::{Arity, Chunk, NativeFn, NativeFunction, Value};
+use crate::sandbox::{Capabilities, Limits};
+use crate::replay::NativeLog;
+use crate::mock::Mocks;
+use crate::docs::NativeDoc;
+use crate::vm::{VM, InterpretResult, RunOutcome};
 use crate::linter::{Linter, LintError};
+use crate::verifier;
+use crate::config::ProjectConfig;
+use crate::modules::{self, ModuleResolver, ModuleSource};
+use crate::frontend::{Frontend, GreaseSyntax};
+use crate::ast::Program;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 pub struct Grease {
     pub vm: VM,
     pub verbose: bool,
+    pub config: Option<ProjectConfig>,
+    /// Parses what `run` and `check` are given, when not Grease syntax.
+    pub frontend: Option<Box<dyn Frontend>>,
+}
+
+impl Default for Grease {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Grease {
@@ -19,6 +40,8 @@ impl Grease {
         Grease {
             vm: VM::new(),
             verbose: false,
+            config: None,
+            frontend: None,
         }
     }
 
@@ -27,196 +50,367 @@ impl Grease {
         self
     }
 
-    pub fn register_native(&mut self, name: &str, arity: usize, function: fn(&mut crate::vm::VM, Vec<crate::bytecode::Value>) -> Result<crate::bytecode::Value, String>) {
+    pub fn with_config(mut self, config: ProjectConfig) -> Self {
+        if let Some(width) = config.tab_width() {
+            self.vm.tab_width = width;
+        }
+        if let Some(edition) = config.edition() {
+            self.vm.edition = edition;
+        }
+        self.config = Some(config);
+        self
+    }
+
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.vm.script_args = args;
+        self
+    }
+
+    /// Fix `name` to `value` when scripts and modules compile, alongside
+    /// `platform`, `arch` and the feature flags. Branches of an `if` on it
+    /// that aren't taken aren't compiled.
+    pub fn with_define(mut self, name: &str, value: Value) -> Self {
+        self.vm.defines.insert(name.to_string(), value);
+        self
+    }
+
+    /// Resolve modules and relative paths as a script in `dir` would, for
+    /// source that doesn't come from [`Grease::run_file`], e.g. to lint it.
+    pub fn with_script_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.vm.script_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_module_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.vm.module_paths.push(dir.into());
+        self
+    }
+
+    pub fn with_module_paths(mut self, dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.vm.module_paths.extend(dirs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Make a module of Rust functions available to `use`, e.g.
+    /// `with_native_module("geo", &[("distance", 4, distance)])`.
+    pub fn with_native_module(mut self, name: &str, functions: &[(&str, usize, NativeFn)]) -> Self {
+        let members = functions.iter().map(|&(function_name, arity, function)| {
+            let native = Value::NativeFunction(NativeFunction {
+                name: function_name.to_string(),
+                arity: arity.into(),
+                function,
+            });
+            (function_name.to_string(), native)
+        });
+        self.vm.register_native_module(name, members.collect());
+        self
+    }
+
+    /// Send `print` and other script output to `output` instead of stdout.
+    pub fn with_stdout(mut self, output: Box<dyn std::io::Write + Send>) -> Self {
+        self.vm.set_output(output);
+        self
+    }
+
+    /// Read `input()` lines from `input` instead of stdin.
+    pub fn with_stdin(mut self, input: Box<dyn std::io::BufRead + Send>) -> Self {
+        self.vm.set_input(input);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.vm.limits = limits;
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.vm.capabilities = capabilities;
+        self
+    }
+
+    /// Run scripts reproducibly; see [`crate::sandbox`].
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.vm.deterministic = deterministic;
+        self
+    }
+
+    /// Record the native calls scripts make, or replay a recording; see
+    /// [`crate::replay`].
+    pub fn with_native_log(mut self, log: NativeLog) -> Self {
+        self.vm.native_log = Some(log);
+        self
+    }
+
+    /// Stand mocks in for natives, for testing scripts; see [`crate::mock`].
+    ///
+    /// # Panics
+    ///
+    /// If a mock names a native that isn't registered.
+    pub fn with_mocks(mut self, mocks: Mocks) -> Self {
+        if let Err(e) = mocks.install(&mut self.vm) {
+            panic!("{}", e);
+        }
+        self
+    }
+
+    /// Let `system.setenv` and `dotenv_load` change the process environment
+    /// too, instead of only what this runtime's scripts see.
+    pub fn with_env_write_through(mut self, write_through: bool) -> Self {
+        self.vm.environment.write_through = write_through;
+        self
+    }
+
+    /// Parse the source given to `run`, `evaluate` and `check` with
+    /// `frontend` instead of the Grease parser; see [`crate::frontend`].
+    pub fn with_frontend(mut self, frontend: Box<dyn Frontend>) -> Self {
+        self.frontend = Some(frontend);
+        self
+    }
+
+    pub fn register_native(&mut self, name: &str, arity: impl Into<Arity>, function: NativeFn) {
         self.vm.register_native(name, arity, function);
     }
 
+    /// Register native `name` as `module.name`, with a deprecated flat
+    /// alias `module_name`; see [`VM::register_module_native`].
+    pub fn register_module_native(&mut self, module: &str, name: &str, arity: impl Into<Arity>, function: NativeFn) {
+        self.vm.register_module_native(module, name, arity, function);
+    }
+
+    /// Document native `name` for `help`; see [`crate::docs`].
+    pub fn document_native(&mut self, name: &str, doc: NativeDoc) {
+        self.vm.document_native(name, doc);
+    }
+
+    /// Clear what earlier scripts left behind so the next run starts fresh,
+    /// keeping natives and configuration; see [`VM::reset`].
+    pub fn reset(&mut self) -> Result<(), String> {
+        self.vm.reset()
+    }
+
     pub fn run(&mut self, source: &str) -> Result<InterpretResult, String> {
+        self.evaluate(source).map(InterpretResult::from)
+    }
+
+    /// Like [`Grease::run`], but also report the script's final value, the
+    /// warnings it emitted and how long each phase took.
+    pub fn evaluate(&mut self, source: &str) -> Result<RunOutcome, String> {
+        self.evaluate_source(source, None)
+    }
+
+    /// Run `source`, naming `file` in its source maps.
+    fn evaluate_source(&mut self, source: &str, file: Option<&Path>) -> Result<RunOutcome, String> {
         if self.verbose {
             eprintln!("🔍 Lexical analysis...");
             eprintln!("Source input: '{}'", source);
         }
-        let mut lexer = Lexer::new(source.to_string());
-        let tokens = lexer.tokenize()?;
+        let started = Instant::now();
+        // A custom front end's lexing, if any, counts as parsing
+        let tokens = match self.frontend {
+            Some(_) => None,
+            None => Some(Lexer::new(source.to_string()).with_tab_width(self.vm.tab_width).with_edition(self.vm.edition).tokenize()?),
+        };
+        let lex_time = started.elapsed();
+        self.vm.perf.lex_time += lex_time;
 
         if self.verbose {
             eprintln!("📝 Parsing...");
         }
-        let mut parser = Parser::new(tokens);
-        let program = parser.parse()?;
-
-        // Handle uses before compilation
-        self.process_uses(&program)?;
+        let started = Instant::now();
+        let program = match tokens {
+            Some(tokens) => Parser::new(tokens).parse()?,
+            None => self.parse(source)?,
+        };
+        let parse_time = started.elapsed();
+        self.vm.perf.parse_time += parse_time;
 
         if self.verbose {
             eprintln!("⚙️  Compilation...");
         }
-        let mut compiler = Compiler::new();
+        let started = Instant::now();
+        let compiler = Compiler::new().with_defines(self.vm.defines.clone());
+        let mut compiler = match file {
+            Some(file) => compiler.with_file(file.display().to_string()),
+            None => compiler,
+        };
         let chunk = compiler.compile(&program)?.clone();
+        let compile_time = started.elapsed();
+        self.vm.perf.compile_time += compile_time;
+        Ok(self.interpret(chunk, lex_time, parse_time, compile_time))
+    }
 
+    /// Run a chunk compiled or verified by the caller, reporting the time
+    /// spent getting it.
+    fn interpret(&mut self, chunk: Chunk, lex_time: Duration, parse_time: Duration, compile_time: Duration) -> RunOutcome {
         if self.verbose {
             eprintln!("🚀 Interpretation...");
         }
-        let result = self.vm.interpret(chunk);
-        Ok(result)
+        let started = Instant::now();
+        let result = self.vm.interpret_verified(chunk);
+        RunOutcome {
+            result,
+            value: std::mem::replace(&mut self.vm.last_value, Value::Null),
+            warnings: std::mem::take(&mut self.vm.warnings),
+            traceback: std::mem::take(&mut self.vm.traceback),
+            lex_time,
+            parse_time,
+            compile_time,
+            run_time: started.elapsed(),
+        }
     }
 
-    pub fn lint(&mut self, source: &str) -> Result<Vec<LintError>, String> {
-        if self.verbose {
-            eprintln!("🔍 Lexical analysis...");
+    /// Run `source` with `bindings` as extra globals and return the globals
+    /// it assigned, e.g. to evaluate a configuration or template. The run is
+    /// isolated: afterwards the globals are as they were before, so neither
+    /// the bindings nor the script's assignments persist.
+    pub fn run_with_bindings(&mut self, source: &str, bindings: HashMap<String, Value>) -> Result<HashMap<String, Value>, String> {
+        let saved = self.vm.globals.clone();
+        self.vm.globals.extend(bindings);
+        self.vm.assigned_globals = Some(HashSet::new());
+        let result = self.run(source);
+        let assigned = self.vm.assigned_globals.take().unwrap_or_default();
+        let mut globals = std::mem::replace(&mut self.vm.globals, saved);
+
+        match result? {
+            InterpretResult::Ok => {}
+            InterpretResult::CompileError(msg) | InterpretResult::RuntimeError(msg) => return Err(msg),
         }
-        let mut lexer = Lexer::new(source.to_string());
-        let tokens = lexer.tokenize()?;
+        Ok(assigned.into_iter()
+            .filter_map(|name| globals.remove_entry(&name))
+            .collect())
+    }
 
-        if self.verbose {
-            eprintln!("📝 Parsing...");
-        }
-        let mut parser = Parser::new(tokens);
-        let program = parser.parse()?;
+    /// Run a script file. Modules it uses and relative paths given to file
+    /// natives are resolved against the script's directory.
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<InterpretResult, String> {
+        self.evaluate_file(path).map(InterpretResult::from)
+    }
 
-        if self.verbose {
-            eprintln!("🔎 Linting...");
+    /// [`Grease::run_file`] reporting the full [`RunOutcome`]. A file
+    /// written by [`Grease::compile_file`] runs without being compiled
+    /// again; loading it counts as compile time. If the `.grease` file next
+    /// to it has changed since, or this version can't load it, that source
+    /// is compiled and run instead.
+    pub fn evaluate_file(&mut self, path: impl AsRef<Path>) -> Result<RunOutcome, String> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        self.vm.script_dir = Some(path.parent().map(Path::to_path_buf).unwrap_or_default());
+        if Chunk::is_compiled(&bytes) {
+            let source_path = path.with_extension("grease");
+            if let Ok(source) = fs::read_to_string(&source_path) {
+                if !Chunk::is_current(&bytes, &source) {
+                    return self.evaluate_source(&source, Some(&source_path));
+                }
+            }
+            let started = Instant::now();
+            let chunk = Chunk::from_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+            verifier::verify(&chunk)?;
+            let compile_time = started.elapsed();
+            self.vm.perf.compile_time += compile_time;
+            return Ok(self.interpret(chunk, Duration::ZERO, Duration::ZERO, compile_time));
         }
-        let mut linter = Linter::new();
-        let errors = linter.lint(&program);
+        let source = String::from_utf8(bytes).map_err(|_| format!("Failed to read '{}': not UTF-8 text", path.display()))?;
+        self.evaluate_source(&source, Some(path))
+    }
 
-        Ok(errors)
+    /// Compile the script at `path` to bytecode, verified and in the form
+    /// [`Grease::run_file`] loads, e.g. to write a `.gbc` file that starts
+    /// without lexing, parsing or compiling. Modules it uses are still
+    /// loaded from source when it runs.
+    pub fn compile_file(&self, path: impl AsRef<Path>) -> Result<Vec<u8>, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        let program = self.parse(&source)?;
+        let mut compiler = Compiler::new().with_file(path.display().to_string()).with_defines(self.vm.defines.clone());
+        let chunk = compiler.compile(&program)?;
+        verifier::verify(chunk)?;
+        chunk.to_bytes(&source)
     }
 
-    fn process_uses(&mut self, program: &crate::ast::Program) -> Result<(), String> {
-        for statement in &program.statements {
-            if let crate::ast::Statement::Use { module, alias } = statement {
-                self.load_module(module, alias.as_ref())?;
-            }
-        }
-        Ok(())
-    }
-
-    fn load_module(&mut self, module_name: &str, alias: Option<&String>) -> Result<(), String> {
-        // Check for import cycles (simple check - prevent re-importing the same module)
-        let module_key = alias.unwrap_or(&module_name.to_string()).clone();
-        if self.vm.modules.contains_key(&module_key) {
-            return Err(format!("Circular import detected for module '{}'", module_name));
-        }
-
-        // Try to find the module file
-        let module_path = format!("{}.grease", module_name);
-        let mut paths_to_try = vec![
-            module_path.clone(),
-            format!("modules/{}", module_path),
-        ];
-
-        // Add standard library paths
-        if !module_name.contains('/') && !module_name.contains('\\') {
-            paths_to_try.push(format!("std/{}.grease", module_name));
-        }
-
-        // Handle relative imports
-        if module_name.starts_with('.') {
-            // For relative imports, we need to current file's directory
-            // For now, assume we're in the current working directory
-            // In a real implementation, we'd track the importing file's path
-            let relative_path = if module_name.starts_with("./") {
-                module_name[2..].to_string()
-            } else if module_name.starts_with("../") {
-                // Go up one directory
-                format!("../{}", &module_name[3..])
-            } else {
-                module_name[1..].to_string() // Remove leading .
-            };
-            paths_to_try.insert(0, format!("{}.grease", relative_path));
-        }
-
-        let mut source = None;
-        for path in &paths_to_try {
-            if Path::new(&path).exists() {
-                source = Some(fs::read_to_string(&path).map_err(|e| format!("Failed to read module {}: {}", path, e))?);
-                break;
-            }
-        }
+    /// The modules scripts can `use`, and where each would load from.
+    pub fn available_modules(&self) -> Vec<(String, ModuleSource)> {
+        modules::available(&self.vm)
+    }
 
-        let source = source.ok_or_else(|| format!("Module '{}' not found. Searched in current directory, modules/, and std/", module_name))?;
-        if self.verbose {
-            eprintln!("📦 Loading module '{}' from source:\n{}", module_name, source);
-        }
+    /// Compile `source` without running it and verify the stack discipline
+    /// of the bytecode, as release builds otherwise skip that check.
+    pub fn check(&self, source: &str) -> Result<(), String> {
+        let program = self.parse(source)?;
+        let mut compiler = Compiler::new().with_defines(self.vm.defines.clone());
+        verifier::verify(compiler.compile(&program)?)
+    }
 
-        // Parse and execute the module
-        let mut lexer = Lexer::new(source);
-        let tokens = lexer.tokenize()?;
-        let mut parser = Parser::new(tokens);
-        let module_program = parser.parse()?;
-
-        // Compile the module
-        let mut compiler = Compiler::new();
-        let chunk = compiler.compile(&module_program)?.clone();
-
-        // Execute module
-        let mut module_vm = VM::new();
-        let result = module_vm.interpret(chunk);
-        if let InterpretResult::RuntimeError(e) = result {
-            return Err(format!("Error executing module {}: {}", module_name, e));
-        }
-
-        // Handle relative imports
-        if module_name.starts_with('.') {
-            // For relative imports, we need the current file's directory
-            // For now, assume we're in the current working directory
-            // In a real implementation, we'd track the importing file's path
-            let relative_path = if module_name.starts_with("./") {
-                module_name[2..].to_string()
-            } else if module_name.starts_with("../") {
-                // Go up one directory
-                format!("../{}", &module_name[3..])
-            } else {
-                module_name[1..].to_string() // Remove leading .
-            };
-            paths_to_try.insert(0, format!("{}.grease", relative_path));
-        }
-
-        let mut source = None;
-        for path in paths_to_try {
-            eprintln!("Trying path: {}", path);
-            if Path::new(&path).exists() {
-                eprintln!("Found file at: {}", path);
-                source = Some(fs::read_to_string(&path).map_err(|e| format!("Failed to read module {}: {}", path, e))?);
-                break;
-            }
+    /// Parse `source` with the front end, without compiling it.
+    pub fn parse(&self, source: &str) -> Result<Program, String> {
+        match &self.frontend {
+            Some(frontend) => frontend.parse(source),
+            None => GreaseSyntax { tab_width: self.vm.tab_width, edition: self.vm.edition }.parse(source),
         }
+    }
 
-        let source = source.ok_or_else(|| format!("Module '{}' not found. Searched in current directory, modules/, and std/", module_name))?;
+    pub fn lint(&mut self, source: &str) -> Result<Vec<LintError>, String> {
         if self.verbose {
-            eprintln!("📦 Loading module '{}' from source:\n{}", module_name, source);
+            eprintln!("🔍 Lexical analysis...");
         }
-
-        // Parse and execute the module
-        let mut lexer = Lexer::new(source);
+        let mut lexer = Lexer::new(source.to_string()).with_tab_width(self.vm.tab_width).with_edition(self.vm.edition);
         let tokens = lexer.tokenize()?;
-        let mut parser = Parser::new(tokens);
-        let module_program = parser.parse()?;
-        eprintln!("Module '{}' parsed with {} statements", module_name, module_program.statements.len());
 
-        // Compile the module
-        let mut compiler = Compiler::new();
-        let chunk = compiler.compile(&module_program)?.clone();
-
-        // Execute the module in a new VM instance to capture its globals
-        let mut module_vm = VM::new();
-        let result = module_vm.interpret(chunk);
-        if let InterpretResult::RuntimeError(e) = result {
-            return Err(format!("Error executing module {}: {}", module_name, e));
+        if self.verbose {
+            eprintln!("📝 Parsing...");
         }
+        let mut parser = Parser::new(tokens.clone());
+        let program = parser.parse()?;
 
         if self.verbose {
-            eprintln!("📦 Module '{}' loaded with {} symbols", module_name, module_vm.globals.len());
-            for (name, value) in &module_vm.globals {
-                eprintln!("  {} = {:?}", name, value);
-            }
+            eprintln!("🔎 Linting...");
         }
+        Ok(self.linter().lint_source(source, &tokens, &program))
+    }
 
-        // Make the module's globals available
-        let module_key = alias.unwrap_or(&module_name.to_string()).clone();
-        self.vm.modules.insert(module_key, module_vm.globals.clone());
+    /// `source` with every safe fix applied: missing block colons first,
+    /// then the fixes lint findings carry. A lint fix that would stop the
+    /// source parsing is left out. Fails if the source still doesn't parse.
+    pub fn fix(&mut self, source: &str) -> Result<String, String> {
+        let tokenize = |source: &str| {
+            Lexer::new(source.to_string()).with_tab_width(self.vm.tab_width).with_edition(self.vm.edition).tokenize()
+        };
+        let parses = |source: &str| tokenize(source).and_then(|tokens| Parser::new(tokens).parse()).is_ok();
+
+        // Each pass repairs the error the parser stops at
+        let mut source = source.to_string();
+        let (tokens, program) = loop {
+            let tokens = tokenize(&source)?;
+            let mut parser = Parser::new(tokens.clone());
+            match parser.parse() {
+                Ok(program) => break (tokens, program),
+                Err(e) => {
+                    let fix = parser.span().and_then(|span| crate::fix::missing_colon(&tokens, &e, span)).ok_or(e)?;
+                    source = crate::fix::apply(&source, &fix.edits)?;
+                }
+            }
+        };
+
+        // Last first, so earlier fixes' positions still hold
+        let mut fixes: Vec<_> = self.linter().lint_source(&source, &tokens, &program)
+            .into_iter()
+            .filter_map(|error| error.fix)
+            .collect();
+        fixes.sort_by_key(|fix| std::cmp::Reverse(fix.start()));
+        for fix in fixes {
+            let fixed = crate::fix::apply(&source, &fix.edits)?;
+            if parses(&fixed) {
+                source = fixed;
+            }
+        }
+        Ok(source)
+    }
 
-        Ok(())
+    fn linter(&self) -> Linter {
+        let linter = Linter::new().with_resolver(ModuleResolver::for_vm(&self.vm));
+        match &self.config {
+            Some(config) => linter.with_config(config),
+            None => linter,
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,249 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! `grease highlight`: editor syntax definitions generated from the lexer's
+//! [`KEYWORDS`] and [`OPERATORS`] tables, so highlighting can't drift from
+//! what the lexer accepts. The copies under `editors/` are checked against
+//! this output by the tests.
+
+use crate::lexer::{KEYWORDS, OPERATORS};
+use crate::token::TokenType;
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// TextMate grammar (VS Code, Sublime Text, GitHub)
+    TmLanguage,
+    /// Vim syntax file
+    Vim,
+    /// Emacs major mode
+    Emacs,
+}
+
+pub fn generate(format: Format) -> String {
+    match format {
+        Format::TmLanguage => tm_language(),
+        Format::Vim => vim(),
+        Format::Emacs => emacs(),
+    }
+}
+
+/// Spellings of the keywords whose token satisfies `wanted`.
+fn keywords(wanted: fn(&TokenType) -> bool) -> Vec<&'static str> {
+    KEYWORDS.iter().filter(|(_, token)| wanted(token)).map(|(text, _)| *text).collect()
+}
+
+fn is_word_operator(token: &TokenType) -> bool {
+    matches!(token, TokenType::And | TokenType::Or | TokenType::Not)
+}
+
+fn is_constant(token: &TokenType) -> bool {
+    matches!(token, TokenType::True | TokenType::False | TokenType::Null)
+}
+
+fn is_self(token: &TokenType) -> bool {
+    matches!(token, TokenType::SelfKw | TokenType::Super)
+}
+
+fn is_statement_keyword(token: &TokenType) -> bool {
+    !is_word_operator(token) && !is_constant(token) && !is_self(token)
+}
+
+fn keyword_for(wanted: TokenType) -> &'static str {
+    KEYWORDS.iter().find(|(_, token)| *token == wanted).map(|(text, _)| *text).expect("keyword is in KEYWORDS")
+}
+
+/// Symbolic operators, excluding brackets and separators.
+fn operators() -> Vec<&'static str> {
+    OPERATORS.iter()
+        .filter(|(_, token)| !matches!(token,
+            TokenType::LeftParen | TokenType::RightParen | TokenType::LeftBrace | TokenType::RightBrace
+            | TokenType::LeftBracket | TokenType::RightBracket | TokenType::Comma | TokenType::Colon
+            | TokenType::Semicolon | TokenType::At))
+        .map(|(text, _)| *text)
+        .collect()
+}
+
+fn regex_escape(text: &str) -> String {
+    text.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c.to_string() } else { format!("\\{}", c) }).collect()
+}
+
+fn word_alternation(words: &[&str]) -> String {
+    format!("\\b({})\\b", words.join("|"))
+}
+
+fn tm_language() -> String {
+    let operators: Vec<String> = operators().iter().map(|op| regex_escape(op)).collect();
+    let grammar = json!({
+        "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+        "name": "Grease",
+        "scopeName": "source.grease",
+        "fileTypes": ["grease"],
+        "patterns": [
+            { "name": "comment.line.number-sign.grease", "match": "#.*$" },
+            { "name": "string.quoted.double.grease", "begin": "\"", "end": "\"" },
+            { "name": "string.quoted.single.grease", "begin": "'", "end": "'" },
+            { "name": "constant.numeric.grease", "match": "\\b\\d+(\\.\\d+)?\\b" },
+            {
+                "match": format!("\\b({})\\s+([A-Za-z_][A-Za-z0-9_]*)", keyword_for(TokenType::Fn)),
+                "captures": { "1": { "name": "storage.type.function.grease" }, "2": { "name": "entity.name.function.grease" } },
+            },
+            {
+                "match": format!("\\b({})\\s+([A-Za-z_][A-Za-z0-9_]*)", keyword_for(TokenType::Class)),
+                "captures": { "1": { "name": "storage.type.class.grease" }, "2": { "name": "entity.name.type.class.grease" } },
+            },
+            { "name": "entity.name.function.decorator.grease", "match": "@[A-Za-z_][A-Za-z0-9_]*" },
+            { "name": "keyword.control.grease", "match": word_alternation(&keywords(is_statement_keyword)) },
+            { "name": "keyword.operator.logical.grease", "match": word_alternation(&keywords(is_word_operator)) },
+            { "name": "constant.language.grease", "match": word_alternation(&keywords(is_constant)) },
+            { "name": "variable.language.self.grease", "match": word_alternation(&keywords(is_self)) },
+            { "name": "keyword.operator.grease", "match": operators.join("|") },
+        ],
+    });
+    serde_json::to_string_pretty(&grammar).expect("grammar is valid JSON") + "\n"
+}
+
+fn vim() -> String {
+    // Very nomagic (`\V`): only `\|` is special in the operator pattern
+    let operators = operators().join("\\|");
+    format!(
+        r##"" Vim syntax file
+" Language: Grease
+" Generated by `grease highlight --format vim`; do not edit.
+" Install as ~/.vim/syntax/grease.vim and add:
+"   au BufRead,BufNewFile *.grease set filetype=grease
+
+if exists("b:current_syntax")
+  finish
+endif
+
+syn keyword greaseKeyword {keywords}
+syn keyword greaseOperatorWord {word_operators}
+syn keyword greaseConstant {constants}
+syn keyword greaseSelf {selves}
+syn match greaseFunction "\%(\<{def}\s\+\)\@<=\h\w*"
+syn match greaseType "\%(\<{class}\s\+\)\@<=\h\w*"
+syn match greaseDecorator "@\h\w*"
+syn match greaseNumber "\<\d\+\%(\.\d\+\)\=\>"
+syn match greaseOperator "\V{operators}"
+syn region greaseString start=+"+ end=+"+ oneline
+syn region greaseString start=+'+ end=+'+ oneline
+syn match greaseComment "#.*$"
+
+hi def link greaseKeyword Keyword
+hi def link greaseOperatorWord Operator
+hi def link greaseConstant Constant
+hi def link greaseSelf Identifier
+hi def link greaseFunction Function
+hi def link greaseType Type
+hi def link greaseDecorator PreProc
+hi def link greaseNumber Number
+hi def link greaseOperator Operator
+hi def link greaseString String
+hi def link greaseComment Comment
+
+let b:current_syntax = "grease"
+"##,
+        keywords = keywords(is_statement_keyword).join(" "),
+        word_operators = keywords(is_word_operator).join(" "),
+        constants = keywords(is_constant).join(" "),
+        selves = keywords(is_self).join(" "),
+        def = keyword_for(TokenType::Fn),
+        class = keyword_for(TokenType::Class),
+        operators = operators,
+    )
+}
+
+fn elisp_strings(words: &[&str]) -> String {
+    words.iter()
+        .map(|word| format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn emacs() -> String {
+    format!(
+        r##";;; grease-mode.el --- Major mode for Grease  -*- lexical-binding: t; -*-
+
+;; Generated by `grease highlight --format emacs'; do not edit.
+
+;;; Code:
+
+(defvar grease-mode-syntax-table
+  (let ((table (make-syntax-table)))
+    (modify-syntax-entry ?# "<" table)
+    (modify-syntax-entry ?\n ">" table)
+    (modify-syntax-entry ?' "\"" table)
+    (modify-syntax-entry ?_ "_" table)
+    table)
+  "Syntax table for `grease-mode'.")
+
+(defconst grease-font-lock-keywords
+  `((,(regexp-opt '({keywords}) 'symbols) . font-lock-keyword-face)
+    (,(regexp-opt '({word_operators}) 'symbols) . font-lock-keyword-face)
+    (,(regexp-opt '({constants}) 'symbols) . font-lock-constant-face)
+    (,(regexp-opt '({selves}) 'symbols) . font-lock-builtin-face)
+    ("\\_<{def}\\s-+\\(\\(?:\\sw\\|\\s_\\)+\\)" 1 font-lock-function-name-face)
+    ("\\_<{class}\\s-+\\(\\(?:\\sw\\|\\s_\\)+\\)" 1 font-lock-type-face)
+    ("@\\(?:\\sw\\|\\s_\\)+" . font-lock-preprocessor-face)
+    (,(regexp-opt '({operators})) . 'font-lock-operator-face))
+  "Highlighting for `grease-mode'.")
+
+;;;###autoload
+(define-derived-mode grease-mode prog-mode "Grease"
+  "Major mode for editing Grease scripts."
+  (setq-local comment-start "# ")
+  (setq-local comment-start-skip "#+\\s-*")
+  (setq-local indent-tabs-mode nil)
+  (setq-local tab-width 4)
+  (setq-local font-lock-defaults '(grease-font-lock-keywords)))
+
+;;;###autoload
+(add-to-list 'auto-mode-alist '("\\.grease\\'" . grease-mode))
+
+(provide 'grease-mode)
+
+;;; grease-mode.el ends here
+"##,
+        keywords = elisp_strings(&keywords(is_statement_keyword)),
+        word_operators = elisp_strings(&keywords(is_word_operator)),
+        constants = elisp_strings(&keywords(is_constant)),
+        selves = elisp_strings(&keywords(is_self)),
+        def = keyword_for(TokenType::Fn),
+        class = keyword_for(TokenType::Class),
+        operators = elisp_strings(&operators()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_definitions_are_up_to_date() {
+        let committed = [
+            (Format::TmLanguage, "tmLanguage", "editors/vscode/syntaxes/grease.tmLanguage.json", include_str!("../editors/vscode/syntaxes/grease.tmLanguage.json")),
+            (Format::Vim, "vim", "editors/vim/syntax/grease.vim", include_str!("../editors/vim/syntax/grease.vim")),
+            (Format::Emacs, "emacs", "editors/emacs/grease-mode.el", include_str!("../editors/emacs/grease-mode.el")),
+        ];
+        for (format, name, path, contents) in committed {
+            assert!(
+                generate(format) == contents,
+                "{} is stale; regenerate it with `cargo run -- highlight --format {} > {}`", path, name, path
+            );
+        }
+    }
+
+    #[test]
+    fn test_definitions_cover_every_keyword() {
+        let grammar = generate(Format::TmLanguage);
+        let vim = generate(Format::Vim);
+        let emacs = generate(Format::Emacs);
+        for (keyword, _) in KEYWORDS {
+            assert!(grammar.contains(keyword), "tmLanguage misses '{}'", keyword);
+            assert!(vim.contains(keyword), "vim misses '{}'", keyword);
+            assert!(emacs.contains(&format!("\"{}\"", keyword)), "emacs misses '{}'", keyword);
+        }
+        assert!(grammar.contains("\\\\=\\\\=|\\\\!\\\\="));
+    }
+}
@@ -0,0 +1,389 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! `grease jupyter`: a Jupyter kernel, so notebooks can run Grease cells.
+//!
+//! Jupyter starts the kernel with a connection file naming five ZeroMQ
+//! ports and an HMAC key. Cells run in one [`Session`], the same warm
+//! interpreter `grease serve` uses, so globals persist between cells. What a
+//! cell prints is published as a `stream` message and its trailing
+//! expression as an `execute_result`.
+
+use crate::eval_server::Session;
+use crate::grease::Grease;
+use serde_json::{json, Value as Json};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeromq::{PubSocket, RepSocket, RouterSocket, Socket, SocketRecv, SocketSend, ZmqMessage};
+
+const PROTOCOL_VERSION: &str = "5.3";
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+/// Write a kernelspec so Jupyter lists Grease as a kernel, returning the
+/// directory it was written to.
+pub fn install_kernelspec(executable: &Path) -> io::Result<PathBuf> {
+    let dir = jupyter_data_dir()?.join("kernels").join("grease");
+    std::fs::create_dir_all(&dir)?;
+    let spec = json!({
+        "argv": [executable.to_string_lossy(), "jupyter", "{connection_file}"],
+        "display_name": "Grease",
+        "language": "grease",
+    });
+    std::fs::write(dir.join("kernel.json"), serde_json::to_string_pretty(&spec)?)?;
+    Ok(dir)
+}
+
+fn jupyter_data_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("JUPYTER_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .ok_or_else(|| io::Error::other("Cannot find the home directory; set JUPYTER_DATA_DIR"))?;
+    let home = PathBuf::from(home);
+    Ok(if cfg!(target_os = "macos") {
+        home.join("Library").join("Jupyter")
+    } else if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from).unwrap_or(home).join("jupyter")
+    } else {
+        home.join(".local").join("share").join("jupyter")
+    })
+}
+
+/// Run the kernel described by a Jupyter connection file until a client
+/// sends `shutdown_request`.
+pub fn run_kernel(grease: Grease, connection_file: &Path) -> io::Result<()> {
+    let text = std::fs::read_to_string(connection_file)?;
+    let connection: Json = serde_json::from_str(&text)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime
+        .block_on(serve(grease, &connection))
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+async fn serve(grease: Grease, connection: &Json) -> Result<(), String> {
+    let transport = connection["transport"].as_str().unwrap_or("tcp");
+    let ip = connection["ip"].as_str().unwrap_or("127.0.0.1");
+    let endpoint = |port: &str| match connection[port].as_u64() {
+        Some(port) => Ok(format!("{}://{}:{}", transport, ip, port)),
+        None => Err(format!("Connection file has no '{}'", port)),
+    };
+
+    let mut shell = RouterSocket::new();
+    shell.bind(&endpoint("shell_port")?).await.map_err(|e| e.to_string())?;
+    let mut control = RouterSocket::new();
+    control.bind(&endpoint("control_port")?).await.map_err(|e| e.to_string())?;
+    let mut iopub = PubSocket::new();
+    iopub.bind(&endpoint("iopub_port")?).await.map_err(|e| e.to_string())?;
+    // Cells can't prompt for input, but clients expect the port to be open
+    let mut stdin = RouterSocket::new();
+    stdin.bind(&endpoint("stdin_port")?).await.map_err(|e| e.to_string())?;
+    let mut heartbeat = RepSocket::new();
+    heartbeat.bind(&endpoint("hb_port")?).await.map_err(|e| e.to_string())?;
+    tokio::spawn(async move {
+        while let Ok(ping) = heartbeat.recv().await {
+            if heartbeat.send(ping).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let key = connection["key"].as_str().unwrap_or("").as_bytes().to_vec();
+    let mut kernel = Kernel::new(grease.with_stdin(Box::new(io::empty())), key);
+    loop {
+        let (frames, from_shell) = tokio::select! {
+            frames = shell.recv() => (frames, true),
+            frames = control.recv() => (frames, false),
+        };
+        let frames = frames.map_err(|e| e.to_string())?.into_vec();
+        let frames: Vec<Vec<u8>> = frames.into_iter().map(|frame| frame.to_vec()).collect();
+        let request = match kernel.parse(&frames) {
+            Ok(request) => request,
+            Err(e) => {
+                eprintln!("Ignoring message: {}", e);
+                continue;
+            }
+        };
+
+        let outgoing = kernel.handle(&request);
+        for (msg_type, content) in outgoing.iopub {
+            let topic = format!("kernel.{}.{}", kernel.session_id, msg_type).into_bytes();
+            iopub.send(to_message(kernel.frames(&[topic], &request, &msg_type, content))).await.map_err(|e| e.to_string())?;
+        }
+        if let Some((msg_type, content)) = outgoing.reply {
+            let reply = to_message(kernel.frames(&request.identities, &request, &msg_type, content));
+            let sent = if from_shell { shell.send(reply).await } else { control.send(reply).await };
+            sent.map_err(|e| e.to_string())?;
+        }
+        if outgoing.shutdown {
+            return Ok(());
+        }
+    }
+}
+
+fn to_message(frames: Vec<Vec<u8>>) -> ZmqMessage {
+    let mut message = ZmqMessage::from(frames[0].clone());
+    for frame in frames.into_iter().skip(1) {
+        message.push_back(frame.into());
+    }
+    message
+}
+
+/// A decoded Jupyter message.
+#[derive(Debug)]
+struct Message {
+    /// Routing frames before the delimiter, echoed back on replies.
+    identities: Vec<Vec<u8>>,
+    header: Json,
+    content: Json,
+}
+
+/// What handling one request produces: messages for the iopub socket, the
+/// reply on the socket the request came from, and whether to exit.
+#[derive(Default)]
+struct Outgoing {
+    iopub: Vec<(String, Json)>,
+    reply: Option<(String, Json)>,
+    shutdown: bool,
+}
+
+struct Kernel {
+    session: Session,
+    key: Vec<u8>,
+    session_id: String,
+    message_count: u64,
+    execution_count: u64,
+}
+
+impl Kernel {
+    fn new(grease: Grease, key: Vec<u8>) -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        Kernel {
+            session: Session::new(grease),
+            key,
+            session_id: format!("{:x}{:x}", nanos, std::process::id()),
+            message_count: 0,
+            execution_count: 0,
+        }
+    }
+
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        if self.key.is_empty() {
+            return String::new();
+        }
+        hex(&hmac_sha256(&self.key, &parts.concat()))
+    }
+
+    /// Split a multipart message and check its signature.
+    fn parse(&self, frames: &[Vec<u8>]) -> Result<Message, String> {
+        let delimiter = frames.iter().position(|frame| frame == DELIMITER)
+            .ok_or("missing <IDS|MSG> delimiter")?;
+        let parts = frames.get(delimiter + 1..delimiter + 6).ok_or("missing message parts")?;
+        let expected = self.sign(&[&parts[1], &parts[2], &parts[3], &parts[4]]);
+        if parts[0] != expected.as_bytes() {
+            return Err("invalid signature".to_string());
+        }
+        let decode = |part: &[u8]| serde_json::from_slice::<Json>(part).map_err(|e| e.to_string());
+        Ok(Message {
+            identities: frames[..delimiter].to_vec(),
+            header: decode(&parts[1])?,
+            content: decode(&parts[4])?,
+        })
+    }
+
+    /// Encode a message replying to `parent`, after the routing `prefix`.
+    fn frames(&mut self, prefix: &[Vec<u8>], parent: &Message, msg_type: &str, content: Json) -> Vec<Vec<u8>> {
+        self.message_count += 1;
+        let header = json!({
+            "msg_id": format!("{}-{}", self.session_id, self.message_count),
+            "session": self.session_id,
+            "username": "kernel",
+            "date": utc_timestamp(),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION,
+        });
+        let parts = [header.to_string(), parent.header.to_string(), "{}".to_string(), content.to_string()];
+        let signature = self.sign(&[parts[0].as_bytes(), parts[1].as_bytes(), parts[2].as_bytes(), parts[3].as_bytes()]);
+
+        let mut frames = prefix.to_vec();
+        frames.push(DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.extend(parts.map(String::into_bytes));
+        frames
+    }
+
+    fn handle(&mut self, request: &Message) -> Outgoing {
+        let msg_type = request.header["msg_type"].as_str().unwrap_or("");
+        let mut outgoing = Outgoing::default();
+        outgoing.iopub.push(("status".to_string(), json!({ "execution_state": "busy" })));
+        let reply = match msg_type {
+            "kernel_info_request" => Some(json!({
+                "status": "ok",
+                "protocol_version": PROTOCOL_VERSION,
+                "implementation": "grease",
+                "implementation_version": crate::VERSION,
+                "language_info": {
+                    "name": "grease",
+                    "version": crate::VERSION,
+                    "mimetype": "text/x-grease",
+                    "file_extension": ".grease",
+                },
+                "banner": format!("Grease {}", crate::VERSION),
+            })),
+            "execute_request" => Some(self.execute(&request.content, &mut outgoing.iopub)),
+            "is_complete_request" => Some(json!({ "status": "unknown" })),
+            "comm_info_request" => Some(json!({ "status": "ok", "comms": {} })),
+            // Cells run to completion; there is nothing to interrupt
+            "interrupt_request" => Some(json!({ "status": "ok" })),
+            "shutdown_request" => {
+                outgoing.shutdown = true;
+                Some(json!({ "status": "ok", "restart": request.content["restart"].as_bool().unwrap_or(false) }))
+            }
+            _ => None,
+        };
+        outgoing.reply = reply.map(|content| (msg_type.replace("_request", "_reply"), content));
+        outgoing.iopub.push(("status".to_string(), json!({ "execution_state": "idle" })));
+        outgoing
+    }
+
+    fn execute(&mut self, content: &Json, iopub: &mut Vec<(String, Json)>) -> Json {
+        let code = content["code"].as_str().unwrap_or("");
+        let silent = content["silent"].as_bool().unwrap_or(false);
+        if !silent {
+            self.execution_count += 1;
+            iopub.push(("execute_input".to_string(), json!({ "code": code, "execution_count": self.execution_count })));
+        }
+
+        let evaluation = self.session.evaluate(code);
+        if !silent && !evaluation.output.is_empty() {
+            iopub.push(("stream".to_string(), json!({ "name": "stdout", "text": evaluation.output })));
+        }
+        if !silent && !evaluation.warnings.is_empty() {
+            let text: String = evaluation.warnings.iter().map(|warning| format!("Warning: {}\n", warning)).collect();
+            iopub.push(("stream".to_string(), json!({ "name": "stderr", "text": text })));
+        }
+
+        match evaluation.error {
+            Some((kind, message)) => {
+                let ename = if kind == "compile" { "CompileError" } else { "RuntimeError" };
+                let error = json!({ "ename": ename, "evalue": message, "traceback": [format!("{}: {}", ename, message)] });
+                iopub.push(("error".to_string(), error.clone()));
+                let mut reply = error;
+                reply["status"] = json!("error");
+                reply["execution_count"] = json!(self.execution_count);
+                reply
+            }
+            None => {
+                if let (Some(value), false) = (evaluation.value, silent) {
+                    iopub.push(("execute_result".to_string(), json!({
+                        "execution_count": self.execution_count,
+                        "data": { "text/plain": value },
+                        "metadata": {},
+                    })));
+                }
+                json!({ "status": "ok", "execution_count": self.execution_count, "user_expressions": {} })
+            }
+        }
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<u8>>();
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(data).finalize();
+    Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The current time in ISO 8601, as message headers require.
+fn utc_timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (days, seconds) = (now.as_secs() / 86_400, now.as_secs() % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let (era, day_of_era) = (z / 146_097, z % 146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year, month, day, seconds / 3600, seconds % 3600 / 60, seconds % 60, now.subsec_micros()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(kernel: &mut Kernel, msg_type: &str, content: Json) -> Message {
+        let header = json!({ "msg_id": "1", "msg_type": msg_type }).to_string();
+        let content = content.to_string();
+        let signature = kernel.sign(&[header.as_bytes(), b"{}", b"{}", content.as_bytes()]);
+        let frames = vec![b"client".to_vec(), DELIMITER.to_vec(), signature.into_bytes(), header.into_bytes(), b"{}".to_vec(), b"{}".to_vec(), content.into_bytes()];
+        kernel.parse(&frames).unwrap()
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_execute_publishes_output_and_result() {
+        let mut kernel = Kernel::new(Grease::new(), b"secret".to_vec());
+        let first = request(&mut kernel, "execute_request", json!({ "code": "x = 20\nprint(\"hi\")" }));
+        let outgoing = kernel.handle(&first);
+        let types: Vec<&str> = outgoing.iopub.iter().map(|(msg_type, _)| msg_type.as_str()).collect();
+        assert_eq!(types, ["status", "execute_input", "stream", "status"]);
+        assert_eq!(outgoing.iopub[2].1["text"], "hi\n");
+        let (reply_type, reply) = outgoing.reply.unwrap();
+        assert_eq!(reply_type, "execute_reply");
+        assert_eq!(reply["status"], "ok");
+
+        let second = request(&mut kernel, "execute_request", json!({ "code": "x + 1" }));
+        let outgoing = kernel.handle(&second);
+        let (_, result) = outgoing.iopub.iter().find(|(msg_type, _)| msg_type == "execute_result").unwrap();
+        assert_eq!(result["data"]["text/plain"], "21");
+        assert_eq!(result["execution_count"], 2);
+
+        let failing = request(&mut kernel, "execute_request", json!({ "code": "print(missing)" }));
+        let (_, reply) = kernel.handle(&failing).reply.unwrap();
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["ename"], "RuntimeError");
+    }
+
+    #[test]
+    fn test_messages_are_signed_and_routed() {
+        let mut kernel = Kernel::new(Grease::new(), b"secret".to_vec());
+        let info = request(&mut kernel, "kernel_info_request", json!({}));
+        assert_eq!(info.identities, [b"client".to_vec()]);
+        let reply = kernel.frames(&info.identities, &info, "kernel_info_reply", json!({ "status": "ok" }));
+        let parsed = kernel.parse(&reply).unwrap();
+        assert_eq!(parsed.header["msg_type"], "kernel_info_reply");
+
+        let mut tampered = reply.clone();
+        tampered[6] = b"{\"status\": \"error\"}".to_vec();
+        assert!(kernel.parse(&tampered).unwrap_err().contains("signature"));
+
+        let shutdown = request(&mut kernel, "shutdown_request", json!({ "restart": false }));
+        assert!(kernel.handle(&shutdown).shutdown);
+    }
+}
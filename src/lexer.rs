@@ -1,33 +1,157 @@
 // Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::token::{Token, TokenType};
+use crate::edition::Edition;
+use crate::token::{Span, Token, TokenType};
+
+/// Reserved words and the tokens they lex to. Editor support generated by
+/// `grease highlight` reads this table, so new keywords belong here.
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("def", TokenType::Fn),
+    ("if", TokenType::If),
+    ("elif", TokenType::Elif),
+    ("else", TokenType::Else),
+    ("while", TokenType::While),
+    ("for", TokenType::For),
+    ("in", TokenType::In),
+    ("break", TokenType::Break),
+    ("continue", TokenType::Continue),
+    ("return", TokenType::Return),
+    ("use", TokenType::Use),
+    ("from", TokenType::From),
+    ("lazy", TokenType::Lazy),
+    ("as", TokenType::As),
+    ("throw", TokenType::Throw),
+    ("try", TokenType::Try),
+    ("catch", TokenType::Catch),
+    ("finally", TokenType::Finally),
+    ("with", TokenType::With),
+    ("class", TokenType::Class),
+    ("new", TokenType::New),
+    ("self", TokenType::SelfKw),
+    ("super", TokenType::Super),
+    ("true", TokenType::True),
+    ("false", TokenType::False),
+    ("null", TokenType::Null),
+    ("and", TokenType::And),
+    ("or", TokenType::Or),
+    ("not", TokenType::Not),
+];
+
+/// Operators and punctuation, longest first so `==` wins over `=`.
+pub const OPERATORS: &[(&str, TokenType)] = &[
+    ("==", TokenType::Equal),
+    ("!=", TokenType::NotEqual),
+    ("<=", TokenType::LessEqual),
+    (">=", TokenType::GreaterEqual),
+    ("=", TokenType::Assign),
+    ("<", TokenType::Less),
+    (">", TokenType::Greater),
+    ("+", TokenType::Plus),
+    ("-", TokenType::Minus),
+    ("*", TokenType::Multiply),
+    ("/", TokenType::Divide),
+    ("%", TokenType::Modulo),
+    ("(", TokenType::LeftParen),
+    (")", TokenType::RightParen),
+    ("{", TokenType::LeftBrace),
+    ("}", TokenType::RightBrace),
+    ("[", TokenType::LeftBracket),
+    ("]", TokenType::RightBracket),
+    (",", TokenType::Comma),
+    (".", TokenType::Dot),
+    (":", TokenType::Colon),
+    (";", TokenType::Semicolon),
+    ("@", TokenType::At),
+];
+
+/// Keywords and operators added by later editions, with the edition that
+/// introduced them. They lex to the same tokens as the spelling they alias.
+pub const ALIASES: &[(&str, TokenType, Edition)] = &[
+    ("fn", TokenType::Fn, Edition::V0_2),
+    ("&&", TokenType::And, Edition::V0_2),
+    ("||", TokenType::Or, Edition::V0_2),
+    ("!", TokenType::Not, Edition::V0_2),
+];
+
+/// Columns a tab indents to unless `grease.toml` says otherwise.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
 
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     line: usize,
     column: usize,
+    /// Line and column where the token being scanned starts.
+    start: (usize, usize),
+    /// Columns a tab indents to.
+    tab_width: usize,
+    /// Language rules in effect; a pragma in the source overrides it.
+    edition: Edition,
+    /// What the source's `# grease: version` pragma asks for, if anything.
+    pragma: Result<Option<Edition>, String>,
+    /// Widths of the enclosing blocks' indentation, outermost first.
     indent_stack: Vec<usize>,
+    /// The tab or space the file's first indented line starts with; every
+    /// other indent has to use it too.
+    indent_char: Option<char>,
+    pending_dedents: usize,
+    /// Open `(`, `[` and `{` not yet closed; newlines inside them don't end
+    /// the line.
+    bracket_depth: usize,
 }
 
 impl Lexer {
     pub fn new(input: String) -> Self {
         Lexer {
+            pragma: Edition::pragma(&input),
             input: input.chars().collect(),
             position: 0,
             line: 1,
             column: 1,
+            start: (1, 1),
+            tab_width: DEFAULT_TAB_WIDTH,
+            edition: Edition::default(),
             indent_stack: vec![0], // Initialize with base indentation level
+            indent_char: None,
+            pending_dedents: 0,
+            bracket_depth: 0,
         }
     }
 
+    /// Indent a tab to `width` columns, e.g. from `grease.toml`.
+    pub fn with_tab_width(mut self, width: usize) -> Self {
+        self.tab_width = width.max(1);
+        self
+    }
+
+    /// Lex by `edition`'s rules unless the source's pragma names another,
+    /// e.g. from `grease.toml`.
+    pub fn with_edition(mut self, edition: Edition) -> Self {
+        self.edition = edition;
+        self
+    }
+
+    /// The edition the source was lexed with.
+    pub fn edition(&self) -> Edition {
+        self.edition
+    }
+
     pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
+        if let Some(edition) = self.pragma.clone()? {
+            self.edition = edition;
+        }
         let mut tokens = Vec::with_capacity(self.input.len() / 4); // Estimate capacity
         
         while !self.is_at_end() {
             match self.scan_token() {
-                Ok(Some(token)) => tokens.push(token),
+                Ok(Some(token)) => {
+                    while self.pending_dedents > 0 {
+                        tokens.push(token.clone());
+                        self.pending_dedents -= 1;
+                    }
+                    tokens.push(token);
+                }
                 Ok(None) => (),
                 Err(e) => return Err(e),
             }
@@ -49,108 +173,18 @@ impl Lexer {
         if self.is_at_end() {
             return Ok(None);
         }
+        self.start = (self.line, self.column);
 
         let c = self.current_char();
         
         match c {
-            '(' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::LeftParen, "(".to_string(), self.line, self.column)))
-            }
-            ')' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::RightParen, ")".to_string(), self.line, self.column)))
-            }
-            '{' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::LeftBrace, "{".to_string(), self.line, self.column)))
-            }
-            '}' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::RightBrace, "}".to_string(), self.line, self.column)))
-            }
-            '[' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::LeftBracket, "[".to_string(), self.line, self.column)))
-            }
-            ']' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::RightBracket, "]".to_string(), self.line, self.column)))
-            }
-            ',' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Comma, ",".to_string(), self.line, self.column)))
-            }
-            '.' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Dot, ".".to_string(), self.line, self.column)))
-            }
-            ':' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Colon, ":".to_string(), self.line, self.column)))
-            }
-            ';' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Semicolon, ";".to_string(), self.line, self.column)))
-            }
-            '+' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Plus, "+".to_string(), self.line, self.column)))
-            }
-            '-' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Minus, "-".to_string(), self.line, self.column)))
-            }
-            '*' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Multiply, "*".to_string(), self.line, self.column)))
-            }
             '#' => {
                 self.skip_comment();
                 Ok(None)
             }
-            '/' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Divide, "/".to_string(), self.line, self.column)))
-            }
-            '%' => {
-                self.advance();
-                Ok(Some(Token::new(TokenType::Modulo, "%".to_string(), self.line, self.column)))
-            }
-            '=' => {
-                self.advance();
-                if self.match_char('=') {
-                    Ok(Some(Token::new(TokenType::Equal, "==".to_string(), self.line, self.column)))
-                } else {
-                    Ok(Some(Token::new(TokenType::Assign, "=".to_string(), self.line, self.column)))
-                }
-            }
-            '!' => {
-                self.advance();
-                if self.match_char('=') {
-                    Ok(Some(Token::new(TokenType::NotEqual, "!=".to_string(), self.line, self.column)))
-                } else {
-                    Err(format!("Unexpected character '!' at line {}, column {}", self.line, self.column))
-                }
-            }
-            '<' => {
-                self.advance();
-                if self.match_char('=') {
-                    Ok(Some(Token::new(TokenType::LessEqual, "<=".to_string(), self.line, self.column)))
-                } else {
-                    Ok(Some(Token::new(TokenType::Less, "<".to_string(), self.line, self.column)))
-                }
-            }
-            '>' => {
-                self.advance();
-                if self.match_char('=') {
-                    Ok(Some(Token::new(TokenType::GreaterEqual, ">=".to_string(), self.line, self.column)))
-                } else {
-                    Ok(Some(Token::new(TokenType::Greater, ">".to_string(), self.line, self.column)))
-                }
-            }
             '"' => self.string(),
             '\'' => self.char_string(),
+            'b' if self.input.get(self.position + 1) == Some(&'"') => self.bytes(),
             '0'..='9' => self.number(),
             'a'..='z' | 'A'..='Z' | '_' => {
                 // Check if this is the start of an inline block
@@ -161,10 +195,46 @@ impl Lexer {
                 }
             }
             '\n' => self.newline(),
-            _ => Err(format!("Unexpected character '{}' at line {}, column {}", c, self.line, self.column)),
+            _ => self.operator(c),
         }
     }
 
+    fn operator(&mut self, c: char) -> Result<Option<Token>, String> {
+        let rest = &self.input[self.position..];
+        let starts_with = |text: &str| text.chars().enumerate().all(|(i, ch)| rest.get(i) == Some(&ch));
+        let operator = OPERATORS.iter()
+            .find(|(text, _)| starts_with(text))
+            .map(|(text, token_type)| (*text, token_type.clone()))
+            .or_else(|| {
+                let (text, _, _) = ALIASES.iter().find(|(text, _, _)| starts_with(text))?;
+                Some((*text, self.alias(|alias| alias == *text)?))
+            });
+        match operator {
+            Some((text, token_type)) => {
+                for _ in 0..text.len() {
+                    self.advance();
+                }
+                match token_type {
+                    TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => self.bracket_depth += 1,
+                    TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                        self.bracket_depth = self.bracket_depth.saturating_sub(1);
+                    }
+                    _ => {}
+                }
+                Ok(Some(self.token(token_type, text.to_string())))
+            }
+            None => Err(format!("Unexpected character '{}' at line {}, column {}", c, self.line, self.column)),
+        }
+    }
+
+    /// The token an alias matching `is_alias` stands for, if this edition has
+    /// it.
+    fn alias(&self, is_alias: impl Fn(&str) -> bool) -> Option<TokenType> {
+        ALIASES.iter()
+            .find(|(text, _, since)| is_alias(text) && self.edition >= *since)
+            .map(|(_, token_type, _)| token_type.clone())
+    }
+
     fn identifier(&mut self) -> Result<Option<Token>, String> {
         let start = self.position;
         while !self.is_at_end() && (self.current_char().is_alphanumeric() || self.current_char() == '_') {
@@ -172,34 +242,13 @@ impl Lexer {
         }
         
         let text: String = self.input[start..self.position].iter().collect();
-        let token_type = match text.as_str() {
-            "def" => TokenType::Fn,
-            "if" => TokenType::If,
-            "elif" => TokenType::Elif,
-            "else" => TokenType::Else,
-            "while" => TokenType::While,
-            "for" => TokenType::For,
-            "in" => TokenType::In,
-            "return" => TokenType::Return,
-            "use" => TokenType::Use,
-            "throw" => TokenType::Throw,
-            "as" => TokenType::As,
-            "true" => TokenType::True,
-            "false" => TokenType::False,
-            "null" => TokenType::Null,
-            "class" => TokenType::Class,
-            "new" => TokenType::New,
-            "self" => TokenType::SelfKw,
-            "super" => TokenType::Super,
-            "and" => TokenType::And,
-            "or" => TokenType::Or,
-            "not" => TokenType::Not,
-            "rust" | "asm" | "try" | "catch" => TokenType::Identifier(text.clone()),
-            "str" => TokenType::Identifier(text.clone()),
-            _ => TokenType::Identifier(text.clone()),
-        };
-        
-        Ok(Some(Token::new(token_type, text, self.line, self.column)))
+        let token_type = KEYWORDS.iter()
+            .find(|(keyword, _)| *keyword == text)
+            .map(|(_, token_type)| token_type.clone())
+            .or_else(|| self.alias(|alias| alias == text))
+            .unwrap_or_else(|| TokenType::Identifier(text.clone()));
+
+        Ok(Some(self.token(token_type, text)))
     }
 
     fn number(&mut self) -> Result<Option<Token>, String> {
@@ -218,8 +267,8 @@ impl Lexer {
         
         let text: String = self.input[start..self.position].iter().collect();
         match text.parse::<f64>() {
-            Ok(value) => Ok(Some(Token::new(TokenType::Number(value), text, self.line, self.column))),
-            Err(_) => Err(format!("Invalid number '{}' at line {}, column {}", text, self.line, self.column)),
+            Ok(value) => Ok(Some(self.token(TokenType::Number(value), text))),
+            Err(_) => Err(format!("Invalid number '{}' at line {}, column {}", text, self.start.0, self.start.1)),
         }
     }
 
@@ -229,19 +278,47 @@ impl Lexer {
         
         while !self.is_at_end() && self.current_char() != '"' {
             if self.current_char() == '\n' {
-                return Err(format!("Unterminated string at line {}", self.line));
+                return Err(format!("Unterminated string at line {}, column {}", self.start.0, self.start.1));
             }
             self.advance();
         }
         
         if self.is_at_end() {
-            return Err(format!("Unterminated string at line {}", self.line));
+            return Err(format!("Unterminated string at line {}, column {}", self.start.0, self.start.1));
         }
         
         let text: String = self.input[start..self.position].iter().collect();
         self.advance(); // skip closing quote
         
-        Ok(Some(Token::new(TokenType::String(text.clone()), text, self.line, self.column)))
+        Ok(Some(self.token(TokenType::String(text.clone()), text)))
+    }
+
+    /// A `b"..."` literal; `\"` doesn't end it.
+    fn bytes(&mut self) -> Result<Option<Token>, String> {
+        self.advance(); // skip the b
+        self.advance(); // and the opening quote
+        let start = self.position;
+
+        while !self.is_at_end() && self.current_char() != '"' {
+            if self.current_char() == '\n' {
+                return Err(format!("Unterminated bytes literal at line {}, column {}", self.start.0, self.start.1));
+            }
+            if self.current_char() == '\\' && self.position + 1 < self.input.len() {
+                self.advance();
+            }
+            self.advance();
+        }
+
+        if self.is_at_end() {
+            return Err(format!("Unterminated bytes literal at line {}, column {}", self.start.0, self.start.1));
+        }
+
+        let text: String = self.input[start..self.position].iter().collect();
+        self.advance(); // skip closing quote
+
+        let bytes = crate::bytes::parse_literal(&text)
+            .map_err(|e| format!("{} at line {}, column {}", e, self.start.0, self.start.1))?;
+        Ok(Some(self.token(TokenType::Bytes(bytes), text)))
     }
 
     fn char_string(&mut self) -> Result<Option<Token>, String> {
@@ -250,55 +327,114 @@ impl Lexer {
         
         while !self.is_at_end() && self.current_char() != '\'' {
             if self.current_char() == '\n' {
-                return Err(format!("Unterminated string at line {}", self.line));
+                return Err(format!("Unterminated string at line {}, column {}", self.start.0, self.start.1));
             }
             self.advance();
         }
         
         if self.is_at_end() {
-            return Err(format!("Unterminated string at line {}", self.line));
+            return Err(format!("Unterminated string at line {}, column {}", self.start.0, self.start.1));
         }
         
         let text: String = self.input[start..self.position].iter().collect();
         self.advance(); // skip closing quote
         
-        Ok(Some(Token::new(TokenType::String(text.clone()), text, self.line, self.column)))
+        Ok(Some(self.token(TokenType::String(text.clone()), text)))
     }
 
     fn newline(&mut self) -> Result<Option<Token>, String> {
         self.advance();
         self.line += 1;
         self.column = 1;
+
+        // Inside brackets the expression just carries on to the next line
+        if self.bracket_depth > 0 {
+            return Ok(None);
+        }
         
-        // Handle indentation
+        // Handle indentation, ignoring blank and comment-only lines
         let mut indent_level = 0;
+        let mut mixed = false;
         let mut peek_pos = self.position;
         while peek_pos < self.input.len() {
             let ch = self.input[peek_pos];
-            if ch == '\n' { break; }
+            if ch == '\n' || ch == '#' {
+                while peek_pos < self.input.len() && self.input[peek_pos] != '\n' {
+                    peek_pos += 1;
+                }
+                if peek_pos >= self.input.len() {
+                    break;
+                }
+                // Blank line: move past it and measure the next one
+                peek_pos += 1;
+                self.position = peek_pos;
+                self.line += 1;
+                indent_level = 0;
+                mixed = false;
+                continue;
+            }
             if !ch.is_whitespace() { break; }
             
-            indent_level += match ch {
-                ' ' => 1,
-                '\t' => 4, // treat tab as 4 spaces
-                _ => 0,
-            };
+            match ch {
+                ' ' => indent_level += 1,
+                // A tab moves to the next tab stop
+                '\t' => indent_level += self.tab_width - indent_level % self.tab_width,
+                _ => {}
+            }
+            if ch == ' ' || ch == '\t' {
+                mixed |= *self.indent_char.get_or_insert(ch) != ch;
+            }
             peek_pos += 1;
         }
         
+        if peek_pos >= self.input.len() {
+            // Trailing blank lines; remaining dedents are emitted at EOF
+            return Ok(Some(self.line_break(TokenType::Newline, "\n")));
+        }
+        
+        if mixed {
+            return Err(format!("Inconsistent use of tabs and spaces in indentation at line {}", self.line));
+        }
         let current_indent = self.indent_stack.last().copied().unwrap_or(0);
         
         if indent_level > current_indent {
             self.indent_stack.push(indent_level);
-            Ok(Some(Token::new(TokenType::Indent, "".to_string(), self.line, self.column)))
+            Ok(Some(self.line_break(TokenType::Indent, "")))
         } else if indent_level < current_indent {
-            self.indent_stack.pop();
-            Ok(Some(Token::new(TokenType::Dedent, "".to_string(), self.line, self.column)))
+            // Closing several blocks at once needs one dedent per level
+            while self.indent_stack.len() > 1 && indent_level < self.indent_stack[self.indent_stack.len() - 1] {
+                self.indent_stack.pop();
+                self.pending_dedents += 1;
+            }
+            if self.indent_stack.last() != Some(&indent_level) {
+                return Err(format!("Unindent does not match any outer indentation level at line {}", self.line));
+            }
+            self.pending_dedents -= 1;
+            Ok(Some(self.line_break(TokenType::Dedent, "")))
         } else {
-            Ok(Some(Token::new(TokenType::Newline, "\n".to_string(), self.line, self.column)))
+            Ok(Some(self.line_break(TokenType::Newline, "\n")))
         }
     }
 
+    /// A token from where the current one started up to the current
+    /// position.
+    fn token(&self, token_type: TokenType, lexeme: String) -> Token {
+        Token::new(token_type, lexeme, self.start.0, self.start.1).ending_at(self.line, self.column)
+    }
+
+    /// A token for the newline the current token started at; indents and
+    /// dedents take the place of the newline before them.
+    fn line_break(&self, token_type: TokenType, lexeme: &str) -> Token {
+        let (line, column) = self.start;
+        Token::new(token_type, lexeme.to_string(), line, column).ending_at(line, column + 1)
+    }
+
+    /// Where the lexer is: after an error, the span from the start of the
+    /// token it failed on to where it stopped.
+    pub fn span(&self) -> Span {
+        Span { line: self.start.0, column: self.start.1, end_line: self.line, end_column: self.column }
+    }
+
     fn skip_whitespace(&mut self) {
         while !self.is_at_end() && self.current_char().is_whitespace() && self.current_char() != '\n' {
             self.advance();
@@ -318,20 +454,13 @@ impl Lexer {
         }
     }
 
-    fn match_char(&mut self, expected: char) -> bool {
-        if self.is_at_end() || self.current_char() != expected {
-            return false;
-        }
-        self.advance();
-        true
-    }
-
     fn current_char(&self) -> char {
         self.input[self.position]
     }
 
     fn check_inline_block_start(&mut self) -> bool {
         let start_pos = self.position;
+        let start_column = self.column;
         
         // Check if current identifier is "rust" or "asm"
         while !self.is_at_end() && (self.current_char().is_alphanumeric() || self.current_char() == '_') {
@@ -343,6 +472,7 @@ impl Lexer {
         
         // Reset position
         self.position = start_pos;
+        self.column = start_column;
         
         if !is_inline_keyword {
             return false;
@@ -363,6 +493,7 @@ impl Lexer {
         
         // Reset position
         self.position = start_pos;
+        self.column = start_column;
         
         has_brace
     }
@@ -398,16 +529,16 @@ impl Lexer {
             } else if self.current_char() == '}' {
                 brace_count -= 1;
             } else if self.current_char() == '\n' {
+                self.advance();
                 self.line += 1;
                 self.column = 1;
-                self.advance();
                 continue;
             }
             self.advance();
         }
         
         if brace_count > 0 {
-            return Err(format!("Unterminated inline {} block at line {}", keyword, self.line));
+            return Err(format!("Unterminated inline {} block at line {}, column {}", keyword, self.start.0, self.start.1));
         }
         
         // Extract the code (everything between the braces)
@@ -420,7 +551,7 @@ impl Lexer {
             TokenType::AsmInline
         };
         
-        Ok(Some(Token::new(token_type, code, self.line, self.column)))
+        Ok(Some(self.token(token_type, code)))
     }
 
     fn is_at_end(&self) -> bool {
@@ -434,11 +565,11 @@ mod tests {
 
     #[test]
     fn test_tokenize_numbers() {
-        let mut lexer = Lexer::new("42 3.14".to_string());
+        let mut lexer = Lexer::new("42 2.5".to_string());
         let tokens = lexer.tokenize().unwrap();
         assert_eq!(tokens.len(), 3); // two numbers + EOF
         assert_eq!(tokens[0].token_type, TokenType::Number(42.0));
-        assert_eq!(tokens[1].token_type, TokenType::Number(3.14));
+        assert_eq!(tokens[1].token_type, TokenType::Number(2.5));
     }
 
     #[test]
@@ -450,6 +581,15 @@ mod tests {
         assert_eq!(tokens[1].token_type, TokenType::String("world".to_string()));
     }
 
+    #[test]
+    fn test_tokenize_bytes() {
+        let tokens = Lexer::new(r#"b"a\x00\"" b x"#.to_string()).tokenize().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::Bytes(b"a\x00\"".to_vec()));
+        assert_eq!(tokens[1].token_type, TokenType::Identifier("b".to_string()));
+        assert!(Lexer::new("b\"caf\u{e9}\"".to_string()).tokenize().unwrap_err().contains("only contain ASCII"));
+        assert!(Lexer::new("b\"open".to_string()).tokenize().unwrap_err().contains("Unterminated bytes literal"));
+    }
+
     #[test]
     fn test_tokenize_identifiers() {
         let mut lexer = Lexer::new("def x y_z".to_string());
@@ -503,9 +643,93 @@ mod tests {
         assert_eq!(tokens[17].token_type, TokenType::EOF);
     }
 
+    #[test]
+    fn test_tokenize_closes_nested_blocks() {
+        let mut lexer = Lexer::new("class A:\n    def f(self):\n        return 1\n\n    # comment\nx = 1".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let indents = tokens.iter().filter(|t| t.token_type == TokenType::Indent).count();
+        let dedents = tokens.iter().filter(|t| t.token_type == TokenType::Dedent).count();
+        assert_eq!(indents, 2);
+        assert_eq!(dedents, 2);
+        // Both blocks are closed before `x`
+        let x = tokens.iter().position(|t| t.token_type == TokenType::Identifier("x".to_string())).unwrap();
+        assert_eq!(tokens[x - 1].token_type, TokenType::Dedent);
+        assert_eq!(tokens[x - 2].token_type, TokenType::Dedent);
+    }
+
+    #[test]
+    fn test_indentation_errors_and_tab_width() {
+        let lex = |source: &str, tab_width| Lexer::new(source.to_string()).with_tab_width(tab_width).tokenize();
+        let mixed = lex("if x:\n\t  y = 1", 4).unwrap_err();
+        assert_eq!(mixed, "Inconsistent use of tabs and spaces in indentation at line 2");
+        let between = lex("if x:\n    if y:\n        z = 1\n  w = 2", 4).unwrap_err();
+        assert_eq!(between, "Unindent does not match any outer indentation level at line 4");
+
+        // Once a file indents with tabs, a line indented with spaces is mixed
+        // however wide tabs are, and the other way round
+        let source = "if x:\n\ty = 1\nif z:\n  w = 2";
+        assert_eq!(lex(source, 2).unwrap_err(), "Inconsistent use of tabs and spaces in indentation at line 4");
+        assert!(lex("if x:\n  y = 1\nif z:\n\tw = 2", 2).is_err());
+        let tokens = lex("if x:\n\ty = 1\n\tif z:\n\t\tw = 2", 2).unwrap();
+        assert_eq!(tokens.iter().filter(|t| t.token_type == TokenType::Indent).count(), 2);
+    }
+
+    #[test]
+    fn test_newlines_inside_brackets_continue_the_line() {
+        let mut lexer = Lexer::new("if x:\n    f(1,\n  2)\n    y = [\n]\nz".to_string());
+        let types: Vec<TokenType> = lexer.tokenize().unwrap().into_iter().map(|t| t.token_type).collect();
+        let breaks = types.iter().filter(|t| matches!(t, TokenType::Newline | TokenType::Indent | TokenType::Dedent)).count();
+        // Only the indent, the newline after the call and the dedent
+        assert_eq!(breaks, 3);
+        assert_eq!(lexer.bracket_depth, 0);
+    }
+
+    #[test]
+    fn test_aliases_follow_the_edition() {
+        let types = |lexer: &mut Lexer| -> Vec<TokenType> {
+            lexer.tokenize().unwrap().into_iter().map(|t| t.token_type).collect()
+        };
+        let source = "fn f(): !a && b || c != d";
+        let mut lexer = Lexer::new(source.to_string()).with_edition(Edition::V0_2);
+        let tokens = types(&mut lexer);
+        assert_eq!(tokens[0], TokenType::Fn);
+        assert!([TokenType::Not, TokenType::And, TokenType::Or, TokenType::NotEqual].iter().all(|t| tokens.contains(t)));
+
+        assert!(Lexer::new(source.to_string()).tokenize().is_err());
+        let mut lexer = Lexer::new("fn = 1".to_string());
+        assert_eq!(types(&mut lexer)[0], TokenType::Identifier("fn".to_string()));
+
+        // The pragma overrides the configured edition
+        let mut lexer = Lexer::new("# grease: version 0.2\nfn".to_string());
+        assert_eq!(types(&mut lexer)[1], TokenType::Fn);
+        assert_eq!(lexer.edition(), Edition::V0_2);
+        let mut lexer = Lexer::new("# grease: version 0.1\nfn".to_string()).with_edition(Edition::V0_2);
+        assert_eq!(types(&mut lexer)[1], TokenType::Identifier("fn".to_string()));
+    }
+
     #[test]
     fn test_unterminated_string() {
         let mut lexer = Lexer::new("\"hello".to_string());
         assert!(lexer.tokenize().is_err());
     }
+
+    #[test]
+    fn test_tokens_record_start_and_end() {
+        let mut lexer = Lexer::new("name = \"hi\"\nif x >= 10:\n    rust {\n  a\n} y".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let spans: Vec<(usize, usize, usize, usize)> = tokens.iter()
+            .filter(|t| !matches!(t.token_type, TokenType::Dedent | TokenType::EOF))
+            .map(|t| (t.line, t.column, t.end_line, t.end_column))
+            .collect();
+        assert_eq!(spans, [
+            (1, 1, 1, 5), (1, 6, 1, 7), (1, 8, 1, 12), (1, 12, 1, 13),
+            (2, 1, 2, 3), (2, 4, 2, 5), (2, 6, 2, 8), (2, 9, 2, 11), (2, 11, 2, 12), (2, 12, 2, 13),
+            // The indent stands in for the newline; an inline block spans lines
+            (3, 5, 5, 2), (5, 3, 5, 4),
+        ]);
+
+        let mut lexer = Lexer::new("x = 1\ny = \"open".to_string());
+        assert_eq!(lexer.tokenize().unwrap_err(), "Unterminated string at line 2, column 5");
+        assert_eq!((lexer.span().line, lexer.span().column), (2, 5));
+    }
 }
\ No newline at end of file
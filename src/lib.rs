@@ -1,35 +1,131 @@
 // Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
 // SPDX-License-Identifier: Apache-2.0
 
+//! Grease scripting language.
+//!
+//! Embedders should use [`prelude`], which is the supported API and follows
+//! semver. The other modules are implementation details: they stay public
+//! for the CLI, fuzzers and benchmarks but may change in any release.
+
+/// Version of the Grease library and language.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub mod prelude;
+
+#[doc(hidden)]
 pub mod token;
+#[doc(hidden)]
+pub mod edition;
+#[doc(hidden)]
 pub mod lexer;
+#[doc(hidden)]
 pub mod ast;
+#[doc(hidden)]
 pub mod parser;
+#[doc(hidden)]
 pub mod bytecode;
+#[doc(hidden)]
+pub mod source_map;
+#[doc(hidden)]
 pub mod compiler;
+#[doc(hidden)]
+pub mod verifier;
+#[doc(hidden)]
+pub mod chunk_builder;
+#[doc(hidden)]
+pub mod frontend;
+#[doc(hidden)]
+pub mod transpile;
+#[doc(hidden)]
+pub mod globals;
+#[doc(hidden)]
 pub mod vm;
+#[doc(hidden)]
 pub mod repl;
+#[doc(hidden)]
 pub mod grease;
+#[doc(hidden)]
 pub mod linter;
+#[doc(hidden)]
+pub mod fix;
+#[cfg(feature = "lsp")]
+#[doc(hidden)]
 pub mod lsp_workspace;
+#[cfg(feature = "lsp")]
+#[doc(hidden)]
 pub mod lsp_server;
+#[doc(hidden)]
+pub mod config;
+#[cfg(feature = "system")]
+#[doc(hidden)]
+pub mod system;
+#[cfg(feature = "term")]
+#[doc(hidden)]
+pub mod term;
+#[doc(hidden)]
+pub mod shell;
+#[doc(hidden)]
+pub mod plugin;
+#[doc(hidden)]
+pub mod argparse;
+#[doc(hidden)]
+pub mod serialize;
+#[doc(hidden)]
+pub mod builtins;
+#[doc(hidden)]
+pub mod methods;
+#[doc(hidden)]
+pub mod exceptions;
+#[doc(hidden)]
+pub mod performance;
+#[doc(hidden)]
+pub mod modules;
+#[doc(hidden)]
+pub mod eval_server;
+#[doc(hidden)]
+pub mod highlight;
+#[doc(hidden)]
+pub mod dump;
+#[cfg(feature = "jupyter")]
+#[doc(hidden)]
+pub mod jupyter_kernel;
+#[doc(hidden)]
+pub mod sandbox;
+#[doc(hidden)]
+pub mod template;
+#[doc(hidden)]
+pub mod py;
+#[doc(hidden)]
+pub mod bytes;
+#[doc(hidden)]
+pub mod numbers;
+#[doc(hidden)]
+pub mod environment;
+#[doc(hidden)]
+pub mod resources;
+// WASI has no threads to run the interpreters on
+#[cfg(not(target_os = "wasi"))]
+#[doc(hidden)]
+pub mod pool;
+#[doc(hidden)]
+pub mod replay;
+#[doc(hidden)]
+pub mod mock;
+#[doc(hidden)]
+pub mod docs;
+#[doc(hidden)]
+pub mod crash;
+#[doc(hidden)]
+pub mod retry;
+#[doc(hidden)]
+pub mod files;
 
-pub use token::*;
-pub use lexer::*;
-pub use ast::*;
-pub use parser::*;
-pub use bytecode::*;
-pub use compiler::*;
-pub use vm::*;
-pub use repl::*;
-pub use grease::*;
-pub use linter::*;
-pub use lsp_workspace::*;
-pub use lsp_server::*;
+pub use prelude::*;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ProjectConfig;
 
     #[test]
     fn test_basic_execution() {
@@ -148,6 +244,97 @@ mod tests {
         assert_eq!(errors.len(), 0);
     }
 
+    #[test]
+    fn test_linter_respects_project_config() {
+        let config = ProjectConfig::parse("[lint]\nunused_variables = false\n").unwrap();
+        let mut grease = Grease::new().with_config(config);
+        let errors = grease.lint("x = 42\ny = \"unused\"\nprint(x)").unwrap();
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_lint_fixes() {
+        let config = ProjectConfig::parse("[lint]\nquotes = \"double\"\n").unwrap();
+        let mut grease = Grease::new().with_config(config);
+        let source = "def show(items):\n    scratch = 0\n    total = 0\n    for item in items:\n        total = total + item\n        stale = 1\n    print(total)\nunused = 42\nname = 'grease'\nif name == 'grease'\n    print(name)\nquoted = 'say \"hi\"'\nprint(quoted, show)";
+        let errors = grease.lint(&source.replacen("== 'grease'", "== 'grease':", 1)).unwrap();
+        let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(messages, [
+            "Unused variable 'scratch'", "Unused variable 'stale'", "Unused variable 'unused'",
+            "Use double quotes for strings", "Use double quotes for strings",
+        ]);
+
+        // Globals may be read by importers, and a string that would need
+        // escaping keeps its quotes
+        let fixed = grease.fix(source).unwrap();
+        assert_eq!(fixed, "def show(items):\n    total = 0\n    for item in items:\n        total = total + item\n    print(total)\nunused = 42\nname = \"grease\"\nif name == \"grease\":\n    print(name)\nquoted = 'say \"hi\"'\nprint(quoted, show)");
+        assert!(grease.fix("if x > 1\n    y = f(").is_err());
+    }
+
+    #[test]
+    fn test_linter_security_rules() {
+        // The rules see calls through the system module and the deprecated flat names alike
+        let source = "use system\nhost = input()\nsystem.shell(\"ping -c 1 \" + host)\nstatus = system_shell(\"ls \" + (\"-l\" + \" -a\"))\nsystem.exec([\"ping\", host])\nsystem.setenv(\"LD_PRELOAD\", \"hook.so\")\nsystem_setenv(\"LANG\", \"C\")\nsystem_setenv(\"PATH\", \"/tmp\")\nprint(status)";
+        let errors = Grease::new().lint(source).unwrap();
+        let found: Vec<(&str, usize)> = errors.iter().map(|error| (error.rule, error.line)).collect();
+        assert_eq!(found, [("unchecked-exit-code", 3), ("shell-injection", 3), ("unchecked-exit-code", 5), ("sensitive-env", 6), ("sensitive-env", 8)]);
+        assert!(errors[1].message.contains("system.exec"));
+
+        let config = ProjectConfig::parse("[lint]\nsecurity = false\n").unwrap();
+        assert!(Grease::new().with_config(config).lint(source).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_linter_python_compat_rule() {
+        let source = "use py\nd = {\"a\": 1}\nfor k in py.keys(d):\n    print(py.get(d, k, 0))\nfor k in py.keys({}):\n    print(py.zip(k, d))";
+        let mut grease = Grease::new();
+        let errors = grease.lint(source).unwrap();
+        let found: Vec<(&str, usize)> = errors.iter().map(|error| (error.rule, error.line)).collect();
+        assert_eq!(found, [("python-compat", 3), ("python-compat", 4), ("python-compat", 5), ("python-compat", 6)]);
+        assert!(errors[2].fix.is_none());
+
+        let fixed = grease.fix(source).unwrap();
+        assert_eq!(fixed, "use py\nd = {\"a\": 1}\nfor k in d:\n    print(dict_get(d, k, 0))\nfor k in py.keys({}):\n    print(zip(k, d))");
+
+        let config = ProjectConfig::parse("[lint]\npython_compat = false\n").unwrap();
+        assert!(Grease::new().with_config(config).lint(source).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_project_config_sets_tab_width() {
+        use crate::bytecode::Value;
+        let config = ProjectConfig::parse("[syntax]\ntab_width = 2\n").unwrap();
+        let mut grease = Grease::new().with_config(config);
+        assert_eq!(grease.vm.tab_width, 2);
+        let source = "total = 0\nif true:\n\ttotal = 1\n\tif true:\n\t\ttotal = total + 1";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("total"), Some(Value::Number(n)) if *n == 2.0));
+
+        // Tabs two wide don't make a two-space line match a tab-indented one
+        assert!(grease.run("if true:\n\ttotal = 1\nif true:\n  total = 2").is_err());
+    }
+
+    #[test]
+    fn test_language_version_from_pragma_or_config() {
+        use crate::bytecode::Value;
+        let source = "fn twice(x):\n    return x * 2\nok = !false && twice(2) == 4";
+        assert!(Grease::new().run(source).is_err());
+
+        let mut grease = Grease::new();
+        assert_eq!(grease.run(&format!("# grease: version 0.2\n{}", source)).unwrap(), InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("ok"), Some(Value::Boolean(true))));
+
+        let config = ProjectConfig::parse("[syntax]\nversion = \"0.2\"\n").unwrap();
+        assert_eq!(Grease::new().with_config(config).run(source).unwrap(), InterpretResult::Ok);
+        // A pragma still selects the old rules, where `fn` is just a name
+        let config = ProjectConfig::parse("[syntax]\nversion = \"0.2\"\n").unwrap();
+        let old = "# grease: version 0.1\nfn = 1";
+        assert_eq!(Grease::new().with_config(config).run(old).unwrap(), InterpretResult::Ok);
+
+        let err = ProjectConfig::parse("[syntax]\nversion = \"3\"\n").unwrap_err();
+        assert_eq!(err, "[syntax] version: Unknown language version '3' (supported: 0.1, 0.2)");
+    }
+
     #[test]
     fn test_native_function() {
         let mut grease = Grease::new();
@@ -156,14 +343,73 @@ mod tests {
         assert_eq!(result.unwrap(), InterpretResult::Ok);
     }
 
+    #[test]
+    fn test_native_arity_ranges() {
+        let mut grease = Grease::new();
+        grease.register_native("pad", 1..=2, |_vm, args| Ok(Value::Number(args.len() as f64)));
+        grease.register_native("first", 1.., |_vm, args| Ok(args[0].clone()));
+        assert_eq!(grease.run("a = pad(\"x\")\nb = pad(\"x\", 3)\nc = first(1, 2, 3)").unwrap(), InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("b"), Some(Value::Number(n)) if *n == 2.0));
+        assert!(matches!(grease.vm.globals.get("c"), Some(Value::Number(n)) if *n == 1.0));
+
+        let error = |source: &str| match Grease::new().run(source).unwrap() {
+            InterpretResult::RuntimeError(e) => e,
+            other => panic!("Expected a runtime error, got {:?}", other),
+        };
+        assert_eq!(error("getenv()"), "TypeError: getenv expects 1 argument, got 0");
+        assert_eq!(error("sorted([1], null, 2)"), "TypeError: sorted expects 1 to 2 arguments, got 3");
+        assert_eq!(error("zip()"), "TypeError: zip expects at least 1 argument, got 0");
+        assert_eq!(error("def f(a, b):\n    return a\nf(1)"), "TypeError: Function 'f' expects 2 arguments, got 1");
+        assert_eq!(Arity::from(VARIADIC).to_string(), "any number of arguments");
+    }
+
+    #[test]
+    #[cfg(feature = "term")]
+    fn test_module_natives_and_deprecated_aliases() {
+        let mut grease = Grease::new();
+        grease.register_module_native("geo", "double", 1, |_vm, args| match &args[0] {
+            Value::Number(n) => Ok(Value::Number(n * 2.0)),
+            _ => Err("geo.double expects a number".to_string()),
+        });
+        let source = "use term\nuse geo\na = term.spinner(0)\nb = term_spinner(10)\nc = term_spinner(20)\nd = geo.double(21)\nstyled = 1";
+        let outcome = grease.evaluate(source).unwrap();
+        assert_eq!(outcome.result, InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("a").unwrap()), VM::format_value(grease.vm.globals.get("c").unwrap()));
+        assert!(matches!(grease.vm.globals.get("d"), Some(Value::Number(n)) if *n == 42.0));
+        // The flat name still works, and warns once per run
+        assert_eq!(outcome.warnings, vec!["term_spinner is deprecated; use term.spinner instead".to_string()]);
+
+        // Errors, help and mocks use the module member's name
+        let result = grease.run("geo.double(\"x\")").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("geo.double expects a number")));
+        assert_eq!(grease.run("h = help(\"term.spinner\")\ni = help(term_spinner)").unwrap(), InterpretResult::Ok);
+        assert!(VM::format_value(grease.vm.globals.get("h").unwrap()).starts_with("term.spinner(frame)"));
+        assert!(VM::format_value(grease.vm.globals.get("i").unwrap()).contains("A frame of a spinner animation."));
+        grease.vm.override_native("term_spinner", |_args| Ok(Value::String("*".to_string()))).unwrap();
+        assert_eq!(grease.run("e = [term.spinner(0), term_spinner(0)]").unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("e").unwrap()), "[*, *]");
+
+        // Host modules survive a reset like the built-in ones
+        grease.reset().unwrap();
+        assert_eq!(grease.run("use geo\nf = geo.double(2)").unwrap(), InterpretResult::Ok);
+        assert!(grease.vm.globals.get("styled").is_none());
+    }
+
     #[test]
     fn test_class_declaration() {
         let mut grease = Grease::new();
-        let result = grease.run("class Animal:\n\tdef make_sound():\n\t\tprint(\"sound\")");
+        let result = grease.run("class Animal:\n\tdef make_sound(self):\n\t\tprint(\"sound\")");
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), InterpretResult::Ok);
     }
 
+    #[test]
+    fn test_method_without_self_is_rejected() {
+        let mut grease = Grease::new();
+        let result = grease.run("class Animal:\n\tdef make_sound():\n\t\tprint(\"sound\")");
+        assert!(matches!(result, Err(msg) if msg.contains("must take 'self'")));
+    }
+
     #[test]
     fn test_class_instantiation() {
         let mut grease = Grease::new();
@@ -194,6 +440,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_mutation_is_seen_through_aliases() {
+        let code = "class Animal:\n    \"An animal\"\nanimal = new Animal()\npet = animal\npet.name = \"Buddy\"\ndef rename(a):\n    a.name = a.name + \"!\"\nrename(animal)\nxs = [1]\nys = xs\nys.push(2)\ndef add(items):\n    items.push(3)\nadd(xs)\nd = {\"k\": 1, \"j\": 2}\nalias = d\nalias.delete(\"j\")\nsnapshot = copy(xs)\nsnapshot.push(4)\nnested = [[1]]\ndeep = deepcopy(nested)\ndeep[0].push(2)\ncycle = []\ncycle.push(cycle)";
+        let mut grease = Grease::new();
+        assert_eq!(grease.run(code), Ok(InterpretResult::Ok));
+        let vm = &grease.vm;
+        let formatted = |name: &str| vm::VM::format_value(vm.globals.get(name).unwrap());
+        assert!(matches!(vm.globals.get("animal"), Some(Value::Object { fields, .. }) if matches!(fields.lock().unwrap().get("name"), Some(Value::String(s)) if s == "Buddy!")));
+        assert_eq!(formatted("xs"), "[1, 2, 3]");
+        assert_eq!(formatted("d"), "{k: 1}");
+        assert_eq!(formatted("snapshot"), "[1, 2, 3, 4]");
+        assert_eq!(formatted("nested"), "[[1]]");
+        assert_eq!(formatted("cycle"), "[[...]]");
+    }
+
     #[test]
     fn test_class_inheritance() {
         let mut grease = Grease::new();
@@ -319,5 +580,483 @@ print("not true = " + (not is_true))
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_function_parameters_resolve_to_correct_slots() {
+        let mut grease = Grease::new();
+        let result = grease.run("def first(a, b, c):\n    return a\nresult = first(1, 2, 3)").unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("result"), Some(crate::bytecode::Value::Number(n)) if *n == 1.0));
+    }
+
+    #[test]
+    fn test_tuples_index_unpack_and_compare() {
+        use crate::bytecode::Value;
+        let mut grease = Grease::new();
+        let source = "t = (1, \"a\", (2,))\nsecond = t[1]\nx, y, z = t\nsame = t == (1, \"a\", (2,))\ndef swap(a, b):\n    p, q = (b, a)\n    return (p, q)\nswapped = swap(1, 2)";
+        let result = grease.run(source).unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("second"), Some(Value::String(s)) if s == "a"));
+        assert!(matches!(grease.vm.globals.get("x"), Some(Value::Number(n)) if *n == 1.0));
+        assert!(matches!(grease.vm.globals.get("z"), Some(Value::Tuple(items)) if items.len() == 1));
+        assert!(matches!(grease.vm.globals.get("same"), Some(Value::Boolean(true))));
+        let swapped = grease.vm.globals.get("swapped").map(crate::vm::VM::format_value);
+        assert_eq!(swapped.as_deref(), Some("(2, 1)"));
+
+        let result = grease.run("a, b = (1, 2, 3)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(msg) if msg.contains("Expected 2 values")));
+    }
+
+    /// A fresh directory of module files for the module tests.
+    fn module_dir(test: &str, modules: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("grease_modules_{}_{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, source) in modules {
+            std::fs::write(dir.join(format!("{}.grease", name)), source).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_modules_have_separate_namespaces() {
+        use crate::bytecode::Value;
+        let dir = module_dir("namespaces", &[
+            ("geometry", "scale = 2\ndef helper(x):\n    return x * scale\ndef area(w, h):\n    return helper(w * h)"),
+            ("finance", "scale = 100\ndef helper(x):\n    return x + scale\ndef area(w, h):\n    return helper(w)"),
+        ]);
+        let mut grease = Grease::new().with_module_path(&dir);
+        let source = "use geometry\nuse finance as fin\nscale = 7\ng = geometry.area(2, 3)\nf = fin.area(1, 0)\nm = geometry\nk = m.scale\nh = m.area(1, 1)";
+        let result = grease.run(source).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        let number = |name: &str| match grease.vm.globals.get(name) {
+            Some(Value::Number(n)) => *n,
+            other => panic!("Expected number for {}, got {:?}", name, other),
+        };
+        assert_eq!(number("g"), 12.0);
+        assert_eq!(number("f"), 101.0);
+        // Module values can be passed around like any other value
+        assert_eq!(number("k"), 2.0);
+        assert_eq!(number("h"), 2.0);
+        // The module's top-level globals stay in its namespace
+        assert_eq!(number("scale"), 7.0);
+        assert!(!grease.vm.globals.contains_key("helper"));
+        assert!(matches!(grease.vm.globals.get("fin"), Some(Value::Module(name)) if name == "finance"));
+    }
+
+    #[test]
+    fn test_from_use_binds_selected_members() {
+        use crate::bytecode::Value;
+        let dir = module_dir("from_use", &[
+            ("shapes", "use units\nclass Square:\n    def area(self):\n        return units.squared(self.side)\ndef square(side):\n    s = new Square()\n    s.side = side\n    return s"),
+            ("units", "def squared(x):\n    return x * x"),
+        ]);
+        let mut grease = Grease::new().with_module_path(&dir);
+        let result = grease.run("from shapes use square\nsq = square(4)\narea = sq.area()").unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("area"), Some(Value::Number(n)) if *n == 16.0));
+        assert!(!grease.vm.globals.contains_key("shapes"));
+        assert!(!grease.vm.globals.contains_key("units"));
+
+        let result = grease.run("from shapes use missing").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("has no member 'missing'")));
+    }
+
+    #[test]
+    fn test_objects_use_the_class_of_their_module() {
+        use crate::bytecode::Value;
+        let dir = module_dir("same_class_name", &[
+            ("trees", "class Node:\n    def kind(self):\n        return \"tree\"\nclass Missing(Error):\n    def kind(self):\n        return \"missing\"\ndef make():\n    return new Node()\ndef fail():\n    throw new Missing(\"no tree\")"),
+            ("graphs", "class Node:\n    def kind(self):\n        return \"graph\"\ndef make():\n    return new Node()"),
+        ]);
+        let mut grease = Grease::new().with_module_path(&dir);
+        let source = "use trees\nuse graphs\nfrom trees use Missing\nclass Node:\n    def kind(self):\n        return \"script\"\nkinds = [trees.make().kind(), graphs.make().kind(), new Node().kind()]\ntry:\n    trees.fail()\ncatch Missing as e:\n    caught = e.kind()";
+        let result = grease.run(source).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        let kinds = match grease.vm.globals.get("kinds") {
+            Some(Value::Array(items)) => items.lock().unwrap().iter().map(VM::format_value).collect::<Vec<_>>(),
+            other => panic!("Expected array, got {:?}", other),
+        };
+        assert_eq!(kinds, ["tree", "graph", "script"]);
+        assert!(matches!(grease.vm.globals.get("caught"), Some(Value::String(s)) if s == "missing"));
+    }
+
+    #[test]
+    fn test_available_modules() {
+        use crate::modules::ModuleSource;
+        let dir = module_dir("available", &[("geo", "x = 1"), ("math", "pi = 3")]);
+        let modules = Grease::new().with_module_path(&dir).available_modules();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let source = |name: &str| modules.iter().find(|(found, _)| found == name).map(|(_, source)| source.clone());
+        assert_eq!(source("geo"), Some(ModuleSource::File(dir.join("geo.grease"))));
+        // Module paths are searched before the standard library
+        assert_eq!(source("math"), Some(ModuleSource::File(dir.join("math.grease"))));
+        assert_eq!(source("template"), Some(ModuleSource::Native));
+        // Listed before their natives are registered
+        assert_eq!(source("py"), Some(ModuleSource::Native));
+        assert!(modules.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_for_iterates_objects_with_iter() {
+        let mut grease = Grease::new();
+        let source = "class Countdown:\n    def __iter__(self):\n        items = []\n        i = self.n\n        while i > 0:\n            items.push(i)\n            i = i - 1\n        return items\n\
+            class Keys:\n    def __iter__(self):\n        return self.inner\n\
+            countdown = new Countdown()\ncountdown.n = 3\nkeys = new Keys()\nkeys.inner = {\"b\": 1, \"a\": 2}\n\
+            def visit(iterable):\n    seen = []\n    for item in iterable:\n        seen.push(item)\n    return seen\n\
+            visited = [visit(countdown), visit(keys), visit(\"hé\"), sum(countdown), zip(countdown, keys)]";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("visited").unwrap()), "[[3, 2, 1], [a, b], [h, é], 6, [(3, a), (2, b)]]");
+
+        match grease.run("class Plain:\n    def f(self):\n        return 1\nfor x in new Plain():\n    print(x)").unwrap() {
+            InterpretResult::RuntimeError(message) => assert!(message.contains("'Plain', which has no __iter__ method"), "{}", message),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_compile_time_defines() {
+        let mut grease = Grease::new().with_define("debug", Value::Boolean(true));
+        let source = "if platform == \"no-such-os\":\n    missing_native()\nelif debug and arch != \"\":\n    taken = platform\n\
+            def check():\n    debug = false\n    if debug:\n        return \"shadowed\"\n    return \"local\"\n\
+            local = check()";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("taken").unwrap()), std::env::consts::OS);
+        assert_eq!(VM::format_value(grease.vm.globals.get("local").unwrap()), "local");
+        assert!(grease.run("debug = false").unwrap_err().contains("'debug' is a compile-time define"));
+    }
+
+    #[test]
+    fn test_native_modules_register_on_first_use() {
+        let mut grease = Grease::new();
+        assert!(!grease.vm.modules.contains_key("py"));
+        assert!(!grease.vm.globals.contains_key("system_setenv"));
+        let source = "a = module_exists(\"py\")\nuse py\nb = py.len([1, 2])\n\
+            system_setenv(\"GREASE_LAZY_TEST\", \"set\")\nc = getenv(\"GREASE_LAZY_TEST\")";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("a"), Some(Value::Boolean(true))));
+        assert!(matches!(grease.vm.globals.get("b"), Some(Value::Number(n)) if *n == 2.0));
+        assert_eq!(VM::format_value(grease.vm.globals.get("c").unwrap()), "set");
+
+        // Once registered they are kept like the other natives
+        grease.reset().unwrap();
+        assert!(grease.vm.modules.contains_key("py"));
+        assert_eq!(grease.run("use system\nsystem.setenv(\"GREASE_LAZY_TEST\", null)").unwrap(), InterpretResult::Ok);
+
+        // Mocks and host modules by the same name see the natives too
+        let mut grease = Grease::new().with_mocks(crate::mock::Mocks::new().returning("len", Value::Number(7.0)));
+        assert_eq!(grease.run("use py\nn = py.len([])").unwrap(), InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("n"), Some(Value::Number(n)) if *n == 7.0));
+        let mut grease = Grease::new().with_native_module("py", &[("len", 1, |_vm, _args| Ok(Value::Null))]);
+        assert_eq!(grease.run("use py\nn = py.len([])").unwrap(), InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("n"), Some(Value::Null)));
+    }
+
+    #[test]
+    fn test_runtime_error_has_traceback() {
+        let mut grease = Grease::new();
+        let outcome = grease.evaluate("def area(w):\n    return w * missing\n\nx = area(2)").unwrap();
+        assert!(matches!(outcome.result, InterpretResult::RuntimeError(_)));
+        let frames: Vec<(Option<&str>, usize)> = outcome.traceback.iter()
+            .map(|frame| (frame.function.as_deref(), frame.location.map_or(0, |at| at.line)))
+            .collect();
+        assert_eq!(frames, [(Some("area"), 2), (None, 4)]);
+        assert_eq!(outcome.traceback[0].location.unwrap().column, 16);
+        let snippet = outcome.error_location().and_then(|at| at.snippet("def area(w):\n    return w * missing"));
+        assert_eq!(snippet.unwrap(), "2 |     return w * missing\n  |                ^");
+
+        // Caught errors leave no traceback behind
+        let outcome = grease.evaluate("try:\n    y = missing\ncatch e:\n    y = 0").unwrap();
+        assert_eq!(outcome.result, InterpretResult::Ok);
+        assert!(outcome.traceback.is_empty());
+    }
+
+    #[test]
+    fn test_circular_module_imports_are_rejected() {
+        let dir = module_dir("cycle", &[("ping", "use pong\nx = 1"), ("pong", "use ping\ny = 2")]);
+        let mut grease = Grease::new().with_module_path(&dir);
+        let result = grease.run("use ping").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.starts_with("ImportError: Circular import ping -> pong -> ping (use 'lazy use ping' in 'pong'")));
+        assert!(!grease.vm.modules.contains_key("ping") && !grease.vm.modules.contains_key("pong"));
+    }
+
+    #[test]
+    fn test_lazy_use_defers_binding_in_module_cycles() {
+        use crate::bytecode::Value;
+        let dir = module_dir("lazy_cycle", &[
+            ("even", "use odd\ndef is_even(n):\n    if n == 0:\n        return true\n    return odd.is_odd(n - 1)"),
+            ("odd", "lazy use even\ndef is_odd(n):\n    if n == 0:\n        return false\n    return even.is_even(n - 1)"),
+            ("eager", "lazy use early\nx = early.value"),
+            ("early", "use eager\nvalue = 1"),
+        ]);
+        let mut grease = Grease::new().with_module_path(&dir);
+        let result = grease.run("use even\nanswer = even.is_even(10)").unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("answer"), Some(Value::Boolean(true))));
+
+        // Reading a member of a module that hasn't finished loading isn't sound
+        let result = grease.run("use early").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("Module 'early' is still loading, so 'value' is not defined yet (import cycle early -> eager -> early)")));
+
+        let program = crate::parser::Parser::new(crate::lexer::Lexer::new("use early".to_string()).tokenize().unwrap()).parse().unwrap();
+        let resolver = modules::ModuleResolver::new(None).with_module_paths([&dir]);
+        assert_eq!(modules::find_import_cycle(&program, &resolver), None);
+        std::fs::write(dir.join("eager.grease"), "use early").unwrap();
+        let cycle = modules::find_import_cycle(&program, &resolver);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(cycle, Some(vec!["early".to_string(), "eager".to_string(), "early".to_string()]));
+    }
+
+    #[test]
+    fn test_lint_resolves_imports_like_the_runtime() {
+        let dir = module_dir("lint", &[
+            ("shapes", "use helpers\nPI = 3\ndef area(r):\n    return PI * r * r\nclass Square:\n    \"A square\""),
+            ("helpers", "def double(x):\n    return x * 2"),
+            ("loop_a", "use loop_b"),
+            ("loop_b", "use loop_a"),
+        ]);
+        let source = "use shapes\nfrom shapes use area, perimeter\nuse system\nuse missing\ntry:\n    use optional\ncatch:\n    print(\"no optional\")\n\
+            print(shapes.area(2), shapes.PI, shapes.helpers, shapes.volume(2), area(1), perimeter)\nuse loop_a";
+        let mut grease = Grease::new().with_script_dir(&dir);
+        let errors = grease.lint(source).unwrap();
+        let found: Vec<(&str, usize, usize, &str)> = errors.iter()
+            .map(|error| (error.rule, error.line, error.column, error.message.as_str()))
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(found, [
+            ("unknown-import", 2, 23, "Module 'shapes' has no member 'perimeter'"),
+            ("unresolved-import", 4, 5, "Module 'missing' not found"),
+            ("unknown-member", 9, 57, "Module 'shapes' has no member 'volume'"),
+            ("import-cycle", 10, 5, "Circular import loop_a -> loop_b -> loop_a"),
+        ]);
+    }
+
+    #[test]
+    fn test_missing_module_raises_catchable_import_error() {
+        use crate::bytecode::Value;
+        let dir = module_dir("optional", &[("present", "def hello():
+    return \"hi\"")]);
+        let mut grease = Grease::new().with_module_path(&dir);
+        let source = "\
+has_ui = module_exists(\"no_such_ui\")
+has_present = module_exists(\"present\")
+try:
+    use no_such_ui as ui
+    kind = \"loaded\"
+catch ImportError as e:
+    kind = \"missing\"
+lazy use present
+lazy use no_such_http as http
+greeting = present.hello()
+";
+        let result = grease.run(source).unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("has_ui"), Some(Value::Boolean(false))));
+        assert!(matches!(grease.vm.globals.get("has_present"), Some(Value::Boolean(true))));
+        assert!(matches!(grease.vm.globals.get("kind"), Some(Value::String(s)) if s == "missing"));
+        assert!(matches!(grease.vm.globals.get("greeting"), Some(Value::String(s)) if s == "hi"));
+        // A lazy import of a missing module only fails once it is used
+        assert!(!grease.vm.modules.contains_key("no_such_http"));
+        let result = grease.run("http.get(\"x\")").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let searched = dir.join("no_such_http.grease").display().to_string();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.starts_with("ImportError: Module 'no_such_http' not found") && e.contains(&searched)));
+    }
+
+    #[test]
+    fn test_evaluate_reports_value_and_warnings() {
+        use crate::bytecode::Value;
+        let mut grease = Grease::new();
+        let outcome = grease.evaluate("warn(\"deprecated\")\nx = 6\nx * 7").unwrap();
+        assert_eq!(outcome.result, InterpretResult::Ok);
+        assert!(matches!(outcome.value, Value::Number(n) if n == 42.0));
+        assert_eq!(outcome.warnings, vec!["deprecated".to_string()]);
+
+        // Statements have no value, and warnings don't leak into the next run
+        let outcome = grease.evaluate("y = 1").unwrap();
+        assert!(matches!(outcome.value, Value::Null));
+        assert!(outcome.warnings.is_empty());
+
+        let result: InterpretResult = grease.evaluate("undefined_name").unwrap().into();
+        assert!(matches!(result, InterpretResult::RuntimeError(_)));
+    }
+
+    #[test]
+    fn test_run_file_resolves_paths_against_script_directory() {
+        use crate::bytecode::Value;
+        let dir = module_dir("run_file", &[
+            ("main", "use helper\nfrom util use twice\nloaded = dotenv_load(\"settings.env\")\nvalue = twice(helper.base())"),
+            ("helper", "def base():\n    return 21"),
+        ]);
+        std::fs::create_dir_all(dir.join("modules")).unwrap();
+        std::fs::write(dir.join("modules").join("util.grease"), "def twice(x):\n    return x * 2").unwrap();
+        std::fs::write(dir.join("settings.env"), "GREASE_RUN_FILE_TEST_VALUE=1\n").unwrap();
+
+        let mut grease = Grease::new();
+        let result = grease.run_file(dir.join("main.grease")).unwrap();
+        let missing = Grease::new().run_file(dir.join("missing.grease")).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("value"), Some(Value::Number(n)) if *n == 42.0));
+        assert!(matches!(grease.vm.globals.get("loaded"), Some(Value::Number(n)) if *n == 1.0));
+        assert!(missing.starts_with("Failed to read"));
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_compiled_files_run_without_source() {
+        use crate::bytecode::Chunk;
+        let source = "use helper\nclass Box:\n    def get(self):\n        return helper.base()\ndef counter():\n    n = 0\n    def inc():\n        n = n + 1\n        return n\n    return inc\n\
+            c = counter()\nc()\nvalue = [new Box().get(), c(), b\"\\x00\", (1.5, null, true)]\nmissing";
+        let dir = module_dir("compiled", &[("main", source), ("helper", "def base():\n    return 21")]);
+        let bytes = Grease::new().compile_file(dir.join("main.grease")).unwrap();
+        assert!(Chunk::is_compiled(&bytes));
+        assert_eq!(Grease::new().compile_file(dir.join("main.grease")).unwrap(), bytes);
+        std::fs::write(dir.join("main.gbc"), &bytes).unwrap();
+        std::fs::remove_file(dir.join("main.grease")).unwrap();
+
+        let mut grease = Grease::new();
+        let outcome = grease.evaluate_file(dir.join("main.gbc")).unwrap();
+        assert!(matches!(&outcome.result, InterpretResult::RuntimeError(e) if e.contains("Undefined variable 'missing'")));
+        assert_eq!(outcome.error_location().map(|at| at.line), Some(14));
+        assert_eq!(crate::vm::VM::format_value(grease.vm.globals.get("value").unwrap()), "[21, 2, b\"\\x00\", (1.5, null, true)]");
+
+        // Bytecode older than the source next to it gives way to the source
+        std::fs::write(dir.join("main.grease"), "value = 7").unwrap();
+        assert!(!Chunk::is_current(&bytes, "value = 7"));
+        let mut grease = Grease::new();
+        assert_eq!(grease.evaluate_file(dir.join("main.gbc")).unwrap().result, InterpretResult::Ok);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(grease.vm.globals.get("value"), Some(Value::Number(n)) if *n == 7.0));
+        assert!(Chunk::is_current(&bytes, source));
+
+        assert!(Chunk::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err().contains("truncated"));
+        let mut other_version = bytes.clone();
+        other_version[4] += 1;
+        assert!(Chunk::from_bytes(&other_version).unwrap_err().contains("compile the source again"));
+        // A build whose opcodes differ can't load it either
+        let mut other_opcodes = bytes.clone();
+        other_opcodes[4 + 1 + 8 + crate::VERSION.len()] ^= 1;
+        assert!(Chunk::from_bytes(&other_opcodes).unwrap_err().contains("compile the source again"));
+
+        // One constant: an array in an array in ... far too deep to read
+        let header_len = 4 + 1 + 8 + crate::VERSION.len() + 32 + 32;
+        let mut nested = bytes[..header_len].to_vec();
+        nested.extend_from_slice(&0u64.to_le_bytes());
+        nested.extend_from_slice(&1u64.to_le_bytes());
+        for _ in 0..200_000 {
+            nested.push(8);
+            nested.extend_from_slice(&1u64.to_le_bytes());
+        }
+        nested.push(0);
+        assert!(Chunk::from_bytes(&nested).unwrap_err().contains("nests constants more than 256 deep"));
+    }
+
+    #[test]
+    fn test_builder_configures_io_modules_and_capabilities() {
+        use crate::bytecode::Value;
+        use crate::sandbox::Capabilities;
+        use std::io::Write;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+        impl Write for Buffer {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(bytes);
+                Ok(bytes.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Buffer::default();
+        let mut grease = Grease::new()
+            .with_stdout(Box::new(output.clone()))
+            .with_stdin(Box::new(std::io::Cursor::new("Ada\n")))
+            .with_native_module("geo", &[("double", 1, |_vm, args| match &args[0] {
+                Value::Number(n) => Ok(Value::Number(n * 2.0)),
+                _ => Err("double expects a number".to_string()),
+            })])
+            .with_capabilities(Capabilities { filesystem: false, ..Capabilities::default() });
+        let source = "\
+name = input(\"Name? \")
+print(\"Hello\", name)
+done = input(null)
+from geo use double
+use geo
+x = double(geo.double(5))
+try:
+    config_load(\"grease.toml\")
+catch IOError as e:
+    denied = e.message
+";
+        let result = grease.run(source).unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(String::from_utf8(output.0.lock().unwrap().clone()).unwrap(), "Name? Hello Ada\n");
+        assert!(matches!(grease.vm.globals.get("done"), Some(Value::Null)));
+        assert!(matches!(grease.vm.globals.get("x"), Some(Value::Number(n)) if *n == 20.0));
+        assert!(matches!(grease.vm.globals.get("denied"), Some(Value::String(s)) if s.contains("needs the filesystem capability")));
+    }
+
+    #[test]
+    fn test_reset_keeps_natives_and_configuration() {
+        use crate::bytecode::Value;
+        let dir = module_dir("reset", &[("counter", "count = 1")]);
+        let mut grease = Grease::new()
+            .with_module_path(&dir)
+            .with_native_module("geo", &[("double", 1, |_vm, args| match &args[0] {
+                Value::Number(n) => Ok(Value::Number(n * 2.0)),
+                _ => Err("double expects a number".to_string()),
+            })]);
+        grease.register_native("triple", 1, |_vm, args| match &args[0] {
+            Value::Number(n) => Ok(Value::Number(n * 3.0)),
+            _ => Err("triple expects a number".to_string()),
+        });
+        let source = "use counter\nleft = 1\nbytes_len = 0\nsystem_setenv(\"GREASE_RESET_TEST\", \"set\")\nopen = file_open(\"counter.grease\", \"r\")";
+        let source = source.replace("counter.grease", &dir.join("counter.grease").display().to_string());
+        assert_eq!(grease.run(&source).unwrap(), InterpretResult::Ok);
+        assert_eq!(grease.vm.resources.kinds(), vec!["File"]);
+
+        grease.reset().unwrap();
+        assert!(!grease.vm.globals.contains_key("left"));
+        assert!(!grease.vm.modules.contains_key("counter"));
+        assert!(grease.vm.resources.kinds().is_empty());
+        let source = "from geo use double\nx = [triple(double(1)), bytes_len(b\"ab\"), getenv(\"GREASE_RESET_TEST\")]";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert_eq!(crate::vm::VM::format_value(grease.vm.globals.get("x").unwrap()), "[6, 2, null]");
+        // Module paths are configuration, so modules still load
+        assert_eq!(grease.run("use counter\ny = counter.count").unwrap(), InterpretResult::Ok);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_with_bindings_returns_outputs_and_isolates_globals() {
+        use crate::bytecode::Value;
+        use std::collections::HashMap;
+        let mut grease = Grease::new();
+        grease.run("greeting = \"Hello\"").unwrap();
+
+        let mut bindings = HashMap::new();
+        bindings.insert("name".to_string(), Value::String("Ada".to_string()));
+        bindings.insert("port".to_string(), Value::Number(8080.0));
+        let source = "title = greeting + \", \" + name\nurl = \"http://localhost:\" + port\nport = port + 1\ndef helper():\n    local = 1\n    return local";
+        let outputs = grease.run_with_bindings(source, bindings).unwrap();
+
+        let mut names: Vec<&str> = outputs.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["helper", "port", "title", "url"]);
+        assert!(matches!(outputs.get("title"), Some(Value::String(s)) if s == "Hello, Ada"));
+        assert!(matches!(outputs.get("url"), Some(Value::String(s)) if s == "http://localhost:8080"));
+        assert!(matches!(outputs.get("port"), Some(Value::Number(n)) if *n == 8081.0));
+        for name in ["name", "port", "title"] {
+            assert!(!grease.vm.globals.contains_key(name));
+        }
+        assert!(grease.vm.globals.contains_key("greeting"));
+
+        let error = grease.run_with_bindings("missing + 1", HashMap::new()).unwrap_err();
+        assert!(error.contains("Undefined variable 'missing'"));
+    }
+}
@@ -2,19 +2,56 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::ast::*;
-use std::collections::HashMap;
+use crate::config::ProjectConfig;
+use crate::fix::{Edit, Fix};
+use crate::modules::{self, ModuleResolver};
+use crate::token::{Span, Token, TokenType};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone)]
 pub struct LintError {
+    /// Name of the rule that found it, e.g. `unused-variable`.
+    pub rule: &'static str,
     pub message: String,
     pub line: usize,
     pub column: usize,
+    /// A change that resolves the finding without changing what the code
+    /// does, when the rule knows one.
+    pub fix: Option<Fix>,
 }
 
 pub struct Linter {
     errors: Vec<LintError>,
     variables: HashMap<String, VariableInfo>,
     scope_depth: usize,
+    /// Functions the statement being linted is nested in.
+    function_depth: usize,
+    /// Statements in the block being linted.
+    block_len: usize,
+    check_unused_variables: bool,
+    /// Whether to flag risky `system.shell`, `system.exec` and
+    /// `system.setenv` calls (`[lint] security`).
+    check_security: bool,
+    /// Whether to suggest Grease's own ways over the `py` module's
+    /// (`[lint] python_compat`).
+    check_python_compat: bool,
+    /// Quote every string should use (`[lint] quotes`), if any.
+    quote_style: Option<char>,
+    /// Tokens and lines of the source being linted, for rules that look
+    /// past the AST; empty when linting just a program.
+    tokens: Vec<Token>,
+    lines: Vec<String>,
+    /// How often each name occurs in the source.
+    mentions: HashMap<String, usize>,
+    /// Where `use` finds modules; imports are only checked with one.
+    resolver: Option<ModuleResolver>,
+    /// File modules bound by `use`, by the name they're bound to, with the
+    /// names they define.
+    imports: HashMap<String, (String, HashSet<String>)>,
+    /// The module and position of each `use` linted so far.
+    uses: Vec<(String, usize, usize)>,
+    /// `try` blocks the statement being linted is in.
+    try_depth: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -22,6 +59,14 @@ struct VariableInfo {
     declared_at: (usize, usize),
     used: bool,
     scope_depth: usize,
+    /// Deletes the declaration if the variable turns out to be unused.
+    removal: Option<Fix>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Linter {
@@ -30,34 +75,119 @@ impl Linter {
             errors: Vec::new(),
             variables: HashMap::new(),
             scope_depth: 0,
+            function_depth: 0,
+            block_len: 0,
+            check_unused_variables: true,
+            check_security: true,
+            check_python_compat: true,
+            quote_style: None,
+            tokens: Vec::new(),
+            lines: Vec::new(),
+            mentions: HashMap::new(),
+            resolver: None,
+            imports: HashMap::new(),
+            uses: Vec::new(),
+            try_depth: 0,
         }
     }
 
+    /// Check `use` statements against the modules `resolver` finds: modules
+    /// that don't exist, imported names they don't define, and import
+    /// cycles.
+    pub fn with_resolver(mut self, resolver: ModuleResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Apply the `[lint]` section of a project's `grease.toml`.
+    pub fn with_config(mut self, config: &ProjectConfig) -> Self {
+        if let Some(enabled) = config.get_bool("lint", "unused_variables") {
+            self.check_unused_variables = enabled;
+        }
+        if let Some(enabled) = config.get_bool("lint", "security") {
+            self.check_security = enabled;
+        }
+        if let Some(enabled) = config.get_bool("lint", "python_compat") {
+            self.check_python_compat = enabled;
+        }
+        self.quote_style = match config.get_string("lint", "quotes") {
+            Some("double") => Some('"'),
+            Some("single") => Some('\''),
+            _ => None,
+        };
+        self
+    }
+
+    /// Lint `program`, parsed from `tokens` of `source`. Unlike [`lint`],
+    /// this also runs the rules that need the source and attaches fixes.
+    ///
+    /// [`lint`]: Linter::lint
+    pub fn lint_source(&mut self, source: &str, tokens: &[Token], program: &Program) -> Vec<LintError> {
+        self.tokens = tokens.to_vec();
+        self.lines = source.lines().map(str::to_string).collect();
+        self.mentions.clear();
+        for token in tokens {
+            if let TokenType::Identifier(name) = &token.token_type {
+                *self.mentions.entry(name.clone()).or_default() += 1;
+            }
+        }
+
+        let mut errors = self.lint(program);
+        if let Some(quote) = self.quote_style {
+            errors.extend(self.quote_errors(quote));
+        }
+        errors.sort_by_key(|error| (error.line, error.column));
+
+        self.tokens.clear();
+        self.lines.clear();
+        self.mentions.clear();
+        errors
+    }
+
     pub fn lint(&mut self, program: &Program) -> Vec<LintError> {
         self.errors.clear();
         self.variables.clear();
         self.scope_depth = 0;
+        self.function_depth = 0;
+        self.imports.clear();
+        self.uses.clear();
+        self.try_depth = 0;
 
         self.lint_program(program);
+        self.lint_import_cycle(program);
 
-        // Check for unused variables
+        self.report_unused(0);
+        self.errors.clone()
+    }
+
+    /// Report the unused variables declared at `depth` or deeper.
+    fn report_unused(&mut self, depth: usize) {
+        if !self.check_unused_variables {
+            return;
+        }
         for (name, info) in &self.variables {
-            if !info.used {
+            if info.scope_depth >= depth && !info.used {
                 self.errors.push(LintError {
+                    rule: "unused-variable",
                     message: format!("Unused variable '{}'", name),
                     line: info.declared_at.0,
                     column: info.declared_at.1,
+                    fix: info.removal.clone(),
                 });
             }
         }
-
-        self.errors.clone()
     }
 
     fn lint_program(&mut self, program: &Program) {
-        for statement in &program.statements {
+        self.lint_block(&program.statements);
+    }
+
+    fn lint_block(&mut self, statements: &[Statement]) {
+        let enclosing = std::mem::replace(&mut self.block_len, statements.len());
+        for statement in statements {
             self.lint_statement(statement);
         }
+        self.block_len = enclosing;
     }
 
     fn lint_statement(&mut self, statement: &Statement) {
@@ -72,17 +202,38 @@ impl Linter {
                     declared_at: (name.line, name.column),
                     used: false,
                     scope_depth: self.scope_depth,
+                    removal: self.removal(name, &var_name, initializer.as_ref()),
                 };
-                self.variables.insert(var_name, info);
+                // Assigning a declared variable again isn't a new declaration
+                self.variables.entry(var_name).or_insert(info);
 
                 if let Some(init) = initializer {
                     self.lint_expression(init);
                 }
             }
-            Statement::FunctionDeclaration { name: _, parameters, return_type: _, body } => {
+            Statement::Unpack { targets, value } => {
+                self.lint_expression(value);
+                for target in targets {
+                    if let crate::token::TokenType::Identifier(name) = &target.token_type {
+                        let info = VariableInfo {
+                            declared_at: (target.line, target.column),
+                            used: false,
+                            scope_depth: self.scope_depth,
+                            removal: None,
+                        };
+                        self.variables.insert(name.clone(), info);
+                    }
+                }
+            }
+            Statement::FunctionDeclaration { name: _, parameters, return_type: _, body, decorators } => {
+                for decorator in decorators {
+                    self.lint_expression(decorator);
+                }
                 self.scope_depth += 1;
+                self.function_depth += 1;
 
-                // Add parameters as variables in function scope
+                // Add parameters as variables in function scope; callers
+                // decide what's passed, so they're never reported unused
                 for (param, _) in parameters {
                     let param_name = match &param.token_type {
                         crate::token::TokenType::Identifier(s) => s.clone(),
@@ -90,45 +241,41 @@ impl Linter {
                     };
                     let info = VariableInfo {
                         declared_at: (param.line, param.column),
-                        used: false,
+                        used: true,
                         scope_depth: self.scope_depth,
+                        removal: None,
                     };
                     self.variables.insert(param_name, info);
                 }
 
-                for stmt in body {
-                    self.lint_statement(stmt);
-                }
+                self.lint_block(body);
 
-                // Remove variables from this scope
+                // Locals can't be used once the function ends
+                self.report_unused(self.scope_depth);
                 self.variables.retain(|_, info| info.scope_depth < self.scope_depth);
                 self.scope_depth -= 1;
+                self.function_depth -= 1;
             }
             Statement::If { condition, then_branch, else_branch } => {
                 self.lint_expression(condition);
                 self.scope_depth += 1;
-                for stmt in then_branch {
-                    self.lint_statement(stmt);
-                }
+                self.lint_block(then_branch);
                 self.scope_depth -= 1;
 
                 if let Some(else_stmts) = else_branch {
                     self.scope_depth += 1;
-                    for stmt in else_stmts {
-                        self.lint_statement(stmt);
-                    }
+                    self.lint_block(else_stmts);
                     self.scope_depth -= 1;
                 }
             }
             Statement::While { condition, body } => {
                 self.lint_expression(condition);
                 self.scope_depth += 1;
-                for stmt in body {
-                    self.lint_statement(stmt);
-                }
+                self.lint_block(body);
                 self.scope_depth -= 1;
             }
             Statement::For { variable, iterable, body } => {
+                self.lint_iterable(iterable);
                 self.lint_expression(iterable);
 
                 self.scope_depth += 1;
@@ -141,22 +288,26 @@ impl Linter {
                     declared_at: (variable.line, variable.column),
                     used: false,
                     scope_depth: self.scope_depth,
+                    removal: None,
                 };
                 self.variables.insert(var_name, info);
 
-                for stmt in body {
-                    self.lint_statement(stmt);
-                }
+                self.lint_block(body);
                 self.scope_depth -= 1;
             }
             Statement::Block(statements) => {
                 self.scope_depth += 1;
-                for stmt in statements {
-                    self.lint_statement(stmt);
-                }
+                self.lint_block(statements);
                 self.scope_depth -= 1;
             }
             Statement::Expression(expr) => {
+                if let Some((token, function)) = process_call(expr) {
+                    self.security_error(
+                        "unchecked-exit-code",
+                        format!("Result of {} is discarded; check its exit code", function),
+                        Some(token),
+                    );
+                }
                 self.lint_expression(expr);
             }
             Statement::Return { value } => {
@@ -164,9 +315,9 @@ impl Linter {
                     self.lint_expression(val);
                 }
             }
-            Statement::Use { module: _, alias: _ } => {
-                // Imports are handled elsewhere
-            }
+            Statement::Break | Statement::Continue => {}
+            Statement::Use { module, alias, names, .. } => self.lint_use(module, alias.as_deref(), names),
+            Statement::UsePlugin { .. } => self.uses.push(("plugin".to_string(), 0, 0)),
             Statement::ClassDeclaration { name, superclass: _, methods } => {
                 // Lint class name as variable
                 let class_name = match &name.token_type {
@@ -177,32 +328,69 @@ impl Linter {
                     declared_at: (name.line, name.column),
                     used: false,
                     scope_depth: self.scope_depth,
+                    removal: None,
                 };
                 self.variables.insert(class_name, info);
 
                  // Lint methods
-                 for method in methods {
-                     self.lint_statement(method);
-                 }
+                 self.lint_block(methods);
              }
-             Statement::Try { try_block, catch_block } => {
+             Statement::Try { try_block, catch_clauses, finally_block } => {
                  self.scope_depth += 1;
-                 for stmt in try_block {
-                     self.lint_statement(stmt);
-                 }
+                 self.try_depth += 1;
+                 self.lint_block(try_block);
+                 self.try_depth -= 1;
                  self.scope_depth -= 1;
 
-                 self.scope_depth += 1;
-                 for stmt in catch_block {
-                     self.lint_statement(stmt);
+                 for clause in catch_clauses {
+                     for exception_type in &clause.exception_types {
+                         self.lint_expression(exception_type);
+                     }
+                     self.scope_depth += 1;
+                     if let Some(name) = &clause.name {
+                         if let crate::token::TokenType::Identifier(var_name) = &name.token_type {
+                             let info = VariableInfo {
+                                 declared_at: (name.line, name.column),
+                                 used: false,
+                                 scope_depth: self.scope_depth,
+                                 removal: None,
+                             };
+                             self.variables.insert(var_name.clone(), info);
+                         }
+                     }
+                     self.lint_block(&clause.body);
+                     self.scope_depth -= 1;
+                 }
+
+                 if let Some(finally_block) = finally_block {
+                     self.scope_depth += 1;
+                     self.lint_block(finally_block);
+                     self.scope_depth -= 1;
                  }
-                 self.scope_depth -= 1;
              }
              Statement::Throw { value } => {
                  if let Some(val) = value {
                      self.lint_expression(val);
                  }
              }
+             Statement::With { manager, name, body } => {
+                 self.lint_expression(manager);
+
+                 self.scope_depth += 1;
+                 if let Some(name) = name {
+                     if let crate::token::TokenType::Identifier(var_name) = &name.token_type {
+                         let info = VariableInfo {
+                             declared_at: (name.line, name.column),
+                             used: false,
+                             scope_depth: self.scope_depth,
+                             removal: None,
+                         };
+                         self.variables.insert(var_name.clone(), info);
+                     }
+                 }
+                 self.lint_block(body);
+                 self.scope_depth -= 1;
+             }
              Statement::RustInline { code: _ } => {
                  // Inline Rust code doesn't need linting for now
              }
@@ -212,6 +400,202 @@ impl Linter {
         }
     }
 
+    /// Check a `use` against the module it loads. A missing module inside
+    /// `try` is taken to be optional.
+    fn lint_use(&mut self, module: &str, alias: Option<&str>, names: &[Token]) {
+        let (line, column) = self.use_position(module);
+        self.uses.push((module.to_string(), line, column));
+        let Some(resolver) = &self.resolver else { return };
+        if resolver.is_native(module) {
+            return;
+        }
+        let Some(program) = resolver.parse(module) else {
+            if resolver.resolve(module).is_none() && self.try_depth == 0 {
+                let message = format!("Module '{}' not found", module);
+                self.errors.push(LintError { rule: "unresolved-import", message, line, column, fix: None });
+            }
+            return;
+        };
+        let defined = modules::defined_names(&program.statements);
+        for name in names {
+            if !defined.contains(&name.lexeme) {
+                let message = format!("Module '{}' has no member '{}'", module, name.lexeme);
+                self.errors.push(LintError { rule: "unknown-import", message, line: name.line, column: name.column, fix: None });
+            }
+        }
+        if names.is_empty() {
+            self.imports.insert(alias.unwrap_or(module).to_string(), (module.to_string(), defined));
+        }
+    }
+
+    /// `module.member` where `module` was bound by `use` to a file that
+    /// doesn't define `member`.
+    fn lint_member(&mut self, object: &Expression, member: &Token) {
+        let Expression::Identifier(binding) = object else { return };
+        if self.variables.contains_key(&binding.lexeme) {
+            return;
+        }
+        if let Some((module, defined)) = self.imports.get(&binding.lexeme) {
+            if !defined.contains(&member.lexeme) {
+                let message = format!("Module '{}' has no member '{}'", module, member.lexeme);
+                self.errors.push(LintError { rule: "unknown-member", message, line: member.line, column: member.column, fix: None });
+            }
+        }
+    }
+
+    /// Modules that load each other while loading, reported at the `use`
+    /// the cycle is reached through.
+    fn lint_import_cycle(&mut self, program: &Program) {
+        let Some(cycle) = self.resolver.as_ref().and_then(|resolver| modules::find_import_cycle(program, resolver)) else { return };
+        let (_, line, column) = self.uses.iter()
+            .find(|(module, ..)| *module == cycle[0])
+            .or(self.uses.first())
+            .cloned()
+            .unwrap_or_default();
+        let message = format!("Circular import {}", cycle.join(" -> "));
+        self.errors.push(LintError { rule: "import-cycle", message, line: line.max(1), column: column.max(1), fix: None });
+    }
+
+    /// Where the next `use` of `module` names it. `use` statements don't
+    /// keep their position, but each has one `use` token, in order; without
+    /// the source's tokens it's the first line.
+    fn use_position(&self, module: &str) -> (usize, usize) {
+        let found = self.tokens.iter().enumerate()
+            .filter(|(_, token)| token.token_type == TokenType::Use)
+            .nth(self.uses.len())
+            .and_then(|(index, _)| {
+                // `use module` or `from module use ...`
+                [self.tokens.get(index + 1), index.checked_sub(1).and_then(|before| self.tokens.get(before))]
+                    .into_iter()
+                    .flatten()
+                    .find(|token| token.lexeme == module)
+            });
+        found.map_or((1, 1), |token| (token.line, token.column))
+    }
+
+    /// Security rules for calls to the process builtins.
+    fn lint_call(&mut self, expression: &Expression) {
+        let Some((token, function, arguments)) = system_call(expression) else { return };
+        match (function, arguments.first()) {
+            ("shell", Some(command)) if is_built_from_variables(command) => self.security_error(
+                "shell-injection",
+                "Command passed to system.shell is built from variables, so they can inject shell syntax; \
+                 pass the program and its arguments as a list to system.exec, or quote them with shell_quote"
+                    .to_string(),
+                Some(token),
+            ),
+            ("setenv", Some(Expression::String(name))) if SENSITIVE_VARIABLES.contains(&name.as_str()) => self.security_error(
+                "sensitive-env",
+                format!("Setting '{}' with system.setenv changes which programs and libraries child processes load", name),
+                Some(token),
+            ),
+            _ => {}
+        }
+    }
+
+    /// `py` functions with a builtin equivalent: `dict_get` for `py.get`,
+    /// and the builtins of the same name.
+    fn lint_py_call(&mut self, expression: &Expression) {
+        let Some((module, function, _)) = py_call(expression) else { return };
+        let Expression::MethodCall { method, .. } = expression else { return };
+        let (message, title, span, text) = match function {
+            "get" => ("py.get is the builtin dict_get".to_string(), "Use dict_get", module.span().to(method.span()), "dict_get"),
+            "enumerate" | "zip" | "sorted" | "min" | "max" | "sum" => {
+                let prefix = Span { line: module.line, column: module.column, end_line: method.line, end_column: method.column };
+                (format!("{} is a builtin", function), "Use the builtin", prefix, "")
+            }
+            _ => return,
+        };
+        let fix = Fix { title: title.to_string(), edits: vec![Edit { span, text: text.to_string() }] };
+        self.python_compat_error(message, module, Some(fix));
+    }
+
+    /// `for key in py.keys(d)` visits the keys in the same order as
+    /// `for key in d`.
+    fn lint_iterable(&mut self, iterable: &Expression) {
+        let Some((module, "keys", [dictionary])) = py_call(iterable) else { return };
+        let fix = match dictionary {
+            Expression::Identifier(name) => self.call_span(module).map(|span| Fix {
+                title: format!("Iterate over '{}'", name.lexeme),
+                edits: vec![Edit { span, text: name.lexeme.clone() }],
+            }),
+            _ => None,
+        };
+        self.python_compat_error("Iterating over a dictionary visits its keys; py.keys isn't needed".to_string(), module, fix);
+    }
+
+    /// The span of a one-argument `py.method(name)` call starting at
+    /// `module`, closing parenthesis included.
+    fn call_span(&self, module: &Token) -> Option<Span> {
+        let start = self.tokens.iter().position(|token| token.span() == module.span())?;
+        let call = self.tokens.get(start..start + 6)?;
+        let shape = (&call[1].token_type, &call[3].token_type, &call[5].token_type);
+        matches!(shape, (TokenType::Dot, TokenType::LeftParen, TokenType::RightParen)).then(|| module.span().to(call[5].span()))
+    }
+
+    fn python_compat_error(&mut self, message: String, at: &Token, fix: Option<Fix>) {
+        if self.check_python_compat {
+            self.errors.push(LintError { rule: "python-compat", message, line: at.line, column: at.column, fix });
+        }
+    }
+
+    fn security_error(&mut self, rule: &'static str, message: String, at: Option<&Token>) {
+        if let (true, Some(at)) = (self.check_security, at) {
+            self.errors.push(LintError { rule, message, line: at.line, column: at.column, fix: None });
+        }
+    }
+
+    /// A fix deleting the declaration of `name` at `target` if that can't
+    /// change what the program does: it declares a function local (globals
+    /// may be read by scripts that import this one), nothing else in the
+    /// source mentions the name, the value is a literal, the declaration has
+    /// its lines to itself and removing it doesn't empty a block.
+    fn removal(&self, target: &Token, name: &str, initializer: Option<&Expression>) -> Option<Fix> {
+        if self.function_depth == 0 || self.block_len < 2 {
+            return None;
+        }
+        if self.mentions.get(name) != Some(&1) || !initializer.is_some_and(is_literal) {
+            return None;
+        }
+        let start = self.tokens.iter().position(|token| token.span() == target.span())?;
+        let line_break = |token: &Token| matches!(token.token_type, TokenType::Newline | TokenType::Indent | TokenType::Dedent);
+        if start > 0 && !line_break(&self.tokens[start - 1]) {
+            return None;
+        }
+        let end = start + self.tokens[start..].iter().position(|token| {
+            line_break(token) || matches!(token.token_type, TokenType::Semicolon | TokenType::EOF)
+        })?;
+        if self.tokens[end].token_type == TokenType::Semicolon {
+            return None;
+        }
+        let last = &self.tokens[end - 1];
+        let span = Span { line: target.line, column: 1, end_line: last.end_line + 1, end_column: 1 };
+        Some(Fix { title: format!("Remove unused variable '{}'", name), edits: vec![Edit { span, text: String::new() }] })
+    }
+
+    /// Strings not written with `quote`, where switching to it needs no
+    /// escaping.
+    fn quote_errors(&self, quote: char) -> Vec<LintError> {
+        let name = if quote == '"' { "double" } else { "single" };
+        self.tokens.iter()
+            .filter(|token| matches!(token.token_type, TokenType::String(_)))
+            .filter(|token| {
+                let written = self.lines.get(token.line - 1).and_then(|line| line.chars().nth(token.column - 1));
+                written.is_some_and(|written| written != quote) && !token.lexeme.contains(quote)
+            })
+            .map(|token| LintError {
+                rule: "quotes",
+                message: format!("Use {} quotes for strings", name),
+                line: token.line,
+                column: token.column,
+                fix: Some(Fix {
+                    title: format!("Use {} quotes", name),
+                    edits: vec![Edit { span: token.span(), text: format!("{}{}{}", quote, token.lexeme, quote) }],
+                }),
+            })
+            .collect()
+    }
+
     fn lint_expression(&mut self, expression: &Expression) {
         match expression {
             Expression::Identifier(token) => {
@@ -243,6 +627,7 @@ impl Linter {
                 self.lint_expression(value);
             }
             Expression::Call { callee, arguments } => {
+                self.lint_call(expression);
                 self.lint_expression(callee);
                 for arg in arguments {
                     self.lint_expression(arg);
@@ -260,20 +645,35 @@ impl Linter {
             Expression::Grouping(expr) => {
                 self.lint_expression(expr);
             }
+            Expression::Tuple(elements) => {
+                for element in elements {
+                    self.lint_expression(element);
+                }
+            }
             Expression::Index { array, index } => {
                 self.lint_expression(array);
                 self.lint_expression(index);
             }
+            Expression::Slice { array, start, end } => {
+                self.lint_expression(array);
+                for bound in [start, end].into_iter().flatten() {
+                    self.lint_expression(bound);
+                }
+            }
             Expression::NewInstance { class, arguments } => {
                 self.lint_expression(class);
                 for arg in arguments {
                     self.lint_expression(arg);
                 }
             }
-            Expression::PropertyAccess { object, property: _ } => {
+            Expression::PropertyAccess { object, property } => {
+                self.lint_member(object, property);
                 self.lint_expression(object);
             }
-            Expression::MethodCall { object, method: _, arguments } => {
+            Expression::MethodCall { object, method, arguments } => {
+                self.lint_member(object, method);
+                self.lint_call(expression);
+                self.lint_py_call(expression);
                 self.lint_expression(object);
                 for arg in arguments {
                     self.lint_expression(arg);
@@ -290,9 +690,87 @@ impl Linter {
             Expression::AsmInline { code: _ } => {
                 // Inline assembly code doesn't need linting for now
             }
-            Expression::Number(_) | Expression::String(_) | Expression::Boolean(_) | Expression::Null | Expression::Array(_) | Expression::Dictionary(_) => {
+            Expression::Number(_) | Expression::String(_) | Expression::Bytes(_) | Expression::Boolean(_) | Expression::Null | Expression::Array(_) | Expression::Dictionary(_) => {
                 // Literals don't need linting
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Environment variables that decide what child processes run or load.
+const SENSITIVE_VARIABLES: &[&str] = &[
+    "PATH", "LD_PRELOAD", "LD_LIBRARY_PATH", "DYLD_INSERT_LIBRARIES", "DYLD_LIBRARY_PATH",
+    "IFS", "BASH_ENV", "ENV", "SHELL", "PYTHONPATH", "NODE_OPTIONS",
+];
+
+/// The builtin `expression` calls, if it runs a process and returns how the
+/// process exited.
+fn process_call(expression: &Expression) -> Option<(&Token, &'static str)> {
+    match system_call(expression)? {
+        (token, "shell", _) => Some((token, "system.shell")),
+        (token, "exec", _) => Some((token, "system.exec")),
+        _ => None,
+    }
+}
+
+/// The first token, member name and arguments of a call to a `system`
+/// native, as `system.member(...)` or by its deprecated flat name
+/// `system_member(...)`.
+fn system_call(expression: &Expression) -> Option<(&Token, &str, &[Expression])> {
+    let (token, member, arguments) = match expression {
+        Expression::Call { callee, arguments } => match callee.as_ref() {
+            Expression::Identifier(token) => match &token.token_type {
+                TokenType::Identifier(name) => (token, name.strip_prefix("system_")?, arguments),
+                _ => return None,
+            },
+            _ => return None,
+        },
+        Expression::MethodCall { object, method, arguments } => match (object.as_ref(), &method.token_type) {
+            (Expression::Identifier(token), TokenType::Identifier(member)) if token.lexeme == "system" => (token, member.as_str(), arguments),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((token, member, arguments.as_slice()))
+}
+
+/// The `py` token, method name and arguments of a `py.method(...)` call.
+fn py_call(expression: &Expression) -> Option<(&Token, &str, &[Expression])> {
+    let Expression::MethodCall { object, method, arguments } = expression else { return None };
+    let Expression::Identifier(module) = object.as_ref() else { return None };
+    match (&module.token_type, &method.token_type) {
+        (TokenType::Identifier(name), TokenType::Identifier(method)) if name == "py" => Some((module, method, arguments)),
+        _ => None,
+    }
+}
+
+/// Whether `expression` concatenates strings with something that isn't a
+/// literal.
+fn is_built_from_variables(expression: &Expression) -> bool {
+    fn has_variable_part(expression: &Expression) -> bool {
+        match expression {
+            Expression::Binary { left, operator, right } if operator.token_type == TokenType::Plus => {
+                has_variable_part(left) || has_variable_part(right)
+            }
+            Expression::Grouping(inner) => has_variable_part(inner),
+            other => !is_literal(other),
+        }
+    }
+    match expression {
+        Expression::Binary { operator, .. } if operator.token_type == TokenType::Plus => has_variable_part(expression),
+        Expression::Grouping(inner) => is_built_from_variables(inner),
+        _ => false,
+    }
+}
+
+/// Whether evaluating `expression` can't fail or have side effects.
+fn is_literal(expression: &Expression) -> bool {
+    match expression {
+        Expression::Number(_) | Expression::String(_) | Expression::Bytes(_) | Expression::Boolean(_) | Expression::Null => true,
+        Expression::Unary { right, .. } => matches!(right.as_ref(), Expression::Number(_) | Expression::Boolean(_)),
+        Expression::Grouping(inner) => is_literal(inner),
+        Expression::Array(elements) | Expression::Tuple(elements) => elements.iter().all(is_literal),
+        Expression::Dictionary(pairs) => pairs.iter().all(|(key, value)| is_literal(key) && is_literal(value)),
+        _ => false,
+    }
+}
@@ -7,7 +7,7 @@ use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
-use crate::lsp_workspace::Workspace;
+use crate::lsp_workspace::{span_to_range, Workspace};
 
 pub struct GreaseLanguageServer {
     client: Client,
@@ -42,6 +42,7 @@ impl LanguageServer for GreaseLanguageServer {
             document_symbol_provider: Some(OneOf::Left(true)),
             workspace_symbol_provider: Some(OneOf::Left(true)),
             hover_provider: Some(HoverProviderCapability::Simple(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
             semantic_tokens_provider: Some(
                 SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
                     work_done_progress_options: Default::default(),
@@ -229,6 +230,7 @@ impl LanguageServer for GreaseLanguageServer {
         Ok(None)
     }
 
+    #[allow(deprecated)]
     async fn symbol(&self, params: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
         let workspace = self.workspace.lock().await;
         let mut symbols = Vec::new();
@@ -266,7 +268,8 @@ impl LanguageServer for GreaseLanguageServer {
         };
 
         if let Some(word) = word {
-            let hover_text = get_hover_text(&word);
+            let hover_text = get_hover_text(&word)
+                .or_else(|| crate::docs::builtin(&word).map(|doc| crate::docs::hover_markdown(&word, &doc)));
             if let Some(text) = hover_text {
                 return Ok(Some(Hover {
                     contents: HoverContents::Scalar(MarkedString::String(text)),
@@ -278,6 +281,13 @@ impl LanguageServer for GreaseLanguageServer {
         Ok(None)
     }
 
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let actions = self.workspace.lock().await.get_document(&params.text_document.uri)
+            .map(|doc| doc.code_actions(params.range))
+            .unwrap_or_default();
+        Ok(if actions.is_empty() { None } else { Some(actions) })
+    }
+
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
@@ -336,8 +346,11 @@ fn get_hover_text(word: &str) -> Option<String> {
         "if" => Some("if - Conditional statement\n\n```grease\nif condition:\n    # code to execute if condition is true\n```".to_string()),
         "while" => Some("while - Loop while condition is true\n\n```grease\nwhile condition:\n    # code to execute in each iteration\n```".to_string()),
         "for" => Some("for - Loop over iterable\n\n```grease\nfor item in iterable:\n    # code to execute for each item\n```".to_string()),
-        "use" => Some("use - Import a module\n\n```grease\nuse math\nuse string as str\n```".to_string()),
+        "break" => Some("break - Leave the innermost loop\n\n```grease\nfor item in items:\n    if item == target:\n        break\n```".to_string()),
+        "continue" => Some("continue - Skip to the next iteration of the innermost loop\n\n```grease\nfor item in items:\n    if item == null:\n        continue\n```".to_string()),
+        "use" | "from" => Some("use - Import a module, or selected members with `from`\n\n```grease\nuse math\nuse string as str\nfrom math use add, sqrt\nuse plugin \"libfoo.so\"\n```".to_string()),
         "return" => Some("return - Return a value from a function\n\n```grease\nreturn value\n```".to_string()),
+        "with" => Some("with - Use a context manager; `__exit__` runs however the block is left\n\n```grease\nwith manager as value:\n    # code using value\n```".to_string()),
         "and" | "or" | "not" => Some(format!("{} - Boolean operator", word)),
         "true" | "false" => Some(format!("{} - Boolean literal", word)),
         "null" => Some("null - Null value".to_string()),
@@ -345,6 +358,7 @@ fn get_hover_text(word: &str) -> Option<String> {
     }
 }
 
+#[allow(deprecated)]
 fn extract_document_symbols(ast: &crate::ast::Program, _uri: &Url) -> Vec<DocumentSymbol> {
     let mut symbols = Vec::new();
     
@@ -362,8 +376,8 @@ fn extract_document_symbols(ast: &crate::ast::Program, _uri: &Url) -> Vec<Docume
                                 detail: Some("parameter".to_string()),
                                 kind: SymbolKind::VARIABLE,
                                 tags: None,
-                                range: Range::default(),
-                                selection_range: Range::default(),
+                                range: span_to_range(param.span()),
+                                selection_range: span_to_range(param.span()),
                                 children: None,
                                 deprecated: None,
 
@@ -381,8 +395,8 @@ fn extract_document_symbols(ast: &crate::ast::Program, _uri: &Url) -> Vec<Docume
                         detail: Some(format!("function({})", parameters.len())),
                         kind: SymbolKind::FUNCTION,
                         tags: None,
-                        range: Range::default(),
-                        selection_range: Range::default(),
+                        range: statement_range(statement, name),
+                        selection_range: span_to_range(name.span()),
                         children: Some(children),
                         deprecated: None,
                     });
@@ -395,8 +409,8 @@ fn extract_document_symbols(ast: &crate::ast::Program, _uri: &Url) -> Vec<Docume
                         detail: type_annotation.clone(),
                         kind: SymbolKind::VARIABLE,
                         tags: None,
-                        range: Range::default(),
-                        selection_range: Range::default(),
+                        range: statement_range(statement, name),
+                        selection_range: span_to_range(name.span()),
                         children: None,
                         deprecated: None,
                     });
@@ -409,6 +423,7 @@ fn extract_document_symbols(ast: &crate::ast::Program, _uri: &Url) -> Vec<Docume
     symbols
 }
 
+#[allow(deprecated)]
 fn extract_symbols_from_statement(stmt: &crate::ast::Statement, symbols: &mut Vec<DocumentSymbol>) {
     match stmt {
         crate::ast::Statement::VariableDeclaration { name, type_annotation, .. } => {
@@ -418,8 +433,8 @@ fn extract_symbols_from_statement(stmt: &crate::ast::Statement, symbols: &mut Ve
                     detail: type_annotation.clone(),
                     kind: SymbolKind::VARIABLE,
                     tags: None,
-                    range: Range::default(),
-                    selection_range: Range::default(),
+                    range: statement_range(stmt, name),
+                    selection_range: span_to_range(name.span()),
                     children: None,
                     deprecated: None,
                 });
@@ -436,8 +451,8 @@ fn extract_symbols_from_statement(stmt: &crate::ast::Statement, symbols: &mut Ve
                                 detail: Some("parameter".to_string()),
                                 kind: SymbolKind::VARIABLE,
                                 tags: None,
-                                range: Range::default(),
-                                selection_range: Range::default(),
+                                range: span_to_range(param.span()),
+                                selection_range: span_to_range(param.span()),
                                 children: None,
                                 deprecated: None,
 
@@ -454,8 +469,8 @@ fn extract_symbols_from_statement(stmt: &crate::ast::Statement, symbols: &mut Ve
                     detail: Some(format!("function({})", parameters.len())),
                     kind: SymbolKind::FUNCTION,
                     tags: None,
-                    range: Range::default(),
-                    selection_range: Range::default(),
+                    range: statement_range(stmt, name),
+                    selection_range: span_to_range(name.span()),
                     children: None,
                     deprecated: None,
                 });
@@ -470,23 +485,31 @@ fn extract_symbols_from_statement(stmt: &crate::ast::Statement, symbols: &mut Ve
     }
 }
 
+/// The range of a symbol's whole declaration, falling back to its name.
+fn statement_range(statement: &crate::ast::Statement, name: &crate::token::Token) -> Range {
+    span_to_range(statement.span().unwrap_or_else(|| name.span()))
+}
+
 fn extract_semantic_tokens(_ast: &crate::ast::Program) -> Vec<SemanticToken> {
-    let mut tokens = Vec::new();
-    
     // This is a simplified implementation
     // In a full implementation, you'd walk the AST and generate semantic tokens
     // with proper line/column information
     
     // Example: add some basic tokens
-    tokens.push(SemanticToken {
+    vec![SemanticToken {
         delta_line: 0,
         delta_start: 0,
         length: 3,
         token_type: 1, // KEYWORD
         token_modifiers_bitset: 0,
-    });
-    
-    tokens
+    }]
+}
+
+/// Serve LSP over stdin/stdout on a new Tokio runtime until the client
+/// disconnects, for callers that don't run an async runtime themselves.
+pub fn serve_stdio() -> std::io::Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(run_server()).map_err(|e| std::io::Error::other(e.to_string()))
 }
 
 pub async fn run_server() -> Result<()> {
@@ -2,12 +2,48 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::collections::HashMap;
+use std::path::Path;
 use dashmap::DashMap;
 use ropey::Rope;
 use tower_lsp::lsp_types::*;
 use crate::ast::{Program, Statement};
-use crate::lexer::Lexer;
+use crate::config::ProjectConfig;
+use crate::fix::{Edit, Fix};
+use crate::lexer::{Lexer, DEFAULT_TAB_WIDTH};
+use crate::linter::Linter;
+use crate::modules::ModuleResolver;
 use crate::parser::Parser;
+use crate::token::Span;
+
+/// The LSP range of `span`. LSP lines and characters count from 0.
+pub(crate) fn span_to_range(span: Span) -> Range {
+    let position = |line: usize, column: usize| Position::new(line.saturating_sub(1) as u32, column.saturating_sub(1) as u32);
+    Range {
+        start: position(span.line, span.column),
+        end: position(span.end_line, span.end_column),
+    }
+}
+
+fn error_diagnostic(span: Span, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Some(DiagnosticSeverity::ERROR),
+        ..warning_diagnostic(span, message)
+    }
+}
+
+fn warning_diagnostic(span: Span, message: String) -> Diagnostic {
+    Diagnostic {
+        range: span_to_range(span),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: None,
+        code_description: None,
+        source: Some("grease-lsp".to_string()),
+        message,
+        related_information: None,
+        tags: None,
+        data: None,
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Document {
@@ -17,6 +53,8 @@ pub struct Document {
     pub language_id: String,
     pub ast: Option<Program>,
     pub diagnostics: Vec<Diagnostic>,
+    /// Fixes for the diagnostics, offered as quick fixes.
+    pub fixes: Vec<Fix>,
 }
 
 impl Document {
@@ -29,6 +67,7 @@ impl Document {
             language_id,
             ast: None,
             diagnostics: Vec::new(),
+            fixes: Vec::new(),
         }
     }
 
@@ -37,41 +76,79 @@ impl Document {
         self.version = version;
         self.ast = None;
         self.diagnostics.clear();
+        self.fixes.clear();
     }
 
     pub fn parse(&mut self) -> Result<(), String> {
         let source = self.text.to_string();
-        let mut lexer = Lexer::new(source);
-        let tokens = lexer.tokenize()?;
-        let mut parser = Parser::new(tokens);
+        let config = self.uri.to_file_path().ok()
+            .and_then(|path| ProjectConfig::discover(&path))
+            .and_then(|config| ProjectConfig::load(&config).ok());
+        let tab_width = config.as_ref().and_then(|config| config.tab_width()).unwrap_or(DEFAULT_TAB_WIDTH);
+        let edition = config.as_ref().and_then(|config| config.edition()).unwrap_or_default();
+        let mut lexer = Lexer::new(source.clone()).with_tab_width(tab_width).with_edition(edition);
+        let tokens = match lexer.tokenize() {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                self.diagnostics.push(error_diagnostic(lexer.span(), e.clone()));
+                return Err(e);
+            }
+        };
+        let mut parser = Parser::new(tokens.clone());
         
         match parser.parse() {
             Ok(program) => {
-                self.ast = Some(program);
                 self.diagnostics.clear();
+                let script_dir = self.uri.to_file_path().ok().and_then(|path| path.parent().map(Path::to_path_buf));
+                let linter = Linter::new().with_resolver(ModuleResolver::new(script_dir.as_deref()));
+                let mut linter = match &config {
+                    Some(config) => linter.with_config(config),
+                    None => linter,
+                };
+                for error in linter.lint_source(&source, &tokens, &program) {
+                    let span = Span { line: error.line, column: error.column, end_line: error.line, end_column: error.column + 1 };
+                    self.diagnostics.push(warning_diagnostic(span, error.message));
+                    self.fixes.extend(error.fix);
+                }
+                self.ast = Some(program);
                 Ok(())
             }
             Err(e) => {
-                let error_msg = e.clone();
-                self.diagnostics.push(Diagnostic {
-                    range: Range {
-                        start: Position::new(0, 0),
-                        end: Position::new(0, 0),
-                    },
-                    severity: Some(DiagnosticSeverity::ERROR),
-                    code: None,
-                    code_description: None,
-                    source: Some("grease-lsp".to_string()),
-                    message: error_msg.clone(),
-                    related_information: None,
-                    tags: None,
-                    data: None,
-                });
+                let span = parser.span().unwrap_or(Span { line: 1, column: 1, end_line: 1, end_column: 1 });
+                self.diagnostics.push(error_diagnostic(span, e.clone()));
+                self.fixes.extend(crate::fix::missing_colon(&tokens, &e, span));
                 Err(e)
             }
         }
     }
 
+    /// Quick fixes with an edit touching `range`.
+    pub fn code_actions(&self, range: Range) -> Vec<CodeActionOrCommand> {
+        let touches = |edit: &Edit| {
+            let edit = span_to_range(edit.span);
+            edit.start <= range.end && range.start <= edit.end
+        };
+        self.fixes.iter()
+            .filter(|fix| fix.edits.iter().any(touches))
+            .map(|fix| {
+                let edits = fix.edits.iter()
+                    .map(|edit| TextEdit { range: span_to_range(edit.span), new_text: edit.text.clone() })
+                    .collect();
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.title.clone(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(self.uri.clone(), edits)])),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    /// An error on the `use` that starts a chain of modules eagerly using
+    /// each other, resolved from the document's directory.
     pub fn get_diagnostics(&self) -> Vec<Diagnostic> {
         self.diagnostics.clone()
     }
@@ -88,7 +165,7 @@ pub struct Symbol {
     pub children: Vec<Symbol>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Workspace {
     pub documents: DashMap<Url, Document>,
     pub symbols: HashMap<String, Vec<Symbol>>,
@@ -96,10 +173,7 @@ pub struct Workspace {
 
 impl Workspace {
     pub fn new() -> Self {
-        Self {
-            documents: DashMap::new(),
-            symbols: HashMap::new(),
-        }
+        Self::default()
     }
 
     pub fn get_document(&self, uri: &Url) -> Option<Document> {
@@ -275,12 +349,7 @@ impl Workspace {
     }
 
     fn token_to_range(&self, token: &crate::token::Token) -> Range {
-        // Convert token position to LSP range
-        // This is a simplified implementation - you'd need to map line/column properly
-        Range {
-            start: Position::new((token.line - 1) as u32, 0),
-            end: Position::new((token.line - 1) as u32, 100), // Simplified end position
-        }
+        span_to_range(token.span())
     }
 
     pub fn find_definitions(&self, name: &str, _uri: &Url, _position: Position) -> Vec<Location> {
@@ -299,29 +368,20 @@ impl Workspace {
     }
 
     pub fn find_references(&self, _name: &str, _uri: &Url, _position: Position) -> Vec<Location> {
-        let locations = Vec::new();
-        
         // This is a simplified implementation
         // In a full implementation, you'd need to:
         // 1. Parse all documents to find identifier references
         // 2. Filter by the symbol at the given position
         // 3. Return all locations where this symbol is referenced
         
-        locations
+        Vec::new()
     }
 
     pub fn get_completions(&self, uri: &Url, _position: Position) -> Vec<CompletionItem> {
         let mut completions = Vec::new();
         
         // Add language keywords
-        let keywords = vec![
-            "def", "if", "elif", "else", "while", "for", "in",
-            "return", "use", "as", "true", "false", "null",
-            "class", "new", "self", "super",
-            "and", "or", "not"
-        ];
-        
-        for keyword in keywords {
+        for (keyword, _) in crate::lexer::KEYWORDS {
             completions.push(CompletionItem {
                 label: keyword.to_string(),
                 kind: Some(CompletionItemKind::KEYWORD),
@@ -0,0 +1,348 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Methods on built-in values, which `CallMethod` looks up here when the
+//! receiver isn't an object or module:
+//!
+//! ```text
+//! words = "a, b, c".split(", ")
+//! print(", ".join(words).upper())
+//! words.push("d")
+//!
+//! def length(word):
+//!     return word.len()
+//!
+//! print(words.map(length))
+//! ```
+//!
+//! A method that changes an array or dictionary, like `words.push("d")`,
+//! changes it in place, for every variable holding it. Strings don't
+//! change; string methods return a new string.
+//!
+//! Methods that call back into the script, like `map`, work on a copy of
+//! the elements taken first, so the function can use the array freely.
+
+use crate::bytecode::{Arity, Shared, Value};
+use crate::vm::{range_len, VM};
+use std::collections::HashMap;
+
+type StringMethod = fn(&str, &[Value]) -> Result<Value, String>;
+type ArrayMethod = fn(&mut VM, &Shared<Vec<Value>>, Vec<Value>) -> Result<Value, String>;
+type DictionaryMethod = fn(&mut HashMap<String, Value>, Vec<Value>) -> Result<Value, String>;
+type RangeMethod = fn(&Value, &[Value]) -> Result<Value, String>;
+
+const STRING_METHODS: &[(&str, Arity, StringMethod)] = &[
+    ("split", Arity { min: 0, max: 1 }, split),
+    ("join", Arity { min: 1, max: 1 }, join),
+    ("upper", Arity { min: 0, max: 0 }, |s, _| Ok(Value::String(s.to_uppercase()))),
+    ("lower", Arity { min: 0, max: 0 }, |s, _| Ok(Value::String(s.to_lowercase()))),
+    ("trim", Arity { min: 0, max: 0 }, |s, _| Ok(Value::String(s.trim().to_string()))),
+    ("replace", Arity { min: 2, max: 2 }, replace),
+    ("contains", Arity { min: 1, max: 1 }, |s, args| Ok(Value::Boolean(s.contains(string_argument("contains", &args[0])?)))),
+    ("starts_with", Arity { min: 1, max: 1 }, |s, args| Ok(Value::Boolean(s.starts_with(string_argument("starts_with", &args[0])?)))),
+    ("ends_with", Arity { min: 1, max: 1 }, |s, args| Ok(Value::Boolean(s.ends_with(string_argument("ends_with", &args[0])?)))),
+    ("find", Arity { min: 1, max: 1 }, find),
+    ("len", Arity { min: 0, max: 0 }, |s, _| Ok(Value::Number(s.chars().count() as f64))),
+    ("chars", Arity { min: 0, max: 0 }, |s, _| Ok(Value::array(s.chars().map(|c| Value::String(c.to_string())).collect()))),
+];
+
+const ARRAY_METHODS: &[(&str, Arity, ArrayMethod)] = &[
+    ("push", Arity { min: 1, max: 1 }, |_, items, args| {
+        items.lock().unwrap().extend(args);
+        Ok(Value::Null)
+    }),
+    ("pop", Arity { min: 0, max: 0 }, |_, items, _| items.lock().unwrap().pop().ok_or_else(|| "pop from an empty array".to_string())),
+    ("insert", Arity { min: 2, max: 2 }, insert),
+    ("remove", Arity { min: 1, max: 1 }, remove),
+    ("sort", Arity { min: 0, max: 1 }, sort),
+    ("reverse", Arity { min: 0, max: 0 }, |_, items, _| {
+        items.lock().unwrap().reverse();
+        Ok(Value::Null)
+    }),
+    ("map", Arity { min: 1, max: 1 }, map),
+    ("filter", Arity { min: 1, max: 1 }, filter),
+    ("reduce", Arity { min: 1, max: 2 }, reduce),
+    ("contains", Arity { min: 1, max: 1 }, |_, items, args| {
+        let items = items.lock().unwrap().clone();
+        Ok(Value::Boolean(items.iter().any(|item| VM::values_equal(item, &args[0]))))
+    }),
+    ("len", Arity { min: 0, max: 0 }, |_, items, _| Ok(Value::Number(items.lock().unwrap().len() as f64))),
+];
+
+/// Dictionary methods list entries in the order `for` visits the keys.
+const DICTIONARY_METHODS: &[(&str, Arity, DictionaryMethod)] = &[
+    ("keys", Arity { min: 0, max: 0 }, |entries, _| Ok(Value::array(sorted_keys(entries).into_iter().map(|key| Value::String(key.clone())).collect()))),
+    ("values", Arity { min: 0, max: 0 }, |entries, _| Ok(Value::array(sorted_keys(entries).into_iter().map(|key| entries[key].clone()).collect()))),
+    ("items", Arity { min: 0, max: 0 }, |entries, _| {
+        let items = sorted_keys(entries).into_iter().map(|key| Value::Tuple(vec![Value::String(key.clone()), entries[key].clone()]));
+        Ok(Value::array(items.collect()))
+    }),
+    ("get", Arity { min: 1, max: 2 }, |entries, args| {
+        let found = entries.get(key_argument("get", &args[0])?).cloned();
+        Ok(found.or_else(|| args.get(1).cloned()).unwrap_or(Value::Null))
+    }),
+    ("has_key", Arity { min: 1, max: 1 }, |entries, args| Ok(Value::Boolean(entries.contains_key(key_argument("has_key", &args[0])?)))),
+    ("delete", Arity { min: 1, max: 1 }, |entries, args| Ok(entries.remove(key_argument("delete", &args[0])?).unwrap_or(Value::Null))),
+    ("len", Arity { min: 0, max: 0 }, |entries, _| Ok(Value::Number(entries.len() as f64))),
+];
+
+/// Range methods answer without making the numbers, except `to_array`.
+const RANGE_METHODS: &[(&str, Arity, RangeMethod)] = &[
+    ("len", Arity { min: 0, max: 0 }, |range, _| match *range {
+        Value::Range { start, stop, step } => Ok(Value::Number(range_len(start, stop, step) as f64)),
+        _ => unreachable!("range method called on {}", VM::format_value(range)),
+    }),
+    ("contains", Arity { min: 1, max: 1 }, range_contains),
+    ("to_array", Arity { min: 0, max: 0 }, |range, _| VM::elements(range).map(Value::array)),
+];
+
+/// Call the built-in method `name` of `receiver`.
+pub fn call(vm: &mut VM, receiver: Value, name: &str, args: Vec<Value>) -> Result<Value, String> {
+    match receiver {
+        Value::String(s) => lookup("string", STRING_METHODS, name, args.len())?(&s, &args),
+        Value::Array(items) => lookup("array", ARRAY_METHODS, name, args.len())?(vm, &items, args),
+        Value::Dictionary(entries) => lookup("dictionary", DICTIONARY_METHODS, name, args.len())?(&mut entries.lock().unwrap(), args),
+        range @ Value::Range { .. } => lookup("range", RANGE_METHODS, name, args.len())?(&range, &args),
+        _ => Err("TypeError: Expected object".to_string()),
+    }
+}
+
+/// Whether `args[0]` is one of the numbers the range counts through.
+fn range_contains(range: &Value, args: &[Value]) -> Result<Value, String> {
+    let (&Value::Range { start, stop, step }, Value::Number(n)) = (range, &args[0]) else {
+        return Ok(Value::Boolean(false));
+    };
+    let index = (n - start) / step;
+    Ok(Value::Boolean(index >= 0.0 && index.fract() == 0.0 && (index as u128) < range_len(start, stop, step)))
+}
+
+fn lookup<F: Copy>(kind: &str, methods: &[(&str, Arity, F)], name: &str, arg_count: usize) -> Result<F, String> {
+    let &(_, arity, method) = methods
+        .iter()
+        .find(|(method, ..)| *method == name)
+        .ok_or_else(|| format!("Method '{}' not found on {}", name, kind))?;
+    if !arity.accepts(arg_count) {
+        return Err(format!("TypeError: Method '{}' expects {}, got {}", name, arity, arg_count));
+    }
+    Ok(method)
+}
+
+fn string_argument<'a>(method: &str, value: &'a Value) -> Result<&'a str, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(format!("TypeError: {} expects a string", method)),
+    }
+}
+
+/// `s.split()` splits on runs of whitespace, `s.split(sep)` on each `sep`.
+fn split(s: &str, args: &[Value]) -> Result<Value, String> {
+    let parts: Vec<Value> = match args.first() {
+        None | Some(Value::Null) => s.split_whitespace().map(|part| Value::String(part.to_string())).collect(),
+        Some(Value::String(sep)) if !sep.is_empty() => s.split(sep.as_str()).map(|part| Value::String(part.to_string())).collect(),
+        _ => return Err("TypeError: split expects a non-empty separator string".to_string()),
+    };
+    Ok(Value::array(parts))
+}
+
+/// `sep.join(items)`, as in Python.
+fn join(sep: &str, args: &[Value]) -> Result<Value, String> {
+    let items = match &args[0] {
+        Value::Array(items) => items.lock().unwrap().clone(),
+        Value::Tuple(items) => items.clone(),
+        _ => return Err("TypeError: join expects an array of strings".to_string()),
+    };
+    let parts = items
+        .iter()
+        .map(|item| string_argument("join", item).map_err(|_| "TypeError: join expects an array of strings".to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::String(parts.join(sep)))
+}
+
+fn replace(s: &str, args: &[Value]) -> Result<Value, String> {
+    let (old, new) = (string_argument("replace", &args[0])?, string_argument("replace", &args[1])?);
+    Ok(Value::String(s.replace(old, new)))
+}
+
+/// The character index of the first match, or -1.
+fn find(s: &str, args: &[Value]) -> Result<Value, String> {
+    let sub = string_argument("find", &args[0])?;
+    Ok(Value::Number(s.find(sub).map_or(-1.0, |byte| s[..byte].chars().count() as f64)))
+}
+
+fn key_argument<'a>(method: &str, value: &'a Value) -> Result<&'a str, String> {
+    match value {
+        Value::String(key) => Ok(key),
+        _ => Err(format!("TypeError: {} expects a string key", method)),
+    }
+}
+
+/// A dictionary's keys in the order `for` visits them.
+pub(crate) fn sorted_keys(entries: &HashMap<String, Value>) -> Vec<&String> {
+    let mut keys: Vec<&String> = entries.keys().collect();
+    keys.sort();
+    keys
+}
+
+fn index_argument(method: &str, value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        _ => Err(format!("TypeError: {} expects a number index", method)),
+    }
+}
+
+fn function_argument(method: &str, value: Value) -> Result<Value, String> {
+    match value {
+        Value::Function(_) | Value::Closure(_) | Value::NativeFunction(_) => Ok(value),
+        other => Err(format!("TypeError: {} expects a function, got {}", method, VM::format_value(&other))),
+    }
+}
+
+/// `items.insert(i, x)` puts `x` before index `i`; indexes past either end
+/// insert there, as in Python.
+fn insert(_vm: &mut VM, items: &Shared<Vec<Value>>, args: Vec<Value>) -> Result<Value, String> {
+    index_argument("insert", &args[0])?;
+    let mut items = items.lock().unwrap();
+    let at = crate::vm::slice_bound(&args[0], items.len(), items.len())?;
+    items.insert(at, args[1].clone());
+    Ok(Value::Null)
+}
+
+/// `items.remove(i)` takes out and returns the element at index `i`.
+fn remove(_vm: &mut VM, items: &Shared<Vec<Value>>, args: Vec<Value>) -> Result<Value, String> {
+    let i = index_argument("remove", &args[0])?;
+    let mut items = items.lock().unwrap();
+    let at = crate::vm::position(i, items.len())
+        .ok_or_else(|| format!("IndexError: Index {} out of bounds for array of length {}", i, items.len()))?;
+    Ok(items.remove(at))
+}
+
+/// `items.sort()` orders the elements as `sorted` would, by the key
+/// function's result if one is given.
+fn sort(vm: &mut VM, items: &Shared<Vec<Value>>, args: Vec<Value>) -> Result<Value, String> {
+    let mut sorted_args = vec![Value::array(items.lock().unwrap().clone())];
+    sorted_args.extend(args);
+    if let Value::Array(sorted) = crate::builtins::sorted(vm, sorted_args)? {
+        *items.lock().unwrap() = sorted.lock().unwrap().clone();
+    }
+    Ok(Value::Null)
+}
+
+fn map(vm: &mut VM, items: &Shared<Vec<Value>>, args: Vec<Value>) -> Result<Value, String> {
+    let function = function_argument("map", args.into_iter().next().unwrap_or(Value::Null))?;
+    let items = items.lock().unwrap().clone();
+    let mapped = items.into_iter().map(|item| vm.call_function(function.clone(), vec![item]));
+    Ok(Value::array(mapped.collect::<Result<_, _>>()?))
+}
+
+fn filter(vm: &mut VM, items: &Shared<Vec<Value>>, args: Vec<Value>) -> Result<Value, String> {
+    let function = function_argument("filter", args.into_iter().next().unwrap_or(Value::Null))?;
+    let items = items.lock().unwrap().clone();
+    let mut kept = Vec::new();
+    for item in items {
+        if VM::is_truthy(&vm.call_function(function.clone(), vec![item.clone()])?) {
+            kept.push(item);
+        }
+    }
+    Ok(Value::array(kept))
+}
+
+/// `items.reduce(f, initial)` folds the elements into `f(f(initial, a), b)`
+/// and so on; without `initial`, the first element starts the fold.
+fn reduce(vm: &mut VM, items: &Shared<Vec<Value>>, args: Vec<Value>) -> Result<Value, String> {
+    let mut args = args.into_iter();
+    let function = function_argument("reduce", args.next().unwrap_or(Value::Null))?;
+    let items = items.lock().unwrap().clone();
+    let mut rest = items.into_iter();
+    let mut total = args.next().or_else(|| rest.next())
+        .ok_or_else(|| "reduce of an empty array with no initial value".to_string())?;
+    for item in rest {
+        total = vm.call_function(function.clone(), vec![total, item])?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Grease, InterpretResult};
+
+    fn formatted(grease: &Grease, name: &str) -> String {
+        crate::vm::VM::format_value(grease.vm.globals.get(name).unwrap())
+    }
+
+    #[test]
+    fn test_string_methods() {
+        let mut grease = Grease::new();
+        let result = grease.run("s = \"  Héllo, World  \"\n\
+            t = s.trim()\n\
+            cases = [t.upper(), t.lower(), t.len(), t.find(\"l\"), t.find(\"z\")]\n\
+            parts = [t.split(\", \"), \" a  b \".split(), \"-\".join([\"x\", \"y\"]), \"ab\".chars()]\n\
+            checks = [t.replace(\"l\", \"L\"), t.contains(\"llo\"), t.starts_with(\"Hé\"), t.ends_with(\"x\")]\n\
+            shout = \"a,b\".split(\",\")[1].upper()");
+        assert_eq!(result, Ok(InterpretResult::Ok));
+        assert_eq!(formatted(&grease, "s"), "  Héllo, World  ");
+        assert_eq!(formatted(&grease, "cases"), "[HÉLLO, WORLD, héllo, world, 12, 2, -1]");
+        assert_eq!(formatted(&grease, "parts"), "[[Héllo, World], [a, b], x-y, [a, b]]");
+        assert_eq!(formatted(&grease, "checks"), "[HéLLo, WorLd, true, true, false]");
+        assert_eq!(formatted(&grease, "shout"), "B");
+    }
+
+    #[test]
+    fn test_array_methods() {
+        let mut grease = Grease::new();
+        let result = grease.run("def double(x):\n    return x * 2\n\
+            def odd(x):\n    return x % 2 == 1\n\
+            def add(a, b):\n    return a + b\n\
+            def build():\n    items = [3]\n    items.push(1)\n    items.insert(0, 5)\n    return items\n\
+            xs = build()\n\
+            popped = [xs.pop(), xs.remove(-1)]\n\
+            xs.push(4)\n\
+            xs.insert(99, 2)\n\
+            xs.sort()\n\
+            ascending = copy(xs)\n\
+            xs.sort(double)\n\
+            xs.reverse()\n\
+            derived = [xs.map(double), xs.filter(odd), xs.reduce(add), [].reduce(add, 10), xs.contains(4), xs.len()]");
+        assert_eq!(result, Ok(InterpretResult::Ok));
+        assert_eq!(formatted(&grease, "popped"), "[1, 3]");
+        assert_eq!(formatted(&grease, "ascending"), "[2, 4, 5]");
+        assert_eq!(formatted(&grease, "xs"), "[5, 4, 2]");
+        assert_eq!(formatted(&grease, "derived"), "[[10, 8, 4], [5], 11, 10, true, 3]");
+    }
+
+    #[test]
+    fn test_dictionary_methods() {
+        let mut grease = Grease::new();
+        let result = grease.run("d = {\"b\": 2, \"a\": 1, \"c\": 3}\n\
+            removed = [d.delete(\"c\"), d.delete(\"z\")]\n\
+            listed = [d.keys(), d.values(), d.items(), d.len()]\n\
+            lookups = [d.get(\"a\"), d.get(\"z\"), d.get(\"z\", 0), d.has_key(\"b\"), d.has_key(\"c\")]\n\
+            visited = []\n\
+            for key in d:\n    visited.push(key)");
+        assert_eq!(result, Ok(InterpretResult::Ok));
+        assert_eq!(formatted(&grease, "removed"), "[3, null]");
+        assert_eq!(formatted(&grease, "listed"), "[[a, b], [1, 2], [(a, 1), (b, 2)], 2]");
+        assert_eq!(formatted(&grease, "lookups"), "[1, null, 0, true, false]");
+        assert_eq!(formatted(&grease, "visited"), "[a, b]");
+    }
+
+    #[test]
+    fn test_method_errors() {
+        for (source, error) in [
+            ("\"x\".nope()", "Method 'nope' not found on string"),
+            ("\"x\".upper(1)", "Method 'upper' expects 0 arguments, got 1"),
+            ("\"x\".split(\"\")", "split expects a non-empty separator string"),
+            ("\",\".join([1])", "join expects an array of strings"),
+            ("[].pop()", "pop from an empty array"),
+            ("[1].remove(1)", "Index 1 out of bounds for array of length 1"),
+            ("[1].map(2)", "map expects a function, got 2"),
+            ("[].reduce(hash)", "reduce of an empty array with no initial value"),
+            ("d = {}\nd.get(1)", "get expects a string key"),
+        ] {
+            let mut grease = Grease::new();
+            match grease.run(source) {
+                Ok(InterpretResult::RuntimeError(message)) => assert!(message.contains(error), "{}", message),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+}
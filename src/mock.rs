@@ -0,0 +1,138 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Stand-ins for natives, so tests of scripts that read the environment,
+//! open files or run processes can decide what those calls return and check
+//! what the script passed them. A mock replaces the native by name however
+//! the script reaches it, keeps its arity, and survives [`VM::reset`]:
+//!
+//! ```text
+//! let mocks = Mocks::new()
+//!     .returning("getenv", Value::String("/home/test".to_string()))
+//!     .failing("file_open", "Failed to open 'data.txt': denied");
+//! let calls = mocks.calls();
+//! let mut grease = Grease::new().with_mocks(mocks);
+//! grease.run(script)?;
+//! assert!(matches!(&calls.of("getenv")[0][..], [Value::String(name)] if name == "HOME"));
+//! ```
+//!
+//! [`VM::override_native`] replaces a single native with any closure.
+
+use crate::bytecode::Value;
+use crate::vm::VM;
+use std::sync::{Arc, Mutex};
+
+/// What a mocked native does when called, given the script's arguments.
+pub type MockFn = Box<dyn FnMut(&[Value]) -> Result<Value, String> + Send>;
+
+/// Mocks to install together with `Grease::with_mocks`, which records
+/// every call made to them.
+#[derive(Default)]
+pub struct Mocks {
+    natives: Vec<(String, MockFn)>,
+    calls: MockCalls,
+}
+
+/// A call to a mock: the native's name and the arguments.
+pub type MockCall = (String, Vec<Value>);
+
+/// The calls scripts made to a set of [`Mocks`], in order. Clones share
+/// the same record.
+#[derive(Debug, Clone, Default)]
+pub struct MockCalls(Arc<Mutex<Vec<MockCall>>>);
+
+impl Mocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Have native `name` call `function` instead.
+    pub fn with(mut self, name: &str, function: impl FnMut(&[Value]) -> Result<Value, String> + Send + 'static) -> Self {
+        self.natives.push((name.to_string(), Box::new(function)));
+        self
+    }
+
+    /// Have native `name` return `value` every time.
+    pub fn returning(self, name: &str, value: Value) -> Self {
+        self.with(name, move |_| Ok(value.clone()))
+    }
+
+    /// Have native `name` raise `error` every time.
+    pub fn failing(self, name: &str, error: &str) -> Self {
+        let error = error.to_string();
+        self.with(name, move |_| Err(error.clone()))
+    }
+
+    /// The record the mocks' calls will be added to.
+    pub fn calls(&self) -> MockCalls {
+        self.calls.clone()
+    }
+
+    /// Install the mocks in `vm`; fails if one names a native that isn't
+    /// registered.
+    pub fn install(self, vm: &mut VM) -> Result<(), String> {
+        for (name, mut function) in self.natives {
+            let calls = self.calls.clone();
+            let native = name.clone();
+            vm.override_native(&name, move |args| {
+                calls.push(&native, args);
+                function(args)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl MockCalls {
+    fn push(&self, name: &str, args: &[Value]) {
+        if let Ok(mut calls) = self.0.lock() {
+            calls.push((name.to_string(), args.to_vec()));
+        }
+    }
+
+    pub fn all(&self) -> Vec<MockCall> {
+        self.0.lock().map(|calls| calls.clone()).unwrap_or_default()
+    }
+
+    /// The arguments of each call to native `name`.
+    pub fn of(&self, name: &str) -> Vec<Vec<Value>> {
+        self.all().into_iter().filter(|(native, _)| native == name).map(|(_, args)| args).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    #[test]
+    fn test_mocks_stand_in_for_natives() {
+        let mocks = Mocks::new()
+            .returning("getenv", Value::String("/home/test".to_string()))
+            .failing("file_open", "denied")
+            .with("sorted", |args| Ok(args[0].clone()));
+        let calls = mocks.calls();
+        let mut grease = Grease::new().with_mocks(mocks);
+        let source = "read = getenv\n\
+            home = read(\"HOME\")\n\
+            try:\n    file_open(\"data.txt\", \"r\")\ncatch e:\n    error = e.message\n\
+            same = sorted([3, 1, 2])";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("home").unwrap()), "/home/test");
+        assert_eq!(VM::format_value(grease.vm.globals.get("error").unwrap()), "denied");
+        assert_eq!(VM::format_value(grease.vm.globals.get("same").unwrap()), "[3, 1, 2]");
+        assert!(matches!(&calls.of("getenv")[..], [args] if matches!(&args[..], [Value::String(name)] if name == "HOME")));
+        let order: Vec<String> = calls.all().into_iter().map(|(native, _)| native).collect();
+        assert_eq!(order, vec!["getenv", "file_open", "sorted"]);
+
+        // Mocks are configuration, so they outlive a reset; arity still holds
+        grease.reset().unwrap();
+        assert_eq!(grease.run("again = getenv(\"USER\")").unwrap(), InterpretResult::Ok);
+        assert_eq!(calls.of("getenv").len(), 2);
+        assert!(matches!(grease.run("getenv()").unwrap(), InterpretResult::RuntimeError(_)));
+
+        let error = Mocks::new().returning("no_such_native", Value::Null).install(&mut grease.vm).unwrap_err();
+        assert_eq!(error, "Can't mock 'no_such_native': there is no native by that name");
+    }
+}
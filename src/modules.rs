@@ -0,0 +1,354 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::ast::{Program, Statement};
+use crate::bytecode::Value;
+use crate::compiler::Compiler;
+use crate::edition::Edition;
+use crate::lexer::{Lexer, DEFAULT_TAB_WIDTH};
+use crate::parser::Parser;
+use crate::vm::VM;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub fn register_natives(vm: &mut VM) {
+    // Lets scripts check for optional modules before using them
+    vm.register_native("module_exists", 1, |vm, args| match &args[0] {
+        Value::String(name) => Ok(Value::Boolean(vm.modules.contains_key(name) || vm.lazy_modules.contains_key(name) || find_module(vm, name).is_some())),
+        _ => Err("TypeError: module_exists requires a module name string".to_string()),
+    });
+}
+
+/// Load the module `name` unless it already is: its top-level code runs once
+/// in the module's own namespace, and later imports share the result. Failing
+/// to find or compile the module, or an import cycle, is an `ImportError`;
+/// errors raised by the module's own code propagate unchanged.
+///
+/// A module whose top-level code eagerly uses a module that is still loading
+/// is rejected before it runs. `lazy use` defers the binding instead, so
+/// mutually recursive modules work as long as neither reads the other's
+/// members while loading.
+pub fn import(vm: &mut VM, name: &str) -> Result<(), String> {
+    if vm.importing.iter().any(|module| module == name) {
+        return Err(cycle_error(&vm.importing, name));
+    }
+    if vm.load_lazy_module(name) || vm.modules.contains_key(name) {
+        return Ok(());
+    }
+
+    let path = find_module(vm, name).ok_or_else(|| {
+        let searched: Vec<String> = module_candidates(name, vm.script_dir.as_deref(), &vm.module_paths).iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        format!("ImportError: Module '{}' not found. Searched {}", name, searched.join(", "))
+    })?;
+    let source = fs::read_to_string(&path)
+        .map_err(|e| format!("ImportError: Failed to read module {}: {}", path.display(), e))?;
+    let program = parse(&source, vm.tab_width, vm.edition).map_err(|e| format!("ImportError: Failed to compile module '{}': {}", name, e))?;
+    let mut chain = vm.importing.clone();
+    chain.push(name.to_string());
+    if let Some(cycle) = load_time_uses(&program.statements).into_iter().find(|used| chain.contains(used)) {
+        return Err(cycle_error(&chain, &cycle));
+    }
+    let module = compile(vm, name, &path, &program).map_err(|e| format!("ImportError: Failed to compile module '{}': {}", name, e))?;
+
+    vm.importing.push(name.to_string());
+    vm.modules.insert(name.to_string(), HashMap::new());
+    let result = vm.call_function(module, Vec::new());
+    vm.importing.pop();
+    if result.is_err() {
+        // Don't leave a half-initialized module behind for later imports
+        vm.modules.remove(name);
+    }
+    result.map(|_| ())
+}
+
+fn parse(source: &str, tab_width: usize, edition: Edition) -> Result<Program, String> {
+    let tokens = Lexer::new(source.to_string()).with_tab_width(tab_width).with_edition(edition).tokenize()?;
+    Parser::new(tokens).parse()
+}
+
+fn compile(vm: &VM, name: &str, path: &Path, program: &Program) -> Result<Value, String> {
+    // Functions compiled for the module resolve globals in its namespace
+    let function = Compiler::for_module(name)
+        .with_file(path.display().to_string())
+        .with_defines(vm.defines.clone())
+        .compile_module(program)?;
+    Ok(Value::Function(function))
+}
+
+/// The error for `name` being used while the modules in `chain` load, e.g.
+/// `a -> b -> a`.
+fn cycle_error(chain: &[String], name: &str) -> String {
+    let start = chain.iter().position(|module| module == name).unwrap_or(0);
+    let importer = chain.last().map_or(name, String::as_str);
+    format!(
+        "ImportError: Circular import {} -> {} (use 'lazy use {}' in '{}' to defer loading it)",
+        chain[start..].join(" -> "), name, name, importer
+    )
+}
+
+/// Modules that `statements` eagerly use while they run as top-level code.
+/// Uses inside functions and classes only run when called, so they are
+/// left out.
+fn load_time_uses(statements: &[Statement]) -> Vec<String> {
+    let mut uses = Vec::new();
+    for statement in statements {
+        match statement {
+            Statement::Use { module, lazy: false, .. } => uses.push(module.clone()),
+            Statement::If { then_branch, else_branch, .. } => {
+                uses.extend(load_time_uses(then_branch));
+                uses.extend(else_branch.as_deref().map(load_time_uses).unwrap_or_default());
+            }
+            Statement::While { body, .. } | Statement::For { body, .. }
+            | Statement::With { body, .. } | Statement::Block(body) => uses.extend(load_time_uses(body)),
+            Statement::Try { try_block, catch_clauses, finally_block } => {
+                uses.extend(load_time_uses(try_block));
+                for clause in catch_clauses {
+                    uses.extend(load_time_uses(&clause.body));
+                }
+                uses.extend(finally_block.as_deref().map(load_time_uses).unwrap_or_default());
+            }
+            _ => {}
+        }
+    }
+    uses
+}
+
+/// The first chain of modules reachable from `program` that eagerly use
+/// each other while loading, e.g. `["a", "b", "a"]`, resolving modules as
+/// `resolver` does. Lets tools report cycles without running anything;
+/// modules that can't be found or parsed are skipped.
+pub fn find_import_cycle(program: &Program, resolver: &ModuleResolver) -> Option<Vec<String>> {
+    fn visit(name: &str, chain: &mut Vec<String>, done: &mut HashSet<String>, resolver: &ModuleResolver) -> Option<Vec<String>> {
+        if let Some(start) = chain.iter().position(|module| module == name) {
+            let mut cycle = chain[start..].to_vec();
+            cycle.push(name.to_string());
+            return Some(cycle);
+        }
+        if !done.insert(name.to_string()) {
+            return None;
+        }
+        let program = resolver.parse(name)?;
+        chain.push(name.to_string());
+        let cycle = load_time_uses(&program.statements).iter()
+            .find_map(|used| visit(used, chain, done, resolver));
+        chain.pop();
+        cycle
+    }
+
+    let mut done = HashSet::new();
+    load_time_uses(&program.statements).iter()
+        .find_map(|used| visit(used, &mut Vec::new(), &mut done, resolver))
+}
+
+/// Where `use` finds modules, for tools that look at scripts without
+/// running them: the same search the VM makes, plus the names of modules
+/// registered from Rust, which have no file.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleResolver {
+    script_dir: Option<PathBuf>,
+    module_paths: Vec<PathBuf>,
+    native: HashSet<String>,
+}
+
+impl ModuleResolver {
+    /// Resolve modules as a script in `script_dir` (or, when `None`, code
+    /// run inline) would in a fresh VM.
+    pub fn new(script_dir: Option<&Path>) -> Self {
+        static BUILTIN: OnceLock<HashSet<String>> = OnceLock::new();
+        let native = BUILTIN.get_or_init(|| ModuleResolver::for_vm(&VM::new()).native).clone();
+        ModuleResolver { script_dir: script_dir.map(Path::to_path_buf), module_paths: Vec::new(), native }
+    }
+
+    /// Resolve modules as a script run by `vm` would, including the native
+    /// modules registered with it.
+    pub fn for_vm(vm: &VM) -> Self {
+        let native = available(vm).into_iter()
+            .filter(|(_, source)| *source == ModuleSource::Native)
+            .map(|(name, _)| name)
+            .collect();
+        ModuleResolver { script_dir: vm.script_dir.clone(), module_paths: vm.module_paths.clone(), native }
+    }
+
+    /// Also search `dirs`, after the usual places and before `std/`.
+    pub fn with_module_paths(mut self, dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.module_paths.extend(dirs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether `name` is registered from Rust rather than loaded from a file.
+    pub fn is_native(&self, name: &str) -> bool {
+        self.native.contains(name)
+    }
+
+    /// The file `use name` loads, if there is one.
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        module_candidates(name, self.script_dir.as_deref(), &self.module_paths).into_iter().find(|path| path.exists())
+    }
+
+    /// Every place `use name` looks, in order.
+    pub fn candidates(&self, name: &str) -> Vec<PathBuf> {
+        module_candidates(name, self.script_dir.as_deref(), &self.module_paths)
+    }
+
+    /// The module file `use name` loads, parsed; `None` if there's no such
+    /// file or it doesn't parse.
+    pub fn parse(&self, name: &str) -> Option<Program> {
+        let source = fs::read_to_string(self.resolve(name)?).ok()?;
+        parse(&source, DEFAULT_TAB_WIDTH, Edition::default()).ok()
+    }
+}
+
+/// The names a module's top-level code defines, which `use` makes its
+/// members. Names bound in top-level blocks count; function locals don't.
+pub fn defined_names(statements: &[Statement]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for statement in statements {
+        match statement {
+            Statement::VariableDeclaration { name, .. } | Statement::FunctionDeclaration { name, .. }
+            | Statement::ClassDeclaration { name, .. } => {
+                names.insert(name.lexeme.clone());
+            }
+            Statement::Unpack { targets, .. } => names.extend(targets.iter().map(|target| target.lexeme.clone())),
+            Statement::Use { module, alias, names: members, .. } => match members.as_slice() {
+                [] => {
+                    names.insert(alias.clone().unwrap_or_else(|| module.clone()));
+                }
+                members => names.extend(members.iter().map(|member| member.lexeme.clone())),
+            },
+            Statement::If { then_branch, else_branch, .. } => {
+                names.extend(defined_names(then_branch));
+                names.extend(else_branch.as_deref().map(defined_names).unwrap_or_default());
+            }
+            Statement::For { variable, body, .. } => {
+                names.insert(variable.lexeme.clone());
+                names.extend(defined_names(body));
+            }
+            Statement::While { body, .. } | Statement::Block(body) => names.extend(defined_names(body)),
+            Statement::With { name, body, .. } => {
+                names.extend(name.iter().map(|name| name.lexeme.clone()));
+                names.extend(defined_names(body));
+            }
+            Statement::Try { try_block, catch_clauses, finally_block } => {
+                names.extend(defined_names(try_block));
+                for clause in catch_clauses {
+                    names.extend(clause.name.iter().map(|name| name.lexeme.clone()));
+                    names.extend(defined_names(&clause.body));
+                }
+                names.extend(finally_block.as_deref().map(defined_names).unwrap_or_default());
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// The source file for module `name`, searched for next to the running
+/// script, in the current directory, `modules/`, the VM's extra module paths
+/// and finally `std/`.
+fn find_module(vm: &VM, name: &str) -> Option<PathBuf> {
+    module_candidates(name, vm.script_dir.as_deref(), &vm.module_paths).into_iter().find(|path| path.exists())
+}
+
+/// Where a module that `use` can load comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleSource {
+    /// Registered from Rust, e.g. with `Grease::with_native_module`.
+    Native,
+    File(PathBuf),
+    /// A native plugin in a plugin directory, for `use plugin "name"`.
+    Plugin(PathBuf),
+}
+
+/// Every module `use` can load by name, sorted: modules registered from Rust,
+/// the `.grease` files in the search directories and the plugins in the
+/// plugin directories. A file shadowed by one of the same name earlier in
+/// the search is left out.
+pub fn available(vm: &VM) -> Vec<(String, ModuleSource)> {
+    let mut modules: Vec<(String, ModuleSource)> = Vec::new();
+    let mut dirs = search_dirs(vm.script_dir.as_deref(), &vm.module_paths);
+    dirs.push(PathBuf::from("std"));
+    for dir in dirs {
+        // The working directory is the empty path, which can't be listed
+        let listed = if dir.as_os_str().is_empty() { Path::new(".") } else { dir.as_path() };
+        let Ok(entries) = fs::read_dir(listed) else { continue };
+        let mut files: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        files.sort();
+        for path in files {
+            if path.extension().is_none_or(|extension| extension != "grease") || !path.is_file() {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+            if !modules.iter().any(|(found, _)| found == name) {
+                modules.push((name.to_string(), ModuleSource::File(path.clone())));
+            }
+        }
+    }
+    for (name, members) in &vm.modules {
+        // Script modules that already ran are listed by their file
+        let is_native = members.values().all(|member| matches!(member, Value::NativeFunction(_)));
+        if is_native && !modules.iter().any(|(found, _)| found == name) {
+            modules.push((name.clone(), ModuleSource::Native));
+        }
+    }
+    for name in vm.lazy_modules.keys() {
+        if !modules.iter().any(|(found, _)| found == name) {
+            modules.push((name.clone(), ModuleSource::Native));
+        }
+    }
+    for dir in crate::plugin::plugin_dirs() {
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let name = path.file_name().and_then(|name| name.to_str()).and_then(crate::plugin::plugin_name);
+            if let Some(name) = name {
+                modules.push((name.to_string(), ModuleSource::Plugin(path.clone())));
+            }
+        }
+    }
+    modules.sort_by(|a, b| a.0.cmp(&b.0));
+    modules
+}
+
+/// The directories searched for modules before `std/`, in order.
+fn search_dirs(script_dir: Option<&Path>, module_paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = script_dir {
+        dirs.push(dir.to_path_buf());
+        dirs.push(dir.join("modules"));
+    }
+    dirs.push(PathBuf::new());
+    dirs.push(PathBuf::from("modules"));
+    dirs.extend(module_paths.iter().cloned());
+    dirs
+}
+
+fn module_candidates(name: &str, script_dir: Option<&Path>, module_paths: &[PathBuf]) -> Vec<PathBuf> {
+    let file_name = format!("{}.grease", name);
+    let mut paths_to_try: Vec<PathBuf> = search_dirs(script_dir, module_paths).iter().map(|dir| dir.join(&file_name)).collect();
+
+    // Add standard library paths
+    if !name.contains('/') && !name.contains('\\') {
+        paths_to_try.push(Path::new("std").join(&file_name));
+    }
+
+    // Relative imports are resolved against the script's directory
+    if name.starts_with('.') {
+        let relative_path = if let Some(rest) = name.strip_prefix("./") {
+            rest.to_string()
+        } else if let Some(rest) = name.strip_prefix("../") {
+            format!("../{}", rest)
+        } else {
+            name.trim_start_matches('.').to_string()
+        };
+        let relative_path = PathBuf::from(format!("{}.grease", relative_path));
+        paths_to_try.insert(0, match script_dir {
+            Some(dir) if relative_path.is_relative() => dir.join(relative_path),
+            _ => relative_path,
+        });
+    }
+
+    paths_to_try
+}
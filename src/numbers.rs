@@ -0,0 +1,132 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Converting numbers to and from text beyond what `+` coercion does.
+//! `parse_int` and `parse_float` throw a `ValueError` on malformed input;
+//! `to_hex`, `to_bin` and `to_oct` write integers without a prefix, which
+//! `parse_int` accepts but doesn't need:
+//!
+//! ```text
+//! parse_int("ff", 16)    # 255, as is parse_int("0xff", 16)
+//! to_bin(-5)             # "-101"
+//! round(2.71828, 2)      # 2.72; round(1234, -2) is 1200
+//! ```
+
+use crate::bytecode::Value;
+use crate::vm::VM;
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("parse_int", 2, |_vm, args| match (&args[0], &args[1]) {
+        (Value::String(text), Value::Number(base)) => parse_int(text, *base).map(Value::Number),
+        _ => Err("TypeError: parse_int expects (string, base number)".to_string()),
+    });
+    vm.register_native("parse_float", 1, |_vm, args| match &args[0] {
+        Value::String(text) => text.trim().parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| format!("ValueError: Invalid number '{}'", text)),
+        _ => Err("TypeError: parse_float expects a string".to_string()),
+    });
+
+    vm.register_native("to_hex", 1, |_vm, args| in_base("to_hex", &args[0], 16));
+    vm.register_native("to_bin", 1, |_vm, args| in_base("to_bin", &args[0], 2));
+    vm.register_native("to_oct", 1, |_vm, args| in_base("to_oct", &args[0], 8));
+
+    vm.register_native("is_nan", 1, |_vm, args| match args[0] {
+        Value::Number(n) => Ok(Value::Boolean(n.is_nan())),
+        _ => Err("TypeError: is_nan expects a number".to_string()),
+    });
+    vm.register_native("is_finite", 1, |_vm, args| match args[0] {
+        Value::Number(n) => Ok(Value::Boolean(n.is_finite())),
+        _ => Err("TypeError: is_finite expects a number".to_string()),
+    });
+
+    // Halves round away from zero; negative digits round to tens, hundreds...
+    vm.register_native("round", 2, |_vm, args| match (&args[0], &args[1]) {
+        (Value::Number(n), Value::Number(digits)) if digits.fract() == 0.0 => {
+            // Scale by a whole power of ten so 1234 to -2 digits is exactly 1200
+            let scale = 10f64.powi(digits.abs() as i32);
+            Ok(Value::Number(if *digits < 0.0 { (n / scale).round() * scale } else { (n * scale).round() / scale }))
+        }
+        _ => Err("TypeError: round expects (number, whole number of digits)".to_string()),
+    });
+}
+
+/// An integer written in `base` (2 to 36), with an optional sign and, for
+/// bases 2, 8 and 16, an optional `0b`, `0o` or `0x` prefix.
+fn parse_int(text: &str, base: f64) -> Result<f64, String> {
+    if base.fract() != 0.0 || !(2.0..=36.0).contains(&base) {
+        return Err(format!("TypeError: parse_int expects a base from 2 to 36, got {}", base));
+    }
+    let base = base as u32;
+    let invalid = || format!("ValueError: Invalid base {} integer '{}'", base, text);
+    let trimmed = text.trim();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let prefix = match base {
+        2 => Some("0b"),
+        8 => Some("0o"),
+        16 => Some("0x"),
+        _ => None,
+    };
+    let digits = prefix
+        .and_then(|prefix| digits.strip_prefix(prefix).or_else(|| digits.strip_prefix(&prefix.to_uppercase())))
+        .unwrap_or(digits);
+    if digits.is_empty() || digits.starts_with(['+', '-']) {
+        return Err(invalid());
+    }
+    let magnitude = u64::from_str_radix(digits, base).map_err(|_| invalid())?;
+    Ok(if negative { -(magnitude as f64) } else { magnitude as f64 })
+}
+
+fn in_base(name: &str, value: &Value, base: u32) -> Result<Value, String> {
+    let n = match value {
+        Value::Number(n) if n.fract() == 0.0 && n.abs() <= u64::MAX as f64 => *n,
+        _ => return Err(format!("TypeError: {} expects an integer", name)),
+    };
+    let magnitude = n.abs() as u64;
+    let digits = match base {
+        16 => format!("{:x}", magnitude),
+        8 => format!("{:o}", magnitude),
+        _ => format!("{:b}", magnitude),
+    };
+    Ok(Value::String(if n < 0.0 { format!("-{}", digits) } else { digits }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int("ff", 16.0), Ok(255.0));
+        assert_eq!(parse_int(" -0xFF ", 16.0), Ok(-255.0));
+        assert_eq!(parse_int("0b101", 2.0), Ok(5.0));
+        assert_eq!(parse_int("zz", 36.0), Ok(1295.0));
+        for (text, base) in [("12", 2.0), ("", 10.0), ("--1", 10.0), ("0x", 16.0), ("1.5", 10.0)] {
+            assert!(parse_int(text, base).unwrap_err().starts_with("ValueError"), "{} in base {}", text, base);
+        }
+        assert!(parse_int("1", 37.0).unwrap_err().contains("base from 2 to 36"));
+    }
+
+    #[test]
+    fn test_number_natives() {
+        let mut grease = Grease::new();
+        let source = "text = [to_hex(255), to_bin(-5), to_oct(8), parse_float(\" 2.5e3 \")]\n\
+            checks = [is_nan(parse_float(\"nan\")), is_finite(1 / 3), is_finite(parse_float(\"inf\"))]\n\
+            rounded = [round(2.71828, 2), round(1234, -2), round(-2.5, 0)]\n\
+            try:\n    parse_int(\"12z\", 10)\ncatch ValueError as e:\n    message = e.message";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        let formatted = |name| VM::format_value(grease.vm.globals.get(name).unwrap());
+        assert_eq!(formatted("text"), "[ff, -101, 10, 2500]");
+        assert_eq!(formatted("checks"), "[true, true, false]");
+        assert_eq!(formatted("rounded"), "[2.72, 1200, -3]");
+        assert_eq!(formatted("message"), "Invalid base 10 integer '12z'");
+
+        let result = grease.run("to_hex(1.5)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("to_hex expects an integer")));
+    }
+}
@@ -1,36 +1,21 @@
 // Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::token::{Token, TokenType};
-use crate::ast::{Expression, Statement, Program};
-use std::iter::Peekable;
+use crate::token::{Span, Token, TokenType};
+use crate::ast::{CatchClause, Expression, Statement, Program};
 use std::vec::IntoIter;
 
-#[allow(dead_code)]
-fn parse_expr(input: &str) -> Result<Expression, String> {
-    let mut lexer = crate::lexer::Lexer::new(input.to_string());
-    let tokens = lexer.tokenize()?;
-    let mut parser = Parser::new(tokens);
-    parser.expression()
-}
-
-#[allow(dead_code)]
-fn parse_program(input: &str) -> Result<Program, String> {
-    let mut lexer = crate::lexer::Lexer::new(input.to_string());
-    let tokens = lexer.tokenize()?;
-    let mut parser = Parser::new(tokens);
-    parser.parse()
-}
-
 pub struct Parser {
-    tokens: Peekable<IntoIter<Token>>,
+    /// Tokens not yet consumed; lookahead borrows them through `as_slice`
+    /// so it doesn't copy the rest of the program.
+    tokens: IntoIter<Token>,
     previous: Option<Token>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         Parser {
-            tokens: tokens.into_iter().peekable(),
+            tokens: tokens.into_iter(),
             previous: None,
         }
     }
@@ -46,24 +31,39 @@ impl Parser {
             if let Some(stmt) = self.declaration()? {
                 program.statements.push(stmt);
             } else {
-                return Err(format!("Unexpected token at line {}", self.tokens.peek().map(|t| t.line).unwrap_or(0)));
+                return Err(self.error("Unexpected token"));
             }
         }
 
         Ok(program)
     }
 
+    /// Skip blank lines and the `;`s that separate statements on one line.
     fn skip_newlines(&mut self) {
-        while self.match_token(&TokenType::Newline) {
+        while self.match_token(&TokenType::Newline) || self.match_token(&TokenType::Semicolon) {
             // Skip
         }
     }
 
+    /// Whether the statement being parsed ends here, before a newline, a
+    /// `;` or the end of its block.
+    fn at_statement_end(&mut self) -> bool {
+        self.check(&TokenType::Newline) || self.check(&TokenType::Semicolon)
+            || self.check(&TokenType::Dedent) || self.is_at_end()
+    }
+
     fn declaration(&mut self) -> Result<Option<Statement>, String> {
         if self.match_token(&TokenType::Fn) {
             Ok(Some(self.function_declaration()?))
+        } else if self.match_token(&TokenType::At) {
+            Ok(Some(self.decorated_function()?))
         } else if self.match_token(&TokenType::Use) {
-            Ok(Some(self.use_statement()?))
+            Ok(Some(self.use_statement(false)?))
+        } else if self.match_token(&TokenType::From) {
+            Ok(Some(self.selective_use_statement()?))
+        } else if self.match_token(&TokenType::Lazy) {
+            self.consume(TokenType::Use, "Expected 'use' after 'lazy'")?;
+            Ok(Some(self.use_statement(true)?))
         } else {
             self.statement()
         }
@@ -86,7 +86,7 @@ impl Parser {
                 };
                 parameters.push((param_name, None)); // No type annotations
 
-                if !self.match_token(&TokenType::Comma) {
+                if !self.match_token(&TokenType::Comma) || self.check(&TokenType::RightParen) {
                     break;
                 }
             }
@@ -103,15 +103,48 @@ impl Parser {
             parameters,
             return_type: None, // No return type annotations
             body,
+            decorators: Vec::new(),
         })
     }
 
-    fn use_statement(&mut self) -> Result<Statement, String> {
+    fn decorated_function(&mut self) -> Result<Statement, String> {
+        let mut decorators = Vec::new();
+        loop {
+            decorators.push(self.call()?);
+            self.consume(TokenType::Newline, "Expected newline after decorator")?;
+            self.skip_newlines();
+            if !self.match_token(&TokenType::At) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::Fn, "Expected function definition after decorator")?;
+        match self.function_declaration()? {
+            Statement::FunctionDeclaration { name, parameters, return_type, body, .. } => Ok(Statement::FunctionDeclaration {
+                name,
+                parameters,
+                return_type,
+                body,
+                decorators,
+            }),
+            _ => unreachable!("function_declaration returns a function declaration"),
+        }
+    }
+
+    fn use_statement(&mut self, lazy: bool) -> Result<Statement, String> {
+        // `plugin` is only special before a string, so it stays a module name
+        if let [Token { token_type: TokenType::Identifier(name), .. }, Token { token_type: TokenType::String(_), .. }, ..] = self.tokens.as_slice() {
+            if name == "plugin" && !lazy {
+                self.advance();
+                let path = self.advance().unwrap();
+                return Ok(Statement::UsePlugin { path });
+            }
+        }
         let module_token = self.consume_identifier("Expected module name after 'use'")?;
         let module = if let TokenType::Identifier(ref name) = module_token.token_type {
             name.clone()
         } else {
-            return Err("Expected identifier for module name".to_string());
+            return Err(self.error("Expected identifier for module name"));
         };
 
         let alias = if self.match_token(&TokenType::As) {
@@ -119,13 +152,26 @@ impl Parser {
             if let TokenType::Identifier(ref name) = alias_token.token_type {
                 Some(name.clone())
             } else {
-                return Err("Expected identifier for alias".to_string());
+                return Err(self.error("Expected identifier for alias"));
             }
         } else {
             None
         };
 
-        Ok(Statement::Use { module, alias })
+        Ok(Statement::Use { module, alias, names: Vec::new(), lazy })
+    }
+
+    fn selective_use_statement(&mut self) -> Result<Statement, String> {
+        let module_token = self.consume_identifier("Expected module name after 'from'")?;
+        self.consume(TokenType::Use, "Expected 'use' after module name")?;
+
+        let mut names = vec![self.consume_identifier("Expected name to import")?];
+        while self.match_token(&TokenType::Comma) {
+            names.push(self.consume_identifier("Expected name to import after ','")?);
+        }
+        self.match_token(&TokenType::Newline);
+
+        Ok(Statement::Use { module: module_token.lexeme, alias: None, names, lazy: false })
     }
 
     fn statement(&mut self) -> Result<Option<Statement>, String> {
@@ -141,14 +187,24 @@ impl Parser {
             Ok(Some(self.try_statement()?))
         } else if self.match_token(&TokenType::Throw) {
             Ok(Some(self.throw_statement()?))
+        } else if self.match_token(&TokenType::With) {
+            Ok(Some(self.with_statement()?))
         } else if self.match_token(&TokenType::Return) {
             Ok(Some(self.return_statement()?))
+        } else if self.match_token(&TokenType::Break) {
+            self.match_token(&TokenType::Newline);
+            Ok(Some(Statement::Break))
+        } else if self.match_token(&TokenType::Continue) {
+            self.match_token(&TokenType::Newline);
+            Ok(Some(Statement::Continue))
         } else if self.check(&TokenType::LeftBrace) {
             Ok(Some(self.block_statement()?))
         } else if self.check(&TokenType::RustInline) {
             Ok(Some(self.rust_inline_statement()?))
         } else if self.check(&TokenType::AsmInline) {
             Ok(Some(self.asm_inline_statement()?))
+        } else if self.is_unpack_statement() {
+            Ok(Some(self.unpack_statement()?))
         } else if self.is_assignment_statement() {
             Ok(Some(self.assignment_statement()?))
         } else {
@@ -182,17 +238,69 @@ impl Parser {
     fn try_statement(&mut self) -> Result<Statement, String> {
         self.consume(TokenType::Colon, "Expected ':' after try")?;
         let try_block = self.block()?;
-        self.consume(TokenType::Catch, "Expected 'catch' after try block")?;
-        self.consume(TokenType::Colon, "Expected ':' after catch")?;
-        let catch_block = self.block()?;
+
+        let mut catch_clauses = Vec::new();
+        while self.match_token(&TokenType::Catch) {
+            catch_clauses.push(self.catch_clause()?);
+        }
+
+        let finally_block = if self.match_token(&TokenType::Finally) {
+            self.consume(TokenType::Colon, "Expected ':' after finally")?;
+            Some(self.block()?)
+        } else {
+            None
+        };
+
+        if catch_clauses.is_empty() && finally_block.is_none() {
+            return Err(self.error("Expected 'catch' or 'finally' after try block"));
+        }
+
         Ok(Statement::Try {
             try_block,
-            catch_block,
+            catch_clauses,
+            finally_block,
+        })
+    }
+
+    /// Parse what follows `catch`: `catch:`, `catch e:`, `catch Type as e:`
+    /// or `catch (TypeA, TypeB) as e:`.
+    fn catch_clause(&mut self) -> Result<CatchClause, String> {
+        let mut exception_types = Vec::new();
+        let mut name = None;
+
+        if self.match_token(&TokenType::LeftParen) {
+            loop {
+                exception_types.push(Expression::Identifier(self.consume_identifier("Expected exception class name")?));
+                if !self.match_token(&TokenType::Comma) || self.check(&TokenType::RightParen) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after exception classes")?;
+        } else if self.check_identifier() {
+            let identifier = self.consume_identifier("Expected exception class or variable name")?;
+            if self.check(&TokenType::As) {
+                exception_types.push(Expression::Identifier(identifier));
+            } else {
+                name = Some(identifier);
+            }
+        }
+
+        if !exception_types.is_empty() && self.match_token(&TokenType::As) {
+            name = Some(self.consume_identifier("Expected variable name after 'as'")?);
+        }
+
+        self.consume(TokenType::Colon, "Expected ':' after catch")?;
+        let body = self.block()?;
+
+        Ok(CatchClause {
+            exception_types,
+            name,
+            body,
         })
     }
 
     fn throw_statement(&mut self) -> Result<Statement, String> {
-        let value = if self.check(&TokenType::Newline) || self.check(&TokenType::EOF) {
+        let value = if self.at_statement_end() {
             None
         } else {
             Some(self.expression()?)
@@ -200,6 +308,23 @@ impl Parser {
         Ok(Statement::Throw { value })
     }
 
+    fn with_statement(&mut self) -> Result<Statement, String> {
+        let manager = self.expression()?;
+        let name = if self.match_token(&TokenType::As) {
+            Some(self.consume_identifier("Expected variable name after 'as'")?)
+        } else {
+            None
+        };
+        self.consume(TokenType::Colon, "Expected ':' after with clause")?;
+        let body = self.block()?;
+
+        Ok(Statement::With {
+            manager,
+            name,
+            body,
+        })
+    }
+
     fn while_statement(&mut self) -> Result<Statement, String> {
         let condition = self.expression()?;
         self.consume(TokenType::Colon, "Expected ':' after while condition")?;
@@ -226,10 +351,10 @@ impl Parser {
     }
 
     fn return_statement(&mut self) -> Result<Statement, String> {
-        let value = if !self.check(&TokenType::Newline) && !self.is_at_end() {
-            Some(self.expression()?)
-        } else {
+        let value = if self.at_statement_end() {
             None
+        } else {
+            Some(self.expression_list()?)
         };
         
         self.match_token(&TokenType::Newline);
@@ -237,15 +362,29 @@ impl Parser {
         Ok(Statement::Return { value })
     }
 
+    /// One expression, or several separated by commas collected into a tuple,
+    /// as in `return low, high`.
+    fn expression_list(&mut self) -> Result<Expression, String> {
+        let first = self.expression()?;
+        if !self.check(&TokenType::Comma) {
+            return Ok(first);
+        }
+        let mut elements = vec![first];
+        while self.match_token(&TokenType::Comma) {
+            elements.push(self.expression()?);
+        }
+        Ok(Expression::Tuple(elements))
+    }
+
     fn block_statement(&mut self) -> Result<Statement, String> {
         Ok(Statement::Block(self.block()?))
     }
 
     fn is_assignment_statement(&mut self) -> bool {
         // Check if this looks like an assignment: identifier [: Type] = ...
-        if let Some(token) = self.tokens.peek() {
+        if let Some(token) = self.peek() {
             if let TokenType::Identifier(_) = &token.token_type {
-                let mut temp_tokens = self.tokens.clone();
+                let mut temp_tokens = self.tokens.as_slice().iter();
                 temp_tokens.next(); // consume identifier
                 if let Some(next_token) = temp_tokens.next() {
                     if matches!(next_token.token_type, TokenType::Assign) {
@@ -269,6 +408,36 @@ impl Parser {
         }
     }
 
+    fn is_unpack_statement(&mut self) -> bool {
+        // Check if this looks like an unpacking assignment: identifier (, identifier)+ = ...
+        let mut temp_tokens = self.tokens.as_slice().iter();
+        let mut targets = 0;
+        loop {
+            match temp_tokens.next().map(|token| &token.token_type) {
+                Some(TokenType::Identifier(_)) => targets += 1,
+                _ => return false,
+            }
+            match temp_tokens.next().map(|token| &token.token_type) {
+                Some(TokenType::Comma) => continue,
+                Some(TokenType::Assign) => return targets > 1,
+                _ => return false,
+            }
+        }
+    }
+
+    fn unpack_statement(&mut self) -> Result<Statement, String> {
+        let mut targets = vec![self.consume_identifier("Expected variable name")?];
+        while self.match_token(&TokenType::Comma) {
+            targets.push(self.consume_identifier("Expected variable name after ','")?);
+        }
+
+        self.consume(TokenType::Assign, "Expected '=' after unpacking targets")?;
+        let value = self.expression_list()?;
+        self.match_token(&TokenType::Newline);
+
+        Ok(Statement::Unpack { targets, value })
+    }
+
     fn assignment_statement(&mut self) -> Result<Statement, String> {
         let name = self.consume_identifier("Expected variable name")?;
 
@@ -277,7 +446,7 @@ impl Parser {
             let type_token = self.consume_identifier("Expected type name")?;
             type_annotation = Some(match &type_token.token_type {
                 TokenType::Identifier(name) => name.clone(),
-                _ => return Err("Expected type name".to_string()),
+                _ => return Err(self.error("Expected type name")),
             });
         }
 
@@ -293,6 +462,11 @@ impl Parser {
     }
 
     fn block(&mut self) -> Result<Vec<Statement>, String> {
+        // `if x: a = 1; b = 2` keeps a short block on the header's line
+        if !self.check(&TokenType::Newline) && !self.check(&TokenType::Indent) && !self.is_at_end() {
+            return self.single_line_block();
+        }
+
         let mut statements = Vec::new();
 
         // Skip the newline after the colon
@@ -316,11 +490,30 @@ impl Parser {
         Ok(statements)
     }
 
+    /// The `;`-separated statements after a block header's colon, up to the
+    /// end of the line.
+    fn single_line_block(&mut self) -> Result<Vec<Statement>, String> {
+        let mut statements = Vec::new();
+        loop {
+            if let Some(statement) = self.declaration()? {
+                statements.push(statement);
+            }
+            // A statement that ends the line takes its newline with it
+            if !self.match_token(&TokenType::Semicolon) || self.at_statement_end() {
+                break;
+            }
+        }
+        // After a trailing `;` the newline is still there
+        self.match_token(&TokenType::Newline);
+        Ok(statements)
+    }
+
     fn expression(&mut self) -> Result<Expression, String> {
         let expr = self.logical_or()?;
         
         // Check for assignment
         if self.match_token(&TokenType::Assign) {
+            let target = expr.span();
             let value = self.expression()?;
             
             match expr {
@@ -337,7 +530,12 @@ impl Parser {
                         value: Box::new(value),
                     });
                 }
-                _ => return Err("Invalid assignment target".to_string()),
+                _ => {
+                    return Err(match target {
+                        Some(span) => format!("Invalid assignment target at line {}, column {}", span.line, span.column),
+                        None => self.error("Invalid assignment target"),
+                    });
+                }
             }
         }
         
@@ -474,7 +672,7 @@ impl Parser {
                     if !self.check(&TokenType::RightParen) {
                         loop {
                             arguments.push(self.expression()?);
-                            if !self.match_token(&TokenType::Comma) {
+                            if !self.match_token(&TokenType::Comma) || self.check(&TokenType::RightParen) {
                                 break;
                             }
                         }
@@ -492,6 +690,20 @@ impl Parser {
                         property: member,
                     };
                 }
+            } else if self.match_token(&TokenType::LeftBracket) {
+                let index = if self.check(&TokenType::Colon) { None } else { Some(self.expression()?) };
+                if self.match_token(&TokenType::Colon) {
+                    let end = if self.check(&TokenType::RightBracket) { None } else { Some(Box::new(self.expression()?)) };
+                    self.consume(TokenType::RightBracket, "Expected ']' after slice")?;
+                    expr = Expression::Slice { array: Box::new(expr), start: index.map(Box::new), end };
+                    continue;
+                }
+                let index = index.ok_or("Expected an index or slice inside '[]'")?;
+                self.consume(TokenType::RightBracket, "Expected ']' after index")?;
+                expr = Expression::Index {
+                    array: Box::new(expr),
+                    index: Box::new(index),
+                };
             } else {
                 break;
             }
@@ -506,7 +718,7 @@ impl Parser {
         if !self.check(&TokenType::RightParen) {
             loop {
                 arguments.push(self.expression()?);
-                if !self.match_token(&TokenType::Comma) {
+                if !self.match_token(&TokenType::Comma) || self.check(&TokenType::RightParen) {
                     break;
                 }
             }
@@ -521,7 +733,7 @@ impl Parser {
     }
 
     fn primary(&mut self) -> Result<Expression, String> {
-        if let Some(token) = self.tokens.peek() {
+        if let Some(token) = self.peek() {
             let token_type = token.token_type.clone();
             match token_type {
                 TokenType::Number(value) => {
@@ -532,6 +744,10 @@ impl Parser {
                     self.advance();
                     return Ok(Expression::String(value));
                 }
+                TokenType::Bytes(value) => {
+                    self.advance();
+                    return Ok(Expression::Bytes(value));
+                }
                 TokenType::True => {
                     self.advance();
                     return Ok(Expression::Boolean(true));
@@ -552,7 +768,7 @@ impl Parser {
                     if !self.check(&TokenType::RightParen) {
                         loop {
                             arguments.push(self.expression()?);
-                            if !self.match_token(&TokenType::Comma) {
+                            if !self.match_token(&TokenType::Comma) || self.check(&TokenType::RightParen) {
                                 break;
                             }
                         }
@@ -575,7 +791,7 @@ impl Parser {
                     if !self.check(&TokenType::RightParen) {
                         loop {
                             arguments.push(self.expression()?);
-                            if !self.match_token(&TokenType::Comma) {
+                            if !self.match_token(&TokenType::Comma) || self.check(&TokenType::RightParen) {
                                 break;
                             }
                         }
@@ -596,9 +812,25 @@ impl Parser {
                 }
                 TokenType::LeftParen => {
                     self.advance();
+                    if self.match_token(&TokenType::RightParen) {
+                        return Ok(Expression::Tuple(Vec::new()));
+                    }
                     let expr = self.expression()?;
-                    self.consume(TokenType::RightParen, "Expected ')' after expression")?;
-                    return Ok(Expression::Grouping(Box::new(expr)));
+                    if !self.match_token(&TokenType::Comma) {
+                        self.consume(TokenType::RightParen, "Expected ')' after expression")?;
+                        return Ok(Expression::Grouping(Box::new(expr)));
+                    }
+
+                    // A comma makes this a tuple; `(x,)` is a one-element tuple
+                    let mut elements = vec![expr];
+                    while !self.check(&TokenType::RightParen) {
+                        elements.push(self.expression()?);
+                        if !self.match_token(&TokenType::Comma) {
+                            break;
+                        }
+                    }
+                    self.consume(TokenType::RightParen, "Expected ')' after tuple elements")?;
+                    return Ok(Expression::Tuple(elements));
                 }
                 TokenType::LeftBracket => {
                     self.advance();
@@ -607,7 +839,7 @@ impl Parser {
                     if !self.check(&TokenType::RightBracket) {
                         loop {
                             elements.push(self.expression()?);
-                            if !self.match_token(&TokenType::Comma) {
+                            if !self.match_token(&TokenType::Comma) || self.check(&TokenType::RightBracket) {
                                 break;
                             }
                         }
@@ -627,7 +859,7 @@ impl Parser {
                             let value = self.expression()?;
                             pairs.push((key, value));
                             
-                            if !self.match_token(&TokenType::Comma) {
+                            if !self.match_token(&TokenType::Comma) || self.check(&TokenType::RightBrace) {
                                 break;
                             }
                         }
@@ -640,11 +872,11 @@ impl Parser {
             }
         }
         
-        Err(format!("Unexpected token at line {}", self.current_line()))
+        Err(self.error("Unexpected token"))
     }
 
     fn match_token(&mut self, token_type: &TokenType) -> bool {
-        if let Some(token) = self.tokens.peek() {
+        if let Some(token) = self.peek() {
             if std::mem::discriminant(&token.token_type) == std::mem::discriminant(token_type) {
                 self.advance();
                 return true;
@@ -654,13 +886,17 @@ impl Parser {
     }
 
     fn check(&mut self, token_type: &TokenType) -> bool {
-        if let Some(token) = self.tokens.peek() {
+        if let Some(token) = self.peek() {
             std::mem::discriminant(&token.token_type) == std::mem::discriminant(token_type)
         } else {
             false
         }
     }
 
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.as_slice().first()
+    }
+
     fn advance(&mut self) -> Option<Token> {
         self.previous = self.tokens.next();
         self.previous.clone()
@@ -670,14 +906,14 @@ impl Parser {
         if self.check(&token_type) {
             Ok(self.advance().unwrap())
         } else {
-            Err(format!("{} at line {}", message, self.current_line()))
+            Err(self.error(message))
         }
     }
 
 
 
     fn is_at_end(&mut self) -> bool {
-        match self.tokens.peek() {
+        match self.peek() {
             Some(token) => matches!(token.token_type, TokenType::EOF),
             None => true,
         }
@@ -709,8 +945,11 @@ impl Parser {
             if self.match_token(&TokenType::Fn) {
                 let method = self.function_declaration()?;
                 methods.push(method);
+            } else if self.match_token(&TokenType::String(String::new())) {
+                // A docstring; lets a class have no methods of its own
+                self.match_token(&TokenType::Newline);
             } else {
-                return Err("Expected method declaration in class".to_string());
+                return Err(self.error("Expected method declaration in class"));
             }
         }
         
@@ -722,17 +961,32 @@ impl Parser {
         })
     }
 
+    fn check_identifier(&mut self) -> bool {
+        matches!(self.peek().map(|token| &token.token_type), Some(TokenType::Identifier(_)))
+    }
+
     fn consume_identifier(&mut self, message: &str) -> Result<Token, String> {
-        if let Some(token) = self.tokens.peek() {
+        if let Some(token) = self.peek() {
             if let TokenType::Identifier(_) = &token.token_type {
                 return Ok(self.tokens.next().unwrap());
             }
         }
-        Err(message.to_string())
+        Err(self.error(message))
+    }
+
+    /// Where the parser is: the token it is looking at, or the last one it
+    /// took once there are none left. After an error this is the token the
+    /// error is about.
+    pub fn span(&self) -> Option<Span> {
+        self.peek().or(self.previous.as_ref()).map(Token::span)
     }
 
-    fn current_line(&self) -> usize {
-        self.previous.as_ref().map(|t| t.line).unwrap_or(0)
+    /// `message` located at the token the parser is looking at.
+    fn error(&self, message: &str) -> String {
+        match self.span() {
+            Some(span) => format!("{} at line {}, column {}", message, span.line, span.column),
+            None => message.to_string(),
+        }
     }
 
     fn rust_inline_statement(&mut self) -> Result<Statement, String> {
@@ -752,32 +1006,51 @@ impl Parser {
             Err("Expected asm inline block".to_string())
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    #[cfg(test)]
+    fn parse_expr(input: &str) -> Result<Expression, String> {
+        let mut lexer = crate::lexer::Lexer::new(input.to_string());
+        let tokens = lexer.tokenize()?;
+        let mut parser = Parser::new(tokens);
+        parser.expression()
+    }
+
+    fn parse_program(input: &str) -> Result<Program, String> {
+        let mut lexer = crate::lexer::Lexer::new(input.to_string());
+        let tokens = lexer.tokenize()?;
+        let mut parser = Parser::new(tokens);
+        parser.parse()
+    }
+
+    #[test]
     fn test_parse_number() {
         let expr = parse_expr("42").unwrap();
         assert!(matches!(expr, Expression::Number(42.0)));
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_string() {
         let expr = parse_expr("\"hello\"").unwrap();
         assert!(matches!(expr, Expression::String(s) if s == "hello"));
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_boolean() {
         let expr = parse_expr("true").unwrap();
         assert!(matches!(expr, Expression::Boolean(true)));
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_null() {
         let expr = parse_expr("null").unwrap();
         assert!(matches!(expr, Expression::Null));
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_identifier() {
         let expr = parse_expr("x").unwrap();
         match expr {
@@ -788,7 +1061,7 @@ impl Parser {
         }
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_binary_expression() {
         let expr = parse_expr("1 + 2").unwrap();
         match expr {
@@ -801,7 +1074,7 @@ impl Parser {
         }
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_unary_expression() {
         let expr = parse_expr("-5").unwrap();
         match expr {
@@ -813,9 +1086,7 @@ impl Parser {
         }
     }
 
-
-
-    #[cfg(test)]
+    #[test]
     fn test_parse_call() {
         let expr = parse_expr("print(42)").unwrap();
         match expr {
@@ -833,7 +1104,95 @@ impl Parser {
         }
     }
 
-    #[cfg(test)]
+    #[test]
+    fn test_parse_index() {
+        let expr = parse_expr("items[1][\"key\"]").unwrap();
+        match expr {
+            Expression::Index { array, index } => {
+                assert!(matches!(*array, Expression::Index { .. }));
+                assert!(matches!(*index, Expression::String(ref s) if s == "key"));
+            }
+            _ => panic!("Expected index expression"),
+        }
+    }
+
+    #[test]
+    fn test_parse_slice() {
+        let expr = parse_expr("items[1:][:-1]").unwrap();
+        match expr {
+            Expression::Slice { array, start: None, end: Some(end) } => {
+                assert!(matches!(*array, Expression::Slice { start: Some(_), end: None, .. }));
+                assert!(matches!(*end, Expression::Unary { .. }));
+            }
+            _ => panic!("Expected slice expression"),
+        }
+        assert!(matches!(parse_expr("items[:]"), Ok(Expression::Slice { start: None, end: None, .. })));
+        assert!(parse_expr("items[]").is_err());
+    }
+
+    #[test]
+    fn test_parse_tuple_and_grouping() {
+        assert!(matches!(parse_expr("(1, 2)").unwrap(), Expression::Tuple(ref items) if items.len() == 2));
+        assert!(matches!(parse_expr("(1,)").unwrap(), Expression::Tuple(ref items) if items.len() == 1));
+        assert!(matches!(parse_expr("()").unwrap(), Expression::Tuple(ref items) if items.is_empty()));
+        assert!(matches!(parse_expr("(1)").unwrap(), Expression::Grouping(_)));
+    }
+
+    #[test]
+    fn test_parse_try_catch_clauses() {
+        let program = parse_program("try:\n    x = 1\ncatch (KeyError, IndexError) as e:\n    y = e\ncatch ValueError as v:\n    y = v\ncatch err:\n    y = err\ncatch:\n    y = 0\nfinally:\n    z = 1").unwrap();
+        match &program.statements[0] {
+            Statement::Try { catch_clauses, finally_block, .. } => {
+                let shape: Vec<(usize, Option<&str>)> = catch_clauses.iter()
+                    .map(|clause| (clause.exception_types.len(), clause.name.as_ref().map(|t| t.lexeme.as_str())))
+                    .collect();
+                assert_eq!(shape, vec![(2, Some("e")), (1, Some("v")), (0, Some("err")), (0, None)]);
+                assert!(finally_block.is_some());
+            }
+            other => panic!("Expected try statement, got {:?}", other),
+        }
+        assert!(parse_program("try:\n    x = 1\ny = 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_with_statement() {
+        let program = parse_program("with open(path) as f:\n    x = f\nwith lock:\n    y = 1").unwrap();
+        match &program.statements[0] {
+            Statement::With { manager, name, body } => {
+                assert!(matches!(manager, Expression::Call { .. }));
+                assert_eq!(name.as_ref().map(|t| t.lexeme.as_str()), Some("f"));
+                assert_eq!(body.len(), 1);
+            }
+            other => panic!("Expected with statement, got {:?}", other),
+        }
+        assert!(matches!(&program.statements[1], Statement::With { name: None, .. }));
+    }
+
+    #[test]
+    fn test_parse_unpack_statement() {
+        let program = parse_program("a, b = (1, 2)").unwrap();
+        match &program.statements[0] {
+            Statement::Unpack { targets, value } => {
+                assert_eq!(targets.len(), 2);
+                assert!(matches!(value, Expression::Tuple(_)));
+            }
+            other => panic!("Expected unpack statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_multiple_return_values() {
+        let program = parse_program("def minmax(a, b):\n    return a, b\nlow, high = high, low").unwrap();
+        match &program.statements[0] {
+            Statement::FunctionDeclaration { body, .. } => {
+                assert!(matches!(&body[0], Statement::Return { value: Some(Expression::Tuple(items)) } if items.len() == 2));
+            }
+            other => panic!("Expected function declaration, got {:?}", other),
+        }
+        assert!(matches!(&program.statements[1], Statement::Unpack { value: Expression::Tuple(_), .. }));
+    }
+
+    #[test]
     fn test_parse_variable_declaration() {
         let program = parse_program("x = 42").unwrap();
         assert_eq!(program.statements.len(), 1);
@@ -847,7 +1206,7 @@ impl Parser {
         }
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_variable_declaration_with_type() {
         let program = parse_program("x: Number = 42").unwrap();
         assert_eq!(program.statements.len(), 1);
@@ -861,7 +1220,7 @@ impl Parser {
         }
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_if_statement() {
         let program = parse_program("if true:\n    print(1)").unwrap();
         assert_eq!(program.statements.len(), 1);
@@ -875,7 +1234,7 @@ impl Parser {
         }
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_while_statement() {
         let program = parse_program("while true:\n    print(1)").unwrap();
         assert_eq!(program.statements.len(), 1);
@@ -888,12 +1247,12 @@ impl Parser {
         }
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_use_statement() {
         let program = parse_program("use math").unwrap();
         assert_eq!(program.statements.len(), 1);
         match &program.statements[0] {
-            Statement::Use { module, alias } => {
+            Statement::Use { module, alias, .. } => {
                 assert_eq!(module, "math");
                 assert!(alias.is_none());
             }
@@ -901,12 +1260,12 @@ impl Parser {
         }
     }
 
-    #[cfg(test)]
+    #[test]
     fn test_parse_use_statement_with_alias() {
         let program = parse_program("use math as m").unwrap();
         assert_eq!(program.statements.len(), 1);
         match &program.statements[0] {
-            Statement::Use { module, alias } => {
+            Statement::Use { module, alias, .. } => {
                 assert_eq!(module, "math");
                 assert_eq!(*alias, Some("m".to_string()));
             }
@@ -914,5 +1273,108 @@ impl Parser {
         }
     }
 
+    #[test]
+    fn test_parse_selective_use_statement() {
+        let program = parse_program("from math use add, sqrt").unwrap();
+        match &program.statements[0] {
+            Statement::Use { module, alias, names, .. } => {
+                assert_eq!(module, "math");
+                assert!(alias.is_none());
+                let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+                assert_eq!(names, vec!["add", "sqrt"]);
+            }
+            other => panic!("Expected use statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_the_offending_token() {
+        assert_eq!(parse_program("x = 1\nif x > 1\n    y = 2").unwrap_err(), "Expected ':' after if condition at line 2, column 9");
+        assert_eq!(parse_program("total = (1 + 2\nprint(total)").unwrap_err(), "Expected ')' after expression at line 2, column 1");
+        assert_eq!(parse_program("f(x) = 3").unwrap_err(), "Invalid assignment target at line 1, column 1");
+    }
+
+    #[test]
+    fn test_statement_spans_cover_their_tokens() {
+        let program = parse_program("def area(w, h):\n    return w * h\nresult = area(2, 3)").unwrap();
+        let span = program.statements[0].span().unwrap();
+        assert_eq!((span.line, span.column, span.end_line, span.end_column), (1, 5, 2, 17));
+        let span = program.statements[1].span().unwrap();
+        assert_eq!((span.line, span.column, span.end_line, span.end_column), (3, 1, 3, 14));
+        assert!(parse_expr("42").unwrap().span().is_none());
+    }
+
+    #[test]
+    fn test_parse_semicolons_and_single_line_blocks() {
+        let program = parse_program("a = 1; b = 2;\nif a < b: print(a); print(b)\nelse: return\nwhile a: a = a - 1").unwrap();
+        assert_eq!(program.statements.len(), 4);
+        match &program.statements[2] {
+            Statement::If { then_branch, else_branch: Some(else_branch), .. } => {
+                assert_eq!(then_branch.len(), 2);
+                assert!(matches!(else_branch[..], [Statement::Return { value: None }]));
+            }
+            other => panic!("Expected if statement, got {:?}", other),
+        }
+        assert!(matches!(&program.statements[3], Statement::While { body, .. } if body.len() == 1));
 
-}
\ No newline at end of file
+        // A one-line block ends with its line
+        let program = parse_program("def f(x): return x;\nf(1)").unwrap();
+        assert!(matches!(&program.statements[0], Statement::FunctionDeclaration { body, .. } if body.len() == 1));
+        assert_eq!(program.statements.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_multiline_brackets() {
+        let source = "def add(a,\n        b):\n    return a + b\nitems = [\n    add(1,\n        2),\n    3\n]\nd = {\n  \"a\": 1,  # first\n\n  \"b\": 2\n}\nprint(items)";
+        let program = parse_program(source).unwrap();
+        assert_eq!(program.statements.len(), 4);
+        assert!(matches!(&program.statements[0], Statement::FunctionDeclaration { parameters, .. } if parameters.len() == 2));
+        match &program.statements[1] {
+            Statement::VariableDeclaration { initializer: Some(Expression::Array(elements)), .. } => assert_eq!(elements.len(), 2),
+            other => panic!("Expected array assignment, got {:?}", other),
+        }
+        assert!(matches!(&program.statements[2],
+            Statement::VariableDeclaration { initializer: Some(Expression::Dictionary(pairs)), .. } if pairs.len() == 2));
+    }
+
+    #[test]
+    fn test_parse_trailing_commas() {
+        let source = "def add(a, b,):\n    return a + b\nitems = [\n    add(1, 2,),\n    new Point(3, 4,),\n]\nd = {\"a\": 1, \"b\": 2,}";
+        let program = parse_program(source).unwrap();
+        assert!(matches!(&program.statements[0], Statement::FunctionDeclaration { parameters, .. } if parameters.len() == 2));
+        match &program.statements[1] {
+            Statement::VariableDeclaration { initializer: Some(Expression::Array(elements)), .. } => {
+                assert!(matches!(&elements[0], Expression::Call { arguments, .. } if arguments.len() == 2));
+                assert!(matches!(&elements[1], Expression::NewInstance { arguments, .. } if arguments.len() == 2));
+            }
+            other => panic!("Expected array assignment, got {:?}", other),
+        }
+        assert!(matches!(&program.statements[2],
+            Statement::VariableDeclaration { initializer: Some(Expression::Dictionary(pairs)), .. } if pairs.len() == 2));
+
+        // A comma still has to follow something
+        assert!(parse_program("[,]").is_err());
+        assert!(parse_program("f(1,,)").is_err());
+    }
+
+    #[test]
+    fn test_parse_use_plugin_statement() {
+        let program = parse_program("use plugin \"lib/libshout.so\"\nuse plugin").unwrap();
+        assert!(matches!(&program.statements[0], Statement::UsePlugin { path } if path.lexeme == "lib/libshout.so"));
+        // Without a path it's a module like any other
+        assert!(matches!(&program.statements[1], Statement::Use { module, .. } if module == "plugin"));
+    }
+
+    #[test]
+    fn test_parse_lazy_use_statement() {
+        let program = parse_program("lazy use http as h").unwrap();
+        match &program.statements[0] {
+            Statement::Use { module, alias, lazy, .. } => {
+                assert_eq!(module, "http");
+                assert_eq!(*alias, Some("h".to_string()));
+                assert!(*lazy);
+            }
+            other => panic!("Expected use statement, got {:?}", other),
+        }
+    }
+}
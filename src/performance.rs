@@ -0,0 +1,425 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::builtins::hash_value;
+use crate::bytecode::{Arity, Chunk, Function, NativeFunction, OpCode, Value};
+use crate::source_map::SourceLocation;
+use crate::vm::VM;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Name of the native memoized wrappers look their arguments up with.
+/// Wrappers are plain Grease functions whose first constant is this native,
+/// which is how `cache_info`/`cache_clear` recognise them.
+const MEMO_LOOKUP: &str = "memo_lookup";
+
+/// Counters the VM keeps about its own work, reported by `perf_counters()`.
+#[derive(Debug, Clone, Default)]
+pub struct PerfCounters {
+    pub instructions: u64,
+    /// Arrays, tuples, dictionaries, objects and strings built by the VM.
+    pub allocations: u64,
+    /// Values are dropped as soon as they go out of scope, so the VM never
+    /// runs a collector; the counter is kept so scripts can rely on the key.
+    pub gc_cycles: u64,
+    pub global_lookups: u64,
+    pub lex_time: Duration,
+    pub parse_time: Duration,
+    pub compile_time: Duration,
+    pub run_time: Duration,
+    run_started: Option<Instant>,
+}
+
+impl PerfCounters {
+    /// Zero every counter. A script that is running keeps being timed from now.
+    pub fn reset(&mut self) {
+        let running = self.run_started.map(|_| Instant::now());
+        *self = PerfCounters::default();
+        self.run_started = running;
+    }
+
+    /// Start timing a run; returns false when an outer run is already timed.
+    pub(crate) fn start_run(&mut self) -> bool {
+        if self.run_started.is_some() {
+            return false;
+        }
+        self.run_started = Some(Instant::now());
+        true
+    }
+
+    pub(crate) fn finish_run(&mut self, started: bool) {
+        if started {
+            if let Some(run_started) = self.run_started.take() {
+                self.run_time += run_started.elapsed();
+            }
+        }
+    }
+
+    /// Run time including the run in progress, if any.
+    pub fn total_run_time(&self) -> Duration {
+        self.run_time + self.run_started.map_or(Duration::ZERO, |started| started.elapsed())
+    }
+}
+
+/// Results cached for one memoized function, keyed by the hash of the
+/// argument tuple.
+#[derive(Debug, Clone)]
+pub struct MemoCache {
+    function: Value,
+    max_size: Option<usize>,
+    entries: HashMap<u64, Vec<CacheEntry>>,
+    len: usize,
+    hits: u64,
+    misses: u64,
+    clock: u64,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    args: Value,
+    result: Value,
+    last_used: u64,
+}
+
+impl MemoCache {
+    fn new(function: Value, max_size: Option<usize>) -> Self {
+        MemoCache {
+            function,
+            max_size,
+            entries: HashMap::new(),
+            len: 0,
+            hits: 0,
+            misses: 0,
+            clock: 0,
+        }
+    }
+
+    fn lookup(&mut self, key: u64, args: &Value) -> Option<Value> {
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self.entries.get_mut(&key)?
+            .iter_mut()
+            .find(|entry| VM::values_equal(&entry.args, args))?;
+        entry.last_used = clock;
+        Some(entry.result.clone())
+    }
+
+    fn insert(&mut self, key: u64, args: Value, result: Value) {
+        if self.max_size.is_some_and(|max_size| self.len >= max_size) {
+            self.evict_least_recently_used();
+        }
+        self.entries.entry(key).or_default().push(CacheEntry {
+            args,
+            result,
+            last_used: self.clock,
+        });
+        self.len += 1;
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let oldest = self.entries.iter()
+            .flat_map(|(key, bucket)| bucket.iter().enumerate().map(move |(index, entry)| (entry.last_used, *key, index)))
+            .min();
+        if let Some((_, key, index)) = oldest {
+            let bucket = self.entries.get_mut(&key).expect("key was just found");
+            bucket.swap_remove(index);
+            if bucket.is_empty() {
+                self.entries.remove(&key);
+            }
+            self.len -= 1;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.len = 0;
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("perf_counters", 0, |vm, _args| {
+        let perf = &vm.perf;
+        let deterministic = vm.deterministic;
+        let millis = |duration: Duration| Value::Number(if deterministic { 0.0 } else { duration.as_secs_f64() * 1000.0 });
+        let mut counters = HashMap::new();
+        counters.insert("instructions".to_string(), Value::Number(perf.instructions as f64));
+        counters.insert("allocations".to_string(), Value::Number(perf.allocations as f64));
+        counters.insert("gc_cycles".to_string(), Value::Number(perf.gc_cycles as f64));
+        counters.insert("global_lookups".to_string(), Value::Number(perf.global_lookups as f64));
+        counters.insert("lex_ms".to_string(), millis(perf.lex_time));
+        counters.insert("parse_ms".to_string(), millis(perf.parse_time));
+        counters.insert("compile_ms".to_string(), millis(perf.compile_time));
+        counters.insert("run_ms".to_string(), millis(perf.total_run_time()));
+        Ok(Value::dictionary(counters))
+    });
+
+    vm.register_native("perf_reset", 0, |vm, _args| {
+        vm.perf.reset();
+        Ok(Value::Null)
+    });
+
+    vm.register_native("memoize", 1, |vm, args| memoize(vm, args[0].clone(), None));
+
+    vm.register_native("cache", 2, |vm, args| {
+        let max_size = match &args[1] {
+            Value::Null => None,
+            Value::Number(n) if *n >= 1.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => return Err("TypeError: cache expects a positive whole max_size or null".to_string()),
+        };
+        memoize(vm, args[0].clone(), max_size)
+    });
+
+    vm.register_native("cache_info", 1, |vm, args| {
+        let cache = memo_cache(vm, &args[0], "cache_info")?;
+        let mut info = HashMap::new();
+        info.insert("hits".to_string(), Value::Number(cache.hits as f64));
+        info.insert("misses".to_string(), Value::Number(cache.misses as f64));
+        info.insert("size".to_string(), Value::Number(cache.len as f64));
+        info.insert("max_size".to_string(), cache.max_size.map_or(Value::Null, |max_size| Value::Number(max_size as f64)));
+        Ok(Value::dictionary(info))
+    });
+
+    vm.register_native("cache_clear", 1, |vm, args| {
+        memo_cache(vm, &args[0], "cache_clear")?.clear();
+        Ok(Value::Null)
+    });
+}
+
+/// Wrap `function` in a Grease function of the same arity that answers
+/// repeated calls from the cache.
+fn memoize(vm: &mut VM, function: Value, max_size: Option<usize>) -> Result<Value, String> {
+    let (name, arity) = match &function {
+        Value::Function(function) => (function.name.clone(), function.arity),
+        Value::Closure(closure) => (closure.function.name.clone(), closure.function.arity),
+        Value::NativeFunction(function) => match function.arity.exact() {
+            Some(arity) => (function.name.clone(), arity),
+            None => return Err(format!("TypeError: Cannot memoize '{}': it takes {}", function.name, function.arity)),
+        },
+        _ => return Err("TypeError: memoize expects a function".to_string()),
+    };
+    if arity > u8::MAX as usize {
+        return Err(format!("TypeError: Cannot memoize '{}': too many parameters", name));
+    }
+
+    let id = vm.memo_caches.len();
+    vm.memo_caches.push(MemoCache::new(function, max_size));
+
+    // The wrapper calls the function itself rather than through a native,
+    // so memoized recursion runs in one VM loop instead of nesting a Rust
+    // call per level:
+    //
+    //     (value, hit) = memo_lookup(id, (arg0, arg1, ...))
+    //     if hit: return value
+    //     return memo_store(id, (arg0, arg1, ...), function(arg0, arg1, ...))
+    //
+    // It has no source of its own.
+    let mut chunk = Chunk::new();
+    chunk.source_map.function = Some(name.clone());
+    let unknown = SourceLocation::default();
+    let native = |name: &str, arity: usize, function| Value::NativeFunction(NativeFunction { name: name.to_string(), arity: Arity::from(arity), function });
+    let lookup = chunk.add_constant(native(MEMO_LOOKUP, 2, memo_lookup)) as u8;
+    let id_constant = chunk.add_constant(Value::Number(id as f64)) as u8;
+    let store = chunk.add_constant(native("memo_store", 3, memo_store)) as u8;
+    let function_constant = chunk.add_constant(vm.memo_caches[id].function.clone()) as u8;
+    let write_args = |chunk: &mut Chunk| {
+        for slot in 0..arity {
+            chunk.write(OpCode::GetLocal.to_byte(), unknown);
+            chunk.write(slot as u8, unknown);
+        }
+    };
+    let write = |chunk: &mut Chunk, op: OpCode, operand: Option<u8>| {
+        chunk.write(op.to_byte(), unknown);
+        if let Some(operand) = operand {
+            chunk.write(operand, unknown);
+        }
+    };
+
+    write(&mut chunk, OpCode::Constant, Some(lookup));
+    write(&mut chunk, OpCode::Constant, Some(id_constant));
+    write_args(&mut chunk);
+    write(&mut chunk, OpCode::Tuple, Some(arity as u8));
+    write(&mut chunk, OpCode::Call, Some(2));
+    write(&mut chunk, OpCode::Unpack, Some(2));
+    // On a hit, skip to the POP and RETURN that follow
+    write(&mut chunk, OpCode::JumpIfFalse, Some(0));
+    chunk.write(2, unknown);
+    write(&mut chunk, OpCode::Pop, None);
+    write(&mut chunk, OpCode::Return, None);
+
+    write(&mut chunk, OpCode::Pop, None);
+    write(&mut chunk, OpCode::Pop, None);
+    write(&mut chunk, OpCode::Constant, Some(store));
+    write(&mut chunk, OpCode::Constant, Some(id_constant));
+    write_args(&mut chunk);
+    write(&mut chunk, OpCode::Tuple, Some(arity as u8));
+    write(&mut chunk, OpCode::Constant, Some(function_constant));
+    write_args(&mut chunk);
+    write(&mut chunk, OpCode::Call, Some(arity as u8));
+    write(&mut chunk, OpCode::Call, Some(3));
+    write(&mut chunk, OpCode::Return, None);
+
+    Ok(Value::Function(Function { name, arity, chunk, module: None }))
+}
+
+/// `memo_lookup(id, args)`: `(result, true)` if the cache has a result for
+/// `args`, otherwise `(null, false)`.
+fn memo_lookup(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let (id, key) = memo_key(vm, &args)?;
+    let cache = &mut vm.memo_caches[id];
+    match cache.lookup(key, &args[1]) {
+        Some(result) => {
+            cache.hits += 1;
+            Ok(Value::Tuple(vec![result, Value::Boolean(true)]))
+        }
+        None => {
+            cache.misses += 1;
+            Ok(Value::Tuple(vec![Value::Null, Value::Boolean(false)]))
+        }
+    }
+}
+
+/// `memo_store(id, args, result)`: cache `result` for `args` and return it.
+fn memo_store(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let (id, key) = memo_key(vm, &args)?;
+    vm.memo_caches[id].insert(key, args[1].clone(), args[2].clone());
+    Ok(args[2].clone())
+}
+
+/// The cache `args[0]` names and the hash of the argument tuple `args[1]`.
+fn memo_key(vm: &VM, args: &[Value]) -> Result<(usize, u64), String> {
+    let id = match &args[0] {
+        Value::Number(n) if (*n as usize) < vm.memo_caches.len() => *n as usize,
+        _ => return Err("Invalid memoization cache".to_string()),
+    };
+    if !matches!(&args[1], Value::Tuple(_)) {
+        return Err("Invalid memoized arguments".to_string());
+    }
+    Ok((id, hash_value(&args[1])?))
+}
+
+/// The cache of memoized function `value`. Caches don't outlive
+/// `Grease::reset`, so a wrapper kept from before one has none.
+fn memo_cache<'a>(vm: &'a mut VM, value: &Value, name: &str) -> Result<&'a mut MemoCache, String> {
+    if let Value::Function(function) = value {
+        if let [Value::NativeFunction(native), Value::Number(id), ..] = function.chunk.constants.as_slice() {
+            if native.name == MEMO_LOOKUP {
+                return vm.memo_caches.get_mut(*id as usize)
+                    .ok_or_else(|| format!("{}: the cache of '{}' was discarded when the runtime was reset", name, function.name));
+            }
+        }
+    }
+    Err(format!("TypeError: {} expects a memoized function", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    fn run(source: &str) -> Grease {
+        let mut grease = Grease::new();
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        grease
+    }
+
+    fn info_number(grease: &Grease, key: &str) -> f64 {
+        match grease.vm.globals.get("info") {
+            Some(Value::Dictionary(info)) => match info.lock().unwrap().get(key) {
+                Some(Value::Number(n)) => *n,
+                other => panic!("Expected number for {}, got {:?}", key, other),
+            },
+            other => panic!("Expected info dictionary, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_memoize_decorator_caches_recursive_calls() {
+        let grease = run("@memoize\ndef fib(n):\n    if n < 2:\n        return n\n    return fib(n - 1) + fib(n - 2)\nresult = fib(30)\ninfo = cache_info(fib)");
+        assert!(matches!(grease.vm.globals.get("result"), Some(Value::Number(n)) if *n == 832040.0));
+        // Each n from 0 to 30 is computed exactly once
+        assert_eq!(info_number(&grease, "misses"), 31.0);
+        assert_eq!(info_number(&grease, "size"), 31.0);
+        assert_eq!(info_number(&grease, "hits"), 28.0);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let grease = run("def square(x):\n    return x * x\nsq = cache(square, 2)\na = sq(1)\nb = sq(2)\nc = sq(1)\nd = sq(3)\ne = sq(2)\ninfo = cache_info(sq)");
+        assert!(matches!(grease.vm.globals.get("e"), Some(Value::Number(n)) if *n == 4.0));
+        // sq(3) evicted 2, so the final sq(2) misses again
+        assert_eq!(info_number(&grease, "hits"), 1.0);
+        assert_eq!(info_number(&grease, "misses"), 4.0);
+        assert_eq!(info_number(&grease, "size"), 2.0);
+        assert_eq!(info_number(&grease, "max_size"), 2.0);
+    }
+
+    #[test]
+    fn test_cache_clear_and_unhashable_arguments() {
+        let grease = run("def first(items):\n    return items[0]\nf = memoize(first)\na = f([1, 2])\nb = f([1, 2])\ncache_clear(f)\ninfo = cache_info(f)");
+        assert!(matches!(grease.vm.globals.get("b"), Some(Value::Number(n)) if *n == 1.0));
+        assert_eq!(info_number(&grease, "hits"), 0.0);
+        assert_eq!(info_number(&grease, "size"), 0.0);
+
+        let mut grease = Grease::new();
+        let result = grease.run("f = memoize(native_add)\nf(native_add, 1)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(msg) if msg.contains("unhashable")));
+    }
+
+    #[test]
+    fn test_memoized_recursion_does_not_nest_native_calls() {
+        // Runs on the small test-thread stack: each level is a VM frame,
+        // not a Rust call
+        let grease = run("@memoize\ndef count(n):\n    if n == 0:\n        return 0\n    return count(n - 1) + 1\nresult = count(5000)");
+        assert!(matches!(grease.vm.globals.get("result"), Some(Value::Number(n)) if *n == 5000.0));
+    }
+
+    #[test]
+    fn test_cache_info_after_reset_is_an_error() {
+        let mut grease = run("def square(x):\n    return x * x\nsq = memoize(square)");
+        let sq = grease.vm.globals.get("sq").cloned().unwrap();
+        grease.reset().unwrap();
+        for name in ["cache_info", "cache_clear"] {
+            let native = grease.vm.globals.get(name).cloned().unwrap();
+            let error = grease.vm.call_function(native, vec![sq.clone()]).unwrap_err();
+            assert!(error.contains("discarded"), "{}", error);
+        }
+        let error = grease.vm.call_function(sq, vec![Value::Number(2.0)]).unwrap_err();
+        assert!(error.contains("Invalid memoization cache"), "{}", error);
+    }
+
+    #[test]
+    fn test_perf_counters_track_execution() {
+        let grease = run("x = 1\ny = [x, x]\nbefore = perf_counters()\nperf_reset()\nfor i in [1, 2, 3]:\n    z = x + i\nafter = perf_counters()");
+        let counter = |name: &str, key: &str| match grease.vm.globals.get(name) {
+            Some(Value::Dictionary(counters)) => match counters.lock().unwrap().get(key) {
+                Some(Value::Number(n)) => *n,
+                other => panic!("Expected number for {}, got {:?}", key, other),
+            },
+            other => panic!("Expected counters dictionary, got {:?}", other),
+        };
+        assert!(counter("before", "instructions") > 0.0);
+        assert!(counter("before", "allocations") >= 1.0);
+        assert!(counter("before", "lex_ms") >= 0.0);
+        assert_eq!(counter("before", "gc_cycles"), 0.0);
+        // The reset cleared the compile time and the counts from before it
+        assert_eq!(counter("after", "compile_ms"), 0.0);
+        // `x` and `i` three times each, plus `perf_counters` itself
+        assert_eq!(counter("after", "global_lookups"), 7.0);
+        assert!(counter("after", "instructions") < counter("before", "instructions") + 100.0);
+        assert!(grease.vm.perf.run_time > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_deterministic_runs_report_no_timings() {
+        use crate::sandbox::{require, Capability};
+        let mut grease = Grease::new().with_deterministic(true);
+        let source = "total = 0\nfor i in [1, 2, 3]:\n    total = total + i\ncounters = perf_counters()\ntimes = [counters[\"lex_ms\"], counters[\"run_ms\"]]";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("times").unwrap()), "[0, 0]");
+        assert!(require(&grease.vm, Capability::Process, "spy").unwrap_err().contains("deterministic mode"));
+        assert!(require(&grease.vm, Capability::Filesystem, "read").is_ok());
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Native plugins: natives shipped as a shared library (`.so`, `.dylib` or
+//! `.dll`) and loaded by scripts with `use plugin "path/to/libfoo.so"`, so
+//! third parties can extend the interpreter without rebuilding it.
+//!
+//! A plugin is a `cdylib` crate depending on `grease` that declares its
+//! entry point with [`grease_plugin!`](crate::grease_plugin):
+//!
+//! ```text
+//! fn init(vm: &mut grease::vm::VM) {
+//!     vm.register_native("shout", 1, |_vm, args| Ok(args[0].clone()));
+//! }
+//! grease::grease_plugin!(init);
+//! ```
+//!
+//! The entry point receives the VM itself, so a plugin must be built with
+//! the same Grease version (and compiler) as the interpreter loading it;
+//! the loader checks the version and [`ABI_VERSION`] before calling it.
+//! `use plugin "foo"` without a path looks for the platform's file name for
+//! `foo` (e.g. `libfoo.so`) in `~/.grease/plugins`.
+
+use crate::vm::VM;
+use std::ffi::CStr;
+use std::path::PathBuf;
+
+/// Revision of the plugin entry points; bumped when their signatures change.
+pub const ABI_VERSION: u32 = 1;
+
+/// The Grease version, NUL-terminated for `grease_plugin_version`.
+pub const VERSION: &CStr = match CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()) {
+    Ok(version) => version,
+    Err(_) => panic!("the package version contains a NUL byte"),
+};
+
+/// Export `init`, a `fn(&mut VM)`, as the plugin's entry point along with
+/// the ABI and version it was built for.
+#[macro_export]
+macro_rules! grease_plugin {
+    ($init:path) => {
+        #[no_mangle]
+        pub extern "C" fn grease_plugin_abi() -> u32 {
+            $crate::plugin::ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn grease_plugin_version() -> *const ::std::os::raw::c_char {
+            $crate::plugin::VERSION.as_ptr()
+        }
+
+        /// # Safety
+        ///
+        /// `vm` must point to a live VM of the same Grease version.
+        #[no_mangle]
+        pub unsafe extern "C" fn grease_plugin_init(vm: *mut $crate::vm::VM) {
+            let init: fn(&mut $crate::vm::VM) = $init;
+            init(unsafe { &mut *vm });
+        }
+    };
+}
+
+/// Directories `use plugin "name"` searches: `~/.grease/plugins`.
+pub fn plugin_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    home.map(|home| PathBuf::from(home).join(".grease").join("plugins")).into_iter().collect()
+}
+
+/// The plugin name a library file in a plugin directory provides, e.g.
+/// `foo` for `libfoo.so`.
+pub fn plugin_name(file_name: &str) -> Option<&str> {
+    let stem = file_name.strip_suffix(std::env::consts::DLL_SUFFIX)?;
+    Some(stem.strip_prefix(std::env::consts::DLL_PREFIX).unwrap_or(stem))
+}
+
+/// The library `use plugin "spec"` loads: a path (relative to the script)
+/// when `spec` has a directory or extension, else the named plugin in the
+/// plugin directories.
+pub fn resolve(vm: &VM, spec: &str, dirs: &[PathBuf]) -> Result<PathBuf, String> {
+    let path = std::path::Path::new(spec);
+    if path.components().count() > 1 || path.extension().is_some() {
+        return Ok(vm.resolve_path(spec));
+    }
+    let file_name = format!("{}{}{}", std::env::consts::DLL_PREFIX, spec, std::env::consts::DLL_SUFFIX);
+    dirs.iter().map(|dir| dir.join(&file_name)).find(|path| path.is_file()).ok_or_else(|| {
+        let searched: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+        format!("ImportError: Plugin '{}' not found. Searched for {} in {}", spec, file_name, searched.join(", "))
+    })
+}
+
+/// Load the plugin `spec` names into `vm`, unless this VM already has.
+#[cfg(feature = "plugins")]
+pub fn load(vm: &mut VM, spec: &str) -> Result<(), String> {
+    use crate::sandbox::{require, Capability};
+    use libloading::{Library, Symbol};
+    use std::os::raw::c_char;
+
+    require(vm, Capability::Plugins, "use plugin")?;
+    let path = resolve(vm, spec, &plugin_dirs())?;
+    let path = path.canonicalize().map_err(|e| format!("ImportError: Failed to load plugin '{}': {}", path.display(), e))?;
+    if vm.plugins.contains(&path) {
+        return Ok(());
+    }
+    let failed = |reason: String| format!("ImportError: Failed to load plugin '{}': {}", path.display(), reason);
+
+    // SAFETY: loading a library runs its initializers; a script naming a
+    // library trusts it as it would trust a native module built in
+    let library = unsafe { Library::new(&path) }.map_err(|e| failed(e.to_string()))?;
+    unsafe {
+        let abi: Symbol<extern "C" fn() -> u32> = library
+            .get(b"grease_plugin_abi\0")
+            .map_err(|_| failed("not a Grease plugin (no grease_plugin_abi)".to_string()))?;
+        if abi() != ABI_VERSION {
+            return Err(failed(format!("built for plugin ABI {}, this interpreter uses {}", abi(), ABI_VERSION)));
+        }
+        let version: Symbol<extern "C" fn() -> *const c_char> =
+            library.get(b"grease_plugin_version\0").map_err(|e| failed(e.to_string()))?;
+        let version = CStr::from_ptr(version()).to_string_lossy().into_owned();
+        if version != crate::VERSION {
+            return Err(failed(format!("built for Grease {}, this is Grease {}", version, crate::VERSION)));
+        }
+        let init: Symbol<unsafe extern "C" fn(*mut VM)> =
+            library.get(b"grease_plugin_init\0").map_err(|e| failed(e.to_string()))?;
+        init(vm);
+    }
+    // The natives it registered point into the library, and values holding
+    // them can outlive this VM, so it stays loaded for the process
+    std::mem::forget(library);
+    vm.plugins.push(path);
+    Ok(())
+}
+
+#[cfg(not(feature = "plugins"))]
+pub fn load(_vm: &mut VM, spec: &str) -> Result<(), String> {
+    Err(format!("ImportError: Can't load plugin '{}': plugin support is disabled (the `plugins` feature)", spec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+
+    #[test]
+    fn test_resolve_plugin_paths() {
+        let dir = std::env::temp_dir().join(format!("grease_plugins_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_name = format!("{}shout{}", DLL_PREFIX, DLL_SUFFIX);
+        std::fs::write(dir.join(&file_name), "").unwrap();
+        let vm = VM::new();
+
+        assert_eq!(resolve(&vm, "shout", std::slice::from_ref(&dir)), Ok(dir.join(&file_name)));
+        assert_eq!(plugin_name(&file_name), Some("shout"));
+        assert_eq!(resolve(&vm, "lib/libfoo.so", &[]), Ok(PathBuf::from("lib/libfoo.so")));
+        assert!(resolve(&vm, "whisper", std::slice::from_ref(&dir)).unwrap_err().starts_with("ImportError: Plugin 'whisper' not found"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "plugins")]
+    fn test_load_rejects_non_plugins() {
+        let mut grease = crate::Grease::new();
+        let result = grease.run("use plugin \"Cargo.toml\"").unwrap();
+        assert!(matches!(result, crate::InterpretResult::RuntimeError(e) if e.starts_with("ImportError")));
+
+        let mut grease = crate::Grease::new().with_capabilities(crate::Capabilities::none());
+        let result = grease.run("try:\n    use plugin \"Cargo.toml\"\ncatch IOError as e:\n    denied = e.message").unwrap();
+        assert_eq!(result, crate::InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("denied"), Some(crate::Value::String(e)) if e.contains("plugins capability")));
+    }
+
+    #[test]
+    #[cfg(not(feature = "plugins"))]
+    fn test_load_reports_plugins_disabled() {
+        let mut grease = crate::Grease::new();
+        let result = grease.run("use plugin \"shout\"").unwrap();
+        assert_eq!(
+            result,
+            crate::InterpretResult::RuntimeError("ImportError: Can't load plugin 'shout': plugin support is disabled (the `plugins` feature)".to_string())
+        );
+    }
+}
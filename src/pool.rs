@@ -0,0 +1,143 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! A fixed set of interpreters shared between threads, for hosts such as
+//! web servers that run many short scripts at once. Each interpreter lives
+//! on its own worker thread and is built there by the host's function, so
+//! they all start configured the same way. Jobs wait on one queue that
+//! whichever worker is free takes from next, and the interpreter is reset
+//! after every job, so runs never see each other's globals or open files:
+//!
+//! ```text
+//! let pool = GreasePool::new(4, || Grease::new().with_limits(limits));
+//! let result = pool.run("x = 6 * 7")?;            // from any thread
+//! let x = pool.execute(|grease| { ... })?;       // or with the interpreter
+//! ```
+
+use crate::grease::Grease;
+use crate::vm::{InterpretResult, RunOutcome};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+type Job = Box<dyn FnOnce(&mut Grease) + Send>;
+
+pub struct GreasePool {
+    /// Queue the workers take jobs from; `None` once the pool is shutting
+    /// down.
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl GreasePool {
+    /// Start `size` workers (at least one), each with an interpreter made
+    /// by `build`.
+    pub fn new(size: usize, build: impl Fn() -> Grease + Send + Sync + 'static) -> Self {
+        let (jobs, queue) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let build = Arc::new(build);
+        let workers = (0..size.max(1)).map(|_| {
+            let queue = queue.clone();
+            let build = build.clone();
+            std::thread::spawn(move || work(&queue, &*build))
+        });
+        GreasePool { jobs: Some(jobs), workers: workers.collect() }
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Run `job` on the next free interpreter and wait for what it returns.
+    pub fn execute<R: Send + 'static>(&self, job: impl FnOnce(&mut Grease) -> R + Send + 'static) -> Result<R, String> {
+        let (reply, result) = mpsc::channel();
+        let job: Job = Box::new(move |grease| {
+            let _ = reply.send(job(grease));
+        });
+        let jobs = self.jobs.as_ref().ok_or("The pool has shut down")?;
+        jobs.send(job).map_err(|_| "The pool has shut down".to_string())?;
+        result.recv().map_err(|_| "The interpreter panicked while running the job".to_string())
+    }
+
+    /// [`Grease::run`] on the next free interpreter.
+    pub fn run(&self, source: &str) -> Result<InterpretResult, String> {
+        let source = source.to_string();
+        self.execute(move |grease| grease.run(&source))?
+    }
+
+    /// [`Grease::evaluate`] on the next free interpreter.
+    pub fn evaluate(&self, source: &str) -> Result<RunOutcome, String> {
+        let source = source.to_string();
+        self.execute(move |grease| grease.evaluate(&source))?
+    }
+}
+
+/// A worker's loop: take jobs until the pool is dropped.
+fn work(queue: &Mutex<Receiver<Job>>, build: &(dyn Fn() -> Grease + Send + Sync)) {
+    let mut grease = build();
+    loop {
+        // The lock is released before the job runs, so others can take jobs
+        let job = match queue.lock() {
+            Ok(queue) => queue.recv(),
+            Err(_) => return,
+        };
+        let Ok(job) = job else { return };
+        match panic::catch_unwind(AssertUnwindSafe(|| job(&mut grease))) {
+            // Nobody is left to report errors closing the job's resources to
+            Ok(()) => drop(grease.reset()),
+            // The interpreter may be half way through an instruction
+            Err(_) => grease = build(),
+        }
+    }
+}
+
+impl Drop for GreasePool {
+    /// Finish the jobs already queued, then stop the workers.
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::Value;
+
+    #[test]
+    fn test_pool_runs_jobs_from_many_threads() {
+        let pool = GreasePool::new(2, || {
+            let mut grease = Grease::new();
+            grease.register_native("boom", 0, |_vm, _args| panic!("native panicked"));
+            grease
+        });
+        assert_eq!(pool.size(), 2);
+        std::thread::scope(|scope| {
+            for n in 0..8 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    let outcome = pool.evaluate(&format!("x = {}\nx * 2", n)).unwrap();
+                    assert!(matches!(outcome.value, Value::Number(v) if v == (n * 2) as f64));
+                });
+            }
+        });
+
+        // Each job starts from a fresh interpreter
+        let result = pool.run("y = x").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("Undefined variable 'x'")));
+        let count = pool.execute(|grease| {
+            grease.run("z = 1").unwrap();
+            grease.vm.globals.len()
+        }).unwrap();
+        assert_eq!(pool.execute(|grease| grease.vm.globals.len()).unwrap(), count - 1);
+
+        // A panicking job fails alone; the worker carries on
+        assert!(pool.run("boom()").unwrap_err().contains("panicked"));
+        for _ in 0..4 {
+            assert_eq!(pool.run("w = 1").unwrap(), InterpretResult::Ok);
+        }
+    }
+}
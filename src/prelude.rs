@@ -0,0 +1,33 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! The stable embedding API.
+//!
+//! ```
+//! use grease::prelude::*;
+//!
+//! let mut grease = Grease::new();
+//! grease.register_native("double", 1, |_vm, args| match &args[0] {
+//!     Value::Number(n) => Ok(Value::Number(n * 2.0)),
+//!     _ => Err("double expects a number".to_string()),
+//! });
+//! assert_eq!(grease.run("x = double(21)"), Ok(InterpretResult::Ok));
+//! assert!(matches!(grease.vm.globals.get("x"), Some(Value::Number(n)) if *n == 42.0));
+//! ```
+//!
+//! Errors are reported as `String` messages: compile and load failures as
+//! the `Err` of [`Grease::run`], script failures as
+//! [`InterpretResult::RuntimeError`], and native functions return `Err` to
+//! raise one.
+
+pub use crate::bytecode::{Arity, NativeFn, Value, VARIADIC};
+pub use crate::config::ProjectConfig;
+pub use crate::globals::Globals;
+pub use crate::grease::Grease;
+pub use crate::linter::LintError;
+pub use crate::modules::ModuleSource;
+#[cfg(not(target_os = "wasi"))]
+pub use crate::pool::GreasePool;
+pub use crate::sandbox::{Capabilities, Limits};
+pub use crate::source_map::{SourceLocation, TraceFrame};
+pub use crate::vm::{InterpretResult, RunOutcome, VM};
@@ -0,0 +1,246 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! The `py` module: Python builtins and methods as functions, so snippets
+//! written for Python port with few edits. `len(xs)` becomes `py.len(xs)`
+//! (or `len(xs)` after `from py use len`), and methods take their object
+//! first: `s.upper()` is `py.upper(s)`, `d.items()` is `py.items(d)`.
+//!
+//! `py.range` and `py.split` take Python's optional arguments; `py.get`
+//! needs its default. There's no `update` or `pop`; use `dict_merge` and the
+//! `d.delete(key)` method. The linter's `python-compat` rule points out
+//! where Grease has its own way.
+
+use crate::bytecode::{Arity, NativeFn, NativeFunction, Value};
+use crate::methods::sorted_keys;
+use crate::vm::VM;
+use std::collections::HashMap;
+use std::sync::MutexGuard;
+
+const FUNCTIONS: &[(&str, Arity, NativeFn)] = &[
+    ("len", Arity { min: 1, max: 1 }, len),
+    ("range", Arity { min: 1, max: 3 }, crate::builtins::range),
+    ("enumerate", Arity { min: 1, max: 1 }, crate::builtins::enumerate),
+    ("zip", Arity { min: 2, max: 2 }, crate::builtins::zip),
+    ("sum", Arity { min: 1, max: 1 }, crate::builtins::sum),
+    ("min", Arity { min: 1, max: 1 }, crate::builtins::min),
+    ("max", Arity { min: 1, max: 1 }, crate::builtins::max),
+    ("sorted", Arity { min: 1, max: 1 }, crate::builtins::sorted),
+    ("str", Arity { min: 1, max: 1 }, str),
+    ("int", Arity { min: 1, max: 1 }, int),
+    ("float", Arity { min: 1, max: 1 }, float),
+    ("keys", Arity { min: 1, max: 1 }, keys),
+    ("values", Arity { min: 1, max: 1 }, values),
+    ("items", Arity { min: 1, max: 1 }, items),
+    ("get", Arity { min: 3, max: 3 }, get),
+    ("upper", Arity { min: 1, max: 1 }, upper),
+    ("lower", Arity { min: 1, max: 1 }, lower),
+    ("strip", Arity { min: 1, max: 1 }, strip),
+    ("split", Arity { min: 1, max: 2 }, split),
+    ("join", Arity { min: 2, max: 2 }, join),
+    ("startswith", Arity { min: 2, max: 2 }, startswith),
+    ("endswith", Arity { min: 2, max: 2 }, endswith),
+    ("replace", Arity { min: 3, max: 3 }, replace),
+    ("find", Arity { min: 2, max: 2 }, find),
+];
+
+/// Register the `py` module's natives, which `VM::new` leaves until a
+/// script uses it.
+pub fn register_natives(vm: &mut VM) {
+    let members = FUNCTIONS.iter().map(|&(name, arity, function)| {
+        (name.to_string(), Value::NativeFunction(NativeFunction { name: name.to_string(), arity, function }))
+    });
+    vm.modules.insert("py".to_string(), members.collect());
+}
+
+fn items_of(function: &str, value: &Value) -> Result<Vec<Value>, String> {
+    match value {
+        Value::Array(items) => Ok(items.lock().unwrap().clone()),
+        Value::Tuple(items) => Ok(items.clone()),
+        _ => Err(format!("TypeError: py.{} expects an array", function)),
+    }
+}
+
+fn string_of<'a>(function: &str, value: &'a Value) -> Result<&'a str, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(format!("TypeError: py.{} expects a string", function)),
+    }
+}
+
+fn dictionary_of<'a>(function: &str, value: &'a Value) -> Result<MutexGuard<'a, HashMap<String, Value>>, String> {
+    match value {
+        Value::Dictionary(entries) => Ok(entries.lock().unwrap()),
+        _ => Err(format!("TypeError: py.{} expects a dictionary", function)),
+    }
+}
+
+fn len(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let len = match &args[0] {
+        Value::Array(items) => items.lock().unwrap().len(),
+        Value::Tuple(items) => items.len(),
+        Value::String(s) => s.chars().count(),
+        Value::Dictionary(entries) => entries.lock().unwrap().len(),
+        Value::Bytes(bytes) => bytes.len(),
+        _ => return Err("TypeError: py.len expects an array, string, bytes or dictionary".to_string()),
+    };
+    Ok(Value::Number(len as f64))
+}
+
+fn str(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::String(VM::format_value(&args[0])))
+}
+
+fn int(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Number(n) => Ok(Value::Number(n.trunc())),
+        Value::Boolean(b) => Ok(Value::Number(*b as u8 as f64)),
+        Value::String(s) => s.trim().parse::<i64>()
+            .map(|n| Value::Number(n as f64))
+            .map_err(|_| format!("py.int can't parse '{}'", s)),
+        _ => Err("TypeError: py.int expects a number, boolean or string".to_string()),
+    }
+}
+
+fn float(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::String(s) => s.trim().parse::<f64>().map(Value::Number).map_err(|_| format!("py.float can't parse '{}'", s)),
+        _ => Err("TypeError: py.float expects a number or string".to_string()),
+    }
+}
+
+fn keys(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let entries = dictionary_of("keys", &args[0])?;
+    Ok(Value::array(sorted_keys(&entries).into_iter().map(|key| Value::String(key.clone())).collect()))
+}
+
+fn values(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let entries = dictionary_of("values", &args[0])?;
+    Ok(Value::array(sorted_keys(&entries).into_iter().map(|key| entries[key].clone()).collect()))
+}
+
+fn items(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let entries = dictionary_of("items", &args[0])?;
+    Ok(Value::array(sorted_keys(&entries).into_iter()
+        .map(|key| Value::Tuple(vec![Value::String(key.clone()), entries[key].clone()]))
+        .collect()))
+}
+
+fn get(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let entries = dictionary_of("get", &args[0])?;
+    let key = string_of("get", &args[1])?;
+    Ok(entries.get(key).cloned().unwrap_or_else(|| args[2].clone()))
+}
+
+fn upper(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::String(string_of("upper", &args[0])?.to_uppercase()))
+}
+
+fn lower(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::String(string_of("lower", &args[0])?.to_lowercase()))
+}
+
+fn strip(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::String(string_of("strip", &args[0])?.trim().to_string()))
+}
+
+/// `py.split(s[, sep])`; without `sep`, or with null, splits on runs of
+/// whitespace.
+fn split(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let s = string_of("split", &args[0])?;
+    let parts: Vec<Value> = match args.get(1).unwrap_or(&Value::Null) {
+        Value::Null => s.split_whitespace().map(|part| Value::String(part.to_string())).collect(),
+        Value::String(sep) if !sep.is_empty() => s.split(sep.as_str()).map(|part| Value::String(part.to_string())).collect(),
+        _ => return Err("TypeError: py.split expects a non-empty separator string or null".to_string()),
+    };
+    Ok(Value::array(parts))
+}
+
+/// `py.join(sep, items)`, as Python's `sep.join(items)`.
+fn join(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let sep = string_of("join", &args[0])?;
+    let items = items_of("join", &args[1])?;
+    let parts = items.iter().map(|item| string_of("join", item)).collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::String(parts.join(sep)))
+}
+
+fn startswith(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Boolean(string_of("startswith", &args[0])?.starts_with(string_of("startswith", &args[1])?)))
+}
+
+fn endswith(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Boolean(string_of("endswith", &args[0])?.ends_with(string_of("endswith", &args[1])?)))
+}
+
+fn replace(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let (s, old, new) = (string_of("replace", &args[0])?, string_of("replace", &args[1])?, string_of("replace", &args[2])?);
+    Ok(Value::String(s.replace(old, new)))
+}
+
+/// The character index of the first `sub` in `s`, or -1.
+fn find(_vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let (s, sub) = (string_of("find", &args[0])?, string_of("find", &args[1])?);
+    Ok(Value::Number(s.find(sub).map_or(-1.0, |byte| s[..byte].chars().count() as f64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bytecode::Value;
+    use crate::{Grease, InterpretResult};
+
+    fn run(source: &str) -> Grease {
+        let mut grease = Grease::new();
+        assert_eq!(grease.run(source), Ok(InterpretResult::Ok));
+        grease
+    }
+
+    fn formatted(grease: &Grease, name: &str) -> String {
+        crate::vm::VM::format_value(grease.vm.globals.get(name).unwrap())
+    }
+
+    #[test]
+    fn test_py_builtins() {
+        let grease = run("use py\n\
+            n = py.len(\"héllo\")\n\
+            r = []\n\
+            for i in py.range(10, 0, -3):\n    r.push(i)\n\
+            count = sorted(py.range(4))\n\
+            e = py.enumerate([\"a\", \"b\"])\n\
+            z = py.zip([1, 2, 3], [\"x\", \"y\"])\n\
+            stats = [py.sum([1, 2, 3]), py.min([3, 1, 2]), py.max([\"b\", \"c\", \"a\"])]\n\
+            s = py.sorted([3, 1, 2])\n\
+            converted = [py.int(\"42\"), py.float(\" 2.5\"), py.str(7) + \"!\"]");
+        assert_eq!(formatted(&grease, "n"), "5");
+        assert_eq!(formatted(&grease, "r"), "[10, 7, 4, 1]");
+        assert_eq!(formatted(&grease, "count"), "[0, 1, 2, 3]");
+        assert_eq!(formatted(&grease, "e"), "[(0, a), (1, b)]");
+        assert_eq!(formatted(&grease, "z"), "[(1, x), (2, y)]");
+        assert_eq!(formatted(&grease, "stats"), "[6, 1, c]");
+        assert_eq!(formatted(&grease, "s"), "[1, 2, 3]");
+        assert_eq!(formatted(&grease, "converted"), "[42, 2.5, 7!]");
+    }
+
+    #[test]
+    fn test_py_methods() {
+        let grease = run("from py use split, join, items, get\n\
+            use py\n\
+            words = split(\"  to be  or \")\n\
+            csv = join(\",\", split(\"a-b-c\", \"-\"))\n\
+            d = {\"b\": 2, \"a\": 1}\n\
+            pairs = items(d)\n\
+            missing = get(d, \"z\", 0)\n\
+            text = [py.upper(\"ab\"), py.strip(\" x \"), py.replace(\"aaa\", \"a\", \"b\"), py.find(\"héllo\", \"l\")]\n\
+            checks = [py.startswith(\"grease\", \"gr\"), py.endswith(\"grease\", \"x\")]");
+        assert_eq!(formatted(&grease, "words"), "[to, be, or]");
+        assert_eq!(formatted(&grease, "csv"), "a,b,c");
+        assert_eq!(formatted(&grease, "pairs"), "[(a, 1), (b, 2)]");
+        assert!(matches!(grease.vm.globals.get("missing"), Some(Value::Number(n)) if *n == 0.0));
+        assert_eq!(formatted(&grease, "text"), "[AB, x, bbb, 2]");
+        assert_eq!(formatted(&grease, "checks"), "[true, false]");
+
+        let mut grease = Grease::new();
+        let result = grease.run("use py\npy.len(5)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("py.len expects")));
+    }
+}
@@ -1,12 +1,47 @@
 // Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
 // SPDX-License-Identifier: Apache-2.0
 
+//! The interactive prompt. With the `repl` feature, input is edited with
+//! rustyline and colored live from the lexer: keywords, strings, numbers
+//! and comments, the bracket matching the one at the cursor, and a red
+//! underline where the line stops lexing or parsing. Errors at the end of
+//! the line only mean it isn't finished, so those stay unmarked.
+//!
+//! A startup file, `~/.greaserc` for `grease`, runs before the first prompt
+//! to define helpers and `use` modules for the session. Setting the global
+//! `repl_prompt` to a string changes the prompt.
+//!
+//! Commands starting with `:` give a lightweight notebook: `:edit` opens
+//! `$EDITOR` on a scratch buffer and runs what was saved as a numbered
+//! cell, `:edit 3` edits cell 3 and runs it again, `:rerun 3` runs it again
+//! as it is, and `:cells` lists the cells.
+//!
+//! The value of each expression typed in is echoed and kept as a global:
+//! `_` is the latest and `_1`, `_2`, ... number them in order.
+
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use crate::grease::Grease;
+use crate::lexer::{Lexer, KEYWORDS};
+use crate::parser::Parser;
+use crate::token::{Token, TokenType};
 
 pub struct REPL {
     prompt: String,
     grease: Grease,
+    startup_file: Option<PathBuf>,
+    /// Code run with `:edit`; cell `n` is `cells[n - 1]`.
+    cells: Vec<String>,
+    /// The command `:edit` runs, instead of `$VISUAL` or `$EDITOR`.
+    editor: Option<String>,
+    /// How many values have been echoed, the last one being `_<results>`.
+    results: usize,
+}
+
+impl Default for REPL {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl REPL {
@@ -14,16 +49,43 @@ impl REPL {
         REPL {
             prompt: "grease> ".to_string(),
             grease: Grease::new(),
+            startup_file: None,
+            cells: Vec::new(),
+            editor: None,
+            results: 0,
         }
     }
 
+    /// Run `path` before the first prompt, if it exists.
+    pub fn with_startup_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.startup_file = Some(path.into());
+        self
+    }
+
+    /// The user's startup file, `~/.greaserc`.
+    pub fn default_startup_file() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+        Some(Path::new(&home).join(".greaserc"))
+    }
+
     pub fn run(&mut self) {
         println!("Grease Scripting Language v{}", env!("CARGO_PKG_VERSION"));
-        println!("Type 'exit()' to quit.");
+        println!("Type 'exit()' to quit, ':edit' to write a cell in $EDITOR.");
         println!();
+        if let Some(path) = self.startup_file.clone() {
+            self.run_startup_file(&path);
+        }
+
+        #[cfg(feature = "repl")]
+        if io::IsTerminal::is_terminal(&io::stdin()) {
+            match self.run_editor() {
+                Ok(()) => return,
+                Err(error) => eprintln!("Line editing unavailable ({}), reading plain input", error),
+            }
+        }
 
         loop {
-            print!("{}", self.prompt);
+            print!("{}", self.prompt());
             io::stdout().flush().unwrap();
 
             let mut input = String::new();
@@ -41,7 +103,7 @@ impl REPL {
                         continue;
                     }
                     
-                    self.execute(input);
+                    self.handle(input);
                 }
                 Err(error) => {
                     eprintln!("Error reading input: {}", error);
@@ -50,16 +112,172 @@ impl REPL {
         }
     }
 
+    /// The prompt loop, edited with rustyline.
+    #[cfg(feature = "repl")]
+    fn run_editor(&mut self) -> rustyline::Result<()> {
+        use rustyline::error::ReadlineError;
+
+        let mut editor = rustyline::Editor::<Helper, rustyline::history::DefaultHistory>::new()?;
+        editor.set_helper(Some(Helper::default()));
+        loop {
+            match editor.readline(&self.prompt()) {
+                Ok(input) => {
+                    let input = input.trim();
+                    if input == "exit()" {
+                        println!("Goodbye!");
+                        return Ok(());
+                    }
+                    if input.is_empty() {
+                        continue;
+                    }
+                    editor.add_history_entry(input)?;
+                    self.handle(input);
+                }
+                // Ctrl-C abandons the line, Ctrl-D quits
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(()),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Run a line typed at the prompt: a `:` command or code.
+    fn handle(&mut self, input: &str) {
+        if !input.starts_with(':') {
+            self.execute(input);
+            return;
+        }
+        let mut words = input.split_whitespace();
+        let result = match (words.next(), words.next(), words.next()) {
+            (Some(":edit"), None, _) => self.edit(None),
+            (Some(":edit"), Some(cell), None) => self.cell_index(cell).and_then(|index| self.edit(Some(index))),
+            (Some(":rerun"), Some(cell), None) => self.cell_index(cell).map(|index| self.run_cell(index)),
+            (Some(":cells"), None, _) => {
+                for (index, cell) in self.cells.iter().enumerate() {
+                    println!("[{}]", index + 1);
+                    println!("{}", cell.trim_end());
+                }
+                Ok(())
+            }
+            _ => Err(format!("Unknown command '{}'. Commands: :edit [n], :rerun n, :cells", input)),
+        };
+        if let Err(msg) = result {
+            eprintln!("Error: {}", msg);
+        }
+    }
+
+    /// The index in `cells` of cell number `cell`.
+    fn cell_index(&self, cell: &str) -> Result<usize, String> {
+        match cell.parse::<usize>() {
+            Ok(number) if (1..=self.cells.len()).contains(&number) => Ok(number - 1),
+            _ => Err(format!("No cell '{}'; there are {}", cell, self.cells.len())),
+        }
+    }
+
+    /// Edit cell `index`, or a new one, in the editor and run it once it's
+    /// saved. An empty buffer discards a new cell.
+    fn edit(&mut self, index: Option<usize>) -> Result<(), String> {
+        let number = index.unwrap_or(self.cells.len()) + 1;
+        let path = std::env::temp_dir().join(format!("grease-{}-cell-{}.grease", std::process::id(), number));
+        let initial = index.map_or("", |index| self.cells[index].as_str());
+        std::fs::write(&path, initial).map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+
+        let environment = &self.grease.vm.environment;
+        let editor = self.editor.clone()
+            .or_else(|| environment.get("VISUAL"))
+            .or_else(|| environment.get("EDITOR"))
+            .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+        let argv = crate::shell::split(&editor, crate::shell::Style::host())?;
+        let (program, args) = argv.split_first().ok_or("The editor command is empty")?;
+        // The editor sees the session's environment and directory, as set
+        // with `system.setenv` and `chdir`
+        let mut command = std::process::Command::new(program);
+        environment.apply(command.args(args).arg(&path));
+        let status = command.status()
+            .map_err(|e| format!("Failed to run editor '{}': {}", program, e));
+        let code = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e));
+        let _ = std::fs::remove_file(&path);
+        if !status?.success() {
+            return Err(format!("Editor '{}' failed; cell not run", program));
+        }
+
+        let code = code?;
+        let index = match index {
+            Some(index) => {
+                self.cells[index] = code;
+                index
+            }
+            None if code.trim().is_empty() => return Ok(()),
+            None => {
+                self.cells.push(code);
+                self.cells.len() - 1
+            }
+        };
+        self.run_cell(index);
+        Ok(())
+    }
+
+    fn run_cell(&mut self, index: usize) {
+        println!("[{}]", index + 1);
+        let code = self.cells[index].clone();
+        self.execute(&code);
+    }
+
+    /// The script's `repl_prompt`, or the default.
+    fn prompt(&self) -> String {
+        match self.grease.vm.globals.get("repl_prompt") {
+            Some(crate::bytecode::Value::String(prompt)) => prompt.clone(),
+            _ => self.prompt.clone(),
+        }
+    }
+
+    /// Run the startup file in the session, so what it defines stays
+    /// defined. It is run as typed-in code: relative paths still resolve
+    /// against the working directory.
+    fn run_startup_file(&mut self, path: &Path) {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return,
+            Err(error) => {
+                eprintln!("Error reading {}: {}", path.display(), error);
+                return;
+            }
+        };
+        use crate::vm::InterpretResult;
+
+        let error = match self.grease.evaluate(&source) {
+            Ok(outcome) => {
+                for warning in &outcome.warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+                match outcome.result {
+                    InterpretResult::Ok => return,
+                    InterpretResult::CompileError(msg) | InterpretResult::RuntimeError(msg) => msg,
+                }
+            }
+            Err(msg) => msg,
+        };
+        eprintln!("Error in {}: {}", path.display(), error);
+    }
+
     fn execute(&mut self, source: &str) {
         use crate::vm::InterpretResult;
         
-        match self.grease.run(source) {
-            Ok(result) => {
-                match result {
+        match self.grease.evaluate(source) {
+            Ok(outcome) => {
+                for warning in &outcome.warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+                match outcome.result {
                     InterpretResult::Ok => {
-                        // Print the last value on the stack if any
-                        if let Some(value) = self.grease.vm.stack.last() {
-                            println!("{}", self.format_value(value));
+                        // Echo the value of an expression, like `1 + 2`,
+                        // and keep it as `_` and `_<n>`
+                        if !matches!(outcome.value, crate::bytecode::Value::Null) {
+                            self.results += 1;
+                            println!("_{} = {}", self.results, Self::format_value(&outcome.value));
+                            let globals = &mut self.grease.vm.globals;
+                            globals.insert(format!("_{}", self.results), outcome.value.clone());
+                            globals.insert("_".to_string(), outcome.value);
                         }
                     }
                     InterpretResult::CompileError(msg) => {
@@ -76,35 +294,361 @@ impl REPL {
         }
     }
 
-    fn format_value(&self, value: &crate::bytecode::Value) -> String {
+    fn format_value(value: &crate::bytecode::Value) -> String {
         match value {
             crate::bytecode::Value::Number(n) => n.to_string(),
             crate::bytecode::Value::String(s) => format!("\"{}\"", s),
+            crate::bytecode::Value::Bytes(bytes) => crate::bytes::literal(bytes),
             crate::bytecode::Value::Boolean(b) => b.to_string(),
             crate::bytecode::Value::Null => "null".to_string(),
             crate::bytecode::Value::Function(f) => format!("<fn {}>", f.name),
+            crate::bytecode::Value::Closure(c) => format!("<fn {}>", c.function.name),
             crate::bytecode::Value::NativeFunction(f) => format!("<native fn {}>", f.name),
             crate::bytecode::Value::Array(arr) => {
-                let elements: Vec<String> = arr.iter().map(|v| match v {
+                let elements: Vec<String> = arr.lock().unwrap().iter().map(|v| match v {
                     crate::bytecode::Value::Number(n) => n.to_string(),
                     crate::bytecode::Value::String(s) => format!("\"{}\"", s),
+                    crate::bytecode::Value::Bytes(bytes) => crate::bytes::literal(bytes),
                     crate::bytecode::Value::Boolean(b) => b.to_string(),
                     crate::bytecode::Value::Null => "null".to_string(),
                     crate::bytecode::Value::Function(f) => format!("<fn {}>", f.name),
+                    crate::bytecode::Value::Closure(c) => format!("<fn {}>", c.function.name),
                     crate::bytecode::Value::NativeFunction(f) => format!("<native fn {}>", f.name),
                     crate::bytecode::Value::Array(_) => "[...]".to_string(),
                     crate::bytecode::Value::Object { class_name, .. } => format!("<{} instance>", class_name),
                     crate::bytecode::Value::Class { name, .. } => format!("<class {}>", name),
                     crate::bytecode::Value::Dictionary(_) => "{...}".to_string(),
+                    crate::bytecode::Value::Tuple(_) => "(...)".to_string(),
+                    crate::bytecode::Value::Module(name) => format!("<module {}>", name),
+                    range @ crate::bytecode::Value::Range { .. } => crate::vm::VM::format_value(range),
                 }).collect();
                 format!("[{}]", elements.join(", "))
             },
+            crate::bytecode::Value::Tuple(items) => {
+                let elements: Vec<String> = items.iter().map(Self::format_value).collect();
+                if elements.len() == 1 {
+                    format!("({},)", elements[0])
+                } else {
+                    format!("({})", elements.join(", "))
+                }
+            },
             crate::bytecode::Value::Object { class_name, .. } => format!("<{} instance>", class_name),
             crate::bytecode::Value::Class { name, .. } => format!("<class {}>", name),
+            crate::bytecode::Value::Module(name) => format!("<module {}>", name),
+            crate::bytecode::Value::Range { .. } => crate::vm::VM::format_value(value),
             crate::bytecode::Value::Dictionary(dict) => {
-                let elements: Vec<String> = dict.iter().map(|(k, v)| format!("\"{}\": {}", k, self.format_value(v))).collect();
+                // Already locked: the dictionary contains itself
+                let Ok(dict) = dict.try_lock() else { return "{...}".to_string() };
+                let elements: Vec<String> = dict.iter().map(|(k, v)| format!("\"{}\": {}", k, Self::format_value(v))).collect();
                 format!("{{{}}}", elements.join(", "))
             },
         }
     }
-}
\ No newline at end of file
+}
+const KEYWORD: &str = "\x1b[35m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const COMMENT: &str = "\x1b[90m";
+const BRACKET: &str = "\x1b[1;34m";
+const ERROR: &str = "\x1b[4;31m";
+const RESET: &str = "\x1b[0m";
+
+/// `line` with ANSI colors, and the bracket matching the one at or just
+/// before `cursor` (a byte offset) highlighted.
+pub fn highlight(line: &str, cursor: Option<usize>) -> String {
+    // `:` commands aren't code
+    if line.starts_with(':') {
+        return line.to_string();
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let mut styles = styles(&chars);
+    if let Some(cursor) = cursor {
+        let cursor = line.get(..cursor).map_or(chars.len(), |before| before.chars().count());
+        if let Some((open, close)) = matching_brackets(&chars, &styles, cursor) {
+            styles[open] = Some(BRACKET);
+            styles[close] = Some(BRACKET);
+        }
+    }
+
+    let mut highlighted = String::new();
+    let mut current = None;
+    for (&c, &style) in chars.iter().zip(&styles) {
+        if style != current {
+            if current.is_some() {
+                highlighted.push_str(RESET);
+            }
+            highlighted.extend(style);
+            current = style;
+        }
+        highlighted.push(c);
+    }
+    if current.is_some() {
+        highlighted.push_str(RESET);
+    }
+    highlighted
+}
+
+/// The style of each char of the line.
+fn styles(chars: &[char]) -> Vec<Option<&'static str>> {
+    let mut styles = vec![None; chars.len()];
+    // Lex up to each error, mark it, and go on after it
+    let mut offset = 0;
+    while offset < chars.len() {
+        let mut lexer = Lexer::new(chars[offset..].iter().collect());
+        match lexer.tokenize() {
+            Ok(tokens) => {
+                style_tokens(&mut styles, &chars[offset..], offset, &tokens);
+                if offset == 0 {
+                    mark_parse_error(&mut styles, chars, tokens);
+                }
+                break;
+            }
+            Err(_) => {
+                let span = lexer.span();
+                let start = offset + char_index(&chars[offset..], span.line, span.column);
+                let end = (offset + char_index(&chars[offset..], span.end_line, span.end_column)).clamp(start + 1, chars.len());
+                if let Ok(tokens) = Lexer::new(chars[offset..start].iter().collect()).tokenize() {
+                    style_tokens(&mut styles, &chars[offset..start], offset, &tokens);
+                }
+                if start >= chars.len() {
+                    break;
+                }
+                // A string still being typed isn't an error yet
+                if matches!(chars[start], '"' | '\'') {
+                    styles[start..].fill(Some(STRING));
+                    break;
+                }
+                styles[start..end].fill(Some(ERROR));
+                offset = end;
+            }
+        }
+    }
+
+    // The lexer skips comments, so they are the `#`s no token covers
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '#' && styles[i].is_none() {
+            while i < chars.len() && chars[i] != '\n' {
+                styles[i] = Some(COMMENT);
+                i += 1;
+            }
+        }
+        i += 1;
+    }
+    styles
+}
+
+/// The index in `chars` of a lexer position.
+fn char_index(chars: &[char], line: usize, column: usize) -> usize {
+    let line_start = if line <= 1 {
+        0
+    } else {
+        chars.iter().enumerate().filter(|(_, &c)| c == '\n').nth(line - 2).map_or(chars.len(), |(i, _)| i + 1)
+    };
+    (line_start + column.saturating_sub(1)).min(chars.len())
+}
+
+fn token_style(token: &Token) -> Option<&'static str> {
+    match token.token_type {
+        TokenType::Number(_) => Some(NUMBER),
+        TokenType::String(_) | TokenType::Bytes(_) => Some(STRING),
+        TokenType::Boolean(_) => Some(KEYWORD),
+        ref token_type if KEYWORDS.iter().any(|(_, keyword)| keyword == token_type) => Some(KEYWORD),
+        _ => None,
+    }
+}
+
+fn style_tokens(styles: &mut [Option<&'static str>], chars: &[char], offset: usize, tokens: &[Token]) {
+    for token in tokens {
+        if let Some(style) = token_style(token) {
+            let start = offset + char_index(chars, token.line, token.column);
+            let end = offset + char_index(chars, token.end_line, token.end_column);
+            styles[start..end.max(start)].fill(Some(style));
+        }
+    }
+}
+
+/// Underline where the line stops parsing, unless that's its end.
+fn mark_parse_error(styles: &mut [Option<&'static str>], chars: &[char], tokens: Vec<Token>) {
+    let mut parser = Parser::new(tokens);
+    if parser.parse().is_ok() {
+        return;
+    }
+    let Some(span) = parser.span() else { return };
+    let typed = chars.iter().rposition(|c| !c.is_whitespace()).map_or(0, |last| last + 1);
+    let start = char_index(chars, span.line, span.column);
+    if start < typed {
+        let end = char_index(chars, span.end_line, span.end_column).clamp(start + 1, typed);
+        styles[start..end].fill(Some(ERROR));
+    }
+}
+
+/// The bracket at or just before `cursor` and the one matching it, skipping
+/// brackets inside strings and comments.
+fn matching_brackets(chars: &[char], styles: &[Option<&'static str>], cursor: usize) -> Option<(usize, usize)> {
+    let is_code = |i: usize| styles[i].is_none();
+    let at = [cursor, cursor.checked_sub(1)?]
+        .into_iter()
+        .find(|&i| i < chars.len() && is_code(i) && "()[]{}".contains(chars[i]))?;
+    let (open, close, forward) = match chars[at] {
+        '(' => ('(', ')', true),
+        '[' => ('[', ']', true),
+        '{' => ('{', '}', true),
+        ')' => ('(', ')', false),
+        ']' => ('[', ']', false),
+        _ => ('{', '}', false),
+    };
+    let mut depth = 0;
+    let indices: Box<dyn Iterator<Item = usize>> = if forward { Box::new(at..chars.len()) } else { Box::new((0..=at).rev()) };
+    for i in indices.filter(|&i| is_code(i)) {
+        if chars[i] == open {
+            depth += if forward { 1 } else { -1 };
+        } else if chars[i] == close {
+            depth += if forward { -1 } else { 1 };
+        }
+        if depth == 0 {
+            return Some(if forward { (at, i) } else { (i, at) });
+        }
+    }
+    None
+}
+
+/// rustyline hooks: live highlighting.
+#[cfg(feature = "repl")]
+#[derive(Default)]
+struct Helper {
+    /// Whether to show the matching bracket; not once the line is entered.
+    brackets: std::cell::Cell<bool>,
+}
+
+#[cfg(feature = "repl")]
+impl rustyline::highlight::Highlighter for Helper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> std::borrow::Cow<'l, str> {
+        highlight(line, self.brackets.get().then_some(pos)).into()
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: rustyline::highlight::CmdKind) -> bool {
+        self.brackets.set(kind != rustyline::highlight::CmdKind::ForcedRefresh);
+        true
+    }
+}
+
+#[cfg(feature = "repl")]
+impl rustyline::completion::Completer for Helper {
+    type Candidate = String;
+}
+
+#[cfg(feature = "repl")]
+impl rustyline::hint::Hinter for Helper {
+    type Hint = String;
+}
+
+#[cfg(feature = "repl")]
+impl rustyline::validate::Validator for Helper {}
+
+#[cfg(feature = "repl")]
+impl rustyline::Helper for Helper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `line`'s styles, one letter per char: k)eyword s)tring n)umber
+    /// c)omment b)racket e)rror, `.` for none.
+    fn style_map(line: &str, cursor: Option<usize>) -> String {
+        let chars: Vec<char> = line.chars().collect();
+        let mut styles = styles(&chars);
+        if let Some((open, close)) = cursor.and_then(|cursor| matching_brackets(&chars, &styles, cursor)) {
+            styles[open] = Some(BRACKET);
+            styles[close] = Some(BRACKET);
+        }
+        styles.iter().map(|style| match *style {
+            Some(KEYWORD) => 'k',
+            Some(STRING) => 's',
+            Some(NUMBER) => 'n',
+            Some(COMMENT) => 'c',
+            Some(BRACKET) => 'b',
+            Some(ERROR) => 'e',
+            _ => '.',
+        }).collect()
+    }
+
+    #[test]
+    fn test_startup_file() {
+        let path = std::env::temp_dir().join(format!("greaserc_{}", std::process::id()));
+        std::fs::write(&path, "def double(x):\n    return x * 2\nrepl_prompt = \">>> \"\n").unwrap();
+        let mut repl = REPL::new().with_startup_file(&path);
+        assert_eq!(repl.prompt(), "grease> ");
+        repl.run_startup_file(&path);
+        assert_eq!(repl.prompt(), ">>> ");
+        repl.execute("y = double(4)");
+        assert!(matches!(repl.grease.vm.globals.get("y"), Some(crate::bytecode::Value::Number(n)) if *n == 8.0));
+        std::fs::remove_file(&path).unwrap();
+
+        // A missing startup file is no error
+        REPL::new().run_startup_file(&path);
+    }
+
+    #[test]
+    fn test_value_history() {
+        let mut repl = REPL::new();
+        repl.execute("1 + 2");
+        repl.execute("x = 10");
+        repl.execute("_ * x");
+        repl.execute("_1 + _2");
+        let global = |name: &str| match repl.grease.vm.globals.get(name) {
+            Some(crate::bytecode::Value::Number(n)) => Some(*n),
+            _ => None,
+        };
+        assert_eq!((global("_1"), global("_2"), global("_3"), global("_")), (Some(3.0), Some(30.0), Some(33.0), Some(33.0)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_and_rerun_cells() {
+        let mut repl = REPL::new();
+        // An "editor" that appends a line to the buffer it's given
+        repl.editor = Some("sh -c 'echo \"n = n + 1\" >> \"$1\"' editor".to_string());
+        repl.execute("n = 0");
+        repl.handle(":edit");
+        repl.handle(":rerun 1");
+        assert_eq!(repl.cells, ["n = n + 1\n"]);
+        assert!(matches!(repl.grease.vm.globals.get("n"), Some(crate::bytecode::Value::Number(n)) if *n == 2.0));
+
+        // Editing a cell replaces it and runs the new code
+        repl.handle(":edit 1");
+        assert_eq!(repl.cells, ["n = n + 1\nn = n + 1\n"]);
+        assert!(matches!(repl.grease.vm.globals.get("n"), Some(crate::bytecode::Value::Number(n)) if *n == 4.0));
+        assert!(repl.cell_index("2").is_err());
+
+        // The editor runs with the environment the script sees
+        repl.grease.vm.environment.set("GREASE_REPL_STEP", Some("10"));
+        repl.editor = Some("sh -c 'echo \"n = n + $GREASE_REPL_STEP\" >> \"$1\"' editor".to_string());
+        repl.handle(":edit");
+        assert_eq!(repl.cells[1], "n = n + 10\n");
+        assert!(matches!(repl.grease.vm.globals.get("n"), Some(crate::bytecode::Value::Number(n)) if *n == 14.0));
+    }
+
+    #[test]
+    fn test_highlight_tokens() {
+        assert_eq!(style_map("if x == 10 and \"a(\": # (", None), "kk......nn.kkk.ssss..ccc");
+        // An unfinished string or line isn't an error yet...
+        assert_eq!(style_map("print(\"hel", None), "......ssss");
+        assert_eq!(style_map("x = foo(1, ", None), "........n..");
+        // ...but a stray character or misplaced token is
+        assert_eq!(style_map("x = 1 $ 2", None), "....n.e.n");
+        assert_eq!(style_map("x = ) + 1", None), "....e...n");
+        assert_eq!(highlight("x = 1", None), "x = \x1b[33m1\x1b[0m");
+        assert_eq!(highlight(":rerun 1", None), ":rerun 1");
+    }
+
+    #[test]
+    fn test_highlight_matching_brackets() {
+        let line = "f(a[0], \")\", (b))";
+        assert_eq!(style_map(line, Some(1)), ".b..n...sss.....b");
+        assert_eq!(style_map(line, Some(line.len())), ".b..n...sss.....b");
+        assert_eq!(style_map(line, Some(5)), "...bnb..sss......");
+        assert_eq!(style_map(line, Some(9)), "....n...sss......");
+        assert_eq!(style_map("(()", Some(0)), "...");
+    }
+}
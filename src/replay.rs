@@ -0,0 +1,167 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Recording the native calls a script makes, and replaying them.
+//!
+//! A recording VM writes one JSON line per native call the script makes,
+//! with its arguments and what it returned or the error it raised, in the
+//! [`crate::serialize`] format. A replaying VM serves those results back in
+//! order instead of calling the natives, so a script that reads files,
+//! environment variables or input can be tested hermetically:
+//!
+//! ```text
+//! {"native":"getenv","args":"GRS1A1;S4:HOME","result":"GRS1S10:/home/user"}
+//! {"native":"file_open","args":"GRS1A2;S8:data.txtS1:r","error":"Failed to open 'data.txt': ..."}
+//! ```
+//!
+//! Calls natives make themselves aren't recorded: replaying the outer call
+//! stands for them. A call whose arguments or result can't be serialized,
+//! such as `memoize(f)`, is recorded as `"live"` and replayed by calling the
+//! native for real. Replay fails as soon as the script calls a different
+//! native, or the same one with different arguments, than was recorded.
+
+use crate::bytecode::{NativeFunction, Value};
+use crate::serialize::{deserialize, serialize};
+use crate::vm::VM;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub enum NativeLog {
+    /// Write each call to the sink as it returns.
+    Record(Box<dyn Write + Send>),
+    /// Recorded calls not served yet, in order.
+    Replay(VecDeque<RecordedCall>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub native: String,
+    /// The arguments as an array, or `None` if they couldn't be serialized.
+    pub args: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Call the native again when replaying.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub live: bool,
+}
+
+impl NativeLog {
+    /// Record into the file at `path`, replacing it.
+    pub fn record(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("Failed to create '{}': {}", path.display(), e))?;
+        Ok(NativeLog::Record(Box::new(BufWriter::new(file))))
+    }
+
+    /// Replay the recording in the file at `path`.
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        let calls = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| format!("{}:{}: invalid recorded call: {}", path.display(), index + 1, e))
+        });
+        calls.collect::<Result<_, String>>().map(NativeLog::Replay)
+    }
+
+    /// Recorded calls the script hasn't made yet when replaying.
+    pub fn remaining(&self) -> usize {
+        match self {
+            NativeLog::Record(_) => 0,
+            NativeLog::Replay(calls) => calls.len(),
+        }
+    }
+}
+
+/// Call `native` from script code through the VM's log.
+pub(crate) fn call(vm: &mut VM, native: &NativeFunction, args: Vec<Value>) -> Result<Value, String> {
+    // Taken out while the native runs, so the calls it makes aren't logged
+    let Some(mut log) = vm.native_log.take() else {
+        return (native.function)(vm, args);
+    };
+    let result = match &mut log {
+        NativeLog::Record(out) => record(vm, out, native, args),
+        NativeLog::Replay(calls) => replay(vm, calls, native, args),
+    };
+    vm.native_log = Some(log);
+    result
+}
+
+fn record(vm: &mut VM, out: &mut Box<dyn Write + Send>, native: &NativeFunction, args: Vec<Value>) -> Result<Value, String> {
+    let recorded_args = serialize(&Value::array(args.clone())).ok();
+    let result = (native.function)(vm, args);
+    let mut call = RecordedCall { native: native.name.clone(), live: recorded_args.is_none(), args: recorded_args, result: None, error: None };
+    match &result {
+        Ok(value) => match serialize(value) {
+            Ok(value) => call.result = Some(value),
+            Err(_) => call.live = true,
+        },
+        Err(e) => call.error = Some(e.clone()),
+    }
+    let line = serde_json::to_string(&call).map_err(|e| e.to_string())?;
+    writeln!(out, "{}", line)
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("Failed to record native call: {}", e))?;
+    result
+}
+
+fn replay(vm: &mut VM, calls: &mut VecDeque<RecordedCall>, native: &NativeFunction, args: Vec<Value>) -> Result<Value, String> {
+    let call = calls.pop_front()
+        .ok_or_else(|| format!("Replay ran out of recorded calls when the script called {}", native.name))?;
+    if call.native != native.name {
+        return Err(format!("Replay diverged: the script called {} where {} was recorded", native.name, call.native));
+    }
+    if call.args.is_some() && serialize(&Value::array(args.clone())).ok() != call.args {
+        return Err(format!("Replay diverged: the script called {} with different arguments than were recorded", native.name));
+    }
+    match call {
+        RecordedCall { live: true, .. } => (native.function)(vm, args),
+        RecordedCall { error: Some(error), .. } => Err(error),
+        RecordedCall { result: Some(result), .. } => deserialize(&result),
+        RecordedCall { .. } => Err(format!("Recorded call to {} has no result", native.name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    #[test]
+    fn test_record_then_replay() {
+        let dir = std::env::temp_dir().join(format!("grease-replay-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("calls.jsonl");
+        std::env::set_var("GREASE_REPLAY_TEST", "recorded");
+        let source = "def double(x):\n    return x * 2\n\
+            home = getenv(\"GREASE_REPLAY_TEST\")\n\
+            twice = memoize(double)\n\
+            try:\n    file_open(\"missing.txt\", \"r\")\ncatch e:\n    failed = true\n\
+            seen = [home, twice(4), sorted([3, 1, 2]), failed]";
+
+        let mut recording = Grease::new().with_native_log(NativeLog::record(&log).unwrap());
+        recording.vm.script_dir = Some(dir.clone());
+        assert_eq!(recording.run(source).unwrap(), InterpretResult::Ok);
+        let recorded = VM::format_value(recording.vm.globals.get("seen").unwrap());
+        assert_eq!(recorded, "[recorded, 8, [1, 2, 3], true]");
+
+        // The replay sees what the recording did, not the changed environment
+        std::env::set_var("GREASE_REPLAY_TEST", "changed");
+        std::fs::write(dir.join("missing.txt"), "now it exists").unwrap();
+        let mut replaying = Grease::new().with_native_log(NativeLog::replay(&log).unwrap());
+        replaying.vm.script_dir = Some(dir.clone());
+        assert_eq!(replaying.run(source).unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(replaying.vm.globals.get("seen").unwrap()), recorded);
+        assert_eq!(replaying.vm.native_log.as_ref().unwrap().remaining(), 0);
+
+        let mut diverged = Grease::new().with_native_log(NativeLog::replay(&log).unwrap());
+        let result = diverged.run("x = getenv(\"OTHER\")").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("different arguments")));
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Things scripts hold open, such as files, kept in a table the VM owns.
+//! A script gets a handle object for each; `close(handle)` releases it
+//! straight away, a `with` block releases it when the block is left, and
+//! whatever is still open is closed when the VM is dropped, so a long-lived
+//! host doesn't leak what its scripts forgot to close:
+//!
+//! ```text
+//! with file_open("log.txt", "a") as log:
+//!     file_write_line(log, "started")
+//! input = file_open("data.txt", "r")
+//! line = file_read_line(input)     # null at the end of the file
+//! close(input)
+//! ```
+//!
+//! Natives add kinds of resource by implementing [`Resource`] and handing
+//! scripts the value [`open`] returns.
+
+use crate::bytecode::Value;
+use crate::sandbox::{require, Capability};
+use crate::vm::VM;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// Something a script holds open.
+pub trait Resource: Any + Send {
+    /// Release the resource. Called once, however it ends up closed.
+    fn close(&mut self) -> Result<(), String>;
+}
+
+#[derive(Default)]
+pub struct Resources {
+    /// Open resources by handle id, with their kind.
+    open: BTreeMap<u64, (String, Box<dyn Resource>)>,
+    next_id: u64,
+}
+
+impl Resources {
+    pub fn insert(&mut self, kind: &str, resource: Box<dyn Resource>) -> u64 {
+        self.next_id += 1;
+        self.open.insert(self.next_id, (kind.to_string(), resource));
+        self.next_id
+    }
+
+    pub fn get_mut<T: Resource>(&mut self, id: u64) -> Option<&mut T> {
+        let (_, resource) = self.open.get_mut(&id)?;
+        (resource.as_mut() as &mut dyn Any).downcast_mut()
+    }
+
+    /// Close resource `id`; closing one that is already closed does nothing.
+    pub fn close(&mut self, id: u64) -> Result<(), String> {
+        match self.open.remove(&id) {
+            Some((_, mut resource)) => resource.close(),
+            None => Ok(()),
+        }
+    }
+
+    /// Close everything still open, returning the errors closing raised.
+    pub fn close_all(&mut self) -> Vec<String> {
+        let open = std::mem::take(&mut self.open);
+        open.into_values().filter_map(|(_, mut resource)| resource.close().err()).collect()
+    }
+
+    /// Kinds of the resources still open, oldest first.
+    pub fn kinds(&self) -> Vec<&str> {
+        self.open.values().map(|(kind, _)| kind.as_str()).collect()
+    }
+}
+
+impl Drop for Resources {
+    fn drop(&mut self) {
+        self.close_all();
+    }
+}
+
+impl std::fmt::Debug for Resources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.open.iter().map(|(id, (kind, _))| (id, kind))).finish()
+    }
+}
+
+/// Add `resource` to the VM's table and return the handle object of native
+/// class `kind` scripts use for it, which also works as a `with` manager.
+/// Only handles made here are accepted as handles: a script's own object of
+/// a class called `kind` isn't one.
+pub fn open(vm: &mut VM, kind: &str, resource: Box<dyn Resource>) -> Value {
+    vm.register_resource_type(kind, close_handle);
+    let id = vm.resources.insert(kind, resource);
+    Value::native_object(kind, HashMap::from([("id".to_string(), Value::Number(id as f64))]))
+}
+
+/// The open resource behind `handle`, for native `name`.
+pub fn get<'a, T: Resource>(vm: &'a mut VM, handle: &Value, name: &str) -> Result<&'a mut T, String> {
+    let id = handle_id(handle).ok_or_else(|| format!("TypeError: {} expects a resource handle", name))?;
+    vm.resources.get_mut(id).ok_or_else(|| format!("{}: the handle is closed", name))
+}
+
+fn handle_id(handle: &Value) -> Option<u64> {
+    match handle {
+        Value::Object { fields, module: Some(module), .. } if module == crate::bytecode::NATIVE_MODULE => match fields.lock().unwrap().get("id") {
+            Some(Value::Number(id)) => Some(*id as u64),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn close_handle(vm: &mut VM, handle: Value) -> Result<(), String> {
+    match handle_id(&handle) {
+        Some(id) => vm.resources.close(id),
+        None => Err("TypeError: close expects a resource handle".to_string()),
+    }
+}
+
+enum FileHandle {
+    Read(BufReader<File>),
+    Write(BufWriter<File>),
+}
+
+impl Resource for FileHandle {
+    fn close(&mut self) -> Result<(), String> {
+        match self {
+            FileHandle::Read(_) => Ok(()),
+            FileHandle::Write(writer) => writer.flush().map_err(|e| format!("IOError: Failed to write file: {}", e)),
+        }
+    }
+}
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("close", 1, |vm, args| {
+        close_handle(vm, args[0].clone())?;
+        Ok(Value::Null)
+    });
+
+    // "r" reads, "w" truncates and writes, "a" appends
+    vm.register_native("file_open", 2, |vm, args| {
+        require(vm, Capability::Filesystem, "file_open")?;
+        let (Value::String(path), Value::String(mode)) = (&args[0], &args[1]) else {
+            return Err("TypeError: file_open expects (path string, mode string)".to_string());
+        };
+        let mut options = OpenOptions::new();
+        match mode.as_str() {
+            "r" => options.read(true),
+            "w" => options.write(true).create(true).truncate(true),
+            "a" => options.append(true).create(true),
+            _ => return Err(format!("file_open: unknown mode '{}', expected \"r\", \"w\" or \"a\"", mode)),
+        };
+        let file = options.open(vm.resolve_path(path)).map_err(|e| format!("IOError: Failed to open '{}': {}", path, e))?;
+        let handle = match mode.as_str() {
+            "r" => FileHandle::Read(BufReader::new(file)),
+            _ => FileHandle::Write(BufWriter::new(file)),
+        };
+        Ok(open(vm, "File", Box::new(handle)))
+    });
+
+    // The next line without its line ending, or null at the end of the file
+    vm.register_native("file_read_line", 1, |vm, args| {
+        let FileHandle::Read(reader) = get(vm, &args[0], "file_read_line")? else {
+            return Err("file_read_line: the file is not open for reading".to_string());
+        };
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| format!("IOError: Failed to read file: {}", e))? == 0 {
+            return Ok(Value::Null);
+        }
+        let trimmed = line.strip_suffix('\n').map(|line| line.strip_suffix('\r').unwrap_or(line));
+        Ok(Value::String(trimmed.unwrap_or(&line).to_string()))
+    });
+
+    vm.register_native("file_write", 2, |vm, args| write(vm, args, "file_write", ""));
+    vm.register_native("file_write_line", 2, |vm, args| write(vm, args, "file_write_line", "\n"));
+}
+
+fn write(vm: &mut VM, args: Vec<Value>, name: &str, ending: &str) -> Result<Value, String> {
+    let text = match &args[1] {
+        Value::String(text) => text.clone() + ending,
+        _ => return Err(format!("TypeError: {} expects (file, string)", name)),
+    };
+    let FileHandle::Write(writer) = get(vm, &args[0], name)? else {
+        return Err(format!("{}: the file is not open for writing", name));
+    };
+    writer.write_all(text.as_bytes()).map_err(|e| format!("IOError: Failed to write file: {}", e))?;
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counted;
+
+    impl Resource for Counted {
+        fn close(&mut self) -> Result<(), String> {
+            CLOSED.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_resources_close_once() {
+        let mut resources = Resources::default();
+        let first = resources.insert("Counted", Box::new(Counted));
+        resources.insert("Counted", Box::new(Counted));
+        assert!(resources.get_mut::<Counted>(first).is_some());
+        resources.close(first).unwrap();
+        resources.close(first).unwrap();
+        assert!(resources.get_mut::<Counted>(first).is_none());
+        assert_eq!(resources.kinds(), vec!["Counted"]);
+        drop(resources);
+        assert_eq!(CLOSED.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_file_handles() {
+        let dir = std::env::temp_dir().join(format!("grease-resources-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lines.txt");
+        let source = format!("with file_open(\"{0}\", \"w\") as out:\n    file_write_line(out, \"one\")\n\
+            extra = file_open(\"{0}\", \"a\")\n\
+            file_write(extra, \"t\")\n\
+            file_write_line(extra, \"wo\")\n\
+            close(extra)\n\
+            input = file_open(\"{0}\", \"r\")\n\
+            lines = [file_read_line(input), file_read_line(input), file_read_line(input)]\n\
+            left_open = file_open(\"{0}\", \"r\")",
+            path.display());
+        let mut grease = Grease::new();
+        assert_eq!(grease.run(&source).unwrap(), InterpretResult::Ok);
+        assert_eq!(VM::format_value(grease.vm.globals.get("lines").unwrap()), "[one, two, null]");
+        assert_eq!(grease.vm.resources.kinds(), vec!["File", "File"]);
+
+        let result = grease.run("close(input)\nfile_read_line(input)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("the handle is closed")));
+        let result = grease.run("file_write(left_open, \"x\")").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("not open for writing")));
+
+        // A script's own File, even one copying a handle's id, isn't a handle
+        let source = "class File:\n    def __enter__(self):\n        return \"script\"\n    def __exit__(self, error):\n        self.exited = true\n\
+            fake = new File()\nfake.id = 1\nwith fake as value:\n    entered = value\nexited = fake.exited";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("entered"), Some(Value::String(s)) if s == "script"));
+        assert!(matches!(grease.vm.globals.get("exited"), Some(Value::Boolean(true))));
+        let result = grease.run("close(fake)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("close expects a resource handle")));
+        let result = grease.run("deserialize(serialize(left_open))").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.contains("Cannot serialize a native 'File' object")));
+        assert!(crate::serialize::deserialize("GRS1O1;S13:<native>.FileS2:idN2;").is_err());
+        assert_eq!(grease.vm.resources.kinds(), vec!["File"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! `retry` and `until`, for scripts that wait on commands, servers and
+//! requests that don't always work the first time.
+//!
+//! ```text
+//! output = retry(fetch, 5, 200)     # up to 5 calls, waiting ~200, ~400, ... ms
+//! until(server_is_up, 10000)        # poll for up to 10 seconds
+//! ```
+//!
+//! Waits double after each failure, up to a minute, and are jittered, so
+//! scripts retrying the same thing at once don't stay in step. In
+//! deterministic mode nothing is jittered and nothing sleeps: `retry`
+//! retries straight away and `until` counts the waits it would have made
+//! towards its timeout, so it checks the same number of times every run.
+
+use crate::bytecode::Value;
+use crate::vm::VM;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Calls `retry` makes when no attempt count is given.
+const DEFAULT_ATTEMPTS: f64 = 3.0;
+/// Milliseconds `retry` waits after the first failure when no backoff is given.
+const DEFAULT_BACKOFF_MS: f64 = 100.0;
+/// Most milliseconds `retry` waits between two attempts.
+const MAX_BACKOFF_MS: f64 = 60_000.0;
+/// Milliseconds `until` waits after the first check, and at most between two.
+const FIRST_POLL_MS: f64 = 10.0;
+const MAX_POLL_MS: f64 = 1000.0;
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("retry", 1..=3, retry);
+    vm.register_native("until", 2, until);
+}
+
+/// `retry(fn[, attempts[, backoff_ms]])` calls `fn` until it returns
+/// without throwing, at most `attempts` times, and returns what it
+/// returned. The last error is rethrown when every attempt fails.
+pub fn retry(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let callback = callable("retry", &args[0])?;
+    let attempts = match args.get(1) {
+        None => DEFAULT_ATTEMPTS,
+        Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 => *n,
+        Some(_) => return Err("TypeError: retry expects a whole number of attempts of at least 1".to_string()),
+    };
+    let backoff = match args.get(2) {
+        None => DEFAULT_BACKOFF_MS,
+        Some(Value::Number(n)) if *n >= 0.0 && n.is_finite() => *n,
+        Some(_) => return Err("TypeError: retry expects a non-negative backoff in milliseconds".to_string()),
+    };
+
+    let mut attempt = 1.0;
+    let mut wait = backoff.min(MAX_BACKOFF_MS);
+    loop {
+        match vm.call_function(callback.clone(), Vec::new()) {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= attempts => return Err(e),
+            Err(_) => {
+                if !vm.deterministic {
+                    thread::sleep(jittered(wait, false));
+                }
+                wait = (wait * 2.0).min(MAX_BACKOFF_MS);
+                attempt += 1.0;
+            }
+        }
+    }
+}
+
+/// `until(fn, timeout_ms)` calls `fn` until it returns a truthy value and
+/// returns that value, waiting a little longer between each check. A check
+/// that throws counts as not yet; after `timeout_ms` the wait fails with
+/// the last error, if there was one.
+pub fn until(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    let callback = callable("until", &args[0])?;
+    let timeout = match &args[1] {
+        // Too long to represent is as good as forever
+        Value::Number(n) if *n >= 0.0 && n.is_finite() => Duration::try_from_secs_f64(n / 1000.0).unwrap_or(Duration::MAX),
+        _ => return Err("TypeError: until expects a non-negative timeout in milliseconds".to_string()),
+    };
+
+    let started = Instant::now();
+    // Time spent waiting between checks, which is all the time that passes
+    // in deterministic mode
+    let mut waited = Duration::ZERO;
+    let mut poll = FIRST_POLL_MS;
+    loop {
+        let last_error = match vm.call_function(callback.clone(), Vec::new()) {
+            Ok(value) if VM::is_truthy(&value) => return Ok(value),
+            Ok(_) => None,
+            Err(e) => Some(e),
+        };
+        let elapsed = if vm.deterministic { waited } else { started.elapsed() };
+        if elapsed >= timeout {
+            let waited = timeout.as_millis();
+            return Err(match last_error {
+                Some(e) => format!("until timed out after {} ms: {}", waited, e),
+                None => format!("until timed out after {} ms", waited),
+            });
+        }
+        let wait = jittered(poll, vm.deterministic).min(timeout - elapsed);
+        if !vm.deterministic {
+            thread::sleep(wait);
+        }
+        waited += wait;
+        poll = (poll * 2.0).min(MAX_POLL_MS);
+    }
+}
+
+fn callable(name: &str, value: &Value) -> Result<Value, String> {
+    match value {
+        Value::Function(_) | Value::Closure(_) | Value::NativeFunction(_) => Ok(value.clone()),
+        _ => Err(format!("TypeError: {} expects a function to call", name)),
+    }
+}
+
+/// Between half and all of `ms` milliseconds, picked at random, or all of
+/// it when `deterministic`.
+fn jittered(ms: f64, deterministic: bool) -> Duration {
+    let fraction = if deterministic {
+        1.0
+    } else {
+        // Each RandomState is seeded differently, which is random enough to
+        // spread out waits without a dependency
+        let random = RandomState::new().build_hasher().finish();
+        0.5 + (random >> 11) as f64 / (1u64 << 53) as f64 / 2.0
+    };
+    Duration::try_from_secs_f64(ms * fraction / 1000.0).unwrap_or(Duration::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grease::Grease;
+    use crate::vm::{InterpretResult, VM};
+
+    fn formatted(grease: &Grease, name: &str) -> String {
+        VM::format_value(grease.vm.globals.get(name).unwrap())
+    }
+
+    #[test]
+    fn test_retry_until_success_and_rethrow() {
+        let mut grease = Grease::new();
+        let result = grease.run("calls = []\n\
+            def flaky():\n    calls.push(1)\n    if calls.len() < 3:\n        throw \"down\"\n    return \"up\"\n\
+            value = retry(flaky, 5, 1)\n\
+            attempts = calls.len()\n\
+            def broken():\n    throw \"still down\"\n\
+            try:\n    retry(broken, 2, 0)\ncatch e:\n    caught = e");
+        assert_eq!(result, Ok(InterpretResult::Ok));
+        assert_eq!(formatted(&grease, "value"), "up");
+        assert_eq!(formatted(&grease, "attempts"), "3");
+        assert_eq!(formatted(&grease, "caught"), "still down");
+    }
+
+    #[test]
+    fn test_until_polls_and_times_out() {
+        let mut grease = Grease::new();
+        let result = grease.run("checks = []\n\
+            def ready():\n    checks.push(1)\n    return checks.len() >= 3\n\
+            value = until(ready, 5000)\n\
+            def never():\n    return false\n\
+            try:\n    until(never, 30)\ncatch e:\n    caught = e.message");
+        assert_eq!(result, Ok(InterpretResult::Ok));
+        assert_eq!(formatted(&grease, "value"), "true");
+        assert_eq!(formatted(&grease, "checks"), "[1, 1, 1]");
+        assert!(formatted(&grease, "caught").contains("until timed out after 30 ms"));
+        assert_eq!(grease.run("retry(1)"), Ok(InterpretResult::RuntimeError("TypeError: retry expects a function to call".to_string())));
+    }
+
+    #[test]
+    fn test_deterministic_waits_dont_sleep() {
+        let mut grease = Grease::new().with_deterministic(true);
+        let started = std::time::Instant::now();
+        let result = grease.run("checks = []\n\
+            def never():\n    checks.push(1)\n    return false\n\
+            try:\n    until(never, 100000)\ncatch e:\n    caught = e.message\n\
+            def broken():\n    throw \"down\"\n\
+            try:\n    retry(broken, 2000, 1000)\ncatch e:\n    rethrown = e");
+        assert_eq!(result, Ok(InterpretResult::Ok));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(formatted(&grease, "caught").contains("until timed out after 100000 ms"));
+        // 10 ms doubling to 1 s: 8 waits reach 2550 ms, then 98 more of 1 s
+        // and the check at the timeout
+        assert_eq!(grease.run("count = checks.len()"), Ok(InterpretResult::Ok));
+        assert_eq!(formatted(&grease, "count"), "107");
+        assert_eq!(formatted(&grease, "rethrown"), "down");
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Resource limits and capabilities a host can impose on scripts, and
+//! deterministic mode.
+//!
+//! A deterministic VM (`Grease::with_deterministic(true)`) runs the same
+//! script the same way every time, for reproducible CI runs and recorded
+//! behavior that replays exactly: natives that observe other processes are
+//! denied as if the process capability were, `perf_counters()` reports
+//! every timing as 0, and `retry` and `until` wait without sleeping or
+//! jitter (see [`crate::retry`]). Dictionaries are always iterated, printed and
+//! serialized in key order, deterministic or not.
+
+use crate::vm::VM;
+
+/// Bounds on a single run. `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Bytecode instructions a run may execute, counting code in imported
+    /// modules and callbacks.
+    pub max_instructions: Option<u64>,
+    /// Nested Grease function and method calls.
+    pub max_call_depth: Option<usize>,
+}
+
+/// What natives may touch outside the VM. Everything is allowed by default;
+/// a denied native raises an error when called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Reading and writing files and the working directory, e.g.
+    /// `config_load`, `file_open` and `chdir`.
+    pub filesystem: bool,
+    /// Reading and changing environment variables (`getenv`,
+    /// `system.setenv`, `dotenv_load`).
+    pub environment: bool,
+    /// Inspecting processes (`system.monitor_process`).
+    pub process: bool,
+    /// Raw terminal control and key input (the `term` module).
+    pub terminal: bool,
+    /// Loading native code with `use plugin`.
+    pub plugins: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities {
+            filesystem: true,
+            environment: true,
+            process: true,
+            terminal: true,
+            plugins: true,
+        }
+    }
+}
+
+impl Capabilities {
+    /// Deny everything; enable what the host allows field by field.
+    pub fn none() -> Self {
+        Capabilities {
+            filesystem: false,
+            environment: false,
+            process: false,
+            terminal: false,
+            plugins: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Capability {
+    Filesystem,
+    Environment,
+    Process,
+    Terminal,
+    Plugins,
+}
+
+impl Capability {
+    fn name(self) -> &'static str {
+        match self {
+            Capability::Filesystem => "filesystem",
+            Capability::Environment => "environment",
+            Capability::Process => "process",
+            Capability::Terminal => "terminal",
+            Capability::Plugins => "plugins",
+        }
+    }
+}
+
+/// Fail with a permission error unless the host granted `capability` to
+/// scripts. Natives call this before touching the outside world.
+pub fn require(vm: &VM, capability: Capability, native: &str) -> Result<(), String> {
+    let granted = match capability {
+        Capability::Filesystem => vm.capabilities.filesystem,
+        Capability::Environment => vm.capabilities.environment,
+        Capability::Process => vm.capabilities.process,
+        Capability::Terminal => vm.capabilities.terminal,
+        Capability::Plugins => vm.capabilities.plugins,
+    };
+    if vm.deterministic && matches!(capability, Capability::Process) {
+        Err(format!("IOError: Permission denied: {} isn't available in deterministic mode", native))
+    } else if granted {
+        Ok(())
+    } else {
+        Err(format!("IOError: Permission denied: {} needs the {} capability", native, capability.name()))
+    }
+}
@@ -0,0 +1,354 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pickle-style serialization of Grease values.
+//!
+//! The encoding is a compact, length-prefixed text format so that any string
+//! content round-trips unchanged:
+//!
+//! ```text
+//! GRS1            header (format version 1)
+//! Z               null
+//! T / F           true / false
+//! N<number>;      number, e.g. `N3.5;`, `NNaN;`, `Ninf;`
+//! S<len>:<utf8>   string of <len> bytes
+//! A<n>;<values>   array of n values
+//! U<n>;<values>   tuple of n values
+//! D<n>;<pairs>    dictionary of n (string, value) pairs, keys sorted
+//! O<n>;<class><pairs>  object: class name string followed by n field pairs
+//! ```
+//!
+//! The class name of an object whose class a module defined is qualified
+//! with the module, as in `S13:geometry.Node`.
+
+use crate::bytecode::Value;
+use crate::vm::VM;
+use std::collections::HashMap;
+
+const HEADER: &str = "GRS1";
+
+/// How deeply values may nest in data to deserialize. The data can come
+/// from another process, and reading each level takes stack.
+const MAX_DEPTH: usize = 256;
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("serialize", 1, |_vm, args| serialize(&args[0]).map(Value::String));
+
+    vm.register_native("deserialize", 1, |_vm, args| {
+        match &args[0] {
+            Value::String(data) => deserialize(data),
+            _ => Err("TypeError: deserialize expects a string produced by serialize".to_string()),
+        }
+    });
+}
+
+/// Encode `value` into the portable serialization format.
+pub fn serialize(value: &Value) -> Result<String, String> {
+    let mut out = String::from(HEADER);
+    write_value(&mut out, value)?;
+    Ok(out)
+}
+
+/// Decode data produced by [`serialize`].
+pub fn deserialize(data: &str) -> Result<Value, String> {
+    let body = data
+        .strip_prefix(HEADER)
+        .ok_or_else(|| "Invalid serialized data: missing GRS1 header".to_string())?;
+    let mut reader = Reader { data: body.as_bytes(), pos: 0, depth: 0 };
+    let value = reader.read_value()?;
+    if reader.pos != reader.data.len() {
+        return Err(format!("Invalid serialized data: trailing bytes at offset {}", reader.pos + HEADER.len()));
+    }
+    Ok(value)
+}
+
+fn write_value(out: &mut String, value: &Value) -> Result<(), String> {
+    match value {
+        Value::Null => out.push('Z'),
+        Value::Boolean(true) => out.push('T'),
+        Value::Boolean(false) => out.push('F'),
+        Value::Number(n) => {
+            out.push('N');
+            out.push_str(&n.to_string());
+            out.push(';');
+        }
+        Value::String(s) => write_string(out, s),
+        Value::Bytes(bytes) => {
+            // Hex keeps the output text
+            out.push_str(&format!("B{}:", bytes.len()));
+            out.push_str(&crate::bytes::to_hex(bytes));
+        }
+        // Already locked: being written further up, so it contains itself
+        Value::Array(items) => {
+            let items = items.try_lock().map_err(|_| "TypeError: Cannot serialize an array that contains itself".to_string())?;
+            out.push_str(&format!("A{};", items.len()));
+            for item in items.iter() {
+                write_value(out, item)?;
+            }
+        }
+        Value::Tuple(items) => {
+            out.push_str(&format!("U{};", items.len()));
+            for item in items {
+                write_value(out, item)?;
+            }
+        }
+        Value::Dictionary(entries) => {
+            let entries = entries.try_lock().map_err(|_| "TypeError: Cannot serialize a dictionary that contains itself".to_string())?;
+            out.push_str(&format!("D{};", entries.len()));
+            write_pairs(out, &entries)?;
+        }
+        Value::Object { class_name, module: Some(module), .. } if module == crate::bytecode::NATIVE_MODULE => {
+            return Err(format!("Cannot serialize a native '{}' object", class_name));
+        }
+        Value::Object { class_name, module, fields } => {
+            let fields = fields.try_lock().map_err(|_| format!("TypeError: Cannot serialize a '{}' object that contains itself", class_name))?;
+            out.push_str(&format!("O{};", fields.len()));
+            match module {
+                Some(module) => write_string(out, &format!("{}.{}", module, class_name)),
+                None => write_string(out, class_name),
+            }
+            write_pairs(out, &fields)?;
+        }
+        Value::Function(function) => {
+            return Err(format!("TypeError: Cannot serialize function '{}'", function.name));
+        }
+        Value::Closure(closure) => {
+            return Err(format!("TypeError: Cannot serialize function '{}'", closure.function.name));
+        }
+        Value::NativeFunction(function) => {
+            return Err(format!("TypeError: Cannot serialize native function '{}'", function.name));
+        }
+        Value::Class { name, .. } => {
+            return Err(format!("TypeError: Cannot serialize class '{}'", name));
+        }
+        Value::Module(name) => {
+            return Err(format!("TypeError: Cannot serialize module '{}'", name));
+        }
+        Value::Range { .. } => {
+            return Err("TypeError: Cannot serialize a range; convert it with to_array() first".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push_str(&format!("S{}:", s.len()));
+    out.push_str(s);
+}
+
+fn write_pairs(out: &mut String, entries: &HashMap<String, Value>) -> Result<(), String> {
+    // Sort keys so equal values always produce identical output
+    let mut keys: Vec<&String> = entries.keys().collect();
+    keys.sort();
+    for key in keys {
+        write_string(out, key);
+        write_value(out, &entries[key])?;
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Values being read around the current one.
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("Invalid serialized data at offset {}: {}", self.pos + HEADER.len(), message)
+    }
+
+    fn next_byte(&mut self) -> Result<u8, String> {
+        let byte = *self.data.get(self.pos).ok_or_else(|| self.error("unexpected end of data"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    /// Read raw text up to (and consuming) `terminator`.
+    fn read_until(&mut self, terminator: u8) -> Result<&str, String> {
+        let start = self.pos;
+        let len = self.data[start..]
+            .iter()
+            .position(|&b| b == terminator)
+            .ok_or_else(|| self.error(&format!("expected '{}'", terminator as char)))?;
+        self.pos = start + len + 1;
+        std::str::from_utf8(&self.data[start..start + len]).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    fn read_count(&mut self, terminator: u8) -> Result<usize, String> {
+        let text = self.read_until(terminator)?;
+        text.parse::<usize>().map_err(|_| format!("Invalid serialized data: bad length '{}'", text))
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        if self.next_byte()? != b'S' {
+            self.pos -= 1;
+            return Err(self.error("expected string"));
+        }
+        self.read_string_body()
+    }
+
+    fn read_string_body(&mut self) -> Result<String, String> {
+        let len = self.read_count(b':')?;
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len())
+            .ok_or_else(|| self.error("string length exceeds data"))?;
+        let s = std::str::from_utf8(&self.data[self.pos..end]).map_err(|_| self.error("invalid UTF-8"))?;
+        self.pos = end;
+        Ok(s.to_string())
+    }
+
+    fn read_values(&mut self) -> Result<Vec<Value>, String> {
+        let count = self.read_count(b';')?;
+        let mut items = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            items.push(self.read_value()?);
+        }
+        Ok(items)
+    }
+
+    fn read_pairs(&mut self, count: usize) -> Result<HashMap<String, Value>, String> {
+        let mut entries = HashMap::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let key = self.read_string()?;
+            let value = self.read_value()?;
+            entries.insert(key, value);
+        }
+        Ok(entries)
+    }
+
+    fn read_value(&mut self) -> Result<Value, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(&format!("values nest more than {} deep", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let value = self.read_value_contents();
+        self.depth -= 1;
+        value
+    }
+
+    fn read_value_contents(&mut self) -> Result<Value, String> {
+        match self.next_byte()? {
+            b'Z' => Ok(Value::Null),
+            b'T' => Ok(Value::Boolean(true)),
+            b'F' => Ok(Value::Boolean(false)),
+            b'N' => {
+                let text = self.read_until(b';')?;
+                text.parse::<f64>()
+                    .map(Value::Number)
+                    .map_err(|_| format!("Invalid serialized data: bad number '{}'", text))
+            }
+            b'S' => self.read_string_body().map(Value::String),
+            b'B' => {
+                let len = self.read_count(b':')?;
+                let end = len.checked_mul(2).and_then(|digits| self.pos.checked_add(digits)).filter(|&end| end <= self.data.len())
+                    .ok_or_else(|| self.error("bytes length exceeds data"))?;
+                let hex = std::str::from_utf8(&self.data[self.pos..end]).map_err(|_| self.error("invalid UTF-8"))?;
+                let bytes = crate::bytes::from_hex(hex).map_err(|_| self.error("invalid hex in bytes"))?;
+                self.pos = end;
+                Ok(Value::Bytes(bytes))
+            }
+            b'A' => self.read_values().map(Value::array),
+            b'U' => self.read_values().map(Value::Tuple),
+            b'D' => {
+                let count = self.read_count(b';')?;
+                self.read_pairs(count).map(Value::dictionary)
+            }
+            b'O' => {
+                let count = self.read_count(b';')?;
+                let class_name = self.read_string()?;
+                let fields = self.read_pairs(count)?;
+                // Class names have no dots, so the last one ends the module's
+                Ok(match class_name.rsplit_once('.') {
+                    Some((crate::bytecode::NATIVE_MODULE, class_name)) => {
+                        return Err(self.error(&format!("a native '{}' object can't be deserialized", class_name)));
+                    }
+                    Some((module, class_name)) => Value::object_in(Some(module.to_string()), class_name, fields),
+                    None => Value::object(&class_name, fields),
+                })
+            }
+            tag => {
+                self.pos -= 1;
+                Err(self.error(&format!("unknown tag '{}'", tag as char)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_nested_values() {
+        let mut fields = HashMap::new();
+        fields.insert("name".to_string(), Value::String("Rex: \"the\" dog;\n".to_string()));
+        fields.insert("tags".to_string(), Value::array(vec![Value::Null, Value::Boolean(true), Value::Number(-1.5)]));
+        let object = Value::object("Dog", fields);
+        let mut dict = HashMap::new();
+        dict.insert("pet".to_string(), object);
+        dict.insert("ünïcode".to_string(), Value::Number(f64::INFINITY));
+        dict.insert("raw".to_string(), Value::Bytes(vec![0, 0xff, b';']));
+
+        let data = serialize(&Value::dictionary(dict)).unwrap();
+        assert!(data.starts_with("GRS1D3;") && data.contains("S3:rawB3:00ff3b"));
+        let decoded = deserialize(&data).unwrap();
+        assert_eq!(serialize(&decoded).unwrap(), data);
+        match decoded {
+            Value::Dictionary(dict) => match dict.lock().unwrap().get("pet") {
+                Some(Value::Object { class_name, fields, .. }) => {
+                    assert_eq!(class_name, "Dog");
+                    assert!(matches!(fields.lock().unwrap().get("name"), Some(Value::String(s)) if s == "Rex: \"the\" dog;\n"));
+                }
+                other => panic!("Expected object, got {:?}", other),
+            },
+            other => panic!("Expected dictionary, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_keeps_the_module_of_an_object() {
+        let object = Value::object_in(Some("geometry".to_string()), "Node", HashMap::new());
+        let data = serialize(&object).unwrap();
+        assert_eq!(data, "GRS1O0;S13:geometry.Node");
+        assert!(matches!(deserialize(&data).unwrap(), Value::Object { class_name, module: Some(module), .. } if class_name == "Node" && module == "geometry"));
+    }
+
+    #[test]
+    fn test_serialize_is_deterministic() {
+        let data = serialize(&Value::array(vec![Value::Number(1.0), Value::String("a".to_string())])).unwrap();
+        assert_eq!(data, "GRS1A2;N1;S1:a");
+        let tuple = Value::Tuple(vec![Value::Boolean(true), Value::Null]);
+        assert_eq!(serialize(&tuple).unwrap(), "GRS1U2;TZ");
+        assert!(matches!(deserialize("GRS1U2;TZ").unwrap(), Value::Tuple(items) if items.len() == 2));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_bad_data() {
+        assert!(deserialize("A1;Z").is_err());
+        assert!(deserialize("GRS1A2;Z").is_err());
+        assert!(deserialize("GRS1S10:abc").is_err());
+        assert!(deserialize("GRS1ZZ").is_err());
+        assert!(deserialize("GRS1Q").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_limits_nesting() {
+        let nested = |depth: usize| format!("GRS1{}Z", "A1;".repeat(depth));
+        assert!(deserialize(&nested(MAX_DEPTH - 1)).is_ok());
+        let error = deserialize(&nested(200_000)).unwrap_err();
+        assert!(error.contains("values nest more than 256 deep"), "{}", error);
+    }
+
+    #[test]
+    fn test_serialize_natives() {
+        let mut grease = crate::grease::Grease::new();
+        let source = "data = serialize({\"a\": [1, 2], \"b\": \"x\"})\nback = deserialize(data)\nvalue = back[\"a\"][1]";
+        let result = grease.run(source).unwrap();
+        assert_eq!(result, crate::vm::InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("value"), Some(Value::Number(n)) if *n == 2.0));
+
+        let result = grease.run("serialize(native_add)").unwrap();
+        assert!(matches!(result, crate::vm::InterpretResult::RuntimeError(msg) if msg.contains("native function")));
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Quoting and splitting command lines, so scripts that compose commands
+//! for a shell never paste raw strings into one.
+//!
+//! `shell_quote(s)` quotes one argument, `shell_join(argv)` quotes and joins
+//! an argument list, and `shell_split(cmdline)` splits a command line back
+//! into arguments. They follow the host's rules: POSIX `sh` quoting, or on
+//! Windows the rules programs use to split their command line
+//! (`CommandLineToArgvW`).
+
+use crate::bytecode::Value;
+use crate::vm::VM;
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_native("shell_quote", 1, |_vm, args| match &args[0] {
+        Value::String(arg) => Ok(Value::String(quote(arg, Style::host()))),
+        _ => Err("TypeError: shell_quote expects a string".to_string()),
+    });
+    vm.register_native("shell_join", 1, |_vm, args| {
+        let args = match &args[0] {
+            Value::Array(items) => items.lock().unwrap().clone(),
+            Value::Tuple(items) => items.clone(),
+            _ => return Err("TypeError: shell_join expects an array of strings".to_string()),
+        };
+        let args = args.iter().map(|arg| match arg {
+            Value::String(arg) => Ok(arg.as_str()),
+            _ => Err("TypeError: shell_join expects an array of strings".to_string()),
+        }).collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::String(join(&args, Style::host())))
+    });
+    vm.register_native("shell_split", 1, |_vm, args| match &args[0] {
+        Value::String(cmdline) => {
+            let args = split(cmdline, Style::host())?;
+            Ok(Value::array(args.into_iter().map(Value::String).collect()))
+        }
+        _ => Err("TypeError: shell_split expects a string".to_string()),
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// POSIX `sh`
+    Posix,
+    /// How Windows programs split their command line
+    Windows,
+}
+
+impl Style {
+    pub fn host() -> Style {
+        if cfg!(windows) { Style::Windows } else { Style::Posix }
+    }
+}
+
+/// `arg` quoted so the shell passes it through as one argument, unchanged.
+pub fn quote(arg: &str, style: Style) -> String {
+    match style {
+        Style::Posix => {
+            let safe = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c);
+            if !arg.is_empty() && arg.chars().all(safe) {
+                arg.to_string()
+            } else {
+                // Nothing is special inside single quotes, so only a quote
+                // itself needs closing, escaping and reopening
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        }
+        Style::Windows => {
+            if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '\x0b', '"']) {
+                return arg.to_string();
+            }
+            let mut quoted = String::from("\"");
+            let mut backslashes = 0;
+            for c in arg.chars() {
+                match c {
+                    '\\' => backslashes += 1,
+                    // Backslashes before a quote escape, so double them
+                    '"' => {
+                        quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                        quoted.push('"');
+                        backslashes = 0;
+                    }
+                    _ => {
+                        quoted.extend(std::iter::repeat_n('\\', backslashes));
+                        quoted.push(c);
+                        backslashes = 0;
+                    }
+                }
+            }
+            // ...including the closing quote
+            quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+            quoted.push('"');
+            quoted
+        }
+    }
+}
+
+/// `args` as one command line.
+pub fn join(args: &[&str], style: Style) -> String {
+    args.iter().map(|arg| quote(arg, style)).collect::<Vec<_>>().join(" ")
+}
+
+/// The arguments `cmdline` passes, with quotes and escapes removed.
+pub fn split(cmdline: &str, style: Style) -> Result<Vec<String>, String> {
+    match style {
+        Style::Posix => split_posix(cmdline),
+        Style::Windows => Ok(split_windows(cmdline)),
+    }
+}
+
+fn split_posix(cmdline: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    // The argument being read, if one has started; `''` starts an empty one
+    let mut current: Option<String> = None;
+    let mut chars = cmdline.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => args.extend(current.take()),
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("shell_split: unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Only these can be escaped inside double quotes
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(c @ ('\\' | '"' | '$' | '`')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("shell_split: unterminated double quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("shell_split: unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // A line continuation
+                Some('\n') => {}
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err("shell_split: nothing to escape after a trailing backslash".to_string()),
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+fn split_windows(cmdline: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = cmdline.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' if !quoted => args.extend(current.take()),
+            '\\' => {
+                let mut backslashes = 1;
+                while chars.next_if_eq(&'\\').is_some() {
+                    backslashes += 1;
+                }
+                let arg = current.get_or_insert_with(String::new);
+                if chars.peek() == Some(&'"') {
+                    // 2n backslashes and a quote are n backslashes and a
+                    // delimiter; 2n + 1 are n and a literal quote
+                    arg.extend(std::iter::repeat_n('\\', backslashes / 2));
+                    if backslashes % 2 == 1 {
+                        arg.push('"');
+                        chars.next();
+                    }
+                } else {
+                    arg.extend(std::iter::repeat_n('\\', backslashes));
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                // `""` inside quotes is a literal quote
+                if quoted && chars.next_if_eq(&'"').is_some() {
+                    arg.push('"');
+                } else {
+                    quoted = !quoted;
+                }
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_posix_quoting_round_trips() {
+        let args = ["ls", "-la", "my file", "it's", "", "$HOME", "a\"b\\c", "x;rm -rf /"];
+        let cmdline = join(&args, Style::Posix);
+        assert_eq!(cmdline, r#"ls -la 'my file' 'it'\''s' '' '$HOME' 'a"b\c' 'x;rm -rf /'"#);
+        assert_eq!(split(&cmdline, Style::Posix).unwrap(), args);
+
+        assert_eq!(split("echo \"a \\$b\\q\" c\\ d \\\n e", Style::Posix).unwrap(), ["echo", "a $b\\q", "c d", "e"]);
+        assert!(split("echo 'oops", Style::Posix).unwrap_err().contains("unterminated"));
+        assert!(split("echo \\", Style::Posix).is_err());
+    }
+
+    #[test]
+    fn test_windows_quoting_round_trips() {
+        let args = ["C:\\Program Files\\app.exe", "say \"hi\"", "trailing\\", "dir\\ with space\\", "", "plain"];
+        let cmdline = join(&args, Style::Windows);
+        assert_eq!(cmdline, r#""C:\Program Files\app.exe" "say \"hi\"" trailing\ "dir\ with space\\" "" plain"#);
+        assert_eq!(split(&cmdline, Style::Windows).unwrap(), args);
+        assert_eq!(split(r#"a\\\"b "c""d" e\\f"#, Style::Windows).unwrap(), [r#"a\"b"#, r#"c"d"#, r"e\\f"]);
+    }
+
+    #[test]
+    fn test_natives() {
+        let mut grease = crate::Grease::new();
+        grease.run("args = shell_split(shell_join([\"grep\", \"-e\", \"it's here\"]))\nquoted = shell_quote(\"a b\")").unwrap();
+        let args: Vec<String> = match grease.vm.globals.get("args") {
+            Some(Value::Array(args)) => args.lock().unwrap().iter().filter_map(|arg| if let Value::String(arg) = arg { Some(arg.clone()) } else { None }).collect(),
+            other => panic!("expected an array, got {:?}", other),
+        };
+        assert_eq!(args, ["grep", "-e", "it's here"]);
+        assert!(matches!(grease.vm.globals.get("quoted"), Some(Value::String(quoted)) if *quoted == quote("a b", Style::host())));
+        assert!(matches!(grease.run("shell_join([1])").unwrap(), crate::InterpretResult::RuntimeError(_)));
+    }
+}
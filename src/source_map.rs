@@ -0,0 +1,139 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Source maps from bytecode back to the source it was compiled from.
+//!
+//! Every [`Chunk`](crate::bytecode::Chunk) carries a [`SourceMap`] next to
+//! its code: runs of byte offsets that share a line and column, plus the
+//! file and function the chunk came from. Runtime errors use it for their
+//! traceback; anything else that needs to point at source (a debugger, a
+//! profiler, another backend) can look offsets up the same way.
+
+use std::fmt;
+
+/// A position in the source. Line 0 means unknown, e.g. for code the
+/// compiler synthesizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceLocation {
+    /// The line of `source` at this location, numbered, with a caret under
+    /// the column; `None` if the source has no such line.
+    ///
+    /// ```text
+    /// 3 | total = count + "x"
+    ///   |         ^
+    /// ```
+    pub fn snippet(&self, source: &str) -> Option<String> {
+        let text = source.lines().nth(self.line.checked_sub(1)?)?;
+        let number = self.line.to_string();
+        // Keep tabs so the caret lines up however they are displayed
+        let indent: String = text.chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        Some(format!("{} | {}\n{} | {}^", number, text, " ".repeat(number.len()), indent))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// Script or module file the chunk was compiled from, if any.
+    pub file: Option<String>,
+    /// Function the chunk is the body of; `None` for a script or module.
+    pub function: Option<String>,
+    /// `(first byte offset, location)` runs in offset order.
+    entries: Vec<(usize, SourceLocation)>,
+}
+
+impl SourceMap {
+    /// Note that the code from `offset` on comes from `location`. Unknown
+    /// locations keep the previous run going.
+    pub fn record(&mut self, offset: usize, location: SourceLocation) {
+        if location.line == 0 || self.entries.last().is_some_and(|(_, last)| *last == location) {
+            return;
+        }
+        self.entries.push((offset, location));
+    }
+
+    /// The source location of the instruction byte at `offset`.
+    pub fn lookup(&self, offset: usize) -> Option<SourceLocation> {
+        let runs = self.entries.partition_point(|(start, _)| *start <= offset);
+        runs.checked_sub(1).map(|run| self.entries[run].1)
+    }
+
+    pub fn entries(&self) -> &[(usize, SourceLocation)] {
+        &self.entries
+    }
+}
+
+/// One call in a runtime error's traceback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceFrame {
+    /// `None` for top-level script or module code.
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub location: Option<SourceLocation>,
+}
+
+impl TraceFrame {
+    /// The frame executing the instruction byte at `offset` of a chunk with
+    /// source map `map`.
+    pub fn at(map: &SourceMap, offset: usize) -> Self {
+        TraceFrame {
+            function: map.function.clone(),
+            file: map.file.clone(),
+            location: map.lookup(offset),
+        }
+    }
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}", self.function.as_deref().unwrap_or("<script>"))?;
+        match (&self.file, self.location) {
+            (Some(file), Some(location)) => write!(f, " ({}:{}:{})", file, location.line, location.column),
+            (None, Some(location)) => write!(f, " (line {}, column {})", location.line, location.column),
+            (Some(file), None) => write!(f, " ({})", file),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map_lookup_uses_runs() {
+        let mut map = SourceMap::default();
+        let at = |line, column| SourceLocation { line, column };
+        map.record(0, at(1, 1));
+        map.record(2, at(1, 1));
+        map.record(4, at(2, 5));
+        map.record(6, SourceLocation::default());
+        assert_eq!(map.entries().len(), 2);
+        assert_eq!(map.lookup(3), Some(at(1, 1)));
+        assert_eq!(map.lookup(4), Some(at(2, 5)));
+        assert_eq!(map.lookup(7), Some(at(2, 5)));
+        assert_eq!(SourceMap::default().lookup(0), None);
+
+        map.function = Some("area".to_string());
+        assert_eq!(TraceFrame::at(&map, 5).to_string(), "at area (line 2, column 5)");
+        map.file = Some("shapes.grease".to_string());
+        assert_eq!(TraceFrame::at(&map, 0).to_string(), "at area (shapes.grease:1:1)");
+    }
+
+    #[test]
+    fn test_snippet_points_at_the_column() {
+        let source = "x = 1\n\ty = x + z";
+        let at = |line, column| SourceLocation { line, column };
+        assert_eq!(at(2, 10).snippet(source).unwrap(), "2 | \ty = x + z\n  | \t        ^");
+        assert_eq!(at(1, 1).snippet(source).unwrap(), "1 | x = 1\n  | ^");
+        assert_eq!(at(3, 1).snippet(source), None);
+        assert_eq!(SourceLocation::default().snippet(source), None);
+    }
+}
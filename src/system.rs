@@ -0,0 +1,151 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::bytecode::Value;
+use crate::sandbox::{require, Capability};
+use crate::vm::VM;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessesToUpdate, System};
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_module_native("system", "monitor_process", 4, system_monitor_process);
+}
+
+/// `system.monitor_process(pid, samples, interval_ms, callback)`
+///
+/// Samples CPU usage, resident/virtual memory and open file descriptors of
+/// `pid` (or of the interpreter itself when `pid` is null) every `interval_ms`
+/// milliseconds and returns the samples as an array of dictionaries. When
+/// `callback` is a function it is called with each sample as it is taken;
+/// returning `false` from it stops monitoring early. Sampling also stops when
+/// the process exits.
+fn system_monitor_process(vm: &mut VM, args: Vec<Value>) -> Result<Value, String> {
+    require(vm, Capability::Process, "system.monitor_process")?;
+    let pid = match &args[0] {
+        Value::Null => sysinfo::get_current_pid().map_err(|e| e.to_string())?,
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Pid::from_u32(*n as u32),
+        _ => return Err("TypeError: system.monitor_process expects a PID number or null".to_string()),
+    };
+    let samples = match &args[1] {
+        Value::Number(n) if *n >= 1.0 => *n as usize,
+        _ => return Err("TypeError: system.monitor_process expects a sample count of at least 1".to_string()),
+    };
+    let interval = match &args[2] {
+        // An infinite interval would sleep forever
+        Value::Number(n) if n.is_finite() && *n >= 0.0 => Duration::try_from_secs_f64(n / 1000.0)
+            .map_err(|_| "TypeError: system.monitor_process expects an interval short enough to wait for".to_string())?,
+        _ => return Err("TypeError: system.monitor_process expects a finite, non-negative interval in milliseconds".to_string()),
+    };
+    let callback = match &args[3] {
+        Value::Null => None,
+        callable @ (Value::Function(_) | Value::Closure(_) | Value::NativeFunction(_)) => Some(callable.clone()),
+        _ => return Err("TypeError: system.monitor_process callback must be a function or null".to_string()),
+    };
+
+    let mut system = System::new();
+    let refresh_kind = ProcessRefreshKind::nothing().with_cpu().with_memory();
+    // CPU usage is measured between two refreshes, so take a baseline first
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, refresh_kind);
+    if system.process(pid).is_none() {
+        return Err(format!("No process with PID {}", pid));
+    }
+
+    let start = Instant::now();
+    // The process may exit long before `samples`, so grow as samples come
+    let mut series = Vec::new();
+    for _ in 0..samples {
+        thread::sleep(interval);
+        system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, refresh_kind);
+        let sample = match system.process(pid) {
+            Some(process) => sample_to_value(pid, process, start.elapsed()),
+            None => break, // The process exited
+        };
+        series.push(sample.clone());
+
+        if let Some(callback) = &callback {
+            if let Value::Boolean(false) = vm.call_function(callback.clone(), vec![sample])? {
+                break;
+            }
+        }
+    }
+
+    Ok(Value::array(series))
+}
+
+fn sample_to_value(pid: Pid, process: &Process, elapsed: Duration) -> Value {
+    let mut sample = HashMap::with_capacity(6);
+    sample.insert("pid".to_string(), Value::Number(pid.as_u32() as f64));
+    sample.insert("time_ms".to_string(), Value::Number(elapsed.as_millis() as f64));
+    // Percentage of a single core; may exceed 100 for multi-threaded processes
+    sample.insert("cpu_percent".to_string(), Value::Number(process.cpu_usage() as f64));
+    sample.insert("rss".to_string(), Value::Number(process.memory() as f64));
+    sample.insert("virtual_memory".to_string(), Value::Number(process.virtual_memory() as f64));
+    sample.insert(
+        "open_fds".to_string(),
+        process.open_files().map_or(Value::Null, |count| Value::Number(count as f64)),
+    );
+    Value::dictionary(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bytecode::Value;
+    use crate::grease::Grease;
+    use crate::vm::InterpretResult;
+
+    #[test]
+    fn test_monitor_current_process_time_series() {
+        let mut grease = Grease::new();
+        let result = grease.run("use system\nseries = system.monitor_process(null, 2, 5, null)").unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        match grease.vm.globals.get("series") {
+            Some(Value::Array(samples)) => {
+                let samples = samples.lock().unwrap();
+                assert_eq!(samples.len(), 2);
+                match &samples[0] {
+                    Value::Dictionary(sample) => {
+                        let sample = sample.lock().unwrap();
+                        assert!(matches!(sample.get("rss"), Some(Value::Number(n)) if *n > 0.0));
+                        assert!(matches!(sample.get("cpu_percent"), Some(Value::Number(_))));
+                        assert!(sample.contains_key("open_fds"));
+                    }
+                    other => panic!("Expected sample dictionary, got {:?}", other),
+                }
+            }
+            other => panic!("Expected array of samples, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_monitor_callback_can_stop_sampling() {
+        let mut grease = Grease::new();
+        let source = "def on_sample(sample):\n    return false\nuse system\nseries = system.monitor_process(null, 5, 1, on_sample)";
+        let result = grease.run(source).unwrap();
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("series"), Some(Value::Array(samples)) if samples.lock().unwrap().len() == 1));
+    }
+
+    #[test]
+    fn test_monitor_rejects_bad_arguments() {
+        let mut grease = Grease::new();
+        let result = grease.run("use system\nsystem.monitor_process(\"abc\", 1, 1, null)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(_)));
+        let result = grease.run("use system\nsystem.monitor_process(null, 0, 1, null)").unwrap();
+        assert!(matches!(result, InterpretResult::RuntimeError(_)));
+        for interval in ["py.float(\"inf\")", "py.float(\"nan\")", "-1"] {
+            let result = grease.run(&format!("use system\nuse py\nsystem.monitor_process(null, 1, {}, null)", interval)).unwrap();
+            assert!(matches!(result, InterpretResult::RuntimeError(ref e) if e.contains("finite, non-negative interval")), "{}: {:?}", interval, result);
+        }
+    }
+
+    #[test]
+    fn test_monitor_takes_any_sample_count() {
+        // Nothing is set aside for samples that may never be taken
+        let mut grease = Grease::new();
+        let source = "def on_sample(sample):\n    return false\nuse system\nseries = system.monitor_process(null, 1000000000000000000, 0, on_sample)";
+        assert_eq!(grease.run(source).unwrap(), InterpretResult::Ok);
+        assert!(matches!(grease.vm.globals.get("series"), Some(Value::Array(samples)) if samples.lock().unwrap().len() == 1));
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! The `template` module: `template.render(text, context)` fills in
+//! `{{ name }}` placeholders and runs `{% for %}` and `{% if %}` blocks
+//! against a dictionary, for generating configs, HTML and reports.
+//!
+//! ```text
+//! {% for user in users %}{{ user.name }}{% if user.admin %} (admin){% endif %}
+//! {% endfor %}
+//! ```
+//!
+//! Placeholders and conditions are dotted paths into the context: dictionary
+//! keys, object fields, or array/tuple indices (`items.0`). Conditions may be
+//! negated with `not`. `{% for key, value in dict %}` walks a dictionary in
+//! key order.
+
+use crate::bytecode::{Arity, NativeFunction, Value};
+use crate::vm::VM;
+use std::collections::HashMap;
+
+pub fn register_natives(vm: &mut VM) {
+    let render = Value::NativeFunction(NativeFunction {
+        name: "render".to_string(),
+        arity: Arity::from(2),
+        function: |_vm, args| match (&args[0], &args[1]) {
+            (Value::String(text), Value::Dictionary(context)) => {
+                let context = context.lock().unwrap().clone();
+                render(text, &context).map(Value::String)
+            }
+            _ => Err("TypeError: template.render expects (template string, dictionary)".to_string()),
+        },
+    });
+    let mut members = HashMap::new();
+    members.insert("render".to_string(), render);
+    vm.modules.insert("template".to_string(), members);
+}
+
+/// Render `text` with the variables in `context`.
+pub fn render(text: &str, context: &HashMap<String, Value>) -> Result<String, String> {
+    let mut segments = tokenize(text)?.into_iter().peekable();
+    let nodes = parse_block(&mut segments, &[])?;
+
+    let mut scopes = vec![context.clone()];
+    let mut output = String::new();
+    render_nodes(&nodes, &mut scopes, &mut output)?;
+    Ok(output)
+}
+
+enum Segment {
+    Text(String),
+    Expression(String),
+    Tag(String),
+}
+
+enum Node {
+    Text(String),
+    Expression(String),
+    For {
+        names: Vec<String>,
+        iterable: String,
+        body: Vec<Node>,
+    },
+    If {
+        /// Conditions with their bodies, for `if` and each `elif`.
+        branches: Vec<(String, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+}
+
+fn tokenize(text: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    // Braces not followed by `{` or `%` are ordinary text
+    let next_opener = |rest: &str| rest.match_indices('{').map(|(index, _)| index).find(|&index| rest[index + 1..].starts_with(['{', '%']));
+    while let Some(start) = next_opener(rest) {
+        let opener = &rest[start..];
+        let (close, is_tag) = if opener.starts_with("{{") { ("}}", false) } else { ("%}", true) };
+
+        if start > 0 {
+            segments.push(Segment::Text(rest[..start].to_string()));
+        }
+        let inner = &opener[2..];
+        let end = inner.find(close).ok_or_else(|| format!("ValueError: Unclosed '{}' in template", &opener[..2]))?;
+        let content = inner[..end].trim().to_string();
+        segments.push(if is_tag { Segment::Tag(content) } else { Segment::Expression(content) });
+        rest = &inner[end + 2..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_string()));
+    }
+    Ok(segments)
+}
+
+/// Parse nodes until one of the `terminators` tags (left unconsumed) or the
+/// end of the template.
+fn parse_block(segments: &mut std::iter::Peekable<std::vec::IntoIter<Segment>>, terminators: &[&str]) -> Result<Vec<Node>, String> {
+    let mut nodes = Vec::new();
+    while let Some(segment) = segments.peek() {
+        if let Segment::Tag(tag) = segment {
+            let keyword = tag.split_whitespace().next().unwrap_or("");
+            if terminators.contains(&keyword) {
+                return Ok(nodes);
+            }
+        }
+        match segments.next().expect("peeked above") {
+            Segment::Text(text) => nodes.push(Node::Text(text)),
+            Segment::Expression(path) => nodes.push(Node::Expression(path)),
+            Segment::Tag(tag) => nodes.push(parse_tag(&tag, segments)?),
+        }
+    }
+    if terminators.is_empty() {
+        Ok(nodes)
+    } else {
+        Err(format!("ValueError: Missing '{{% {} %}}' in template", terminators.last().expect("checked non-empty")))
+    }
+}
+
+fn parse_tag(tag: &str, segments: &mut std::iter::Peekable<std::vec::IntoIter<Segment>>) -> Result<Node, String> {
+    let (keyword, rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    match keyword {
+        "for" => {
+            let (names, iterable) = rest.split_once(" in ")
+                .ok_or_else(|| format!("ValueError: Expected '{{% for name in items %}}', got '{{% {} %}}'", tag))?;
+            let names: Vec<String> = names.split(',').map(|name| name.trim().to_string()).collect();
+            if names.is_empty() || names.len() > 2 || names.iter().any(|name| name.is_empty()) {
+                return Err(format!("ValueError: Expected one or two loop variables in '{{% {} %}}'", tag));
+            }
+            let body = parse_block(segments, &["endfor"])?;
+            segments.next();
+            Ok(Node::For { names, iterable: iterable.trim().to_string(), body })
+        }
+        "if" => {
+            let mut branches = Vec::new();
+            let mut condition = rest.trim().to_string();
+            loop {
+                let body = parse_block(segments, &["elif", "else", "endif"])?;
+                branches.push((condition, body));
+                match segments.next() {
+                    Some(Segment::Tag(next)) if next.starts_with("elif") => {
+                        condition = next["elif".len()..].trim().to_string();
+                    }
+                    Some(Segment::Tag(next)) if next == "else" => {
+                        let otherwise = parse_block(segments, &["endif"])?;
+                        segments.next();
+                        return Ok(Node::If { branches, otherwise });
+                    }
+                    _ => return Ok(Node::If { branches, otherwise: Vec::new() }),
+                }
+            }
+        }
+        "elif" | "else" | "endif" | "endfor" => Err(format!("ValueError: Unexpected '{{% {} %}}' in template", tag)),
+        _ => Err(format!("ValueError: Unknown template tag '{{% {} %}}'", tag)),
+    }
+}
+
+fn render_nodes(nodes: &[Node], scopes: &mut Vec<HashMap<String, Value>>, output: &mut String) -> Result<(), String> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Expression(path) => output.push_str(&VM::format_value(&lookup(scopes, path)?)),
+            Node::For { names, iterable, body } => {
+                let items: Vec<Vec<Value>> = match lookup(scopes, iterable)? {
+                    Value::Array(items) => items.lock().unwrap().iter().map(|item| vec![item.clone()]).collect(),
+                    Value::Tuple(items) => items.into_iter().map(|item| vec![item]).collect(),
+                    Value::Dictionary(dict) => {
+                        let mut entries: Vec<(String, Value)> = dict.lock().unwrap().clone().into_iter().collect();
+                        entries.sort_by(|a, b| a.0.cmp(&b.0));
+                        entries.into_iter().map(|(key, value)| vec![Value::String(key), value]).collect()
+                    }
+                    other => return Err(format!("TypeError: Cannot loop over {} in template", VM::format_value(&other))),
+                };
+                for mut values in items {
+                    // Two loop variables also unpack pairs such as `(key, value)`
+                    if names.len() == 2 && values.len() == 1 {
+                        let pair = match &values[0] {
+                            Value::Array(pair) => Some(pair.lock().unwrap().clone()),
+                            Value::Tuple(pair) => Some(pair.clone()),
+                            _ => None,
+                        };
+                        if let Some(pair) = pair {
+                            values = pair;
+                        }
+                    }
+                    if names.len() == 2 && values.len() != 2 {
+                        return Err(format!("TypeError: Cannot unpack '{}' items into 2 variables", iterable));
+                    }
+                    let scope: HashMap<String, Value> = names.iter().cloned().zip(values).collect();
+                    scopes.push(scope);
+                    let result = render_nodes(body, scopes, output);
+                    scopes.pop();
+                    result?;
+                }
+            }
+            Node::If { branches, otherwise } => {
+                let mut chosen = otherwise;
+                for (condition, body) in branches {
+                    if evaluate_condition(scopes, condition)? {
+                        chosen = body;
+                        break;
+                    }
+                }
+                render_nodes(chosen, scopes, output)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn evaluate_condition(scopes: &[HashMap<String, Value>], condition: &str) -> Result<bool, String> {
+    match condition.strip_prefix("not ") {
+        Some(rest) => evaluate_condition(scopes, rest.trim()).map(|value| !value),
+        // A missing variable is simply false in a condition
+        None => Ok(lookup(scopes, condition).map(|value| VM::is_truthy(&value)).unwrap_or(false)),
+    }
+}
+
+/// Resolve a dotted path such as `user.address.city` or `items.0`.
+fn lookup(scopes: &[HashMap<String, Value>], path: &str) -> Result<Value, String> {
+    let mut parts = path.split('.').map(str::trim);
+    let name = parts.next().unwrap_or("");
+    let mut value = scopes.iter().rev().find_map(|scope| scope.get(name)).cloned()
+        .ok_or_else(|| format!("Undefined template variable '{}'", name))?;
+    for part in parts {
+        value = match &value {
+            Value::Dictionary(dict) => dict.lock().unwrap().get(part).cloned(),
+            Value::Object { fields, .. } => fields.lock().unwrap().get(part).cloned(),
+            Value::Array(items) => part.parse::<usize>().ok().and_then(|index| items.lock().unwrap().get(index).cloned()),
+            Value::Tuple(items) => part.parse::<usize>().ok().and_then(|index| items.get(index).cloned()),
+            _ => None,
+        }
+        .ok_or_else(|| format!("Undefined template variable '{}'", path))?;
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(entries: &[(&str, Value)]) -> HashMap<String, Value> {
+        entries.iter().map(|(key, value)| (key.to_string(), value.clone())).collect()
+    }
+
+    fn user(name: &str, admin: bool) -> Value {
+        Value::dictionary(context(&[("name", Value::String(name.to_string())), ("admin", Value::Boolean(admin))]))
+    }
+
+    #[test]
+    fn test_render_variables_loops_and_conditionals() {
+        let ctx = context(&[
+            ("title", Value::String("Team".to_string())),
+            ("users", Value::array(vec![user("Ada", true), user("Linus", false)])),
+            ("ports", Value::dictionary(context(&[("https", Value::Number(443.0)), ("http", Value::Number(80.0))]))),
+            ("pairs", Value::array(vec![Value::Tuple(vec![Value::String("x".to_string()), Value::String("y".to_string())])])),
+        ]);
+        let text = "# {{ title }} ({{ users.1.name }})\n{% for u in users %}- {{ u.name }}{% if u.admin %} *{% elif not u.name %}?{% else %}.{% endif %}\n{% endfor %}{% for name, port in ports %}{{name}}={{port}};{% endfor %}{% for a, b in pairs %}{{a}}{{b}}{% endfor %}";
+        assert_eq!(render(text, &ctx).unwrap(), "# Team (Linus)\n- Ada *\n- Linus.\nhttp=80;https=443;xy");
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let ctx = context(&[("items", Value::array(Vec::new()))]);
+        assert_eq!(render("{{ missing }}", &ctx).unwrap_err(), "Undefined template variable 'missing'");
+        assert!(render("{% for x in items %}", &ctx).unwrap_err().contains("Missing '{% endfor %}'"));
+        assert!(render("{{ items", &ctx).unwrap_err().contains("Unclosed '{{'"));
+        assert!(render("{% endif %}", &ctx).unwrap_err().contains("Unexpected"));
+        // Lone braces and missing condition variables are fine
+        assert_eq!(render("{a} {% if nope %}x{% endif %}", &ctx).unwrap(), "{a} ");
+    }
+}
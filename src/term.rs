@@ -0,0 +1,282 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::bytecode::Value;
+use crate::sandbox::{require, Capability};
+use crate::vm::VM;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::collections::HashMap;
+
+/// Widest bar `term.progress` draws, far wider than any terminal.
+const MAX_PROGRESS_WIDTH: f64 = 10_000.0;
+
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+pub fn register_natives(vm: &mut VM) {
+    vm.register_module_native("term", "styled", 2, |_vm, args| {
+        match (&args[0], &args[1]) {
+            (Value::String(text), Value::String(spec)) => {
+                styled(text, spec, colors_enabled()).map(Value::String)
+            }
+            _ => Err("TypeError: term.styled expects (text, style) strings".to_string()),
+        }
+    });
+
+    vm.register_module_native("term", "write", 1, |vm, args| {
+        require(vm, Capability::Terminal, "term.write")?;
+        let text = match &args[0] {
+            Value::String(s) => s.clone(),
+            other => VM::format_value(other),
+        };
+        vm.write_output_partial(&text)?;
+        Ok(Value::Null)
+    });
+
+    vm.register_module_native("term", "clear", 0, |vm, _args| {
+        require(vm, Capability::Terminal, "term.clear")?;
+        vm.write_output_partial("\x1b[2J\x1b[H")?;
+        Ok(Value::Null)
+    });
+
+    vm.register_module_native("term", "clear_line", 0, |vm, _args| {
+        require(vm, Capability::Terminal, "term.clear_line")?;
+        vm.write_output_partial("\r\x1b[2K")?;
+        Ok(Value::Null)
+    });
+
+    vm.register_module_native("term", "move_cursor", 2, |vm, args| {
+        require(vm, Capability::Terminal, "term.move_cursor")?;
+        match (&args[0], &args[1]) {
+            (Value::Number(column), Value::Number(row)) if *column >= 0.0 && *row >= 0.0 => {
+                // ANSI cursor positions are 1-based; scripts use 0-based coordinates
+                vm.write_output_partial(&format!("\x1b[{};{}H", *row as u64 + 1, *column as u64 + 1))?;
+                Ok(Value::Null)
+            }
+            _ => Err("TypeError: term.move_cursor expects non-negative (column, row) numbers".to_string()),
+        }
+    });
+
+    vm.register_module_native("term", "show_cursor", 1, |vm, args| {
+        require(vm, Capability::Terminal, "term.show_cursor")?;
+        match &args[0] {
+            Value::Boolean(true) => vm.write_output_partial("\x1b[?25h")?,
+            Value::Boolean(false) => vm.write_output_partial("\x1b[?25l")?,
+            _ => return Err("TypeError: term.show_cursor expects a boolean".to_string()),
+        }
+        Ok(Value::Null)
+    });
+
+    vm.register_module_native("term", "size", 0, |vm, _args| {
+        require(vm, Capability::Terminal, "term.size")?;
+        let (columns, rows) = terminal::size().map_err(|e| format!("IOError: Failed to query terminal size: {}", e))?;
+        let mut size = HashMap::with_capacity(2);
+        size.insert("columns".to_string(), Value::Number(columns as f64));
+        size.insert("rows".to_string(), Value::Number(rows as f64));
+        Ok(Value::dictionary(size))
+    });
+
+    vm.register_module_native("term", "progress", 3, |_vm, args| {
+        match (&args[0], &args[1], &args[2]) {
+            (Value::Number(current), Value::Number(total), Value::Number(width)) if (1.0..=MAX_PROGRESS_WIDTH).contains(width) => {
+                Ok(Value::String(progress_bar(*current, *total, *width as usize)))
+            }
+            (Value::Number(_), Value::Number(_), Value::Number(width)) => {
+                Err(format!("TypeError: term.progress expects a width from 1 to {}, got {}", MAX_PROGRESS_WIDTH, width))
+            }
+            _ => Err("TypeError: term.progress expects (current, total, width) numbers".to_string()),
+        }
+    });
+
+    vm.register_module_native("term", "spinner", 1, |_vm, args| {
+        match &args[0] {
+            Value::Number(frame) if *frame >= 0.0 => {
+                Ok(Value::String(SPINNER_FRAMES[*frame as usize % SPINNER_FRAMES.len()].to_string()))
+            }
+            _ => Err("TypeError: term.spinner expects a non-negative frame number".to_string()),
+        }
+    });
+
+    vm.register_module_native("term", "read_key", 0, |vm, _args| {
+        require(vm, Capability::Terminal, "term.read_key")?;
+        read_key().map(Value::String)
+    });
+}
+
+/// Wrap `text` in the ANSI escape codes described by a space-separated style
+/// spec such as `"red bold"` or `"white on_blue underline"`.
+pub fn styled(text: &str, spec: &str, enabled: bool) -> Result<String, String> {
+    let mut codes = Vec::new();
+    for word in spec.split_whitespace() {
+        let code = match word {
+            "bold" => 1,
+            "dim" => 2,
+            "italic" => 3,
+            "underline" => 4,
+            "blink" => 5,
+            "reverse" => 7,
+            "strike" => 9,
+            _ => {
+                let (name, background) = match word.strip_prefix("on_") {
+                    Some(name) => (name, true),
+                    None => (word, false),
+                };
+                let (name, bright) = match name.strip_prefix("bright_") {
+                    Some(name) => (name, true),
+                    None => (name, false),
+                };
+                let base = color_offset(name).ok_or_else(|| format!("Unknown style '{}'", word))?;
+                base + if background { 40 } else { 30 } + if bright { 60 } else { 0 }
+            }
+        };
+        codes.push(code.to_string());
+    }
+
+    if !enabled || codes.is_empty() {
+        return Ok(text.to_string());
+    }
+    Ok(format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text))
+}
+
+fn color_offset(name: &str) -> Option<u8> {
+    match name {
+        "black" => Some(0),
+        "red" => Some(1),
+        "green" => Some(2),
+        "yellow" => Some(3),
+        "blue" => Some(4),
+        "magenta" => Some(5),
+        "cyan" => Some(6),
+        "white" => Some(7),
+        _ => None,
+    }
+}
+
+/// Honour the NO_COLOR convention (https://no-color.org).
+fn colors_enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Render a textual progress bar such as `[#####-----] 50%`.
+pub fn progress_bar(current: f64, total: f64, width: usize) -> String {
+    let ratio = if total > 0.0 { (current / total).clamp(0.0, 1.0) } else { 1.0 };
+    let filled = ((ratio * width as f64).round() as usize).min(width);
+    format!(
+        "[{}{}] {:>3}%",
+        "#".repeat(filled),
+        "-".repeat(width - filled),
+        (ratio * 100.0).round() as u32
+    )
+}
+
+/// Read a single key press in raw mode and return its name, e.g. `"a"`,
+/// `"Enter"`, `"Up"` or `"Ctrl+c"`.
+fn read_key() -> Result<String, String> {
+    terminal::enable_raw_mode().map_err(|e| format!("IOError: Failed to enable raw mode: {}", e))?;
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => {
+                let name = key_name(key.code);
+                if key.modifiers.contains(KeyModifiers::CONTROL) {
+                    break Ok(format!("Ctrl+{}", name));
+                }
+                if key.modifiers.contains(KeyModifiers::ALT) {
+                    break Ok(format!("Alt+{}", name));
+                }
+                break Ok(name);
+            }
+            Ok(_) => continue,
+            Err(e) => break Err(format!("IOError: Failed to read key: {}", e)),
+        }
+    };
+    // Always restore the terminal, even if reading failed
+    terminal::disable_raw_mode().map_err(|e| format!("IOError: Failed to disable raw mode: {}", e))?;
+    result
+}
+
+fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::BackTab => "BackTab".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::Delete => "Delete".to_string(),
+        KeyCode::Insert => "Insert".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        _ => "Unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_styled_combines_codes() {
+        assert_eq!(styled("hi", "red bold", true).unwrap(), "\x1b[31;1mhi\x1b[0m");
+        assert_eq!(styled("hi", "bright_green on_blue", true).unwrap(), "\x1b[92;44mhi\x1b[0m");
+    }
+
+    #[test]
+    fn test_styled_disabled_or_empty_is_plain() {
+        assert_eq!(styled("hi", "red", false).unwrap(), "hi");
+        assert_eq!(styled("hi", "", true).unwrap(), "hi");
+    }
+
+    #[test]
+    fn test_styled_rejects_unknown_style() {
+        assert!(styled("hi", "sparkly", true).is_err());
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar(5.0, 10.0, 10), "[#####-----]  50%");
+        assert_eq!(progress_bar(20.0, 10.0, 4), "[####] 100%");
+        assert_eq!(progress_bar(0.0, 0.0, 2), "[##] 100%");
+
+        let mut grease = crate::grease::Grease::new();
+        let result = grease.run("use term\nterm.progress(1, 1, 100000000000000000000)").unwrap();
+        assert!(matches!(result, crate::vm::InterpretResult::RuntimeError(e) if e.contains("width from 1 to 10000")));
+    }
+
+    #[test]
+    fn test_terminal_writes_go_to_the_script_output() {
+        #[derive(Clone, Default)]
+        struct Buffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for Buffer {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(bytes);
+                Ok(bytes.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Buffer::default();
+        let mut grease = crate::grease::Grease::new().with_stdout(Box::new(output.clone()));
+        let result = grease.run("use term\nterm.clear()\nterm.move_cursor(2, 0)\nterm.write(\"hi\")\nterm.show_cursor(false)").unwrap();
+        assert_eq!(result, crate::vm::InterpretResult::Ok);
+        assert_eq!(String::from_utf8(output.0.lock().unwrap().clone()).unwrap(), "\x1b[2J\x1b[H\x1b[1;3Hhi\x1b[?25l");
+    }
+
+    #[test]
+    fn test_spinner_native_wraps_frames() {
+        let mut grease = crate::grease::Grease::new();
+        let result = grease.run("use term\na = term.spinner(0)\nb = term.spinner(10)").unwrap();
+        assert_eq!(result, crate::vm::InterpretResult::Ok);
+        let a = grease.vm.globals.get("a").map(VM::format_value);
+        let b = grease.vm.globals.get("b").map(VM::format_value);
+        assert_eq!(a, b);
+    }
+}
@@ -1,11 +1,14 @@
 // Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
 // SPDX-License-Identifier: Apache-2.0
 
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Literals
     Number(f64),
     String(String),
+    Bytes(Vec<u8>),
     Boolean(bool),
     Identifier(String),
     
@@ -17,11 +20,17 @@ pub enum TokenType {
     While,
     For,
     In,
+    Break,
+    Continue,
     Return,
     Use,
+    From,
+    Lazy,
     Try,
     Catch,
+    Finally,
     Throw,
+    With,
     As,
     True,
     False,
@@ -63,6 +72,7 @@ pub enum TokenType {
     Dot,          // .
     Colon,        // :
     Semicolon,    // ;
+    At,           // @
     
     // Special
     Newline,
@@ -77,17 +87,109 @@ pub enum TokenType {
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
+    /// Position of the token's first character; lines and columns count
+    /// from 1.
     pub line: usize,
     pub column: usize,
+    /// Position just past the token's last character.
+    pub end_line: usize,
+    pub end_column: usize,
 }
 
 impl Token {
+    /// A token at `line` and `column` with no extent, e.g. one the compiler
+    /// synthesizes; the lexer gives its tokens an end with `ending_at`.
     pub fn new(token_type: TokenType, lexeme: String, line: usize, column: usize) -> Self {
         Token {
             token_type,
             lexeme,
             line,
             column,
+            end_line: line,
+            end_column: column,
+        }
+    }
+
+    /// An identifier token for `name`, for front ends building an AST by hand.
+    pub fn identifier(name: &str, line: usize, column: usize) -> Self {
+        Token::new(TokenType::Identifier(name.to_string()), name.to_string(), line, column)
+            .ending_at(line, column + name.chars().count())
+    }
+
+    /// An operator or keyword token spelled as Grease spells it, e.g. `+`
+    /// for `TokenType::Plus`.
+    pub fn operator(token_type: TokenType, line: usize, column: usize) -> Self {
+        let lexeme = match &token_type {
+            TokenType::Assign => "=",
+            TokenType::Plus => "+",
+            TokenType::Minus => "-",
+            TokenType::Multiply => "*",
+            TokenType::Divide => "/",
+            TokenType::Modulo => "%",
+            TokenType::Equal => "==",
+            TokenType::NotEqual => "!=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::And => "and",
+            TokenType::Or => "or",
+            TokenType::Not => "not",
+            _ => "",
+        };
+        Token::new(token_type, lexeme.to_string(), line, column).ending_at(line, column + lexeme.len())
+    }
+
+    pub fn ending_at(mut self, line: usize, column: usize) -> Self {
+        self.end_line = line;
+        self.end_column = column;
+        self
+    }
+
+    pub fn span(&self) -> Span {
+        Span { line: self.line, column: self.column, end_line: self.end_line, end_column: self.end_column }
+    }
+
+    /// The token type's name without its payload, e.g. `Identifier`.
+    pub fn kind(&self) -> String {
+        let name = format!("{:?}", self.token_type);
+        match name.find('(') {
+            Some(paren) => name[..paren].to_string(),
+            None => name,
         }
     }
+}
+
+/// A range of source from `line`:`column` up to, but not including,
+/// `end_line`:`end_column`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        let (line, column) = (self.line, self.column).min((other.line, other.column));
+        let (end_line, end_column) = (self.end_line, self.end_column).max((other.end_line, other.end_column));
+        Span { line, column, end_line, end_column }
+    }
+}
+
+/// Tokens serialize with their position, for `grease tokens` and the spans
+/// in `grease parse --json`.
+impl Serialize for Token {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut token = serializer.serialize_struct("Token", 6)?;
+        token.serialize_field("kind", &self.kind())?;
+        token.serialize_field("lexeme", &self.lexeme)?;
+        token.serialize_field("line", &self.line)?;
+        token.serialize_field("column", &self.column)?;
+        token.serialize_field("end_line", &self.end_line)?;
+        token.serialize_field("end_column", &self.end_column)?;
+        token.end()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,566 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! `grease transpile`: Grease source to Rust source, as a path from a
+//! prototype script to a native tool. The Rust keeps Grease's dynamic
+//! values and calls the [`runtime`] shim for every operation, so it behaves
+//! as the VM does; a runtime error panics with the VM's message.
+//!
+//! Only a subset translates: number, string, boolean and null literals,
+//! arrays and indexing, arithmetic, comparison and logic operators,
+//! variables, `if`, `while`, `for`, `break`, `continue`, `throw`, top-level
+//! `def` functions and `print`. A function sees its parameters, its locals
+//! and the other functions, not the script's globals. Anything else is an
+//! error naming its line.
+//!
+//! ```text
+//! def square(x):          fn square(mut x: Value) -> Value {
+//!     return x * x            return rt::mul(&x, &x);
+//!                         }
+//! print(square(3))        fn main() { rt::print(&[square(Value::Number(3.0))]); }
+//! ```
+
+use crate::ast::{Expression, Program, Statement};
+use crate::token::{Token, TokenType};
+use std::collections::HashMap;
+
+/// What transpiled programs call: Grease's operators and builtins over
+/// [`Value`](runtime::Value)s, with the VM's semantics.
+pub mod runtime {
+    pub use crate::bytecode::Value;
+    use crate::vm::VM;
+
+    fn fail(message: &str) -> ! {
+        panic!("Runtime Error: {}", message)
+    }
+
+    pub fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    pub fn truthy(value: &Value) -> bool {
+        VM::is_truthy(value)
+    }
+
+    fn numbers(a: &Value, b: &Value) -> (f64, f64) {
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => (*a, *b),
+            _ => fail("Operands must be numbers"),
+        }
+    }
+
+    /// `+`: numbers add; with a string, the other side is appended as text.
+    pub fn add(a: &Value, b: &Value) -> Value {
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+            (Value::String(_), Value::String(_) | Value::Number(_) | Value::Boolean(_))
+            | (Value::Number(_) | Value::Boolean(_), Value::String(_)) => {
+                Value::String(VM::format_value(a) + &VM::format_value(b))
+            }
+            (Value::Bytes(a), Value::Bytes(b)) => Value::Bytes([a.as_slice(), b].concat()),
+            _ => fail("Operands must be numbers or strings"),
+        }
+    }
+
+    pub fn sub(a: &Value, b: &Value) -> Value {
+        let (a, b) = numbers(a, b);
+        Value::Number(a - b)
+    }
+
+    pub fn mul(a: &Value, b: &Value) -> Value {
+        let (a, b) = numbers(a, b);
+        Value::Number(a * b)
+    }
+
+    pub fn div(a: &Value, b: &Value) -> Value {
+        let (a, b) = numbers(a, b);
+        if b == 0.0 {
+            fail("Division by zero");
+        }
+        Value::Number(a / b)
+    }
+
+    pub fn rem(a: &Value, b: &Value) -> Value {
+        let (a, b) = numbers(a, b);
+        if b == 0.0 {
+            fail("Modulo by zero");
+        }
+        Value::Number(a % b)
+    }
+
+    pub fn neg(value: &Value) -> Value {
+        match value {
+            Value::Number(n) => Value::Number(-n),
+            _ => fail("Operand must be a number"),
+        }
+    }
+
+    pub fn not(value: &Value) -> Value {
+        Value::Boolean(!truthy(value))
+    }
+
+    /// `and`: both sides are evaluated, as in the VM.
+    pub fn and(a: &Value, b: &Value) -> Value {
+        Value::Boolean(truthy(a) && truthy(b))
+    }
+
+    pub fn or(a: &Value, b: &Value) -> Value {
+        Value::Boolean(truthy(a) || truthy(b))
+    }
+
+    pub fn eq(a: &Value, b: &Value) -> Value {
+        Value::Boolean(VM::values_equal(a, b))
+    }
+
+    pub fn ne(a: &Value, b: &Value) -> Value {
+        Value::Boolean(!VM::values_equal(a, b))
+    }
+
+    pub fn lt(a: &Value, b: &Value) -> Value {
+        let (a, b) = numbers(a, b);
+        Value::Boolean(a < b)
+    }
+
+    pub fn le(a: &Value, b: &Value) -> Value {
+        let (a, b) = numbers(a, b);
+        Value::Boolean(a <= b)
+    }
+
+    pub fn gt(a: &Value, b: &Value) -> Value {
+        let (a, b) = numbers(a, b);
+        Value::Boolean(a > b)
+    }
+
+    pub fn ge(a: &Value, b: &Value) -> Value {
+        let (a, b) = numbers(a, b);
+        Value::Boolean(a >= b)
+    }
+
+    pub fn index(target: &Value, index: &Value) -> Value {
+        VM::index(target, index).unwrap_or_else(|e| fail(&e))
+    }
+
+    pub fn slice(target: &Value, start: &Value, end: &Value) -> Value {
+        VM::slice(target, start, end).unwrap_or_else(|e| fail(&e))
+    }
+
+    /// The elements a `for` loop visits.
+    pub fn iter(value: &Value) -> Vec<Value> {
+        VM::elements(value).unwrap_or_else(|e| fail(&e))
+    }
+
+    pub fn print(values: &[Value]) -> Value {
+        let line: Vec<String> = values.iter().map(VM::format_value).collect();
+        println!("{}", line.join(" "));
+        Value::Null
+    }
+
+    pub fn throw(value: &Value) -> ! {
+        fail(&VM::format_value(value))
+    }
+}
+
+/// Rust keywords, which Grease names become raw identifiers for.
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "dyn", "enum", "extern", "fn", "gen", "impl",
+    "let", "loop", "match", "mod", "move", "mut", "priv", "pub", "ref", "static", "struct", "trait", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "yield", "abstract", "become", "do", "final",
+    "macro", "override", "try",
+];
+
+/// Rust source for `program`, saying in its header that it came from
+/// `source_name`.
+pub fn transpile(program: &Program, source_name: &str) -> Result<String, String> {
+    let mut functions = HashMap::new();
+    for statement in &program.statements {
+        if let Statement::FunctionDeclaration { name, parameters, decorators, .. } = statement {
+            if !decorators.is_empty() {
+                return Err(unsupported("Decorators", name.line));
+            }
+            if name.lexeme == "main" {
+                return Err(format!("A function can't be named 'main' in Rust (line {})", name.line));
+            }
+            functions.insert(name.lexeme.clone(), parameters.len());
+        }
+    }
+
+    let mut transpiler = Transpiler { functions, locals: Vec::new(), out: String::new(), indent: 0 };
+    transpiler.line(&format!("// Transpiled from {} by `grease transpile`.", source_name));
+    transpiler.line("#![allow(unused_mut, unused_assignments, unreachable_code, clippy::all)]");
+    transpiler.line("");
+    transpiler.line("use grease::transpile::runtime as rt;");
+    transpiler.line("use grease::transpile::runtime::Value;");
+
+    let mut main = Vec::new();
+    for statement in &program.statements {
+        match statement {
+            Statement::FunctionDeclaration { name, parameters, body, .. } => {
+                let parameters: Vec<&Token> = parameters.iter().map(|(parameter, _)| parameter).collect();
+                transpiler.function(name, &parameters, body)?;
+            }
+            statement => main.push(statement.clone()),
+        }
+    }
+    transpiler.line("");
+    transpiler.line("fn main() {");
+    transpiler.body(&[], &main, false)?;
+    transpiler.line("}");
+    Ok(transpiler.out)
+}
+
+fn unsupported(what: &str, line: usize) -> String {
+    format!("{} can't be transpiled to Rust (line {})", what, line)
+}
+
+struct Transpiler {
+    /// Each top-level function's arity.
+    functions: HashMap<String, usize>,
+    /// The variables of the function being written.
+    locals: Vec<String>,
+    out: String,
+    indent: usize,
+}
+
+impl Transpiler {
+    fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.out.push_str(&"    ".repeat(self.indent));
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn function(&mut self, name: &Token, parameters: &[&Token], body: &[Statement]) -> Result<(), String> {
+        let mut signature = Vec::new();
+        for parameter in parameters {
+            signature.push(format!("mut {}: Value", ident(parameter)?));
+        }
+        self.line("");
+        self.line(&format!("fn {}({}) -> Value {{", ident(name)?, signature.join(", ")));
+        self.body(parameters, body, true)?;
+        self.line("}");
+        Ok(())
+    }
+
+    /// A function's body: its locals declared up front, as Grease scopes
+    /// them to the whole function, then its statements.
+    fn body(&mut self, parameters: &[&Token], body: &[Statement], returns: bool) -> Result<(), String> {
+        self.indent += 1;
+        self.locals = parameters.iter().map(|parameter| parameter.lexeme.clone()).collect();
+        let mut assigned = Vec::new();
+        for statement in body {
+            assigned_names(statement, &mut assigned)?;
+        }
+        for name in assigned {
+            if !self.locals.contains(&name.lexeme) {
+                if self.functions.contains_key(&name.lexeme) {
+                    return Err(unsupported(&format!("Assigning to function '{}'", name.lexeme), name.line));
+                }
+                self.line(&format!("let mut {} = Value::Null;", ident(&name)?));
+                self.locals.push(name.lexeme.clone());
+            }
+        }
+        for statement in body {
+            self.statement(statement, returns)?;
+        }
+        if returns && !matches!(body.last(), Some(Statement::Return { .. })) {
+            self.line("Value::Null");
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    /// `header {` and the statements of `body`; the caller closes it.
+    fn block(&mut self, header: &str, body: &[Statement], returns: bool) -> Result<(), String> {
+        self.line(&format!("{}{{", if header.is_empty() { String::new() } else { format!("{} ", header) }));
+        self.indent += 1;
+        for statement in body {
+            self.statement(statement, returns)?;
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement, returns: bool) -> Result<(), String> {
+        let line = statement.span().map_or(0, |span| span.line);
+        match statement {
+            Statement::Expression(Expression::Assignment { name, value }) => {
+                let value = self.expression(value)?;
+                self.line(&format!("{} = {};", ident(name)?, value));
+            }
+            Statement::Expression(expression) => {
+                let expression = self.expression(expression)?;
+                self.line(&format!("{};", expression));
+            }
+            Statement::VariableDeclaration { name, initializer, .. } => {
+                let value = match initializer {
+                    Some(initializer) => self.expression(initializer)?,
+                    None => "Value::Null".to_string(),
+                };
+                self.line(&format!("{} = {};", ident(name)?, value));
+            }
+            Statement::Return { value } => {
+                if !returns {
+                    return Err(unsupported("'return' outside a function", line));
+                }
+                let value = match value {
+                    Some(value) => self.expression(value)?,
+                    None => "Value::Null".to_string(),
+                };
+                self.line(&format!("return {};", value));
+            }
+            Statement::If { condition, then_branch, else_branch } => {
+                let condition = self.condition(condition)?;
+                self.block(&format!("if {}", condition), then_branch, returns)?;
+                if let Some(else_branch) = else_branch {
+                    self.block("} else", else_branch, returns)?;
+                }
+                self.line("}");
+            }
+            Statement::While { condition, body } => {
+                let condition = self.condition(condition)?;
+                self.block(&format!("while {}", condition), body, returns)?;
+                self.line("}");
+            }
+            Statement::For { variable, iterable, body } => {
+                // The variable keeps the last element after the loop, as in
+                // Grease
+                let iterable = self.borrowed(iterable)?;
+                self.line(&format!("for __item in rt::iter({}) {{", iterable));
+                self.indent += 1;
+                self.line(&format!("{} = __item;", ident(variable)?));
+                for statement in body {
+                    self.statement(statement, returns)?;
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            Statement::Break => self.line("break;"),
+            Statement::Continue => self.line("continue;"),
+            Statement::Block(statements) => {
+                self.block("", statements, returns)?;
+                self.line("}");
+            }
+            Statement::Throw { value: Some(value) } => {
+                let value = self.borrowed(value)?;
+                self.line(&format!("rt::throw({});", value));
+            }
+            Statement::FunctionDeclaration { name, .. } => return Err(unsupported("Nested functions", name.line)),
+            Statement::Unpack { .. } => return Err(unsupported("Unpacking", line)),
+            Statement::Use { .. } | Statement::UsePlugin { .. } => return Err(unsupported("Modules", line)),
+            Statement::ClassDeclaration { .. } => return Err(unsupported("Classes", line)),
+            Statement::Try { .. } => return Err(unsupported("'try'", line)),
+            Statement::With { .. } => return Err(unsupported("'with'", line)),
+            Statement::Throw { value: None } => return Err(unsupported("A bare 'throw'", line)),
+            Statement::RustInline { .. } | Statement::AsmInline { .. } => return Err(unsupported("Inline code", line)),
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self, condition: &Expression) -> Result<String, String> {
+        Ok(format!("rt::truthy({})", self.borrowed(condition)?))
+    }
+
+    /// A reference to the expression's value, without cloning a variable.
+    fn borrowed(&mut self, expression: &Expression) -> Result<String, String> {
+        match expression {
+            Expression::Identifier(name) => Ok(format!("&{}", self.variable(name)?)),
+            Expression::Grouping(inner) => self.borrowed(inner),
+            expression => Ok(format!("&{}", self.expression(expression)?)),
+        }
+    }
+
+    fn variable(&self, name: &Token) -> Result<String, String> {
+        if self.locals.contains(&name.lexeme) {
+            ident(name)
+        } else if self.functions.contains_key(&name.lexeme) {
+            Err(unsupported(&format!("Function '{}' used as a value", name.lexeme), name.line))
+        } else {
+            Err(format!("'{}' isn't a parameter or local here; transpiled functions can't see globals (line {})", name.lexeme, name.line))
+        }
+    }
+
+    /// Rust for the expression's value, as an owned `Value`.
+    fn expression(&mut self, expression: &Expression) -> Result<String, String> {
+        let line = expression.span().map_or(0, |span| span.line);
+        Ok(match expression {
+            Expression::Number(n) => format!("Value::Number({:?})", n),
+            Expression::String(s) => format!("rt::string({:?})", s),
+            Expression::Bytes(bytes) => format!("Value::Bytes(vec!{:?})", bytes),
+            Expression::Boolean(b) => format!("Value::Boolean({})", b),
+            Expression::Null => "Value::Null".to_string(),
+            Expression::Identifier(name) => format!("{}.clone()", self.variable(name)?),
+            Expression::Grouping(inner) => self.expression(inner)?,
+            Expression::Binary { left, operator, right } => {
+                let function = match operator.token_type {
+                    TokenType::Plus => "add",
+                    TokenType::Minus => "sub",
+                    TokenType::Multiply => "mul",
+                    TokenType::Divide => "div",
+                    TokenType::Modulo => "rem",
+                    TokenType::Equal => "eq",
+                    TokenType::NotEqual => "ne",
+                    TokenType::Less => "lt",
+                    TokenType::LessEqual => "le",
+                    TokenType::Greater => "gt",
+                    TokenType::GreaterEqual => "ge",
+                    TokenType::And => "and",
+                    TokenType::Or => "or",
+                    _ => return Err(unsupported(&format!("Operator '{}'", operator.lexeme), operator.line)),
+                };
+                format!("rt::{}({}, {})", function, self.borrowed(left)?, self.borrowed(right)?)
+            }
+            Expression::Unary { operator, right } => {
+                let function = match operator.token_type {
+                    TokenType::Minus => "neg",
+                    TokenType::Not => "not",
+                    _ => return Err(unsupported(&format!("Operator '{}'", operator.lexeme), operator.line)),
+                };
+                format!("rt::{}({})", function, self.borrowed(right)?)
+            }
+            Expression::Assignment { name, value } => {
+                let name = ident(name)?;
+                format!("{{ {} = {}; {}.clone() }}", name, self.expression(value)?, name)
+            }
+            Expression::Array(elements) => format!("Value::array(vec![{}])", self.arguments(elements)?),
+            Expression::Index { array, index } => format!("rt::index({}, {})", self.borrowed(array)?, self.borrowed(index)?),
+            Expression::Slice { array, start, end } => {
+                let bound = |this: &mut Self, bound: &Option<Box<Expression>>| match bound {
+                    Some(bound) => this.borrowed(bound),
+                    None => Ok("&Value::Null".to_string()),
+                };
+                let (start, end) = (bound(self, start)?, bound(self, end)?);
+                format!("rt::slice({}, {}, {})", self.borrowed(array)?, start, end)
+            }
+            Expression::Call { callee, arguments } => {
+                let Expression::Identifier(name) = callee.as_ref() else {
+                    return Err(unsupported("Calling a computed value", line));
+                };
+                match self.functions.get(&name.lexeme) {
+                    Some(&arity) if arity == arguments.len() => format!("{}({})", ident(name)?, self.arguments(arguments)?),
+                    Some(&arity) => return Err(format!(
+                        "'{}' takes {} arguments but is called with {} (line {})", name.lexeme, arity, arguments.len(), name.line
+                    )),
+                    None if name.lexeme == "print" => format!("rt::print(&[{}])", self.arguments(arguments)?),
+                    None => return Err(unsupported(&format!("Calling '{}', which isn't a function in the file,", name.lexeme), name.line)),
+                }
+            }
+            Expression::Tuple(_) => return Err(unsupported("Tuples", line)),
+            Expression::Dictionary(_) => return Err(unsupported("Dictionaries", line)),
+            Expression::PropertyAssignment { .. } | Expression::PropertyAccess { .. } | Expression::MethodCall { .. }
+            | Expression::NewInstance { .. } | Expression::SuperCall { .. } => return Err(unsupported("Objects", line)),
+            Expression::ModuleAccess { .. } => return Err(unsupported("Modules", line)),
+            Expression::RustInline { .. } | Expression::AsmInline { .. } => return Err(unsupported("Inline code", line)),
+        })
+    }
+
+    fn arguments(&mut self, arguments: &[Expression]) -> Result<String, String> {
+        let arguments = arguments.iter().map(|argument| self.expression(argument)).collect::<Result<Vec<_>, _>>()?;
+        Ok(arguments.join(", "))
+    }
+}
+
+/// The Rust identifier for a Grease name.
+fn ident(name: &Token) -> Result<String, String> {
+    match name.lexeme.as_str() {
+        "self" | "Self" | "super" | "crate" | "_" => {
+            Err(format!("'{}' can't be a Rust name; rename it (line {})", name.lexeme, name.line))
+        }
+        lexeme if RUST_KEYWORDS.contains(&lexeme) => Ok(format!("r#{}", lexeme)),
+        lexeme => Ok(lexeme.to_string()),
+    }
+}
+
+/// The variables `statement` assigns, in order, outside nested functions.
+fn assigned_names(statement: &Statement, names: &mut Vec<Token>) -> Result<(), String> {
+    let mut add = |name: &Token| {
+        if !names.iter().any(|known| known.lexeme == name.lexeme) {
+            names.push(name.clone());
+        }
+    };
+    match statement {
+        Statement::Expression(Expression::Assignment { name, .. }) => add(name),
+        Statement::VariableDeclaration { name, .. } => add(name),
+        Statement::For { variable, body, .. } => {
+            add(variable);
+            for statement in body {
+                assigned_names(statement, names)?;
+            }
+        }
+        Statement::If { then_branch, else_branch, .. } => {
+            for statement in then_branch.iter().chain(else_branch.iter().flatten()) {
+                assigned_names(statement, names)?;
+            }
+        }
+        Statement::While { body, .. } | Statement::Block(body) => {
+            for statement in body {
+                assigned_names(statement, names)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn rust(source: &str) -> Result<String, String> {
+        let program = Parser::new(Lexer::new(source.to_string()).tokenize()?).parse()?;
+        transpile(&program, "test.grease")
+    }
+
+    #[test]
+    fn test_transpile_functions_and_loops() {
+        let source = "def fib(n):\n    if n < 2:\n        return n\n    return fib(n - 1) + fib(n - 2)\n\ntotal = 0\nfor x in [1, 2, 3]:\n    total = total + fib(x)\nprint(\"total\", total)\n";
+        assert_eq!(rust(source).unwrap(), concat!(
+            "// Transpiled from test.grease by `grease transpile`.\n",
+            "#![allow(unused_mut, unused_assignments, unreachable_code, clippy::all)]\n",
+            "\n",
+            "use grease::transpile::runtime as rt;\n",
+            "use grease::transpile::runtime::Value;\n",
+            "\n",
+            "fn fib(mut n: Value) -> Value {\n",
+            "    if rt::truthy(&rt::lt(&n, &Value::Number(2.0))) {\n",
+            "        return n.clone();\n",
+            "    }\n",
+            "    return rt::add(&fib(rt::sub(&n, &Value::Number(1.0))), &fib(rt::sub(&n, &Value::Number(2.0))));\n",
+            "}\n",
+            "\n",
+            "fn main() {\n",
+            "    let mut total = Value::Null;\n",
+            "    let mut x = Value::Null;\n",
+            "    total = Value::Number(0.0);\n",
+            "    for __item in rt::iter(&Value::array(vec![Value::Number(1.0), Value::Number(2.0), Value::Number(3.0)])) {\n",
+            "        x = __item;\n",
+            "        total = rt::add(&total, &fib(x.clone()));\n",
+            "    }\n",
+            "    rt::print(&[rt::string(\"total\"), total.clone()]);\n",
+            "}\n",
+        ));
+    }
+
+    #[test]
+    fn test_transpile_rejects_what_it_cannot_translate() {
+        assert!(rust("limit = 3\ndef f():\n    return limit\n").unwrap_err().contains("can't see globals (line 3)"));
+        assert!(rust("class A:\n    def f(self):\n        return 1\n").unwrap_err().contains("Classes"));
+        assert!(rust("x = input()\n").unwrap_err().contains("Calling 'input'"));
+        assert!(rust("def f(a):\n    return a\nf(1, 2)\n").unwrap_err().contains("takes 1 arguments"));
+        assert!(rust("type = 1\n").unwrap().contains("let mut r#type = Value::Null;"));
+    }
+
+    #[test]
+    fn test_runtime_matches_the_vm() {
+        use runtime::*;
+        assert!(matches!(add(&string("n="), &Value::Number(1.5)), Value::String(s) if s == "n=1.5"));
+        assert!(matches!(and(&Value::Number(1.0), &string("")), Value::Boolean(false)));
+        assert_eq!(iter(&string("ab")).len(), 2);
+        assert!(matches!(slice(&string("grease"), &Value::Number(-5.0), &Value::Null), Value::String(s) if s == "rease"));
+        assert!(matches!(index(&Value::array(vec![Value::Null, Value::Boolean(true)]), &Value::Number(-1.0)), Value::Boolean(true)));
+        let divided = std::panic::catch_unwind(|| div(&Value::Number(1.0), &Value::Number(0.0)));
+        assert_eq!(divided.unwrap_err().downcast_ref::<String>().map(String::as_str), Some("Runtime Error: Division by zero"));
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Stack-effect verification of compiled chunks.
+//!
+//! [`verify`] walks every path through a chunk, tracking how deep the stack
+//! is before each instruction. It rejects code that pops more than is there,
+//! reaches the same instruction with different depths along different paths,
+//! reads a local slot that isn't on the stack or an upvalue the function
+//! didn't capture, jumps outside the chunk or runs off its end. Functions and class methods in the constant table are
+//! verified too. The compiler runs it in debug builds; `grease check` runs it
+//! on demand.
+
+use crate::bytecode::{Chunk, OpCode, Value};
+use std::collections::HashMap;
+
+pub fn verify(chunk: &Chunk) -> Result<(), String> {
+    verify_function(chunk, 0, 0)
+}
+
+/// Verify `chunk` as the body of a function whose `arity` arguments are on
+/// the stack when it starts and that captured `upvalues` variables.
+fn verify_function(chunk: &Chunk, arity: usize, upvalues: usize) -> Result<(), String> {
+    let mut verifier = Verifier { chunk, upvalues, closures: HashMap::new(), depths: vec![None; chunk.code.len()], pending: Vec::new() };
+    verifier.run(arity).map_err(|e| {
+        let name = chunk.source_map.function.as_deref().unwrap_or("<script>");
+        format!("Invalid bytecode in {}: {}", name, e)
+    })?;
+
+    for (index, constant) in chunk.constants.iter().enumerate() {
+        match constant {
+            Value::Function(function) => {
+                let upvalues = verifier.closures.get(&index).copied().unwrap_or(0);
+                verify_function(&function.chunk, function.arity, upvalues)?;
+            }
+            Value::Class { methods, .. } => {
+                for method in methods.values() {
+                    if let Value::Function(function) = method {
+                        verify_function(&function.chunk, function.arity, 0)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+struct Verifier<'a> {
+    chunk: &'a Chunk,
+    /// Variables the function captured.
+    upvalues: usize,
+    /// Variables captured by the closures made from each function constant.
+    closures: HashMap<usize, usize>,
+    /// Stack depth on entry to each instruction reached so far.
+    depths: Vec<Option<usize>>,
+    /// Instructions reached but not yet checked.
+    pending: Vec<usize>,
+}
+
+impl Verifier<'_> {
+    fn run(&mut self, arity: usize) -> Result<(), String> {
+        self.reach(0, arity, 0)?;
+        while let Some(offset) = self.pending.pop() {
+            let depth = self.depths[offset].expect("pending instructions have a depth");
+            self.step(offset, depth).map_err(|e| self.locate(offset, e))?;
+        }
+        Ok(())
+    }
+
+    fn locate(&self, offset: usize, message: String) -> String {
+        match self.chunk.source_map.lookup(offset) {
+            Some(location) => format!("{} at offset {} (line {})", message, offset, location.line),
+            None => format!("{} at offset {}", message, offset),
+        }
+    }
+
+    /// Record that control reaches `target` from `from` with `depth` values
+    /// on the stack.
+    fn reach(&mut self, target: usize, depth: usize, from: usize) -> Result<(), String> {
+        match self.depths.get(target) {
+            None => Err(format!("Control runs past the end of the code from offset {}", from)),
+            Some(None) => {
+                self.depths[target] = Some(depth);
+                self.pending.push(target);
+                Ok(())
+            }
+            Some(Some(known)) if *known == depth => Ok(()),
+            Some(Some(known)) => Err(self.locate(
+                target,
+                format!("Stack depth {} from offset {} disagrees with depth {} from another path", depth, from, known),
+            )),
+        }
+    }
+
+    fn operand(&self, offset: usize, width: usize) -> Result<usize, String> {
+        let bytes = self.chunk.code.get(offset + 1..offset + 1 + width)
+            .ok_or_else(|| "Truncated operand".to_string())?;
+        Ok(bytes.iter().fold(0, |value, &byte| value << 8 | byte as usize))
+    }
+
+    fn constant(&self, offset: usize) -> Result<&Value, String> {
+        let index = self.operand(offset, 1)?;
+        self.chunk.constants.get(index).ok_or_else(|| format!("Constant {} out of range", index))
+    }
+
+    fn name_constant(&self, offset: usize) -> Result<(), String> {
+        match self.constant(offset)? {
+            Value::String(_) => Ok(()),
+            other => Err(format!("Expected a name constant, found {:?}", other)),
+        }
+    }
+
+    /// Check the instruction at `offset`, entered with `depth` values on the
+    /// stack, and reach its successors.
+    fn step(&mut self, offset: usize, depth: usize) -> Result<(), String> {
+        let byte = self.chunk.code[offset];
+        let op = OpCode::from_byte(byte).ok_or_else(|| format!("Unknown opcode {}", byte))?;
+        let long = op.is_long_jump();
+
+        // (values popped, values pushed, instruction length)
+        let (pops, pushes, length) = match op {
+            OpCode::Constant => {
+                self.constant(offset)?;
+                (0, 1, 2)
+            }
+            OpCode::Null | OpCode::True | OpCode::False => (0, 1, 1),
+            OpCode::GetGlobal | OpCode::Import => {
+                self.name_constant(offset)?;
+                (0, 1, 2)
+            }
+            OpCode::SetGlobal => {
+                self.name_constant(offset)?;
+                (1, 0, 2)
+            }
+            OpCode::LoadPlugin => {
+                self.name_constant(offset)?;
+                (0, 0, 2)
+            }
+            OpCode::GetLocal | OpCode::SetLocal => {
+                let slot = self.operand(offset, 1)?;
+                let pops = usize::from(matches!(op, OpCode::SetLocal));
+                // SetLocal pops the value before storing it
+                if slot + pops >= depth {
+                    return Err(format!("Local slot {} is not on the stack (depth {})", slot, depth));
+                }
+                (pops, 1 - pops, 2)
+            }
+            OpCode::Closure => {
+                if !matches!(self.constant(offset)?, Value::Function(_)) {
+                    return Err("CLOSURE needs a function constant".to_string());
+                }
+                let count = self.operand(offset + 1, 1)?;
+                for capture in 0..count {
+                    let (is_local, index) = (self.operand(offset + 2 + 2 * capture, 1)?, self.operand(offset + 3 + 2 * capture, 1)?);
+                    // A function that calls itself captures the slot its
+                    // closure is about to be pushed into
+                    if is_local == 1 && index > depth {
+                        return Err(format!("Captured local slot {} is not on the stack (depth {})", index, depth));
+                    }
+                    if is_local != 1 && index >= self.upvalues {
+                        return Err(format!("Captured upvalue {} out of range ({} captured)", index, self.upvalues));
+                    }
+                }
+                let function = self.operand(offset, 1)?;
+                match self.closures.insert(function, count) {
+                    Some(known) if known != count => return Err(format!("Closures of constant {} capture different variables", function)),
+                    _ => {}
+                }
+                (0, 1, 3 + 2 * count)
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue => {
+                let index = self.operand(offset, 1)?;
+                if index >= self.upvalues {
+                    return Err(format!("Upvalue {} out of range ({} captured)", index, self.upvalues));
+                }
+                let pops = usize::from(matches!(op, OpCode::SetUpvalue));
+                (pops, 1 - pops, 2)
+            }
+            OpCode::CloseUpvalue => (1, 0, 1),
+            OpCode::Jump | OpCode::JumpLong
+            | OpCode::JumpIfFalse | OpCode::JumpIfFalseLong
+            | OpCode::JumpIfTrue | OpCode::JumpIfTrueLong
+            | OpCode::Loop | OpCode::LoopLong
+            | OpCode::ForIter | OpCode::ForIterLong
+            | OpCode::Try | OpCode::TryLong => return self.jump(op, offset, depth, if long { 4 } else { 2 }),
+            OpCode::Dup => (1, 2, 1),
+            OpCode::Call => (self.operand(offset, 1)? + 1, 1, 2),
+            OpCode::CreateInstance => (self.operand(offset, 1)? + 1, 1, 2),
+            OpCode::CallMethod => (self.operand(offset, 1)? + 2, 1, 2),
+            OpCode::Return => return Ok(()),
+            OpCode::Throw | OpCode::Rethrow => {
+                return if depth == 0 { Err("Nothing to throw".to_string()) } else { Ok(()) };
+            }
+            OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide | OpCode::Modulo
+            | OpCode::Index | OpCode::Equal | OpCode::NotEqual | OpCode::Less | OpCode::LessEqual
+            | OpCode::Greater | OpCode::GreaterEqual | OpCode::And | OpCode::Or
+            | OpCode::GetModule | OpCode::GetProperty | OpCode::GetSuper => (2, 1, 1),
+            OpCode::Negate | OpCode::Not | OpCode::Length | OpCode::GetIter => (1, 1, 1),
+            OpCode::Array | OpCode::Tuple => (self.operand(offset, 1)?, 1, 2),
+            OpCode::Dictionary => (self.operand(offset, 1)? * 2, 1, 2),
+            OpCode::Unpack => (1, self.operand(offset, 1)?, 2),
+            OpCode::SetProperty | OpCode::Slice => (3, 1, 1),
+            OpCode::Pop => (1, 0, 1),
+            OpCode::RustInline | OpCode::AsmInline => {
+                self.constant(offset)?;
+                (0, 1, 2)
+            }
+            OpCode::Catch | OpCode::PopException | OpCode::CreateClass => (0, 0, 1),
+            OpCode::ExceptionMatches => (self.operand(offset, 1)? + 1, 1, 2),
+            // Keeps the manager and pushes what entering it returned
+            OpCode::WithEnter => (1, 2, 1),
+            OpCode::WithExit => {
+                // Pops the exception, then reads the manager `distance` below the top
+                let distance = self.operand(offset, 1)?;
+                (distance + 2, distance + 1, 2)
+            }
+        };
+
+        if pops > depth {
+            return Err(format!("Stack underflow: needs {} values, has {}", pops, depth));
+        }
+        self.reach(offset + length, depth - pops + pushes, offset)
+    }
+
+    fn jump(&mut self, op: OpCode, offset: usize, depth: usize, width: usize) -> Result<(), String> {
+        let distance = self.operand(offset, width)?;
+        let next = offset + 1 + width;
+        let backward = matches!(op, OpCode::Loop | OpCode::LoopLong);
+        let target = if backward {
+            next.checked_sub(distance).ok_or("Loop jumps before the start of the code")?
+        } else {
+            next + distance
+        };
+        match op {
+            OpCode::Jump | OpCode::JumpLong | OpCode::Loop | OpCode::LoopLong => self.reach(target, depth, offset),
+            OpCode::JumpIfFalse | OpCode::JumpIfFalseLong | OpCode::JumpIfTrue | OpCode::JumpIfTrueLong => {
+                // The condition stays on the stack either way
+                if depth == 0 {
+                    return Err("Stack underflow: jump needs a condition".to_string());
+                }
+                self.reach(next, depth, offset)?;
+                self.reach(target, depth, offset)
+            }
+            OpCode::ForIter | OpCode::ForIterLong => {
+                // Iterable and index stay; the next element is pushed unless
+                // the loop is done
+                if depth < 2 {
+                    return Err(format!("Stack underflow: needs 2 values, has {}", depth));
+                }
+                self.reach(next, depth + 1, offset)?;
+                self.reach(target, depth, offset)
+            }
+            _ => {
+                // Try: the handler starts with the exception on the stack as
+                // it was when the handler was registered
+                self.reach(next, depth, offset)?;
+                self.reach(target, depth + 1, offset)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source_map::SourceLocation;
+
+    fn chunk(code: &[u8], constants: Vec<Value>) -> Chunk {
+        let mut chunk = Chunk::new();
+        for &byte in code {
+            chunk.write(byte, SourceLocation { line: 1, column: 1 });
+        }
+        chunk.constants = constants;
+        chunk
+    }
+
+    #[test]
+    fn test_verify_accepts_compiled_code() {
+        let source = "def total(items):\n    sum = 0\n    for item in items:\n        if item > 1:\n            sum = sum + item\n    return sum\ntry:\n    x = total([1, 2, 3])\ncatch e:\n    x = 0\nwhile x > 0:\n    x = x - 1";
+        let program = Parser::new(Lexer::new(source.to_string()).tokenize().unwrap()).parse().unwrap();
+        let chunk = Compiler::new().compile(&program).unwrap().clone();
+        assert_eq!(verify(&chunk), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_stack_effects() {
+        let add = OpCode::Add.to_byte();
+        let ret = OpCode::Return.to_byte();
+        let constant = OpCode::Constant.to_byte();
+        let number = || vec![Value::Number(1.0)];
+
+        let underflow = chunk(&[constant, 0, add, ret], number());
+        assert!(verify(&underflow).unwrap_err().contains("Stack underflow"));
+
+        // One branch pushes a value the other doesn't
+        let jump_if_false = OpCode::JumpIfFalse.to_byte();
+        let imbalanced = chunk(&[OpCode::True.to_byte(), jump_if_false, 0, 2, constant, 0, ret], number());
+        assert!(verify(&imbalanced).unwrap_err().contains("disagrees"));
+
+        let runs_off = chunk(&[OpCode::Null.to_byte()], Vec::new());
+        assert!(verify(&runs_off).unwrap_err().contains("past the end"));
+
+        let bad_local = chunk(&[OpCode::GetLocal.to_byte(), 0, ret], Vec::new());
+        assert!(verify(&bad_local).unwrap_err().contains("Local slot 0"));
+
+        let bad_upvalue = chunk(&[OpCode::GetUpvalue.to_byte(), 0, ret], Vec::new());
+        assert!(verify(&bad_upvalue).unwrap_err().contains("Upvalue 0 out of range"));
+    }
+}
@@ -2,16 +2,111 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::bytecode::*;
-use std::collections::HashMap;
+use crate::globals::Globals;
+use crate::source_map::{SourceLocation, TraceFrame};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::io::{BufRead, Write};
 
 pub struct VM {
     pub chunk: Option<Chunk>,
     ip: usize,
     pub stack: Vec<Value>,
-    pub globals: HashMap<String, Value>,
+    pub globals: Globals,
+    /// Globals the runtime and host provide, such as natives, which
+    /// [`VM::reset`] restores.
+    builtins: Globals,
+    /// Modules the runtime and host provide, which [`VM::reset`] keeps.
+    native_modules: HashSet<String>,
+    /// Native modules whose natives are registered the first time a script
+    /// uses them, with the function that registers them.
+    pub(crate) lazy_modules: HashMap<String, fn(&mut VM)>,
+    /// Deprecated flat names of module natives, such as `term_styled`, and
+    /// the module member each stands for (`term.styled`).
+    deprecated_natives: HashMap<String, String>,
+    /// Slots in `globals` of the names the current chunk's constants hold,
+    /// filled in as `GET_GLOBAL`/`SET_GLOBAL` resolve them; `usize::MAX` if
+    /// not resolved yet.
+    global_slots: Vec<usize>,
     frames: Vec<CallFrame>,
+    /// Variables the running closure captured.
+    upvalues: Vec<Upvalue>,
+    /// Captured variables whose scope is still running, so closures that
+    /// capture the same variable share it.
+    open_upvalues: Vec<Upvalue>,
     pub modules: HashMap<String, HashMap<String, Value>>,
-    exception_stack: Vec<usize>,
+    exception_stack: Vec<ExceptionHandler>,
+    /// Value passed to the most recent `throw`, paired with the error message
+    /// it produced, so a handler can recover the original value.
+    thrown: Option<(String, Value)>,
+    /// Exception value most recently delivered to a handler and the message it
+    /// was raised with, so re-raising it keeps the original error message.
+    caught: Option<(String, Value)>,
+    /// Native classes usable in `with` blocks, mapped to the function that
+    /// releases an instance when the block is left.
+    resource_types: HashMap<String, ResourceCloser>,
+    /// Caches behind functions wrapped by `memoize`/`cache`.
+    pub(crate) memo_caches: Vec<crate::performance::MemoCache>,
+    /// Methods resolved through the superclass chain, keyed by class and
+    /// method name. Cleared whenever a global is bound to a class.
+    method_cache: HashMap<(Option<String>, String, String), Option<Value>>,
+    /// Execution statistics reported by `perf_counters()`.
+    pub perf: crate::performance::PerfCounters,
+    /// Command-line arguments passed to the running script (after the script path).
+    pub script_args: Vec<String>,
+    /// Names the compiler replaces with their value, like `platform`.
+    pub defines: HashMap<String, Value>,
+    /// Module whose code is running; global reads and writes resolve in its
+    /// namespace before the shared globals.
+    namespace: Option<String>,
+    /// Extra directories searched for modules named by `use`.
+    pub module_paths: Vec<std::path::PathBuf>,
+    /// Directory of the running script file, if any. Modules and relative
+    /// paths given to file natives are resolved against it.
+    pub script_dir: Option<std::path::PathBuf>,
+    /// Columns a tab indents to when lexing modules, from `grease.toml`.
+    pub tab_width: usize,
+    /// Language edition for modules without a version pragma, from
+    /// `grease.toml`.
+    pub edition: crate::edition::Edition,
+    /// Modules whose top-level code is running, innermost last, to detect
+    /// import cycles.
+    pub(crate) importing: Vec<String>,
+    /// Value left by the script's final expression statement.
+    pub(crate) last_value: Value,
+    /// Warnings emitted during the current run.
+    pub(crate) warnings: Vec<String>,
+    /// Calls active when the last uncaught runtime error was raised,
+    /// innermost first.
+    pub(crate) traceback: Vec<TraceFrame>,
+    /// Names of script globals assigned while recording, for
+    /// `Grease::run_with_bindings`.
+    pub(crate) assigned_globals: Option<std::collections::HashSet<String>>,
+    /// Where `print` and other script output goes; stdout unless replaced
+    /// with [`VM::set_output`].
+    output: Box<dyn Write + Send>,
+    /// Where `input()` reads lines from; stdin unless replaced with
+    /// [`VM::set_input`].
+    input: Box<dyn BufRead + Send>,
+    pub limits: crate::sandbox::Limits,
+    pub capabilities: crate::sandbox::Capabilities,
+    /// Make runs reproducible; see [`crate::sandbox`].
+    pub deterministic: bool,
+    /// Native calls being recorded or replayed; see [`crate::replay`].
+    pub native_log: Option<crate::replay::NativeLog>,
+    /// Stand-ins for natives by name; see [`crate::mock`].
+    mocks: HashMap<String, crate::mock::MockFn>,
+    /// Docs hosts gave their natives; see [`crate::docs`].
+    native_docs: HashMap<String, crate::docs::NativeDoc>,
+    /// Environment variables as scripts see them.
+    pub environment: crate::environment::Environment,
+    /// Files and other resources scripts hold open; closed when the VM is
+    /// dropped.
+    pub resources: crate::resources::Resources,
+    /// Plugin libraries `use plugin` has loaded into this VM.
+    pub plugins: Vec<std::path::PathBuf>,
+    /// Instructions the current run may still execute.
+    instruction_budget: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -20,8 +115,24 @@ struct CallFrame {
     ip: usize,
     slot: usize,
     chunk: Chunk,
+    global_slots: Vec<usize>,
+    /// Module namespace of the caller, restored when the frame is left.
+    namespace: Option<String>,
+    /// Variables the caller captured, restored when the frame is left.
+    upvalues: Vec<Upvalue>,
 }
 
+/// An active `try` block: where to resume and how much state to unwind.
+#[derive(Debug, Clone, Copy)]
+struct ExceptionHandler {
+    catch_ip: usize,
+    frame_depth: usize,
+    stack_height: usize,
+}
+
+/// Releases a native resource object at the end of a `with` block.
+pub type ResourceCloser = fn(&mut VM, Value) -> Result<(), String>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum InterpretResult {
     Ok,
@@ -29,16 +140,90 @@ pub enum InterpretResult {
     RuntimeError(String),
 }
 
+/// Everything a run produced, for hosts and the REPL that need more than
+/// success or failure. Converts into the plain [`InterpretResult`].
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    pub result: InterpretResult,
+    /// Value of the script's final statement when it is an expression,
+    /// otherwise null.
+    pub value: Value,
+    /// Warnings emitted while running, e.g. with `warn()`.
+    pub warnings: Vec<String>,
+    /// For a runtime error, the calls that were active, innermost first.
+    pub traceback: Vec<TraceFrame>,
+    pub lex_time: std::time::Duration,
+    pub parse_time: std::time::Duration,
+    pub compile_time: std::time::Duration,
+    pub run_time: std::time::Duration,
+}
+
+impl RunOutcome {
+    /// Where a runtime error happened, if the code there has a source map.
+    pub fn error_location(&self) -> Option<SourceLocation> {
+        self.traceback.first().and_then(|frame| frame.location)
+    }
+}
+
+impl From<RunOutcome> for InterpretResult {
+    fn from(outcome: RunOutcome) -> Self {
+        outcome.result
+    }
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl VM {
     pub fn new() -> Self {
         let mut vm = VM {
             chunk: None,
             ip: 0,
             stack: Vec::with_capacity(256),
-            globals: HashMap::with_capacity(64),
+            globals: Globals::with_capacity(64),
+            builtins: Globals::new(),
+            native_modules: HashSet::new(),
+            lazy_modules: HashMap::new(),
+            deprecated_natives: HashMap::new(),
+            global_slots: Vec::new(),
             frames: Vec::with_capacity(16),
+            upvalues: Vec::new(),
+            open_upvalues: Vec::new(),
             modules: HashMap::new(),
             exception_stack: Vec::with_capacity(8),
+            thrown: None,
+            caught: None,
+            resource_types: HashMap::new(),
+            memo_caches: Vec::new(),
+            method_cache: HashMap::new(),
+            perf: crate::performance::PerfCounters::default(),
+            script_args: Vec::new(),
+            defines: crate::compiler::default_defines(),
+            output: Box::new(std::io::stdout()),
+            input: Box::new(std::io::BufReader::new(std::io::stdin())),
+            limits: crate::sandbox::Limits::default(),
+            capabilities: crate::sandbox::Capabilities::default(),
+            deterministic: false,
+            native_log: None,
+            mocks: HashMap::new(),
+            native_docs: HashMap::new(),
+            environment: crate::environment::Environment::default(),
+            resources: crate::resources::Resources::default(),
+            plugins: Vec::new(),
+            instruction_budget: u64::MAX,
+            namespace: None,
+            module_paths: Vec::new(),
+            tab_width: crate::lexer::DEFAULT_TAB_WIDTH,
+            edition: crate::edition::Edition::default(),
+            script_dir: None,
+            last_value: Value::Null,
+            warnings: Vec::new(),
+            traceback: Vec::new(),
+            assigned_globals: None,
+            importing: Vec::new(),
         };
 
         // Add built-in functions
@@ -48,35 +233,400 @@ impl VM {
         vm.register_native("native_add", 2, |_vm, args| {
             match (&args[0], &args[1]) {
                 (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
-                _ => Err("Arguments must be numbers".to_string()),
+                _ => Err("TypeError: Arguments must be numbers".to_string()),
                 }
         });
 
+        crate::config::register_natives(&mut vm);
+        crate::shell::register_natives(&mut vm);
+        crate::argparse::register_natives(&mut vm);
+        crate::serialize::register_natives(&mut vm);
+        crate::builtins::register_natives(&mut vm);
+        crate::retry::register_natives(&mut vm);
+        crate::files::register_natives(&mut vm);
+        crate::exceptions::register_natives(&mut vm);
+        crate::performance::register_natives(&mut vm);
+        crate::modules::register_natives(&mut vm);
+        crate::template::register_natives(&mut vm);
+        crate::bytes::register_natives(&mut vm);
+        crate::numbers::register_natives(&mut vm);
+        crate::environment::register_natives(&mut vm);
+        crate::resources::register_natives(&mut vm);
+        crate::docs::register_natives(&mut vm);
+
+        // Most scripts use none of these, so they cost nothing until one does
+        vm.register_lazy_module("system", |vm| {
+            crate::environment::register_system_natives(vm);
+            #[cfg(feature = "system")]
+            crate::system::register_natives(vm);
+        });
+        #[cfg(feature = "term")]
+        vm.register_lazy_module("term", crate::term::register_natives);
+        vm.register_lazy_module("py", crate::py::register_natives);
+
+        vm.builtins = vm.globals.clone();
         vm
     }
 
-    pub fn register_native(&mut self, name: &str, arity: usize, function: fn(&mut VM, Vec<Value>) -> Result<Value, String>) {
+    pub fn register_native(&mut self, name: &str, arity: impl Into<crate::bytecode::Arity>, function: crate::bytecode::NativeFn) {
         let native_func = Value::NativeFunction(NativeFunction {
             name: name.to_string(),
-            arity,
+            arity: arity.into(),
             function,
         });
+        self.builtins.insert(name.to_string(), native_func.clone());
         self.globals.insert(name.to_string(), native_func);
     }
 
+    /// Register native `name` as a member of native module `module`, so
+    /// scripts call it as `module.name` after `use module`. It is also a
+    /// global under the flat name `module_name`, which warns that it is
+    /// deprecated the first time a run calls it.
+    pub fn register_module_native(&mut self, module: &str, name: &str, arity: impl Into<crate::bytecode::Arity>, function: crate::bytecode::NativeFn) {
+        let arity = arity.into();
+        let qualified = format!("{}.{}", module, name);
+        let member = Value::NativeFunction(NativeFunction { name: qualified.clone(), arity, function });
+        self.native_modules.insert(module.to_string());
+        self.modules.entry(module.to_string()).or_default().insert(name.to_string(), member);
+        let flat = format!("{}_{}", module, name);
+        self.register_native(&flat, arity, function);
+        self.deprecated_natives.insert(flat, qualified);
+    }
+
+    /// Provide native module `name`, calling `register` to register its
+    /// natives the first time a script uses the module or calls one of its
+    /// deprecated flat names, rather than now.
+    pub fn register_lazy_module(&mut self, name: &str, register: fn(&mut VM)) {
+        self.native_modules.insert(name.to_string());
+        self.lazy_modules.insert(name.to_string(), register);
+    }
+
+    /// Register the natives of lazy module `name` if that hasn't happened
+    /// yet; returns whether it did.
+    pub fn load_lazy_module(&mut self, name: &str) -> bool {
+        match self.lazy_modules.remove(name) {
+            Some(register) => {
+                self.modules.entry(name.to_string()).or_default();
+                register(self);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Register every lazy module's natives, e.g. to list them all.
+    pub fn load_lazy_modules(&mut self) {
+        let names: Vec<String> = self.lazy_modules.keys().cloned().collect();
+        for name in names {
+            self.load_lazy_module(&name);
+        }
+    }
+
+    /// Load the lazy module native `name` belongs to, given as
+    /// `module.member` or by its flat name `module_member`.
+    pub(crate) fn load_lazy_module_of(&mut self, name: &str) -> bool {
+        let module = name.split_once('.').or_else(|| name.split_once('_')).map(|(module, _)| module);
+        module.is_some_and(|module| self.load_lazy_module(module))
+    }
+
+    /// Whether `name` is a module the runtime or host provides.
+    pub fn is_native_module(&self, name: &str) -> bool {
+        self.native_modules.contains(name)
+    }
+
+    /// The module member a deprecated flat native name stands for, e.g.
+    /// `term.styled` for `term_styled`.
+    pub fn deprecated_native(&self, name: &str) -> Option<&str> {
+        self.deprecated_natives.get(name).map(String::as_str)
+    }
+
+    /// Document native `name` for `help`, replacing any docs it had.
+    pub fn document_native(&mut self, name: &str, doc: crate::docs::NativeDoc) {
+        self.native_docs.insert(name.to_string(), doc);
+    }
+
+    /// The docs of native `name`: what the host gave it, or the built-in
+    /// native's.
+    pub fn native_doc(&self, name: &str) -> Option<crate::docs::NativeDoc> {
+        let name = self.deprecated_native(name).unwrap_or(name);
+        self.native_docs.get(name).cloned().or_else(|| crate::docs::builtin(name))
+    }
+
+    /// Make a module of natives available to `use`; [`VM::reset`] keeps it.
+    pub fn register_native_module(&mut self, name: &str, members: HashMap<String, Value>) {
+        self.lazy_modules.remove(name);
+        self.native_modules.insert(name.to_string());
+        self.modules.insert(name.to_string(), members);
+    }
+
+    /// Have the native `name` call `function` with its arguments instead,
+    /// wherever scripts call it from. Fails if no native by that name is
+    /// registered, globally or in a native module. Mocking a module native
+    /// or its deprecated flat name replaces both.
+    pub fn override_native(&mut self, name: &str, function: impl FnMut(&[Value]) -> Result<Value, String> + Send + 'static) -> Result<(), String> {
+        self.load_lazy_modules();
+        let is_native = |value: &Value| matches!(value, Value::NativeFunction(native) if native.name == name);
+        let registered = self.builtins.get(name).is_some_and(is_native)
+            || self.native_modules.iter().any(|module| self.modules.get(module).is_some_and(|members| members.values().any(is_native)));
+        if !registered {
+            return Err(format!("Can't mock '{}': there is no native by that name", name));
+        }
+        let name = self.deprecated_native(name).unwrap_or(name).to_string();
+        self.mocks.insert(name, Box::new(function));
+        Ok(())
+    }
+
+    /// Forget what scripts did: their globals, loaded modules, open
+    /// resources, caches and environment changes. Natives, native modules
+    /// and configuration such as limits, capabilities and module paths are
+    /// kept, so a host can reuse one warmed-up VM for many runs. Returns the
+    /// errors closing resources raised, if any.
+    pub fn reset(&mut self) -> Result<(), String> {
+        self.globals = self.builtins.clone();
+        self.global_slots.clear();
+        self.modules.retain(|name, _| self.native_modules.contains(name));
+        self.chunk = None;
+        self.ip = 0;
+        self.stack.clear();
+        self.frames.clear();
+        self.upvalues.clear();
+        self.open_upvalues.clear();
+        self.exception_stack.clear();
+        self.thrown = None;
+        self.caught = None;
+        self.memo_caches.clear();
+        self.method_cache.clear();
+        self.perf.reset();
+        self.namespace = None;
+        self.importing.clear();
+        self.last_value = Value::Null;
+        self.warnings.clear();
+        self.traceback.clear();
+        self.assigned_globals = None;
+        self.environment.clear();
+        let errors = self.resources.close_all();
+        if errors.is_empty() { Ok(()) } else { Err(errors.join("; ")) }
+    }
+
+    /// Resolve a path given by a script: relative paths are taken from the
+    /// directory the script moved to with `chdir`, else the script's
+    /// directory when running a file, else the working directory.
+    pub fn resolve_path(&self, path: &str) -> std::path::PathBuf {
+        match self.environment.current_dir().or(self.script_dir.as_deref()) {
+            Some(dir) if std::path::Path::new(path).is_relative() => dir.join(path),
+            _ => std::path::PathBuf::from(path),
+        }
+    }
+
+    /// Record a warning for the host; it is reported with the run's outcome.
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+
+    /// Redirect script output, e.g. to capture what a script prints.
+    pub fn set_output(&mut self, output: Box<dyn Write + Send>) {
+        self.output = output;
+    }
+
+    /// Supply the lines `input()` reads, e.g. to script an interactive program.
+    pub fn set_input(&mut self, input: Box<dyn BufRead + Send>) {
+        self.input = input;
+    }
+
+    /// Read one line of input without its line ending; `None` at end of input.
+    pub fn read_input_line(&mut self) -> Result<Option<String>, String> {
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                let trimmed = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(trimmed);
+                Ok(Some(line))
+            }
+            Err(e) => Err(format!("IOError: Failed to read input: {}", e)),
+        }
+    }
+
+    /// Write text to the output sink without ending the line, e.g. a prompt.
+    pub fn write_output_partial(&mut self, text: &str) -> Result<(), String> {
+        write!(self.output, "{}", text)
+            .and_then(|_| self.output.flush())
+            .map_err(|e| format!("IOError: Failed to write output: {}", e))
+    }
+
+    /// Write one line of script output to the output sink.
+    pub fn write_output(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.output, "{}", line).map_err(|e| format!("IOError: Failed to write output: {}", e))
+    }
+
+    /// Let objects of native class `class_name`, made with
+    /// [`Value::native_object`], be used directly in `with` blocks: the
+    /// object itself is bound by `as`, and `close` runs when the block is
+    /// left, whether normally or by an exception. Script objects of a class
+    /// with the same name are left alone.
+    pub fn register_resource_type(&mut self, class_name: &str, close: ResourceCloser) {
+        self.resource_types.insert(class_name.to_string(), close);
+    }
+
+    /// Run `chunk` as a script. The chunk is verified first, so bytecode
+    /// that was built by hand, loaded or corrupted fails with a runtime error
+    /// instead of panicking partway through.
     pub fn interpret(&mut self, chunk: Chunk) -> InterpretResult {
+        if let Err(e) = crate::verifier::verify(&chunk) {
+            return InterpretResult::RuntimeError(e);
+        }
+        self.interpret_verified(chunk)
+    }
+
+    /// [`VM::interpret`] for a chunk the compiler just made or that was
+    /// already verified, which release builds don't check again.
+    pub(crate) fn interpret_verified(&mut self, chunk: Chunk) -> InterpretResult {
+        self.namespace = None;
+        self.start(chunk)
+    }
+
+    fn start(&mut self, chunk: Chunk) -> InterpretResult {
         self.chunk = Some(chunk);
+        self.global_slots.clear();
         self.ip = 0;
         self.stack.clear();
         self.frames.clear();
+        self.upvalues.clear();
+        self.open_upvalues.clear();
         self.exception_stack.clear();
+        self.importing.clear();
+        self.last_value = Value::Null;
+        self.warnings.clear();
+        self.traceback.clear();
+        self.instruction_budget = self.limits.max_instructions.unwrap_or(u64::MAX);
+        self.thrown = None;
+        self.caught = None;
         
-        self.run()
+        let started = self.perf.start_run();
+        let result = self.run(None);
+        self.perf.finish_run(started);
+        result
+    }
+
+    /// Call a Grease callable (function or native) from Rust, e.g. a callback
+    /// handed to a native function, and return its result.
+    pub fn call_function(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, String> {
+        let base_depth = self.frames.len();
+        let stack_base = self.stack.len();
+        let arg_count = args.len();
+
+        self.stack.push(callee);
+        self.stack.extend(args);
+        if let Err(e) = self.call_value(arg_count) {
+            self.stack.truncate(stack_base);
+            return Err(e);
+        }
+
+        // A Grease function pushed a frame; run it until it returns to us
+        if self.frames.len() > base_depth {
+            if let InterpretResult::RuntimeError(e) = self.run(Some(base_depth)) {
+                // Unwind back to the caller so the VM is left in a consistent state
+                if self.frames.len() > base_depth {
+                    let frame = self.frames[base_depth].clone();
+                    self.frames.truncate(base_depth);
+                    self.chunk = Some(frame.chunk);
+                    self.global_slots = frame.global_slots;
+                    self.ip = frame.ip;
+                    self.namespace = frame.namespace;
+                    self.upvalues = frame.upvalues;
+                }
+                self.exception_stack.retain(|handler| handler.frame_depth <= base_depth);
+                self.close_upvalues(stack_base);
+                self.stack.truncate(stack_base);
+                return Err(e);
+            }
+        }
+
+        let result = self.stack.pop().unwrap_or(Value::Null);
+        self.stack.truncate(stack_base);
+        Ok(result)
+    }
+
+    /// Execute bytecode until the script finishes or, when `return_depth` is
+    /// set, until a function returns back to that call-frame depth. Runtime
+    /// errors are routed to the innermost enclosing `try` handler, if any.
+    fn run(&mut self, return_depth: Option<usize>) -> InterpretResult {
+        loop {
+            match self.execute(return_depth) {
+                InterpretResult::RuntimeError(message) => {
+                    if !self.handle_error(&message, return_depth) {
+                        // Errors from callbacks pass through their native
+                        // caller; keep the innermost traceback
+                        if self.traceback.is_empty() {
+                            self.traceback = self.capture_traceback();
+                        }
+                        return InterpretResult::RuntimeError(message);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The chunk of the script the last run started with, which stays put
+    /// while the functions it called are on the frames.
+    pub(crate) fn script_chunk(&self) -> Option<&Chunk> {
+        self.frames.first().map(|frame| &frame.chunk).or(self.chunk.as_ref())
+    }
+
+    /// Whether the runtime or host provides global `name`, e.g. a native.
+    pub(crate) fn is_builtin(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    pub(crate) fn capture_traceback(&self) -> Vec<TraceFrame> {
+        // Each saved frame resumes just past its call instruction
+        let current = self.chunk.as_ref().map(|chunk| TraceFrame::at(&chunk.source_map, self.ip.saturating_sub(1)));
+        let callers = self.frames.iter().rev().map(|frame| TraceFrame::at(&frame.chunk.source_map, frame.ip.saturating_sub(1)));
+        current.into_iter().chain(callers).collect()
     }
 
-    fn run(&mut self) -> InterpretResult {
+    /// Unwind to the innermost handler that belongs to this `run` invocation,
+    /// push the exception value and resume at its catch block.
+    fn handle_error(&mut self, message: &str, return_depth: Option<usize>) -> bool {
+        // Handlers at or below `return_depth` belong to an outer `run`; the
+        // error must propagate out through the native caller first
+        let handler = match self.exception_stack.last() {
+            Some(handler) if return_depth.is_none_or(|depth| handler.frame_depth > depth) => *handler,
+            _ => return false,
+        };
+        self.exception_stack.pop();
+
+        let exception = match self.thrown.take() {
+            Some((thrown_message, value)) if thrown_message == message => value,
+            _ => crate::exceptions::from_runtime_error(message),
+        };
+
+        while self.frames.len() > handler.frame_depth {
+            let frame = self.frames.pop().expect("frame depth checked above");
+            self.chunk = Some(frame.chunk);
+            self.global_slots = frame.global_slots;
+            self.namespace = frame.namespace;
+            self.upvalues = frame.upvalues;
+        }
+        self.close_upvalues(handler.stack_height);
+        self.stack.truncate(handler.stack_height);
+        self.traceback.clear();
+        self.caught = Some((message.to_string(), exception.clone()));
+        self.stack.push(exception);
+        self.ip = handler.catch_ip;
+        true
+    }
+
+    fn execute(&mut self, return_depth: Option<usize>) -> InterpretResult {
     loop {
+        if self.instruction_budget == 0 {
+            let limit = self.limits.max_instructions.unwrap_or_default();
+            return InterpretResult::RuntimeError(format!("Instruction limit of {} exceeded", limit));
+        }
+        self.instruction_budget -= 1;
         let instruction = self.read_byte().expect("Unexpected end of bytecode");
+        self.perf.instructions += 1;
         match OpCode::from_byte(instruction) {
             Some(OpCode::Constant) => {
                 let constant_index = self.read_byte().expect("Expected constant index") as usize;
@@ -89,6 +639,10 @@ impl VM {
                     (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
+                // Anything but numeric addition builds a new string or bytes
+                if !matches!((&a, &b), (Value::Number(_), Value::Number(_))) {
+                    self.perf.allocations += 1;
+                }
                 
                 match (a, b) {
                     (Value::Number(a_num), Value::Number(b_num)) => {
@@ -98,6 +652,10 @@ impl VM {
                         a_str.push_str(&b_str);
                         self.stack.push(Value::String(a_str));
                     }
+                    (Value::Bytes(mut a_bytes), Value::Bytes(b_bytes)) => {
+                        a_bytes.extend(b_bytes);
+                        self.stack.push(Value::Bytes(a_bytes));
+                    }
                     (Value::String(mut a_str), Value::Number(b_num)) => {
                         a_str.push_str(&b_num.to_string());
                         self.stack.push(Value::String(a_str));
@@ -117,34 +675,34 @@ impl VM {
                         self.stack.push(Value::String(result));
                     }
                     _ => {
-                        return InterpretResult::RuntimeError("Operands must be numbers or strings".to_string());
+                        return InterpretResult::RuntimeError("TypeError: Operands must be numbers or strings".to_string());
                     }
                 }
             }
             Some(OpCode::Multiply) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(Value::Number(b)), Some(Value::Number(a))) => (a, b),
-                    _ => return InterpretResult::RuntimeError("Operands must be numbers".to_string()),
+                    (Some(Value::Number(b)), Some(Value::Number(a))) => (b, a),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operands must be numbers".to_string()),
                 };
                 self.stack.push(Value::Number(a * b));
             }
             Some(OpCode::Divide) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(Value::Number(b)), Some(Value::Number(a))) => (a, b),
-                    _ => return InterpretResult::RuntimeError("Operands must be numbers".to_string()),
+                    (Some(Value::Number(b)), Some(Value::Number(a))) => (b, a),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operands must be numbers".to_string()),
                 };
                 if b == 0.0 {
-                    return InterpretResult::RuntimeError("Division by zero".to_string());
+                    return InterpretResult::RuntimeError("ValueError: Division by zero".to_string());
                 }
                 self.stack.push(Value::Number(a / b));
             }
             Some(OpCode::Modulo) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(Value::Number(b)), Some(Value::Number(a))) => (a, b),
-                    _ => return InterpretResult::RuntimeError("Operands must be numbers".to_string()),
+                    (Some(Value::Number(b)), Some(Value::Number(a))) => (b, a),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operands must be numbers".to_string()),
                 };
                 if b == 0.0 {
-                    return InterpretResult::RuntimeError("Modulo by zero".to_string());
+                    return InterpretResult::RuntimeError("ValueError: Modulo by zero".to_string());
                 }
                 self.stack.push(Value::Number(a % b));
             }
@@ -158,27 +716,83 @@ impl VM {
                     self.stack.push(Value::Boolean(false));
                 }
             Some(OpCode::GetGlobal) => {
-                let name = match self.read_string() {
-                    Value::String(s) => s,
+                let index = self.read_byte().expect("Expected constant index") as usize;
+                let chunk = self.chunk.as_ref().expect("No chunk loaded");
+                let name = match &chunk.constants[index] {
+                    Value::String(s) => s.as_str(),
                     _ => return InterpretResult::RuntimeError("Global name must be a string".to_string()),
                 };
-                
-                match self.globals.get(&name) {
+
+                self.perf.global_lookups += 1;
+                let value = match &self.namespace {
+                    // Module code checks its own namespace first
+                    Some(namespace) => self.modules.get(namespace)
+                        .and_then(|members| members.get(name))
+                        .or_else(|| self.globals.get(name)),
+                    None => {
+                        let cached = self.global_slots.get(index).copied()
+                            .filter(|&slot| self.globals.slot_is(slot, name));
+                        let slot = cached.or_else(|| {
+                            let slot = self.globals.slot_of(name)?;
+                            if self.global_slots.len() <= index {
+                                self.global_slots.resize(chunk.constants.len(), usize::MAX);
+                            }
+                            self.global_slots[index] = slot;
+                            Some(slot)
+                        });
+                        slot.and_then(|slot| self.globals.get_slot(slot))
+                    }
+                };
+                match value {
                     Some(value) => self.stack.push(value.clone()),
-                    None => return InterpretResult::RuntimeError(format!("Undefined variable '{}'", name)),
+                    None => {
+                        // A deprecated flat name registers its module's natives
+                        let name = name.to_string();
+                        let value = match self.load_lazy_module_of(&name) {
+                            true => self.globals.get(&name).cloned(),
+                            false => None,
+                        };
+                        match value {
+                            Some(value) => self.stack.push(value),
+                            None => return InterpretResult::RuntimeError(format!("Undefined variable '{}'", name)),
+                        }
+                    }
                 }
             }
             Some(OpCode::SetGlobal) => {
-                let name = match self.read_string() {
-                    Value::String(s) => s,
+                let index = self.read_byte().expect("Expected constant index") as usize;
+                let chunk = self.chunk.as_ref().expect("No chunk loaded");
+                let name = match &chunk.constants[index] {
+                    Value::String(s) => s.as_str(),
                     _ => return InterpretResult::RuntimeError("Global name must be a string".to_string()),
                 };
-                
+
                 let value = match self.stack.pop() {
                     Some(v) => v,
                     None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
-                self.globals.insert(name, value);
+                if matches!(value, Value::Class { .. }) {
+                    self.method_cache.clear();
+                }
+                if let Some(members) = self.namespace.as_ref().and_then(|namespace| self.modules.get_mut(namespace)) {
+                    members.insert(name.to_string(), value);
+                } else {
+                    if let Some(assigned) = &mut self.assigned_globals {
+                        assigned.insert(name.to_string());
+                    }
+                    let slot = match self.global_slots.get(index) {
+                        Some(&slot) if self.globals.slot_is(slot, name) => slot,
+                        _ => {
+                            let slot = self.globals.slot(name);
+                            if self.global_slots.len() <= index {
+                                self.global_slots.resize(chunk.constants.len(), usize::MAX);
+                            }
+                            self.global_slots[index] = slot;
+                            slot
+                        }
+                    };
+                    self.globals.set_slot(slot, value);
+                }
             }
             Some(OpCode::GetLocal) => {
                 let slot = self.read_byte().expect("Expected slot") as usize;
@@ -210,32 +824,96 @@ impl VM {
                     return InterpretResult::RuntimeError("SetLocal outside of function".to_string());
                 }
             }
-            Some(OpCode::Jump) => {
-                let offset = self.read_short() as usize;
+            Some(OpCode::Closure) => {
+                if let Err(e) = self.create_closure() {
+                    return InterpretResult::RuntimeError(e);
+                }
+            }
+            Some(OpCode::GetUpvalue) => {
+                let index = self.read_byte().expect("Expected upvalue index") as usize;
+                match self.get_upvalue(index) {
+                    Ok(value) => self.stack.push(value),
+                    Err(e) => return InterpretResult::RuntimeError(e),
+                }
+            }
+            Some(OpCode::SetUpvalue) => {
+                let index = self.read_byte().expect("Expected upvalue index") as usize;
+                let value = match self.stack.pop() {
+                    Some(v) => v,
+                    None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
+                };
+                if let Err(e) = self.set_upvalue(index, value) {
+                    return InterpretResult::RuntimeError(e);
+                }
+            }
+            Some(OpCode::CloseUpvalue) => {
+                // The captured local on top of the stack is going out of scope
+                let Some(top) = self.stack.len().checked_sub(1) else {
+                    return InterpretResult::RuntimeError("Stack underflow".to_string());
+                };
+                self.close_upvalues(top);
+                self.stack.pop();
+            }
+            Some(op @ (OpCode::Jump | OpCode::JumpLong)) => {
+                let offset = self.read_jump(&op);
                 self.ip += offset;
             }
-            Some(OpCode::JumpIfFalse) => {
-                let offset = self.read_short() as usize;
+            Some(op @ (OpCode::JumpIfFalse | OpCode::JumpIfFalseLong)) => {
+                let offset = self.read_jump(&op);
                 if let Some(value) = self.stack.last() {
-                    if !self.is_truthy(value) {
+                    if !Self::is_truthy(value) {
                         self.ip += offset;
                     }
                 } else {
                     return InterpretResult::RuntimeError("Stack underflow".to_string());
                 }
             }
-            Some(OpCode::JumpIfTrue) => {
-                let offset = self.read_short() as usize;
+            Some(op @ (OpCode::JumpIfTrue | OpCode::JumpIfTrueLong)) => {
+                let offset = self.read_jump(&op);
                 if let Some(value) = self.stack.last() {
-                    if self.is_truthy(value) {
+                    if Self::is_truthy(value) {
                         self.ip += offset;
                     }
                 } else {
                     return InterpretResult::RuntimeError("Stack underflow".to_string());
                 }
             }
-            Some(OpCode::Loop) => {
-                let offset = self.read_short() as usize;
+            Some(OpCode::GetIter) => {
+                let iterable = match self.stack.pop() {
+                    Some(value) => value,
+                    None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
+                };
+                match self.iterator(iterable) {
+                    Ok(iterator) => self.stack.push(iterator),
+                    Err(e) => return InterpretResult::RuntimeError(e),
+                }
+            }
+            Some(op @ (OpCode::ForIter | OpCode::ForIterLong)) => {
+                // Stack: [..., iterable, index]; pushes the next element or
+                // jumps past the loop when the iterable is exhausted
+                let offset = self.read_jump(&op);
+                let len = self.stack.len();
+                if len < 2 {
+                    return InterpretResult::RuntimeError("Stack underflow".to_string());
+                }
+                let index = match self.stack[len - 1] {
+                    Value::Number(n) => n as usize,
+                    _ => return InterpretResult::RuntimeError("Invalid loop index".to_string()),
+                };
+                let element = match Self::element(&self.stack[len - 2], index) {
+                    Ok(element) => element,
+                    Err(e) => return InterpretResult::RuntimeError(e),
+                };
+                match element {
+                    Some(element) => {
+                        self.stack[len - 1] = Value::Number((index + 1) as f64);
+                        self.stack.push(element);
+                    }
+                    None => self.ip += offset,
+                }
+            }
+            Some(op @ (OpCode::Loop | OpCode::LoopLong)) => {
+                let offset = self.read_jump(&op);
                 self.ip = self.ip.checked_sub(offset).expect("Loop underflow");
             }
             Some(OpCode::Dup) => {
@@ -247,8 +925,8 @@ impl VM {
             }
             Some(OpCode::Call) => {
                 let arg_count = self.read_byte().expect("Expected argument count") as usize;
-                if !self.call_value(arg_count) {
-                    return InterpretResult::RuntimeError("Failed to call value".to_string());
+                if let Err(e) = self.call_value(arg_count) {
+                    return InterpretResult::RuntimeError(e);
                 }
             }
             Some(OpCode::Return) => {
@@ -256,27 +934,38 @@ impl VM {
                 
                 // If we have call frames, restore the previous one
                 if let Some(frame) = self.frames.pop() {
+                    // Drop handlers of try blocks the function returned out of
+                    let depth = self.frames.len();
+                    self.exception_stack.retain(|handler| handler.frame_depth <= depth);
+                    self.close_upvalues(frame.slot);
                     self.stack.truncate(frame.slot);
                     self.ip = frame.ip;
                     self.chunk = Some(frame.chunk); // Restore the previous chunk
+                    self.global_slots = frame.global_slots;
+                    self.namespace = frame.namespace;
+                    self.upvalues = frame.upvalues;
                     // Push the result back (may be None, in which case push Null)
                     self.stack.push(result.unwrap_or(Value::Null));
+                    if return_depth == Some(self.frames.len()) {
+                        return InterpretResult::Ok;
+                    }
                 } else {
-                    // No frames left, execution is done
-                    // If there was a result, we could return it, but for now just return Ok
+                    // No frames left: the script is done, and a trailing
+                    // expression statement left its value for the host
+                    self.last_value = result.unwrap_or(Value::Null);
                     return InterpretResult::Ok;
                 }
             }
             Some(OpCode::Subtract) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
                 match (a, b) {
                     (Value::Number(a), Value::Number(b)) => {
                         self.stack.push(Value::Number(a - b));
                     }
-                    _ => return InterpretResult::RuntimeError("Operands must be numbers".to_string()),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operands must be numbers".to_string()),
                 }
             }
             Some(OpCode::Negate) => {
@@ -286,7 +975,7 @@ impl VM {
                 };
                 match value {
                     Value::Number(n) => self.stack.push(Value::Number(-n)),
-                    _ => return InterpretResult::RuntimeError("Operand must be a number".to_string()),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operand must be a number".to_string()),
                 }
             }
             Some(OpCode::Array) => {
@@ -296,7 +985,31 @@ impl VM {
                     None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
                 let elements = self.stack.drain(start_idx..).collect();
-                self.stack.push(Value::Array(elements));
+                self.perf.allocations += 1;
+                self.stack.push(Value::array(elements));
+            }
+            Some(OpCode::Tuple) => {
+                let count = self.read_byte().expect("Expected tuple count") as usize;
+                let start_idx = match self.stack.len().checked_sub(count) {
+                    Some(idx) => idx,
+                    None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
+                };
+                let elements = self.stack.drain(start_idx..).collect();
+                self.perf.allocations += 1;
+                self.stack.push(Value::Tuple(elements));
+            }
+            Some(OpCode::Unpack) => {
+                let count = self.read_byte().expect("Expected unpack count") as usize;
+                let elements = match self.stack.pop() {
+                    Some(Value::Tuple(elements)) => elements,
+                    Some(Value::Array(elements)) => elements.lock().unwrap().clone(),
+                    Some(other) => return InterpretResult::RuntimeError(format!("TypeError: Cannot unpack {}", Self::format_value(&other))),
+                    None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
+                };
+                if elements.len() != count {
+                    return InterpretResult::RuntimeError(format!("ValueError: Expected {} values to unpack, got {}", count, elements.len()));
+                }
+                self.stack.extend(elements);
             }
             Some(OpCode::Dictionary) => {
                 let count = self.read_byte().expect("Expected dictionary count") as usize;
@@ -314,26 +1027,30 @@ impl VM {
                         Value::String(key_str) => {
                             dict.insert(key_str.clone(), chunk[1].clone());
                         }
-                        _ => return InterpretResult::RuntimeError("Dictionary keys must be strings".to_string()),
+                        _ => return InterpretResult::RuntimeError("TypeError: Dictionary keys must be strings".to_string()),
                     }
                 }
-                self.stack.push(Value::Dictionary(dict));
+                self.perf.allocations += 1;
+                self.stack.push(Value::dictionary(dict));
             }
             Some(OpCode::Index) => {
-                let (array, index) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(array), Some(index)) => (array, index),
+                let (index, array) = match (self.stack.pop(), self.stack.pop()) {
+                    (Some(index), Some(array)) => (index, array),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
-                match (array, index) {
-                    (Value::Array(elements), Value::Number(i))=> {
-                        let idx = i as usize;
-                        if idx < elements.len() {
-                            self.stack.push(elements[idx].clone());
-                        } else {
-                            return InterpretResult::RuntimeError(format!("Index {} out of bounds for array of length {}", idx, elements.len()));
-                        }
-                    }
-                    _ => return InterpretResult::RuntimeError("Index operation requires array and number".to_string()),
+                match Self::index(&array, &index) {
+                    Ok(value) => self.stack.push(value),
+                    Err(e) => return InterpretResult::RuntimeError(e),
+                }
+            }
+            Some(OpCode::Slice) => {
+                let (end, start, array) = match (self.stack.pop(), self.stack.pop(), self.stack.pop()) {
+                    (Some(end), Some(start), Some(array)) => (end, start, array),
+                    _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
+                };
+                match Self::slice(&array, &start, &end) {
+                    Ok(value) => self.stack.push(value),
+                    Err(e) => return InterpretResult::RuntimeError(e),
                 }
             }
             Some(OpCode::Length) => {
@@ -342,72 +1059,71 @@ impl VM {
                     None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
                 match value {
-                    Value::Array(elements)=> {
-                        self.stack.push(Value::Number(elements.len() as f64));
-                    }
-                    _ => return InterpretResult::RuntimeError("Length operation requires array".to_string()),
+                    Value::Array(elements) => self.stack.push(Value::Number(elements.lock().unwrap().len() as f64)),
+                    Value::Tuple(elements) => self.stack.push(Value::Number(elements.len() as f64)),
+                    _ => return InterpretResult::RuntimeError("TypeError: Length operation requires array".to_string()),
                 }
             }
             Some(OpCode::Equal) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
-                self.stack.push(Value::Boolean(self.values_equal(&a, &b)));
+                self.stack.push(Value::Boolean(Self::values_equal(&a, &b)));
             }
             Some(OpCode::NotEqual) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
-                self.stack.push(Value::Boolean(!self.values_equal(&a, &b)));
+                self.stack.push(Value::Boolean(!Self::values_equal(&a, &b)));
             }
             Some(OpCode::Less) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
                 match (a, b) {
                     (Value::Number(a), Value::Number(b))=> {
                         self.stack.push(Value::Boolean(a < b));
                     }
-                    _ => return InterpretResult::RuntimeError("Operands must be numbers".to_string()),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operands must be numbers".to_string()),
                 }
             }
             Some(OpCode::LessEqual) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
                 match (a, b) {
                     (Value::Number(a), Value::Number(b))=> {
                         self.stack.push(Value::Boolean(a <= b));
                     }
-                    _ => return InterpretResult::RuntimeError("Operands must be numbers".to_string()),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operands must be numbers".to_string()),
                 }
             }
             Some(OpCode::Greater) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
                 match (a, b) {
                     (Value::Number(a), Value::Number(b))=> {
                         self.stack.push(Value::Boolean(a > b));
                     }
-                    _ => return InterpretResult::RuntimeError("Operands must be numbers".to_string()),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operands must be numbers".to_string()),
                 }
             }
             Some(OpCode::GreaterEqual) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
                 match (a, b) {
                     (Value::Number(a), Value::Number(b))=> {
                         self.stack.push(Value::Boolean(a >= b));
                     }
-                    _ => return InterpretResult::RuntimeError("Operands must be numbers".to_string()),
+                    _ => return InterpretResult::RuntimeError("TypeError: Operands must be numbers".to_string()),
                 }
             }
             Some(OpCode::Not) => {
@@ -415,32 +1131,43 @@ impl VM {
                     Some(v) => v,
                     None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
-                self.stack.push(Value::Boolean(!self.is_truthy(&value)));
+                self.stack.push(Value::Boolean(!Self::is_truthy(&value)));
             }
             Some(OpCode::And) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
-                self.stack.push(Value::Boolean(self.is_truthy(&a) && self.is_truthy(&b)));
+                self.stack.push(Value::Boolean(Self::is_truthy(&a) && Self::is_truthy(&b)));
             }
             Some(OpCode::Or) => {
                 let (b, a) = match (self.stack.pop(), self.stack.pop()) {
-                    (Some(b), Some(a)) => (a, b),
+                    (Some(b), Some(a)) => (b, a),
                     _ => return InterpretResult::RuntimeError("Stack underflow".to_string()),
                 };
-                self.stack.push(Value::Boolean(self.is_truthy(&a) || self.is_truthy(&b)));
+                self.stack.push(Value::Boolean(Self::is_truthy(&a) || Self::is_truthy(&b)));
             }
             Some(OpCode::Pop) => {
                     self.stack.pop();
                 }
             Some(OpCode::Import) => {
-                    // For now, this is a placeholder
-                    // The module name and alias are on the stack
-                    // In a full implementation, this would load and execute the module
-                    let _alias = self.stack.pop();
-                    let _module = self.stack.pop();
-                    // TODO: Implement actual module loading
+                    let name = match self.read_string() {
+                        Value::String(s) => s,
+                        _ => return InterpretResult::RuntimeError("Module name must be a string".to_string()),
+                    };
+                    if let Err(e) = crate::modules::import(self, &name) {
+                        return InterpretResult::RuntimeError(e);
+                    }
+                    self.stack.push(Value::Module(name));
+                }
+            Some(OpCode::LoadPlugin) => {
+                    let spec = match self.read_string() {
+                        Value::String(s) => s,
+                        _ => return InterpretResult::RuntimeError("Plugin path must be a string".to_string()),
+                    };
+                    if let Err(e) = crate::plugin::load(self, &spec) {
+                        return InterpretResult::RuntimeError(e);
+                    }
                 }
             Some(OpCode::GetModule) => {
                     // Stack has: [..., module_name, member_name]
@@ -482,15 +1209,20 @@ impl VM {
 
                 // Get the class
                 if let Some(class_value) = self.stack.pop() {
-                    if let Value::Class { name, .. } = class_value {
+                    if let Value::Class { name, module, .. } = class_value {
                         // Create instance with empty fields
-                        let instance = Value::Object {
-                            class_name: name,
-                            fields: std::collections::HashMap::new(),
-                        };
+                        let instance = Value::object_in(module, &name, std::collections::HashMap::new());
+                        self.perf.allocations += 1;
+                        // Exceptions take their message as the first argument
+                        if self.is_instance(&instance, None, "Error") {
+                            let message = args.first().map(Self::format_value).unwrap_or_default();
+                            if let Value::Object { fields, .. } = &instance {
+                                fields.lock().unwrap().insert("message".to_string(), Value::String(message));
+                            }
+                        }
                         self.stack.push(instance);
                     } else {
-                        return InterpretResult::RuntimeError("Expected class".to_string());
+                        return InterpretResult::RuntimeError("TypeError: Expected class".to_string());
                     }
                 } else {
                     return InterpretResult::RuntimeError("Stack underflow".to_string());
@@ -508,14 +1240,16 @@ impl VM {
                     _ => return InterpretResult::RuntimeError("Property name must be a string".to_string()),
                 };
 
-                if let Some(Value::Object { fields, .. }) = self.stack.pop() {
-                    if let Some(value) = fields.get(&property_name) {
-                        self.stack.push(value.clone());
-                    } else {
-                        return InterpretResult::RuntimeError(format!("Undefined property '{}'", property_name));
-                    }
-                } else {
-                    return InterpretResult::RuntimeError("Expected object".to_string());
+                match self.stack.pop() {
+                    Some(Value::Object { fields, .. }) => match fields.lock().unwrap().get(&property_name).cloned() {
+                        Some(value) => self.stack.push(value),
+                        None => return InterpretResult::RuntimeError(format!("Undefined property '{}'", property_name)),
+                    },
+                    Some(Value::Module(module_name)) => match self.module_member(&module_name, &property_name) {
+                        Ok(value) => self.stack.push(value),
+                        Err(e) => return InterpretResult::RuntimeError(e),
+                    },
+                    _ => return InterpretResult::RuntimeError("TypeError: Expected object".to_string()),
                 }
             }
             Some(OpCode::SetProperty) => {
@@ -530,18 +1264,14 @@ impl VM {
                     _ => return InterpretResult::RuntimeError("Property name must be a string".to_string()),
                 };
 
-                if let Some(Value::Object { fields, class_name }) = self.stack.pop() {
-                    // Create a new object with the updated property
-                    let mut new_fields = fields.clone();
-                    new_fields.insert(property_name, value);
-                    
-                    // Push the updated object back on the stack
-                    self.stack.push(Value::Object {
-                        class_name,
-                        fields: new_fields,
-                    });
+                // The object is changed in place, so everything holding it
+                // sees the new field; the assignment's value is what was
+                // assigned
+                if let Some(Value::Object { fields, .. }) = self.stack.pop() {
+                    fields.lock().unwrap().insert(property_name, value.clone());
+                    self.stack.push(value);
                 } else {
-                    return InterpretResult::RuntimeError("Expected object".to_string());
+                    return InterpretResult::RuntimeError("TypeError: Expected object".to_string());
                 }
             }
             Some(OpCode::CallMethod) => {
@@ -571,38 +1301,51 @@ impl VM {
                 };
 
                 // Get the class name from the object
-                let class_name = match &object {
-                    Value::Object { class_name, .. } => class_name.clone(),
-                    _ => return InterpretResult::RuntimeError("Expected object".to_string()),
-                };
-
-                // Look up the class in globals
-                let class_value = match self.globals.get(&class_name) {
-                    Some(Value::Class { methods, .. }) => methods,
-                    _ => return InterpretResult::RuntimeError(format!("Class '{}' not found", class_name)),
+                let (module, class_name) = match &object {
+                    Value::Object { class_name, module, .. } => (module.clone(), class_name.clone()),
+                    Value::Module(_) => {
+                        if let Err(e) = self.call_module_member(object, &method_name, args) {
+                            return InterpretResult::RuntimeError(e);
+                        }
+                        continue;
+                    }
+                    // Strings and other built-in values have native methods
+                    _ => {
+                        match crate::methods::call(self, object, &method_name, args) {
+                            Ok(result) => self.stack.push(result),
+                            Err(e) => return InterpretResult::RuntimeError(e),
+                        }
+                        continue;
+                    }
                 };
 
-                // Look up the method in the class
-                let method_constant_index = match class_value.get(&method_name) {
-                    Some(index) => index,
-                    None => return InterpretResult::RuntimeError(format!("Method '{}' not found in class '{}'", method_name, class_name)),
+                if self.class_named(module.as_deref(), &class_name).is_none() {
+                    return InterpretResult::RuntimeError(format!("Class '{}' not found", class_name));
+                }
+                let method_function = match self.find_method(module.as_deref(), &class_name, &method_name) {
+                    Some(Value::Function(func)) => func,
+                    _ => return InterpretResult::RuntimeError(format!("Method '{}' not found in class '{}'", method_name, class_name)),
                 };
+                // `self` is bound automatically as the first parameter
+                if method_function.arity != arg_count + 1 {
+                    return InterpretResult::RuntimeError(format!("TypeError: Method '{}' expects {} arguments, got {}", method_name, method_function.arity.saturating_sub(1), arg_count));
+                }
 
-                // Get the method function from constants
-                let method_function = match &self.chunk.as_ref().unwrap().constants[*method_constant_index] {
-                    Value::Function(func) => func.clone(),
-                    _ => return InterpretResult::RuntimeError("Method is not a function".to_string()),
-                };
+                if let Err(e) = self.check_call_depth() {
+                    return InterpretResult::RuntimeError(e);
+                }
 
-                // Create a new call frame for the method
+                // Create a new call frame for the method, returning to the caller
                 let frame = CallFrame {
-                    ip: 0,
+                    ip: self.ip,
                     slot: self.stack.len(),
-                    chunk: method_function.chunk.clone(),
+                    chunk: self.chunk.take().unwrap_or_default(),
+                    global_slots: std::mem::take(&mut self.global_slots),
+                    namespace: std::mem::replace(&mut self.namespace, method_function.module.clone()),
+                    upvalues: std::mem::take(&mut self.upvalues),
                 };
                 self.frames.push(frame);
 
-                // Push the object as the first argument (self)
                 self.stack.push(object);
 
                 // Push the arguments
@@ -627,14 +1370,14 @@ impl VM {
                 };
 
                 // Get the class name from the object
-                let class_name = match &object {
-                    Value::Object { class_name, .. } => class_name.clone(),
-                    _ => return InterpretResult::RuntimeError("Expected object".to_string()),
+                let (module, class_name) = match &object {
+                    Value::Object { class_name, module, .. } => (module.clone(), class_name.clone()),
+                    _ => return InterpretResult::RuntimeError("TypeError: Expected object".to_string()),
                 };
 
-                // Look up the class in globals
-                let class_value = match self.globals.get(&class_name) {
-                    Some(Value::Class { methods, superclass, .. }) => (methods, superclass),
+                // Look up the class where the object's class was defined
+                let class_value = match self.class_named(module.as_deref(), &class_name) {
+                    Some(Value::Class { superclass, module, .. }) => (module, superclass),
                     _ => return InterpretResult::RuntimeError(format!("Class '{}' not found", class_name)),
                 };
 
@@ -644,24 +1387,19 @@ impl VM {
                     None => return InterpretResult::RuntimeError(format!("Class '{}' has no superclass", class_name)),
                 };
 
-                // Look up the superclass in globals
-                let superclass_value = match self.globals.get(superclass_name.as_str()) {
+                // Look up the superclass as the class's module sees it
+                let superclass_value = match self.class_named(class_value.0.as_deref(), superclass_name) {
                     Some(Value::Class { methods, .. }) => methods,
                     _ => return InterpretResult::RuntimeError(format!("Superclass '{}' not found", superclass_name)),
                 };
 
                 // Look up the method in the superclass
-                let method_constant_index = match superclass_value.get(&method_name) {
-                    Some(index) => index,
+                let method_function = match superclass_value.get(&method_name) {
+                    Some(Value::Function(func)) => func.clone(),
+                    Some(_) => return InterpretResult::RuntimeError("Super method is not a function".to_string()),
                     None => return InterpretResult::RuntimeError(format!("Method '{}' not found in superclass '{}'", method_name, superclass_name)),
                 };
 
-                // Get the method function from constants
-                let method_function = match &self.chunk.as_ref().unwrap().constants[*method_constant_index] {
-                    Value::Function(func) => func.clone(),
-                    _ => return InterpretResult::RuntimeError("Super method is not a function".to_string()),
-                };
-
                 // Push the method function onto the stack
                 self.stack.push(Value::Function(method_function));
             }
@@ -670,7 +1408,10 @@ impl VM {
                 if let Value::String(code) = &self.chunk.as_ref().unwrap().constants[constant_index] {
                     // For now, just print inline Rust code to show it's working
                     // In a real implementation, this would compile and execute Rust code
-                    println!("[Executing Rust: {}]", code);
+                    let line = format!("[Executing Rust: {}]", code);
+                    if let Err(e) = self.write_output(&line) {
+                        return InterpretResult::RuntimeError(e);
+                    }
                     self.stack.push(Value::String("Rust inline executed".to_string()));
                 } else {
                     return InterpretResult::RuntimeError("RustInline expects string constant".to_string());
@@ -681,16 +1422,22 @@ impl VM {
                 if let Value::String(code) = &self.chunk.as_ref().unwrap().constants[constant_index] {
                     // For now, just print inline assembly code to show it's working
                     // In a real implementation, this would assemble and execute assembly code
-                    println!("[Executing Assembly: {}]", code);
+                    let line = format!("[Executing Assembly: {}]", code);
+                    if let Err(e) = self.write_output(&line) {
+                        return InterpretResult::RuntimeError(e);
+                    }
                     self.stack.push(Value::String("Assembly inline executed".to_string()));
                 } else {
                     return InterpretResult::RuntimeError("AsmInline expects string constant".to_string());
                 }
             }
-            Some(OpCode::Try) => {
-                // Try instruction - just a placeholder for now
-                // The actual exception handling will be done by compiler-generated jumps
-                let _jump_offset = self.read_short() as usize;
+            Some(op @ (OpCode::Try | OpCode::TryLong)) => {
+                let offset = self.read_jump(&op);
+                self.exception_stack.push(ExceptionHandler {
+                    catch_ip: self.ip + offset,
+                    frame_depth: self.frames.len(),
+                    stack_height: self.stack.len(),
+                });
             }
             Some(OpCode::Catch) => {
                 // Catch instruction - exception is already on stack
@@ -699,12 +1446,9 @@ impl VM {
             Some(OpCode::Throw) => {
                 // Throw an exception
                 if let Some(exception) = self.stack.pop() {
-                    // For now, just return runtime error with exception message
-                    if let Value::String(msg) = &exception {
-                        return InterpretResult::RuntimeError(format!("Exception: {}", msg));
-                    } else {
-                        return InterpretResult::RuntimeError("Exception thrown".to_string());
-                    }
+                    let message = Self::exception_message(&exception);
+                    self.thrown = Some((message.clone(), exception));
+                    return InterpretResult::RuntimeError(message);
                 } else {
                     return InterpretResult::RuntimeError("No exception to throw".to_string());
                 }
@@ -713,64 +1457,237 @@ impl VM {
                 // Pop exception handler from stack
                 self.exception_stack.pop();
             }
-            None => return InterpretResult::RuntimeError("Unknown opcode".to_string()),
+            Some(OpCode::Rethrow) => {
+                let exception = match self.stack.pop() {
+                    Some(value) => value,
+                    None => return InterpretResult::RuntimeError("No exception to rethrow".to_string()),
+                };
+                // Re-raise with the message it was caught with so runtime
+                // errors surface unchanged
+                let message = match self.caught.take() {
+                    Some((message, caught)) if Self::values_equal(&caught, &exception) => message,
+                    _ => Self::exception_message(&exception),
+                };
+                self.thrown = Some((message.clone(), exception));
+                return InterpretResult::RuntimeError(message);
+            }
+            Some(OpCode::ExceptionMatches) => {
+                // Stack: [..., exception, class1, ..., classN] -> [..., matched]
+                let class_count = self.read_byte().expect("Expected class count") as usize;
+                if self.stack.len() < class_count + 1 {
+                    return InterpretResult::RuntimeError("Stack underflow".to_string());
                 }
-        }
-    }
-
-    fn call_value(&mut self, arg_count: usize) -> bool {
-        // The function is below the arguments
-        let func_index = self.stack.len().checked_sub(arg_count + 1).unwrap_or(0);
-        let callee = match self.stack.get(func_index).cloned() {
-            Some(callee) => callee,
-            None => return false,
-        };
-        
-        match callee {
-            Value::String(name) if name == "print" => {
-                // Built-in print function
-                if arg_count != 1 {
-                    return false;
+                let classes = self.stack.split_off(self.stack.len() - class_count);
+                let exception = self.stack.pop().expect("length checked above");
+                let mut matched = false;
+                for class in &classes {
+                    match class {
+                        Value::Class { name, module, .. } => matched |= self.is_instance(&exception, module.as_deref(), name),
+                        other => return InterpretResult::RuntimeError(format!("TypeError: catch expects exception classes, got {}", Self::format_value(other))),
+                    }
                 }
-                if let Some(arg) = self.stack.pop() {
-                    self.stack.pop(); // Remove the function name
-                    println!("{}", self.format_value(&arg));
-                    self.stack.push(Value::Null);
-                    true
-                } else {
-                    false
+                self.stack.push(Value::Boolean(matched));
+            }
+            Some(OpCode::WithEnter) => {
+                // Stack: [..., manager] -> [..., manager, entered value]
+                let manager = match self.stack.last() {
+                    Some(value) => value.clone(),
+                    None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
+                };
+                match self.enter_context(manager) {
+                    Ok(value) => self.stack.push(value),
+                    Err(e) => return InterpretResult::RuntimeError(e),
+                }
+            }
+            Some(OpCode::WithExit) => {
+                // Stack: [..., manager, <distance values>, exception]
+                let distance = self.read_byte().expect("Expected manager distance") as usize;
+                let exception = match self.stack.pop() {
+                    Some(value) => value,
+                    None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
+                };
+                let manager = match self.stack.len().checked_sub(distance + 1) {
+                    Some(index) => self.stack[index].clone(),
+                    None => return InterpretResult::RuntimeError("Stack underflow".to_string()),
+                };
+                if let Err(e) = self.exit_context(manager, exception) {
+                    return InterpretResult::RuntimeError(e);
                 }
             }
-            Value::Function(func) => {
-                // User-defined function
-                if arg_count != func.arity {
-                    return false;
+            None => return InterpretResult::RuntimeError("Unknown opcode".to_string()),
                 }
+        }
+    }
 
-                // Remove the function from the stack
-                self.stack.remove(func_index);
+    fn exception_message(exception: &Value) -> String {
+        match exception {
+            Value::String(msg) => format!("Exception: {}", msg),
+            Value::Object { class_name, fields, .. } => match fields.lock().unwrap().get("message") {
+                Some(message @ Value::String(_)) => format!("{}: {}", class_name, Self::format_value(message)),
+                _ => format!("Exception: {}", Self::format_value(exception)),
+            },
+            other => format!("Exception: {}", Self::format_value(other)),
+        }
+    }
 
-                // Create a new call frame
-                let slot = self.stack.len() - arg_count;
-                let current_chunk = self.chunk.take().unwrap_or_else(|| Chunk::new());
-                let frame = CallFrame {
-                    ip: self.ip,
-                    slot,
-                    chunk: current_chunk,
-                };
-                self.frames.push(frame);
+    /// Look up a method on class `class_name` of `module` (see
+    /// [`VM::class_named`]). Methods a class doesn't define are inherited
+    /// from the nearest superclass that does. Results are cached until a
+    /// class is (re)defined.
+    fn find_method(&mut self, module: Option<&str>, class_name: &str, method_name: &str) -> Option<Value> {
+        let key = (module.map(str::to_string), class_name.to_string(), method_name.to_string());
+        if let Some(method) = self.method_cache.get(&key) {
+            return method.clone();
+        }
+        let mut namespace = module.map(str::to_string);
+        let mut current = Some(class_name.to_string());
+        let mut found = None;
+        // Bound the walk so a cyclic superclass chain can't hang the VM
+        for _ in 0..64 {
+            let Some(Value::Class { methods, superclass, module, .. }) = current.as_ref().and_then(|name| self.class_named(namespace.as_deref(), name)) else {
+                break;
+            };
+            if let Some(method) = methods.get(method_name) {
+                found = Some(method.clone());
+                break;
+            }
+            current = superclass.clone();
+            namespace = module.clone();
+        }
+        self.method_cache.insert(key, found.clone());
+        found
+    }
 
-                // Set up the function's chunk
-                self.chunk = Some(func.chunk.clone());
+    /// The class called `name` as code in `module` sees it: one the module
+    /// defines, or else a global, the same way the module's code resolves
+    /// names. `None` is the script itself.
+    fn class_named(&self, module: Option<&str>, name: &str) -> Option<&Value> {
+        let is_class = |value: &&Value| matches!(value, Value::Class { .. });
+        // Native classes have no script class behind them
+        if module == Some(crate::bytecode::NATIVE_MODULE) {
+            return None;
+        }
+        module.and_then(|module| self.modules.get(module)).and_then(|members| members.get(name)).filter(is_class)
+            .or_else(|| self.globals.get(name).filter(is_class))
+    }
 
-                // Jump to the start of the function
-                self.ip = 0;
-                true
-            }
+    /// `module.name(args)`: a plain call of the module's member.
+    fn call_module_member(&mut self, module: Value, name: &str, args: Vec<Value>) -> Result<(), String> {
+        let function = match &module {
+            Value::Module(module_name) => self.module_member(module_name, name)?,
+            _ => return Err("Expected module".to_string()),
+        };
+        let arg_count = args.len();
+        self.stack.push(function);
+        self.stack.extend(args);
+        self.call_value(arg_count)
+    }
+
+    fn module_member(&mut self, module_name: &str, member: &str) -> Result<Value, String> {
+        // Modules bound by `lazy use` load on first use
+        if !self.modules.contains_key(module_name) {
+            crate::modules::import(self, module_name)?;
+        }
+        if let Some(value) = self.modules[module_name].get(member) {
+            return Ok(value.clone());
+        }
+        match self.importing.iter().position(|module| module == module_name) {
+            // Reached through a `lazy use` cycle before the module got there
+            Some(start) => Err(format!(
+                "ImportError: Module '{}' is still loading, so '{}' is not defined yet (import cycle {} -> {})",
+                module_name, member, self.importing[start..].join(" -> "), module_name
+            )),
+            None => Err(format!("Module '{}' has no member '{}'", module_name, member)),
+        }
+    }
+
+    /// Whether `value` is an object of class `class_name` of `module` or
+    /// one of its subclasses.
+    fn is_instance(&self, value: &Value, module: Option<&str>, class_name: &str) -> bool {
+        let (mut namespace, mut current) = match value {
+            Value::Object { class_name, module, .. } => (module.clone(), Some(class_name.clone())),
+            _ => return false,
+        };
+        // Bound the walk so a cyclic superclass chain can't hang the VM
+        for _ in 0..64 {
+            let Some(name) = current else {
+                return false;
+            };
+            match self.class_named(namespace.as_deref(), &name) {
+                Some(Value::Class { name, superclass, module: defined_in, .. }) => {
+                    if name == class_name && defined_in.as_deref() == module {
+                        return true;
+                    }
+                    current = superclass.clone();
+                    namespace = defined_in.clone();
+                }
+                // Objects made by natives may have no class to look up
+                _ => return name == class_name && namespace.as_deref() == module,
+            }
+        }
+        false
+    }
+
+    /// Acquire a `with` manager and return the value bound by `as`.
+    fn enter_context(&mut self, manager: Value) -> Result<Value, String> {
+        let (module, class_name) = match &manager {
+            Value::Object { class_name, module, .. } => (module.clone(), class_name.clone()),
+            other => return Err(format!("TypeError: 'with' expects a context manager, got {}", Self::format_value(other))),
+        };
+        if self.resource_types.contains_key(&class_name) && manager.is_native_object(&class_name) {
+            return Ok(manager);
+        }
+        match (self.find_method(module.as_deref(), &class_name, "__enter__"), self.find_method(module.as_deref(), &class_name, "__exit__")) {
+            (Some(enter), Some(_)) => self.call_function(enter, vec![manager]),
+            _ => Err(format!("Class '{}' does not define __enter__ and __exit__", class_name)),
+        }
+    }
+
+    /// Release a `with` manager. `exception` is null when the block finished normally.
+    fn exit_context(&mut self, manager: Value, exception: Value) -> Result<(), String> {
+        let (module, class_name) = match &manager {
+            Value::Object { class_name, module, .. } => (module.clone(), class_name.clone()),
+            _ => return Ok(()),
+        };
+        if let Some(close) = self.resource_types.get(&class_name).copied().filter(|_| manager.is_native_object(&class_name)) {
+            return close(self, manager);
+        }
+        match self.find_method(module.as_deref(), &class_name, "__exit__") {
+            Some(exit) => self.call_function(exit, vec![manager, exception]).map(|_| ()),
+            None => Err(format!("Class '{}' does not define __exit__", class_name)),
+        }
+    }
+
+    fn call_value(&mut self, arg_count: usize) -> Result<(), String> {
+        // The function is below the arguments
+        let func_index = self.stack.len().saturating_sub(arg_count + 1);
+        let callee = match self.stack.get(func_index).cloned() {
+            Some(callee) => callee,
+            None => return Err("Failed to call value".to_string()),
+        };
+        
+        match callee {
+            Value::String(name) if name == "print" => {
+                // Built-in print function; multiple arguments are space-separated
+                if arg_count == 0 || self.stack.len() <= arg_count {
+                    return Err("Failed to call value".to_string());
+                }
+                let args = self.stack.split_off(self.stack.len() - arg_count);
+                self.stack.pop(); // Remove the function name
+                let line: Vec<String> = args.iter().map(Self::format_value).collect();
+                self.write_output(&line.join(" "))?;
+                self.stack.push(Value::Null);
+                Ok(())
+            }
+            Value::Function(func) => self.enter_function(func, Vec::new(), arg_count, func_index),
+            Value::Closure(closure) => {
+                let Closure { function, upvalues } = *closure;
+                self.enter_function(function, upvalues, arg_count, func_index)
+            }
             Value::NativeFunction(native_func) => {
                 // Native function
-                if arg_count != native_func.arity {
-                    return false;
+                if !native_func.arity.accepts(arg_count) {
+                    return Err(format!("TypeError: {} expects {}, got {}", native_func.name, native_func.arity, arg_count));
                 }
 
                 // Collect arguments (they are above the function on the stack)
@@ -779,44 +1696,327 @@ impl VM {
                     if let Some(arg) = self.stack.get(func_index + 1 + i).cloned() {
                         args.push(arg);
                     } else {
-                        return false;
+                        return Err("Failed to call value".to_string());
                     }
                 }
 
                 // Remove the function and arguments from the stack
                 self.stack.truncate(func_index);
 
-                // Call the native function
-                match (native_func.function)(self, args) {
+                let mut name = native_func.name.as_str();
+                if let Some(qualified) = self.deprecated_natives.get(name) {
+                    let message = format!("{} is deprecated; use {} instead", name, qualified);
+                    if !self.warnings.contains(&message) {
+                        self.warnings.push(message);
+                    }
+                    name = qualified;
+                }
+
+                // Call the native function, or the mock standing in for it
+                let mock = if self.mocks.is_empty() { None } else { self.mocks.get_mut(name) };
+                let result = match mock {
+                    Some(mock) => mock(&args),
+                    None => crate::replay::call(self, &native_func, args),
+                };
+                match result {
                     Ok(result)=> {
                         self.stack.push(result);
-                        true
+                        Ok(())
                     }
-                    Err(_) => false,
+                    Err(e) => Err(e),
                 }
             }
             _ => {
                 self.stack.push(Value::Null);
-                true
+                Ok(())
             }
         }
     }
 
-    pub fn format_value(&self, value: &Value) -> String {
+    /// Start running `func` with `upvalues` as its captured variables, in a
+    /// new frame over the arguments above it on the stack.
+    fn enter_function(&mut self, func: Function, upvalues: Vec<Upvalue>, arg_count: usize, func_index: usize) -> Result<(), String> {
+        if arg_count != func.arity {
+            return Err(format!("TypeError: Function '{}' expects {}, got {}", func.name, Arity::from(func.arity), arg_count));
+        }
+
+        self.check_call_depth()?;
+
+        // Remove the function from the stack
+        self.stack.remove(func_index);
+
+        // Create a new call frame
+        let slot = self.stack.len() - arg_count;
+        let current_chunk = self.chunk.take().unwrap_or_default();
+        let frame = CallFrame {
+            ip: self.ip,
+            slot,
+            chunk: current_chunk,
+            global_slots: std::mem::take(&mut self.global_slots),
+            namespace: std::mem::replace(&mut self.namespace, func.module.clone()),
+            upvalues: std::mem::replace(&mut self.upvalues, upvalues),
+        };
+        self.frames.push(frame);
+
+        // Set up the function's chunk
+        self.chunk = Some(func.chunk);
+
+        // Jump to the start of the function
+        self.ip = 0;
+        Ok(())
+    }
+
+    /// Push a closure over the function constant operand of `CLOSURE`,
+    /// capturing the variables it lists.
+    fn create_closure(&mut self) -> Result<(), String> {
+        let function = match self.read_constant() {
+            Value::Function(function) => function,
+            _ => return Err("Closure of a non-function".to_string()),
+        };
+        let count = self.read_byte().expect("Expected capture count") as usize;
+        let base = self.frames.last().map_or(0, |frame| frame.slot);
+        let mut upvalues = Vec::with_capacity(count);
+        for _ in 0..count {
+            let is_local = self.read_byte().expect("Expected capture kind") == 1;
+            let index = self.read_byte().expect("Expected capture index") as usize;
+            upvalues.push(if is_local {
+                self.capture_upvalue(base + index)
+            } else {
+                self.upvalues.get(index).cloned().ok_or("Invalid upvalue")?
+            });
+        }
+        self.perf.allocations += 1;
+        self.stack.push(Value::Closure(Box::new(Closure { function, upvalues })));
+        Ok(())
+    }
+
+    fn get_upvalue(&self, index: usize) -> Result<Value, String> {
+        let upvalue = self.upvalues.get(index).ok_or("Invalid upvalue")?;
+        let value = match &*upvalue.lock().unwrap() {
+            UpvalueCell::Open(slot) => self.stack.get(*slot).cloned(),
+            UpvalueCell::Closed(value) => Some(value.clone()),
+        };
+        value.ok_or_else(|| "Invalid upvalue".to_string())
+    }
+
+    fn set_upvalue(&mut self, index: usize, value: Value) -> Result<(), String> {
+        let upvalue = self.upvalues.get(index).ok_or("Invalid upvalue")?;
+        match &mut *upvalue.lock().unwrap() {
+            UpvalueCell::Open(slot) => *self.stack.get_mut(*slot).ok_or("Invalid upvalue")? = value,
+            UpvalueCell::Closed(variable) => *variable = value,
+        }
+        Ok(())
+    }
+
+    /// The open upvalue for absolute stack `slot`, created if no closure has
+    /// captured the variable yet.
+    fn capture_upvalue(&mut self, slot: usize) -> Upvalue {
+        let open = self.open_upvalues.iter()
+            .find(|upvalue| matches!(*upvalue.lock().unwrap(), UpvalueCell::Open(open) if open == slot));
+        if let Some(upvalue) = open {
+            return upvalue.clone();
+        }
+        let upvalue = Upvalue::new(std::sync::Mutex::new(UpvalueCell::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Move the variables captured from stack slots at or above `from` into
+    /// their upvalues, before those slots are discarded.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut cell = upvalue.lock().unwrap();
+            match *cell {
+                UpvalueCell::Open(slot) if slot >= from => {
+                    *cell = UpvalueCell::Closed(stack.get(slot).cloned().unwrap_or(Value::Null));
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    /// The element a `for` loop visits at `index` of `iterable`, or `None`
+    /// past the end: array and tuple items, a string's characters, or a
+    /// a dictionary's keys in sorted order, or bytes as numbers.
+    pub fn element(iterable: &Value, index: usize) -> Result<Option<Value>, String> {
+        Ok(match iterable {
+            Value::Array(items) => items.lock().unwrap().get(index).cloned(),
+            Value::Tuple(items) => items.get(index).cloned(),
+            Value::String(s) => s.chars().nth(index).map(|c| Value::String(c.to_string())),
+            Value::Bytes(bytes) => bytes.get(index).map(|&byte| Value::Number(byte as f64)),
+            Value::Dictionary(entries) => {
+                let entries = entries.lock().unwrap();
+                let mut keys: Vec<&String> = entries.keys().collect();
+                keys.sort();
+                keys.get(index).map(|key| Value::String((*key).clone()))
+            }
+            &Value::Range { start, stop, step } => {
+                ((index as u128) < range_len(start, stop, step)).then_some(Value::Number(start + index as f64 * step))
+            }
+            other => return Err(format!("TypeError: Cannot iterate over {}", Self::format_value(other))),
+        })
+    }
+
+    /// `target[index]`: an element of an array, tuple, string or bytes, a
+    /// negative index counting back from the end, or a dictionary's value.
+    pub fn index(target: &Value, index: &Value) -> Result<Value, String> {
+        let out_of_bounds = |i: f64, len: usize, kind: &str| format!("IndexError: Index {} out of bounds for {} of length {}", i, kind, len);
+        match (target, index) {
+            (Value::Array(elements), Value::Number(i)) => {
+                let elements = elements.lock().unwrap();
+                match position(*i, elements.len()) {
+                    Some(at) => Ok(elements[at].clone()),
+                    None => Err(out_of_bounds(*i, elements.len(), "array")),
+                }
+            }
+            (Value::Tuple(elements), Value::Number(i)) => match position(*i, elements.len()) {
+                Some(at) => Ok(elements[at].clone()),
+                None => Err(out_of_bounds(*i, elements.len(), "array")),
+            },
+            (Value::String(s), Value::Number(i)) => {
+                let len = s.chars().count();
+                match position(*i, len).and_then(|at| s.chars().nth(at)) {
+                    Some(c) => Ok(Value::String(c.to_string())),
+                    None => Err(out_of_bounds(*i, len, "string")),
+                }
+            }
+            (Value::Bytes(bytes), Value::Number(i)) => match position(*i, bytes.len()) {
+                Some(at) => Ok(Value::Number(bytes[at] as f64)),
+                None => Err(out_of_bounds(*i, bytes.len(), "bytes")),
+            },
+            (&Value::Range { start, stop, step }, Value::Number(i)) => {
+                let len = range_len(start, stop, step);
+                match offset(*i, len) {
+                    Some(at) => Ok(Value::Number(start + at as f64 * step)),
+                    None => Err(format!("IndexError: Index {} out of bounds for range of length {}", i, len)),
+                }
+            }
+            (Value::Dictionary(entries), Value::String(key)) => match entries.lock().unwrap().get(key) {
+                Some(value) => Ok(value.clone()),
+                None => Err(format!("KeyError: '{}' not found in dictionary", key)),
+            },
+            _ => Err("TypeError: Index operation requires array and number".to_string()),
+        }
+    }
+
+    /// `target[start:end]`: the elements of an array, tuple, string or bytes
+    /// from `start` up to but not including `end`, as the same type. Null
+    /// bounds mean the start and end, negative ones count back from the end,
+    /// and bounds past either end stop there, as in Python.
+    pub fn slice(target: &Value, start: &Value, end: &Value) -> Result<Value, String> {
+        let range = |len: usize| -> Result<std::ops::Range<usize>, String> {
+            let start = slice_bound(start, len, 0)?;
+            Ok(start..slice_bound(end, len, len)?.max(start))
+        };
+        match target {
+            Value::Array(elements) => {
+                let elements = elements.lock().unwrap();
+                Ok(Value::array(elements[range(elements.len())?].to_vec()))
+            }
+            Value::Tuple(elements) => Ok(Value::Tuple(elements[range(elements.len())?].to_vec())),
+            Value::String(s) => {
+                let range = range(s.chars().count())?;
+                Ok(Value::String(s.chars().skip(range.start).take(range.len()).collect()))
+            }
+            Value::Bytes(bytes) => Ok(Value::Bytes(bytes[range(bytes.len())?].to_vec())),
+            _ => Err("TypeError: Slice operation requires an array, tuple, string or bytes".to_string()),
+        }
+    }
+
+    /// What `GET_ITER` leaves for `FOR_ITER` to step through: arrays,
+    /// tuples and bytes as they are, a string's characters or a
+    /// dictionary's sorted keys, or for an object, what its `__iter__`
+    /// method returns.
+    pub fn iterator(&mut self, iterable: Value) -> Result<Value, String> {
+        match iterable {
+            Value::Array(_) | Value::Tuple(_) | Value::Bytes(_) | Value::Range { .. } => Ok(iterable),
+            Value::Object { ref class_name, ref module, .. } => {
+                let (module, class_name) = (module.clone(), class_name.clone());
+                let Some(method) = self.find_method(module.as_deref(), &class_name, "__iter__") else {
+                    return Err(format!("TypeError: Cannot iterate over an instance of '{}', which has no __iter__ method", class_name));
+                };
+                match self.call_function(method, vec![iterable])? {
+                    Value::Object { class_name: returned, .. } if returned == class_name => {
+                        Err(format!("__iter__ of '{}' must return an iterable, not another '{}'", class_name, returned))
+                    }
+                    iterable => self.iterator(iterable),
+                }
+            }
+            other => Self::elements(&other).map(Value::array),
+        }
+    }
+
+    /// [`VM::elements`], also taking objects with an `__iter__` method.
+    pub fn iterate(&mut self, iterable: Value) -> Result<Vec<Value>, String> {
+        let iterator = self.iterator(iterable)?;
+        Self::elements(&iterator)
+    }
+
+    /// Every element a `for` loop over `iterable` visits.
+    pub fn elements(iterable: &Value) -> Result<Vec<Value>, String> {
+        match iterable {
+            Value::Array(items) => Ok(items.lock().unwrap().clone()),
+            Value::Tuple(items) => Ok(items.clone()),
+            Value::String(s) => Ok(s.chars().map(|c| Value::String(c.to_string())).collect()),
+            Value::Bytes(bytes) => Ok(bytes.iter().map(|&byte| Value::Number(byte as f64)).collect()),
+            Value::Dictionary(entries) => {
+                let entries = entries.lock().unwrap();
+                let mut keys: Vec<&String> = entries.keys().collect();
+                keys.sort();
+                Ok(keys.into_iter().map(|key| Value::String(key.clone())).collect())
+            }
+            &Value::Range { start, stop, step } => {
+                // Ask for the memory first: a range can count further than
+                // any array could hold
+                let len = range_len(start, stop, step);
+                let mut numbers = Vec::new();
+                match usize::try_from(len) {
+                    Ok(len) if numbers.try_reserve_exact(len).is_ok() => {}
+                    _ => return Err(format!("A range of {} numbers is too large to make into an array", len)),
+                }
+                numbers.extend((0..len as usize).map(|i| Value::Number(start + i as f64 * step)));
+                Ok(numbers)
+            }
+            other => Self::element(other, 0).map(|_| Vec::new()),
+        }
+    }
+
+    pub fn format_value(value: &Value) -> String {
         match value {
             Value::Number(n) => n.to_string(),
             Value::String(s) => s.clone(),
+            Value::Bytes(bytes) => crate::bytes::literal(bytes),
             Value::Boolean(b) => b.to_string(),
             Value::Null => "null".to_string(),
             Value::Function(f) => format!("<fn {}>", f.name),
+            Value::Closure(c) => format!("<fn {}>", c.function.name),
             Value::NativeFunction(f) => format!("<native fn {}>", f.name),
-            Value::Array(arr)=> {
-                let elements: Vec<String> = arr.iter().map(|v| self.format_value(v)).collect();
-                format!("[{}]", elements.join(", "))
+            // An array or dictionary that's already locked is one being
+            // formatted further up: it contains itself
+            Value::Array(arr)=> match arr.try_lock() {
+                Ok(arr) => {
+                    let elements: Vec<String> = arr.iter().map(Self::format_value).collect();
+                    format!("[{}]", elements.join(", "))
+                }
+                Err(_) => "[...]".to_string(),
+            },
+            Value::Tuple(items) => {
+                let elements: Vec<String> = items.iter().map(Self::format_value).collect();
+                if elements.len() == 1 {
+                    format!("({},)", elements[0])
+                } else {
+                    format!("({})", elements.join(", "))
+                }
             },
             Value::Dictionary(dict) => {
-                let pairs: Vec<String> = dict.iter()
-                    .map(|(k, v)| format!("{}: {}", k, self.format_value(v)))
+                let Ok(dict) = dict.try_lock() else { return "{...}".to_string() };
+                // Sort keys so printed dictionaries are stable across runs
+                let mut keys: Vec<&String> = dict.keys().collect();
+                keys.sort();
+                let pairs: Vec<String> = keys.into_iter()
+                    .map(|k| format!("{}: {}", k, Self::format_value(&dict[k])))
                     .collect();
                 format!("{{{}}}", pairs.join(", "))
             },
@@ -824,6 +2024,16 @@ impl VM {
                 format!("Object of class {}", class_name)
             },
             Value::Class { name, .. } => format!("Class {:?}", name),
+            Value::Module(name) => format!("<module {}>", name),
+            Value::Range { start, stop, step } if *step == 1.0 => format!("range({}, {})", start, stop),
+            Value::Range { start, stop, step } => format!("range({}, {}, {})", start, stop, step),
+        }
+    }
+
+    fn check_call_depth(&self) -> Result<(), String> {
+        match self.limits.max_call_depth {
+            Some(limit) if self.frames.len() >= limit => Err(format!("Maximum call depth of {} exceeded", limit)),
+            _ => Ok(()),
         }
     }
 
@@ -838,51 +2048,129 @@ impl VM {
         }
     }
 
-    fn read_short(&mut self) -> u16 {
+    /// Read the offset operand of jump instruction `op`: 16 bits, or 32 for
+    /// the long forms.
+    fn read_jump(&mut self, op: &OpCode) -> usize {
         let chunk = self.chunk.as_ref().expect("No chunk loaded");
-        let high = chunk.code[self.ip] as u16;
-        let low = chunk.code[self.ip + 1] as u16;
-        self.ip += 2;
-        (high << 8) | low
+        let width = if op.is_long_jump() { 4 } else { 2 };
+        let offset = chunk.code[self.ip..self.ip + width].iter().fold(0, |offset, &byte| offset << 8 | byte as usize);
+        self.ip += width;
+        offset
     }
 
     fn read_constant(&mut self) -> Value {
         let index = self.read_byte().expect("Expected constant") as usize;
         let chunk = self.chunk.as_ref().expect("No chunk loaded");
-        chunk.constants[index].clone()
+        // A constant array or dictionary, such as a define, is read afresh
+        // each time so changes to one don't reach the next
+        match &chunk.constants[index] {
+            constant @ (Value::Array(_) | Value::Dictionary(_) | Value::Object { .. }) => constant.deep_copy(),
+            constant => constant.clone(),
+        }
     }
 
     fn read_string(&mut self) -> Value {
         self.read_constant()
     }
 
-    fn is_truthy(&self, value: &Value) -> bool {
+    pub fn is_truthy(value: &Value) -> bool {
         match value {
             Value::Boolean(b) => *b,
             Value::Null => false,
             Value::Number(n) => *n != 0.0,
             Value::String(s) => !s.is_empty(),
+            Value::Bytes(bytes) => !bytes.is_empty(),
             Value::Function(_) => true,
+            Value::Closure(_) => true,
             Value::NativeFunction(_) => true,
-            Value::Array(arr) => !arr.is_empty(),
-            Value::Dictionary(dict) => !dict.is_empty(),
+            Value::Array(arr) => !arr.lock().unwrap().is_empty(),
+            Value::Tuple(items) => !items.is_empty(),
+            Value::Dictionary(dict) => !dict.lock().unwrap().is_empty(),
             Value::Object { .. } => true,
             Value::Class { .. } => true,
+            Value::Module(_) => true,
+            &Value::Range { start, stop, step } => range_len(start, stop, step) > 0,
         }
     }
 
-    fn values_equal(&self, a: &Value, b: &Value) -> bool {
+    /// Structural equality used by `==`, `!=` and the `equals` builtin.
+    pub fn values_equal(a: &Value, b: &Value) -> bool {
         match (a, b) {
             (Value::Number(a), Value::Number(b)) => a == b,
             (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::Null, Value::Null) => true,
-            (Value::Array(a), Value::Array(b))=> {
-                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| self.values_equal(x, y))
-                }
+            // Values already locked are being compared further up, so they
+            // contain themselves; only the same value equals itself then
+            (Value::Array(a), Value::Array(b)) => Arc::ptr_eq(a, b) || match (a.try_lock(), b.try_lock()) {
+                (Ok(a), Ok(b)) => Self::elements_equal(&a, &b),
+                _ => false,
+            },
+            (Value::Tuple(a), Value::Tuple(b)) => Self::elements_equal(a, b),
+            (Value::Dictionary(a), Value::Dictionary(b)) => Arc::ptr_eq(a, b) || match (a.try_lock(), b.try_lock()) {
+                (Ok(a), Ok(b)) => Self::entries_equal(&a, &b),
+                _ => false,
+            },
+            (Value::Object { class_name: a_class, module: a_module, fields: a_fields }, Value::Object { class_name: b_class, module: b_module, fields: b_fields }) => {
+                a_class == b_class && a_module == b_module && (Arc::ptr_eq(a_fields, b_fields) || match (a_fields.try_lock(), b_fields.try_lock()) {
+                    (Ok(a), Ok(b)) => Self::entries_equal(&a, &b),
+                    _ => false,
+                })
+            }
+            (Value::NativeFunction(a), Value::NativeFunction(b)) => a.name == b.name,
+            (Value::Module(a), Value::Module(b)) => a == b,
+            (&Value::Range { start, stop, step }, &Value::Range { start: b_start, stop: b_stop, step: b_step }) => {
+                start == b_start && stop == b_stop && step == b_step
+            }
             _ => false,
                 }
     }
+
+    fn elements_equal(a: &[Value], b: &[Value]) -> bool {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| Self::values_equal(x, y))
+    }
+
+    fn entries_equal(a: &HashMap<String, Value>, b: &HashMap<String, Value>) -> bool {
+        a.len() == b.len() && a.iter().all(|(key, x)| b.get(key).is_some_and(|y| Self::values_equal(x, y)))
+    }
+}
+
+/// How many numbers `range(start, stop, step)` counts through. Ranges are
+/// never made whole, so this can be more than a `usize` holds.
+pub(crate) fn range_len(start: f64, stop: f64, step: f64) -> u128 {
+    let count = ((stop - start) / step).ceil();
+    if count > 0.0 { count as u128 } else { 0 }
+}
+
+/// Where index `i` falls in a sequence of `len` elements, counting back from
+/// the end if it's negative; `None` if that's outside it.
+pub(crate) fn position(i: f64, len: usize) -> Option<usize> {
+    offset(i, len as u128).map(|at| at as usize)
+}
+
+/// [`position`] within a sequence as long as a range can be. Counting back
+/// is done in integers, which stay exact where floats near `len` don't.
+fn offset(i: f64, len: u128) -> Option<u128> {
+    if i.is_nan() {
+        return None;
+    }
+    let i = i.trunc();
+    if i < 0.0 {
+        len.checked_sub(-i as u128)
+    } else {
+        Some(i as u128).filter(|&at| at < len)
+    }
+}
+
+/// A slice bound within a sequence of `len` elements, `default` if null.
+pub(crate) fn slice_bound(bound: &Value, len: usize, default: usize) -> Result<usize, String> {
+    match bound {
+        Value::Null => Ok(default),
+        Value::Number(n) if *n < 0.0 => Ok((len as f64 + n.trunc()).max(0.0) as usize),
+        Value::Number(n) => Ok((n.trunc() as usize).min(len)),
+        _ => Err("TypeError: Slice bounds must be numbers or null".to_string()),
+    }
 }
 
 #[cfg(test)]
@@ -974,4 +2262,404 @@ mod tests {
         let result = run_code("print(42)").unwrap();
         assert_eq!(result, InterpretResult::Ok);
     }
-}
\ No newline at end of file
+
+    fn run_vm(code: &str) -> (VM, InterpretResult) {
+        run_in(VM::new(), code)
+    }
+
+    fn run_in(mut vm: VM, code: &str) -> (VM, InterpretResult) {
+        let mut lexer = Lexer::new(code.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+        let mut compiler = Compiler::new();
+        let chunk = compiler.compile(&program).unwrap().clone();
+        let result = vm.interpret(chunk);
+        (vm, result)
+    }
+
+    /// A VM with a `record(value)` native that appends to the global `log`.
+    fn recording_vm() -> VM {
+        let mut vm = VM::new();
+        vm.globals.insert("log".to_string(), Value::array(Vec::new()));
+        vm.register_native("record", 1, |vm, args| {
+            if let Some(Value::Array(log)) = vm.globals.get("log") {
+                log.lock().unwrap().push(args[0].clone());
+            }
+            Ok(Value::Null)
+        });
+        vm
+    }
+
+    fn log_strings(vm: &VM) -> Vec<String> {
+        match vm.globals.get("log") {
+            // Exception objects are logged as "Class: message"
+            Some(Value::Array(log)) => log.lock().unwrap().iter().map(|value| match value {
+                Value::Object { .. } => VM::exception_message(value),
+                other => VM::format_value(other),
+            }).collect(),
+            other => panic!("Expected log array, got {:?}", other),
+        }
+    }
+
+    fn global_number(vm: &VM, name: &str) -> f64 {
+        match vm.globals.get(name) {
+            Some(Value::Number(n)) => *n,
+            other => panic!("Expected number in '{}', got {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn test_vm_operand_order() {
+        let (vm, result) = run_vm("a = 5 - 3\nb = 6 / 3\nc = 7 % 3\nd = 1 < 2");
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "a"), 2.0);
+        assert_eq!(global_number(&vm, "b"), 2.0);
+        assert_eq!(global_number(&vm, "c"), 1.0);
+        assert!(matches!(vm.globals.get("d"), Some(Value::Boolean(true))));
+    }
+
+    #[test]
+    fn test_vm_loops_update_variables() {
+        let code = "i = 0\nwhile i < 3:\n    i = i + 1\ndef total(items):\n    sum = 0\n    for item in items:\n        sum = sum + item\n    return sum\nt = total([1, 2, 3])";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "i"), 3.0);
+        assert_eq!(global_number(&vm, "t"), 6.0);
+    }
+
+    #[test]
+    fn test_vm_global_slot_cache_sees_native_changes() {
+        let mut vm = VM::new();
+        vm.register_native("reset", 0, |vm, _| {
+            vm.globals.remove("n");
+            vm.globals = vm.globals.clone().into_iter().collect();
+            vm.globals.insert("calls".to_string(), Value::Number(1.0));
+            Ok(Value::Null)
+        });
+        // `reset` drops `n` and rebuilds the globals, shifting every slot
+        let code = "def get():\n    return n\nn = 0\ncalls = 0\ni = 0\nwhile i < 3:\n    n = n + 1\n    i = i + 1\nreset()\nafter = calls\nm = get()";
+        let (vm, result) = run_in(vm, code);
+        assert!(matches!(result, InterpretResult::RuntimeError(ref e) if e.contains("'n'")));
+        assert_eq!(global_number(&vm, "after"), 1.0);
+        assert_eq!(global_number(&vm, "i"), 3.0);
+
+        let (vm, result) = run_vm("def get():\n    return n\nn = 5\nm = get()\nn = 6\nk = get()");
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "k"), 6.0);
+    }
+
+    #[test]
+    fn test_vm_runs_long_jumps() {
+        let padding = "            a = b\n".repeat(20_000);
+        let code = format!("def count(n):\n    a = 0\n    b = 0\n    c = 0\n    while n > 0:\n        n = n - 1\n        try:\n{}            c = c + 1\n        catch:\n            c = -1\n    return c\nresult = count(3)", padding);
+        let (vm, result) = run_vm(&code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "result"), 3.0);
+    }
+
+    #[test]
+    fn test_vm_try_catch_runtime_error() {
+        let code = "caught = false\ntry:\n    x = {\"a\": 1}[\"b\"]\ncatch:\n    caught = true\nafter = 1";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(vm.globals.get("caught"), Some(Value::Boolean(true))));
+        assert!(!vm.globals.contains_key("x"));
+        assert_eq!(global_number(&vm, "after"), 1.0);
+    }
+
+    #[test]
+    fn test_vm_try_catch_unwinds_call_frames() {
+        let code = "def fail(n):\n    if n > 2:\n        throw \"deep\"\n    return fail(n + 1)\ndef guarded():\n    try:\n        fail(0)\n    catch:\n        return 1\n    return 0\nr = guarded()\nstill = 2";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "r"), 1.0);
+        assert_eq!(global_number(&vm, "still"), 2.0);
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_vm_uncaught_throw() {
+        let (_, result) = run_vm("try:\n    x = 1\ncatch:\n    x = 2\nthrow \"boom\"");
+        assert_eq!(result, InterpretResult::RuntimeError("Exception: boom".to_string()));
+    }
+
+    const RESOURCE_CLASS: &str = "class Res:\n    def __enter__(self):\n        record(\"enter\")\n        return 42\n    def __exit__(self, err):\n        record(err)\n";
+
+    #[test]
+    fn test_vm_with_binds_enter_result_and_exits() {
+        let code = format!("{}with new Res() as r:\n    value = r\nafter = 1", RESOURCE_CLASS);
+        let (vm, result) = run_in(recording_vm(), &code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "value"), 42.0);
+        assert_eq!(global_number(&vm, "after"), 1.0);
+        assert_eq!(log_strings(&vm), vec!["enter", "null"]);
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_vm_with_exits_when_body_throws() {
+        let code = format!("{}try:\n    with new Res():\n        x = {{\"a\": 1}}[\"b\"]\ncatch:\n    caught = true", RESOURCE_CLASS);
+        let (vm, result) = run_in(recording_vm(), &code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(vm.globals.get("caught"), Some(Value::Boolean(true))));
+        assert_eq!(log_strings(&vm), vec!["enter", "KeyError: 'b' not found in dictionary"]);
+
+        // Uncaught errors still propagate with their original message
+        let code = format!("{}with new Res():\n    x = 1 / 0", RESOURCE_CLASS);
+        let (vm, result) = run_in(recording_vm(), &code);
+        assert_eq!(result, InterpretResult::RuntimeError("ValueError: Division by zero".to_string()));
+        assert_eq!(log_strings(&vm), vec!["enter", "ValueError: Division by zero"]);
+    }
+
+    #[test]
+    fn test_vm_with_exits_on_return() {
+        let code = format!("{}def f():\n    with new Res() as v:\n        return v + 1\n    return 0\nr = f()", RESOURCE_CLASS);
+        let (vm, result) = run_in(recording_vm(), &code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "r"), 43.0);
+        assert_eq!(log_strings(&vm), vec!["enter", "null"]);
+    }
+
+    #[test]
+    fn test_vm_with_native_resource_type() {
+        let mut vm = recording_vm();
+        vm.register_native("open_handle", 0, |_vm, _args| {
+            Ok(Value::native_object("Handle", HashMap::new()))
+        });
+        vm.register_resource_type("Handle", |vm, _handle| {
+            vm.globals.insert("closed".to_string(), Value::Boolean(true));
+            Ok(())
+        });
+        let (vm, result) = run_in(vm, "with open_handle() as h:\n    throw \"oops\"");
+        assert_eq!(result, InterpretResult::RuntimeError("Exception: oops".to_string()));
+        assert!(matches!(vm.globals.get("h"), Some(Value::Object { class_name, .. }) if class_name == "Handle"));
+        assert!(matches!(vm.globals.get("closed"), Some(Value::Boolean(true))));
+    }
+
+    #[test]
+    fn test_vm_with_rejects_non_managers() {
+        let (_, result) = run_vm("with 5 as x:\n    y = x");
+        assert_eq!(result, InterpretResult::RuntimeError("TypeError: 'with' expects a context manager, got 5".to_string()));
+    }
+
+    #[test]
+    fn test_vm_finally_runs_on_every_exit() {
+        let code = "def f(fail):\n    try:\n        if fail:\n            throw \"boom\"\n        return 1\n    catch e:\n        record(e)\n        return 2\n    finally:\n        record(\"finally\")\n    return 3\na = f(false)\nb = f(true)\ntry:\n    throw \"outer\"\nfinally:\n    record(\"last\")";
+        let (vm, result) = run_in(recording_vm(), code);
+        assert_eq!(result, InterpretResult::RuntimeError("Exception: outer".to_string()));
+        assert_eq!(global_number(&vm, "a"), 1.0);
+        assert_eq!(global_number(&vm, "b"), 2.0);
+        assert_eq!(log_strings(&vm), vec!["finally", "boom", "finally", "last"]);
+    }
+
+    #[test]
+    fn test_vm_break_and_continue() {
+        // Top-level loops keep their temporaries on the stack; in functions
+        // they are locals, and leaving the body also unwinds try/with/finally
+        let code = "for i in [1, 2, 3, 4]:\n    if i == 2:\n        continue\n    if i == 4:\n        break\n    for j in [1, 2]:\n        try:\n            if j == 2:\n                break\n        catch e:\n            record(e)\n        record(i * 10 + j)\n\
+            def f():\n    n = 0\n    while true:\n        n = n + 1\n        step = n\n        def seen():\n            return step\n        try:\n            with open_handle() as h:\n                if n < 3:\n                    continue\n                break\n        finally:\n            record(seen())\n    return n\n\
+            result = f()";
+        let mut vm = recording_vm();
+        vm.register_native("open_handle", 0, |_vm, _args| {
+            Ok(Value::native_object("Handle", HashMap::new()))
+        });
+        vm.register_resource_type("Handle", |vm, _handle| {
+            let closed = match vm.globals.get("closed") {
+                Some(Value::Number(closed)) => closed + 1.0,
+                _ => 1.0,
+            };
+            vm.globals.insert("closed".to_string(), Value::Number(closed));
+            Ok(())
+        });
+        let (vm, result) = run_in(vm, code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(log_strings(&vm), vec!["11", "31", "1", "2", "3"]);
+        assert_eq!(global_number(&vm, "result"), 3.0);
+        assert_eq!(global_number(&vm, "closed"), 3.0);
+    }
+
+    #[test]
+    fn test_vm_slices_and_negative_indexes() {
+        let code = "a = [1, 2, 3, 4, 5]\ns = \"héllo\"\n\
+            record(a[-1])\nrecord(s[1])\nrecord(s[-1])\nrecord(b\"ab\"[-2])\n\
+            record(a[1:3])\nrecord(a[:2])\nrecord(a[3:])\nrecord(a[:])\nrecord(a[-2:])\nrecord(a[:-3])\n\
+            record(a[4:1])\nrecord(a[-10:10])\nrecord(s[1:4])\nrecord((1, 2, 3)[1:])\nrecord(b\"abc\"[1:])\n\
+            n = 2\nrecord(a[n - 1:n + 1])";
+        let (vm, result) = run_in(recording_vm(), code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(log_strings(&vm), vec![
+            "5", "é", "o", "97", "[2, 3]", "[1, 2]", "[4, 5]", "[1, 2, 3, 4, 5]", "[4, 5]", "[1, 2]",
+            "[]", "[1, 2, 3, 4, 5]", "éll", "(2, 3)", "b\"bc\"", "[2, 3]",
+        ]);
+
+        let error = |code: &str| match run_in(VM::new(), code).1 {
+            InterpretResult::RuntimeError(e) => e,
+            other => panic!("Expected a runtime error, got {:?}", other),
+        };
+        assert_eq!(error("x = [1, 2][-3]"), "IndexError: Index -3 out of bounds for array of length 2");
+        assert_eq!(error("x = \"ab\"[2]"), "IndexError: Index 2 out of bounds for string of length 2");
+        assert_eq!(error("x = [1, 2][\"a\":]"), "TypeError: Slice bounds must be numbers or null");
+        assert_eq!(error("x = 5[1:]"), "TypeError: Slice operation requires an array, tuple, string or bytes");
+    }
+
+    #[test]
+    fn test_vm_unwinding_keeps_captured_variables() {
+        // The throw discards leak's frame; the closure it throws keeps the
+        // variable it captured from that frame
+        let code = "def leak():\n    count = 10\n    def inc():\n        count = count + 1\n        return count\n    inc()\n    throw inc\ntry:\n    leak()\ncatch f:\n    a = f()\n    b = f()";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "a"), 12.0);
+        assert_eq!(global_number(&vm, "b"), 13.0);
+    }
+
+    #[test]
+    fn test_vm_catch_clauses_filter_by_class() {
+        let code = "class Base:\n    def describe(self):\n        return \"base\"\nclass Derived(Base):\n    def extra(self):\n        return 1\nclass Other:\n    def describe(self):\n        return \"other\"\ndef classify(value):\n    try:\n        throw value\n    catch Other as e:\n        return \"other\"\n    catch (Derived, Base) as e:\n        return \"base\"\n    catch e:\n        return e\n    return \"none\"\na = classify(new Derived())\nb = classify(new Other())\nc = classify(\"text\")";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(vm.globals.get("a"), Some(Value::String(s)) if s == "base"));
+        assert!(matches!(vm.globals.get("b"), Some(Value::String(s)) if s == "other"));
+        assert!(matches!(vm.globals.get("c"), Some(Value::String(s)) if s == "text"));
+    }
+
+    #[test]
+    fn test_vm_unmatched_catch_propagates() {
+        let code = "class Special:\n    def f(self):\n        return 1\ntry:\n    x = 1 / 0\ncatch Special as e:\n    handled = true";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::RuntimeError("ValueError: Division by zero".to_string()));
+        assert!(!vm.globals.contains_key("handled"));
+    }
+
+    #[test]
+    fn test_vm_runtime_errors_are_exception_objects() {
+        let code = "try:\n    x = [1, 2][5]\ncatch IndexError as e:\n    kind = \"index\"\n    message = e.message\ntry:\n    y = {}[\"k\"]\ncatch Error as e:\n    base = e.message";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(vm.globals.get("kind"), Some(Value::String(s)) if s == "index"));
+        assert!(matches!(vm.globals.get("message"), Some(Value::String(s)) if s == "Index 5 out of bounds for array of length 2"));
+        assert!(matches!(vm.globals.get("base"), Some(Value::String(s)) if s == "'k' not found in dictionary"));
+    }
+
+    #[test]
+    fn test_vm_user_exception_subclasses() {
+        let code = "class AppError(Error):\n    \"Base class for application errors\"\nclass ConfigError(AppError):\n    \"Raised for invalid configuration\"\ntry:\n    throw new ConfigError(\"missing key\")\ncatch ValueError as e:\n    wrong = true\ncatch AppError as e:\n    message = e.message\nthrow new ConfigError(\"fatal\")";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::RuntimeError("ConfigError: fatal".to_string()));
+        assert!(!vm.globals.contains_key("wrong"));
+        assert!(matches!(vm.globals.get("message"), Some(Value::String(s)) if s == "missing key"));
+    }
+
+    #[test]
+    fn test_vm_method_call_returns_to_caller() {
+        let code = "class Counter:\n    def step(self, n):\n        return n + 1\n    def twice(self, n):\n        return n * 2\nc = new Counter()\ndef run():\n    return c.step(c.twice(20))\nresult = run()\nc.label = \"counter\"";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "result"), 41.0);
+        assert!(matches!(vm.globals.get("c"), Some(Value::Object { fields, .. }) if fields.lock().unwrap().contains_key("label")));
+    }
+
+    #[test]
+    fn test_vm_self_property_writes_reach_caller() {
+        let code = "class Counter:\n    def bump(self, by):\n        self.count = self.count + by\n        return self.count\n    def bump_twice(self):\n        self.bump(1)\n        return self.bump(1)\nc = new Counter()\nc.count = 0\nfirst = c.bump(5)\nsecond = c.bump_twice()\ndef local_counter():\n    d = new Counter()\n    d.count = 10\n    d.bump(1)\n    return d.count\nthird = local_counter()\nreturned = c.bump(0) + 1";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "first"), 5.0);
+        assert_eq!(global_number(&vm, "second"), 7.0);
+        assert_eq!(global_number(&vm, "third"), 11.0);
+        assert_eq!(global_number(&vm, "returned"), 8.0);
+        assert!(matches!(vm.globals.get("c"), Some(Value::Object { fields, .. }) if matches!(fields.lock().unwrap().get("count"), Some(Value::Number(n)) if *n == 7.0)));
+    }
+
+    #[test]
+    fn test_vm_method_arity_counts_only_explicit_arguments() {
+        let (_, result) = run_vm("class C:\n    def m(self, x):\n        return x\nc = new C()\nc.m()");
+        assert_eq!(result, InterpretResult::RuntimeError("TypeError: Method 'm' expects 1 arguments, got 0".to_string()));
+    }
+
+    #[test]
+    fn test_vm_methods_inherited_through_superclass_chain() {
+        let code = "class Animal:\n    def sound(self):\n        return \"...\"\n    def describe(self):\n        return \"animal\"\nclass Dog(Animal):\n    def sound(self):\n        return \"woof\"\nclass Puppy(Dog):\n    \"A young dog\"\np = new Puppy()\na = p.sound()\nb = p.describe()\nclass Dog(Animal):\n    \"Redefined without sound\"\nc = p.sound()";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert!(matches!(vm.globals.get("a"), Some(Value::String(s)) if s == "woof"));
+        assert!(matches!(vm.globals.get("b"), Some(Value::String(s)) if s == "animal"));
+        // Redefining a class invalidates previously resolved methods
+        assert!(matches!(vm.globals.get("c"), Some(Value::String(s)) if s == "..."));
+    }
+
+    #[test]
+    fn test_vm_methods_of_class_defined_in_function() {
+        let code = "def make():\n    class Inner:\n        def value(self):\n            return 42\n    return new Inner()\ndef call(obj):\n    return obj.value()\nobj = make()\ndirect = obj.value()\nnested = call(obj)";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "direct"), 42.0);
+        assert_eq!(global_number(&vm, "nested"), 42.0);
+    }
+
+    #[test]
+    fn test_vm_multiple_return_values() {
+        let code = "def minmax(arr):\n    low = arr[0]\n    high = arr[0]\n    for x in arr:\n        if x < low:\n            low = x\n        if x > high:\n            high = x\n    return low, high\nx, y = minmax([3, 9, 1, 4])\npair = minmax([2])\nx, y = y, x";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "x"), 9.0);
+        assert_eq!(global_number(&vm, "y"), 1.0);
+        assert!(matches!(vm.globals.get("pair"), Some(Value::Tuple(items)) if items.len() == 2));
+    }
+
+    #[test]
+    fn test_vm_closures_capture_enclosing_variables() {
+        let code = "def make_counter():\n    count = 0\n    def increment():\n        count = count + 1\n        return count\n    return increment\ncounter = make_counter()\ncounter()\ncounter()\nother = make_counter()\na = counter()\nb = other()\ndef factorial(n):\n    def fact(k):\n        if k < 2:\n            return 1\n        return k * fact(k - 1)\n    return fact(n)\nf = factorial(5)";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "a"), 3.0);
+        assert_eq!(global_number(&vm, "b"), 1.0);
+        // A nested function that calls itself captures its own variable
+        assert_eq!(global_number(&vm, "f"), 120.0);
+    }
+
+    #[test]
+    fn test_vm_closures_share_variables_with_their_scope() {
+        // Through two levels of nesting, a closure sees later changes to
+        // the variable and its own changes are seen by the enclosing function
+        let code = "def outer(x):\n    def middle():\n        def inner():\n            return x * 2\n        return inner\n    get = middle()\n    x = 10\n    return get()\ndef tally(items):\n    total = 0\n    def add(n):\n        total = total + n\n    for item in items:\n        add(item)\n    return total\ny = outer(1)\nt = tally([1, 2, 3])";
+        let (vm, result) = run_vm(code);
+        assert_eq!(result, InterpretResult::Ok);
+        assert_eq!(global_number(&vm, "y"), 20.0);
+        assert_eq!(global_number(&vm, "t"), 6.0);
+    }
+
+    #[test]
+    fn test_vm_limits_stop_runaway_scripts() {
+        let mut vm = VM::new();
+        vm.limits.max_instructions = Some(1000);
+        let (vm, result) = run_in(vm, "while true:\n    x = 1");
+        assert_eq!(result, InterpretResult::RuntimeError("Instruction limit of 1000 exceeded".to_string()));
+
+        // The budget is per run, and catching the error doesn't buy more
+        let (vm, result) = run_in(vm, "x = 1 + 2");
+        assert_eq!(result, InterpretResult::Ok);
+        let (_, result) = run_in(vm, "try:\n    while true:\n        x = 1\ncatch Error as e:\n    while true:\n        x = 2");
+        assert!(matches!(result, InterpretResult::RuntimeError(e) if e.starts_with("Instruction limit")));
+
+        let mut vm = VM::new();
+        vm.limits.max_call_depth = Some(50);
+        let (_, result) = run_in(vm, "def down(n):\n    return down(n + 1)\ndown(0)");
+        assert_eq!(result, InterpretResult::RuntimeError("Maximum call depth of 50 exceeded".to_string()));
+    }
+
+    #[test]
+    fn test_vm_rejects_invalid_bytecode() {
+        let valid = Compiler::new().compile(&Parser::new(Lexer::new("x = 1 + 2".to_string()).tokenize().unwrap()).parse().unwrap()).unwrap().clone();
+        let mut truncated = valid.clone();
+        truncated.code.truncate(1);
+        let mut out_of_range = valid;
+        out_of_range.constants.clear();
+
+        for chunk in [truncated, out_of_range] {
+            let result = VM::new().interpret(chunk);
+            assert!(matches!(result, InterpretResult::RuntimeError(e) if e.starts_with("Invalid bytecode")));
+        }
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2025 Nicholas Girga <nickgirga@gmail.com>
+// SPDX-License-Identifier: Apache-2.0
+
+//! `grease transpile` output, compiled: `transpiled/sample.rs` is what the
+//! transpiler makes of `transpiled/sample.grease`, and it is built and run
+//! as the `transpiled_sample` test, so Rust the transpiler writes that
+//! doesn't compile against the crate fails the build.
+//!
+//! After an intentional change to the generated code, regenerate the file
+//! with `GREASE_BLESS=1 cargo test --test transpile` and review the diff.
+
+use grease::lexer::Lexer;
+use grease::parser::Parser;
+use std::fs;
+
+#[test]
+fn transpiled_sample_is_current() {
+    let source = fs::read_to_string("tests/transpiled/sample.grease").unwrap();
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+    let rust = grease::transpile::transpile(&program, "sample.grease").unwrap();
+
+    if std::env::var_os("GREASE_BLESS").is_some() {
+        fs::write("tests/transpiled/sample.rs", &rust).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string("tests/transpiled/sample.rs").unwrap();
+    assert!(
+        rust == expected,
+        "tests/transpiled/sample.rs is stale (run with GREASE_BLESS=1 to regenerate it)\n--- expected\n{}--- actual\n{}",
+        expected,
+        rust
+    );
+}
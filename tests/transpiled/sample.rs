@@ -0,0 +1,41 @@
+// Transpiled from sample.grease by `grease transpile`.
+#![allow(unused_mut, unused_assignments, unreachable_code, clippy::all)]
+
+use grease::transpile::runtime as rt;
+use grease::transpile::runtime::Value;
+
+fn fib(mut n: Value) -> Value {
+    if rt::truthy(&rt::lt(&n, &Value::Number(2.0))) {
+        return n.clone();
+    }
+    return rt::add(&fib(rt::sub(&n, &Value::Number(1.0))), &fib(rt::sub(&n, &Value::Number(2.0))));
+}
+
+fn first_even(mut items: Value) -> Value {
+    let mut item = Value::Null;
+    for __item in rt::iter(&items) {
+        item = __item;
+        if rt::truthy(&rt::eq(&rt::rem(&item, &Value::Number(2.0)), &Value::Number(0.0))) {
+            return item.clone();
+        }
+    }
+    return Value::Null;
+}
+
+fn main() {
+    let mut total = Value::Null;
+    let mut i = Value::Null;
+    let mut numbers = Value::Null;
+    total = Value::Number(0.0);
+    i = Value::Number(0.0);
+    while rt::truthy(&Value::Boolean(true)) {
+        i = rt::add(&i, &Value::Number(1.0));
+        if rt::truthy(&rt::gt(&i, &Value::Number(5.0))) {
+            break;
+        }
+        total = rt::add(&total, &rt::mul(&i, &i));
+    }
+    numbers = Value::array(vec![Value::Number(3.0), Value::Number(8.0), Value::Number(5.0), Value::Number(13.0)]);
+    rt::print(&[rt::string("fib"), fib(Value::Number(10.0)), total.clone(), rt::slice(&numbers, &Value::Number(1.0), &Value::Number(3.0)), rt::index(&numbers, &rt::neg(&Value::Number(1.0)))]);
+    rt::print(&[rt::string("first even"), first_even(numbers.clone()), rt::not(&first_even(Value::array(vec![Value::Number(1.0)])))]);
+}